@@ -0,0 +1,65 @@
+import java.io.BufferedReader;
+import java.io.File;
+import java.io.FileReader;
+import java.util.HashSet;
+import java.util.Set;
+
+import org.apache.iotdb.tsfile.file.metadata.enums.CompressionType;
+import org.apache.iotdb.tsfile.file.metadata.enums.TSDataType;
+import org.apache.iotdb.tsfile.file.metadata.enums.TSEncoding;
+import org.apache.iotdb.tsfile.read.common.Path;
+import org.apache.iotdb.tsfile.write.TsFileWriter;
+import org.apache.iotdb.tsfile.write.record.TSRecord;
+import org.apache.iotdb.tsfile.write.record.datapoint.FloatDataPoint;
+import org.apache.iotdb.tsfile.write.record.datapoint.LongDataPoint;
+import org.apache.iotdb.tsfile.write.schema.MeasurementSchema;
+
+/**
+ * Java counterpart of the `compare` benchmark binary.
+ *
+ * <p>Reads the dataset generated by the harness and writes it with the Java TsFile writer
+ * (Apache IoTDB 0.13). Only the write part is timed, the result is printed as
+ * `elapsed_ms=<n>`.
+ *
+ * <p>Usage: java -cp tsfile-0.13.0.jar:. TsFileBenchmark dataset.csv output.tsfile
+ */
+public class TsFileBenchmark {
+
+  public static void main(String[] args) throws Exception {
+    String dataset = args[0];
+    File output = new File(args[1]);
+
+    java.util.List<String[]> rows = new java.util.ArrayList<>();
+    try (BufferedReader reader = new BufferedReader(new FileReader(dataset))) {
+      // Skip header
+      String line = reader.readLine();
+      while ((line = reader.readLine()) != null) {
+        rows.add(line.split(","));
+      }
+    }
+
+    long start = System.nanoTime();
+    try (TsFileWriter writer = new TsFileWriter(output)) {
+      Set<String> devices = new HashSet<>();
+      for (String[] row : rows) {
+        if (devices.add(row[0])) {
+          writer.registerTimeseries(
+              new Path(row[0]),
+              new MeasurementSchema(
+                  "s1", TSDataType.INT64, TSEncoding.PLAIN, CompressionType.UNCOMPRESSED));
+          writer.registerTimeseries(
+              new Path(row[0]),
+              new MeasurementSchema(
+                  "s2", TSDataType.FLOAT, TSEncoding.PLAIN, CompressionType.UNCOMPRESSED));
+        }
+        TSRecord record = new TSRecord(Long.parseLong(row[1]), row[0]);
+        record.addTuple(new LongDataPoint("s1", Long.parseLong(row[2])));
+        record.addTuple(new FloatDataPoint("s2", Float.parseFloat(row[3])));
+        writer.write(record);
+      }
+    }
+    long elapsed = (System.nanoTime() - start) / 1_000_000;
+
+    System.out.println("elapsed_ms=" + elapsed);
+  }
+}
@@ -83,7 +83,7 @@ fn main() {
                 )
                 .expect("");
         }
-        writer.close();
+        writer.close().expect("Close failed");
 
         let end = SystemTime::now();
 
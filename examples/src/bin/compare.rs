@@ -0,0 +1,300 @@
+//! Reproducible benchmark comparing this crate with the Java TsFile writer.
+//!
+//! The harness
+//!
+//! 1. generates a deterministic dataset (`target/compare/dataset.csv`),
+//! 2. writes it with the Rust writer several times and
+//! 3. optionally runs an external command (e.g. the Java writer from `examples/java`) on the
+//!    same dataset.
+//!
+//! Afterwards a markdown report is printed and stored in `target/compare/report.md`, and one
+//! line per implementation is appended to `target/compare/history.csv` so results can be
+//! tracked release over release.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --release --bin compare -- [--devices N] [--points N] [--runs N] [--label L] [--java "<cmd>"]
+//! ```
+//!
+//! The external command is called as `<cmd> <dataset.csv> <output.tsfile>`.
+//! If it prints a line `elapsed_ms=<n>` this value is used as write duration, otherwise the
+//! wall time of the process is taken.
+use simplelog::{LevelFilter, SimpleLogger};
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::Command;
+use std::time::Instant;
+use tsfile_writer::writer::compression::CompressionType;
+use tsfile_writer::writer::encoding::TSEncoding;
+use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+use tsfile_writer::writer::tsfile_writer::{DataPoint, TsFileWriter};
+use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+const OUT_DIR: &str = "target/compare";
+const SEED: u64 = 0x5DEECE66D;
+
+struct Options {
+    devices: usize,
+    points: i64,
+    runs: usize,
+    label: String,
+    java: Option<String>,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut options = Options {
+            devices: 4,
+            points: 1_000_000,
+            runs: 5,
+            label: "dev".to_owned(),
+            java: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args.next().expect("Missing value for argument");
+            match arg.as_str() {
+                "--devices" => options.devices = value.parse().expect("Invalid device count"),
+                "--points" => options.points = value.parse().expect("Invalid point count"),
+                "--runs" => options.runs = value.parse().expect("Invalid run count"),
+                "--label" => options.label = value,
+                "--java" => options.java = Some(value),
+                _ => panic!("Unknown argument {}", arg),
+            }
+        }
+        options
+    }
+}
+
+struct Row {
+    device: String,
+    timestamp: i64,
+    counter: i64,
+    signal: f32,
+}
+
+struct Measurement {
+    implementation: String,
+    durations: Vec<f64>,
+    file_size: u64,
+}
+
+impl Measurement {
+    fn mean(&self) -> f64 {
+        self.durations.iter().sum::<f64>() / self.durations.len() as f64
+    }
+}
+
+/// Simple xorshift generator so that the dataset is identical on every machine
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn generate_dataset(options: &Options, path: &str) -> Vec<Row> {
+    let mut random = XorShift(SEED);
+    let mut rows = Vec::with_capacity(options.devices * options.points as usize);
+    for d in 0..options.devices {
+        let device = format!("root.sg.d{}", d);
+        let mut counter = 0_i64;
+        for timestamp in 0..options.points {
+            counter += (random.next() % 10) as i64;
+            let noise = (random.next() % 1000) as f32 / 1000.0;
+            rows.push(Row {
+                device: device.clone(),
+                timestamp,
+                counter,
+                signal: (timestamp as f32 / 100.0).sin() + noise,
+            });
+        }
+    }
+
+    let mut out = BufWriter::new(File::create(path).expect("Unable to create dataset"));
+    writeln!(out, "device,time,s1,s2").unwrap();
+    for row in rows.iter() {
+        writeln!(
+            out,
+            "{},{},{},{}",
+            row.device, row.timestamp, row.counter, row.signal
+        )
+        .unwrap();
+    }
+
+    rows
+}
+
+fn run_rust(options: &Options, rows: &[Row]) -> Measurement {
+    let devices: Vec<String> = (0..options.devices)
+        .map(|d| format!("root.sg.d{}", d))
+        .collect();
+    let mut builder = TsFileSchemaBuilder::new();
+    for device in devices.iter() {
+        builder.add(
+            device,
+            DeviceBuilder::new()
+                .add(
+                    "s1",
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "s2",
+                    TSDataType::FLOAT,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build(),
+        );
+    }
+    let schema = builder.build();
+
+    let filename = format!("{}/rust.tsfile", OUT_DIR);
+    let mut durations = vec![];
+    for _ in 0..options.runs {
+        let start = Instant::now();
+        let mut writer =
+            TsFileWriter::new(filename.as_str(), schema.clone(), Default::default()).unwrap();
+        for row in rows.iter() {
+            writer
+                .write_many(
+                    row.device.as_str(),
+                    row.timestamp,
+                    [
+                        DataPoint::new("s1", IoTDBValue::LONG(row.counter)),
+                        DataPoint::new("s2", IoTDBValue::FLOAT(row.signal)),
+                    ],
+                )
+                .expect("Write failed");
+        }
+        writer.close().expect("Close failed");
+        durations.push(start.elapsed().as_secs_f64());
+    }
+
+    Measurement {
+        implementation: "rust".to_owned(),
+        durations,
+        file_size: std::fs::metadata(filename).unwrap().len(),
+    }
+}
+
+fn run_external(options: &Options, command: &str, dataset: &str) -> Measurement {
+    let filename = format!("{}/java.tsfile", OUT_DIR);
+    let mut parts = command.split_whitespace();
+    let program = parts.next().expect("Empty command given");
+    let args: Vec<&str> = parts.collect();
+
+    let mut durations = vec![];
+    for _ in 0..options.runs {
+        let _ = std::fs::remove_file(&filename);
+        let start = Instant::now();
+        let output = Command::new(program)
+            .args(&args)
+            .arg(dataset)
+            .arg(&filename)
+            .output()
+            .expect("Failed to execute external writer");
+        let wall_time = start.elapsed().as_secs_f64();
+        assert!(
+            output.status.success(),
+            "External writer failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let reported = BufReader::new(output.stdout.as_slice())
+            .lines()
+            .map_while(Result::ok)
+            .find_map(|line| {
+                line.strip_prefix("elapsed_ms=")
+                    .and_then(|ms| ms.trim().parse::<f64>().ok())
+            });
+        durations.push(reported.map(|ms| ms / 1000.0).unwrap_or(wall_time));
+    }
+
+    Measurement {
+        implementation: "java".to_owned(),
+        durations,
+        file_size: std::fs::metadata(filename).unwrap().len(),
+    }
+}
+
+fn report(options: &Options, measurements: &[Measurement]) -> String {
+    let points = options.devices as f64 * options.points as f64 * 2.0;
+    let mut report = String::new();
+    report.push_str(&format!(
+        "# TsFile write benchmark ({})\n\n{} devices, {} timestamps, {} points, {} runs\n\n",
+        options.label, options.devices, options.points, points, options.runs
+    ));
+    report.push_str("| Implementation | Mean [s] | Min [s] | Points/s | File size [bytes] |\n");
+    report.push_str("|---|---|---|---|---|\n");
+    for m in measurements {
+        let min = m.durations.iter().cloned().fold(f64::MAX, f64::min);
+        report.push_str(&format!(
+            "| {} | {:.3} | {:.3} | {:.0} | {} |\n",
+            m.implementation,
+            m.mean(),
+            min,
+            points / m.mean(),
+            m.file_size
+        ));
+    }
+    report
+}
+
+fn append_history(options: &Options, measurements: &[Measurement]) {
+    let path = format!("{}/history.csv", OUT_DIR);
+    let exists = std::path::Path::new(&path).exists();
+    let mut history = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    if !exists {
+        writeln!(
+            history,
+            "label,implementation,devices,points,runs,mean_s,file_size"
+        )
+        .unwrap();
+    }
+    for m in measurements {
+        writeln!(
+            history,
+            "{},{},{},{},{},{:.3},{}",
+            options.label,
+            m.implementation,
+            options.devices,
+            options.points,
+            options.runs,
+            m.mean(),
+            m.file_size
+        )
+        .unwrap();
+    }
+}
+
+fn main() {
+    let _ = SimpleLogger::init(LevelFilter::Warn, Default::default());
+
+    let options = Options::parse();
+    create_dir_all(OUT_DIR).unwrap();
+
+    let dataset = format!("{}/dataset.csv", OUT_DIR);
+    let rows = generate_dataset(&options, &dataset);
+
+    let mut measurements = vec![run_rust(&options, &rows)];
+    if let Some(command) = &options.java {
+        measurements.push(run_external(&options, command, &dataset));
+    }
+
+    let report = report(&options, &measurements);
+    std::fs::write(format!("{}/report.md", OUT_DIR), &report).unwrap();
+    append_history(&options, &measurements);
+
+    println!("{}", report);
+}
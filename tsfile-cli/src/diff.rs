@@ -0,0 +1,97 @@
+//! Prints the logical and structural differences of two TsFiles, see `TsFileDiff`.
+use crate::options::{usage, Options};
+use std::io::Write;
+use tsfile_writer::diff::{DifferenceKind, TsFileDiff};
+use tsfile_writer::writer::errors::TsFileError;
+
+pub(crate) fn diff(
+    left: &str,
+    right: &str,
+    args: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), TsFileError> {
+    let options = Options::parse(args, &["kind"])?;
+    let kind = match options.get("kind") {
+        None => None,
+        Some("logical") => Some(DifferenceKind::Logical),
+        Some("structural") => Some(DifferenceKind::Structural),
+        Some(kind) => return Err(usage(&format!("Unknown kind {}", kind))),
+    };
+    let differences: Vec<_> = TsFileDiff::new()
+        .diff_files(left, right)?
+        .into_iter()
+        .filter(|difference| kind.is_none() || kind == Some(difference.kind))
+        .collect();
+    for difference in &differences {
+        writeln!(out, "{}", difference)?;
+    }
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(TsFileError::Error {
+            source: Some(format!("Files differ in {} places", differences.len())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::diff;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    fn write(path: &str, encoding: TSEncoding, last: i64) {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        encoding,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new(path, schema, Default::default()).unwrap();
+        for i in 0..10 {
+            let value = if i == 9 { last } else { i };
+            writer
+                .write("d1", "s1", i, IoTDBValue::LONG(value))
+                .unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn diff_files() {
+        let (left, right) = ("../target/diff_left.tsfile", "../target/diff_right.tsfile");
+        write(left, TSEncoding::PLAIN, 9);
+        write(right, TSEncoding::TS2DIFF, 9);
+
+        diff(left, left, &[], &mut vec![]).unwrap();
+        // Only structural differences
+        diff(left, right, &["--kind", "logical"], &mut vec![]).unwrap();
+        let mut out = vec![];
+        let error = diff(left, right, &[], &mut out).unwrap_err();
+        assert!(error.to_string().contains("Files differ"));
+        assert!(diff(left, right, &["--kind", "all"], &mut vec![])
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown kind all"));
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("[structural] d1.s1: Chunk 0 PLAIN UNCOMPRESSED != TS2DIFF UNCOMPRESSED\n"));
+
+        write(right, TSEncoding::PLAIN, 10);
+        let mut out = vec![];
+        diff(left, right, &["--kind", "logical"], &mut out).unwrap_err();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("[logical] d1.s1: Point 9: (9, LONG(9)) != (9, LONG(10))\n"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Prints the layout of a TsFile: header, chunk groups with their chunks and pages,
+//! timeseries metadata, the metadata index tree, the bloom filter and user defined properties.
+use std::fs::File;
+use std::io::{Read, Write};
+use tsfile_writer::reader::chunk_reader::ChunkHeader;
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::statistics::Statistics;
+
+pub(crate) fn inspect(path: &str, out: &mut dyn Write) -> Result<(), TsFileError> {
+    let mut head = [0_u8; 7];
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    file.read_exact(&mut head)?;
+    writeln!(out, "File: {} ({} bytes)", path, file_size)?;
+    writeln!(
+        out,
+        "Magic: {}, version {}",
+        String::from_utf8_lossy(&head[0..6]),
+        head[6]
+    )?;
+
+    let mut reader = TsFileReader::open(path)?;
+    writeln!(out, "Metadata offset: {}", reader.metadata().meta_offset)?;
+
+    writeln!(out)?;
+    writeln!(out, "Chunk groups:")?;
+    for chunk_group in reader.chunk_groups()? {
+        writeln!(
+            out,
+            "  [{}] {}, {} chunks",
+            chunk_group.offset,
+            chunk_group.device,
+            chunk_group.chunks.len()
+        )?;
+        for chunk in chunk_group.chunks {
+            writeln!(
+                out,
+                "    [{}] {}, {} pages",
+                chunk.offset,
+                chunk_header(&chunk.header),
+                chunk.pages.len()
+            )?;
+            for (index, page) in chunk.pages.iter().enumerate() {
+                write!(
+                    out,
+                    "      page {}: {} bytes ({} uncompressed)",
+                    index, page.compressed_size, page.uncompressed_size
+                )?;
+                match &page.statistics {
+                    Some(statistics) => writeln!(out, ", {}", summary(statistics))?,
+                    None => writeln!(out)?,
+                }
+            }
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Timeseries metadata:")?;
+    let metadata_reader = reader.metadata_reader();
+    for device in metadata_reader.devices()? {
+        writeln!(out, "  {}", device)?;
+        for timeseries in metadata_reader.get_device_timeseries_metadata(&device)? {
+            let measurement = match timeseries.measurement_id.as_str() {
+                "" => "(time)",
+                measurement => measurement,
+            };
+            writeln!(
+                out,
+                "    {} {:?}, type {}, {}",
+                measurement,
+                timeseries.data_type,
+                timeseries.timeseries_metadata_type,
+                summary(&timeseries.statistics)
+            )?;
+            let offsets: Vec<String> = timeseries
+                .chunk_metadata_list
+                .iter()
+                .map(|chunk| chunk.offset_of_chunk_header.to_string())
+                .collect();
+            writeln!(out, "      chunks at {}", offsets.join(", "))?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Index tree:")?;
+    for node in metadata_reader.index_tree()? {
+        let indent = "  ".repeat(node.depth + 1);
+        writeln!(
+            out,
+            "{}[{}] {:?}, end {}",
+            indent, node.offset, node.node.node_type, node.node.end_offset
+        )?;
+        for child in &node.node.children {
+            writeln!(out, "{}  {} -> {}", indent, child.name, child.offset)?;
+        }
+    }
+
+    writeln!(out)?;
+    match metadata_reader.bloom_filter() {
+        Some(bloom_filter) => writeln!(
+            out,
+            "Bloom filter: {} bits, {} hash functions",
+            bloom_filter.size(),
+            bloom_filter.hash_function_size()
+        )?,
+        None => writeln!(out, "Bloom filter: none")?,
+    }
+    if !metadata_reader.properties().is_empty() {
+        writeln!(out, "Properties:")?;
+        for (key, value) in metadata_reader.properties() {
+            writeln!(out, "  {} = {}", key, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn chunk_header(header: &ChunkHeader) -> String {
+    let kind = if header.is_time_chunk() {
+        "time chunk"
+    } else if header.is_value_chunk() {
+        "value chunk"
+    } else {
+        "chunk"
+    };
+    format!(
+        "{} {} {:?} {:?} {:?}, {} bytes",
+        kind,
+        header.measurement_id,
+        header.data_type,
+        header.encoding,
+        header.compression,
+        header.data_size
+    )
+}
+
+fn summary(statistics: &Statistics) -> String {
+    let mut summary = format!(
+        "{} points [{}, {}]",
+        statistics.count(),
+        statistics.start_time(),
+        statistics.end_time()
+    );
+    if let (Some(min), Some(max)) = (statistics.min_value(), statistics.max_value()) {
+        summary.push_str(&format!(", min {:?}, max {:?}", min, max));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inspect::inspect;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+
+    #[test]
+    fn inspect_file() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let path = "../target/inspect.tsfile";
+        let mut writer = TsFileWriter::new(path, schema, Default::default()).unwrap();
+        writer.set_property("source", "edge-17");
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+        writer.write("d1", "s1", 2, IoTDBValue::LONG(14)).unwrap();
+        writer.close().unwrap();
+
+        let mut out = vec![];
+        inspect(path, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Magic: TsFile, version 3"));
+        assert!(out.contains("  [7] d1, 1 chunks"));
+        assert!(out.contains("chunk s1 INT64 PLAIN UNCOMPRESSED"));
+        assert!(out.contains("    s1 INT64, type 0, 2 points [1, 2], min LONG(13), max LONG(14)"));
+        assert!(out.contains("LeafDevice"));
+        assert!(out.contains("Bloom filter: "));
+        assert!(out.contains("Properties:\n  source = edge-17"));
+    }
+}
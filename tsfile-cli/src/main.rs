@@ -0,0 +1,59 @@
+//! Command line tool for TsFiles.
+//!
+//! Usage:
+//!
+//! ```text
+//! tsfile inspect <file>
+//! tsfile validate <file>
+//! tsfile schema <file>
+//! tsfile query <file> --path <device.measurement> [--from <time>] [--to <time>] [--format csv|json]
+//! tsfile rewrite <source> <target> [--encoding <encoding>] [--compression <compression>]
+//! tsfile split <source> <directory> --device-prefix <nodes> | --time-window <length>
+//! tsfile diff <left> <right> [--kind logical|structural]
+//! tsfile repair <file>
+//! ```
+use std::io::stdout;
+use std::process::exit;
+
+mod diff;
+mod inspect;
+mod options;
+mod query;
+mod repair;
+mod rewrite;
+mod schema;
+mod split;
+mod validate;
+
+const USAGE: &str = "Usage: tsfile <inspect|validate|schema> <file>
+       tsfile query <file> --path <device.measurement> [--from <time>] [--to <time>] [--format csv|json]
+       tsfile rewrite <source> <target> [--encoding <encoding>] [--compression <compression>]
+       tsfile split <source> <directory> --device-prefix <nodes> | --time-window <length>
+       tsfile diff <left> <right> [--kind logical|structural]
+       tsfile repair <file>";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>()[..] {
+        ["inspect", file] => inspect::inspect(file, &mut stdout()),
+        ["validate", file] => validate::validate(file, &mut stdout()),
+        ["schema", file] => schema::schema(file, &mut stdout()),
+        ["query", file, ref options @ ..] => query::query(file, options, &mut stdout()),
+        ["rewrite", source, target, ref options @ ..] => {
+            rewrite::rewrite(source, target, options, &mut stdout())
+        }
+        ["split", source, directory, ref options @ ..] => {
+            split::split(source, directory, options, &mut stdout())
+        }
+        ["diff", left, right, ref options @ ..] => diff::diff(left, right, options, &mut stdout()),
+        ["repair", file] => repair::repair(file, &mut stdout()),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+//! Options of the commands in the form `--name value`.
+use tsfile_writer::writer::errors::TsFileError;
+
+pub(crate) struct Options<'a> {
+    values: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Options<'a> {
+    /// Fails for unknown names and names without value
+    pub(crate) fn parse(args: &[&'a str], names: &[&str]) -> Result<Options<'a>, TsFileError> {
+        let mut values = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) if names.contains(&name) => name,
+                _ => return Err(usage(&format!("Unknown option {}", arg))),
+            };
+            match args.next() {
+                Some(value) => values.push((name, *value)),
+                None => return Err(usage(&format!("Missing value of --{}", name))),
+            }
+        }
+        Ok(Options { values })
+    }
+
+    /// The last value of the option
+    pub(crate) fn get(&self, name: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub(crate) fn required(&self, name: &str) -> Result<&'a str, TsFileError> {
+        self.get(name)
+            .ok_or_else(|| usage(&format!("Missing option --{}", name)))
+    }
+
+    /// Timestamp in milliseconds
+    pub(crate) fn timestamp(&self, name: &str) -> Result<Option<i64>, TsFileError> {
+        match self.get(name) {
+            Some(value) => match value.parse() {
+                Ok(timestamp) => Ok(Some(timestamp)),
+                Err(_) => Err(usage(&format!("Invalid timestamp {} of --{}", value, name))),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+pub(crate) fn usage(message: &str) -> TsFileError {
+    TsFileError::Error {
+        source: Some(message.to_owned()),
+    }
+}
@@ -0,0 +1,206 @@
+//! Prints the points of a timeseries as CSV or JSON, optionally restricted to a time range.
+use crate::options::{usage, Options};
+use std::io::Write;
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::IoTDBValue;
+
+pub(crate) fn query(path: &str, args: &[&str], out: &mut dyn Write) -> Result<(), TsFileError> {
+    let options = Options::parse(args, &["path", "from", "to", "format"])?;
+    let series = options.required("path")?;
+    let (device, measurement) = match series.rsplit_once('.') {
+        Some((device, measurement)) if !device.is_empty() && !measurement.is_empty() => {
+            (device, measurement)
+        }
+        _ => return Err(usage(&format!("Invalid path {}", series))),
+    };
+    let start = options.timestamp("from")?.unwrap_or(i64::MIN);
+    let end = options.timestamp("to")?.unwrap_or(i64::MAX);
+    let json = match options.get("format").unwrap_or("csv") {
+        "csv" => false,
+        "json" => true,
+        format => return Err(usage(&format!("Unknown format {}", format))),
+    };
+
+    let mut reader = TsFileReader::open(path)?;
+    if reader
+        .get_timeseries_metadata(device, measurement)?
+        .is_none()
+    {
+        return Err(usage(&format!("Timeseries {} does not exist", series)));
+    }
+    let points = reader
+        .query_builder(device, measurement)
+        .time_range(start, end)
+        .build()?;
+    if json {
+        writeln!(out, "[")?;
+        for (index, point) in points.enumerate() {
+            let (timestamp, value) = point?;
+            if index > 0 {
+                writeln!(out, ",")?;
+            }
+            write!(
+                out,
+                "  {{\"time\": {}, \"value\": {}}}",
+                timestamp,
+                json_value(&value)
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "]")?;
+    } else {
+        writeln!(out, "Time,{}", csv_field(series))?;
+        for point in points {
+            let (timestamp, value) = point?;
+            writeln!(out, "{},{}", timestamp, csv_value(&value))?;
+        }
+    }
+    Ok(())
+}
+
+fn csv_value(value: &IoTDBValue) -> String {
+    match value {
+        IoTDBValue::DOUBLE(v) => v.to_string(),
+        IoTDBValue::FLOAT(v) => v.to_string(),
+        IoTDBValue::INT(v) => v.to_string(),
+        IoTDBValue::LONG(v) => v.to_string(),
+        IoTDBValue::TEXT(v) => csv_field(v),
+    }
+}
+
+/// Quotes fields with separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// NaN and infinity have no JSON representation and are written as `null`
+fn json_value(value: &IoTDBValue) -> String {
+    match value {
+        IoTDBValue::DOUBLE(v) if !v.is_finite() => "null".to_owned(),
+        IoTDBValue::FLOAT(v) if !v.is_finite() => "null".to_owned(),
+        IoTDBValue::TEXT(v) => json_string(v),
+        value => csv_value(value),
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::query;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    fn write(path: &str) {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "root.sg.d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new(path, schema, Default::default()).unwrap();
+        for i in 0..5 {
+            writer
+                .write("root.sg.d1", "s1", i, IoTDBValue::DOUBLE(i as f64 + 0.5))
+                .unwrap();
+        }
+        writer
+            .write(
+                "root.sg.d1",
+                "s2",
+                1,
+                IoTDBValue::TEXT("a,\"b\"".to_owned()),
+            )
+            .unwrap();
+        writer.close().unwrap();
+    }
+
+    fn run(path: &str, args: &[&str]) -> String {
+        let mut out = vec![];
+        query(path, args, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        let path = "../target/query_csv.tsfile";
+        write(path);
+        assert_eq!(
+            run(
+                path,
+                &["--path", "root.sg.d1.s1", "--from", "1", "--to", "3"]
+            ),
+            "Time,root.sg.d1.s1\n1,1.5\n2,2.5\n3,3.5\n"
+        );
+        assert_eq!(
+            run(path, &["--path", "root.sg.d1.s2"]),
+            "Time,root.sg.d1.s2\n1,\"a,\"\"b\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let path = "../target/query_json.tsfile";
+        write(path);
+        assert_eq!(
+            run(
+                path,
+                &["--path", "root.sg.d1.s1", "--from", "3", "--format", "json"]
+            ),
+            "[\n  {\"time\": 3, \"value\": 3.5},\n  {\"time\": 4, \"value\": 4.5}\n]\n"
+        );
+        assert_eq!(
+            run(path, &["--path", "root.sg.d1.s2", "--format", "json"]),
+            "[\n  {\"time\": 1, \"value\": \"a,\\\"b\\\"\"}\n]\n"
+        );
+    }
+
+    #[test]
+    fn invalid_options() {
+        let path = "../target/query_invalid.tsfile";
+        write(path);
+        let error = |args: &[&str]| query(path, args, &mut vec![]).unwrap_err().to_string();
+        assert!(error(&[]).contains("Missing option --path"));
+        assert!(error(&["--path", "root.sg.d1.s3"]).contains("does not exist"));
+        assert!(error(&["--path", "root.sg.d1.s1", "--to", "x"]).contains("Invalid timestamp"));
+        assert!(error(&["--path", "root.sg.d1.s1", "--format", "xml"]).contains("Unknown format"));
+        assert!(error(&["--limit", "1"]).contains("Unknown option --limit"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Repairs a truncated or damaged TsFile in place, see `repair_file`.
+use std::io::Write;
+use tsfile_writer::reader::self_check::FileStatus;
+use tsfile_writer::repair::repair_file;
+use tsfile_writer::writer::errors::TsFileError;
+
+pub(crate) fn repair(file: &str, out: &mut dyn Write) -> Result<(), TsFileError> {
+    let repair = repair_file(file, Default::default())?;
+    if repair.status == FileStatus::Complete {
+        writeln!(out, "{} is complete, nothing to repair", file)?;
+        return Ok(());
+    }
+    if let Some(reason) = &repair.reason {
+        writeln!(out, "{:?}: {}", repair.status, reason)?;
+    }
+    writeln!(
+        out,
+        "Kept {} chunk groups up to offset {}, truncated {} bytes",
+        repair.chunk_groups,
+        repair.truncation_point,
+        repair.original_size - repair.truncation_point
+    )?;
+    writeln!(out, "Repaired {}: {} bytes", file, repair.file_size)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repair::repair;
+    use tsfile_writer::reader::tsfile_reader::TsFileReader;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+
+    #[test]
+    fn repair_file() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let path = "../target/repair_cli.tsfile";
+        let mut writer = TsFileWriter::new(path, schema, Default::default()).unwrap();
+        for i in 0..10 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+        }
+        writer.close().unwrap();
+        let meta_offset = TsFileReader::open(path).unwrap().metadata().meta_offset as usize;
+
+        // Without metadata section
+        let buffer = std::fs::read(path).unwrap();
+        std::fs::write(path, &buffer[..meta_offset]).unwrap();
+        let mut out = vec![];
+        repair(path, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Incomplete: File has no metadata section\n"));
+        assert!(out.contains("Kept 1 chunk groups"));
+        assert_eq!(
+            TsFileReader::open(path)
+                .unwrap()
+                .query("d1", "s1")
+                .unwrap()
+                .count(),
+            10
+        );
+
+        let mut out = vec![];
+        repair(path, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "../target/repair_cli.tsfile is complete, nothing to repair\n"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Writes all points of a TsFile into a new file with other encodings and / or compression,
+//! e.g. to shrink files that were written with `PLAIN` and without compression.
+//!
+//! The encoding is only changed for measurements whose data type supports it. The points of a
+//! device are read into memory before they are written, one device at a time.
+use crate::options::{usage, Options};
+use std::collections::BTreeMap;
+use std::io::Write;
+use tsfile_writer::reader::schema::FileSchema;
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::writer::compression::CompressionType;
+use tsfile_writer::writer::encoding::TSEncoding;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::tsfile_writer::{DataPoint, TsFileWriter};
+use tsfile_writer::writer::IoTDBValue;
+
+pub(crate) fn rewrite(
+    source: &str,
+    target: &str,
+    args: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), TsFileError> {
+    let options = Options::parse(args, &["encoding", "compression"])?;
+    let encoding = options.get("encoding").map(encoding).transpose()?;
+    let compression = options.get("compression").map(compression).transpose()?;
+
+    let source_size = std::fs::metadata(source)?.len();
+    let mut reader = TsFileReader::open(source)?;
+    let mut file_schema = FileSchema::new();
+    file_schema.add_file(&mut reader)?;
+    for device_schema in file_schema.devices.values_mut() {
+        for series in device_schema.measurements.values_mut() {
+            match encoding {
+                Some(encoding) if encoding.supports(series.data_type) => series.encoding = encoding,
+                _ => {}
+            }
+            if let Some(compression) = compression {
+                series.compression = compression;
+            }
+        }
+    }
+
+    let mut writer = TsFileWriter::new(target, file_schema.schema()?, Default::default())?;
+    for (key, value) in reader.metadata_reader().properties() {
+        writer.set_property(key, value);
+    }
+    for (device, device_schema) in &file_schema.devices {
+        let mut rows: BTreeMap<i64, Vec<(&str, IoTDBValue)>> = BTreeMap::new();
+        for measurement in device_schema.measurements.keys() {
+            for point in reader.query(device, measurement)? {
+                let (timestamp, value) = point?;
+                rows.entry(timestamp)
+                    .or_default()
+                    .push((measurement, value));
+            }
+        }
+        for (timestamp, row) in rows {
+            writer.write_many(
+                device,
+                timestamp,
+                row.into_iter()
+                    .map(|(measurement, value)| DataPoint::new(measurement, value)),
+            )?;
+        }
+        // Only one device is kept in memory
+        writer.flush()?;
+    }
+    let summary = writer.close()?;
+
+    let series = summary
+        .devices
+        .iter()
+        .flat_map(|device| device.timeseries.iter())
+        // The time column of aligned devices has no measurement id
+        .filter(|timeseries| !timeseries.measurement_id.is_empty())
+        .count();
+    writeln!(
+        out,
+        "Rewrote {} devices with {} timeseries: {} bytes -> {} bytes",
+        summary.devices.len(),
+        series,
+        source_size,
+        summary.file_size
+    )?;
+    Ok(())
+}
+
+fn encoding(name: &str) -> Result<TSEncoding, TsFileError> {
+    match name.to_uppercase().as_str() {
+        "PLAIN" => Ok(TSEncoding::PLAIN),
+        "TS_2DIFF" | "TS2DIFF" => Ok(TSEncoding::TS2DIFF),
+        "GORILLA" => Ok(TSEncoding::GORILLA),
+        "ZIGZAG" => Ok(TSEncoding::ZIGZAG),
+        _ => Err(usage(&format!("Unknown encoding {}", name))),
+    }
+}
+
+fn compression(name: &str) -> Result<CompressionType, TsFileError> {
+    match name.to_uppercase().as_str() {
+        "UNCOMPRESSED" => Ok(CompressionType::UNCOMPRESSED),
+        "SNAPPY" => Ok(CompressionType::SNAPPY),
+        "GZIP" => Ok(CompressionType::GZIP),
+        "LZ4" => Ok(CompressionType::LZ4),
+        _ => Err(usage(&format!("Unknown compression {}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rewrite::rewrite;
+    use tsfile_writer::reader::schema::FileSchema;
+    use tsfile_writer::reader::tsfile_reader::TsFileReader;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn rewrite_file() {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let source = "../target/rewrite_source.tsfile";
+        let target = "../target/rewrite_target.tsfile";
+        let mut writer = TsFileWriter::new(source, schema, Default::default()).unwrap();
+        writer.set_property("source", "edge-17");
+        for i in 0..1_000 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::INT(i as i32 % 10))
+                .unwrap();
+        }
+        writer
+            .write("d1", "s2", 5, IoTDBValue::TEXT("a".to_owned()))
+            .unwrap();
+        writer.close().unwrap();
+
+        let mut out = vec![];
+        rewrite(
+            source,
+            target,
+            &["--encoding", "TS_2DIFF", "--compression", "SNAPPY"],
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Rewrote 2 devices with 3 timeseries"));
+        assert!(
+            std::fs::metadata(target).unwrap().len() < std::fs::metadata(source).unwrap().len()
+        );
+
+        let file_schema = FileSchema::open(target).unwrap();
+        let d1 = &file_schema.devices["d1"].measurements;
+        assert_eq!(d1["s1"].encoding, TSEncoding::TS2DIFF);
+        assert_eq!(d1["s1"].compression, CompressionType::SNAPPY);
+        // TS_2DIFF does not support TEXT
+        assert_eq!(d1["s2"].encoding, TSEncoding::PLAIN);
+        assert_eq!(d1["s2"].compression, CompressionType::SNAPPY);
+        assert!(file_schema.devices["d2"].aligned);
+
+        let mut reader = TsFileReader::open(target).unwrap();
+        let points: Vec<_> = reader
+            .query("d2", "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(points.len(), 1_000);
+        assert_eq!(points[13], (13, IoTDBValue::INT(3)));
+        assert_eq!(reader.query("d1", "s2").unwrap().count(), 1);
+        assert_eq!(
+            reader.metadata_reader().properties()["source"],
+            "edge-17".to_owned()
+        );
+
+        let error = rewrite(source, target, &["--encoding", "RLE"], &mut vec![]).unwrap_err();
+        assert!(error.to_string().contains("Unknown encoding RLE"));
+    }
+}
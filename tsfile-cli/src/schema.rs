@@ -0,0 +1,115 @@
+//! Lists the devices and measurements of a TsFile with data type, encoding, compression and
+//! the number of points. Only the metadata and the chunk headers are read, no pages.
+use std::io::Write;
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::VALUE_SERIES_MASK;
+
+pub(crate) fn schema(path: &str, out: &mut dyn Write) -> Result<(), TsFileError> {
+    let mut reader = TsFileReader::open(path)?;
+    for device in reader.metadata_reader().devices()? {
+        let timeseries = reader
+            .metadata_reader()
+            .get_device_timeseries_metadata(&device)?;
+        let aligned = timeseries
+            .iter()
+            .any(|t| t.timeseries_metadata_type & VALUE_SERIES_MASK != 0);
+        if aligned {
+            writeln!(out, "{} (aligned)", device)?;
+        } else {
+            writeln!(out, "{}", device)?;
+        }
+        // The time column of aligned devices has no measurement id
+        for metadata in timeseries.iter().filter(|t| !t.measurement_id.is_empty()) {
+            write!(
+                out,
+                "  {} {:?}",
+                metadata.measurement_id, metadata.data_type
+            )?;
+            // Encoding and compression of the first chunk
+            if let Some(chunk_metadata) = metadata.chunk_metadata_list.first() {
+                let header = reader.read_chunk_header(chunk_metadata)?;
+                write!(out, " {:?} {:?}", header.encoding, header.compression)?;
+            }
+            writeln!(
+                out,
+                ", {} points [{}, {}], {} chunks",
+                metadata.statistics.count(),
+                metadata.statistics.start_time(),
+                metadata.statistics.end_time(),
+                metadata.chunk_metadata_list.len()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::schema;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn list_schema() {
+        let file_schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::SNAPPY,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let path = "../target/schema.tsfile";
+        let mut writer = TsFileWriter::new(path, file_schema, Default::default()).unwrap();
+        for i in 0..10 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", 100 + i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+            if i == 4 {
+                writer.flush().unwrap();
+            }
+        }
+        writer
+            .write("d1", "s2", 3, IoTDBValue::TEXT("a".to_owned()))
+            .unwrap();
+        writer.close().unwrap();
+
+        let mut out = vec![];
+        schema(path, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "d1\n\
+             \x20 s1 INT64 TS2DIFF SNAPPY, 10 points [0, 9], 2 chunks\n\
+             \x20 s2 TEXT PLAIN UNCOMPRESSED, 1 points [3, 3], 1 chunks\n\
+             d2 (aligned)\n\
+             \x20 s1 DOUBLE GORILLA UNCOMPRESSED, 10 points [100, 109], 2 chunks\n"
+        );
+    }
+}
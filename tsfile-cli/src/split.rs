@@ -0,0 +1,104 @@
+//! Splits a TsFile into one file per device prefix or time window, see `TsFileSplitter`.
+use crate::options::{usage, Options};
+use std::io::Write;
+use tsfile_writer::split::{SplitBy, TsFileSplitter};
+use tsfile_writer::writer::errors::TsFileError;
+
+pub(crate) fn split(
+    source: &str,
+    directory: &str,
+    args: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), TsFileError> {
+    let options = Options::parse(args, &["device-prefix", "time-window"])?;
+    let split_by = match (options.get("device-prefix"), options.get("time-window")) {
+        (Some(nodes), None) => match nodes.parse() {
+            Ok(nodes) if nodes > 0 => SplitBy::DevicePrefix(nodes),
+            _ => return Err(usage(&format!("Invalid number of nodes {}", nodes))),
+        },
+        (None, Some(_)) => match options.timestamp("time-window")? {
+            Some(length) if length > 0 => SplitBy::TimeWindow(length),
+            _ => return Err(usage("The time window has to be positive")),
+        },
+        _ => {
+            return Err(usage(
+                "Either --device-prefix or --time-window has to be given",
+            ))
+        }
+    };
+    for part in TsFileSplitter::new(split_by).split_file(source, directory)? {
+        writeln!(out, "{}", part.display())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::split::split;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn split_file() {
+        let mut schema = TsFileSchemaBuilder::new();
+        for device in ["root.p1.d1", "root.p2.d1"] {
+            schema.add(
+                device,
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            );
+        }
+        let source = "../target/split_cli.tsfile";
+        let mut writer = TsFileWriter::new(source, schema.build(), Default::default()).unwrap();
+        for i in 0..10 {
+            writer
+                .write("root.p1.d1", "s1", i, IoTDBValue::LONG(i))
+                .unwrap();
+            writer
+                .write("root.p2.d1", "s1", i, IoTDBValue::LONG(i))
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut out = vec![];
+        split(
+            source,
+            "../target/split_cli",
+            &["--device-prefix", "2"],
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "../target/split_cli/root.p1.tsfile\n../target/split_cli/root.p2.tsfile\n"
+        );
+
+        let mut out = vec![];
+        split(
+            source,
+            "../target/split_cli_time",
+            &["--time-window", "5"],
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+
+        let error = |args: &[&str]| {
+            split(source, "../target/split_cli", args, &mut vec![])
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error(&[]).contains("Either --device-prefix or --time-window"));
+        assert!(error(&["--time-window", "0"]).contains("has to be positive"));
+        assert!(error(&["--device-prefix", "x"]).contains("Invalid number of nodes x"));
+    }
+}
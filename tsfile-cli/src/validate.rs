@@ -0,0 +1,293 @@
+//! Structural verification of a TsFile.
+//!
+//! Checks the magic string and version in the header, the footer, all markers of the data
+//! section, the metadata index and that the statistics of pages, chunks and timeseries are
+//! consistent. Stops at the first problem and reports its offset.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use tsfile_writer::reader::layout::{ChunkLayout, IndexNodeLayout};
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::reader::TimeseriesMetadata;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::statistics::Statistics;
+
+const MAGIC_STRING: &[u8] = b"TsFile";
+const VERSION_NUMBER: u8 = 3;
+
+pub(crate) fn validate(path: &str, out: &mut dyn Write) -> Result<(), TsFileError> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    if file_size < 17 {
+        return Err(corrupt(0, "File is too small to be a complete TsFile"));
+    }
+    let mut head = [0_u8; 7];
+    file.read_exact(&mut head)?;
+    if &head[0..6] != MAGIC_STRING {
+        return Err(corrupt(0, "Invalid magic string"));
+    }
+    if head[6] != VERSION_NUMBER {
+        return Err(corrupt(6, &format!("Unsupported version {}", head[6])));
+    }
+    let mut tail = [0_u8; 10];
+    file.seek(SeekFrom::End(-10))?;
+    file.read_exact(&mut tail)?;
+    if &tail[4..10] != MAGIC_STRING {
+        return Err(corrupt(file_size - 6, "Invalid magic string"));
+    }
+    let metadata_size = u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+    let metadata_offset = match (file_size - 10).checked_sub(metadata_size) {
+        Some(offset) if offset >= 7 => offset,
+        _ => {
+            return Err(corrupt(
+                file_size - 10,
+                &format!("Invalid metadata size {}", metadata_size),
+            ))
+        }
+    };
+    writeln!(out, "Header and footer: ok")?;
+
+    let mut reader =
+        TsFileReader::open(path).map_err(|e| corrupt(metadata_offset, &e.to_string()))?;
+    let meta_offset = reader.metadata().meta_offset;
+    if meta_offset < 7 || meta_offset as u64 >= metadata_offset {
+        return Err(corrupt(
+            metadata_offset,
+            &format!("Invalid offset of the metadata section {}", meta_offset),
+        ));
+    }
+
+    let chunk_groups = reader.chunk_groups()?;
+    let chunks: HashMap<i64, ChunkLayout> = chunk_groups
+        .iter()
+        .flat_map(|chunk_group| chunk_group.chunks.iter())
+        .map(|chunk| (chunk.offset, chunk.clone()))
+        .collect();
+    for chunk in chunks.values() {
+        check_pages(chunk)?;
+    }
+    writeln!(
+        out,
+        "Data section: ok ({} chunk groups, {} chunks)",
+        chunk_groups.len(),
+        chunks.len()
+    )?;
+
+    let metadata_reader = reader.metadata_reader();
+    let nodes = metadata_reader
+        .index_tree()
+        .map_err(|e| corrupt(metadata_offset, &e.to_string()))?;
+    for node in &nodes {
+        check_index_node(node, meta_offset, metadata_offset as i64)?;
+    }
+    writeln!(out, "Metadata index: ok ({} nodes)", nodes.len())?;
+
+    let mut timeseries_count = 0;
+    for device in metadata_reader.devices()? {
+        for timeseries in metadata_reader.get_device_timeseries_metadata(&device)? {
+            check_timeseries(&device, &timeseries, &chunks)?;
+            timeseries_count += 1;
+        }
+    }
+    writeln!(out, "Statistics: ok ({} timeseries)", timeseries_count)?;
+    writeln!(out, "{} is a valid TsFile", path)?;
+    Ok(())
+}
+
+/// Pages of a chunk have to be ordered by time, they are compared with the chunk metadata
+/// in `check_timeseries`
+fn check_pages(chunk: &ChunkLayout) -> Result<(), TsFileError> {
+    let mut end_time = i64::MIN;
+    for page in &chunk.pages {
+        if let Some(statistics) = &page.statistics {
+            if statistics.start_time() > statistics.end_time()
+                || statistics.start_time() <= end_time
+            {
+                return Err(corrupt(
+                    chunk.offset as u64,
+                    &format!(
+                        "Pages of chunk {} are not ordered by time",
+                        chunk.header.measurement_id
+                    ),
+                ));
+            }
+            end_time = statistics.end_time();
+        }
+    }
+    Ok(())
+}
+
+fn check_index_node(
+    node: &IndexNodeLayout,
+    meta_offset: i64,
+    metadata_offset: i64,
+) -> Result<(), TsFileError> {
+    let mut previous = meta_offset;
+    for child in &node.node.children {
+        if child.offset <= previous || child.offset >= node.node.end_offset {
+            return Err(corrupt(
+                node.offset as u64,
+                &format!(
+                    "Index entry {} points to offset {} outside of the metadata section",
+                    child.name, child.offset
+                ),
+            ));
+        }
+        previous = child.offset;
+    }
+    if node.node.end_offset > metadata_offset {
+        return Err(corrupt(
+            node.offset as u64,
+            &format!("Invalid end offset {} of index node", node.node.end_offset),
+        ));
+    }
+    Ok(())
+}
+
+fn check_timeseries(
+    device: &str,
+    timeseries: &TimeseriesMetadata,
+    chunks: &HashMap<i64, ChunkLayout>,
+) -> Result<(), TsFileError> {
+    let path = format!("{}.{}", device, timeseries.measurement_id);
+    let mut count = 0;
+    let mut start_time = i64::MAX;
+    let mut end_time = i64::MIN;
+    for chunk_metadata in &timeseries.chunk_metadata_list {
+        let offset = chunk_metadata.offset_of_chunk_header;
+        let chunk = match chunks.get(&offset) {
+            Some(chunk) if chunk.header.measurement_id == timeseries.measurement_id => chunk,
+            _ => {
+                return Err(corrupt(
+                    offset.max(0) as u64,
+                    &format!("Chunk metadata of {} does not point to its chunk", path),
+                ))
+            }
+        };
+        let statistics = &chunk_metadata.statistics;
+        let pages: Vec<&Statistics> = chunk
+            .pages
+            .iter()
+            .filter_map(|page| page.statistics.as_ref())
+            .collect();
+        if !pages.is_empty() {
+            let page_count: u32 = pages.iter().map(|page| page.count()).sum();
+            if page_count != statistics.count()
+                || pages[0].start_time() != statistics.start_time()
+                || pages[pages.len() - 1].end_time() != statistics.end_time()
+            {
+                return Err(corrupt(
+                    offset as u64,
+                    &format!("Page statistics of {} do not match the chunk", path),
+                ));
+            }
+        }
+        count += statistics.count();
+        start_time = start_time.min(statistics.start_time());
+        end_time = end_time.max(statistics.end_time());
+    }
+    let statistics = &timeseries.statistics;
+    if count != statistics.count()
+        || start_time != statistics.start_time()
+        || end_time != statistics.end_time()
+    {
+        let offset = match timeseries.chunk_metadata_list.first() {
+            Some(chunk_metadata) => chunk_metadata.offset_of_chunk_header.max(0) as u64,
+            None => 0,
+        };
+        return Err(corrupt(
+            offset,
+            &format!("Chunk statistics of {} do not match the timeseries", path),
+        ));
+    }
+    Ok(())
+}
+
+fn corrupt(offset: u64, message: &str) -> TsFileError {
+    TsFileError::Error {
+        source: Some(format!("Corrupt at offset {}: {}", offset, message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validate::validate;
+    use tsfile_writer::writer::compression::CompressionType;
+    use tsfile_writer::writer::encoding::TSEncoding;
+    use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use tsfile_writer::writer::ts_file_config::TsFileConfig;
+    use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+    fn write(path: &str) -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().max_number_of_points_in_page(10);
+        let mut writer = TsFileWriter::new(path, schema, config).unwrap();
+        for i in 0..25 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+        }
+        writer.close().unwrap();
+        std::fs::read(path).unwrap()
+    }
+
+    fn error(path: &str) -> String {
+        validate(path, &mut vec![]).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn valid_file() {
+        write("../target/validate.tsfile");
+        let mut out = vec![];
+        validate("../target/validate.tsfile", &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Data section: ok (2 chunk groups, 3 chunks)"));
+        assert!(out.contains("Statistics: ok (3 timeseries)"));
+    }
+
+    #[test]
+    fn corrupt_files() {
+        let path = "../target/validate_corrupt.tsfile";
+        let valid = write(path);
+        let corrupt = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = valid.clone();
+            f(&mut bytes);
+            std::fs::write(path, bytes).unwrap();
+            error(path)
+        };
+
+        assert!(corrupt(&|bytes| bytes[0] = b'X').contains("Corrupt at offset 0"));
+        let size = valid.len();
+        assert!(corrupt(&|bytes| bytes[size - 1] = b'X')
+            .contains(&format!("Corrupt at offset {}", size - 6)));
+        // Marker of the first chunk
+        assert!(corrupt(&|bytes| bytes[11] = 9).contains("Unexpected marker 9 at offset 11"));
+        // Data size of the first chunk
+        assert!(corrupt(&|bytes| bytes[15] = 1).contains("Invalid structure at offset 11"));
+    }
+}
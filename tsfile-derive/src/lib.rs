@@ -0,0 +1,278 @@
+//! `#[derive(TsRecord)]` to write a struct as a row of a TsFile device, see `TsRecord` of the
+//! crate `tsfile-writer`, which re-exports the macro with its feature `derive`.
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+#[proc_macro_derive(TsRecord, attributes(tsfile))]
+pub fn derive_ts_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match ts_record(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Data type of a field, `TEXT` values are cloned
+#[derive(Copy, Clone, PartialEq)]
+enum DataType {
+    Int32,
+    Int64,
+    Float,
+    Double,
+    Text,
+}
+
+impl DataType {
+    fn from_ident(ident: &Ident) -> Option<DataType> {
+        match ident.to_string().as_str() {
+            "i32" => Some(DataType::Int32),
+            "i64" => Some(DataType::Int64),
+            "f32" => Some(DataType::Float),
+            "f64" => Some(DataType::Double),
+            "String" => Some(DataType::Text),
+            _ => None,
+        }
+    }
+
+    /// Variants of `TSDataType` and `IoTDBValue`
+    fn variants(self) -> (Ident, Ident) {
+        let (data_type, value) = match self {
+            DataType::Int32 => ("INT32", "INT"),
+            DataType::Int64 => ("INT64", "LONG"),
+            DataType::Float => ("FLOAT", "FLOAT"),
+            DataType::Double => ("DOUBLE", "DOUBLE"),
+            DataType::Text => ("TEXT", "TEXT"),
+        };
+        (
+            Ident::new(data_type, Span::call_site()),
+            Ident::new(value, Span::call_site()),
+        )
+    }
+
+    fn default_encoding(self) -> &'static str {
+        match self {
+            DataType::Int32 | DataType::Int64 => "TS2DIFF",
+            DataType::Float | DataType::Double => "GORILLA",
+            DataType::Text => "PLAIN",
+        }
+    }
+}
+
+struct Measurement {
+    field: Ident,
+    name: String,
+    data_type: DataType,
+    optional: bool,
+    encoding: Ident,
+    compression: Ident,
+}
+
+fn ts_record(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    input,
+                    "TsRecord needs a struct with named fields",
+                ))
+            }
+        },
+        _ => return Err(Error::new_spanned(input, "TsRecord needs a struct")),
+    };
+
+    let mut aligned = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("tsfile")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("aligned") {
+                aligned = true;
+                Ok(())
+            } else {
+                Err(meta.error("Unknown attribute, expected `aligned`"))
+            }
+        })?;
+    }
+
+    let mut timestamp = None;
+    let mut measurements = vec![];
+    for field in fields {
+        let ident = field.ident.clone().expect("Named fields have an ident");
+        let mut is_timestamp = false;
+        let mut skip = false;
+        let mut name = None;
+        let mut encoding = None;
+        let mut compression = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("tsfile")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("timestamp") {
+                    is_timestamp = true;
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("encoding") {
+                    encoding = Some(variant(&meta.value()?.parse::<LitStr>()?));
+                } else if meta.path.is_ident("compression") {
+                    compression = Some(variant(&meta.value()?.parse::<LitStr>()?));
+                } else {
+                    return Err(meta.error(
+                        "Unknown attribute, expected `timestamp`, `skip`, `name`, `encoding` or `compression`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        if skip {
+            continue;
+        }
+        if is_timestamp {
+            if timestamp.is_some() {
+                return Err(Error::new_spanned(
+                    field,
+                    "Only one field can be the timestamp",
+                ));
+            }
+            timestamp = Some(ident);
+            continue;
+        }
+        let (data_type, optional) = match field_type(&field.ty) {
+            Some(field_type) => field_type,
+            None => return Err(Error::new_spanned(
+                &field.ty,
+                "Unsupported type, expected i32, i64, f32, f64 or String (or an Option of them)",
+            )),
+        };
+        measurements.push(Measurement {
+            name: name.unwrap_or_else(|| ident.to_string()),
+            field: ident,
+            data_type,
+            optional,
+            encoding: encoding
+                .unwrap_or_else(|| Ident::new(data_type.default_encoding(), Span::call_site())),
+            compression: compression
+                .unwrap_or_else(|| Ident::new("UNCOMPRESSED", Span::call_site())),
+        });
+    }
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => {
+            return Err(Error::new_spanned(
+                input,
+                "TsRecord needs a field with #[tsfile(timestamp)]",
+            ))
+        }
+    };
+    if measurements.is_empty() {
+        return Err(Error::new_spanned(
+            input,
+            "TsRecord needs at least one measurement",
+        ));
+    }
+
+    let krate = quote!(::tsfile_writer::writer);
+    let builder = if aligned {
+        quote!(#krate::schema::DeviceBuilder::aligned())
+    } else {
+        quote!(#krate::schema::DeviceBuilder::new())
+    };
+    let schemas = measurements.iter().map(|m| {
+        let name = &m.name;
+        let (data_type, _) = m.data_type.variants();
+        let encoding = &m.encoding;
+        let compression = &m.compression;
+        quote! {
+            .add(
+                #name,
+                #krate::TSDataType::#data_type,
+                #krate::encoding::TSEncoding::#encoding,
+                #krate::compression::CompressionType::#compression,
+            )
+        }
+    });
+
+    let write = if measurements.iter().any(|m| m.optional) {
+        let values = measurements.iter().map(|m| {
+            let name = &m.name;
+            let field = &m.field;
+            let (_, value) = m.data_type.variants();
+            let value = match (m.optional, m.data_type) {
+                (true, DataType::Text) => {
+                    quote!(self.#field.clone().map(#krate::IoTDBValue::#value))
+                }
+                (true, _) => quote!(self.#field.map(#krate::IoTDBValue::#value)),
+                (false, DataType::Text) => {
+                    quote!(::std::option::Option::Some(#krate::IoTDBValue::#value(self.#field.clone())))
+                }
+                (false, _) => quote!(::std::option::Option::Some(#krate::IoTDBValue::#value(self.#field))),
+            };
+            quote!((#name, #value))
+        });
+        quote!(writer.write_many_nullable(device, self.#timestamp, [#(#values),*]))
+    } else {
+        let points = measurements.iter().map(|m| {
+            let name = &m.name;
+            let field = &m.field;
+            let (_, value) = m.data_type.variants();
+            let value = match m.data_type {
+                DataType::Text => quote!(#krate::IoTDBValue::#value(self.#field.clone())),
+                _ => quote!(#krate::IoTDBValue::#value(self.#field)),
+            };
+            quote!(#krate::tsfile_writer::DataPoint::new(#name, #value))
+        });
+        quote!(writer.write_many(device, self.#timestamp, [#(#points),*]))
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::record::TsRecord for #ident #type_generics #where_clause {
+            fn measurement_group() -> #krate::MeasurementGroup<'static> {
+                #builder #(#schemas)* .build()
+            }
+
+            fn write_record<'a, T: #krate::PositionedWrite>(
+                &self,
+                writer: &mut #krate::tsfile_writer::TsFileWriter<'a, T>,
+                device: &'a str,
+            ) -> ::std::result::Result<(), #krate::errors::TsFileError> {
+                #write
+            }
+        }
+    })
+}
+
+/// Variant of `TSEncoding` or `CompressionType`, unknown names fail to compile at the string
+fn variant(name: &LitStr) -> Ident {
+    Ident::new(&name.value(), name.span())
+}
+
+/// The data type and whether the field is an `Option`
+fn field_type(ty: &Type) -> Option<(DataType, bool)> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return match segment.arguments {
+            PathArguments::None => DataType::from_ident(&segment.ident).map(|t| (t, false)),
+            _ => None,
+        };
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first()? {
+                GenericArgument::Type(inner) => match field_type(inner)? {
+                    (data_type, false) => Some((data_type, true)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
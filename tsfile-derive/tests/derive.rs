@@ -0,0 +1,93 @@
+use std::io::Cursor;
+use tsfile_writer::reader::schema::FileSchema;
+use tsfile_writer::reader::tsfile_reader::TsFileReader;
+use tsfile_writer::writer::compression::CompressionType;
+use tsfile_writer::writer::encoding::TSEncoding;
+use tsfile_writer::writer::errors::TsFileError;
+use tsfile_writer::writer::record::TsRecord;
+use tsfile_writer::writer::schema::TsFileSchemaBuilder;
+use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+use tsfile_writer::writer::{IoTDBValue, TSDataType};
+
+#[derive(TsRecord)]
+struct Telemetry {
+    #[tsfile(timestamp)]
+    time: i64,
+    #[tsfile(encoding = "PLAIN", compression = "SNAPPY")]
+    temperature: f64,
+    pressure: f32,
+    #[tsfile(name = "count")]
+    counter: i32,
+    total: i64,
+    status: String,
+    #[tsfile(skip)]
+    #[allow(dead_code)]
+    source: Vec<u8>,
+}
+
+#[derive(TsRecord)]
+#[tsfile(aligned)]
+struct Sparse {
+    #[tsfile(timestamp)]
+    time: i64,
+    value: Option<f64>,
+    label: Option<String>,
+}
+
+#[test]
+fn write_records() -> Result<(), TsFileError> {
+    let schema = TsFileSchemaBuilder::new()
+        .add("d1", Telemetry::measurement_group())
+        .add("d2", Sparse::measurement_group())
+        .build();
+    let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+    for i in 0..10 {
+        let telemetry = Telemetry {
+            time: i,
+            temperature: i as f64 / 2.0,
+            pressure: 1.0,
+            counter: i as i32,
+            total: i * 100,
+            status: format!("ok {}", i),
+            source: vec![],
+        };
+        telemetry.write_record(&mut writer, "d1")?;
+        let sparse = Sparse {
+            time: i,
+            value: if i % 2 == 0 { Some(i as f64) } else { None },
+            label: None,
+        };
+        sparse.write_record(&mut writer, "d2")?;
+    }
+    writer.close()?;
+    let file = writer.into_inner();
+
+    let mut file_schema = FileSchema::new();
+    let mut reader = TsFileReader::new(Cursor::new(file))?;
+    file_schema.add_file(&mut reader)?;
+    let d1 = &file_schema.devices["d1"];
+    assert!(!d1.aligned);
+    let names: Vec<&str> = d1.measurements.keys().map(|name| name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["count", "pressure", "status", "temperature", "total"]
+    );
+    assert_eq!(d1.measurements["temperature"].encoding, TSEncoding::PLAIN);
+    assert_eq!(
+        d1.measurements["temperature"].compression,
+        CompressionType::SNAPPY
+    );
+    assert_eq!(d1.measurements["pressure"].encoding, TSEncoding::GORILLA);
+    assert_eq!(d1.measurements["count"].data_type, TSDataType::INT32);
+    assert_eq!(d1.measurements["total"].encoding, TSEncoding::TS2DIFF);
+    assert_eq!(d1.measurements["status"].data_type, TSDataType::TEXT);
+    assert!(file_schema.devices["d2"].aligned);
+
+    let status: Vec<_> = reader.query("d1", "status")?.collect::<Result<_, _>>()?;
+    assert_eq!(status[3], (3, IoTDBValue::TEXT("ok 3".to_owned())));
+    let values: Vec<_> = reader.query("d2", "value")?.collect::<Result<_, _>>()?;
+    assert_eq!(values.len(), 5);
+    assert_eq!(values[1], (2, IoTDBValue::DOUBLE(2.0)));
+    assert_eq!(reader.query("d2", "label")?.count(), 0);
+    Ok(())
+}
@@ -0,0 +1,341 @@
+//! Imports CSV files into TsFiles (feature `csv`).
+//!
+//! The CSV file needs a header and a time column with integer timestamps, sorted ascending.
+//! All other columns are written as measurements of a single device, empty fields are null.
+//! The rows are read as stream and written as `Tablet`s of `batch_size` rows.
+//!
+//! The data type of a column can be declared, otherwise it is inferred from the first non
+//! empty value of the first batch: INT64 if it is an integer, DOUBLE if it is a number and
+//! TEXT otherwise.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+use csv_crate::{ReaderBuilder, StringRecord};
+
+use crate::convert::default_encoding;
+use crate::writer::compression::CompressionType;
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+use crate::writer::tablet::Tablet;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::TsFileWriter;
+use crate::writer::{IoTDBValue, PositionedWrite, TSDataType, WriteWrapper};
+
+/// Imports CSV files into TsFiles with a single device
+///
+/// ```
+/// use tsfile_writer::convert::csv::CsvImporter;
+/// use tsfile_writer::writer::TSDataType;
+///
+/// let csv = "time,temperature,status\n1,20.5,ok\n2,21.0,\n3,,failed\n";
+/// let tsfile = CsvImporter::new("root.sg.d1", "time")
+///     .data_type("temperature", TSDataType::FLOAT)
+///     .import(csv.as_bytes(), vec![])
+///     .unwrap();
+/// assert_eq!(&tsfile[0..6], b"TsFile");
+/// ```
+pub struct CsvImporter {
+    device: String,
+    time_column: String,
+    delimiter: u8,
+    batch_size: usize,
+    data_types: HashMap<String, TSDataType>,
+    encodings: HashMap<String, TSEncoding>,
+    compression: CompressionType,
+    config: TsFileConfig,
+}
+
+impl CsvImporter {
+    pub fn new(device: &str, time_column: &str) -> CsvImporter {
+        CsvImporter {
+            device: device.to_owned(),
+            time_column: time_column.to_owned(),
+            delimiter: b',',
+            batch_size: 10_000,
+            data_types: HashMap::new(),
+            encodings: HashMap::new(),
+            compression: CompressionType::UNCOMPRESSED,
+            config: TsFileConfig::default(),
+        }
+    }
+
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut CsvImporter {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Number of rows written per `Tablet`, the default is 10000
+    pub fn batch_size(&mut self, batch_size: usize) -> &mut CsvImporter {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Declares the data type of a column instead of inferring it
+    pub fn data_type(&mut self, measurement: &str, data_type: TSDataType) -> &mut CsvImporter {
+        self.data_types.insert(measurement.to_owned(), data_type);
+        self
+    }
+
+    /// Encoding of a single measurement, the default is TS2DIFF for integers,
+    /// GORILLA for floating point values and PLAIN for TEXT
+    pub fn encoding(&mut self, measurement: &str, encoding: TSEncoding) -> &mut CsvImporter {
+        self.encodings.insert(measurement.to_owned(), encoding);
+        self
+    }
+
+    /// Compression of all measurements, the default is UNCOMPRESSED
+    pub fn compression(&mut self, compression: CompressionType) -> &mut CsvImporter {
+        self.compression = compression;
+        self
+    }
+
+    pub fn config(&mut self, config: TsFileConfig) -> &mut CsvImporter {
+        self.config = config;
+        self
+    }
+
+    pub fn import_file(&self, csv_file: &str, tsfile: &str) -> Result<(), TsFileError> {
+        let file = File::create(tsfile)?;
+        self.import(File::open(csv_file)?, WriteWrapper::new(file))?;
+        Ok(())
+    }
+
+    /// Imports the CSV data and returns the sink with the complete TsFile
+    pub fn import<R: Read, T: PositionedWrite>(&self, csv: R, out: T) -> Result<T, TsFileError> {
+        if self.batch_size == 0 {
+            return Err(TsFileError::IllegalState {
+                source: Some("Batch size has to be positive".to_owned()),
+            });
+        }
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .from_reader(csv);
+        let header = reader.headers().map_err(csv_error)?.clone();
+        let time_index = match header.iter().position(|name| name == self.time_column) {
+            Some(time_index) => time_index,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some(format!("No time column {} in CSV file", self.time_column)),
+                });
+            }
+        };
+        let measurements: Vec<(usize, &str)> = header
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != time_index)
+            .collect();
+        if measurements.is_empty() {
+            return Err(TsFileError::SchemaError {
+                source: Some("CSV file contains no value columns".to_owned()),
+            });
+        }
+
+        let mut records = reader.into_records();
+        let mut batch = next_batch(&mut records, self.batch_size)?;
+
+        let data_types: Vec<TSDataType> = measurements
+            .iter()
+            .map(
+                |(index, measurement)| match self.data_types.get(*measurement) {
+                    Some(data_type) => *data_type,
+                    None => infer_data_type(&batch, *index),
+                },
+            )
+            .collect();
+        let mut device = DeviceBuilder::new();
+        for ((_, measurement), data_type) in measurements.iter().zip(&data_types) {
+            device.add(
+                measurement,
+                *data_type,
+                self.encoding_for(measurement, *data_type),
+                self.compression,
+            );
+        }
+        let schema = TsFileSchemaBuilder::new()
+            .add(&self.device, device.try_build()?)
+            .try_build()?;
+
+        let mut writer = TsFileWriter::new_from_writer(schema, out, self.config)?;
+        while !batch.is_empty() {
+            let timestamps = batch
+                .iter()
+                .map(|record| parse(record, time_index, TSDataType::INT64))
+                .map(|value| match value? {
+                    Some(IoTDBValue::LONG(timestamp)) => Ok(timestamp),
+                    _ => Err(TsFileError::IllegalState {
+                        source: Some("Empty timestamp in CSV file".to_owned()),
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut tablet = Tablet::new(timestamps);
+            for ((index, measurement), data_type) in measurements.iter().zip(&data_types) {
+                let mut values = Vec::with_capacity(batch.len());
+                let mut bitmap = Vec::with_capacity(batch.len());
+                for record in &batch {
+                    let value = parse(record, *index, *data_type)?;
+                    bitmap.push(value.is_none());
+                    values.push(value.unwrap_or_else(|| null_value(*data_type)));
+                }
+                tablet.add_column_with_bitmap(measurement, values, bitmap)?;
+            }
+            writer.write_tablet(&self.device, tablet)?;
+            batch = next_batch(&mut records, self.batch_size)?;
+        }
+        writer.close()?;
+        Ok(writer.into_inner())
+    }
+
+    fn encoding_for(&self, measurement: &str, data_type: TSDataType) -> TSEncoding {
+        match self.encodings.get(measurement) {
+            Some(encoding) => *encoding,
+            None => default_encoding(data_type),
+        }
+    }
+}
+
+fn next_batch<R: Read>(
+    records: &mut csv_crate::StringRecordsIntoIter<R>,
+    batch_size: usize,
+) -> Result<Vec<StringRecord>, TsFileError> {
+    records
+        .take(batch_size)
+        .collect::<Result<_, _>>()
+        .map_err(csv_error)
+}
+
+fn infer_data_type(batch: &[StringRecord], index: usize) -> TSDataType {
+    match batch
+        .iter()
+        .filter_map(|record| record.get(index))
+        .find(|field| !field.is_empty())
+    {
+        Some(field) if field.parse::<i64>().is_ok() => TSDataType::INT64,
+        Some(field) if field.parse::<f64>().is_ok() => TSDataType::DOUBLE,
+        _ => TSDataType::TEXT,
+    }
+}
+
+/// Parses a field, `None` for an empty field
+fn parse(
+    record: &StringRecord,
+    index: usize,
+    data_type: TSDataType,
+) -> Result<Option<IoTDBValue>, TsFileError> {
+    let field = record.get(index).unwrap_or_default();
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let value = match data_type {
+        TSDataType::INT32 => field.parse().map(IoTDBValue::INT).ok(),
+        TSDataType::INT64 => field.parse().map(IoTDBValue::LONG).ok(),
+        TSDataType::FLOAT => field.parse().map(IoTDBValue::FLOAT).ok(),
+        TSDataType::DOUBLE => field.parse().map(IoTDBValue::DOUBLE).ok(),
+        TSDataType::TEXT => Some(IoTDBValue::TEXT(field.to_owned())),
+        TSDataType::VECTOR => None,
+    };
+    match value {
+        Some(value) => Ok(Some(value)),
+        None => Err(TsFileError::IllegalState {
+            source: Some(format!(
+                "Cannot parse '{}' as {:?} in line {}",
+                field,
+                data_type,
+                record.position().map(|p| p.line()).unwrap_or_default()
+            )),
+        }),
+    }
+}
+
+/// Placeholder for null values in a tablet, it is ignored by the writer
+fn null_value(data_type: TSDataType) -> IoTDBValue {
+    match data_type {
+        TSDataType::INT32 => IoTDBValue::INT(0),
+        TSDataType::FLOAT => IoTDBValue::FLOAT(0.0),
+        TSDataType::DOUBLE => IoTDBValue::DOUBLE(0.0),
+        TSDataType::TEXT => IoTDBValue::TEXT(String::new()),
+        _ => IoTDBValue::LONG(0),
+    }
+}
+
+fn csv_error(e: csv_crate::Error) -> TsFileError {
+    TsFileError::Error {
+        source: Some(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::convert::csv::CsvImporter;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn csv() -> String {
+        let mut csv = "time;temperature;status;count\n".to_owned();
+        for i in 0..1000 {
+            let status = if i % 2 == 0 { "" } else { "ok" };
+            csv.push_str(&format!("{};{}.5;{};{}\n", i, i, status, i));
+        }
+        csv
+    }
+
+    fn query(tsfile: Vec<u8>, measurement: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(Cursor::new(tsfile)).unwrap();
+        reader
+            .query("d1", measurement)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn import() -> Result<(), TsFileError> {
+        let tsfile = CsvImporter::new("d1", "time")
+            .delimiter(b';')
+            .batch_size(64)
+            .data_type("count", TSDataType::INT32)
+            .import(csv().as_bytes(), vec![])?;
+
+        let mut reader = TsFileReader::new(Cursor::new(tsfile.clone()))?;
+        let data_type = |reader: &mut TsFileReader<_>, measurement| {
+            reader
+                .get_timeseries_metadata("d1", measurement)
+                .unwrap()
+                .unwrap()
+                .data_type
+        };
+        assert_eq!(data_type(&mut reader, "temperature"), TSDataType::DOUBLE);
+        assert_eq!(data_type(&mut reader, "status"), TSDataType::TEXT);
+        assert_eq!(data_type(&mut reader, "count"), TSDataType::INT32);
+
+        let temperature = query(tsfile.clone(), "temperature");
+        assert_eq!(temperature.len(), 1000);
+        assert_eq!(temperature[999], (999, IoTDBValue::DOUBLE(999.5)));
+
+        let status = query(tsfile, "status");
+        assert_eq!(status.len(), 500);
+        assert_eq!(status[0], (1, IoTDBValue::TEXT("ok".to_owned())));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_csv() {
+        let importer = CsvImporter::new("d1", "time");
+        assert!(matches!(
+            importer.import("timestamp,s1\n1,2\n".as_bytes(), vec![]),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        assert!(matches!(
+            importer.import("time,s1\n1,2\n2,a\n".as_bytes(), vec![]),
+            Err(TsFileError::IllegalState { .. })
+        ));
+        assert!(matches!(
+            importer.import("time,s1\n2,1\n1,2\n".as_bytes(), vec![]),
+            Err(TsFileError::OutOfOrderData)
+        ));
+    }
+}
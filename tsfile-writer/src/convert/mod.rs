@@ -0,0 +1,18 @@
+//! Converters from other file formats to TsFiles
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+#[cfg(any(feature = "csv", feature = "parquet"))]
+use crate::writer::{encoding::TSEncoding, TSDataType};
+
+/// TS2DIFF for integers, GORILLA for floating point values and PLAIN for TEXT
+#[cfg(any(feature = "csv", feature = "parquet"))]
+fn default_encoding(data_type: TSDataType) -> TSEncoding {
+    match data_type {
+        TSDataType::INT32 | TSDataType::INT64 => TSEncoding::TS2DIFF,
+        TSDataType::FLOAT | TSDataType::DOUBLE => TSEncoding::GORILLA,
+        _ => TSEncoding::PLAIN,
+    }
+}
@@ -0,0 +1,229 @@
+//! Converts Parquet files to TsFiles (feature `parquet`).
+//!
+//! The Parquet file has to contain a time column (Int64 or Timestamp, sorted ascending),
+//! all other columns are written as measurements of a single device. The column types are
+//! mapped like for Arrow `RecordBatch`es, see `writer::arrow`.
+use std::collections::HashMap;
+use std::fs::File;
+
+use parquet_crate::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet_crate::file::reader::ChunkReader;
+
+use crate::convert::default_encoding;
+use crate::writer::arrow::ts_data_type;
+use crate::writer::compression::CompressionType;
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::TsFileWriter;
+use crate::writer::{PositionedWrite, TSDataType, WriteWrapper};
+
+/// Converts Parquet files to TsFiles with a single device
+///
+/// ```no_run
+/// use tsfile_writer::convert::parquet::ParquetConverter;
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+///
+/// ParquetConverter::new("root.sg.d1", "timestamp")
+///     .encoding("status", TSEncoding::PLAIN)
+///     .compression(CompressionType::SNAPPY)
+///     .convert_file("export.parquet", "export.tsfile")
+///     .unwrap();
+/// ```
+pub struct ParquetConverter {
+    device: String,
+    time_column: String,
+    encodings: HashMap<String, TSEncoding>,
+    compression: CompressionType,
+    config: TsFileConfig,
+}
+
+impl ParquetConverter {
+    pub fn new(device: &str, time_column: &str) -> ParquetConverter {
+        ParquetConverter {
+            device: device.to_owned(),
+            time_column: time_column.to_owned(),
+            encodings: HashMap::new(),
+            compression: CompressionType::UNCOMPRESSED,
+            config: TsFileConfig::default(),
+        }
+    }
+
+    /// Encoding of a single measurement, the default is TS2DIFF for integers,
+    /// GORILLA for floating point values and PLAIN for TEXT
+    pub fn encoding(&mut self, measurement: &str, encoding: TSEncoding) -> &mut ParquetConverter {
+        self.encodings.insert(measurement.to_owned(), encoding);
+        self
+    }
+
+    /// Compression of all measurements, the default is UNCOMPRESSED
+    pub fn compression(&mut self, compression: CompressionType) -> &mut ParquetConverter {
+        self.compression = compression;
+        self
+    }
+
+    pub fn config(&mut self, config: TsFileConfig) -> &mut ParquetConverter {
+        self.config = config;
+        self
+    }
+
+    pub fn convert_file(&self, parquet_file: &str, tsfile: &str) -> Result<(), TsFileError> {
+        let file = File::create(tsfile)?;
+        self.convert(File::open(parquet_file)?, WriteWrapper::new(file))?;
+        Ok(())
+    }
+
+    /// Converts the Parquet data (e.g. a `File` or `Bytes`) and returns the sink with the
+    /// complete TsFile
+    pub fn convert<R: ChunkReader + 'static, T: PositionedWrite>(
+        &self,
+        parquet: R,
+        out: T,
+    ) -> Result<T, TsFileError> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(parquet).map_err(parquet_error)?;
+
+        if builder.schema().field_with_name(&self.time_column).is_err() {
+            return Err(TsFileError::SchemaError {
+                source: Some(format!(
+                    "No time column {} in Parquet file",
+                    self.time_column
+                )),
+            });
+        }
+        let mut measurements = vec![];
+        for field in builder.schema().fields() {
+            if field.name() != &self.time_column {
+                measurements.push((field.name().clone(), ts_data_type(field.data_type())?));
+            }
+        }
+        if measurements.is_empty() {
+            return Err(TsFileError::SchemaError {
+                source: Some("Parquet file contains no value columns".to_owned()),
+            });
+        }
+
+        let mut device = DeviceBuilder::new();
+        for (measurement, data_type) in &measurements {
+            device.add(
+                measurement,
+                *data_type,
+                self.encoding_for(measurement, *data_type),
+                self.compression,
+            );
+        }
+        let schema = TsFileSchemaBuilder::new()
+            .add(&self.device, device.try_build()?)
+            .try_build()?;
+
+        let mut writer = TsFileWriter::new_from_writer(schema, out, self.config)?;
+        for batch in builder.build().map_err(parquet_error)? {
+            let batch = batch.map_err(|e| TsFileError::Error {
+                source: Some(e.to_string()),
+            })?;
+            writer.write_record_batch(&self.device, &batch, &self.time_column)?;
+        }
+        writer.close()?;
+        Ok(writer.into_inner())
+    }
+
+    fn encoding_for(&self, measurement: &str, data_type: TSDataType) -> TSEncoding {
+        match self.encodings.get(measurement) {
+            Some(encoding) => *encoding,
+            None => default_encoding(data_type),
+        }
+    }
+}
+
+fn parquet_error(e: parquet_crate::errors::ParquetError) -> TsFileError {
+    TsFileError::Error {
+        source: Some(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Float32Array, Int32Array, RecordBatch, TimestampMillisecondArray};
+    use parquet_crate::arrow::ArrowWriter;
+
+    use crate::convert::parquet::ParquetConverter;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn batch(offset: i64) -> RecordBatch {
+        RecordBatch::try_from_iter(vec![
+            (
+                "timestamp",
+                Arc::new(TimestampMillisecondArray::from_iter_values(
+                    offset..offset + 1000,
+                )) as ArrayRef,
+            ),
+            (
+                "temperature",
+                Arc::new(Float32Array::from_iter_values(
+                    (offset..offset + 1000).map(|i| i as f32),
+                )),
+            ),
+            (
+                "status",
+                Arc::new(Int32Array::from_iter((offset..offset + 1000).map(|i| {
+                    if i % 2 == 0 {
+                        Some(1)
+                    } else {
+                        None
+                    }
+                }))),
+            ),
+        ])
+        .unwrap()
+    }
+
+    /// Two row groups with 1000 rows each
+    fn parquet() -> Vec<u8> {
+        let mut buffer = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buffer, batch(0).schema(), None).unwrap();
+        for offset in [0, 1000] {
+            writer.write(&batch(offset)).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.close().unwrap();
+        buffer
+    }
+
+    #[test]
+    fn convert() -> Result<(), TsFileError> {
+        let tsfile = ParquetConverter::new("d1", "timestamp")
+            .encoding("status", TSEncoding::PLAIN)
+            .convert(bytes::Bytes::from(parquet()), vec![])?;
+
+        let mut reader = TsFileReader::new(Cursor::new(tsfile))?;
+        let status = reader.get_timeseries_metadata("d1", "status")?.unwrap();
+        assert_eq!(status.data_type, TSDataType::INT32);
+
+        let temperature = reader
+            .query("d1", "temperature")?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(temperature.len(), 2000);
+        assert_eq!(temperature[1999], (1999, IoTDBValue::FLOAT(1999.0)));
+
+        let status = reader
+            .query("d1", "status")?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(status.len(), 1000);
+        assert_eq!(status[1], (2, IoTDBValue::INT(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_time_column() {
+        let result =
+            ParquetConverter::new("d1", "time").convert(bytes::Bytes::from(parquet()), vec![]);
+        assert!(matches!(result, Err(TsFileError::SchemaError { .. })));
+    }
+}
@@ -0,0 +1,473 @@
+//! Compares two TsFiles, e.g. a file of this writer with the file IoTDB writes for the same
+//! points.
+//!
+//! Logical differences change the content of the file: devices, timeseries, data types,
+//! statistics, points and properties. Structural differences only change its layout: offsets,
+//! chunks, encodings and compressions. Two files with only structural differences contain
+//! the same data.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+
+use crate::reader::chunk_reader::ChunkHeader;
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::reader::TimeseriesMetadata;
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use crate::writer::Serializable;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DifferenceKind {
+    Logical,
+    Structural,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    /// `device.measurement` of the timeseries or the device, `None` for the whole file
+    pub path: Option<String>,
+    pub description: String,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            DifferenceKind::Logical => "logical",
+            DifferenceKind::Structural => "structural",
+        };
+        match &self.path {
+            Some(path) => write!(f, "[{}] {}: {}", kind, path, self.description),
+            None => write!(f, "[{}] {}", kind, self.description),
+        }
+    }
+}
+
+/// Compares TsFiles, the left file is the reference
+///
+/// ```no_run
+/// use tsfile_writer::diff::TsFileDiff;
+///
+/// for difference in TsFileDiff::new().diff_files("rust.tsfile", "java.tsfile").unwrap() {
+///     println!("{}", difference);
+/// }
+/// ```
+pub struct TsFileDiff {
+    compare_points: bool,
+}
+
+impl TsFileDiff {
+    pub fn new() -> TsFileDiff {
+        TsFileDiff {
+            compare_points: true,
+        }
+    }
+
+    /// Compares the points of every timeseries, enabled by default. If disabled, only the
+    /// metadata and the chunk headers are read and points are compared by their statistics.
+    pub fn compare_points(&mut self, compare_points: bool) -> &mut TsFileDiff {
+        self.compare_points = compare_points;
+        self
+    }
+
+    pub fn diff_files(&self, left: &str, right: &str) -> Result<Vec<Difference>, TsFileError> {
+        self.diff(
+            &mut TsFileReader::open(left)?,
+            &mut TsFileReader::open(right)?,
+        )
+    }
+
+    /// All differences of the files, empty if they are identical
+    pub fn diff<L: Read + Seek, R: Read + Seek>(
+        &self,
+        left: &mut TsFileReader<L>,
+        right: &mut TsFileReader<R>,
+    ) -> Result<Vec<Difference>, TsFileError> {
+        let mut differences = Differences::default();
+        let (left_size, right_size) = (
+            left.metadata_reader().file_size(),
+            right.metadata_reader().file_size(),
+        );
+        if left_size != right_size {
+            differences.structural(None, format!("File size {} != {}", left_size, right_size));
+        }
+        let (left_offset, right_offset) =
+            (left.metadata().meta_offset, right.metadata().meta_offset);
+        if left_offset != right_offset {
+            differences.structural(
+                None,
+                format!("Metadata offset {} != {}", left_offset, right_offset),
+            );
+        }
+        let left_properties = left.metadata_reader().properties().clone();
+        let right_properties = right.metadata_reader().properties().clone();
+        for key in union(left_properties.keys(), right_properties.keys()) {
+            let (left_value, right_value) = (left_properties.get(key), right_properties.get(key));
+            if left_value != right_value {
+                differences.logical(
+                    None,
+                    format!("Property {}: {:?} != {:?}", key, left_value, right_value),
+                );
+            }
+        }
+
+        let left_devices = left.metadata_reader().devices()?;
+        let right_devices = right.metadata_reader().devices()?;
+        for device in union(left_devices.iter(), right_devices.iter()) {
+            let left_series = timeseries(left, device)?;
+            let right_series = timeseries(right, device)?;
+            match (left_series.is_empty(), right_series.is_empty()) {
+                (false, true) => {
+                    differences.logical(Some(device.clone()), "Only in the left file".to_owned());
+                    continue;
+                }
+                (true, false) => {
+                    differences.logical(Some(device.clone()), "Only in the right file".to_owned());
+                    continue;
+                }
+                _ => {}
+            }
+            for measurement in union(left_series.keys(), right_series.keys()) {
+                let path = match measurement.as_str() {
+                    "" => format!("{} (time)", device),
+                    measurement => format!("{}.{}", device, measurement),
+                };
+                match (left_series.get(measurement), right_series.get(measurement)) {
+                    (Some(left_metadata), Some(right_metadata)) => self.diff_timeseries(
+                        left,
+                        right,
+                        device,
+                        &path,
+                        left_metadata,
+                        right_metadata,
+                        &mut differences,
+                    )?,
+                    (Some(_), None) => {
+                        differences.logical(Some(path), "Only in the left file".to_owned())
+                    }
+                    _ => differences.logical(Some(path), "Only in the right file".to_owned()),
+                }
+            }
+        }
+        Ok(differences.0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn diff_timeseries<L: Read + Seek, R: Read + Seek>(
+        &self,
+        left: &mut TsFileReader<L>,
+        right: &mut TsFileReader<R>,
+        device: &str,
+        path: &str,
+        left_metadata: &TimeseriesMetadata,
+        right_metadata: &TimeseriesMetadata,
+        differences: &mut Differences,
+    ) -> Result<(), TsFileError> {
+        let path = Some(path.to_owned());
+        if left_metadata.data_type != right_metadata.data_type {
+            differences.logical(
+                path,
+                format!(
+                    "Data type {:?} != {:?}",
+                    left_metadata.data_type, right_metadata.data_type
+                ),
+            );
+            return Ok(());
+        }
+        if left_metadata.timeseries_metadata_type != right_metadata.timeseries_metadata_type {
+            differences.structural(
+                path.clone(),
+                format!(
+                    "Timeseries metadata type {} != {}",
+                    left_metadata.timeseries_metadata_type, right_metadata.timeseries_metadata_type
+                ),
+            );
+        }
+        if !same_statistics(&left_metadata.statistics, &right_metadata.statistics)? {
+            differences.logical(
+                path.clone(),
+                format!(
+                    "Statistics {} != {}",
+                    describe(&left_metadata.statistics),
+                    describe(&right_metadata.statistics)
+                ),
+            );
+        }
+
+        let (left_chunks, right_chunks) = (
+            &left_metadata.chunk_metadata_list,
+            &right_metadata.chunk_metadata_list,
+        );
+        if left_chunks.len() != right_chunks.len() {
+            differences.structural(
+                path.clone(),
+                format!("{} chunks != {}", left_chunks.len(), right_chunks.len()),
+            );
+        }
+        for (index, (left_chunk, right_chunk)) in left_chunks.iter().zip(right_chunks).enumerate() {
+            if left_chunk.offset_of_chunk_header != right_chunk.offset_of_chunk_header {
+                differences.structural(
+                    path.clone(),
+                    format!(
+                        "Chunk {} at offset {} != {}",
+                        index,
+                        left_chunk.offset_of_chunk_header,
+                        right_chunk.offset_of_chunk_header
+                    ),
+                );
+            }
+            if !same_statistics(&left_chunk.statistics, &right_chunk.statistics)? {
+                differences.structural(
+                    path.clone(),
+                    format!(
+                        "Chunk {} statistics {} != {}",
+                        index,
+                        describe(&left_chunk.statistics),
+                        describe(&right_chunk.statistics)
+                    ),
+                );
+            }
+            let left_header = left.read_chunk_header(left_chunk)?;
+            let right_header = right.read_chunk_header(right_chunk)?;
+            if let Some(description) = diff_headers(&left_header, &right_header) {
+                differences.structural(path.clone(), format!("Chunk {} {}", index, description));
+            }
+        }
+
+        // The time column has no points of its own
+        if self.compare_points && !left_metadata.measurement_id.is_empty() {
+            let measurement = &left_metadata.measurement_id;
+            let mut left_points = left.query(device, measurement)?;
+            let mut right_points = right.query(device, measurement)?;
+            let mut index = 0;
+            loop {
+                match (
+                    left_points.next().transpose()?,
+                    right_points.next().transpose()?,
+                ) {
+                    (Some(left_point), Some(right_point)) if left_point == right_point => {}
+                    (Some(left_point), Some(right_point)) => {
+                        differences.logical(
+                            path,
+                            format!("Point {}: {:?} != {:?}", index, left_point, right_point),
+                        );
+                        break;
+                    }
+                    (Some(_), None) => {
+                        differences.logical(
+                            path,
+                            format!("The left file has more than {} points", index),
+                        );
+                        break;
+                    }
+                    (None, Some(_)) => {
+                        differences.logical(
+                            path,
+                            format!("The right file has more than {} points", index),
+                        );
+                        break;
+                    }
+                    (None, None) => break,
+                }
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TsFileDiff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Default)]
+struct Differences(Vec<Difference>);
+
+impl Differences {
+    fn logical(&mut self, path: Option<String>, description: String) {
+        self.0.push(Difference {
+            kind: DifferenceKind::Logical,
+            path,
+            description,
+        });
+    }
+
+    fn structural(&mut self, path: Option<String>, description: String) {
+        self.0.push(Difference {
+            kind: DifferenceKind::Structural,
+            path,
+            description,
+        });
+    }
+}
+
+/// Sorted union of both sides
+fn union<'a, T: Ord + 'a>(
+    left: impl Iterator<Item = &'a T>,
+    right: impl Iterator<Item = &'a T>,
+) -> BTreeSet<&'a T> {
+    left.chain(right).collect()
+}
+
+/// The timeseries of the device by their measurement id, empty if the device does not exist
+fn timeseries<R: Read + Seek>(
+    reader: &mut TsFileReader<R>,
+    device: &str,
+) -> Result<BTreeMap<String, TimeseriesMetadata>, TsFileError> {
+    Ok(reader
+        .metadata_reader()
+        .get_device_timeseries_metadata(device)?
+        .into_iter()
+        .map(|metadata| (metadata.measurement_id.clone(), metadata))
+        .collect())
+}
+
+/// Compares the serialized statistics, i.e. including the sums of the values
+fn same_statistics(left: &Statistics, right: &Statistics) -> Result<bool, TsFileError> {
+    let (mut left_bytes, mut right_bytes) = (vec![], vec![]);
+    left.serialize(&mut left_bytes)?;
+    right.serialize(&mut right_bytes)?;
+    Ok(left_bytes == right_bytes)
+}
+
+fn describe(statistics: &Statistics) -> String {
+    format!(
+        "({} points [{}, {}], min {:?}, max {:?}, sum {:?})",
+        statistics.count(),
+        statistics.start_time(),
+        statistics.end_time(),
+        statistics.min_value(),
+        statistics.max_value(),
+        statistics.sum_value()
+    )
+}
+
+fn diff_headers(left: &ChunkHeader, right: &ChunkHeader) -> Option<String> {
+    if left.encoding != right.encoding || left.compression != right.compression {
+        Some(format!(
+            "{:?} {:?} != {:?} {:?}",
+            left.encoding, left.compression, right.encoding, right.compression
+        ))
+    } else if left.chunk_type != right.chunk_type || left.data_size != right.data_size {
+        Some(format!(
+            "type {} with {} bytes != type {} with {} bytes",
+            left.chunk_type, left.data_size, right.chunk_type, right.data_size
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::diff::{Difference, DifferenceKind, TsFileDiff};
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn write(encoding: TSEncoding, last: i64, d2: bool) -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        encoding,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        for i in 0..10 {
+            let value = if i == 9 { last } else { i };
+            writer
+                .write("d1", "s1", i, IoTDBValue::LONG(value))
+                .unwrap();
+            if d2 {
+                writer
+                    .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                    .unwrap();
+            }
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn diff(left: Vec<u8>, right: Vec<u8>) -> Result<Vec<Difference>, TsFileError> {
+        TsFileDiff::new().diff(
+            &mut TsFileReader::new(Cursor::new(left))?,
+            &mut TsFileReader::new(Cursor::new(right))?,
+        )
+    }
+
+    #[test]
+    fn identical() -> Result<(), TsFileError> {
+        let file = write(TSEncoding::PLAIN, 9, true);
+        assert!(diff(file.clone(), file)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn structural() -> Result<(), TsFileError> {
+        let differences = diff(
+            write(TSEncoding::PLAIN, 9, true),
+            write(TSEncoding::TS2DIFF, 9, true),
+        )?;
+        assert!(!differences.is_empty());
+        assert!(differences
+            .iter()
+            .all(|difference| difference.kind == DifferenceKind::Structural));
+        assert!(differences.iter().any(|difference| difference.to_string()
+            == "[structural] d1.s1: Chunk 0 PLAIN UNCOMPRESSED != TS2DIFF UNCOMPRESSED"));
+        Ok(())
+    }
+
+    #[test]
+    fn logical() -> Result<(), TsFileError> {
+        let differences = diff(
+            write(TSEncoding::PLAIN, 9, true),
+            write(TSEncoding::PLAIN, 10, false),
+        )?;
+        let logical: Vec<String> = differences
+            .iter()
+            .filter(|difference| difference.kind == DifferenceKind::Logical)
+            .map(|difference| difference.to_string())
+            .collect();
+        assert_eq!(logical.len(), 3, "{:?}", logical);
+        assert!(logical[0].starts_with("[logical] d1.s1: Statistics (10 points [0, 9]"));
+        assert_eq!(
+            logical[1],
+            "[logical] d1.s1: Point 9: (9, LONG(9)) != (9, LONG(10))"
+        );
+        assert_eq!(logical[2], "[logical] d2: Only in the left file");
+
+        // Only the statistics without comparing points
+        let differences = TsFileDiff::new().compare_points(false).diff(
+            &mut TsFileReader::new(Cursor::new(write(TSEncoding::PLAIN, 9, true)))?,
+            &mut TsFileReader::new(Cursor::new(write(TSEncoding::PLAIN, 10, true)))?,
+        )?;
+        assert_eq!(differences.len(), 2);
+        Ok(())
+    }
+}
@@ -2,7 +2,6 @@
 //! Currently not all features of TsFiles are supported.
 //! Most notably:
 //!
-//! * No Aligned Chunks can be written
 //! * Not all Encodings are available
 //! * Not all DataTypes are supported
 //! * Not all Compression Types are supported
@@ -79,7 +78,16 @@
 //! // Write single series
 //! writer.write("d2", "s1", 1, IoTDBValue::LONG(14));
 //! writer.write("d2", "s2", 1, IoTDBValue::FLOAT(14.0 as f32));
+//!
+//! // Write the metadata and finish the file
+//! writer.close().unwrap();
 //! ```
+pub mod convert;
+pub mod diff;
+pub mod merge;
+pub mod reader;
+pub mod repair;
+pub mod split;
 #[cfg(feature = "sync_sender")]
 pub mod sync;
 pub mod writer;
@@ -0,0 +1,388 @@
+//! Merges several TsFiles into one, e.g. to compact small files of an edge node.
+//!
+//! The devices and timeseries of all source files are combined, every device is written as
+//! one time ordered chunk group. Encoding and compression of a timeseries are taken from its
+//! first chunk in the first file that contains it. Points with the same timestamp in several
+//! files are resolved with the `ConflictPolicy`.
+//!
+//! If the time ranges of a device do not overlap between the files, its chunks are copied
+//! without decoding and re-encoding them, only the metadata is rebuilt. Otherwise the points
+//! of the device are merged in memory, so a single device of all source files together has
+//! to fit into memory.
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek};
+
+use crate::reader::layout::ChunkGroupLayout;
+use crate::reader::schema::FileSchema;
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::reader::ChunkMetadata;
+use crate::writer::errors::TsFileError;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_io_writer::RawChunk;
+use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+use crate::writer::{
+    IoTDBValue, PositionedWrite, WriteWrapper, TIME_SERIES_MASK, VALUE_SERIES_MASK,
+};
+
+/// What to do if several source files contain a point for the same timestamp of a timeseries
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The value of the first source file is kept
+    KeepFirst,
+    /// The value of the last source file is kept, i.e. later files overwrite earlier ones
+    KeepLast,
+    /// The merge fails with `TsFileError::IllegalState`
+    Error,
+}
+
+/// Merges TsFiles into a single file
+///
+/// ```no_run
+/// use tsfile_writer::merge::{ConflictPolicy, TsFileMerger};
+///
+/// TsFileMerger::new()
+///     .conflict_policy(ConflictPolicy::KeepFirst)
+///     .merge_files(&["00.tsfile", "01.tsfile"], "merged.tsfile")
+///     .unwrap();
+/// ```
+pub struct TsFileMerger {
+    conflict_policy: ConflictPolicy,
+    copy_chunks: bool,
+    config: TsFileConfig,
+}
+
+impl TsFileMerger {
+    pub fn new() -> TsFileMerger {
+        TsFileMerger {
+            conflict_policy: ConflictPolicy::KeepLast,
+            copy_chunks: true,
+            config: TsFileConfig::default(),
+        }
+    }
+
+    /// Resolution of duplicate timestamps, the default is `KeepLast`
+    pub fn conflict_policy(&mut self, conflict_policy: ConflictPolicy) -> &mut TsFileMerger {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Copies the chunks of devices whose time ranges do not overlap between the files,
+    /// enabled by default. If disabled, all points are re-encoded with the encoding and
+    /// compression of the first file.
+    pub fn copy_chunks(&mut self, copy_chunks: bool) -> &mut TsFileMerger {
+        self.copy_chunks = copy_chunks;
+        self
+    }
+
+    pub fn config(&mut self, config: TsFileConfig) -> &mut TsFileMerger {
+        self.config = config;
+        self
+    }
+
+    pub fn merge_files(&self, sources: &[&str], target: &str) -> Result<(), TsFileError> {
+        let readers = sources
+            .iter()
+            .map(|source| TsFileReader::open(source))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.merge(readers, WriteWrapper::new(File::create(target)?))?;
+        Ok(())
+    }
+
+    /// Merges the source files in the given order and returns the sink with the complete TsFile
+    pub fn merge<R: Read + Seek, T: PositionedWrite>(
+        &self,
+        mut sources: Vec<TsFileReader<R>>,
+        out: T,
+    ) -> Result<T, TsFileError> {
+        let mut file_schema = FileSchema::new();
+        for source in sources.iter_mut() {
+            file_schema.add_file(source)?;
+        }
+        let devices = &file_schema.devices;
+
+        let mut writer = TsFileWriter::new_from_writer(file_schema.schema()?, out, self.config)?;
+        let mut layouts: Vec<Option<Vec<ChunkGroupLayout>>> = vec![None; sources.len()];
+        for (device, device_schema) in devices {
+            if let Some(order) = self.copy_order(&mut sources, device)? {
+                for index in order {
+                    if layouts[index].is_none() {
+                        layouts[index] = Some(sources[index].chunk_groups()?);
+                    }
+                    let chunk_groups = layouts[index].as_deref().unwrap_or_default();
+                    copy_chunk_groups(&mut sources[index], chunk_groups, device, &mut writer)?;
+                }
+                continue;
+            }
+
+            let mut rows: BTreeMap<i64, BTreeMap<&str, IoTDBValue>> = BTreeMap::new();
+            for source in sources.iter_mut() {
+                for measurement in device_schema.measurements.keys() {
+                    for point in source.query(device, measurement)? {
+                        let (timestamp, value) = point?;
+                        let row = rows.entry(timestamp).or_default();
+                        if row.contains_key(measurement.as_str()) {
+                            match self.conflict_policy {
+                                ConflictPolicy::KeepFirst => continue,
+                                ConflictPolicy::KeepLast => {}
+                                ConflictPolicy::Error => {
+                                    return Err(TsFileError::IllegalState {
+                                        source: Some(format!(
+                                            "Duplicate timestamp {} for {}.{}",
+                                            timestamp, device, measurement
+                                        )),
+                                    })
+                                }
+                            }
+                        }
+                        row.insert(measurement, value);
+                    }
+                }
+            }
+            for (timestamp, row) in rows {
+                writer.write_many(
+                    device,
+                    timestamp,
+                    row.into_iter()
+                        .map(|(measurement, value)| DataPoint::new(measurement, value)),
+                )?;
+            }
+            // Only one device is kept in memory
+            writer.flush()?;
+        }
+        writer.close()?;
+        Ok(writer.into_inner())
+    }
+}
+
+impl TsFileMerger {
+    /// Order of the sources that contain the device by time, `None` if chunks cannot be copied
+    fn copy_order<R: Read + Seek>(
+        &self,
+        sources: &mut [TsFileReader<R>],
+        device: &str,
+    ) -> Result<Option<Vec<usize>>, TsFileError> {
+        if !self.copy_chunks {
+            return Ok(None);
+        }
+        let mut ranges = vec![];
+        for (index, source) in sources.iter_mut().enumerate() {
+            let timeseries = source
+                .metadata_reader()
+                .get_device_timeseries_metadata(device)?;
+            let start = timeseries.iter().map(|t| t.statistics.start_time()).min();
+            let end = timeseries.iter().map(|t| t.statistics.end_time()).max();
+            if let (Some(start), Some(end)) = (start, end) {
+                ranges.push((start, end, index));
+            }
+        }
+        ranges.sort_unstable();
+        let overlapping = ranges.windows(2).any(|window| window[1].0 <= window[0].1);
+        Ok(match overlapping {
+            true => None,
+            false => Some(ranges.into_iter().map(|(_, _, index)| index).collect()),
+        })
+    }
+}
+
+/// Copies all chunk groups of the device, the statistics are taken from the chunk metadata
+pub(crate) fn copy_chunk_groups<'a, R: Read + Seek, T: PositionedWrite>(
+    source: &mut TsFileReader<R>,
+    chunk_groups: &[ChunkGroupLayout],
+    device: &'a str,
+    writer: &mut TsFileWriter<'a, T>,
+) -> Result<(), TsFileError> {
+    let chunk_metadata: HashMap<i64, ChunkMetadata> = source
+        .metadata_reader()
+        .get_device_timeseries_metadata(device)?
+        .into_iter()
+        .flat_map(|timeseries| timeseries.chunk_metadata_list)
+        .map(|chunk| (chunk.offset_of_chunk_header, chunk))
+        .collect();
+    for chunk_group in chunk_groups.iter().filter(|c| c.device == device) {
+        let mut chunks = vec![];
+        for chunk in &chunk_group.chunks {
+            let statistics = match chunk_metadata.get(&chunk.offset) {
+                Some(metadata) => metadata.statistics.clone(),
+                None => {
+                    return Err(TsFileError::IllegalState {
+                        source: Some(format!("No chunk metadata for chunk at {}", chunk.offset)),
+                    })
+                }
+            };
+            chunks.push(RawChunk {
+                measurement_id: chunk.header.measurement_id.clone(),
+                data_type: chunk.header.data_type,
+                statistics,
+                mask: chunk.header.chunk_type & (TIME_SERIES_MASK | VALUE_SERIES_MASK),
+                bytes: source
+                    .metadata_reader()
+                    .read_bytes(chunk.offset, chunk.offset + chunk.size)?,
+            });
+        }
+        writer.write_raw_chunk_group(device, chunks)?;
+    }
+    Ok(())
+}
+
+impl Default for TsFileMerger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::merge::{ConflictPolicy, TsFileMerger};
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    /// Points of d1.s1 with the given timestamps and values, d2.s1 in the second file only
+    fn write(timestamps: std::ops::Range<i64>, value: i64, aligned: bool) -> Vec<u8> {
+        let mut d1 = match aligned {
+            true => DeviceBuilder::aligned(),
+            false => DeviceBuilder::new(),
+        };
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                d1.add(
+                    "s1",
+                    TSDataType::INT64,
+                    TSEncoding::TS2DIFF,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        for i in timestamps {
+            writer
+                .write("d1", "s1", i, IoTDBValue::LONG(value))
+                .unwrap();
+            if value == 2 {
+                writer
+                    .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                    .unwrap();
+            }
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn merge(merger: &TsFileMerger, aligned: bool) -> Result<Vec<u8>, TsFileError> {
+        merge_ranges(merger, 0..10, 5..15, aligned)
+    }
+
+    fn merge_ranges(
+        merger: &TsFileMerger,
+        first: std::ops::Range<i64>,
+        second: std::ops::Range<i64>,
+        aligned: bool,
+    ) -> Result<Vec<u8>, TsFileError> {
+        let sources = vec![write(first, 1, aligned), write(second, 2, aligned)]
+            .into_iter()
+            .map(|file| TsFileReader::new(Cursor::new(file)))
+            .collect::<Result<Vec<_>, _>>()?;
+        merger.merge(sources, vec![])
+    }
+
+    fn query(tsfile: Vec<u8>, device: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(Cursor::new(tsfile)).unwrap();
+        reader
+            .query(device, "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_files() -> Result<(), TsFileError> {
+        for aligned in [false, true] {
+            let merged = merge(&TsFileMerger::new(), aligned)?;
+            let d1 = query(merged.clone(), "d1");
+            assert_eq!(d1.len(), 15);
+            assert_eq!(d1[4], (4, IoTDBValue::LONG(1)));
+            assert_eq!(d1[5], (5, IoTDBValue::LONG(2)));
+            assert_eq!(query(merged, "d2").len(), 10);
+
+            let merged = merge(
+                TsFileMerger::new().conflict_policy(ConflictPolicy::KeepFirst),
+                aligned,
+            )?;
+            let d1 = query(merged, "d1");
+            assert_eq!(d1[9], (9, IoTDBValue::LONG(1)));
+            assert_eq!(d1[10], (10, IoTDBValue::LONG(2)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn copy_chunks() -> Result<(), TsFileError> {
+        for aligned in [false, true] {
+            // The second file is older
+            let merged = merge_ranges(&TsFileMerger::new(), 10..20, 0..10, aligned)?;
+            let mut reader = TsFileReader::new(Cursor::new(merged.clone()))?;
+            let devices: Vec<String> = reader
+                .chunk_groups()?
+                .into_iter()
+                .map(|chunk_group| chunk_group.device)
+                .collect();
+            assert_eq!(devices, vec!["d1", "d1", "d2"]);
+            let d1 = query(merged, "d1");
+            assert_eq!(d1.len(), 20);
+            assert_eq!(d1[0], (0, IoTDBValue::LONG(2)));
+            assert_eq!(d1[19], (19, IoTDBValue::LONG(1)));
+
+            // Re-encoded into a single chunk group per device
+            let merged = merge_ranges(
+                TsFileMerger::new().copy_chunks(false),
+                10..20,
+                0..10,
+                aligned,
+            )?;
+            let mut reader = TsFileReader::new(Cursor::new(merged.clone()))?;
+            assert_eq!(reader.chunk_groups()?.len(), 2);
+            assert_eq!(query(merged, "d1").len(), 20);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts() {
+        assert!(matches!(
+            merge(
+                TsFileMerger::new().conflict_policy(ConflictPolicy::Error),
+                false
+            ),
+            Err(TsFileError::IllegalState { .. })
+        ));
+
+        let sources = vec![write(0..10, 1, false), write(0..10, 2, true)]
+            .into_iter()
+            .map(|file| TsFileReader::new(Cursor::new(file)).unwrap())
+            .collect();
+        assert!(matches!(
+            TsFileMerger::new().merge(sources, vec![]),
+            Err(TsFileError::SchemaError { .. })
+        ));
+    }
+}
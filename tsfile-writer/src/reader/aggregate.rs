@@ -0,0 +1,361 @@
+//! Aggregations (count, min, max, sum, avg) of a timeseries over a time range, like the
+//! aggregation engine of IoTDB. Chunks and pages that are completely within the range are
+//! answered from their statistics, only partially covered ones (or ones with deletions) are
+//! decoded.
+//!
+//! ```
+//! use tsfile_writer::reader::tsfile_reader::TsFileReader;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//!
+//! let schema = Schema::simple(
+//!     "d1",
+//!     "s1",
+//!     TSDataType::INT64,
+//!     TSEncoding::PLAIN,
+//!     CompressionType::UNCOMPRESSED,
+//! );
+//! let mut writer = TsFileWriter::new("target/aggregate_doc.tsfile", schema, Default::default()).unwrap();
+//! for i in 0..10 {
+//!     writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+//! }
+//! writer.close().unwrap();
+//!
+//! let mut reader = TsFileReader::open("target/aggregate_doc.tsfile").unwrap();
+//! let aggregation = reader.aggregate("d1", "s1", 2, 5).unwrap();
+//! assert_eq!(aggregation.count, 4);
+//! assert_eq!(aggregation.max_value, Some(IoTDBValue::LONG(5)));
+//! assert_eq!(aggregation.avg(), Some(3.5));
+//! ```
+use crate::reader::chunk_reader::{zip_pages, ChunkReader, PageReader};
+use crate::reader::query::TimeRange;
+use crate::reader::tsfile_reader::{time_chunk_for, TsFileReader};
+use crate::writer::errors::TsFileError;
+use crate::writer::modification::Deletion;
+use crate::writer::statistics::Statistics;
+use crate::writer::IoTDBValue;
+use std::io::{Read, Seek};
+
+/// Aggregated values of the points of a series within a time range
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregation {
+    pub count: u64,
+    /// Not available for TEXT or without points
+    pub min_value: Option<IoTDBValue>,
+    /// Not available for TEXT or without points
+    pub max_value: Option<IoTDBValue>,
+    /// LONG for INT32 and DOUBLE otherwise, see `Statistics::sum_value`. Not available for TEXT.
+    pub sum: Option<IoTDBValue>,
+    /// Number of pages that were decoded, all other data was answered from statistics
+    pub decoded_pages: usize,
+}
+
+impl Aggregation {
+    fn new(statistics: &Statistics, decoded_pages: usize) -> Aggregation {
+        let count = statistics.count() as u64;
+        Aggregation {
+            count,
+            min_value: statistics.min_value().filter(|_| count > 0),
+            max_value: statistics.max_value().filter(|_| count > 0),
+            sum: statistics.sum_value(),
+            decoded_pages,
+        }
+    }
+
+    /// Mean of the values, not available for TEXT or without points
+    pub fn avg(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let sum = match self.sum {
+            Some(IoTDBValue::LONG(sum)) => sum as f64,
+            Some(IoTDBValue::DOUBLE(sum)) => sum,
+            _ => return None,
+        };
+        Some(sum / self.count as f64)
+    }
+}
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Aggregates the points of the series with `start <= timestamp <= end`, deleted points
+    /// are not counted. The aggregation is empty if the series does not exist.
+    pub fn aggregate(
+        &mut self,
+        device: &str,
+        measurement: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<Aggregation, TsFileError> {
+        let time_range = TimeRange::new(start, end);
+        let timeseries_metadata = match self.get_timeseries_metadata(device, measurement)? {
+            Some(timeseries_metadata) => timeseries_metadata,
+            None => {
+                return Ok(Aggregation {
+                    count: 0,
+                    min_value: None,
+                    max_value: None,
+                    sum: None,
+                    decoded_pages: 0,
+                })
+            }
+        };
+        let time_chunks = self.time_chunks(device, &timeseries_metadata)?;
+        let deletions: Vec<Deletion> = self
+            .deletions()
+            .iter()
+            .filter(|deletion| deletion.matches(device, measurement))
+            .cloned()
+            .collect();
+
+        let mut result = Statistics::new(timeseries_metadata.data_type);
+        let mut decoded_pages = 0;
+        for chunk_metadata in &timeseries_metadata.chunk_metadata_list {
+            let statistics = &chunk_metadata.statistics;
+            if !time_range.overlaps(statistics) {
+                continue;
+            }
+            let chunk_deletions: Vec<&Deletion> = deletions
+                .iter()
+                .filter(|deletion| deletion.applies_to(chunk_metadata.offset_of_chunk_header))
+                .collect();
+            if is_covered(&time_range, &chunk_deletions, statistics) {
+                result.merge(statistics)?;
+                continue;
+            }
+
+            let chunk = self.read_chunk(chunk_metadata)?;
+            let pages: Vec<(Option<PageReader>, PageReader)> =
+                match time_chunk_for(&time_chunks, chunk_metadata) {
+                    Some(time_offset) => {
+                        let reader = &mut self.metadata_reader().reader;
+                        let time_chunk = ChunkReader::read(reader, time_offset)?;
+                        zip_pages(time_chunk.pages()?, chunk.pages()?)?
+                            .into_iter()
+                            .map(|(time_page, page)| (Some(time_page), page))
+                            .collect()
+                    }
+                    None => chunk
+                        .pages()?
+                        .into_iter()
+                        .map(|page| (None, page))
+                        .collect(),
+                };
+            for (time_page, page) in pages {
+                // Pages without statistics are the only page of the chunk
+                match &page.header().statistics {
+                    Some(statistics) if !time_range.overlaps(statistics) => continue,
+                    Some(statistics) if is_covered(&time_range, &chunk_deletions, statistics) => {
+                        result.merge(statistics)?;
+                        continue;
+                    }
+                    _ => {}
+                }
+                let points = match time_page {
+                    Some(time_page) => page.read_aligned(&time_page)?,
+                    None => page.read_all()?,
+                };
+                decoded_pages += 1;
+                for (timestamp, value) in &points {
+                    if time_range.contains(*timestamp)
+                        && !chunk_deletions
+                            .iter()
+                            .any(|deletion| deletion.covers(*timestamp))
+                    {
+                        result.update(*timestamp, value)?;
+                    }
+                }
+            }
+        }
+        Ok(Aggregation::new(&result, decoded_pages))
+    }
+}
+
+/// Whether all points described by the statistics are within the range and none is deleted
+fn is_covered(time_range: &TimeRange, deletions: &[&Deletion], statistics: &Statistics) -> bool {
+    time_range.contains(statistics.start_time())
+        && time_range.contains(statistics.end_time())
+        && !deletions.iter().any(|deletion| {
+            deletion.start_time <= statistics.end_time()
+                && deletion.end_time >= statistics.start_time()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::modification::Deletion;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    fn write() -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        // Chunks of 50 points with pages of 10 points
+        let config = TsFileConfig::default().max_number_of_points_in_page(10);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config).unwrap();
+        for i in 0..100 {
+            writer
+                .write("d1", "s1", i, IoTDBValue::INT(i as i32))
+                .unwrap();
+            writer
+                .write("d1", "s2", i, IoTDBValue::TEXT(i.to_string()))
+                .unwrap();
+            let mut values = vec![DataPoint::new("s1", IoTDBValue::DOUBLE(i as f64 / 2.0))];
+            if i % 2 == 1 {
+                values.push(DataPoint::new("s2", IoTDBValue::LONG(i)));
+            }
+            writer.write_many("d2", i, values).unwrap();
+            if i == 49 {
+                writer.flush().unwrap();
+            }
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn reader() -> TsFileReader<Cursor<Vec<u8>>> {
+        TsFileReader::new(Cursor::new(write())).unwrap()
+    }
+
+    #[test]
+    fn aggregate() -> Result<(), TsFileError> {
+        let mut reader = reader();
+
+        // Everything from the chunk statistics
+        let all = reader.aggregate("d1", "s1", i64::MIN, i64::MAX)?;
+        assert_eq!(all.count, 100);
+        assert_eq!(all.min_value, Some(IoTDBValue::INT(0)));
+        assert_eq!(all.max_value, Some(IoTDBValue::INT(99)));
+        assert_eq!(all.sum, Some(IoTDBValue::LONG(4950)));
+        assert_eq!(all.avg(), Some(49.5));
+        assert_eq!(all.decoded_pages, 0);
+
+        // Only the pages at the borders of the range are decoded
+        let range = reader.aggregate("d1", "s1", 15, 84)?;
+        assert_eq!(range.count, 70);
+        assert_eq!(range.min_value, Some(IoTDBValue::INT(15)));
+        assert_eq!(range.max_value, Some(IoTDBValue::INT(84)));
+        assert_eq!(range.sum, Some(IoTDBValue::LONG((15..=84).sum())));
+        assert_eq!(range.decoded_pages, 2);
+
+        let text = reader.aggregate("d1", "s2", 15, 84)?;
+        assert_eq!(
+            (text.count, text.max_value.clone(), text.avg()),
+            (70, None, None)
+        );
+
+        // Aligned series with null values
+        let aligned = reader.aggregate("d2", "s2", 10, 59)?;
+        assert_eq!(aligned.count, 25);
+        assert_eq!(aligned.min_value, Some(IoTDBValue::LONG(11)));
+        assert_eq!(aligned.avg(), Some(35.0));
+        assert_eq!(
+            reader.aggregate("d2", "s1", 0, 99)?.sum,
+            Some(IoTDBValue::DOUBLE(2475.0))
+        );
+
+        let empty = reader.aggregate("d1", "s1", 200, 300)?;
+        assert_eq!(
+            (empty.count, empty.min_value.clone(), empty.avg()),
+            (0, None, None)
+        );
+        assert_eq!(reader.aggregate("d3", "s1", 0, 10)?.count, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn aggregate_with_deletions() -> Result<(), TsFileError> {
+        let mut reader = reader();
+        reader.set_deletions(vec![Deletion::new("d1.s1", i64::MAX, 90, 200)]);
+        let aggregation = reader.aggregate("d1", "s1", 0, 99)?;
+        assert_eq!(aggregation.count, 90);
+        assert_eq!(aggregation.max_value, Some(IoTDBValue::INT(89)));
+        assert_eq!(aggregation.decoded_pages, 1);
+        Ok(())
+    }
+
+    /// Aggregates all series of the file, returns the first error
+    fn aggregate_all(buffer: Vec<u8>) -> Result<(), TsFileError> {
+        let mut reader = TsFileReader::new(Cursor::new(buffer))?;
+        for (device, measurement) in [("d1", "s1"), ("d1", "s2"), ("d2", "s1"), ("d2", "s2")] {
+            reader.aggregate(device, measurement, i64::MIN, i64::MAX)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn aggregate_corrupt_statistics() {
+        let buffer = write();
+        let replace = |buffer: &mut Vec<u8>, from: i64, to: i64| {
+            let from = from.to_be_bytes();
+            let position = buffer
+                .windows(from.len())
+                .position(|window| window == from)
+                .unwrap();
+            buffer[position..position + 8].copy_from_slice(&to.to_be_bytes());
+        };
+        // The sums of the chunk metadata of d1.s1 add up to more than i64::MAX
+        let mut corrupt = buffer.clone();
+        replace(&mut corrupt, (0..50).sum(), i64::MAX);
+        replace(&mut corrupt, (50..100).sum(), i64::MAX);
+        assert!(matches!(
+            aggregate_all(corrupt),
+            Err(TsFileError::Corrupt { .. })
+        ));
+
+        // Damaged footers are either read or fail, but never panic
+        let meta_offset = TsFileReader::new(Cursor::new(buffer.clone()))
+            .unwrap()
+            .metadata()
+            .meta_offset as usize;
+        for position in meta_offset..buffer.len() {
+            for byte in [0x00, 0x7F, 0xFF] {
+                let mut corrupt = buffer.clone();
+                corrupt[position] = byte;
+                let _ = aggregate_all(corrupt);
+            }
+        }
+    }
+}
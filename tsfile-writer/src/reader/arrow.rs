@@ -0,0 +1,237 @@
+//! Query results as Arrow `RecordBatch`es (feature `arrow`).
+//!
+//! The batch has a `time` column (`Timestamp(Millisecond)`, the default precision of IoTDB)
+//! and one nullable column per measurement. Rows are the union of the timestamps of all
+//! queried measurements, measurements without a value at a timestamp are null.
+use std::collections::BTreeSet;
+use std::io::{Read, Seek};
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, Float32Array, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    TimestampMillisecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+use crate::reader::query::TimeRange;
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::writer::errors::TsFileError;
+use crate::writer::{IoTDBValue, TSDataType};
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Queries the given measurements of a device as a single `RecordBatch`
+    pub fn query_record_batch(
+        &mut self,
+        device: &str,
+        measurements: &[&str],
+    ) -> Result<RecordBatch, TsFileError> {
+        self.record_batch(device, measurements, Default::default())
+    }
+
+    pub(crate) fn record_batch(
+        &mut self,
+        device: &str,
+        measurements: &[&str],
+        time_range: TimeRange,
+    ) -> Result<RecordBatch, TsFileError> {
+        let mut series = Vec::with_capacity(measurements.len());
+        for &measurement in measurements {
+            let data_type = match self.get_timeseries_metadata(device, measurement)? {
+                Some(metadata) => metadata.data_type,
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some(format!("Unknown timeseries {}.{}", device, measurement)),
+                    });
+                }
+            };
+            let points = self
+                .series_iterator(device, measurement, time_range, None)?
+                .collect::<Result<Vec<_>, _>>()?;
+            series.push((measurement, data_type, points));
+        }
+
+        let timestamps: Vec<i64> = series
+            .iter()
+            .flat_map(|(_, _, points)| points.iter().map(|(timestamp, _)| *timestamp))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let schema = batch_schema(
+            &series
+                .iter()
+                .map(|(measurement, data_type, _)| (*measurement, *data_type))
+                .collect::<Vec<_>>(),
+        )?;
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(TimestampMillisecondArray::from(
+            timestamps.clone(),
+        ))];
+        for (_, data_type, points) in series {
+            columns.push(to_array(data_type, align(&timestamps, points)));
+        }
+
+        RecordBatch::try_new(Arc::new(schema), columns).map_err(|e| TsFileError::Error {
+            source: Some(e.to_string()),
+        })
+    }
+}
+
+/// One value per timestamp, `None` if the series has no point at this timestamp
+fn align(timestamps: &[i64], points: Vec<(i64, IoTDBValue)>) -> Vec<Option<IoTDBValue>> {
+    let mut points = points.into_iter().peekable();
+    timestamps
+        .iter()
+        .map(|&timestamp| match points.peek() {
+            Some((time, _)) if *time == timestamp => points.next().map(|(_, value)| value),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Arrow type of the values of a timeseries
+pub(crate) fn arrow_type(data_type: TSDataType) -> Result<DataType, TsFileError> {
+    match data_type {
+        TSDataType::INT32 => Ok(DataType::Int32),
+        TSDataType::INT64 => Ok(DataType::Int64),
+        TSDataType::FLOAT => Ok(DataType::Float32),
+        TSDataType::DOUBLE => Ok(DataType::Float64),
+        TSDataType::TEXT => Ok(DataType::Utf8),
+        TSDataType::VECTOR => Err(TsFileError::UnsupportedFeature {
+            source: Some("VECTOR is not a value type".to_owned()),
+        }),
+    }
+}
+
+/// Schema of a batch with the time column and the given measurements
+pub(crate) fn batch_schema(measurements: &[(&str, TSDataType)]) -> Result<Schema, TsFileError> {
+    let mut fields = vec![Field::new(
+        "time",
+        DataType::Timestamp(TimeUnit::Millisecond, None),
+        false,
+    )];
+    for (measurement, data_type) in measurements {
+        fields.push(Field::new(*measurement, arrow_type(*data_type)?, true));
+    }
+    Ok(Schema::new(fields))
+}
+
+/// Values of the type of `arrow_type`, the data type is checked by `batch_schema` before
+fn to_array(data_type: TSDataType, values: Vec<Option<IoTDBValue>>) -> ArrayRef {
+    let values = values.into_iter();
+    match data_type {
+        TSDataType::INT32 => Arc::new(Int32Array::from_iter(values.map(|v| match v {
+            Some(IoTDBValue::INT(v)) => Some(v),
+            _ => None,
+        }))),
+        TSDataType::FLOAT => Arc::new(Float32Array::from_iter(values.map(|v| match v {
+            Some(IoTDBValue::FLOAT(v)) => Some(v),
+            _ => None,
+        }))),
+        TSDataType::DOUBLE => Arc::new(Float64Array::from_iter(values.map(|v| match v {
+            Some(IoTDBValue::DOUBLE(v)) => Some(v),
+            _ => None,
+        }))),
+        TSDataType::TEXT => Arc::new(StringArray::from_iter(values.map(|v| match v {
+            Some(IoTDBValue::TEXT(v)) => Some(v),
+            _ => None,
+        }))),
+        _ => Arc::new(Int64Array::from_iter(values.map(|v| match v {
+            Some(IoTDBValue::LONG(v)) => Some(v),
+            _ => None,
+        }))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, Int64Type, TimestampMillisecondType};
+    use arrow_array::Array;
+
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn write(aligned: bool) -> Vec<u8> {
+        let mut device = if aligned {
+            DeviceBuilder::aligned()
+        } else {
+            DeviceBuilder::new()
+        };
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                device
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        writer
+            .write_many(
+                "d1",
+                1,
+                vec![
+                    DataPoint::new("s1", IoTDBValue::LONG(1)),
+                    DataPoint::new("s2", IoTDBValue::DOUBLE(1.0)),
+                ],
+            )
+            .unwrap();
+        writer
+            .write_many("d1", 2, vec![DataPoint::new("s1", IoTDBValue::LONG(2))])
+            .unwrap();
+        writer
+            .write_many("d1", 3, vec![DataPoint::new("s2", IoTDBValue::DOUBLE(3.0))])
+            .unwrap();
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn query_record_batch() -> Result<(), TsFileError> {
+        for aligned in [false, true] {
+            let mut reader = TsFileReader::new(Cursor::new(write(aligned)))?;
+            let batch = reader.query_record_batch("d1", &["s1", "s2"])?;
+
+            assert_eq!(batch.num_rows(), 3);
+            let time = batch.column(0).as_primitive::<TimestampMillisecondType>();
+            assert_eq!(time.values().to_vec(), vec![1, 2, 3]);
+
+            let s1 = batch.column(1).as_primitive::<Int64Type>();
+            assert_eq!(s1.iter().collect::<Vec<_>>(), vec![Some(1), Some(2), None]);
+            let s2 = batch.column(2).as_primitive::<Float64Type>();
+            assert_eq!(
+                s2.iter().collect::<Vec<_>>(),
+                vec![Some(1.0), None, Some(3.0)]
+            );
+            assert_eq!(s2.null_count(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_timeseries() {
+        let mut reader = TsFileReader::new(Cursor::new(write(false))).unwrap();
+        assert!(matches!(
+            reader.query_record_batch("d1", &["s3"]),
+            Err(TsFileError::SchemaError { .. })
+        ));
+    }
+}
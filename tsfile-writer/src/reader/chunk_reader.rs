@@ -0,0 +1,303 @@
+//! Low level readers for single chunks and pages.
+//!
+//! These are the building blocks of the `TsFileReader` and can be used to implement
+//! custom scan strategies, e.g. skipping pages based on their statistics.
+use crate::reader::decoding::time_decoder::decode_timestamps;
+use crate::reader::decoding::{unexpected_end, Decoder};
+use crate::reader::read_data_type;
+use crate::writer::compression::CompressionType;
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use crate::writer::utils::{read_byte, read_bytes, read_str, read_var_u32};
+use crate::writer::{IoTDBValue, TSDataType, CHUNK_HEADER, TIME_SERIES_MASK, VALUE_SERIES_MASK};
+use std::io::{Read, Seek, SeekFrom};
+
+#[derive(Clone, Debug)]
+pub struct ChunkHeader {
+    pub chunk_type: u8,
+    pub measurement_id: String,
+    pub data_size: u32,
+    pub data_type: TSDataType,
+    pub compression: CompressionType,
+    pub encoding: TSEncoding,
+}
+
+impl ChunkHeader {
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<ChunkHeader, TsFileError> {
+        let chunk_type = read_byte(reader)?;
+        let measurement_id = read_str(reader)?;
+        let data_size = read_var_u32(reader)?;
+        let data_type = read_data_type(reader)?;
+        let compression = read_byte(reader)?;
+        let compression =
+            CompressionType::try_from(compression).map_err(|_| TsFileError::Corrupt {
+                source: Some(format!("Unknown compression type {}", compression)),
+            })?;
+        let encoding = read_byte(reader)?;
+        let encoding = TSEncoding::try_from(encoding).map_err(|_| TsFileError::Corrupt {
+            source: Some(format!("Unknown encoding {}", encoding)),
+        })?;
+        Ok(ChunkHeader {
+            chunk_type,
+            measurement_id,
+            data_size,
+            data_type,
+            compression,
+            encoding,
+        })
+    }
+
+    /// Page headers only contain statistics if the chunk has more than one page
+    pub fn has_page_statistics(&self) -> bool {
+        self.chunk_type & 0x3F == CHUNK_HEADER
+    }
+
+    /// Time chunk of an aligned device, contains only timestamps
+    pub fn is_time_chunk(&self) -> bool {
+        self.chunk_type & TIME_SERIES_MASK != 0
+    }
+
+    /// Value chunk of an aligned device, its timestamps are stored in the time chunk
+    pub fn is_value_chunk(&self) -> bool {
+        self.chunk_type & VALUE_SERIES_MASK != 0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PageHeader {
+    pub uncompressed_size: u32,
+    pub compressed_size: u32,
+    /// Only available if the chunk has more than one page, otherwise
+    /// the statistics of the chunk apply
+    pub statistics: Option<Statistics>,
+}
+
+/// Reads a single chunk, i.e. its header and all of its pages
+pub struct ChunkReader {
+    header: ChunkHeader,
+    data: Vec<u8>,
+}
+
+impl ChunkReader {
+    /// Reads the chunk starting at the given offset, see `ChunkMetadata::offset_of_chunk_header`
+    pub fn read<R: Read + Seek>(
+        reader: &mut R,
+        offset_of_chunk_header: i64,
+    ) -> Result<ChunkReader, TsFileError> {
+        if offset_of_chunk_header < 0 {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!("Invalid chunk offset {}", offset_of_chunk_header)),
+            });
+        }
+        reader.seek(SeekFrom::Start(offset_of_chunk_header as u64))?;
+        let header = ChunkHeader::deserialize(reader)?;
+        let data = read_bytes(reader, header.data_size as usize)?;
+        Ok(ChunkReader { header, data })
+    }
+
+    pub fn header(&self) -> &ChunkHeader {
+        &self.header
+    }
+
+    /// Splits the chunk into its pages, page data is only decompressed and decoded on read
+    pub fn pages(&self) -> Result<Vec<PageReader>, TsFileError> {
+        let mut buffer = self.data.as_slice();
+        let mut pages = vec![];
+        while !buffer.is_empty() {
+            let uncompressed_size = read_var_u32(&mut buffer)?;
+            let compressed_size = read_var_u32(&mut buffer)?;
+            let statistics = match self.header.has_page_statistics() {
+                true => Some(Statistics::deserialize(self.header.data_type, &mut buffer)?),
+                false => None,
+            };
+            if buffer.len() < compressed_size as usize {
+                return Err(unexpected_end());
+            }
+            let (page_data, remainder) = buffer.split_at(compressed_size as usize);
+            buffer = remainder;
+            pages.push(PageReader {
+                header: PageHeader {
+                    uncompressed_size,
+                    compressed_size,
+                    statistics,
+                },
+                data: page_data.to_vec(),
+                chunk_type: self.header.chunk_type,
+                compression: self.header.compression,
+                data_type: self.header.data_type,
+                encoding: self.header.encoding,
+                // Only time chunks of aligned devices record their encoding
+                time_encoding: match self.header.is_time_chunk() {
+                    true => self.header.encoding,
+                    false => TSEncoding::TS2DIFF,
+                },
+            });
+        }
+        Ok(pages)
+    }
+
+    /// Decodes all points of all pages
+    pub fn read_all(&self) -> Result<Vec<(i64, IoTDBValue)>, TsFileError> {
+        let mut points = vec![];
+        for page in self.pages()? {
+            points.append(&mut page.read_all()?);
+        }
+        Ok(points)
+    }
+
+    /// Decodes all non null points of a value chunk of an aligned device together with
+    /// the time chunk of the same chunk group
+    pub fn read_aligned(
+        &self,
+        time_chunk: &ChunkReader,
+    ) -> Result<Vec<(i64, IoTDBValue)>, TsFileError> {
+        let mut points = vec![];
+        for (time_page, page) in zip_pages(time_chunk.pages()?, self.pages()?)? {
+            points.append(&mut page.read_aligned(&time_page)?);
+        }
+        Ok(points)
+    }
+}
+
+/// Pairs the pages of a time chunk with those of a value chunk, both contain the same rows
+pub(crate) fn zip_pages(
+    time_pages: Vec<PageReader>,
+    value_pages: Vec<PageReader>,
+) -> Result<Vec<(PageReader, PageReader)>, TsFileError> {
+    if time_pages.len() != value_pages.len() {
+        return Err(TsFileError::Corrupt {
+            source: Some(format!(
+                "Time chunk contains {} pages but value chunk {} pages",
+                time_pages.len(),
+                value_pages.len()
+            )),
+        });
+    }
+    Ok(time_pages.into_iter().zip(value_pages).collect())
+}
+
+/// Decompresses and decodes a single page
+pub struct PageReader {
+    header: PageHeader,
+    data: Vec<u8>,
+    chunk_type: u8,
+    compression: CompressionType,
+    data_type: TSDataType,
+    encoding: TSEncoding,
+    time_encoding: TSEncoding,
+}
+
+impl PageReader {
+    pub fn header(&self) -> &PageHeader {
+        &self.header
+    }
+
+    /// Decodes only the time column of the page, not available for value pages of aligned
+    /// devices
+    pub fn timestamps(&self) -> Result<Vec<i64>, TsFileError> {
+        let data = self.uncompressed_data()?;
+        if self.chunk_type & TIME_SERIES_MASK != 0 {
+            return decode_timestamps(self.time_encoding, &data);
+        }
+        if self.chunk_type & VALUE_SERIES_MASK != 0 {
+            return Err(aligned_page_error());
+        }
+        let (time_buffer, _) = split_columns(&data)?;
+        decode_timestamps(self.time_encoding, time_buffer)
+    }
+
+    fn uncompressed_data(&self) -> Result<Vec<u8>, TsFileError> {
+        self.compression
+            .decompress(&self.data, self.header.uncompressed_size as usize)
+    }
+
+    /// Decodes all points of the page, pages of aligned devices have to be read
+    /// with `read_aligned`
+    pub fn read_all(&self) -> Result<Vec<(i64, IoTDBValue)>, TsFileError> {
+        if self.chunk_type & (TIME_SERIES_MASK | VALUE_SERIES_MASK) != 0 {
+            return Err(aligned_page_error());
+        }
+        let data = self.uncompressed_data()?;
+        let (time_buffer, value_buffer) = split_columns(&data)?;
+
+        let timestamps = decode_timestamps(self.time_encoding, time_buffer)?;
+        let values = <dyn Decoder>::new(self.data_type, self.encoding)?.decode(value_buffer)?;
+        if timestamps.len() != values.len() {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!(
+                    "Page contains {} timestamps but {} values",
+                    timestamps.len(),
+                    values.len()
+                )),
+            });
+        }
+        Ok(timestamps.into_iter().zip(values).collect())
+    }
+
+    /// Decodes a value page of an aligned device with the matching page of the time chunk,
+    /// null values are skipped
+    pub fn read_aligned(
+        &self,
+        time_page: &PageReader,
+    ) -> Result<Vec<(i64, IoTDBValue)>, TsFileError> {
+        if self.chunk_type & VALUE_SERIES_MASK == 0 {
+            return Err(TsFileError::Error {
+                source: Some("Page is not a value page of an aligned device".to_owned()),
+            });
+        }
+        let timestamps = time_page.timestamps()?;
+        let data = self.uncompressed_data()?;
+
+        // Header: number of rows and a bitmap of the non null rows
+        if data.len() < 4 {
+            return Err(unexpected_end());
+        }
+        let row_count = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if row_count != timestamps.len() {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!(
+                    "Page contains {} timestamps but {} rows",
+                    timestamps.len(),
+                    row_count
+                )),
+            });
+        }
+        // One bit per row, the last byte may be partially used
+        let bitmap_size = (row_count + 7) >> 3;
+        if data.len() < 4 + bitmap_size {
+            return Err(unexpected_end());
+        }
+        let (bitmap, value_buffer) = data[4..].split_at(bitmap_size);
+
+        let mut values = <dyn Decoder>::new(self.data_type, self.encoding)?
+            .decode(value_buffer)?
+            .into_iter();
+        let mut points = vec![];
+        for (row, timestamp) in timestamps.into_iter().enumerate() {
+            if bitmap[row / 8] & (0x80 >> (row % 8)) == 0 {
+                continue;
+            }
+            match values.next() {
+                Some(value) => points.push((timestamp, value)),
+                None => return Err(unexpected_end()),
+            }
+        }
+        Ok(points)
+    }
+}
+
+fn aligned_page_error() -> TsFileError {
+    TsFileError::Error {
+        source: Some("Pages of aligned devices have to be read with read_aligned".to_owned()),
+    }
+}
+
+/// Splits uncompressed page data into the time and the value column
+fn split_columns(mut buffer: &[u8]) -> Result<(&[u8], &[u8]), TsFileError> {
+    let time_buffer_size = read_var_u32(&mut buffer)? as usize;
+    if buffer.len() < time_buffer_size {
+        return Err(unexpected_end());
+    }
+    Ok(buffer.split_at(time_buffer_size))
+}
@@ -0,0 +1,82 @@
+use crate::reader::decoding::time_decoder::{IntTs2DiffDecoder, LongTs2DiffDecoder};
+use crate::reader::decoding::Decoder;
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::read_var_u32;
+use crate::writer::{IoTDBValue, TSDataType};
+
+/// Decoder of `crate::writer::encoding::float::FloatEncoder`, the number of decimal places is
+/// read from the start of the page
+pub struct FloatDecoder {
+    data_type: TSDataType,
+}
+
+impl FloatDecoder {
+    pub(crate) fn new(data_type: TSDataType) -> FloatDecoder {
+        Self { data_type }
+    }
+}
+
+impl Decoder for FloatDecoder {
+    fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError> {
+        if buffer.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut buffer = buffer;
+        let max_point_number = read_var_u32(&mut buffer)?;
+        let max_point_value = 10_f64.powi(max_point_number as i32);
+        match self.data_type {
+            TSDataType::FLOAT => Ok(IntTs2DiffDecoder::new()
+                .decode_values(buffer)?
+                .into_iter()
+                .map(|value| IoTDBValue::FLOAT((value as f64 / max_point_value) as f32))
+                .collect()),
+            TSDataType::DOUBLE => Ok(LongTs2DiffDecoder::new()
+                .decode_values(buffer)?
+                .into_iter()
+                .map(|value| IoTDBValue::DOUBLE(value as f64 / max_point_value))
+                .collect()),
+            _ => Err(TsFileError::Encoding),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::decoding::float::FloatDecoder;
+    use crate::reader::decoding::Decoder;
+    use crate::writer::encoding::float::FloatEncoder;
+    use crate::writer::encoding::Encoder;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn roundtrip() {
+        let mut encoder = FloatEncoder::new(TSDataType::DOUBLE, 3).unwrap();
+        for value in [1.0, 1.23456, -2.0004, 1e6] {
+            encoder.write(&IoTDBValue::DOUBLE(value)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+        assert_eq!(
+            FloatDecoder::new(TSDataType::DOUBLE)
+                .decode(&buffer)
+                .unwrap(),
+            vec![
+                IoTDBValue::DOUBLE(1.0),
+                IoTDBValue::DOUBLE(1.235),
+                IoTDBValue::DOUBLE(-2.0),
+                IoTDBValue::DOUBLE(1e6)
+            ]
+        );
+
+        let mut encoder = FloatEncoder::new(TSDataType::FLOAT, 0).unwrap();
+        encoder.write(&IoTDBValue::FLOAT(2.5)).unwrap();
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+        assert_eq!(
+            FloatDecoder::new(TSDataType::FLOAT)
+                .decode(&buffer)
+                .unwrap(),
+            vec![IoTDBValue::FLOAT(3.0)]
+        );
+    }
+}
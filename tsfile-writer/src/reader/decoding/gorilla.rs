@@ -0,0 +1,145 @@
+use crate::reader::decoding::{unexpected_end, Decoder};
+use crate::writer::errors::TsFileError;
+use crate::writer::IoTDBValue;
+
+/// Bit reader over a byte slice (most significant bit first)
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read_bit(&mut self) -> Result<bool, TsFileError> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    fn read_bits(&mut self, bits: u32) -> Result<u64, TsFileError> {
+        let mut value = 0_u64;
+        for _ in 0..bits {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or_else(unexpected_end)?;
+            let bit = (byte >> (7 - self.position % 8)) & 0x01;
+            value = (value << 1) | bit as u64;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn has_remaining(&self) -> bool {
+        self.position / 8 < self.data.len()
+    }
+}
+
+macro_rules! gorilla_decoder {
+    ( $name:ident, $type:ty, $bits:ty, $value_bits:expr, $leading_zero_bits:expr, $meaningful_xor_bits:expr, $variant:ident ) => {
+        pub struct $name {}
+
+        impl Decoder for $name {
+            fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError> {
+                let ending = <$type>::NAN.to_bits();
+                let mut reader = BitReader {
+                    data: buffer,
+                    position: 0,
+                };
+                let mut values = vec![];
+                // Each flush of the encoder starts a new stream
+                while reader.has_remaining() {
+                    let mut stored_value = reader.read_bits($value_bits)? as $bits;
+                    let mut stored_leading_zeros = 0;
+                    let mut stored_trailing_zeros = 0;
+                    while stored_value != ending {
+                        values.push(IoTDBValue::$variant(<$type>::from_bits(stored_value)));
+
+                        if !reader.read_bit()? {
+                            // Same value as before
+                            continue;
+                        }
+                        if reader.read_bit()? {
+                            stored_leading_zeros = reader.read_bits($leading_zero_bits)? as u32;
+                            let significant_bits =
+                                reader.read_bits($meaningful_xor_bits)? as u32 + 1;
+                            stored_trailing_zeros = ($value_bits as u32)
+                                .checked_sub(stored_leading_zeros + significant_bits)
+                                .ok_or(TsFileError::Corrupt {
+                                    source: Some("Invalid Gorilla window".to_owned()),
+                                })?;
+                        }
+                        let significant_bits =
+                            $value_bits - stored_leading_zeros - stored_trailing_zeros;
+                        let xor =
+                            (reader.read_bits(significant_bits)? as $bits) << stored_trailing_zeros;
+                        stored_value ^= xor;
+                    }
+                    // The encoder always writes the byte containing the end of the ending value,
+                    // if this ends exactly on a byte boundary an additional empty byte is written
+                    reader.position = (reader.position / 8 + 1) * 8;
+                }
+                Ok(values)
+            }
+        }
+
+        impl $name {
+            pub(crate) fn new() -> $name {
+                Self {}
+            }
+        }
+    };
+}
+
+gorilla_decoder!(FloatGorillaDecoder, f32, u32, 32, 5, 5, FLOAT);
+gorilla_decoder!(DoubleGorillaDecoder, f64, u64, 64, 6, 6, DOUBLE);
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::decoding::gorilla::{DoubleGorillaDecoder, FloatGorillaDecoder};
+    use crate::reader::decoding::Decoder;
+    use crate::writer::encoding::gorilla::{DoubleGorillaEncoder, FloatGorillaEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
+
+    #[test]
+    fn test_float_round_trip() {
+        let values: Vec<IoTDBValue> = [1.0, 1.0, 1.5, -3.25, 0.0, 1000.125, 1000.125]
+            .iter()
+            .map(|v| IoTDBValue::FLOAT(*v))
+            .collect();
+        let mut encoder = FloatGorillaEncoder::new();
+        for v in values.iter() {
+            encoder.write(v).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert_eq!(FloatGorillaDecoder::new().decode(&buffer).unwrap(), values);
+    }
+
+    #[test]
+    fn test_double_round_trip() {
+        let values: Vec<IoTDBValue> = (0..1000)
+            .map(|i| IoTDBValue::DOUBLE((i as f64 / 10.0).sin()))
+            .collect();
+        let mut encoder = DoubleGorillaEncoder::new();
+        for v in values.iter() {
+            encoder.write(v).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert_eq!(DoubleGorillaDecoder::new().decode(&buffer).unwrap(), values);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut encoder = FloatGorillaEncoder::new();
+        encoder.write(&IoTDBValue::FLOAT(1.0)).unwrap();
+        encoder.write(&IoTDBValue::FLOAT(2.0)).unwrap();
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert!(FloatGorillaDecoder::new()
+            .decode(&buffer[..buffer.len() - 2])
+            .is_err());
+    }
+}
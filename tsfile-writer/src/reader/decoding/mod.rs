@@ -0,0 +1,50 @@
+//! Decoders for the Encodings written by `crate::writer::encoding`
+use crate::reader::decoding::float::FloatDecoder;
+use crate::reader::decoding::gorilla::{DoubleGorillaDecoder, FloatGorillaDecoder};
+use crate::reader::decoding::plain::PlainDecoder;
+use crate::reader::decoding::time_decoder::{IntTs2DiffDecoder, LongTs2DiffDecoder};
+use crate::reader::decoding::zigzag::{IntZigzagDecoder, LongZigzagDecoder};
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::{IoTDBValue, TSDataType};
+
+pub mod float;
+pub mod gorilla;
+pub mod plain;
+pub mod time_decoder;
+pub mod zigzag;
+
+pub trait Decoder {
+    /// Decodes all values contained in the buffer
+    fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError>;
+}
+
+impl dyn Decoder {
+    pub(crate) fn new(
+        data_type: TSDataType,
+        encoding: TSEncoding,
+    ) -> Result<Box<dyn Decoder>, TsFileError> {
+        match (data_type, encoding) {
+            // The time column of aligned series is decoded separately
+            (TSDataType::VECTOR, _) => Err(TsFileError::Encoding),
+            (_, TSEncoding::PLAIN) => Ok(Box::new(PlainDecoder::new(data_type))),
+            (TSDataType::INT64, TSEncoding::TS2DIFF) => Ok(Box::new(LongTs2DiffDecoder::new())),
+            (TSDataType::INT32, TSEncoding::TS2DIFF) => Ok(Box::new(IntTs2DiffDecoder::new())),
+            (TSDataType::FLOAT | TSDataType::DOUBLE, TSEncoding::TS2DIFF) => {
+                Ok(Box::new(FloatDecoder::new(data_type)))
+            }
+            (TSDataType::FLOAT, TSEncoding::GORILLA) => Ok(Box::new(FloatGorillaDecoder::new())),
+            (TSDataType::DOUBLE, TSEncoding::GORILLA) => Ok(Box::new(DoubleGorillaDecoder::new())),
+            (TSDataType::INT32, TSEncoding::ZIGZAG) => Ok(Box::new(IntZigzagDecoder::new())),
+            (TSDataType::INT64, TSEncoding::ZIGZAG) => Ok(Box::new(LongZigzagDecoder::new())),
+            (_, _) => Err(TsFileError::Encoding),
+        }
+    }
+}
+
+/// Error for a buffer that ends in the middle of a value
+pub(crate) fn unexpected_end() -> TsFileError {
+    TsFileError::Corrupt {
+        source: Some("Unexpected end of encoded data".to_owned()),
+    }
+}
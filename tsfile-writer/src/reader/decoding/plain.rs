@@ -0,0 +1,109 @@
+use crate::reader::decoding::Decoder;
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::{read_bytes, read_i64, read_var_i32};
+use crate::writer::{IoTDBValue, TSDataType};
+use std::io::Read;
+
+pub struct PlainDecoder {
+    data_type: TSDataType,
+}
+
+impl PlainDecoder {
+    pub(crate) fn new(data_type: TSDataType) -> PlainDecoder {
+        Self { data_type }
+    }
+
+    fn decode_value(&self, buffer: &mut &[u8]) -> Result<IoTDBValue, TsFileError> {
+        Ok(match self.data_type {
+            TSDataType::INT32 => IoTDBValue::INT(read_var_i32(buffer)?),
+            TSDataType::INT64 => IoTDBValue::LONG(read_i64(buffer)?),
+            TSDataType::FLOAT => {
+                let mut bytes = [0_u8; 4];
+                buffer.read_exact(&mut bytes)?;
+                IoTDBValue::FLOAT(f32::from_be_bytes(bytes))
+            }
+            TSDataType::DOUBLE => {
+                let mut bytes = [0_u8; 8];
+                buffer.read_exact(&mut bytes)?;
+                IoTDBValue::DOUBLE(f64::from_be_bytes(bytes))
+            }
+            TSDataType::TEXT => {
+                let length = read_var_i32(buffer)?.max(0) as usize;
+                let bytes = read_bytes(buffer, length)?;
+                IoTDBValue::TEXT(String::from_utf8(bytes).map_err(|_| TsFileError::Corrupt {
+                    source: Some("TEXT value is not valid UTF-8".to_owned()),
+                })?)
+            }
+            TSDataType::VECTOR => return Err(TsFileError::Encoding),
+        })
+    }
+}
+
+impl Decoder for PlainDecoder {
+    fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError> {
+        let mut buffer = buffer;
+        let mut values = vec![];
+        while !buffer.is_empty() {
+            values.push(self.decode_value(&mut buffer)?);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::decoding::plain::PlainDecoder;
+    use crate::reader::decoding::Decoder;
+    use crate::writer::encoding::plain::PlainEncoder;
+    use crate::writer::encoding::Encoder;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn round_trip(data_type: TSDataType, values: Vec<IoTDBValue>) {
+        let mut encoder = PlainEncoder::new(data_type);
+        for value in values.iter() {
+            encoder.write(value).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        let decoded = PlainDecoder::new(data_type).decode(&buffer).unwrap();
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(
+            TSDataType::INT32,
+            vec![
+                IoTDBValue::INT(0),
+                IoTDBValue::INT(-13),
+                IoTDBValue::INT(i32::MAX),
+            ],
+        );
+        round_trip(
+            TSDataType::INT64,
+            vec![IoTDBValue::LONG(i64::MIN), IoTDBValue::LONG(42)],
+        );
+        round_trip(
+            TSDataType::FLOAT,
+            vec![IoTDBValue::FLOAT(1.5), IoTDBValue::FLOAT(-2.25)],
+        );
+        round_trip(
+            TSDataType::DOUBLE,
+            vec![IoTDBValue::DOUBLE(1.5), IoTDBValue::DOUBLE(-2.25)],
+        );
+        round_trip(
+            TSDataType::TEXT,
+            vec![
+                IoTDBValue::TEXT("abc".to_owned()),
+                IoTDBValue::TEXT("".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut decoder = PlainDecoder::new(TSDataType::INT64);
+        assert!(decoder.decode(&[0x00, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::reader::decoding::plain::PlainDecoder;
+use crate::reader::decoding::{unexpected_end, Decoder};
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::{IoTDBValue, TSDataType};
+use std::io::Read;
+
+/// Blocks written by this crate and by IoTDB (`deltaBlockSize`) contain 128 values. Larger
+/// counts are accepted up to this limit, beyond it the block header is considered corrupt as
+/// a width of 0 would let it expand to billions of values without any payload.
+const MAX_BLOCK_SIZE: usize = 1 << 16;
+
+/// Decodes the time column of a page, see `TsFileConfig::time_encoding`
+pub(crate) fn decode_timestamps(
+    encoding: TSEncoding,
+    buffer: &[u8],
+) -> Result<Vec<i64>, TsFileError> {
+    match encoding {
+        TSEncoding::TS2DIFF => LongTs2DiffDecoder::new().decode_values(buffer),
+        TSEncoding::PLAIN => PlainDecoder::new(TSDataType::INT64)
+            .decode(buffer)?
+            .into_iter()
+            .map(|value| match value {
+                IoTDBValue::LONG(timestamp) => Ok(timestamp),
+                _ => Err(TsFileError::Encoding),
+            })
+            .collect(),
+        _ => Err(TsFileError::Encoding),
+    }
+}
+
+/// Reads `width` bits starting at bit `pos` (most significant bit first)
+pub(crate) fn read_bits(data: &[u8], pos: usize, width: u32) -> u64 {
+    let mut value = 0_u64;
+    for bit_pos in pos..pos + width as usize {
+        let bit = (data[bit_pos / 8] >> (7 - bit_pos % 8)) & 0x01;
+        value = (value << 1) | bit as u64;
+    }
+    value
+}
+
+macro_rules! ts2diff_decoder {
+    ( $name:ident, $type:ty, $num_bits:expr, $variant:ident ) => {
+        pub struct $name {}
+
+        impl Decoder for $name {
+            fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError> {
+                Ok(self
+                    .decode_values(buffer)?
+                    .into_iter()
+                    .map(IoTDBValue::$variant)
+                    .collect())
+            }
+        }
+
+        impl $name {
+            pub(crate) fn new() -> $name {
+                Self {}
+            }
+
+            fn read_value(buffer: &mut &[u8]) -> Result<$type, TsFileError> {
+                let mut bytes = [0_u8; std::mem::size_of::<$type>()];
+                buffer.read_exact(&mut bytes)?;
+                Ok(<$type>::from_be_bytes(bytes))
+            }
+
+            /// Decodes all blocks in the buffer
+            pub(crate) fn decode_values(&self, buffer: &[u8]) -> Result<Vec<$type>, TsFileError> {
+                let mut buffer = buffer;
+                let mut values = vec![];
+                while !buffer.is_empty() {
+                    // Header: number of deltas, width, min delta and first value
+                    let mut header = [0_u8; 8];
+                    buffer.read_exact(&mut header)?;
+                    let number_of_entries =
+                        u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+                    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+                    if number_of_entries > MAX_BLOCK_SIZE {
+                        return Err(TsFileError::Corrupt {
+                            source: Some(format!(
+                                "TS2DIFF block with {} values",
+                                number_of_entries
+                            )),
+                        });
+                    }
+                    if width > $num_bits {
+                        return Err(TsFileError::Corrupt {
+                            source: Some(format!("Invalid TS2DIFF width {}", width)),
+                        });
+                    }
+                    let min_delta = Self::read_value(&mut buffer)?;
+                    let first_value = Self::read_value(&mut buffer)?;
+
+                    let payload_bits = number_of_entries * width as usize;
+                    let payload_size = payload_bits / 8 + usize::from(payload_bits % 8 != 0);
+                    if buffer.len() < payload_size {
+                        return Err(unexpected_end());
+                    }
+                    let (payload, remainder) = buffer.split_at(payload_size);
+                    buffer = remainder;
+
+                    let mut previous = first_value;
+                    values.push(previous);
+                    for i in 0..number_of_entries {
+                        let delta = read_bits(payload, i * width as usize, width) as $type;
+                        // overflows wrap around like in the encoder
+                        previous = previous.wrapping_add(delta.wrapping_add(min_delta));
+                        values.push(previous);
+                    }
+                }
+                Ok(values)
+            }
+        }
+    };
+}
+
+ts2diff_decoder!(LongTs2DiffDecoder, i64, 64, LONG);
+ts2diff_decoder!(IntTs2DiffDecoder, i32, 32, INT);
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::decoding::time_decoder::{IntTs2DiffDecoder, LongTs2DiffDecoder};
+    use crate::writer::encoding::time_encoder::{IntTs2DiffEncoder, LongTs2DiffEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
+
+    #[test]
+    fn test_long_round_trip() {
+        let values: Vec<i64> = (0..300).map(|i| i * i - 1000).collect();
+        let mut encoder = LongTs2DiffEncoder::new();
+        for v in values.iter() {
+            encoder.write(&IoTDBValue::LONG(*v)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert_eq!(
+            LongTs2DiffDecoder::new().decode_values(&buffer).unwrap(),
+            values
+        );
+    }
+
+    #[test]
+    fn test_int_round_trip_with_overflow() {
+        let values = vec![i32::MAX, i32::MIN, 0, i32::MIN, i32::MAX];
+        let mut encoder = IntTs2DiffEncoder::new();
+        for v in values.iter() {
+            encoder.write(&IoTDBValue::INT(*v)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert_eq!(
+            IntTs2DiffDecoder::new().decode_values(&buffer).unwrap(),
+            values
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut encoder = LongTs2DiffEncoder::new();
+        for v in 0..10 {
+            encoder.write(&IoTDBValue::LONG(v * 3)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        assert!(LongTs2DiffDecoder::new()
+            .decode_values(&buffer[..buffer.len() - 1])
+            .is_err());
+    }
+}
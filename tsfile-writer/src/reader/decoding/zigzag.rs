@@ -0,0 +1,109 @@
+use crate::reader::decoding::{unexpected_end, Decoder};
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::read_var_u32;
+use crate::writer::IoTDBValue;
+
+macro_rules! zigzag_decoder {
+    ( $name:ident, $type:ty, $unsigned:ty, $num_bits:expr, $variant:ident ) => {
+        pub struct $name {}
+
+        impl Decoder for $name {
+            fn decode(&mut self, buffer: &[u8]) -> Result<Vec<IoTDBValue>, TsFileError> {
+                let mut buffer = buffer;
+                let mut values = vec![];
+                while !buffer.is_empty() {
+                    // Header: byte length of the var ints and number of values
+                    let length = read_var_u32(&mut buffer)? as usize;
+                    let count = read_var_u32(&mut buffer)? as usize;
+                    if buffer.len() < length {
+                        return Err(unexpected_end());
+                    }
+                    let (mut block, remainder) = buffer.split_at(length);
+                    buffer = remainder;
+                    for _ in 0..count {
+                        let zigzag = Self::read_var_int(&mut block)?;
+                        let value = (zigzag >> 1) as $type ^ -((zigzag & 1) as $type);
+                        values.push(IoTDBValue::$variant(value));
+                    }
+                }
+                Ok(values)
+            }
+        }
+
+        impl $name {
+            pub(crate) fn new() -> $name {
+                Self {}
+            }
+
+            fn read_var_int(buffer: &mut &[u8]) -> Result<$unsigned, TsFileError> {
+                let mut value: $unsigned = 0;
+                let mut shift = 0;
+                loop {
+                    let (&byte, remainder) = buffer.split_first().ok_or_else(unexpected_end)?;
+                    *buffer = remainder;
+                    if shift >= $num_bits {
+                        return Err(TsFileError::Corrupt {
+                            source: Some("Var int is too long".to_owned()),
+                        });
+                    }
+                    value |= ((byte & 0x7F) as $unsigned) << shift;
+                    if byte & 0x80 == 0 {
+                        return Ok(value);
+                    }
+                    shift += 7;
+                }
+            }
+        }
+    };
+}
+
+zigzag_decoder!(IntZigzagDecoder, i32, u32, 32, INT);
+zigzag_decoder!(LongZigzagDecoder, i64, u64, 64, LONG);
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::decoding::zigzag::{IntZigzagDecoder, LongZigzagDecoder};
+    use crate::reader::decoding::Decoder;
+    use crate::writer::encoding::zigzag::{IntZigzagEncoder, LongZigzagEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
+
+    #[test]
+    fn roundtrip() {
+        let ints: Vec<IoTDBValue> = [0, -1, 1, 1_000, -1_000, i32::MIN, i32::MAX]
+            .into_iter()
+            .map(IoTDBValue::INT)
+            .collect();
+        let mut encoder = IntZigzagEncoder::new();
+        let mut buffer = vec![];
+        // Two pages of the same chunk
+        for value in &ints {
+            encoder.write(value).unwrap();
+        }
+        encoder.serialize(&mut buffer);
+        encoder.write(&IoTDBValue::INT(-7)).unwrap();
+        encoder.serialize(&mut buffer);
+        let mut expected = ints;
+        expected.push(IoTDBValue::INT(-7));
+        assert_eq!(IntZigzagDecoder::new().decode(&buffer).unwrap(), expected);
+
+        let longs: Vec<IoTDBValue> = [-3, 300, i64::MIN, i64::MAX]
+            .into_iter()
+            .map(IoTDBValue::LONG)
+            .collect();
+        let mut encoder = LongZigzagEncoder::new();
+        for value in &longs {
+            encoder.write(value).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+        assert_eq!(LongZigzagDecoder::new().decode(&buffer).unwrap(), longs);
+    }
+
+    #[test]
+    fn truncated() {
+        // Header announces 3 bytes but only 2 follow
+        assert!(IntZigzagDecoder::new().decode(&[0x03, 0x02, 0x00]).is_err());
+        assert!(IntZigzagDecoder::new().decode(&[0x01, 0x01, 0x80]).is_err());
+    }
+}
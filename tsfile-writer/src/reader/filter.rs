@@ -0,0 +1,206 @@
+//! Filter expressions on time and value of a timeseries.
+//!
+//! Filters are evaluated against chunk and page statistics first, so that data which cannot
+//! match is skipped without decoding it.
+use crate::writer::statistics::Statistics;
+use crate::writer::IoTDBValue;
+use std::cmp::Ordering;
+
+/// Filter on a single timeseries, all comparisons are strict and `Between` is inclusive.
+/// Values of a different type than the series never match.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    TimeGt(i64),
+    TimeLt(i64),
+    TimeEq(i64),
+    TimeBetween(i64, i64),
+    ValueGt(IoTDBValue),
+    ValueLt(IoTDBValue),
+    ValueEq(IoTDBValue),
+    ValueBetween(IoTDBValue, IoTDBValue),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Whether the given point satisfies the filter
+    pub fn matches(&self, timestamp: i64, value: &IoTDBValue) -> bool {
+        match self {
+            Filter::TimeGt(t) => timestamp > *t,
+            Filter::TimeLt(t) => timestamp < *t,
+            Filter::TimeEq(t) => timestamp == *t,
+            Filter::TimeBetween(start, end) => *start <= timestamp && timestamp <= *end,
+            Filter::ValueGt(v) => compare(value, v) == Some(Ordering::Greater),
+            Filter::ValueLt(v) => compare(value, v) == Some(Ordering::Less),
+            Filter::ValueEq(v) => compare(value, v) == Some(Ordering::Equal),
+            Filter::ValueBetween(low, high) => {
+                matches!(
+                    compare(value, low),
+                    Some(Ordering::Greater | Ordering::Equal)
+                ) && matches!(compare(value, high), Some(Ordering::Less | Ordering::Equal))
+            }
+            Filter::And(left, right) => {
+                left.matches(timestamp, value) && right.matches(timestamp, value)
+            }
+            Filter::Or(left, right) => {
+                left.matches(timestamp, value) || right.matches(timestamp, value)
+            }
+        }
+    }
+
+    /// Returns `false` if no point described by the statistics can satisfy the filter
+    pub fn may_match(&self, statistics: &Statistics) -> bool {
+        let (start_time, end_time) = (statistics.start_time(), statistics.end_time());
+        match self {
+            Filter::TimeGt(t) => end_time > *t,
+            Filter::TimeLt(t) => start_time < *t,
+            Filter::TimeEq(t) => start_time <= *t && *t <= end_time,
+            Filter::TimeBetween(start, end) => start_time <= *end && *start <= end_time,
+            Filter::ValueGt(v) => may_be(&statistics.max_value(), v, &[Ordering::Greater]),
+            Filter::ValueLt(v) => may_be(&statistics.min_value(), v, &[Ordering::Less]),
+            Filter::ValueEq(v) => {
+                may_be(
+                    &statistics.max_value(),
+                    v,
+                    &[Ordering::Greater, Ordering::Equal],
+                ) && may_be(
+                    &statistics.min_value(),
+                    v,
+                    &[Ordering::Less, Ordering::Equal],
+                )
+            }
+            Filter::ValueBetween(low, high) => {
+                may_be(
+                    &statistics.max_value(),
+                    low,
+                    &[Ordering::Greater, Ordering::Equal],
+                ) && may_be(
+                    &statistics.min_value(),
+                    high,
+                    &[Ordering::Less, Ordering::Equal],
+                )
+            }
+            Filter::And(left, right) => left.may_match(statistics) && right.may_match(statistics),
+            Filter::Or(left, right) => left.may_match(statistics) || right.may_match(statistics),
+        }
+    }
+}
+
+/// Compares two values of the same type, `None` for different types (or NaN)
+fn compare(a: &IoTDBValue, b: &IoTDBValue) -> Option<Ordering> {
+    match (a, b) {
+        (IoTDBValue::INT(a), IoTDBValue::INT(b)) => Some(a.cmp(b)),
+        (IoTDBValue::LONG(a), IoTDBValue::LONG(b)) => Some(a.cmp(b)),
+        (IoTDBValue::FLOAT(a), IoTDBValue::FLOAT(b)) => a.partial_cmp(b),
+        (IoTDBValue::DOUBLE(a), IoTDBValue::DOUBLE(b)) => a.partial_cmp(b),
+        (IoTDBValue::TEXT(a), IoTDBValue::TEXT(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Checks the bound from the statistics, without a bound (e.g. for TEXT) everything may match
+fn may_be(bound: &Option<IoTDBValue>, value: &IoTDBValue, expected: &[Ordering]) -> bool {
+    match bound {
+        Some(bound) => match compare(bound, value) {
+            Some(ordering) => expected.contains(&ordering),
+            // Values of a different type never match
+            None => false,
+        },
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::filter::Filter;
+    use crate::writer::statistics::Statistics;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn statistics(points: &[(i64, i64)]) -> Statistics {
+        let mut statistics = Statistics::new(TSDataType::INT64);
+        for (timestamp, value) in points {
+            statistics
+                .update(*timestamp, &IoTDBValue::LONG(*value))
+                .unwrap();
+        }
+        statistics
+    }
+
+    #[test]
+    fn matches() {
+        let filter = Filter::ValueGt(IoTDBValue::LONG(100)).and(Filter::TimeBetween(10, 20));
+
+        assert!(filter.matches(10, &IoTDBValue::LONG(101)));
+        assert!(filter.matches(20, &IoTDBValue::LONG(101)));
+        assert!(!filter.matches(21, &IoTDBValue::LONG(101)));
+        assert!(!filter.matches(15, &IoTDBValue::LONG(100)));
+        // Different types never match
+        assert!(!filter.matches(15, &IoTDBValue::INT(101)));
+
+        let filter = Filter::ValueLt(IoTDBValue::DOUBLE(0.0)).or(Filter::TimeEq(5));
+        assert!(filter.matches(5, &IoTDBValue::DOUBLE(1.0)));
+        assert!(filter.matches(6, &IoTDBValue::DOUBLE(-1.0)));
+        assert!(!filter.matches(6, &IoTDBValue::DOUBLE(f64::NAN)));
+
+        let filter = Filter::ValueBetween(
+            IoTDBValue::TEXT("b".to_owned()),
+            IoTDBValue::TEXT("c".to_owned()),
+        );
+        assert!(filter.matches(0, &IoTDBValue::TEXT("b".to_owned())));
+        assert!(filter.matches(0, &IoTDBValue::TEXT("bz".to_owned())));
+        assert!(!filter.matches(0, &IoTDBValue::TEXT("cz".to_owned())));
+    }
+
+    #[test]
+    fn may_match() {
+        // Times 10 - 20, values 5 - 50
+        let statistics = statistics(&[(10, 50), (20, 5)]);
+
+        assert!(Filter::TimeGt(19).may_match(&statistics));
+        assert!(!Filter::TimeGt(20).may_match(&statistics));
+        assert!(!Filter::TimeLt(10).may_match(&statistics));
+        assert!(Filter::TimeEq(15).may_match(&statistics));
+        assert!(!Filter::TimeBetween(21, 30).may_match(&statistics));
+
+        assert!(Filter::ValueGt(IoTDBValue::LONG(49)).may_match(&statistics));
+        assert!(!Filter::ValueGt(IoTDBValue::LONG(50)).may_match(&statistics));
+        assert!(!Filter::ValueLt(IoTDBValue::LONG(5)).may_match(&statistics));
+        assert!(Filter::ValueEq(IoTDBValue::LONG(5)).may_match(&statistics));
+        assert!(!Filter::ValueEq(IoTDBValue::LONG(51)).may_match(&statistics));
+        assert!(
+            Filter::ValueBetween(IoTDBValue::LONG(50), IoTDBValue::LONG(60)).may_match(&statistics)
+        );
+        assert!(
+            !Filter::ValueBetween(IoTDBValue::LONG(51), IoTDBValue::LONG(60))
+                .may_match(&statistics)
+        );
+        assert!(!Filter::ValueGt(IoTDBValue::INT(0)).may_match(&statistics));
+
+        assert!(!Filter::TimeGt(20)
+            .or(Filter::ValueGt(IoTDBValue::LONG(50)))
+            .may_match(&statistics));
+        assert!(!Filter::TimeGt(10)
+            .and(Filter::ValueGt(IoTDBValue::LONG(50)))
+            .may_match(&statistics));
+    }
+
+    #[test]
+    fn may_match_text() {
+        let mut statistics = Statistics::new(TSDataType::TEXT);
+        statistics
+            .update(1, &IoTDBValue::TEXT("a".to_owned()))
+            .unwrap();
+
+        // No min / max for TEXT, only time can be used
+        assert!(Filter::ValueGt(IoTDBValue::TEXT("z".to_owned())).may_match(&statistics));
+        assert!(!Filter::TimeGt(1).may_match(&statistics));
+    }
+}
@@ -0,0 +1,205 @@
+//! Downsampling of a timeseries into buckets of a fixed time interval, like `GROUP BY` in
+//! IoTDB, e.g. to thin out a file for visualization. Points are streamed, only the current
+//! bucket is kept in memory.
+//!
+//! ```
+//! use tsfile_writer::reader::tsfile_reader::TsFileReader;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//!
+//! let schema = Schema::simple(
+//!     "d1",
+//!     "s1",
+//!     TSDataType::DOUBLE,
+//!     TSEncoding::PLAIN,
+//!     CompressionType::UNCOMPRESSED,
+//! );
+//! let mut writer = TsFileWriter::new("target/group_by_doc.tsfile", schema, Default::default()).unwrap();
+//! for i in 0..120 {
+//!     writer.write("d1", "s1", i * 1_000, IoTDBValue::DOUBLE(i as f64)).unwrap();
+//! }
+//! writer.close().unwrap();
+//!
+//! // One minute buckets
+//! let mut reader = TsFileReader::open("target/group_by_doc.tsfile").unwrap();
+//! for bucket in reader.group_by("d1", "s1", 0, 120_000, 60_000).unwrap() {
+//!     let bucket = bucket.unwrap();
+//!     println!("{}: {:?} {:?} {:?}", bucket.start, bucket.first_value, bucket.mean, bucket.max_value);
+//! }
+//! ```
+use crate::reader::tsfile_reader::{SeriesIterator, TsFileReader};
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use crate::writer::{IoTDBValue, TSDataType};
+use std::io::{Read, Seek};
+use std::iter::Peekable;
+
+/// Aggregated points of the interval `start <= timestamp < end`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bucket {
+    pub start: i64,
+    pub end: i64,
+    pub count: u64,
+    /// The value fields are `None` for empty buckets
+    pub first_value: Option<IoTDBValue>,
+    pub last_value: Option<IoTDBValue>,
+    /// Not available for TEXT
+    pub min_value: Option<IoTDBValue>,
+    /// Not available for TEXT
+    pub max_value: Option<IoTDBValue>,
+    /// Not available for TEXT
+    pub mean: Option<f64>,
+}
+
+impl Bucket {
+    fn new(start: i64, end: i64, statistics: &Statistics) -> Bucket {
+        let count = statistics.count() as u64;
+        let value = |value: Option<IoTDBValue>| value.filter(|_| count > 0);
+        let mean = match statistics.sum_value().filter(|_| count > 0) {
+            Some(IoTDBValue::LONG(sum)) => Some(sum as f64 / count as f64),
+            Some(IoTDBValue::DOUBLE(sum)) => Some(sum / count as f64),
+            _ => None,
+        };
+        Bucket {
+            start,
+            end,
+            count,
+            first_value: value(statistics.first_value()),
+            last_value: value(statistics.last_value()),
+            min_value: value(statistics.min_value()),
+            max_value: value(statistics.max_value()),
+            mean,
+        }
+    }
+}
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Splits `start <= timestamp < end` into buckets of `interval` and aggregates the points
+    /// of each bucket. All buckets are returned, including empty ones; the last one ends at
+    /// `end`. Deleted points are skipped.
+    pub fn group_by(
+        &mut self,
+        device: &str,
+        measurement: &str,
+        start: i64,
+        end: i64,
+        interval: i64,
+    ) -> Result<GroupByIterator<'_, R>, TsFileError> {
+        if interval <= 0 {
+            return Err(TsFileError::Error {
+                source: Some(format!("Interval must be positive, got {}", interval)),
+            });
+        }
+        // Empty buckets of a series that does not exist have no values at all
+        let data_type = match self.get_timeseries_metadata(device, measurement)? {
+            Some(timeseries_metadata) => timeseries_metadata.data_type,
+            None => TSDataType::INT64,
+        };
+        let points = self
+            .query_builder(device, measurement)
+            .time_range(start, end.saturating_sub(1))
+            .build()?;
+        Ok(GroupByIterator {
+            points: points.peekable(),
+            data_type,
+            next_start: start,
+            end,
+            interval,
+        })
+    }
+}
+
+/// Iterator over the buckets of `TsFileReader::group_by`
+pub struct GroupByIterator<'a, R: Read + Seek> {
+    points: Peekable<SeriesIterator<'a, R>>,
+    data_type: TSDataType,
+    next_start: i64,
+    end: i64,
+    interval: i64,
+}
+
+impl<'a, R: Read + Seek> Iterator for GroupByIterator<'a, R> {
+    type Item = Result<Bucket, TsFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_start >= self.end {
+            return None;
+        }
+        let start = self.next_start;
+        let end = start.saturating_add(self.interval).min(self.end);
+        self.next_start = end;
+
+        let mut statistics = Statistics::new(self.data_type);
+        while let Some(point) = self.points.next_if(|point| {
+            matches!(point, Ok((timestamp, _)) if *timestamp < end) || point.is_err()
+        }) {
+            let result = point.and_then(|(timestamp, value)| statistics.update(timestamp, &value));
+            if let Err(e) = result {
+                // No further buckets after an error
+                self.next_start = self.end;
+                return Some(Err(e));
+            }
+        }
+        Some(Ok(Bucket::new(start, end, &statistics)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::modification::Deletion;
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    #[test]
+    fn group_by() -> Result<(), TsFileError> {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT32,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let config = TsFileConfig::default().max_number_of_points_in_page(7);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        for i in 0..100 {
+            writer.write("d1", "s1", i, IoTDBValue::INT(i as i32 % 40))?;
+        }
+        writer.close()?;
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+
+        let buckets = reader
+            .group_by("d1", "s1", 0, 130, 30)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let counts: Vec<u64> = buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, vec![30, 30, 30, 10, 0]);
+        assert_eq!((buckets[3].start, buckets[3].end), (90, 120));
+        assert_eq!((buckets[4].start, buckets[4].end), (120, 130));
+
+        // 30..39 and 0..19
+        let bucket = &buckets[1];
+        assert_eq!(bucket.first_value, Some(IoTDBValue::INT(30)));
+        assert_eq!(bucket.last_value, Some(IoTDBValue::INT(19)));
+        assert_eq!(bucket.min_value, Some(IoTDBValue::INT(0)));
+        assert_eq!(bucket.max_value, Some(IoTDBValue::INT(39)));
+        assert_eq!(bucket.mean, Some((345 + 190) as f64 / 30.0));
+        assert_eq!(buckets[4].first_value, None);
+        assert_eq!(buckets[4].mean, None);
+
+        reader.set_deletions(vec![Deletion::new("d1.s1", i64::MAX, 10, 25)]);
+        // 5..9 and 26..34
+        let bucket = reader.group_by("d1", "s1", 5, 35, 30)?.next().unwrap()?;
+        assert_eq!((bucket.count, bucket.mean), (14, Some(305.0 / 14.0)));
+
+        assert!(reader.group_by("d1", "s1", 0, 10, 0).is_err());
+        assert_eq!(reader.group_by("d2", "s1", 0, 10, 5)?.count(), 2);
+        Ok(())
+    }
+}
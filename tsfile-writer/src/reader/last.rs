@@ -0,0 +1,185 @@
+//! Last point of timeseries, like the last cache of IoTDB. It is answered from the statistics
+//! of the `TimeseriesMetadata` without reading any chunk, unless the last point is deleted.
+//!
+//! ```
+//! use tsfile_writer::reader::tsfile_reader::TsFileReader;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//!
+//! let schema = Schema::simple(
+//!     "d1",
+//!     "s1",
+//!     TSDataType::INT64,
+//!     TSEncoding::PLAIN,
+//!     CompressionType::UNCOMPRESSED,
+//! );
+//! let mut writer = TsFileWriter::new("target/last_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+//! writer.write("d1", "s1", 2, IoTDBValue::LONG(14)).unwrap();
+//! writer.close().unwrap();
+//!
+//! let mut reader = TsFileReader::open("target/last_doc.tsfile").unwrap();
+//! assert_eq!(reader.last("d1", "s1").unwrap(), Some((2, IoTDBValue::LONG(14))));
+//! ```
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::reader::TimeseriesMetadata;
+use crate::writer::errors::TsFileError;
+use crate::writer::IoTDBValue;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Timestamp and value of the last point of the series, `None` if the series does not
+    /// exist or all of its points are deleted
+    pub fn last(
+        &mut self,
+        device: &str,
+        measurement: &str,
+    ) -> Result<Option<(i64, IoTDBValue)>, TsFileError> {
+        match self.get_timeseries_metadata(device, measurement)? {
+            Some(timeseries_metadata) => self.last_point(device, &timeseries_metadata),
+            None => Ok(None),
+        }
+    }
+
+    /// Last points of all measurements of the device by their names, reads the metadata of the
+    /// device only once
+    pub fn last_of_device(
+        &mut self,
+        device: &str,
+    ) -> Result<BTreeMap<String, (i64, IoTDBValue)>, TsFileError> {
+        let mut last_points = BTreeMap::new();
+        let timeseries = self
+            .metadata_reader()
+            .get_device_timeseries_metadata(device)?;
+        // The time column of aligned devices has no measurement id
+        for timeseries_metadata in timeseries.iter().filter(|t| !t.measurement_id.is_empty()) {
+            if let Some(point) = self.last_point(device, timeseries_metadata)? {
+                last_points.insert(timeseries_metadata.measurement_id.clone(), point);
+            }
+        }
+        Ok(last_points)
+    }
+
+    fn last_point(
+        &mut self,
+        device: &str,
+        timeseries_metadata: &TimeseriesMetadata,
+    ) -> Result<Option<(i64, IoTDBValue)>, TsFileError> {
+        let statistics = &timeseries_metadata.statistics;
+        if statistics.count() == 0 {
+            return Ok(None);
+        }
+        let measurement = &timeseries_metadata.measurement_id;
+        let end_time = statistics.end_time();
+        let last_deleted = self
+            .deletions()
+            .iter()
+            .any(|deletion| deletion.matches(device, measurement) && deletion.covers(end_time));
+        if !last_deleted {
+            return Ok(statistics.last_value().map(|value| (end_time, value)));
+        }
+        // The statistics do not know the point before the last one
+        let mut last = None;
+        for point in self.query(device, measurement)? {
+            last = Some(point?);
+        }
+        Ok(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::modification::Deletion;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    #[test]
+    fn last() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().max_number_of_points_in_page(10);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        for i in 0..25 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            if i == 20 {
+                writer.flush()?;
+            }
+            let mut values = vec![DataPoint::new("s1", IoTDBValue::DOUBLE(i as f64))];
+            if i < 5 {
+                values.push(DataPoint::new("s2", IoTDBValue::INT(i as i32)));
+            }
+            writer.write_many("d2", i, values)?;
+        }
+        writer.write("d1", "s2", 3, IoTDBValue::TEXT("three".to_owned()))?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        assert_eq!(reader.last("d1", "s1")?, Some((24, IoTDBValue::LONG(24))));
+        assert_eq!(
+            reader.last("d1", "s2")?,
+            Some((3, IoTDBValue::TEXT("three".to_owned())))
+        );
+        // Null values of aligned series are not part of the statistics
+        assert_eq!(reader.last("d2", "s2")?, Some((4, IoTDBValue::INT(4))));
+        assert_eq!(reader.last("d1", "s3")?, None);
+        assert_eq!(reader.last("d3", "s1")?, None);
+
+        let d2 = reader.last_of_device("d2")?;
+        assert_eq!(d2.len(), 2);
+        assert_eq!(d2["s1"], (24, IoTDBValue::DOUBLE(24.0)));
+
+        // Deleted last points are skipped
+        reader.set_deletions(vec![
+            Deletion::new("d1.s1", i64::MAX, 22, 30),
+            Deletion::new("d1.s2", i64::MAX, 0, 10),
+        ]);
+        assert_eq!(reader.last("d1", "s1")?, Some((21, IoTDBValue::LONG(21))));
+        assert_eq!(reader.last("d1", "s2")?, None);
+        assert_eq!(reader.last_of_device("d1")?.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Physical layout of the data section of a TsFile, i.e. its chunk groups, chunks and pages
+//! with their offsets. Mainly useful for debugging, queries use the metadata index instead.
+use crate::reader::chunk_reader::{ChunkHeader, ChunkReader, PageHeader};
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::reader::MetadataIndexNode;
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::{read_byte, read_str};
+use crate::writer::{CHUNK_HEADER, ONLY_ONE_PAGE_CHUNK_HEADER};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Marker of a chunk group header
+pub(crate) const CHUNK_GROUP_HEADER: u8 = 0;
+/// Marker between the data section and the metadata section
+pub(crate) const SEPARATOR: u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct ChunkLayout {
+    pub offset: i64,
+    /// Number of bytes of the header and the pages
+    pub size: i64,
+    pub header: ChunkHeader,
+    pub pages: Vec<PageHeader>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkGroupLayout {
+    pub offset: i64,
+    pub device: String,
+    pub chunks: Vec<ChunkLayout>,
+}
+
+/// Node of the metadata index tree, see `TsFileMetadataReader::index_tree`
+#[derive(Clone, Debug)]
+pub struct IndexNodeLayout {
+    pub depth: usize,
+    pub offset: i64,
+    pub node: MetadataIndexNode,
+}
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Scans the data section from the file header to the metadata section.
+    /// Fails with the offset of the first chunk group or chunk that cannot be read, or of the
+    /// first marker that is neither a chunk group nor a chunk.
+    pub fn chunk_groups(&mut self) -> Result<Vec<ChunkGroupLayout>, TsFileError> {
+        let meta_offset = self.metadata().meta_offset;
+        let reader = &mut self.metadata_reader().reader;
+        // Magic string and version
+        let mut offset = 7;
+        let mut chunk_groups: Vec<ChunkGroupLayout> = vec![];
+        while offset < meta_offset {
+            reader.seek(SeekFrom::Start(offset as u64))?;
+            let marker = read_byte(reader)?;
+            match marker {
+                CHUNK_GROUP_HEADER => chunk_groups.push(ChunkGroupLayout {
+                    offset,
+                    device: read_str(reader).map_err(|e| invalid(offset, e))?,
+                    chunks: vec![],
+                }),
+                marker if is_chunk_header(marker) => {
+                    let chunk_group = match chunk_groups.last_mut() {
+                        Some(chunk_group) => chunk_group,
+                        None => return Err(unexpected_marker(marker, offset)),
+                    };
+                    let chunk =
+                        ChunkReader::read(reader, offset).map_err(|e| invalid(offset, e))?;
+                    let pages = chunk.pages().map_err(|e| invalid(offset, e))?;
+                    chunk_group.chunks.push(ChunkLayout {
+                        offset,
+                        size: reader.stream_position()? as i64 - offset,
+                        header: chunk.header().clone(),
+                        pages: pages.iter().map(|page| page.header().clone()).collect(),
+                    });
+                }
+                marker => return Err(unexpected_marker(marker, offset)),
+            }
+            offset = reader.stream_position()? as i64;
+        }
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        match read_byte(reader)? {
+            SEPARATOR if offset == meta_offset => Ok(chunk_groups),
+            marker => Err(unexpected_marker(marker, offset)),
+        }
+    }
+}
+
+pub(crate) fn is_chunk_header(marker: u8) -> bool {
+    matches!(marker & 0x3F, CHUNK_HEADER | ONLY_ONE_PAGE_CHUNK_HEADER)
+}
+
+pub(crate) fn invalid(offset: i64, e: TsFileError) -> TsFileError {
+    TsFileError::Error {
+        source: Some(format!("Invalid structure at offset {}: {}", offset, e)),
+    }
+}
+
+pub(crate) fn unexpected_marker(marker: u8, offset: i64) -> TsFileError {
+    TsFileError::Error {
+        source: Some(format!("Unexpected marker {} at offset {}", marker, offset)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::reader::MetadataIndexNodeType;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    fn write() -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().max_number_of_points_in_page(10);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config).unwrap();
+        for i in 0..25 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn chunk_groups() -> Result<(), TsFileError> {
+        let mut reader = TsFileReader::new(Cursor::new(write()))?;
+        let chunk_groups = reader.chunk_groups()?;
+
+        assert_eq!(chunk_groups.len(), 2);
+        assert_eq!(chunk_groups[0].offset, 7);
+        assert_eq!(chunk_groups[0].device, "d1");
+        assert_eq!(chunk_groups[0].chunks.len(), 1);
+        assert_eq!(chunk_groups[0].chunks[0].pages.len(), 3);
+
+        // Time chunk and value chunk
+        let chunks = &chunk_groups[1].chunks;
+        assert_eq!(chunk_groups[1].device, "d2");
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].header.is_time_chunk());
+        assert!(chunks[1].header.is_value_chunk());
+        assert_eq!(chunks[0].offset + chunks[0].size, chunks[1].offset);
+        Ok(())
+    }
+
+    #[test]
+    fn index_tree() -> Result<(), TsFileError> {
+        let mut reader = TsFileReader::new(Cursor::new(write()))?;
+        let meta_offset = reader.metadata().meta_offset;
+        let nodes = reader.metadata_reader().index_tree()?;
+
+        // Device leaf with one measurement leaf per device
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].depth, 0);
+        assert_eq!(nodes[0].node.node_type, MetadataIndexNodeType::LeafDevice);
+        assert!(nodes[0].offset > meta_offset);
+        assert_eq!(nodes[1].depth, 1);
+        assert_eq!(
+            nodes[1].node.node_type,
+            MetadataIndexNodeType::LeafMeasurement
+        );
+        assert_eq!(nodes[2].offset, nodes[0].node.children[1].offset);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_marker() {
+        let mut tsfile = write();
+        tsfile[7] = 9;
+        let mut reader = TsFileReader::new(Cursor::new(tsfile)).unwrap();
+        match reader.chunk_groups() {
+            Err(TsFileError::Error {
+                source: Some(message),
+            }) => {
+                assert_eq!(message, "Unexpected marker 9 at offset 7")
+            }
+            _ => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let mut tsfile = write();
+        // Data size of the first chunk (after marker and measurement id "s1") is too small
+        // for the page header
+        tsfile[15] = 1;
+        let mut reader = TsFileReader::new(Cursor::new(tsfile)).unwrap();
+        match reader.chunk_groups() {
+            Err(TsFileError::Error {
+                source: Some(message),
+            }) => {
+                assert!(message.starts_with("Invalid structure at offset 11"))
+            }
+            _ => panic!("Expected an error"),
+        }
+    }
+}
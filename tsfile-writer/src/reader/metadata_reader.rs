@@ -0,0 +1,687 @@
+//! Reader for the metadata section of a TsFile.
+//!
+//! Only the footer, the metadata index tree, the timeseries metadata and the bloom filter
+//! are read, data pages are never touched.
+use crate::reader::layout::IndexNodeLayout;
+use crate::reader::{
+    MetadataIndexNode, MetadataIndexNodeType, TimeseriesMetadata, TsFileMetadata, MAGIC_STRING,
+    VERSION_NUMBER,
+};
+use crate::writer::bloom_filter::BloomFilter;
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use crate::writer::utils::{read_str, read_var_u32};
+use crate::writer::TSDataType;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+/// Index trees of real files are only a few levels deep, deeper trees are cycles in corrupt
+/// files that would otherwise overflow the stack
+const MAX_INDEX_DEPTH: usize = 32;
+
+/// Summary of a single timeseries, taken from its `TimeseriesMetadata`
+#[derive(Clone, Debug)]
+pub struct TimeseriesSummary {
+    pub measurement_id: String,
+    pub data_type: TSDataType,
+    pub chunk_count: usize,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub statistics: Statistics,
+}
+
+impl From<&TimeseriesMetadata> for TimeseriesSummary {
+    fn from(metadata: &TimeseriesMetadata) -> Self {
+        TimeseriesSummary {
+            measurement_id: metadata.measurement_id.clone(),
+            data_type: metadata.data_type,
+            chunk_count: metadata.chunk_metadata_list.len(),
+            start_time: metadata.statistics.start_time(),
+            end_time: metadata.statistics.end_time(),
+            statistics: metadata.statistics.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceSummary {
+    pub device: String,
+    /// Time range of all timeseries of the device
+    pub start_time: i64,
+    pub end_time: i64,
+    pub timeseries: Vec<TimeseriesSummary>,
+}
+
+impl DeviceSummary {
+    pub(crate) fn new(device: String, timeseries: Vec<TimeseriesSummary>) -> DeviceSummary {
+        DeviceSummary {
+            device,
+            start_time: timeseries.iter().map(|t| t.start_time).min().unwrap_or(0),
+            end_time: timeseries.iter().map(|t| t.end_time).max().unwrap_or(0),
+            timeseries,
+        }
+    }
+}
+
+/// Summary of a TsFile, returned by `TsFileMetadataReader::summary` and `TsFileWriter::close`
+#[derive(Clone, Debug)]
+pub struct TsFileSummary {
+    /// Only known to writers of files
+    pub path: Option<String>,
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// Offset of the `TsFileMetadata` in the footer, which starts with the root of the
+    /// metadata index
+    pub metadata_offset: u64,
+    pub devices: Vec<DeviceSummary>,
+}
+
+pub struct TsFileMetadataReader<R: Read + Seek> {
+    pub(crate) reader: R,
+    file_size: u64,
+    metadata_offset: u64,
+    metadata: TsFileMetadata,
+    bloom_filter: Option<BloomFilter>,
+    properties: BTreeMap<String, String>,
+}
+
+impl TsFileMetadataReader<BufReader<File>> {
+    pub fn open(filename: &str) -> Result<TsFileMetadataReader<BufReader<File>>, TsFileError> {
+        let file = File::open(filename)?;
+        TsFileMetadataReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> TsFileMetadataReader<R> {
+    /// Checks head and tail magic and reads the `TsFileMetadata` and bloom filter from the footer
+    pub fn new(mut reader: R) -> Result<TsFileMetadataReader<R>, TsFileError> {
+        let mut head = [0_u8; 7];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut head)?;
+        if &head[0..6] != MAGIC_STRING {
+            return Err(TsFileError::Error {
+                source: Some("File does not start with the TsFile magic string".to_owned()),
+            });
+        }
+        if head[6] != VERSION_NUMBER {
+            return Err(TsFileError::Error {
+                source: Some(format!("Unsupported TsFile version {}", head[6])),
+            });
+        }
+
+        // Tail: metadata size (4 bytes) followed by the magic string
+        let mut tail = [0_u8; 10];
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        if file_size < (head.len() + tail.len()) as u64 {
+            return Err(TsFileError::Error {
+                source: Some("File is too small to be a complete TsFile".to_owned()),
+            });
+        }
+        reader.seek(SeekFrom::Start(file_size - tail.len() as u64))?;
+        reader.read_exact(&mut tail)?;
+        if &tail[4..10] != MAGIC_STRING {
+            return Err(TsFileError::Error {
+                source: Some(
+                    "File does not end with the TsFile magic string, is it complete?".to_owned(),
+                ),
+            });
+        }
+        let metadata_size = u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+        let metadata_offset = (file_size - tail.len() as u64)
+            .checked_sub(metadata_size)
+            .ok_or(TsFileError::Corrupt {
+                source: Some("Invalid metadata size".to_owned()),
+            })?;
+
+        // The bloom filter directly follows the TsFileMetadata
+        reader.seek(SeekFrom::Start(metadata_offset))?;
+        let metadata = TsFileMetadata::deserialize(&mut reader)?;
+        let footer_end = file_size - tail.len() as u64;
+        // The bloom filter is optional like in IoTDB, it is written unless disabled
+        let position = reader.stream_position()?;
+        let bloom_filter = match position < footer_end {
+            true => Some(BloomFilter::deserialize(&mut Read::take(
+                &mut reader,
+                footer_end - position,
+            ))?),
+            false => None,
+        };
+        // Optional user defined properties in the rest of the footer
+        let mut properties = BTreeMap::new();
+        if reader.stream_position()? < footer_end {
+            let count = read_var_u32(&mut reader)?;
+            for _ in 0..count {
+                let key = read_str(&mut reader)?;
+                properties.insert(key, read_str(&mut reader)?);
+            }
+        }
+
+        Ok(TsFileMetadataReader {
+            reader,
+            file_size,
+            metadata_offset,
+            metadata,
+            bloom_filter,
+            properties,
+        })
+    }
+
+    pub fn metadata(&self) -> &TsFileMetadata {
+        &self.metadata
+    }
+
+    /// Size of the file in bytes
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// `None` if the writer omitted the bloom filter, see `TsFileConfig::bloom_filter`
+    pub fn bloom_filter(&self) -> Option<&BloomFilter> {
+        self.bloom_filter.as_ref()
+    }
+
+    /// User defined properties of the file, see `TsFileWriter::set_property`
+    pub fn properties(&self) -> &BTreeMap<String, String> {
+        &self.properties
+    }
+
+    /// All nodes of the metadata index tree in depth first order, starting with the root
+    /// node at the beginning of the `TsFileMetadata`
+    pub fn index_tree(&mut self) -> Result<Vec<IndexNodeLayout>, TsFileError> {
+        let mut result = vec![IndexNodeLayout {
+            depth: 0,
+            offset: self.metadata_offset as i64,
+            node: self.metadata.metadata_index.clone(),
+        }];
+        self.collect_index_nodes(0, &mut result)?;
+        Ok(result)
+    }
+
+    /// Names of all devices in the order of the metadata index
+    pub fn devices(&mut self) -> Result<Vec<String>, TsFileError> {
+        Ok(self
+            .device_entries()?
+            .into_iter()
+            .map(|(device, _)| device)
+            .collect())
+    }
+
+    /// All `TimeseriesMetadata` of the given device, empty if the device does not exist
+    pub fn get_device_timeseries_metadata(
+        &mut self,
+        device: &str,
+    ) -> Result<Vec<TimeseriesMetadata>, TsFileError> {
+        let root = self.metadata.metadata_index.clone();
+        let mut result = vec![];
+        if let Some(device_node) = self.find_device_node(&root, device, 0)? {
+            self.collect_timeseries_metadata(&device_node, &mut result, 0)?;
+        }
+        Ok(result)
+    }
+
+    /// Walks the metadata index tree down to the `TimeseriesMetadata` of the given series
+    pub fn get_timeseries_metadata(
+        &mut self,
+        device: &str,
+        measurement: &str,
+    ) -> Result<Option<TimeseriesMetadata>, TsFileError> {
+        let root = self.metadata.metadata_index.clone();
+        let device_node = match self.find_device_node(&root, device, 0)? {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        self.find_timeseries_metadata(&device_node, measurement, 0)
+    }
+
+    /// Summary of all devices and timeseries in the file
+    pub fn summary(&mut self) -> Result<TsFileSummary, TsFileError> {
+        let mut devices = vec![];
+        for (device, device_node) in self.device_entries()? {
+            let mut timeseries_metadata = vec![];
+            self.collect_timeseries_metadata(&device_node, &mut timeseries_metadata, 0)?;
+            devices.push(DeviceSummary::new(
+                device,
+                timeseries_metadata.iter().map(|t| t.into()).collect(),
+            ));
+        }
+        Ok(TsFileSummary {
+            path: None,
+            file_size: self.file_size,
+            metadata_offset: self.metadata_offset,
+            devices,
+        })
+    }
+
+    /// All devices with the root of their measurement index
+    fn device_entries(&mut self) -> Result<Vec<(String, MetadataIndexNode)>, TsFileError> {
+        let root = self.metadata.metadata_index.clone();
+        let mut result = vec![];
+        self.collect_device_entries(&root, &mut result, 0)?;
+        Ok(result)
+    }
+
+    /// Appends the children of the node at `result[index]` and their subtrees
+    fn collect_index_nodes(
+        &mut self,
+        index: usize,
+        result: &mut Vec<IndexNodeLayout>,
+    ) -> Result<(), TsFileError> {
+        let parent = result[index].clone();
+        check_depth(parent.depth)?;
+        // Children of leaf measurement nodes are timeseries metadata
+        if parent.node.node_type == MetadataIndexNodeType::LeafMeasurement {
+            return Ok(());
+        }
+        for child in 0..parent.node.children.len() {
+            let offset = parent.node.children[child].offset;
+            let node = self.read_node(offset, parent.node.child_end_offset(child))?;
+            result.push(IndexNodeLayout {
+                depth: parent.depth + 1,
+                offset,
+                node,
+            });
+            self.collect_index_nodes(result.len() - 1, result)?;
+        }
+        Ok(())
+    }
+
+    fn collect_device_entries(
+        &mut self,
+        node: &MetadataIndexNode,
+        result: &mut Vec<(String, MetadataIndexNode)>,
+        depth: usize,
+    ) -> Result<(), TsFileError> {
+        check_depth(depth)?;
+        for index in 0..node.children.len() {
+            let child =
+                self.read_node(node.children[index].offset, node.child_end_offset(index))?;
+            match node.node_type {
+                MetadataIndexNodeType::LeafDevice => {
+                    result.push((node.children[index].name.clone(), child))
+                }
+                MetadataIndexNodeType::InternalDevice => {
+                    self.collect_device_entries(&child, result, depth + 1)?
+                }
+                _ => {
+                    return Err(TsFileError::Corrupt {
+                        source: Some("Expected a device index node".to_owned()),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_timeseries_metadata(
+        &mut self,
+        node: &MetadataIndexNode,
+        result: &mut Vec<TimeseriesMetadata>,
+        depth: usize,
+    ) -> Result<(), TsFileError> {
+        check_depth(depth)?;
+        for index in 0..node.children.len() {
+            let start = node.children[index].offset;
+            let end = node.child_end_offset(index);
+            match node.node_type {
+                MetadataIndexNodeType::InternalMeasurement => {
+                    let child = self.read_node(start, end)?;
+                    self.collect_timeseries_metadata(&child, result, depth + 1)?;
+                }
+                MetadataIndexNodeType::LeafMeasurement => {
+                    let bytes = self.read_bytes(start, end)?;
+                    let mut buffer = bytes.as_slice();
+                    while !buffer.is_empty() {
+                        result.push(TimeseriesMetadata::deserialize(&mut buffer)?);
+                    }
+                }
+                _ => {
+                    return Err(TsFileError::Corrupt {
+                        source: Some("Expected a measurement index node".to_owned()),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn find_device_node(
+        &mut self,
+        node: &MetadataIndexNode,
+        device: &str,
+        depth: usize,
+    ) -> Result<Option<MetadataIndexNode>, TsFileError> {
+        check_depth(depth)?;
+        let exact = match node.node_type {
+            MetadataIndexNodeType::LeafDevice => true,
+            MetadataIndexNodeType::InternalDevice => false,
+            _ => {
+                return Err(TsFileError::Corrupt {
+                    source: Some("Expected a device index node".to_owned()),
+                })
+            }
+        };
+        let index = match node.find_child(device, exact) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let child = self.read_node(node.children[index].offset, node.child_end_offset(index))?;
+        match node.node_type {
+            MetadataIndexNodeType::LeafDevice => Ok(Some(child)),
+            _ => self.find_device_node(&child, device, depth + 1),
+        }
+    }
+
+    fn find_timeseries_metadata(
+        &mut self,
+        node: &MetadataIndexNode,
+        measurement: &str,
+        depth: usize,
+    ) -> Result<Option<TimeseriesMetadata>, TsFileError> {
+        check_depth(depth)?;
+        let index = match node.find_child(measurement, false) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let start = node.children[index].offset;
+        let end = node.child_end_offset(index);
+        match node.node_type {
+            MetadataIndexNodeType::InternalMeasurement => {
+                let child = self.read_node(start, end)?;
+                self.find_timeseries_metadata(&child, measurement, depth + 1)
+            }
+            MetadataIndexNodeType::LeafMeasurement => {
+                let bytes = self.read_bytes(start, end)?;
+                let mut buffer = bytes.as_slice();
+                while !buffer.is_empty() {
+                    let timeseries_metadata = TimeseriesMetadata::deserialize(&mut buffer)?;
+                    if timeseries_metadata.measurement_id == measurement {
+                        return Ok(Some(timeseries_metadata));
+                    }
+                }
+                Ok(None)
+            }
+            _ => Err(TsFileError::Corrupt {
+                source: Some("Expected a measurement index node".to_owned()),
+            }),
+        }
+    }
+
+    fn read_node(&mut self, start: i64, end: i64) -> Result<MetadataIndexNode, TsFileError> {
+        let bytes = self.read_bytes(start, end)?;
+        MetadataIndexNode::deserialize(&mut bytes.as_slice())
+    }
+
+    pub(crate) fn read_bytes(&mut self, start: i64, end: i64) -> Result<Vec<u8>, TsFileError> {
+        if start < 0 || end < start || end as u64 > self.file_size {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!("Invalid offsets {} - {}", start, end)),
+            });
+        }
+        self.reader.seek(SeekFrom::Start(start as u64))?;
+        let mut bytes = vec![0_u8; (end - start) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+fn check_depth(depth: usize) -> Result<(), TsFileError> {
+    match depth < MAX_INDEX_DEPTH {
+        true => Ok(()),
+        false => Err(TsFileError::Corrupt {
+            source: Some(format!(
+                "Metadata index is deeper than {} levels",
+                MAX_INDEX_DEPTH
+            )),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::metadata_reader::TsFileMetadataReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::{TsFileWriter, TsFileWriterBuilder};
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    fn write_to_buffer<'a, F: FnOnce(&mut TsFileWriter<'a, Vec<u8>>)>(
+        schema: Schema<'a>,
+        f: F,
+    ) -> Vec<u8> {
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        f(&mut writer);
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn two_devices() -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::FLOAT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+
+        write_to_buffer(schema, |writer| {
+            for i in 0..10 {
+                writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+                writer
+                    .write("d1", "s2", i + 5, IoTDBValue::FLOAT(i as f32))
+                    .unwrap();
+            }
+            writer.write("d2", "s1", 100, IoTDBValue::INT(1)).unwrap();
+        })
+    }
+
+    #[test]
+    fn summary() {
+        let mut reader = TsFileMetadataReader::new(Cursor::new(two_devices())).unwrap();
+
+        let mut summary = reader.summary().unwrap();
+        summary.devices.sort_by(|a, b| a.device.cmp(&b.device));
+        assert_eq!(summary.devices.len(), 2);
+
+        let d1 = &summary.devices[0];
+        assert_eq!(d1.device, "d1");
+        assert_eq!(d1.timeseries.len(), 2);
+        assert_eq!(d1.timeseries[0].measurement_id, "s1");
+        assert_eq!(d1.timeseries[0].data_type, TSDataType::INT64);
+        assert_eq!(d1.timeseries[0].chunk_count, 1);
+        assert_eq!(d1.timeseries[0].start_time, 0);
+        assert_eq!(d1.timeseries[0].end_time, 9);
+        assert_eq!(d1.timeseries[0].statistics.count(), 10);
+        assert_eq!(d1.timeseries[1].measurement_id, "s2");
+        assert_eq!(d1.timeseries[1].data_type, TSDataType::FLOAT);
+        assert_eq!(d1.timeseries[1].start_time, 5);
+        assert_eq!(d1.timeseries[1].end_time, 14);
+
+        let d2 = &summary.devices[1];
+        assert_eq!(d2.device, "d2");
+        assert_eq!(d2.timeseries.len(), 1);
+        assert_eq!(d2.timeseries[0].data_type, TSDataType::INT32);
+        assert_eq!(d2.timeseries[0].start_time, 100);
+        assert_eq!(d2.timeseries[0].end_time, 100);
+    }
+
+    #[test]
+    fn does_not_read_data() {
+        let mut buffer = two_devices();
+        let meta_offset = {
+            let reader = TsFileMetadataReader::new(Cursor::new(buffer.clone())).unwrap();
+            reader.metadata().meta_offset as usize
+        };
+        // Destroy all chunk data between the head magic and the metadata
+        for byte in buffer[7..meta_offset].iter_mut() {
+            *byte = 0xFF;
+        }
+
+        let mut reader = TsFileMetadataReader::new(Cursor::new(buffer)).unwrap();
+        let summary = reader.summary().unwrap();
+        assert_eq!(summary.devices.len(), 2);
+    }
+
+    #[test]
+    fn device_timeseries_metadata() {
+        let mut reader = TsFileMetadataReader::new(Cursor::new(two_devices())).unwrap();
+
+        let timeseries = reader.get_device_timeseries_metadata("d1").unwrap();
+        assert_eq!(timeseries.len(), 2);
+        assert!(reader
+            .get_device_timeseries_metadata("d3")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn many_measurements() {
+        // More measurements than fit into a single index node
+        let measurements: Vec<String> = (0..300).map(|i| format!("s{:03}", i)).collect();
+        let mut device = DeviceBuilder::new();
+        for measurement in measurements.iter() {
+            device.add(
+                measurement,
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            );
+        }
+        let schema = TsFileSchemaBuilder::new().add("d1", device.build()).build();
+        let buffer = write_to_buffer(schema, |writer| {
+            for (i, measurement) in measurements.iter().enumerate() {
+                writer
+                    .write("d1", measurement, i as i64, IoTDBValue::LONG(i as i64))
+                    .unwrap();
+            }
+        });
+
+        let mut reader = TsFileMetadataReader::new(Cursor::new(buffer)).unwrap();
+        assert_eq!(reader.devices().unwrap(), vec!["d1".to_owned()]);
+
+        let timeseries = reader.get_device_timeseries_metadata("d1").unwrap();
+        let read_measurements: Vec<String> = timeseries
+            .iter()
+            .map(|t| t.measurement_id.clone())
+            .collect();
+        assert_eq!(read_measurements, measurements);
+
+        let timeseries = reader
+            .get_timeseries_metadata("d1", "s123")
+            .unwrap()
+            .unwrap();
+        assert_eq!(timeseries.statistics.start_time(), 123);
+    }
+
+    #[test]
+    fn bloom_filter() {
+        let reader = TsFileMetadataReader::new(Cursor::new(two_devices())).unwrap();
+
+        let bloom_filter = reader.bloom_filter().unwrap();
+        assert!(bloom_filter.might_contain("d1", "s1"));
+        assert!(bloom_filter.might_contain("d1", "s2"));
+        assert!(bloom_filter.might_contain("d2", "s1"));
+        assert!(!bloom_filter.might_contain("d2", "s2"));
+    }
+
+    #[test]
+    fn without_bloom_filter() {
+        let schema = || {
+            Schema::simple(
+                "d1",
+                "s1",
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+        };
+        let mut writer = TsFileWriterBuilder::new(schema())
+            .bloom_filter(false)
+            .build_in_memory()
+            .unwrap();
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+        writer.close().unwrap();
+        assert_eq!(writer.metrics().bloom_filter_size, None);
+
+        let bytes = writer.into_inner();
+        let mut reader = TsFileMetadataReader::new(Cursor::new(bytes)).unwrap();
+        assert!(reader.bloom_filter().is_none());
+        assert_eq!(reader.devices().unwrap(), vec!["d1"]);
+
+        // Properties follow the bloom filter, so it is written anyway
+        let mut writer = TsFileWriterBuilder::new(schema())
+            .bloom_filter(false)
+            .property("source", "edge-17")
+            .build_in_memory()
+            .unwrap();
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+        writer.close().unwrap();
+        let reader = TsFileMetadataReader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert!(reader.bloom_filter().unwrap().might_contain("d1", "s1"));
+        assert_eq!(reader.properties()["source"], "edge-17");
+    }
+
+    #[test]
+    fn properties() {
+        let reader = TsFileMetadataReader::new(Cursor::new(two_devices())).unwrap();
+        assert!(reader.properties().is_empty());
+
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .property("source", "edge-17")
+            .property("firmware", "1.2.3")
+            .build_in_memory()
+            .unwrap();
+        writer.set_property("schema_hash", "d41d8cd9");
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+        writer.close().unwrap();
+
+        let mut reader = TsFileMetadataReader::new(Cursor::new(writer.into_inner())).unwrap();
+        let properties: Vec<(&str, &str)> = reader
+            .properties()
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            properties,
+            vec![
+                ("firmware", "1.2.3"),
+                ("schema_hash", "d41d8cd9"),
+                ("source", "edge-17")
+            ]
+        );
+        assert!(reader.bloom_filter().unwrap().might_contain("d1", "s1"));
+        assert_eq!(reader.devices().unwrap(), vec!["d1"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Reader for TsFiles Version 3 (as written by the `writer` module or Apache IoTDB).
+//!
+//! ```
+//! use tsfile_writer::reader::tsfile_reader::TsFileReader;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//!
+//! let schema = Schema::simple(
+//!     "d1",
+//!     "s1",
+//!     TSDataType::INT64,
+//!     TSEncoding::PLAIN,
+//!     CompressionType::UNCOMPRESSED,
+//! );
+//! let mut writer = TsFileWriter::new("target/reader_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13));
+//! writer.write("d1", "s1", 2, IoTDBValue::LONG(14));
+//! writer.close().unwrap();
+//!
+//! let mut reader = TsFileReader::open("target/reader_doc.tsfile").unwrap();
+//! let points: Vec<(i64, IoTDBValue)> = reader
+//!     .query("d1", "s1")
+//!     .unwrap()
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//!
+//! assert_eq!(points, vec![(1, IoTDBValue::LONG(13)), (2, IoTDBValue::LONG(14))]);
+//! ```
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use crate::writer::utils::{read_byte, read_bytes, read_i64, read_str, read_var_u32};
+use crate::writer::TSDataType;
+use std::io::Read;
+
+pub mod aggregate;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod chunk_reader;
+pub mod decoding;
+pub mod filter;
+pub mod group_by;
+pub mod last;
+pub mod layout;
+pub mod metadata_reader;
+pub mod query;
+pub mod scan;
+pub mod schema;
+pub mod self_check;
+pub mod snapshot;
+#[cfg(feature = "datafusion")]
+pub mod table_provider;
+pub mod time_join;
+pub mod tsfile_reader;
+
+pub(crate) const MAGIC_STRING: &[u8] = b"TsFile";
+pub(crate) const VERSION_NUMBER: u8 = 0x03;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetadataIndexNodeType {
+    InternalDevice,
+    LeafDevice,
+    InternalMeasurement,
+    LeafMeasurement,
+}
+
+impl TryFrom<u8> for MetadataIndexNodeType {
+    type Error = TsFileError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MetadataIndexNodeType::InternalDevice),
+            1 => Ok(MetadataIndexNodeType::LeafDevice),
+            2 => Ok(MetadataIndexNodeType::InternalMeasurement),
+            3 => Ok(MetadataIndexNodeType::LeafMeasurement),
+            _ => Err(TsFileError::Corrupt {
+                source: Some(format!("Unknown metadata index node type {}", value)),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MetadataIndexEntry {
+    pub name: String,
+    pub offset: i64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MetadataIndexNode {
+    pub children: Vec<MetadataIndexEntry>,
+    pub end_offset: i64,
+    pub node_type: MetadataIndexNodeType,
+}
+
+impl MetadataIndexNode {
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<MetadataIndexNode, TsFileError> {
+        let number_of_children = read_var_u32(reader)?;
+        let mut children = vec![];
+        for _ in 0..number_of_children {
+            children.push(MetadataIndexEntry {
+                name: read_str(reader)?,
+                offset: read_i64(reader)?,
+            });
+        }
+        let end_offset = read_i64(reader)?;
+        let node_type = read_byte(reader)?.try_into()?;
+        Ok(MetadataIndexNode {
+            children,
+            end_offset,
+            node_type,
+        })
+    }
+
+    /// The region of a child ends where the next child starts (or at the end of the node)
+    pub(crate) fn child_end_offset(&self, index: usize) -> i64 {
+        match self.children.get(index + 1) {
+            Some(next) => next.offset,
+            None => self.end_offset,
+        }
+    }
+
+    /// Finds the child that may contain `name` by binary search, the children are sorted by name.
+    /// On exact search only a child with the same name matches, otherwise
+    /// the child with the largest name less or equal to `name` is taken.
+    pub(crate) fn find_child(&self, name: &str, exact: bool) -> Option<usize> {
+        if exact {
+            return self
+                .children
+                .binary_search_by(|c| c.name.as_str().cmp(name))
+                .ok();
+        }
+        match self.children.partition_point(|c| c.name.as_str() <= name) {
+            0 => None,
+            index => Some(index - 1),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TsFileMetadata {
+    pub metadata_index: MetadataIndexNode,
+    pub meta_offset: i64,
+}
+
+impl TsFileMetadata {
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<TsFileMetadata, TsFileError> {
+        let metadata_index = MetadataIndexNode::deserialize(reader)?;
+        let meta_offset = read_i64(reader)?;
+        Ok(TsFileMetadata {
+            metadata_index,
+            meta_offset,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkMetadata {
+    pub measurement_id: String,
+    pub data_type: TSDataType,
+    pub offset_of_chunk_header: i64,
+    pub statistics: Statistics,
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeseriesMetadata {
+    pub timeseries_metadata_type: u8,
+    pub measurement_id: String,
+    pub data_type: TSDataType,
+    pub statistics: Statistics,
+    pub chunk_metadata_list: Vec<ChunkMetadata>,
+}
+
+impl TimeseriesMetadata {
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<TimeseriesMetadata, TsFileError> {
+        let timeseries_metadata_type = read_byte(reader)?;
+        let measurement_id = read_str(reader)?;
+        let data_type = read_data_type(reader)?;
+        let chunk_metadata_list_size = read_var_u32(reader)? as usize;
+        let statistics = Statistics::deserialize(data_type, reader)?;
+
+        let buffer = read_bytes(reader, chunk_metadata_list_size)?;
+        let mut buffer = buffer.as_slice();
+
+        // Statistics are only serialized per chunk if there is more than one chunk
+        let has_multiple_chunks = timeseries_metadata_type & 0x3F != 0;
+        let mut chunk_metadata_list = vec![];
+        while !buffer.is_empty() {
+            let offset_of_chunk_header = read_i64(&mut buffer)?;
+            let chunk_statistics = match has_multiple_chunks {
+                true => Statistics::deserialize(data_type, &mut buffer)?,
+                false => statistics.clone(),
+            };
+            chunk_metadata_list.push(ChunkMetadata {
+                measurement_id: measurement_id.clone(),
+                data_type,
+                offset_of_chunk_header,
+                statistics: chunk_statistics,
+            });
+        }
+
+        Ok(TimeseriesMetadata {
+            timeseries_metadata_type,
+            measurement_id,
+            data_type,
+            statistics,
+            chunk_metadata_list,
+        })
+    }
+}
+
+pub(crate) fn read_data_type(reader: &mut dyn Read) -> Result<TSDataType, TsFileError> {
+    let value = read_byte(reader)?;
+    TSDataType::try_from(value).map_err(|_| TsFileError::Corrupt {
+        source: Some(format!("Unknown data type {}", value)),
+    })
+}
@@ -0,0 +1,306 @@
+//! Builder for queries on a single timeseries that are restricted to a time range
+//! and / or a `Filter`.
+//!
+//! Chunk and page statistics are used to skip data that does not overlap the range.
+use crate::reader::filter::Filter;
+use crate::reader::tsfile_reader::{SeriesIterator, TsFileReader};
+use crate::writer::errors::TsFileError;
+use crate::writer::statistics::Statistics;
+use std::io::{Read, Seek};
+
+/// Closed time interval, i.e. `start` and `end` are both included
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl TimeRange {
+    pub fn new(start: i64, end: i64) -> TimeRange {
+        TimeRange { start, end }
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.start <= timestamp && timestamp <= self.end
+    }
+
+    /// Whether any point described by the statistics may be within the range
+    pub fn overlaps(&self, statistics: &Statistics) -> bool {
+        statistics.start_time() <= self.end && statistics.end_time() >= self.start
+    }
+}
+
+impl Default for TimeRange {
+    fn default() -> Self {
+        TimeRange::new(i64::MIN, i64::MAX)
+    }
+}
+
+pub struct QueryBuilder<'a, R: Read + Seek> {
+    reader: &'a mut TsFileReader<R>,
+    device: String,
+    measurement: String,
+    time_range: TimeRange,
+    filter: Option<Filter>,
+    descending: bool,
+}
+
+impl<'a, R: Read + Seek> QueryBuilder<'a, R> {
+    pub(crate) fn new(
+        reader: &'a mut TsFileReader<R>,
+        device: &str,
+        measurement: &str,
+    ) -> QueryBuilder<'a, R> {
+        QueryBuilder {
+            reader,
+            device: device.to_owned(),
+            measurement: measurement.to_owned(),
+            time_range: Default::default(),
+            filter: None,
+            descending: false,
+        }
+    }
+
+    /// Only return points with `start <= timestamp <= end`
+    pub fn time_range(mut self, start: i64, end: i64) -> QueryBuilder<'a, R> {
+        self.time_range = TimeRange::new(start, end);
+        self
+    }
+
+    /// Only return points with `timestamp >= start`
+    pub fn start_time(mut self, start: i64) -> QueryBuilder<'a, R> {
+        self.time_range.start = start;
+        self
+    }
+
+    /// Only return points with `timestamp <= end`
+    pub fn end_time(mut self, end: i64) -> QueryBuilder<'a, R> {
+        self.time_range.end = end;
+        self
+    }
+
+    /// Only return points that match the filter, multiple filters are combined with `And`
+    pub fn filter(mut self, filter: Filter) -> QueryBuilder<'a, R> {
+        self.filter = Some(match self.filter {
+            Some(existing) => existing.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Return the points newest first, e.g. `.descending().build()?.take(10)` reads only the
+    /// pages of the last 10 points
+    pub fn descending(mut self) -> QueryBuilder<'a, R> {
+        self.descending = true;
+        self
+    }
+
+    pub fn build(self) -> Result<SeriesIterator<'a, R>, TsFileError> {
+        let iterator = self.reader.series_iterator(
+            &self.device,
+            &self.measurement,
+            self.time_range,
+            self.filter,
+        )?;
+        Ok(match self.descending {
+            true => iterator.descending(),
+            false => iterator,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::filter::Filter;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    /// Single series with 100k points (i.e. multiple pages) with value == timestamp
+    fn write_series() -> Vec<u8> {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::TS2DIFF,
+            CompressionType::UNCOMPRESSED,
+        );
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        for i in 0..100_000 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn timestamps(buffer: Vec<u8>, start: i64, end: i64) -> Vec<i64> {
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        reader
+            .query_builder("d1", "s1")
+            .time_range(start, end)
+            .build()
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn query_time_range() {
+        let buffer = write_series();
+
+        assert_eq!(
+            timestamps(buffer.clone(), 50_000, 50_004),
+            vec![50_000, 50_001, 50_002, 50_003, 50_004]
+        );
+        assert_eq!(
+            timestamps(buffer.clone(), 1_000, 90_000),
+            (1_000..=90_000).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(buffer.clone(), -10, 0), vec![0]);
+        assert_eq!(timestamps(buffer, 99_999, i64::MAX), vec![99_999]);
+    }
+
+    #[test]
+    fn query_start_and_end_time() {
+        let mut reader = TsFileReader::new(Cursor::new(write_series())).unwrap();
+
+        let points: Vec<(i64, IoTDBValue)> = reader
+            .query_builder("d1", "s1")
+            .start_time(99_998)
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            points,
+            vec![
+                (99_998, IoTDBValue::LONG(99_998)),
+                (99_999, IoTDBValue::LONG(99_999))
+            ]
+        );
+
+        let count = reader
+            .query_builder("d1", "s1")
+            .end_time(9)
+            .build()
+            .unwrap()
+            .count();
+        assert_eq!(count, 10);
+    }
+
+    #[test]
+    fn query_filter() {
+        let mut reader = TsFileReader::new(Cursor::new(write_series())).unwrap();
+
+        let timestamps: Vec<i64> = reader
+            .query_builder("d1", "s1")
+            .filter(Filter::ValueGt(IoTDBValue::LONG(99_990)))
+            .filter(Filter::TimeLt(99_995).or(Filter::TimeEq(99_999)))
+            .build()
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect();
+        assert_eq!(timestamps, vec![99_991, 99_992, 99_993, 99_994, 99_999]);
+
+        let timestamps: Vec<i64> = reader
+            .query_builder("d1", "s1")
+            .time_range(0, 100)
+            .filter(Filter::ValueBetween(
+                IoTDBValue::LONG(98),
+                IoTDBValue::LONG(1_000),
+            ))
+            .build()
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect();
+        assert_eq!(timestamps, vec![98, 99, 100]);
+
+        // Wrong value type
+        let count = reader
+            .query_builder("d1", "s1")
+            .filter(Filter::ValueGt(IoTDBValue::INT(0)))
+            .build()
+            .unwrap()
+            .count();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn query_descending() {
+        let mut reader = TsFileReader::new(Cursor::new(write_series())).unwrap();
+
+        let timestamps: Vec<i64> = reader
+            .query_builder("d1", "s1")
+            .descending()
+            .build()
+            .unwrap()
+            .take(3)
+            .map(|point| point.unwrap().0)
+            .collect();
+        assert_eq!(timestamps, vec![99_999, 99_998, 99_997]);
+
+        let points: Vec<(i64, IoTDBValue)> = reader
+            .query_builder("d1", "s1")
+            .time_range(10, 60_000)
+            .filter(Filter::ValueLt(IoTDBValue::LONG(13)))
+            .descending()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            points,
+            vec![
+                (12, IoTDBValue::LONG(12)),
+                (11, IoTDBValue::LONG(11)),
+                (10, IoTDBValue::LONG(10))
+            ]
+        );
+
+        // Across pages
+        let timestamps: Vec<i64> = reader
+            .query_builder("d1", "s1")
+            .descending()
+            .build()
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect();
+        assert_eq!(timestamps, (0..100_000).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn query_empty_range() {
+        let buffer = write_series();
+
+        assert!(timestamps(buffer.clone(), 10, 5).is_empty());
+        assert!(timestamps(buffer, 100_000, 200_000).is_empty());
+    }
+
+    #[test]
+    fn skip_non_overlapping_chunks() {
+        let mut buffer = write_series();
+        let meta_offset = TsFileReader::new(Cursor::new(buffer.clone()))
+            .unwrap()
+            .metadata()
+            .meta_offset as usize;
+        // Destroy all chunk data, only the statistics can answer the query
+        for byte in buffer[7..meta_offset].iter_mut() {
+            *byte = 0xFF;
+        }
+
+        assert!(timestamps(buffer.clone(), 100_000, 200_000).is_empty());
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        let result = reader
+            .query_builder("d1", "s1")
+            .time_range(0, 10)
+            .build()
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(result.is_err());
+    }
+}
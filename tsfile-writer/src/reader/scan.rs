@@ -0,0 +1,295 @@
+//! Scans the data section of a TsFile for chunk group and chunk headers without using its
+//! metadata section, e.g. for forensics of damaged files or to build an external index.
+//!
+//! Only the headers are read, the pages of a chunk are skipped. The scan ends at the metadata
+//! section, so it works for files without footer as well. `repair_file` rebuilds the metadata
+//! of a file from the same headers.
+//!
+//! ```
+//! # use tsfile_writer::writer::compression::CompressionType;
+//! # use tsfile_writer::writer::encoding::TSEncoding;
+//! # use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! # use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//! use tsfile_writer::reader::scan::ChunkScanner;
+//!
+//! # let schema = Schema::simple("d1", "s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
+//! let mut writer = TsFileWriter::new("target/scan_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+//! writer.close().unwrap();
+//!
+//! for chunk in ChunkScanner::open("target/scan_doc.tsfile").unwrap() {
+//!     let chunk = chunk.unwrap();
+//!     println!(
+//!         "{}.{} ({:?}) at offset {}",
+//!         chunk.device, chunk.header.measurement_id, chunk.header.data_type, chunk.offset
+//!     );
+//! }
+//! ```
+use crate::reader::chunk_reader::ChunkHeader;
+use crate::reader::layout::{
+    invalid, is_chunk_header, unexpected_marker, CHUNK_GROUP_HEADER, SEPARATOR,
+};
+use crate::reader::{MAGIC_STRING, VERSION_NUMBER};
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::{read_byte, read_str};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+/// Magic string and version
+const FILE_HEADER_SIZE: u64 = 7;
+
+/// Chunk found by the `ChunkScanner`
+#[derive(Clone, Debug)]
+pub struct ScannedChunk {
+    /// Device of the chunk group of the chunk
+    pub device: String,
+    /// Offset of the chunk group header
+    pub chunk_group_offset: u64,
+    /// Offset of the chunk header, see `ChunkMetadata::offset_of_chunk_header`
+    pub offset: u64,
+    /// Number of bytes of the header and the pages
+    pub size: u64,
+    /// Measurement, data type, encoding and compression of the chunk. The time chunk of an
+    /// aligned device has an empty measurement id.
+    pub header: ChunkHeader,
+}
+
+/// Iterates over the chunks of the data section in the order of the file, see the module
+/// documentation. The first chunk group or chunk that cannot be read is returned as error
+/// with its offset, e.g. of a truncated file, and ends the iteration.
+pub struct ChunkScanner<R: Read + Seek> {
+    reader: R,
+    length: u64,
+    offset: u64,
+    /// Device and offset of the current chunk group
+    chunk_group: Option<(String, u64)>,
+    done: bool,
+}
+
+impl ChunkScanner<BufReader<File>> {
+    pub fn open(filename: &str) -> Result<ChunkScanner<BufReader<File>>, TsFileError> {
+        ChunkScanner::new(BufReader::new(File::open(filename)?))
+    }
+}
+
+impl<R: Read + Seek> ChunkScanner<R> {
+    /// Checks the magic string and version at the start of the file
+    pub fn new(mut reader: R) -> Result<ChunkScanner<R>, TsFileError> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        let mut head = vec![];
+        reader.seek(SeekFrom::Start(0))?;
+        Read::take(&mut reader, FILE_HEADER_SIZE).read_to_end(&mut head)?;
+        if head.len() < FILE_HEADER_SIZE as usize || &head[..6] != MAGIC_STRING {
+            return Err(TsFileError::Error {
+                source: Some("File does not start with the magic string of a TsFile".to_owned()),
+            });
+        }
+        if head[6] != VERSION_NUMBER {
+            return Err(TsFileError::Error {
+                source: Some(format!("Unsupported version {}", head[6])),
+            });
+        }
+        Ok(ChunkScanner {
+            reader,
+            length,
+            offset: FILE_HEADER_SIZE,
+            chunk_group: None,
+            done: false,
+        })
+    }
+
+    /// Offset of the next chunk group or chunk. After the iteration it is the offset of the
+    /// metadata section, of the end of the file or of the first header that could not be read.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<ScannedChunk>, TsFileError> {
+        while self.offset < self.length {
+            let offset = self.offset;
+            self.reader.seek(SeekFrom::Start(offset))?;
+            match read_byte(&mut self.reader)? {
+                CHUNK_GROUP_HEADER => {
+                    let device =
+                        read_str(&mut self.reader).map_err(|e| invalid(offset as i64, e))?;
+                    self.chunk_group = Some((device, offset));
+                    self.offset = self.reader.stream_position()?;
+                }
+                marker if is_chunk_header(marker) => {
+                    let (device, chunk_group_offset) = match &self.chunk_group {
+                        Some(chunk_group) => chunk_group.clone(),
+                        None => return Err(unexpected_marker(marker, offset as i64)),
+                    };
+                    self.reader.seek(SeekFrom::Start(offset))?;
+                    let header = ChunkHeader::deserialize(&mut self.reader)
+                        .map_err(|e| invalid(offset as i64, e))?;
+                    let end = self.reader.stream_position()? + header.data_size as u64;
+                    if end > self.length {
+                        return Err(invalid(
+                            offset as i64,
+                            TsFileError::Corrupt {
+                                source: Some(format!(
+                                    "Chunk of {} bytes exceeds the end of the file",
+                                    header.data_size
+                                )),
+                            },
+                        ));
+                    }
+                    self.offset = end;
+                    return Ok(Some(ScannedChunk {
+                        device,
+                        chunk_group_offset,
+                        offset,
+                        size: end - offset,
+                        header,
+                    }));
+                }
+                SEPARATOR => return Ok(None),
+                marker => return Err(unexpected_marker(marker, offset as i64)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read + Seek> Iterator for ChunkScanner<R> {
+    type Item = Result<ScannedChunk, TsFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_chunk().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::scan::ChunkScanner;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    fn write() -> Vec<u8> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::FLOAT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        for i in 0..10 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d1", "s2", i, IoTDBValue::FLOAT(i as f32))
+                .unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+        }
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn scan_chunks() -> Result<(), TsFileError> {
+        let buffer = write();
+        let chunks =
+            ChunkScanner::new(Cursor::new(buffer.clone()))?.collect::<Result<Vec<_>, _>>()?;
+        let summary: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.device.as_str(),
+                    chunk.header.measurement_id.as_str(),
+                    chunk.header.data_type,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("d1", "s1", TSDataType::INT64),
+                ("d1", "s2", TSDataType::FLOAT),
+                ("d2", "", TSDataType::VECTOR),
+                ("d2", "s1", TSDataType::DOUBLE),
+            ]
+        );
+        assert_eq!(chunks[0].chunk_group_offset, 7);
+        assert_eq!(chunks[0].offset + chunks[0].size, chunks[1].offset);
+        assert_eq!(chunks[2].chunk_group_offset, chunks[2].offset - 4);
+        assert!(chunks[2].header.is_time_chunk());
+
+        // The offsets are those of the metadata
+        let mut reader = TsFileReader::new(Cursor::new(buffer))?;
+        let metadata = reader.get_timeseries_metadata("d1", "s2")?.unwrap();
+        assert_eq!(
+            metadata.chunk_metadata_list[0].offset_of_chunk_header,
+            chunks[1].offset as i64
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scan_truncated_file() -> Result<(), TsFileError> {
+        let buffer = write();
+        let chunks =
+            ChunkScanner::new(Cursor::new(buffer.clone()))?.collect::<Result<Vec<_>, _>>()?;
+
+        // Without metadata section
+        let end = (chunks[3].offset + chunks[3].size) as usize;
+        let mut scanner = ChunkScanner::new(Cursor::new(buffer[..end].to_vec()))?;
+        assert_eq!(scanner.by_ref().count(), 4);
+        assert_eq!(scanner.offset(), end as u64);
+
+        // In the middle of the last chunk
+        let results: Vec<_> = ChunkScanner::new(Cursor::new(buffer[..end - 1].to_vec()))?.collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|result| result.is_ok()));
+        match &results[3] {
+            Err(TsFileError::Error {
+                source: Some(message),
+            }) => {
+                assert!(message
+                    .starts_with(&format!("Invalid structure at offset {}", chunks[3].offset)))
+            }
+            _ => panic!("Expected an error"),
+        }
+
+        assert!(ChunkScanner::new(Cursor::new(b"TsFile".to_vec())).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Schema of existing TsFiles, e.g. to write the next file of a series with exactly the same
+//! measurements, encodings and compressions.
+//!
+//! ```
+//! use tsfile_writer::reader::schema::FileSchema;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//!
+//! let schema = Schema::simple(
+//!     "d1",
+//!     "s1",
+//!     TSDataType::INT64,
+//!     TSEncoding::TS2DIFF,
+//!     CompressionType::UNCOMPRESSED,
+//! );
+//! let mut writer = TsFileWriter::new("target/schema_doc_00.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+//! writer.close().unwrap();
+//!
+//! // The schema borrows the names of the file schema
+//! let file_schema = FileSchema::open("target/schema_doc_00.tsfile").unwrap();
+//! let schema = file_schema.schema().unwrap();
+//! let mut writer = TsFileWriter::new("target/schema_doc_01.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 2, IoTDBValue::LONG(14)).unwrap();
+//! writer.close().unwrap();
+//! ```
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::writer::errors::TsFileError;
+use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+use crate::writer::{MeasurementSchema, Schema, VALUE_SERIES_MASK};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+/// Devices and measurements of one or more TsFiles by their names
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileSchema {
+    pub devices: BTreeMap<String, DeviceSchema>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceSchema {
+    pub aligned: bool,
+    pub measurements: BTreeMap<String, MeasurementSchema>,
+}
+
+impl FileSchema {
+    pub fn new() -> FileSchema {
+        FileSchema::default()
+    }
+
+    /// Reads the schema of the file
+    pub fn open(filename: &str) -> Result<FileSchema, TsFileError> {
+        let mut schema = FileSchema::new();
+        schema.add_file(&mut TsFileReader::open(filename)?)?;
+        Ok(schema)
+    }
+
+    /// Adds the devices and measurements of the file. Encoding and compression of a measurement
+    /// are taken from its first chunk in the first file that contains it. Fails if a device is
+    /// aligned only in some files or a measurement has different data types.
+    pub fn add_file<R: Read + Seek>(
+        &mut self,
+        source: &mut TsFileReader<R>,
+    ) -> Result<(), TsFileError> {
+        for device in source.metadata_reader().devices()? {
+            let timeseries = source
+                .metadata_reader()
+                .get_device_timeseries_metadata(&device)?;
+            let aligned = timeseries
+                .iter()
+                .any(|t| t.timeseries_metadata_type & VALUE_SERIES_MASK != 0);
+            let device_schema = self.devices.entry(device.clone()).or_insert(DeviceSchema {
+                aligned,
+                measurements: BTreeMap::new(),
+            });
+            if device_schema.aligned != aligned {
+                return Err(TsFileError::SchemaError {
+                    source: Some(format!(
+                        "Device {} is aligned only in some of the files",
+                        device
+                    )),
+                });
+            }
+            // The time column of aligned devices has no measurement id
+            for metadata in timeseries.iter().filter(|t| !t.measurement_id.is_empty()) {
+                match device_schema.measurements.get(&metadata.measurement_id) {
+                    Some(series) if series.data_type != metadata.data_type => {
+                        return Err(TsFileError::SchemaError {
+                            source: Some(format!(
+                                "Timeseries {}.{} has different data types",
+                                device, metadata.measurement_id
+                            )),
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        let header = match metadata.chunk_metadata_list.first() {
+                            Some(chunk_metadata) => source.read_chunk_header(chunk_metadata)?,
+                            None => continue,
+                        };
+                        device_schema.measurements.insert(
+                            metadata.measurement_id.clone(),
+                            MeasurementSchema::new(
+                                metadata.data_type,
+                                header.encoding,
+                                header.compression,
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The schema for a writer, it borrows the names of this schema
+    pub fn schema(&self) -> Result<Schema<'_>, TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for (device, device_schema) in &self.devices {
+            let mut builder = match device_schema.aligned {
+                true => DeviceBuilder::aligned(),
+                false => DeviceBuilder::new(),
+            };
+            for (measurement, series) in &device_schema.measurements {
+                builder.add(
+                    measurement,
+                    series.data_type,
+                    series.encoding,
+                    series.compression,
+                );
+            }
+            schema.add(device, builder.try_build()?);
+        }
+        schema.try_build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::schema::FileSchema;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, MeasurementSchema, TSDataType};
+    use std::io::Cursor;
+
+    #[test]
+    fn schema_of_file() {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::SNAPPY,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::FLOAT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        writer
+            .write_many(
+                "d1",
+                1,
+                vec![
+                    DataPoint::new("s1", IoTDBValue::LONG(1)),
+                    DataPoint::new("s2", IoTDBValue::DOUBLE(1.0)),
+                ],
+            )
+            .unwrap();
+        writer.write("d2", "s1", 1, IoTDBValue::FLOAT(1.0)).unwrap();
+        writer.close().unwrap();
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner())).unwrap();
+        let mut file_schema = FileSchema::new();
+        file_schema.add_file(&mut reader).unwrap();
+
+        let d1 = &file_schema.devices["d1"];
+        assert!(!d1.aligned);
+        assert_eq!(
+            d1.measurements["s2"],
+            MeasurementSchema::new(
+                TSDataType::DOUBLE,
+                TSEncoding::GORILLA,
+                CompressionType::SNAPPY
+            )
+        );
+        assert!(file_schema.devices["d2"].aligned);
+
+        // The follow-up file has the same schema
+        let schema = file_schema.schema().unwrap();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        writer
+            .write("d1", "s2", 2, IoTDBValue::DOUBLE(2.0))
+            .unwrap();
+        writer.write("d2", "s1", 2, IoTDBValue::FLOAT(2.0)).unwrap();
+        writer.close().unwrap();
+        let mut next = FileSchema::new();
+        next.add_file(&mut TsFileReader::new(Cursor::new(writer.into_inner())).unwrap())
+            .unwrap();
+        assert_eq!(next.devices["d1"].measurements.len(), 1);
+        assert_eq!(next.devices["d1"].measurements["s2"], d1.measurements["s2"]);
+        assert_eq!(next.devices["d2"], file_schema.devices["d2"]);
+    }
+}
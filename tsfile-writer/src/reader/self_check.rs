@@ -0,0 +1,281 @@
+//! Structural self check of a TsFile, like `TsFileSequenceReader.selfCheck` of Apache IoTDB.
+//!
+//! The data section is scanned chunk by chunk, so the check works for files without footer as
+//! well, e.g. files of a crashed writer, and finds the end of their last complete chunk group.
+//!
+//! ```
+//! # use tsfile_writer::writer::compression::CompressionType;
+//! # use tsfile_writer::writer::encoding::TSEncoding;
+//! # use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! # use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//! use tsfile_writer::reader::self_check::{self_check_file, FileStatus};
+//!
+//! # let schema = Schema::simple("d1", "s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
+//! let mut writer = TsFileWriter::new("target/self_check_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+//! writer.close().unwrap();
+//!
+//! let check = self_check_file("target/self_check_doc.tsfile").unwrap();
+//! assert_eq!(check.status, FileStatus::Complete);
+//! assert_eq!(check.chunk_groups, 1);
+//! ```
+use crate::reader::metadata_reader::TsFileMetadataReader;
+use crate::reader::{MAGIC_STRING, VERSION_NUMBER};
+use crate::writer::errors::TsFileError;
+use crate::writer::recover::scan_data_section;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The footer is valid and the data section ends with the metadata section
+    Complete,
+    /// The file has no valid footer, e.g. it is still written or its writer crashed. The data
+    /// up to `SelfCheck::truncation_point` can be kept.
+    Incomplete,
+    /// The file is no TsFile or its footer does not match the data section
+    Corrupt,
+}
+
+/// Result of `self_check`
+#[derive(Clone, Debug)]
+pub struct SelfCheck {
+    pub status: FileStatus,
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// Offset of the header of the last complete chunk group, `None` if there is none
+    pub last_chunk_group: Option<u64>,
+    /// End of the last complete chunk group. A chunk group is complete if all of its chunks can
+    /// be read and it is followed by another chunk group, the metadata section or the end of the
+    /// file. The file can be truncated there without losing complete chunk groups.
+    pub truncation_point: u64,
+    /// Number of complete chunk groups with chunks
+    pub chunk_groups: usize,
+    /// Why the file is not complete, `None` for complete files
+    pub reason: Option<String>,
+}
+
+/// Checks the file, see `self_check`
+pub fn self_check_file(filename: &str) -> Result<SelfCheck, TsFileError> {
+    let file = File::open(filename)?;
+    self_check(&mut BufReader::new(file))
+}
+
+/// Checks head, footer, metadata index and all chunks of the file. Only I/O errors fail, all
+/// structural problems are reported by the `SelfCheck`.
+pub fn self_check<R: Read + Seek>(reader: &mut R) -> Result<SelfCheck, TsFileError> {
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    let mut check = SelfCheck {
+        status: FileStatus::Corrupt,
+        file_size,
+        last_chunk_group: None,
+        truncation_point: 0,
+        chunk_groups: 0,
+        reason: None,
+    };
+
+    // Magic string and version
+    let mut head = vec![];
+    reader.seek(SeekFrom::Start(0))?;
+    Read::take(&mut *reader, 7).read_to_end(&mut head)?;
+    let mut expected = MAGIC_STRING.to_vec();
+    expected.push(VERSION_NUMBER);
+    if head != expected[..head.len()] {
+        check.reason =
+            Some("File does not start with the TsFile magic string and version".to_owned());
+        return Ok(check);
+    }
+    if head.len() < expected.len() {
+        check.status = FileStatus::Incomplete;
+        check.reason = Some("File ends in the file header".to_owned());
+        return Ok(check);
+    }
+
+    // Files that end with the magic string are expected to have a valid footer
+    let mut tail = [0_u8; 6];
+    let has_footer = match file_size >= 13 {
+        true => {
+            reader.seek(SeekFrom::Start(file_size - 6))?;
+            reader.read_exact(&mut tail)?;
+            tail == MAGIC_STRING
+        }
+        false => false,
+    };
+    // The data section of a file with an invalid footer is scanned as well, to find where it can
+    // be truncated
+    let (footer, footer_error) = match has_footer {
+        true => match read_footer(&mut *reader) {
+            Ok(footer) => (Some(footer), None),
+            Err(e) => (None, Some(e)),
+        },
+        false => (None, None),
+    };
+
+    let data_section = scan_data_section(reader, file_size)?;
+    check.last_chunk_group = data_section.last_chunk_group;
+    check.truncation_point = data_section.chunk_group_end;
+    check.chunk_groups = data_section
+        .chunk_groups
+        .iter()
+        .filter(|chunk_group| {
+            (chunk_group.chunk_metadata[0].offset_of_chunk_header as u64)
+                < data_section.chunk_group_end
+        })
+        .count();
+
+    match (footer, footer_error) {
+        (Some(meta_offset), _) => match (data_section.error, data_section.separator) {
+            (None, Some(separator)) if separator == meta_offset => {
+                check.status = FileStatus::Complete;
+            }
+            (Some(e), _) => check.reason = Some(e.to_string()),
+            (None, _) => {
+                check.reason = Some(format!(
+                    "Data section does not end at the metadata section at offset {}",
+                    meta_offset
+                ))
+            }
+        },
+        (None, Some(e)) => check.reason = Some(format!("Invalid metadata section: {}", e)),
+        (None, None) => {
+            check.status = FileStatus::Incomplete;
+            check.reason = Some(match data_section.error {
+                Some(e) => e.to_string(),
+                None => "File has no metadata section".to_owned(),
+            });
+        }
+    }
+    Ok(check)
+}
+
+/// Reads the whole metadata index, returns the offset of the metadata section
+fn read_footer<R: Read + Seek>(reader: R) -> Result<u64, TsFileError> {
+    let mut metadata_reader = TsFileMetadataReader::new(reader)?;
+    metadata_reader.summary()?;
+    let meta_offset = metadata_reader.metadata().meta_offset;
+    if meta_offset < 0 {
+        return Err(TsFileError::Corrupt {
+            source: Some(format!("Invalid metadata offset {}", meta_offset)),
+        });
+    }
+    Ok(meta_offset as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::self_check::{self_check, FileStatus, SelfCheck};
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    /// Three chunk groups and the offsets of their headers and of the metadata section
+    fn write() -> (Vec<u8>, Vec<u64>, u64) {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        for i in 0..3 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.close().unwrap();
+        let buffer = writer.into_inner();
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer.clone())).unwrap();
+        let offsets = reader
+            .chunk_groups()
+            .unwrap()
+            .iter()
+            .map(|chunk_group| chunk_group.offset as u64)
+            .collect();
+        let meta_offset = reader.metadata().meta_offset as u64;
+        (buffer, offsets, meta_offset)
+    }
+
+    fn check(buffer: &[u8]) -> SelfCheck {
+        self_check(&mut Cursor::new(buffer.to_vec())).unwrap()
+    }
+
+    #[test]
+    fn complete_file() {
+        let (buffer, offsets, meta_offset) = write();
+        let result = check(&buffer);
+        assert_eq!(result.status, FileStatus::Complete);
+        assert_eq!(result.file_size, buffer.len() as u64);
+        assert_eq!(result.chunk_groups, 3);
+        assert_eq!(result.last_chunk_group, Some(offsets[2]));
+        assert_eq!(result.truncation_point, meta_offset);
+        assert_eq!(result.reason, None);
+    }
+
+    #[test]
+    fn truncated_files() {
+        let (buffer, offsets, meta_offset) = write();
+
+        // Without metadata section all chunk groups are complete
+        let result = check(&buffer[..meta_offset as usize]);
+        assert_eq!(result.status, FileStatus::Incomplete);
+        assert_eq!(result.chunk_groups, 3);
+        assert_eq!(result.truncation_point, meta_offset);
+
+        // Incomplete metadata section
+        let result = check(&buffer[..buffer.len() - 20]);
+        assert_eq!(result.status, FileStatus::Incomplete);
+        assert_eq!(result.truncation_point, meta_offset);
+
+        // In the middle of the last chunk
+        let result = check(&buffer[..meta_offset as usize - 3]);
+        assert_eq!(result.status, FileStatus::Incomplete);
+        assert_eq!(result.chunk_groups, 2);
+        assert_eq!(result.last_chunk_group, Some(offsets[1]));
+        assert_eq!(result.truncation_point, offsets[2]);
+        assert!(result.reason.is_some());
+
+        // Only the file header
+        let result = check(&buffer[..7]);
+        assert_eq!(result.status, FileStatus::Incomplete);
+        assert_eq!(result.chunk_groups, 0);
+        assert_eq!(result.last_chunk_group, None);
+        assert_eq!(result.truncation_point, 7);
+
+        let result = check(&buffer[..3]);
+        assert_eq!(result.status, FileStatus::Incomplete);
+        assert_eq!(result.truncation_point, 0);
+    }
+
+    #[test]
+    fn corrupt_files() {
+        let (buffer, offsets, meta_offset) = write();
+
+        assert_eq!(check(b"NoTsFile").status, FileStatus::Corrupt);
+
+        // Unknown marker instead of the third chunk group, the second one may be incomplete
+        let mut corrupt = buffer.clone();
+        corrupt[offsets[2] as usize] = 0x55;
+        let result = check(&corrupt);
+        assert_eq!(result.status, FileStatus::Corrupt);
+        assert_eq!(result.chunk_groups, 1);
+        assert_eq!(result.last_chunk_group, Some(offsets[0]));
+        assert_eq!(result.truncation_point, offsets[1]);
+
+        // Metadata offset beyond the end of the file
+        let mut corrupt = buffer;
+        let position = corrupt.len() - 10;
+        corrupt[position..position + 4].copy_from_slice(&[0xFF; 4]);
+        let result = check(&corrupt);
+        assert_eq!(result.status, FileStatus::Corrupt);
+        assert!(result
+            .reason
+            .unwrap()
+            .starts_with("Invalid metadata section"));
+        assert_eq!(result.chunk_groups, 3);
+        assert_eq!(result.truncation_point, meta_offset);
+    }
+}
@@ -0,0 +1,270 @@
+//! Reader for files that are still being written, i.e. without metadata section.
+//!
+//! Instead of the metadata index the data section is scanned from the start, chunk group and
+//! chunk headers are found by their markers like in `TsFileWriter::recover`. Only complete
+//! chunks are read, so with `TsFileWriter::flush_open` (or `flush`) the points of an open file
+//! can be queried while it is written, e.g. the last hour of the current file.
+//!
+//! ```
+//! # use tsfile_writer::writer::compression::CompressionType;
+//! # use tsfile_writer::writer::encoding::TSEncoding;
+//! # use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! # use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+//! use tsfile_writer::reader::snapshot::SnapshotReader;
+//!
+//! # let schema = Schema::simple("d1", "s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
+//! let mut writer = TsFileWriter::new("target/snapshot_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+//! writer.flush_open().unwrap();
+//!
+//! let mut snapshot = SnapshotReader::open("target/snapshot_doc.tsfile").unwrap();
+//! let points: Vec<_> = snapshot.query("d1", "s1").unwrap().collect();
+//! assert_eq!(points.len(), 1);
+//! ```
+use crate::reader::query::TimeRange;
+use crate::reader::tsfile_reader::{is_deleted, time_chunk_for, SeriesIterator};
+use crate::reader::ChunkMetadata;
+use crate::writer::errors::TsFileError;
+use crate::writer::modification::{Deletion, ModificationFile};
+use crate::writer::recover::scan;
+use crate::writer::{TIME_SERIES_MASK, VALUE_SERIES_MASK};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+/// Chunks of a series in the order of the file
+#[derive(Default)]
+struct SeriesChunks {
+    aligned: bool,
+    chunks: Vec<ChunkMetadata>,
+}
+
+/// Queries the complete chunk groups of a file without footer, see the module documentation
+pub struct SnapshotReader<R: Read + Seek> {
+    reader: R,
+    deletions: Vec<Deletion>,
+    /// Chunks per device and measurement, the time column of aligned devices has the
+    /// measurement ""
+    series: BTreeMap<String, BTreeMap<String, SeriesChunks>>,
+    end: u64,
+}
+
+impl SnapshotReader<BufReader<File>> {
+    /// Scans the file, deletions of a modification file (`.mods`) next to it are applied
+    pub fn open(filename: &str) -> Result<SnapshotReader<BufReader<File>>, TsFileError> {
+        let file = File::open(filename)?;
+        let mut reader = SnapshotReader::new(BufReader::new(file))?;
+        reader.deletions = ModificationFile::new(filename).read()?;
+        Ok(reader)
+    }
+}
+
+impl<R: Read + Seek> SnapshotReader<R> {
+    /// Scans the data section
+    pub fn new(reader: R) -> Result<SnapshotReader<R>, TsFileError> {
+        let mut snapshot = SnapshotReader {
+            reader,
+            deletions: vec![],
+            series: BTreeMap::new(),
+            end: 0,
+        };
+        snapshot.refresh()?;
+        Ok(snapshot)
+    }
+
+    /// Scans the file again to find the chunk groups written since the last scan
+    pub fn refresh(&mut self) -> Result<(), TsFileError> {
+        let length = self.reader.seek(SeekFrom::End(0))?;
+        let (chunk_groups, end) = scan(&mut self.reader, length)?;
+        self.series.clear();
+        for chunk_group in chunk_groups {
+            let device = self.series.entry(chunk_group.device_id).or_default();
+            for chunk in chunk_group.chunk_metadata {
+                let series = device.entry(chunk.measurement_id.clone()).or_default();
+                series.aligned = chunk.mask & (TIME_SERIES_MASK | VALUE_SERIES_MASK) != 0;
+                series.chunks.push(ChunkMetadata {
+                    measurement_id: chunk.measurement_id,
+                    data_type: chunk.data_type,
+                    offset_of_chunk_header: chunk.offset_of_chunk_header,
+                    statistics: chunk.statistics,
+                });
+            }
+        }
+        self.end = end;
+        Ok(())
+    }
+
+    /// End of the last complete chunk, the file can be truncated there
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Devices with at least one complete chunk
+    pub fn devices(&self) -> Vec<&str> {
+        self.series.keys().map(|device| device.as_str()).collect()
+    }
+
+    /// Measurements of the device with at least one complete chunk
+    pub fn measurements(&self, device: &str) -> Vec<&str> {
+        match self.series.get(device) {
+            Some(measurements) => measurements
+                .keys()
+                .filter(|measurement| !measurement.is_empty())
+                .map(|measurement| measurement.as_str())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns all points of the series in the complete chunks in order of their timestamps.
+    /// If the series has no complete chunk the iterator is empty.
+    pub fn query(
+        &mut self,
+        device: &str,
+        measurement: &str,
+    ) -> Result<SeriesIterator<'_, R>, TsFileError> {
+        self.query_range(device, measurement, Default::default())
+    }
+
+    /// Like `query` but only the points in the time range, e.g. the last hour
+    pub fn query_range(
+        &mut self,
+        device: &str,
+        measurement: &str,
+        time_range: TimeRange,
+    ) -> Result<SeriesIterator<'_, R>, TsFileError> {
+        let deletions: Vec<Deletion> = self
+            .deletions
+            .iter()
+            .filter(|deletion| deletion.matches(device, measurement))
+            .cloned()
+            .collect();
+        let empty = vec![];
+        let (chunks, time_chunks) = match self.series.get(device) {
+            Some(measurements) => (
+                measurements
+                    .get(measurement)
+                    .filter(|_| !measurement.is_empty())
+                    .map(|series| (series.aligned, &series.chunks)),
+                measurements
+                    .get("")
+                    .map(|time| &time.chunks)
+                    .unwrap_or(&empty),
+            ),
+            None => (None, &empty),
+        };
+        let chunks = match chunks {
+            Some((aligned, chunks)) => chunks
+                .iter()
+                .filter(|chunk| time_range.overlaps(&chunk.statistics))
+                .filter(|chunk| !is_deleted(&deletions, chunk))
+                .map(|chunk| {
+                    let time_chunk = if aligned {
+                        time_chunk_for(time_chunks, chunk)
+                    } else {
+                        None
+                    };
+                    (time_chunk, chunk.clone())
+                })
+                .collect(),
+            None => VecDeque::new(),
+        };
+        Ok(SeriesIterator::new(
+            &mut self.reader,
+            time_range,
+            None,
+            deletions,
+            chunks,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::query::TimeRange;
+    use crate::reader::snapshot::SnapshotReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    fn points(
+        snapshot: &mut SnapshotReader<Cursor<Vec<u8>>>,
+        device: &str,
+    ) -> Vec<(i64, IoTDBValue)> {
+        snapshot
+            .query(device, "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn in_progress_file() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default()
+            .max_number_of_points_in_page(10)
+            .close_on_drop(false);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        for i in 0..25 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))?;
+        }
+        writer.flush_open()?;
+        for i in 25..30 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        writer.flush_open()?;
+        // Not flushed yet
+        writer.write("d1", "s1", 30, IoTDBValue::LONG(30))?;
+        let mut bytes = writer.into_inner();
+        let end = bytes.len() as u64;
+        // A chunk group that is being written
+        bytes.extend_from_slice(&[0x00, 0x04, b'd', b'3', 0x01]);
+
+        let mut snapshot = SnapshotReader::new(Cursor::new(bytes))?;
+        assert_eq!(snapshot.end(), end);
+        assert_eq!(snapshot.devices(), vec!["d1", "d2"]);
+        assert_eq!(snapshot.measurements("d2"), vec!["s1"]);
+        let d1 = points(&mut snapshot, "d1");
+        assert_eq!(d1.len(), 30);
+        assert_eq!(d1[29], (29, IoTDBValue::LONG(29)));
+        let d2 = points(&mut snapshot, "d2");
+        assert_eq!(d2.len(), 25);
+        assert_eq!(d2[24], (24, IoTDBValue::DOUBLE(24.0)));
+        assert!(points(&mut snapshot, "d3").is_empty());
+
+        let recent: Vec<_> = snapshot
+            .query_range("d1", "s1", TimeRange::new(27, 100))?
+            .collect::<Result<_, _>>()?;
+        assert_eq!(recent.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+//! DataFusion `TableProvider` for a device of a TsFile (feature `datafusion`).
+//!
+//! The table has the schema of `TsFileReader::query_record_batch`, i.e. a `time` column and
+//! one column per measurement. Only the projected measurements are read and filters on the
+//! time column are used to skip chunks and pages by their statistics. Like in IoTDB, rows
+//! without a value in any of the selected measurements are not returned.
+//!
+//! The time column is a millisecond timestamp, i.e. it is compared with timestamp literals
+//! like `time >= '2022-06-01T00:00:00'`.
+use std::sync::Arc;
+
+use arrow_schema::{Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion_crate::catalog::memory::MemorySourceConfig;
+use datafusion_crate::catalog::{Session, TableProvider};
+use datafusion_crate::common::{DataFusionError, ScalarValue};
+use datafusion_crate::logical_expr::{
+    Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown, TableType,
+};
+use datafusion_crate::physical_plan::ExecutionPlan;
+
+use crate::reader::arrow::batch_schema;
+use crate::reader::query::TimeRange;
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::writer::errors::TsFileError;
+
+/// Table of a single device of a TsFile
+///
+/// ```
+/// use std::sync::Arc;
+/// # use datafusion_crate as datafusion;
+/// use datafusion::prelude::SessionContext;
+/// use tsfile_writer::reader::table_provider::TsFileTable;
+/// # use tsfile_writer::writer::compression::CompressionType;
+/// # use tsfile_writer::writer::encoding::TSEncoding;
+/// # use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+/// # use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+///
+/// # let schema = Schema::simple("d1", "s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
+/// # let mut writer = TsFileWriter::new("target/table_provider_doc.tsfile", schema, Default::default()).unwrap();
+/// # writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+/// # writer.close().unwrap();
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let ctx = SessionContext::new();
+/// let table = TsFileTable::try_new("target/table_provider_doc.tsfile", "d1").unwrap();
+/// ctx.register_table("d1", Arc::new(table)).unwrap();
+///
+/// let batches = ctx
+///     .sql("SELECT s1 FROM d1 WHERE time >= '1970-01-01T00:00:00.001'")
+///     .await
+///     .unwrap()
+///     .collect()
+///     .await
+///     .unwrap();
+/// assert_eq!(batches[0].num_rows(), 1);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct TsFileTable {
+    path: String,
+    device: String,
+    measurements: Vec<String>,
+    schema: SchemaRef,
+}
+
+impl TsFileTable {
+    pub fn try_new(path: &str, device: &str) -> Result<TsFileTable, TsFileError> {
+        let mut reader = TsFileReader::open(path)?;
+        let timeseries: Vec<_> = reader
+            .metadata_reader()
+            .get_device_timeseries_metadata(device)?
+            .into_iter()
+            // Time column of aligned devices
+            .filter(|timeseries| !timeseries.measurement_id.is_empty())
+            .map(|timeseries| (timeseries.measurement_id, timeseries.data_type))
+            .collect();
+        if timeseries.is_empty() {
+            return Err(TsFileError::SchemaError {
+                source: Some(format!("Unknown device {}", device)),
+            });
+        }
+        let schema = batch_schema(
+            &timeseries
+                .iter()
+                .map(|(measurement, data_type)| (measurement.as_str(), *data_type))
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(TsFileTable {
+            path: path.to_owned(),
+            device: device.to_owned(),
+            measurements: timeseries
+                .into_iter()
+                .map(|(measurement, _)| measurement)
+                .collect(),
+            schema: Arc::new(schema),
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for TsFileTable {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> datafusion_crate::common::Result<Arc<dyn ExecutionPlan>> {
+        let columns: Vec<usize> = match projection {
+            Some(projection) => projection.clone(),
+            None => (0..self.schema.fields().len()).collect(),
+        };
+        // Column 0 is the time column
+        let mut measurements: Vec<&str> = columns
+            .iter()
+            .filter(|&&column| column > 0)
+            .map(|&column| self.measurements[column - 1].as_str())
+            .collect();
+        // Only the timestamps are needed, e.g. for count(*)
+        if measurements.is_empty() {
+            measurements = self.measurements.iter().map(|m| m.as_str()).collect();
+        }
+
+        let batch = TsFileReader::open(&self.path)
+            .and_then(|mut reader| {
+                reader.record_batch(&self.device, &measurements, time_range(filters))
+            })
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let indices: Vec<usize> = columns
+            .iter()
+            .map(|&column| match column {
+                0 => 0,
+                column => {
+                    let measurement = &self.measurements[column - 1];
+                    1 + measurements
+                        .iter()
+                        .position(|m| m == measurement)
+                        .unwrap_or(0)
+                }
+            })
+            .collect();
+        let batch = batch.project(&indices)?;
+        let schema = Arc::new(Schema::new(batch.schema().fields().clone()));
+
+        Ok(MemorySourceConfig::try_new_exec(
+            &[vec![batch]],
+            schema,
+            None,
+        )?)
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> datafusion_crate::common::Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| match time_filter(filter) {
+                // DataFusion still applies the filter to the rows
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+}
+
+/// Intersection of the ranges of all filters on the time column
+fn time_range(filters: &[Expr]) -> TimeRange {
+    filters
+        .iter()
+        .filter_map(time_filter)
+        .fold(TimeRange::default(), |range, filter| {
+            TimeRange::new(range.start.max(filter.start), range.end.min(filter.end))
+        })
+}
+
+/// Range of a comparison of the time column with a literal, e.g. `time >= 10`
+fn time_filter(filter: &Expr) -> Option<TimeRange> {
+    match filter {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            match (is_time_column(left), timestamp(right)) {
+                (true, Some(value)) => comparison(*op, value),
+                _ => match (timestamp(left), is_time_column(right)) {
+                    (Some(value), true) => comparison(op.swap()?, value),
+                    _ => None,
+                },
+            }
+        }
+        Expr::Between(Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        }) if is_time_column(expr) => Some(TimeRange::new(timestamp(low)?, timestamp(high)?)),
+        _ => None,
+    }
+}
+
+/// Range of `time <op> value`
+fn comparison(op: Operator, value: i64) -> Option<TimeRange> {
+    match op {
+        Operator::Eq => Some(TimeRange::new(value, value)),
+        Operator::Gt => Some(TimeRange::new(value.saturating_add(1), i64::MAX)),
+        Operator::GtEq => Some(TimeRange::new(value, i64::MAX)),
+        Operator::Lt => Some(TimeRange::new(i64::MIN, value.saturating_sub(1))),
+        Operator::LtEq => Some(TimeRange::new(i64::MIN, value)),
+        _ => None,
+    }
+}
+
+fn is_time_column(expr: &Expr) -> bool {
+    matches!(expr, Expr::Column(column) if column.name == "time")
+}
+
+fn timestamp(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Literal(ScalarValue::TimestampMillisecond(value, _), _)
+        | Expr::Literal(ScalarValue::Int64(value), _) => *value,
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use datafusion_crate::logical_expr::{col, lit};
+    use datafusion_crate::prelude::SessionContext;
+
+    use crate::reader::query::TimeRange;
+    use crate::reader::table_provider::{time_range, TsFileTable};
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    fn write(filename: &str) {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new(filename, schema, Default::default()).unwrap();
+        for i in 0..100 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            if i % 2 == 0 {
+                writer
+                    .write("d1", "s2", i, IoTDBValue::DOUBLE(i as f64))
+                    .unwrap();
+            }
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn time_range_of_filters() {
+        assert_eq!(
+            time_range(&[col("time").gt_eq(lit(10_i64)), lit(20_i64).gt(col("time"))]),
+            TimeRange::new(10, 19)
+        );
+        assert_eq!(
+            time_range(&[col("time").between(lit(5_i64), lit(7_i64))]),
+            TimeRange::new(5, 7)
+        );
+        // Filters on other columns are ignored
+        assert_eq!(
+            time_range(&[col("s1").gt_eq(lit(10_i64))]),
+            TimeRange::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn sql() -> Result<(), TsFileError> {
+        write("target/table_provider.tsfile");
+        let ctx = SessionContext::new();
+        let table = TsFileTable::try_new("target/table_provider.tsfile", "d1")?;
+        ctx.register_table("d1", Arc::new(table)).unwrap();
+
+        let query = |sql: &'static str| {
+            let ctx = ctx.clone();
+            async move {
+                ctx.sql(sql)
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await
+                    .unwrap()
+                    .remove(0)
+            }
+        };
+
+        let batch = query("SELECT count(*) AS c FROM d1").await;
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 100);
+
+        let batch = query("SELECT s1 FROM d1 \
+                 WHERE time >= '1970-01-01T00:00:00.010' AND time < '1970-01-01T00:00:00.020' AND s1 > 15").await;
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            vec![16, 17, 18, 19]
+        );
+
+        let batch =
+            query("SELECT count(s2) AS c FROM d1 WHERE time < '1970-01-01T00:00:00.010'").await;
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_device() {
+        write("target/table_provider_unknown.tsfile");
+        assert!(matches!(
+            TsFileTable::try_new("target/table_provider_unknown.tsfile", "d2"),
+            Err(TsFileError::SchemaError { .. })
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+//! Rows of several timeseries aligned on their timestamps, like a `SELECT` over multiple
+//! series in IoTDB. The timestamps of the rows are the union of the timestamps of all series
+//! (an outer join), series without a point at a timestamp are `None`. The series may belong to
+//! different devices and are read side by side, so only the current page of each series is
+//! kept in memory.
+//!
+//! ```
+//! use tsfile_writer::reader::tsfile_reader::TsFileReader;
+//! use tsfile_writer::writer::compression::CompressionType;
+//! use tsfile_writer::writer::encoding::TSEncoding;
+//! use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+//! use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+//! use tsfile_writer::writer::{IoTDBValue, TSDataType};
+//!
+//! let schema = TsFileSchemaBuilder::new()
+//!     .add(
+//!         "d1",
+//!         DeviceBuilder::new()
+//!             .add("s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED)
+//!             .add("s2", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED)
+//!             .build(),
+//!     )
+//!     .build();
+//! let mut writer = TsFileWriter::new("target/time_join_doc.tsfile", schema, Default::default()).unwrap();
+//! writer.write("d1", "s1", 1, IoTDBValue::LONG(10)).unwrap();
+//! writer.write("d1", "s1", 2, IoTDBValue::LONG(20)).unwrap();
+//! writer.write("d1", "s2", 2, IoTDBValue::LONG(21)).unwrap();
+//! writer.close().unwrap();
+//!
+//! let mut reader = TsFileReader::open("target/time_join_doc.tsfile").unwrap();
+//! let rows = reader
+//!     .time_join(&[("d1", "s1"), ("d1", "s2")], Default::default())
+//!     .unwrap()
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! assert_eq!(rows[0].values, vec![Some(IoTDBValue::LONG(10)), None]);
+//! assert_eq!(rows[1].values, vec![Some(IoTDBValue::LONG(20)), Some(IoTDBValue::LONG(21))]);
+//! ```
+use crate::reader::query::TimeRange;
+use crate::reader::tsfile_reader::{SeriesScan, TsFileReader};
+use crate::writer::errors::TsFileError;
+use crate::writer::IoTDBValue;
+use std::io::{Read, Seek};
+
+/// Values of all series at one timestamp, in the order of the queried series
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub timestamp: i64,
+    pub values: Vec<Option<IoTDBValue>>,
+}
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Joins the given `(device, measurement)` series on their timestamps within the time
+    /// range. Series that do not exist in the file are always `None`, deleted points are
+    /// skipped.
+    pub fn time_join(
+        &mut self,
+        series: &[(&str, &str)],
+        time_range: TimeRange,
+    ) -> Result<TimeJoinIterator<'_, R>, TsFileError> {
+        let mut scans = Vec::with_capacity(series.len());
+        for (device, measurement) in series {
+            scans.push(self.series_scan(device, measurement, time_range)?);
+        }
+        Ok(TimeJoinIterator {
+            reader: &mut self.metadata_reader().reader,
+            heads: vec![None; scans.len()],
+            scans,
+            started: false,
+        })
+    }
+}
+
+/// Iterator over the rows of `TsFileReader::time_join` in order of their timestamps
+pub struct TimeJoinIterator<'a, R: Read + Seek> {
+    reader: &'a mut R,
+    scans: Vec<SeriesScan>,
+    /// Next point of each series, `None` if the series is exhausted
+    heads: Vec<Option<(i64, IoTDBValue)>>,
+    started: bool,
+}
+
+impl<'a, R: Read + Seek> TimeJoinIterator<'a, R> {
+    fn advance(&mut self, index: usize) -> Result<(), TsFileError> {
+        self.heads[index] = self.scans[index].next_point(self.reader).transpose()?;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for TimeJoinIterator<'a, R> {
+    type Item = Result<Row, TsFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            for index in 0..self.scans.len() {
+                if let Err(e) = self.advance(index) {
+                    self.heads.iter_mut().for_each(|head| *head = None);
+                    return Some(Err(e));
+                }
+            }
+        }
+        let timestamp = self
+            .heads
+            .iter()
+            .flatten()
+            .map(|(timestamp, _)| *timestamp)
+            .min()?;
+        let mut values = Vec::with_capacity(self.heads.len());
+        for index in 0..self.heads.len() {
+            match self.heads[index].take() {
+                Some((time, value)) if time == timestamp => {
+                    values.push(Some(value));
+                    if let Err(e) = self.advance(index) {
+                        // Stop after the first error
+                        self.heads.iter_mut().for_each(|head| *head = None);
+                        return Some(Err(e));
+                    }
+                }
+                head => {
+                    self.heads[index] = head;
+                    values.push(None);
+                }
+            }
+        }
+        Some(Ok(Row { timestamp, values }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::query::TimeRange;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::modification::Deletion;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::io::Cursor;
+
+    #[test]
+    fn time_join() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().max_number_of_points_in_page(4);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        for i in 0..30 {
+            // Every second timestamp for d1, every third for d2
+            if i % 2 == 0 {
+                writer.write("d1", "s1", i, IoTDBValue::INT(i as i32))?;
+            }
+            if i % 3 == 0 {
+                let mut values = vec![DataPoint::new("s1", IoTDBValue::DOUBLE(i as f64))];
+                if i % 9 == 0 {
+                    values.push(DataPoint::new("s2", IoTDBValue::TEXT(i.to_string())));
+                }
+                writer.write_many("d2", i, values)?;
+            }
+            if i == 15 {
+                writer.flush()?;
+            }
+        }
+        writer.close()?;
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+
+        let series = [("d1", "s1"), ("d2", "s1"), ("d2", "s2"), ("d3", "s1")];
+        let rows = reader
+            .time_join(&series, Default::default())?
+            .collect::<Result<Vec<_>, _>>()?;
+        // 15 even timestamps, 10 multiples of 3 and 5 multiples of 6 in both
+        assert_eq!(rows.len(), 20);
+        assert!(rows.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        assert_eq!(rows[0].timestamp, 0);
+        assert_eq!(
+            rows[0].values,
+            vec![
+                Some(IoTDBValue::INT(0)),
+                Some(IoTDBValue::DOUBLE(0.0)),
+                Some(IoTDBValue::TEXT("0".to_owned())),
+                None
+            ]
+        );
+        assert_eq!(rows[2].timestamp, 3);
+        assert_eq!(
+            rows[2].values,
+            vec![None, Some(IoTDBValue::DOUBLE(3.0)), None, None]
+        );
+
+        reader.set_deletions(vec![Deletion::new("d2.s1", i64::MAX, 0, 10)]);
+        let rows = reader
+            .time_join(&series[..2], TimeRange::new(4, 12))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let timestamps: Vec<i64> = rows.iter().map(|row| row.timestamp).collect();
+        assert_eq!(timestamps, vec![4, 6, 8, 10, 12]);
+        assert_eq!(rows[4].values[1], Some(IoTDBValue::DOUBLE(12.0)));
+
+        assert_eq!(reader.time_join(&[], Default::default())?.count(), 0);
+        Ok(())
+    }
+}
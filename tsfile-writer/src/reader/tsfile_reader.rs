@@ -0,0 +1,822 @@
+//! High level reader to query single timeseries from a TsFile
+use crate::reader::chunk_reader::{zip_pages, ChunkHeader, ChunkReader, PageReader};
+use crate::reader::filter::Filter;
+use crate::reader::metadata_reader::TsFileMetadataReader;
+use crate::reader::query::{QueryBuilder, TimeRange};
+use crate::reader::{ChunkMetadata, TimeseriesMetadata, TsFileMetadata};
+use crate::writer::errors::TsFileError;
+use crate::writer::modification::{Deletion, ModificationFile};
+use crate::writer::statistics::Statistics;
+use crate::writer::{IoTDBValue, VALUE_SERIES_MASK};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+pub struct TsFileReader<R: Read + Seek> {
+    metadata_reader: TsFileMetadataReader<R>,
+    deletions: Vec<Deletion>,
+}
+
+impl TsFileReader<BufReader<File>> {
+    /// Opens the file, deletions of a modification file (`.mods`) next to it are applied
+    pub fn open(filename: &str) -> Result<TsFileReader<BufReader<File>>, TsFileError> {
+        let file = File::open(filename)?;
+        let mut reader = TsFileReader::new(BufReader::new(file))?;
+        reader.deletions = ModificationFile::new(filename).read()?;
+        Ok(reader)
+    }
+}
+
+impl<R: Read + Seek> TsFileReader<R> {
+    /// Checks head and tail magic and reads the `TsFileMetadata` from the footer
+    pub fn new(reader: R) -> Result<TsFileReader<R>, TsFileError> {
+        Ok(TsFileReader {
+            metadata_reader: TsFileMetadataReader::new(reader)?,
+            deletions: vec![],
+        })
+    }
+
+    /// Deleted points are skipped by all queries
+    pub fn set_deletions(&mut self, deletions: Vec<Deletion>) {
+        self.deletions = deletions;
+    }
+
+    pub fn deletions(&self) -> &[Deletion] {
+        &self.deletions
+    }
+
+    pub fn metadata(&self) -> &TsFileMetadata {
+        self.metadata_reader.metadata()
+    }
+
+    /// Access to the metadata of the file, e.g. to list all devices
+    pub fn metadata_reader(&mut self) -> &mut TsFileMetadataReader<R> {
+        &mut self.metadata_reader
+    }
+
+    /// Returns all points of the given timeseries in order of their timestamps.
+    /// If the timeseries does not exist in the file the iterator is empty.
+    pub fn query(
+        &mut self,
+        device: &str,
+        measurement: &str,
+    ) -> Result<SeriesIterator<'_, R>, TsFileError> {
+        self.series_iterator(device, measurement, Default::default(), None)
+    }
+
+    /// Builder for queries with additional restrictions, e.g. a time range
+    pub fn query_builder(&mut self, device: &str, measurement: &str) -> QueryBuilder<'_, R> {
+        QueryBuilder::new(self, device, measurement)
+    }
+
+    pub(crate) fn series_iterator(
+        &mut self,
+        device: &str,
+        measurement: &str,
+        time_range: TimeRange,
+        filter: Option<Filter>,
+    ) -> Result<SeriesIterator<'_, R>, TsFileError> {
+        let deletions: Vec<Deletion> = self
+            .deletions
+            .iter()
+            .filter(|deletion| deletion.matches(device, measurement))
+            .cloned()
+            .collect();
+        let timeseries_metadata = match self.get_timeseries_metadata(device, measurement)? {
+            Some(timeseries_metadata) => timeseries_metadata,
+            None => return Ok(self.iterator(time_range, filter, deletions, VecDeque::new())),
+        };
+        let time_chunks = self.time_chunks(device, &timeseries_metadata)?;
+        let chunks = timeseries_metadata
+            .chunk_metadata_list
+            .into_iter()
+            .filter(|chunk| may_match(&time_range, &filter, &chunk.statistics))
+            .filter(|chunk| !is_deleted(&deletions, chunk))
+            .map(|chunk| (time_chunk_for(&time_chunks, &chunk), chunk))
+            .collect();
+        Ok(self.iterator(time_range, filter, deletions, chunks))
+    }
+
+    /// Series of aligned devices need the chunks of the shared time column, empty for other
+    /// series
+    pub(crate) fn time_chunks(
+        &mut self,
+        device: &str,
+        timeseries_metadata: &TimeseriesMetadata,
+    ) -> Result<Vec<ChunkMetadata>, TsFileError> {
+        if timeseries_metadata.timeseries_metadata_type & VALUE_SERIES_MASK == 0 {
+            return Ok(vec![]);
+        }
+        match self.get_timeseries_metadata(device, "")? {
+            Some(time_metadata) => Ok(time_metadata.chunk_metadata_list),
+            None => Err(TsFileError::Error {
+                source: Some(format!("No time column found for device {}", device)),
+            }),
+        }
+    }
+
+    fn iterator(
+        &mut self,
+        time_range: TimeRange,
+        filter: Option<Filter>,
+        deletions: Vec<Deletion>,
+        chunks: VecDeque<(Option<i64>, ChunkMetadata)>,
+    ) -> SeriesIterator<'_, R> {
+        SeriesIterator::new(
+            &mut self.metadata_reader.reader,
+            time_range,
+            filter,
+            deletions,
+            chunks,
+        )
+    }
+
+    /// Like `series_iterator` but without borrowing the reader, so that several series can be
+    /// read side by side
+    pub(crate) fn series_scan(
+        &mut self,
+        device: &str,
+        measurement: &str,
+        time_range: TimeRange,
+    ) -> Result<SeriesScan, TsFileError> {
+        Ok(self
+            .series_iterator(device, measurement, time_range, None)?
+            .scan)
+    }
+
+    /// Reads a single chunk, e.g. from the `chunk_metadata_list` of a `TimeseriesMetadata`
+    pub fn read_chunk(
+        &mut self,
+        chunk_metadata: &ChunkMetadata,
+    ) -> Result<ChunkReader, TsFileError> {
+        ChunkReader::read(
+            &mut self.metadata_reader.reader,
+            chunk_metadata.offset_of_chunk_header,
+        )
+    }
+
+    /// Reads only the header of a chunk, e.g. for its encoding and compression
+    pub fn read_chunk_header(
+        &mut self,
+        chunk_metadata: &ChunkMetadata,
+    ) -> Result<ChunkHeader, TsFileError> {
+        let reader = &mut self.metadata_reader.reader;
+        reader.seek(SeekFrom::Start(
+            chunk_metadata.offset_of_chunk_header as u64,
+        ))?;
+        ChunkHeader::deserialize(reader)
+    }
+
+    /// Walks the metadata index tree down to the `TimeseriesMetadata` of the given series
+    pub fn get_timeseries_metadata(
+        &mut self,
+        device: &str,
+        measurement: &str,
+    ) -> Result<Option<TimeseriesMetadata>, TsFileError> {
+        self.metadata_reader
+            .get_timeseries_metadata(device, measurement)
+    }
+}
+
+/// Whether all points of the chunk are deleted, so it does not have to be read
+pub(crate) fn is_deleted(deletions: &[Deletion], chunk: &ChunkMetadata) -> bool {
+    deletions.iter().any(|deletion| {
+        deletion.applies_to(chunk.offset_of_chunk_header)
+            && deletion.covers(chunk.statistics.start_time())
+            && deletion.covers(chunk.statistics.end_time())
+    })
+}
+
+/// The time chunk of a value chunk is the closest chunk before it, i.e. in the same chunk group
+pub(crate) fn time_chunk_for(time_chunks: &[ChunkMetadata], chunk: &ChunkMetadata) -> Option<i64> {
+    time_chunks
+        .iter()
+        .map(|time_chunk| time_chunk.offset_of_chunk_header)
+        .filter(|offset| *offset < chunk.offset_of_chunk_header)
+        .max()
+}
+
+/// Iterator over the points of a single timeseries, chunks and pages are read lazily.
+/// For aligned devices each chunk and page is paired with the one of the time column.
+pub struct SeriesIterator<'a, R: Read + Seek> {
+    reader: &'a mut R,
+    scan: SeriesScan,
+}
+
+/// State of a `SeriesIterator`, the reader is passed to each call
+pub(crate) struct SeriesScan {
+    time_range: TimeRange,
+    filter: Option<Filter>,
+    deletions: Vec<Deletion>,
+    /// Deletions that apply to the chunk the pages belong to
+    chunk_deletions: Vec<Deletion>,
+    chunks: VecDeque<(Option<i64>, ChunkMetadata)>,
+    pages: VecDeque<(Option<PageReader>, PageReader)>,
+    points: VecDeque<(i64, IoTDBValue)>,
+    /// Chunks, pages and the points of a page are taken from the back
+    descending: bool,
+}
+
+impl SeriesScan {
+    pub(crate) fn next_point<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Option<Result<(i64, IoTDBValue), TsFileError>> {
+        while self.points.is_empty() {
+            match self.read_next_page(reader)? {
+                Ok(points) => {
+                    let (time_range, filter) = (self.time_range, &self.filter);
+                    let deletions = &self.chunk_deletions;
+                    self.points = points
+                        .into_iter()
+                        .filter(|(timestamp, value)| {
+                            time_range.contains(*timestamp)
+                                && !deletions.iter().any(|deletion| deletion.covers(*timestamp))
+                                && match filter {
+                                    Some(filter) => filter.matches(*timestamp, value),
+                                    None => true,
+                                }
+                        })
+                        .collect()
+                }
+                Err(e) => {
+                    // Stop after the first error
+                    self.chunks.clear();
+                    self.pages.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+        let point = match self.descending {
+            true => self.points.pop_back(),
+            false => self.points.pop_front(),
+        };
+        point.map(Ok)
+    }
+
+    /// Decodes the next page that overlaps the time range
+    fn read_next_page<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+    ) -> Option<Result<Vec<(i64, IoTDBValue)>, TsFileError>> {
+        loop {
+            let next_page = match self.descending {
+                true => self.pages.pop_back(),
+                false => self.pages.pop_front(),
+            };
+            if let Some((time_page, page)) = next_page {
+                // Pages without statistics are covered by the chunk statistics
+                let may_match = match &page.header().statistics {
+                    Some(statistics) => may_match(&self.time_range, &self.filter, statistics),
+                    None => true,
+                };
+                if may_match {
+                    return Some(match time_page {
+                        Some(time_page) => page.read_aligned(&time_page),
+                        None => page.read_all(),
+                    });
+                }
+                continue;
+            }
+            let next_chunk = match self.descending {
+                true => self.chunks.pop_back(),
+                false => self.chunks.pop_front(),
+            };
+            let (time_offset, chunk_metadata) = next_chunk?;
+            self.chunk_deletions = self
+                .deletions
+                .iter()
+                .filter(|deletion| deletion.applies_to(chunk_metadata.offset_of_chunk_header))
+                .cloned()
+                .collect();
+            match read_pages(reader, time_offset, &chunk_metadata) {
+                Ok(pages) => self.pages = pages,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn read_pages<R: Read + Seek>(
+    reader: &mut R,
+    time_offset: Option<i64>,
+    chunk_metadata: &ChunkMetadata,
+) -> Result<VecDeque<(Option<PageReader>, PageReader)>, TsFileError> {
+    let pages = ChunkReader::read(reader, chunk_metadata.offset_of_chunk_header)?.pages()?;
+    match time_offset {
+        Some(time_offset) => {
+            let time_pages = ChunkReader::read(reader, time_offset)?.pages()?;
+            Ok(zip_pages(time_pages, pages)?
+                .into_iter()
+                .map(|(time_page, page)| (Some(time_page), page))
+                .collect())
+        }
+        None => Ok(pages.into_iter().map(|page| (None, page)).collect()),
+    }
+}
+
+impl<'a, R: Read + Seek> SeriesIterator<'a, R> {
+    /// Reads the given chunks, value chunks of aligned series are paired with the offset of
+    /// their time chunk
+    pub(crate) fn new(
+        reader: &'a mut R,
+        time_range: TimeRange,
+        filter: Option<Filter>,
+        deletions: Vec<Deletion>,
+        chunks: VecDeque<(Option<i64>, ChunkMetadata)>,
+    ) -> SeriesIterator<'a, R> {
+        SeriesIterator {
+            reader,
+            scan: SeriesScan {
+                time_range,
+                filter,
+                deletions,
+                chunk_deletions: vec![],
+                chunks,
+                pages: VecDeque::new(),
+                points: VecDeque::new(),
+                descending: false,
+            },
+        }
+    }
+
+    /// Returns the points newest first
+    pub(crate) fn descending(mut self) -> SeriesIterator<'a, R> {
+        self.scan.descending = true;
+        self
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for SeriesIterator<'a, R> {
+    type Item = Result<(i64, IoTDBValue), TsFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scan.next_point(self.reader)
+    }
+}
+
+/// Whether a chunk or page with the given statistics has to be read
+fn may_match(time_range: &TimeRange, filter: &Option<Filter>, statistics: &Statistics) -> bool {
+    time_range.overlaps(statistics)
+        && match filter {
+            Some(filter) => filter.may_match(statistics),
+            None => true,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    fn write_to_buffer<F: FnOnce(&mut TsFileWriter<Vec<u8>>)>(schema: Schema, f: F) -> Vec<u8> {
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        f(&mut writer);
+        writer.close().unwrap();
+        writer.into_inner()
+    }
+
+    fn query(buffer: Vec<u8>, device: &str, measurement: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        reader
+            .query(device, measurement)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn read_all_data_types() {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s3",
+                        TSDataType::FLOAT,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+
+        let buffer = write_to_buffer(schema, |writer| {
+            for i in 0..100 {
+                writer
+                    .write_many(
+                        "d1",
+                        i,
+                        vec![
+                            DataPoint::new("s1", IoTDBValue::INT(i as i32 * 2)),
+                            DataPoint::new("s2", IoTDBValue::LONG(-i)),
+                            DataPoint::new("s3", IoTDBValue::FLOAT(i as f32 / 4.0)),
+                        ],
+                    )
+                    .unwrap();
+                writer
+                    .write_many(
+                        "d2",
+                        i * 10,
+                        vec![
+                            DataPoint::new("s1", IoTDBValue::DOUBLE(i as f64 * 0.1)),
+                            DataPoint::new("s2", IoTDBValue::TEXT(format!("v{}", i))),
+                        ],
+                    )
+                    .unwrap();
+            }
+        });
+
+        let expect = |f: &dyn Fn(i64) -> (i64, IoTDBValue)| (0..100).map(f).collect::<Vec<_>>();
+        assert_eq!(
+            query(buffer.clone(), "d1", "s1"),
+            expect(&|i| (i, IoTDBValue::INT(i as i32 * 2)))
+        );
+        assert_eq!(
+            query(buffer.clone(), "d1", "s2"),
+            expect(&|i| (i, IoTDBValue::LONG(-i)))
+        );
+        assert_eq!(
+            query(buffer.clone(), "d1", "s3"),
+            expect(&|i| (i, IoTDBValue::FLOAT(i as f32 / 4.0)))
+        );
+        assert_eq!(
+            query(buffer.clone(), "d2", "s1"),
+            expect(&|i| (i * 10, IoTDBValue::DOUBLE(i as f64 * 0.1)))
+        );
+        assert_eq!(
+            query(buffer, "d2", "s2"),
+            expect(&|i| (i * 10, IoTDBValue::TEXT(format!("v{}", i))))
+        );
+    }
+
+    #[test]
+    fn read_multiple_pages() {
+        let schema = Schema::simple(
+            "root.sg.d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let buffer = write_to_buffer(schema, |writer| {
+            for i in 0..100_000 {
+                writer
+                    .write("root.sg.d1", "s1", i, IoTDBValue::LONG(i * 7))
+                    .unwrap();
+            }
+        });
+
+        let points = query(buffer, "root.sg.d1", "s1");
+        assert_eq!(points.len(), 100_000);
+        assert!(points
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p == &(i as i64, IoTDBValue::LONG(i as i64 * 7))));
+    }
+
+    #[test]
+    fn read_chunks_and_pages() {
+        let schema = Schema::simple(
+            "root.sg.d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let buffer = write_to_buffer(schema, |writer| {
+            for i in 0..100_000 {
+                writer
+                    .write("root.sg.d1", "s1", i, IoTDBValue::LONG(i))
+                    .unwrap();
+            }
+        });
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        let timeseries_metadata = reader
+            .get_timeseries_metadata("root.sg.d1", "s1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(timeseries_metadata.chunk_metadata_list.len(), 1);
+
+        let chunk = reader
+            .read_chunk(&timeseries_metadata.chunk_metadata_list[0])
+            .unwrap();
+        assert_eq!(chunk.header().measurement_id, "s1");
+        assert_eq!(chunk.header().data_type, TSDataType::INT64);
+        assert!(chunk.header().has_page_statistics());
+
+        let pages = chunk.pages().unwrap();
+        assert!(pages.len() > 1);
+
+        // Skip all pages but the last one based on their statistics
+        let last_page = pages
+            .iter()
+            .find(|page| page.header().statistics.as_ref().unwrap().end_time() == 99_999)
+            .unwrap();
+        let timestamps = last_page.timestamps().unwrap();
+        let points = last_page.read_all().unwrap();
+        assert_eq!(timestamps.len(), points.len());
+        assert_eq!(points.last(), Some(&(99_999, IoTDBValue::LONG(99_999))));
+        assert_eq!(
+            last_page.header().statistics.as_ref().unwrap().count() as usize,
+            points.len()
+        );
+    }
+
+    fn read_with_compression(compression: CompressionType) {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::TS2DIFF,
+            compression,
+        );
+
+        let buffer = write_to_buffer(schema, |writer| {
+            for i in 0..10_000 {
+                writer
+                    .write("d1", "s1", i, IoTDBValue::LONG(i % 13))
+                    .unwrap();
+            }
+        });
+
+        let points = query(buffer, "d1", "s1");
+        assert_eq!(points.len(), 10_000);
+        assert_eq!(points[9_999], (9_999, IoTDBValue::LONG(9_999 % 13)));
+    }
+
+    #[test]
+    fn read_uncompressed() {
+        read_with_compression(CompressionType::UNCOMPRESSED);
+    }
+
+    #[test]
+    #[cfg(feature = "snappy")]
+    fn read_snappy() {
+        read_with_compression(CompressionType::SNAPPY);
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn read_gzip() {
+        read_with_compression(CompressionType::GZIP);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn read_lz4() {
+        read_with_compression(CompressionType::LZ4);
+    }
+
+    #[test]
+    fn read_unknown_series() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let buffer = write_to_buffer(schema, |writer| {
+            writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+        });
+
+        assert!(query(buffer.clone(), "d1", "s2").is_empty());
+        assert!(query(buffer, "d2", "s1").is_empty());
+    }
+
+    #[test]
+    fn read_invalid_file() {
+        let result = TsFileReader::new(Cursor::new(b"NoTsFile".to_vec()));
+        assert!(matches!(result, Err(TsFileError::Error { .. })));
+    }
+
+    #[test]
+    fn read_truncated_file() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let mut buffer = write_to_buffer(schema, |writer| {
+            writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+        });
+        buffer.truncate(buffer.len() - 3);
+
+        assert!(TsFileReader::new(Cursor::new(buffer)).is_err());
+    }
+
+    #[test]
+    fn read_corrupt_file() {
+        let buffer = write_to_buffer(aligned_schema(), |writer| {
+            for i in 0..20 {
+                writer
+                    .write_many(
+                        "d1",
+                        i,
+                        vec![
+                            DataPoint::new("s1", IoTDBValue::LONG(i)),
+                            DataPoint::new("s3", IoTDBValue::TEXT(format!("v{}", i))),
+                        ],
+                    )
+                    .unwrap();
+                writer.write("d2", "s1", i, IoTDBValue::INT(1)).unwrap();
+            }
+        });
+
+        // Any corrupted byte leads to errors or wrong values, but never to a panic
+        for position in 0..buffer.len() {
+            for value in [0x00, 0x7F, 0xFF] {
+                let mut corrupt = buffer.clone();
+                corrupt[position] = value;
+                let mut reader = match TsFileReader::new(Cursor::new(corrupt)) {
+                    Ok(reader) => reader,
+                    Err(_) => continue,
+                };
+                for (device, measurement) in [("d1", "s1"), ("d1", "s3"), ("d2", "s1")] {
+                    if let Ok(points) = reader.query(device, measurement) {
+                        points.for_each(drop);
+                    }
+                }
+            }
+        }
+    }
+
+    fn aligned_schema() -> Schema<'static> {
+        TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s3",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn read_aligned() {
+        let buffer = write_to_buffer(aligned_schema(), |writer| {
+            for i in 0..100 {
+                // s2 is only written for even rows, s3 never
+                let mut values = vec![DataPoint::new("s1", IoTDBValue::LONG(i * 3))];
+                if i % 2 == 0 {
+                    values.push(DataPoint::new("s2", IoTDBValue::DOUBLE(i as f64 / 2.0)));
+                }
+                writer.write_many("d1", i, values).unwrap();
+                writer
+                    .write("d2", "s1", i, IoTDBValue::INT(i as i32))
+                    .unwrap();
+            }
+        });
+
+        let points = query(buffer.clone(), "d1", "s1");
+        assert_eq!(points.len(), 100);
+        assert_eq!(points[99], (99, IoTDBValue::LONG(297)));
+
+        let points = query(buffer.clone(), "d1", "s2");
+        assert_eq!(
+            points,
+            (0..100)
+                .step_by(2)
+                .map(|i| (i, IoTDBValue::DOUBLE(i as f64 / 2.0)))
+                .collect::<Vec<_>>()
+        );
+
+        // Only nulls, no chunk is written
+        assert!(query(buffer.clone(), "d1", "s3").is_empty());
+
+        let points = query(buffer.clone(), "d2", "s1");
+        assert_eq!(points.len(), 100);
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        let time_metadata = reader.get_timeseries_metadata("d1", "").unwrap().unwrap();
+        assert_eq!(time_metadata.data_type, TSDataType::VECTOR);
+        assert_eq!(time_metadata.statistics.count(), 100);
+
+        let time_chunk = reader
+            .read_chunk(&time_metadata.chunk_metadata_list[0])
+            .unwrap();
+        assert!(time_chunk.header().is_time_chunk());
+        let value_metadata = reader.get_timeseries_metadata("d1", "s2").unwrap().unwrap();
+        let value_chunk = reader
+            .read_chunk(&value_metadata.chunk_metadata_list[0])
+            .unwrap();
+        assert!(value_chunk.header().is_value_chunk());
+        assert!(value_chunk.read_all().is_err());
+        assert_eq!(value_chunk.read_aligned(&time_chunk).unwrap().len(), 50);
+    }
+
+    #[test]
+    fn read_aligned_multiple_pages() {
+        let buffer = write_to_buffer(aligned_schema(), |writer| {
+            for i in 0..100_000 {
+                let mut values = vec![DataPoint::new("s1", IoTDBValue::LONG(i))];
+                if i % 3 == 0 {
+                    values.push(DataPoint::new("s3", IoTDBValue::TEXT(format!("v{}", i))));
+                }
+                writer.write_many("d1", i, values).unwrap();
+            }
+        });
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer.clone())).unwrap();
+        let time_metadata = reader.get_timeseries_metadata("d1", "").unwrap().unwrap();
+        let time_chunk = reader
+            .read_chunk(&time_metadata.chunk_metadata_list[0])
+            .unwrap();
+        assert!(time_chunk.pages().unwrap().len() > 1);
+
+        let points = query(buffer.clone(), "d1", "s1");
+        assert_eq!(points.len(), 100_000);
+        assert!(points
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p == &(i as i64, IoTDBValue::LONG(i as i64))));
+
+        let points = query(buffer.clone(), "d1", "s3");
+        assert_eq!(points.len(), 33_334);
+        assert_eq!(
+            points.last(),
+            Some(&(99_999, IoTDBValue::TEXT("v99999".to_owned())))
+        );
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        let timestamps: Vec<i64> = reader
+            .query_builder("d1", "s3")
+            .time_range(50_000, 50_010)
+            .build()
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect();
+        assert_eq!(timestamps, vec![50_001, 50_004, 50_007, 50_010]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Repairs TsFiles that are truncated or whose metadata section is damaged, e.g. after a crash
+//! or an interrupted copy.
+//!
+//! The file is truncated at the end of its last complete chunk group (see `self_check`). The
+//! timeseries metadata, the metadata index and the bloom filter are rebuilt from the chunk
+//! headers of the remaining data section and a new footer is written. Unlike
+//! `TsFileWriter::recover` no writer is needed, so this works without the schema of the file.
+use std::fs::OpenOptions;
+use std::io::BufReader;
+
+use crate::reader::self_check::{self_check, FileStatus};
+use crate::writer::errors::TsFileError;
+use crate::writer::recover::{scan_data_section, write_metadata};
+use crate::writer::ts_file_config::TsFileConfig;
+
+/// Result of `repair_file`
+#[derive(Clone, Debug)]
+pub struct Repair {
+    /// Status of the file before the repair, nothing was changed for `Complete` files
+    pub status: FileStatus,
+    /// End of the data section that was kept
+    pub truncation_point: u64,
+    /// Number of chunk groups that were kept
+    pub chunk_groups: usize,
+    /// Size of the file before the repair
+    pub original_size: u64,
+    /// Size of the file after the repair
+    pub file_size: u64,
+    /// Why the file was repaired, `None` for complete files
+    pub reason: Option<String>,
+}
+
+/// Repairs the file in place, complete files are not changed.
+///
+/// `config` determines the footer of the repaired file, e.g. its bloom filter and properties.
+/// User defined properties of the old footer are not kept.
+///
+/// Fails if the file does not start with the file header of a TsFile.
+///
+/// ```no_run
+/// use tsfile_writer::repair::repair_file;
+///
+/// let repair = repair_file("crashed.tsfile", Default::default()).unwrap();
+/// println!(
+///     "Kept {} chunk groups, removed {} bytes",
+///     repair.chunk_groups,
+///     repair.original_size - repair.truncation_point
+/// );
+/// ```
+pub fn repair_file(filename: &str, config: TsFileConfig) -> Result<Repair, TsFileError> {
+    config.validate()?;
+    let file = OpenOptions::new().read(true).write(true).open(filename)?;
+    let check = self_check(&mut BufReader::new(&file))?;
+    let mut repair = Repair {
+        status: check.status,
+        truncation_point: check.truncation_point,
+        chunk_groups: check.chunk_groups,
+        original_size: check.file_size,
+        file_size: check.file_size,
+        reason: check.reason,
+    };
+    if check.status == FileStatus::Complete {
+        return Ok(repair);
+    }
+    if check.truncation_point == 0 {
+        return Err(TsFileError::IllegalState {
+            source: Some(format!(
+                "File {} has no TsFile header, nothing to repair",
+                filename
+            )),
+        });
+    }
+
+    // Only complete chunk groups are left up to the truncation point
+    let data_section = scan_data_section(&mut BufReader::new(&file), check.truncation_point)?;
+    log::info!(
+        "Repairing {}, keeping {} chunk groups and truncating {} bytes",
+        filename,
+        data_section.chunk_groups.len(),
+        check.file_size - check.truncation_point
+    );
+    write_metadata(
+        file,
+        check.truncation_point,
+        config,
+        data_section.chunk_groups,
+    )?;
+    repair.file_size = std::fs::metadata(filename)?.len();
+    Ok(repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::self_check::FileStatus;
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::repair::repair_file;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    /// Three chunk groups of a non aligned and an aligned device, returns the file and the
+    /// offset of the metadata section
+    fn write(filename: &str) -> (Vec<u8>, u64) {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new(filename, schema, Default::default()).unwrap();
+        for i in 0..3 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+            writer.flush().unwrap();
+        }
+        writer.close().unwrap();
+        let meta_offset = TsFileReader::open(filename).unwrap().metadata().meta_offset as u64;
+        (std::fs::read(filename).unwrap(), meta_offset)
+    }
+
+    fn points(filename: &str, device: &str) -> Vec<i64> {
+        TsFileReader::open(filename)
+            .unwrap()
+            .query(device, "s1")
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn repair_truncated_file() {
+        let filename = "target/repair_truncated.tsfile";
+        let (buffer, meta_offset) = write(filename);
+
+        // The last chunk of the last chunk group is incomplete
+        std::fs::write(filename, &buffer[..meta_offset as usize - 3]).unwrap();
+        let repair = repair_file(filename, Default::default()).unwrap();
+        assert_eq!(repair.status, FileStatus::Incomplete);
+        assert_eq!(repair.chunk_groups, 5);
+        assert!(repair.truncation_point < meta_offset);
+        assert!(repair.reason.is_some());
+        assert_eq!(repair.file_size, std::fs::metadata(filename).unwrap().len());
+        assert_eq!(points(filename, "d1"), vec![0, 1, 2]);
+        assert_eq!(points(filename, "d2"), vec![0, 1]);
+
+        // Repaired files are complete
+        let repair = repair_file(filename, Default::default()).unwrap();
+        assert_eq!(repair.status, FileStatus::Complete);
+        assert_eq!(repair.file_size, repair.original_size);
+    }
+
+    #[test]
+    fn repair_damaged_footer() {
+        let filename = "target/repair_footer.tsfile";
+        let (mut buffer, meta_offset) = write(filename);
+        let position = buffer.len() - 10;
+        buffer[position..position + 4].copy_from_slice(&[0xFF; 4]);
+        std::fs::write(filename, &buffer).unwrap();
+
+        let repair = repair_file(filename, Default::default()).unwrap();
+        assert_eq!(repair.status, FileStatus::Corrupt);
+        assert_eq!(repair.chunk_groups, 6);
+        assert_eq!(repair.truncation_point, meta_offset);
+        assert_eq!(std::fs::read(filename).unwrap(), write(filename).0);
+    }
+
+    #[test]
+    fn repair_complete_file() {
+        let filename = "target/repair_complete.tsfile";
+        let (buffer, _) = write(filename);
+        let repair = repair_file(filename, Default::default()).unwrap();
+        assert_eq!(repair.status, FileStatus::Complete);
+        assert_eq!(repair.chunk_groups, 6);
+        assert_eq!(std::fs::read(filename).unwrap(), buffer);
+    }
+
+    #[test]
+    fn repair_no_tsfile() {
+        let filename = "target/repair_no_tsfile.tsfile";
+        std::fs::write(filename, b"NoTsFile").unwrap();
+        assert!(matches!(
+            repair_file(filename, Default::default()),
+            Err(crate::writer::errors::TsFileError::IllegalState { .. })
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+//! Splits a TsFile into several files, e.g. a large export into one file per plant or per day.
+//!
+//! Devices are assigned to files by a prefix of their path or their points by time windows.
+//! Chunk groups that belong to a single file are copied without decoding and re-encoding them,
+//! like `TsFileMerger` does. The points of a device with chunk groups that span several time
+//! windows are re-encoded, so such a device has to fit into memory.
+use std::collections::{BTreeMap, HashMap};
+use std::fs::create_dir_all;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::merge::copy_chunk_groups;
+use crate::reader::layout::ChunkGroupLayout;
+use crate::reader::schema::FileSchema;
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::writer::errors::TsFileError;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::{create_file, sync_handle, DataPoint, TsFileWriter};
+use crate::writer::{IoTDBValue, Schema, WriteWrapper};
+
+/// How the content of a file is assigned to the parts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplitBy {
+    /// Devices with the same first nodes of their path share a file, e.g. `root.plant1` for
+    /// `root.plant1.line1` and `root.plant1.line2` with 2 nodes
+    DevicePrefix(usize),
+    /// Points of the same time window share a file, windows of the given length start at
+    /// multiples of it
+    TimeWindow(i64),
+}
+
+/// Splits a TsFile into several files
+///
+/// ```no_run
+/// use tsfile_writer::split::{SplitBy, TsFileSplitter};
+///
+/// // One file per day
+/// let parts = TsFileSplitter::new(SplitBy::TimeWindow(86_400_000))
+///     .split_file("export.tsfile", "parts")
+///     .unwrap();
+/// ```
+pub struct TsFileSplitter {
+    split_by: SplitBy,
+    copy_chunks: bool,
+    config: TsFileConfig,
+}
+
+type PartWriter<'a> = TsFileWriter<'a, WriteWrapper<std::fs::File>>;
+
+impl TsFileSplitter {
+    pub fn new(split_by: SplitBy) -> TsFileSplitter {
+        TsFileSplitter {
+            split_by,
+            copy_chunks: true,
+            config: TsFileConfig::default(),
+        }
+    }
+
+    /// Copies chunk groups that belong to a single part, enabled by default. If disabled, all
+    /// points are re-encoded.
+    pub fn copy_chunks(&mut self, copy_chunks: bool) -> &mut TsFileSplitter {
+        self.copy_chunks = copy_chunks;
+        self
+    }
+
+    pub fn config(&mut self, config: TsFileConfig) -> &mut TsFileSplitter {
+        self.config = config;
+        self
+    }
+
+    /// Writes the parts into the directory, missing directories are created. Parts are named
+    /// after the device prefix or the start of the time window, e.g. `root.plant1.tsfile` or
+    /// `1640995200000.tsfile`. Returns the paths of the parts in order of their names.
+    pub fn split_file(&self, source: &str, directory: &str) -> Result<Vec<PathBuf>, TsFileError> {
+        let mut reader = TsFileReader::open(source)?;
+        create_dir_all(directory)?;
+        self.split(&mut reader, Path::new(directory))
+    }
+
+    /// Splits the content of the reader into files in the directory, see `split_file`
+    pub fn split<R: Read + Seek>(
+        &self,
+        source: &mut TsFileReader<R>,
+        directory: &Path,
+    ) -> Result<Vec<PathBuf>, TsFileError> {
+        if let SplitBy::DevicePrefix(0) | SplitBy::TimeWindow(i64::MIN..=0) = self.split_by {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!("Invalid split {:?}", self.split_by)),
+            });
+        }
+        let mut file_schema = FileSchema::new();
+        file_schema.add_file(source)?;
+        let schema = file_schema.schema()?;
+        let chunk_groups = source.chunk_groups()?;
+
+        let mut parts: BTreeMap<String, PartWriter> = BTreeMap::new();
+        for (device, device_schema) in &file_schema.devices {
+            let device_groups: Vec<&ChunkGroupLayout> = chunk_groups
+                .iter()
+                .filter(|chunk_group| &chunk_group.device == device)
+                .collect();
+            match self.split_by {
+                SplitBy::DevicePrefix(nodes) => {
+                    let writer =
+                        self.part(&mut parts, directory, &schema, &prefix(device, nodes))?;
+                    if self.copy_chunks {
+                        let groups: Vec<_> = device_groups.into_iter().cloned().collect();
+                        copy_chunk_groups(source, &groups, device, writer)?;
+                    } else {
+                        for measurement in device_schema.measurements.keys() {
+                            for point in source.query(device, measurement)? {
+                                let (timestamp, value) = point?;
+                                writer.write(device, measurement, timestamp, value)?;
+                            }
+                        }
+                        writer.flush()?;
+                    }
+                }
+                SplitBy::TimeWindow(length) => {
+                    let windows = match self.copy_chunks {
+                        true => chunk_group_windows(source, device, &device_groups, length)?,
+                        false => None,
+                    };
+                    if let Some(windows) = windows {
+                        for (window, groups) in windows {
+                            let key = (window * length).to_string();
+                            let writer = self.part(&mut parts, directory, &schema, &key)?;
+                            copy_chunk_groups(source, &groups, device, writer)?;
+                        }
+                        continue;
+                    }
+                    let mut rows: BTreeMap<i64, Vec<(&str, IoTDBValue)>> = BTreeMap::new();
+                    for measurement in device_schema.measurements.keys() {
+                        for point in source.query(device, measurement)? {
+                            let (timestamp, value) = point?;
+                            rows.entry(timestamp)
+                                .or_default()
+                                .push((measurement, value));
+                        }
+                    }
+                    for (timestamp, row) in rows {
+                        let key = (timestamp.div_euclid(length) * length).to_string();
+                        let writer = self.part(&mut parts, directory, &schema, &key)?;
+                        writer.write_many(
+                            device,
+                            timestamp,
+                            row.into_iter()
+                                .map(|(measurement, value)| DataPoint::new(measurement, value)),
+                        )?;
+                    }
+                    // Only one device is kept in memory
+                    for writer in parts.values_mut() {
+                        writer.flush()?;
+                    }
+                }
+            }
+        }
+
+        let mut paths = vec![];
+        for (_, mut writer) in parts {
+            writer.close()?;
+            paths.push(PathBuf::from(&writer.filename));
+        }
+        Ok(paths)
+    }
+
+    /// The writer of the part, which is created on first use
+    fn part<'a, 'p>(
+        &self,
+        parts: &'p mut BTreeMap<String, PartWriter<'a>>,
+        directory: &Path,
+        schema: &Schema<'a>,
+        key: &str,
+    ) -> Result<&'p mut PartWriter<'a>, TsFileError> {
+        if !parts.contains_key(key) {
+            let path = directory.join(format!("{}.tsfile", key));
+            let file = create_file(&path, &self.config)?;
+            let sync_file = Some(sync_handle(&file)?);
+            let mut writer = TsFileWriter::new_from_writer(
+                schema.clone(),
+                WriteWrapper::new(file),
+                self.config,
+            )?;
+            writer.filename = path.to_string_lossy().into_owned();
+            writer.file_io_writer.sync_file = sync_file;
+            parts.insert(key.to_owned(), writer);
+        }
+        match parts.get_mut(key) {
+            Some(writer) => Ok(writer),
+            None => Err(TsFileError::IllegalState {
+                source: Some(format!("No part {}", key)),
+            }),
+        }
+    }
+}
+
+/// The first nodes of the device path
+fn prefix(device: &str, nodes: usize) -> String {
+    device.split('.').take(nodes).collect::<Vec<_>>().join(".")
+}
+
+/// The chunk groups of the device by their time window, `None` if a chunk group spans several
+/// windows
+fn chunk_group_windows<R: Read + Seek>(
+    source: &mut TsFileReader<R>,
+    device: &str,
+    chunk_groups: &[&ChunkGroupLayout],
+    length: i64,
+) -> Result<Option<BTreeMap<i64, Vec<ChunkGroupLayout>>>, TsFileError> {
+    let ranges: HashMap<i64, (i64, i64)> = source
+        .metadata_reader()
+        .get_device_timeseries_metadata(device)?
+        .into_iter()
+        .flat_map(|timeseries| timeseries.chunk_metadata_list)
+        .map(|chunk| {
+            let range = (chunk.statistics.start_time(), chunk.statistics.end_time());
+            (chunk.offset_of_chunk_header, range)
+        })
+        .collect();
+    let mut windows: BTreeMap<i64, Vec<ChunkGroupLayout>> = BTreeMap::new();
+    for chunk_group in chunk_groups {
+        let mut window = None;
+        for chunk in &chunk_group.chunks {
+            let (start, end) = match ranges.get(&chunk.offset) {
+                Some(range) => *range,
+                None => return Ok(None),
+            };
+            let (first, last) = (start.div_euclid(length), end.div_euclid(length));
+            if first != last || window.unwrap_or(first) != first {
+                return Ok(None);
+            }
+            window = Some(first);
+        }
+        if let Some(window) = window {
+            windows
+                .entry(window)
+                .or_default()
+                .push((*chunk_group).clone());
+        }
+    }
+    Ok(Some(windows))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::split::{SplitBy, TsFileSplitter};
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    /// Points 0..100 of three devices, flushed every `flush` points
+    fn write(path: &str, flush: i64) {
+        let mut schema = TsFileSchemaBuilder::new();
+        for device in ["root.p1.d1", "root.p1.d2"] {
+            schema.add(
+                device,
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            );
+        }
+        schema.add(
+            "root.p2.d1",
+            DeviceBuilder::aligned()
+                .add(
+                    "s1",
+                    TSDataType::DOUBLE,
+                    TSEncoding::GORILLA,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build(),
+        );
+        let mut writer = TsFileWriter::new(path, schema.build(), Default::default()).unwrap();
+        for i in 0..100 {
+            writer
+                .write("root.p1.d1", "s1", i, IoTDBValue::LONG(i))
+                .unwrap();
+            writer
+                .write("root.p1.d2", "s1", i, IoTDBValue::LONG(-i))
+                .unwrap();
+            writer
+                .write("root.p2.d1", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+            if (i + 1) % flush == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        writer.close().unwrap();
+    }
+
+    fn points(path: &PathBuf, device: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(File::open(path).unwrap()).unwrap();
+        reader
+            .query(device, "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn chunk_groups(path: &PathBuf) -> usize {
+        let mut reader = TsFileReader::new(File::open(path).unwrap()).unwrap();
+        reader.chunk_groups().unwrap().len()
+    }
+
+    #[test]
+    fn split_by_device_prefix() -> Result<(), TsFileError> {
+        write("target/split_device.tsfile", 50);
+        let parts = TsFileSplitter::new(SplitBy::DevicePrefix(2))
+            .split_file("target/split_device.tsfile", "target/split_device")?;
+        assert_eq!(
+            parts,
+            vec![
+                PathBuf::from("target/split_device/root.p1.tsfile"),
+                PathBuf::from("target/split_device/root.p2.tsfile")
+            ]
+        );
+        assert_eq!(
+            points(&parts[0], "root.p1.d2")[99],
+            (99, IoTDBValue::LONG(-99))
+        );
+        assert!(points(&parts[0], "root.p2.d1").is_empty());
+        assert_eq!(points(&parts[1], "root.p2.d1").len(), 100);
+        // Chunk groups are copied
+        assert_eq!(chunk_groups(&parts[0]), 4);
+
+        let parts = TsFileSplitter::new(SplitBy::DevicePrefix(2))
+            .copy_chunks(false)
+            .split_file("target/split_device.tsfile", "target/split_device_encoded")?;
+        assert_eq!(chunk_groups(&parts[0]), 2);
+        assert_eq!(points(&parts[0], "root.p1.d1").len(), 100);
+        Ok(())
+    }
+
+    #[test]
+    fn split_by_time_window() -> Result<(), TsFileError> {
+        for (flush, copied) in [(25, true), (30, false)] {
+            let source = format!("target/split_time_{}.tsfile", flush);
+            let directory = format!("target/split_time_{}", flush);
+            write(&source, flush);
+            let parts =
+                TsFileSplitter::new(SplitBy::TimeWindow(50)).split_file(&source, &directory)?;
+            assert_eq!(parts.len(), 2);
+            assert!(parts[0].ends_with("0.tsfile"));
+            assert!(parts[1].ends_with("50.tsfile"));
+            for (index, part) in parts.iter().enumerate() {
+                let start = index as i64 * 50;
+                let d1 = points(part, "root.p1.d1");
+                assert_eq!(d1.len(), 50);
+                assert_eq!(d1[0], (start, IoTDBValue::LONG(start)));
+                let p2 = points(part, "root.p2.d1");
+                assert_eq!(
+                    p2[49],
+                    (start + 49, IoTDBValue::DOUBLE((start + 49) as f64))
+                );
+            }
+            // Two chunk groups of 25 points per device and window are copied, chunk groups of
+            // 30 points span both windows and are re-encoded
+            let expected = if copied { 6 } else { 3 };
+            assert_eq!(chunk_groups(&parts[0]), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_split() {
+        write("target/split_invalid.tsfile", 50);
+        for split_by in [SplitBy::TimeWindow(0), SplitBy::DevicePrefix(0)] {
+            assert!(matches!(
+                TsFileSplitter::new(split_by)
+                    .split_file("target/split_invalid.tsfile", "target/split_invalid"),
+                Err(TsFileError::IllegalState { .. })
+            ));
+        }
+    }
+}
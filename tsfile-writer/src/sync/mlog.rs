@@ -150,7 +150,7 @@ mod test {
         ];
 
         let mut m_log = MLog::new();
-        m_log.set_storage_group_plan("root.sg");
+        m_log.set_storage_group_plan("root.sg").unwrap();
         // m_log.create_plan("root.sg.d1.s1", TSDataType::INT32, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
 
         let mut mlog_buffer: Vec<u8> = vec![];
@@ -171,16 +171,18 @@ mod test {
 
         let mut m_log = MLog::new();
         // m_log.set_storage_group_plan("root.sg");
-        m_log.create_plan(
-            "root.sg.d1.s1",
-            TSDataType::INT32,
-            TSEncoding::PLAIN,
-            CompressionType::UNCOMPRESSED,
-        );
+        m_log
+            .create_plan(
+                "root.sg.d1.s1",
+                TSDataType::INT32,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .unwrap();
 
         let mut mlog_buffer: Vec<u8> = vec![];
 
-        m_log.flush(&mut mlog_buffer);
+        m_log.flush(&mut mlog_buffer).unwrap();
 
         assert_eq!(expected, mlog_buffer.deref());
     }
@@ -199,16 +201,18 @@ mod test {
         let mut m_log = MLog::new();
         let mut mlog_buffer: Vec<u8> = vec![];
 
-        m_log.set_storage_group_plan("root.sg");
-        m_log.flush(&mut mlog_buffer);
+        m_log.set_storage_group_plan("root.sg").unwrap();
+        m_log.flush(&mut mlog_buffer).unwrap();
 
-        m_log.create_plan(
-            "root.sg.d1.s1",
-            TSDataType::INT32,
-            TSEncoding::PLAIN,
-            CompressionType::UNCOMPRESSED,
-        );
-        m_log.flush(&mut mlog_buffer);
+        m_log
+            .create_plan(
+                "root.sg.d1.s1",
+                TSDataType::INT32,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .unwrap();
+        m_log.flush(&mut mlog_buffer).unwrap();
 
         assert_eq!(expected, mlog_buffer.deref());
     }
@@ -6,7 +6,7 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 #![allow(unreachable_patterns)]
-#![allow(clippy::too_many_arguments, clippy::type_complexity, clippy::vec_box, clippy::match_single_binding)]
+#![allow(clippy::too_many_arguments, clippy::type_complexity, clippy::vec_box, clippy::match_single_binding, clippy::unnecessary_unwrap)]
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
 use std::cell::RefCell;
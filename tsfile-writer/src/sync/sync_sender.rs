@@ -1,4 +1,5 @@
 use std::fs;
+use std::path::Path;
 
 use pnet::datalink;
 use sha2::Digest;
@@ -10,7 +11,9 @@ use thrift::transport::{
 };
 use thrift::Error;
 
+use crate::reader::schema::FileSchema;
 use crate::writer::errors::TsFileError;
+use crate::writer::resource::TsFileResource;
 use crate::writer::Schema;
 
 use crate::sync::mlog::MLog;
@@ -36,6 +39,10 @@ pub enum SyncSenderError {
     UnableGenerateUuid,
     ConnectionError,
     HandshakeError,
+    /// The receiver computed a different digest for the file with the given name
+    DigestMismatch(String),
+    /// The file to send is not a closed TsFile or has no `.resource` file
+    FileError(TsFileError),
 }
 
 impl From<thrift::Error> for SyncSenderError {
@@ -44,6 +51,21 @@ impl From<thrift::Error> for SyncSenderError {
     }
 }
 
+impl From<TsFileError> for SyncSenderError {
+    fn from(e: TsFileError) -> Self {
+        SyncSenderError::FileError(e)
+    }
+}
+
+/// Ships a closed TsFile and its `.resource` file to the sync receiver of the IoTDB server at
+/// `endpoint` (`host:port`), which loads it into the storage group. The timeseries of the file
+/// are created first. The file is checked before the connection is opened.
+pub fn load_tsfile(path: &str, endpoint: &str, storage_group: &str) -> Result<(), SyncSenderError> {
+    let files = SyncSender::files_to_load(path, storage_group)?;
+    let mut sender = SyncSender::new(endpoint, None, None)?;
+    sender.send_files(storage_group, &files)
+}
+
 impl
     SyncSender<
         TBinaryInputProtocol<TFramedReadTransport<ReadHalf<TTcpChannel>>>,
@@ -65,8 +87,8 @@ impl
         let local_addr = match local_addr {
             None => {
                 // GET Info
-                let ip = datalink::interfaces().get(0).map(|interface| {
-                    interface.ips.get(0).map_or_else(
+                let ip = datalink::interfaces().first().map(|interface| {
+                    interface.ips.first().map_or_else(
                         || std::string::String::from("127.0.0.1"),
                         |ip| ip.ip().to_string(),
                     )
@@ -123,63 +145,65 @@ impl
         }
     }
 
-    #[allow(dead_code)]
-    fn sync(
-        &mut self,
-        filename: &str,
-        storage_group: &str,
-        schema: Schema,
-    ) -> Result<(), SyncSenderError> {
-        self.client.start_sync().expect("");
-        self.client.init(std::string::String::from(storage_group))?;
-
-        // First sync a schema
-        self.client
-            .init_sync_data(std::string::String::from("mlog.bin"))?;
-
-        // Create the mlog
-        let mlog_bytes: Vec<u8> =
-            Self::write_mlog(storage_group, schema).expect("Unable to write mlog");
-
-        self.client.sync_data(mlog_bytes.clone())?;
-
-        let digest = Self::calculate_digest(&mlog_bytes);
-        println!("Digest of Sender: {}", digest);
+    /// Ships a closed TsFile and its `.resource` file over this connection, see `load_tsfile`
+    pub fn load_tsfile(&mut self, path: &str, storage_group: &str) -> Result<(), SyncSenderError> {
+        let files = Self::files_to_load(path, storage_group)?;
+        self.send_files(storage_group, &files)
+    }
 
-        match self.client.check_data_digest(digest) {
-            Ok(result) => {
-                println!("Result: {}, {}", result.code, result.msg);
-                if result.code == -1 {
-                    panic!("Digest does not match!")
-                }
-            }
-            _ => {
-                panic!("Error on digest!")
+    /// The schema (as mlog), the TsFile and its resource with the names they are sent with.
+    /// Fails if the file is not closed or has no `.resource` file.
+    fn files_to_load(
+        path: &str,
+        storage_group: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>, SyncSenderError> {
+        let file_schema = FileSchema::open(path)?;
+        let mlog = Self::write_mlog(storage_group, file_schema.schema()?)?;
+        let tsfile = fs::read(path).map_err(TsFileError::from)?;
+        let resource_path = TsFileResource::path(path);
+        let resource = fs::read(&resource_path).map_err(|e| TsFileError::Error {
+            source: Some(format!("Unable to read {}: {}", resource_path, e)),
+        })?;
+        TsFileResource::deserialize(&mut resource.as_slice())?;
+
+        let filename = match Path::new(path).file_name() {
+            Some(filename) => format!("0_0_{}", filename.to_string_lossy()),
+            None => {
+                return Err(SyncSenderError::FileError(TsFileError::Error {
+                    source: Some(format!("{} is not a file", path)),
+                }))
             }
-        }
-
-        let filename = format!("0_0_{}", filename); // 1654074550252-1-0-0.tsfile";
+        };
+        Ok(vec![
+            ("mlog.bin".to_owned(), mlog),
+            (TsFileResource::path(&filename), resource),
+            (filename, tsfile),
+        ])
+    }
 
-        self.client.init_sync_data(filename.to_string()).expect("");
-        let bytes = fs::read(filename).expect("");
-        self.client.sync_data(bytes.clone()).expect("");
-        let digest = Self::calculate_digest(&bytes);
-        println!("Digest of Sender: {}", digest);
-        match self.client.check_data_digest(digest) {
-            Ok(result) => {
-                println!("Result: {}, {}", result.code, result.msg);
-            }
-            _ => {
-                println!("Error on digest!")
+    fn send_files(
+        &mut self,
+        storage_group: &str,
+        files: &[(String, Vec<u8>)],
+    ) -> Result<(), SyncSenderError> {
+        self.client.start_sync()?;
+        self.client.init(storage_group.to_owned())?;
+        for (filename, bytes) in files {
+            self.client.init_sync_data(filename.clone())?;
+            self.client.sync_data(bytes.clone())?;
+            let digest = Self::calculate_digest(bytes);
+            let result = self.client.check_data_digest(digest)?;
+            log::info!("Sent {}: {}, {}", filename, result.code, result.msg);
+            if result.code == -1 {
+                return Err(SyncSenderError::DigestMismatch(filename.clone()));
             }
         }
         self.client.end_sync()?;
         Ok(())
     }
 
-    #[allow(dead_code)]
     fn calculate_digest(writer: &[u8]) -> std::string::String {
-        let sha256 = sha2::Sha256::digest(&writer);
+        let sha256 = sha2::Sha256::digest(writer);
         let digest = sha256.as_slice();
         let digest = hex::encode(digest);
 
@@ -189,7 +213,6 @@ impl
         digest
     }
 
-    #[allow(dead_code)]
     fn write_mlog(storage_group: &str, schema: Schema) -> Result<Vec<u8>, TsFileError> {
         // Create the mlog
         let mut m_log = MLog::new();
@@ -201,7 +224,11 @@ impl
         // Create a plan for each timeseries in Schema
         for (device_id, series) in schema.get_devices() {
             for (measurement_id, timeseries) in series.get_timeseries() {
-                let path = format!("{}.{}.{}", storage_group, device_id, measurement_id);
+                // Devices are relative to the storage group unless they contain it already
+                let path = match device_id.starts_with(&format!("{}.", storage_group)) {
+                    true => format!("{}.{}", device_id, measurement_id),
+                    false => format!("{}.{}.{}", storage_group, device_id, measurement_id),
+                };
                 m_log.create_plan(
                     path.as_str(),
                     timeseries.data_type,
@@ -242,11 +269,55 @@ impl
 
 #[cfg(test)]
 mod tests {
-    use crate::sync::sync_sender::SyncSender;
+    use crate::sync::sync_sender::{SyncSender, SyncSenderError};
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::resource::TsFileResource;
+    use crate::writer::tsfile_writer::TsFileWriterBuilder;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::fs;
 
     #[test]
     #[ignore]
     fn initalize() {
-        let sender = SyncSender::new("129.168.169.1", None, None).unwrap();
+        let _sender = SyncSender::new("129.168.169.1", None, None).unwrap();
+    }
+
+    #[test]
+    fn files_to_load() {
+        let filename = "target/sync_sender.tsfile";
+        let schema = Schema::simple(
+            "root.sg.d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .write_resource(false)
+            .build(filename)
+            .unwrap();
+        writer
+            .write("root.sg.d1", "s1", 1, IoTDBValue::LONG(1))
+            .unwrap();
+        writer.close().unwrap();
+        let _ = fs::remove_file(TsFileResource::path(filename));
+        assert!(matches!(
+            SyncSender::files_to_load(filename, "root.sg"),
+            Err(SyncSenderError::FileError(_))
+        ));
+
+        writer.resource().write_file(filename).unwrap();
+        let files = SyncSender::files_to_load(filename, "root.sg").unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mlog.bin",
+                "0_0_sync_sender.tsfile.resource",
+                "0_0_sync_sender.tsfile"
+            ]
+        );
+        assert_eq!(files[2].1, fs::read(filename).unwrap());
     }
 }
@@ -0,0 +1,294 @@
+//! Ingestion of Arrow `RecordBatch`es (feature `arrow`).
+//!
+//! A record batch is converted into a `Tablet` with the following type mapping:
+//!
+//! | Arrow                              | TsFile |
+//! |------------------------------------|--------|
+//! | Int8, Int16, Int32, UInt8, UInt16  | INT32  |
+//! | Int64, UInt32                      | INT64  |
+//! | Float32                            | FLOAT  |
+//! | Float64                            | DOUBLE |
+//! | Utf8, LargeUtf8                    | TEXT   |
+//!
+//! The time column has to be an Int64 or a Timestamp column without nulls, its values are
+//! taken as is, i.e. without unit conversion.
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type,
+    UInt8Type,
+};
+use arrow_array::{Array, ArrowPrimitiveType, RecordBatch};
+use arrow_schema::{DataType, TimeUnit};
+
+use crate::writer::errors::TsFileError;
+use crate::writer::tablet::Tablet;
+use crate::writer::tsfile_writer::TsFileWriter;
+use crate::writer::{IoTDBValue, PositionedWrite, TSDataType};
+
+impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
+    /// Writes all rows of the record batch to the given device, every column except the
+    /// time column is written to the measurement with the same name
+    pub fn write_record_batch(
+        &mut self,
+        device: &'a str,
+        batch: &RecordBatch,
+        time_column: &str,
+    ) -> Result<(), TsFileError> {
+        self.write_tablet(device, tablet_from_record_batch(batch, time_column)?)
+    }
+}
+
+/// Converts the record batch into a `Tablet`, null values are marked in the bitmap
+pub fn tablet_from_record_batch<'b>(
+    batch: &'b RecordBatch,
+    time_column: &str,
+) -> Result<Tablet<'b>, TsFileError> {
+    let timestamps = match batch.column_by_name(time_column) {
+        Some(column) => timestamps(column.as_ref())?,
+        None => {
+            return Err(TsFileError::SchemaError {
+                source: Some(format!("No time column {} in record batch", time_column)),
+            });
+        }
+    };
+
+    let mut tablet = Tablet::new(timestamps);
+    for (field, column) in batch.schema_ref().fields().iter().zip(batch.columns()) {
+        if field.name() == time_column {
+            continue;
+        }
+        let values = values(column.as_ref())?;
+        if column.null_count() > 0 {
+            let bitmap = (0..column.len()).map(|row| column.is_null(row)).collect();
+            tablet.add_column_with_bitmap(field.name(), values, bitmap)?;
+        } else {
+            tablet.add_column(field.name(), values)?;
+        }
+    }
+    Ok(tablet)
+}
+
+fn timestamps(column: &dyn Array) -> Result<Vec<i64>, TsFileError> {
+    if column.null_count() > 0 {
+        return Err(TsFileError::IllegalState {
+            source: Some("Time column contains null values".to_owned()),
+        });
+    }
+    let timestamps = match column.data_type() {
+        DataType::Int64 => column.as_primitive::<Int64Type>().values().to_vec(),
+        DataType::Timestamp(TimeUnit::Second, _) => column
+            .as_primitive::<TimestampSecondType>()
+            .values()
+            .to_vec(),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => column
+            .as_primitive::<TimestampMillisecondType>()
+            .values()
+            .to_vec(),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => column
+            .as_primitive::<TimestampMicrosecondType>()
+            .values()
+            .to_vec(),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => column
+            .as_primitive::<TimestampNanosecondType>()
+            .values()
+            .to_vec(),
+        other => {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!("Time column of type {}", other)),
+            });
+        }
+    };
+    Ok(timestamps)
+}
+
+/// TsFile type of an Arrow value column, see the type mapping of the module
+pub fn ts_data_type(data_type: &DataType) -> Result<TSDataType, TsFileError> {
+    match data_type {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            Ok(TSDataType::INT32)
+        }
+        DataType::Int64 | DataType::UInt32 => Ok(TSDataType::INT64),
+        DataType::Float32 => Ok(TSDataType::FLOAT),
+        DataType::Float64 => Ok(TSDataType::DOUBLE),
+        DataType::Utf8 | DataType::LargeUtf8 => Ok(TSDataType::TEXT),
+        other => Err(TsFileError::UnsupportedFeature {
+            source: Some(format!("Arrow type {}", other)),
+        }),
+    }
+}
+
+/// Values of null rows are arbitrary, they are masked by the bitmap
+fn values(column: &dyn Array) -> Result<Vec<IoTDBValue>, TsFileError> {
+    let values = match column.data_type() {
+        DataType::Int8 => primitive::<Int8Type>(column, |v| IoTDBValue::INT(v as i32)),
+        DataType::Int16 => primitive::<Int16Type>(column, |v| IoTDBValue::INT(v as i32)),
+        DataType::Int32 => primitive::<Int32Type>(column, IoTDBValue::INT),
+        DataType::UInt8 => primitive::<UInt8Type>(column, |v| IoTDBValue::INT(v as i32)),
+        DataType::UInt16 => primitive::<UInt16Type>(column, |v| IoTDBValue::INT(v as i32)),
+        DataType::Int64 => primitive::<Int64Type>(column, IoTDBValue::LONG),
+        DataType::UInt32 => primitive::<UInt32Type>(column, |v| IoTDBValue::LONG(v as i64)),
+        DataType::Float32 => primitive::<Float32Type>(column, IoTDBValue::FLOAT),
+        DataType::Float64 => primitive::<Float64Type>(column, IoTDBValue::DOUBLE),
+        DataType::Utf8 => column
+            .as_string::<i32>()
+            .iter()
+            .map(|v| IoTDBValue::TEXT(v.unwrap_or_default().to_owned()))
+            .collect(),
+        DataType::LargeUtf8 => column
+            .as_string::<i64>()
+            .iter()
+            .map(|v| IoTDBValue::TEXT(v.unwrap_or_default().to_owned()))
+            .collect(),
+        other => {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!("Arrow type {}", other)),
+            });
+        }
+    };
+    Ok(values)
+}
+
+fn primitive<P: ArrowPrimitiveType>(
+    column: &dyn Array,
+    to_value: impl Fn(P::Native) -> IoTDBValue,
+) -> Vec<IoTDBValue> {
+    column
+        .as_primitive::<P>()
+        .values()
+        .iter()
+        .map(|&v| to_value(v))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow_array::{
+        ArrayRef, Float64Array, Int16Array, Int64Array, RecordBatch, StringArray,
+        TimestampMillisecondArray, UInt64Array,
+    };
+
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::arrow::tablet_from_record_batch;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+
+    fn schema<'a>() -> Schema<'a> {
+        TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s3",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build()
+    }
+
+    fn batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    #[test]
+    fn write_record_batch() -> Result<(), TsFileError> {
+        let batch = batch(vec![
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![1, 2, 3])) as ArrayRef,
+            ),
+            ("s1", Arc::new(Int16Array::from(vec![1, 2, 3]))),
+            (
+                "s2",
+                Arc::new(Float64Array::from(vec![Some(1.5), None, Some(3.5)])),
+            ),
+            ("s3", Arc::new(StringArray::from(vec!["a", "b", "c"]))),
+        ]);
+
+        let mut writer = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        writer.write_record_batch("d1", &batch, "time")?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let query = |reader: &mut TsFileReader<_>, measurement| {
+            reader
+                .query("d1", measurement)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        assert_eq!(
+            query(&mut reader, "s1"),
+            vec![
+                (1, IoTDBValue::INT(1)),
+                (2, IoTDBValue::INT(2)),
+                (3, IoTDBValue::INT(3))
+            ]
+        );
+        assert_eq!(
+            query(&mut reader, "s2"),
+            vec![(1, IoTDBValue::DOUBLE(1.5)), (3, IoTDBValue::DOUBLE(3.5))]
+        );
+        assert_eq!(
+            query(&mut reader, "s3"),
+            vec![
+                (1, IoTDBValue::TEXT("a".to_owned())),
+                (2, IoTDBValue::TEXT("b".to_owned())),
+                (3, IoTDBValue::TEXT("c".to_owned()))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_batches() {
+        let missing_time = batch(vec![(
+            "s1",
+            Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef,
+        )]);
+        assert!(matches!(
+            tablet_from_record_batch(&missing_time, "time"),
+            Err(TsFileError::SchemaError { .. })
+        ));
+
+        let null_time = batch(vec![(
+            "time",
+            Arc::new(Int64Array::from(vec![Some(1), None])) as ArrayRef,
+        )]);
+        assert!(matches!(
+            tablet_from_record_batch(&null_time, "time"),
+            Err(TsFileError::IllegalState { .. })
+        ));
+
+        let unsupported = batch(vec![
+            ("time", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+            ("s1", Arc::new(UInt64Array::from(vec![1, 2]))),
+        ]);
+        assert!(matches!(
+            tablet_from_record_batch(&unsupported, "time"),
+            Err(TsFileError::UnsupportedFeature { .. })
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! Async variant of the `TsFileWriter` for `tokio::io::AsyncWrite` sinks (feature `async`).
+//!
+//! Encoding and compression happen in memory like in the `TsFileWriter`, only the written bytes
+//! are passed to the sink asynchronously. As TsFiles are written strictly sequentially no
+//! `AsyncSeek` is necessary.
+use std::io;
+use std::io::Write;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::writer::errors::TsFileError;
+use crate::writer::tablet::Tablet;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+use crate::writer::{IoTDBValue, PositionedWrite, Schema};
+
+/// Holds the bytes which are not yet written to the async sink but keeps track of the
+/// absolute position in the file
+struct PendingBuffer {
+    position: u64,
+    buffer: Vec<u8>,
+}
+
+impl Write for PendingBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PositionedWrite for PendingBuffer {
+    fn get_position(&self) -> u64 {
+        self.position
+    }
+}
+
+/// Writes a TsFile to any `AsyncWrite`, e.g. a `tokio::fs::File` or a `TcpStream`
+///
+/// ```
+/// use tsfile_writer::writer::async_writer::AsyncTsFileWriter;
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+/// use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+///
+/// # async fn run() -> Result<(), tsfile_writer::writer::errors::TsFileError> {
+/// let schema = Schema::simple(
+///     "d1",
+///     "s1",
+///     TSDataType::INT64,
+///     TSEncoding::PLAIN,
+///     CompressionType::UNCOMPRESSED,
+/// );
+/// let mut writer = AsyncTsFileWriter::new(schema, Vec::new(), Default::default())?;
+/// writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).await?;
+/// writer.close().await?;
+///
+/// let bytes: Vec<u8> = writer.into_inner();
+/// assert_eq!(&bytes[0..6], b"TsFile");
+/// # Ok(())
+/// # }
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(run())
+/// #     .unwrap();
+/// ```
+pub struct AsyncTsFileWriter<'a, W: AsyncWrite + Unpin> {
+    writer: TsFileWriter<'a, PendingBuffer>,
+    out: W,
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncTsFileWriter<'a, W> {
+    pub fn new(
+        schema: Schema<'a>,
+        out: W,
+        config: TsFileConfig,
+    ) -> Result<AsyncTsFileWriter<'a, W>, TsFileError> {
+        let buffer = PendingBuffer {
+            position: 0,
+            buffer: vec![],
+        };
+        Ok(AsyncTsFileWriter {
+            writer: TsFileWriter::new_from_writer(schema, buffer, config)?,
+            out,
+        })
+    }
+
+    pub async fn write(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamp: i64,
+        value: IoTDBValue,
+    ) -> Result<(), TsFileError> {
+        self.writer
+            .write(device, measurement_id, timestamp, value)?;
+        self.write_pending().await
+    }
+
+    pub async fn write_many(
+        &mut self,
+        device: &'a str,
+        timestamp: i64,
+        values: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        self.writer.write_many(device, timestamp, values)?;
+        self.write_pending().await
+    }
+
+    /// See `TsFileWriter::write_tablet`
+    pub async fn write_tablet(
+        &mut self,
+        device: &'a str,
+        tablet: Tablet<'_>,
+    ) -> Result<(), TsFileError> {
+        self.writer.write_tablet(device, tablet)?;
+        self.write_pending().await
+    }
+
+    /// Flushes all buffered data as chunk groups to the sink
+    pub async fn flush(&mut self) -> Result<(), TsFileError> {
+        self.writer.flush()?;
+        self.write_pending().await?;
+        self.out.flush().await?;
+        Ok(())
+    }
+
+    pub async fn close(&mut self) -> Result<(), TsFileError> {
+        self.writer.close()?;
+        self.write_pending().await?;
+        self.out.shutdown().await?;
+        Ok(())
+    }
+
+    /// Returns the sink (after `close`)
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    async fn write_pending(&mut self) -> Result<(), TsFileError> {
+        let pending = &mut self.writer.file_io_writer.out.buffer;
+        if !pending.is_empty() {
+            self.out.write_all(pending).await?;
+            pending.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::writer::async_writer::AsyncTsFileWriter;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+
+    fn schema<'a>() -> Schema<'a> {
+        Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::TS2DIFF,
+            CompressionType::UNCOMPRESSED,
+        )
+    }
+
+    #[tokio::test]
+    async fn same_bytes_as_sync_writer() -> Result<(), TsFileError> {
+        let config = TsFileConfig::default().chunk_group_size_threshold(1024);
+
+        let mut expected = TsFileWriter::new_from_writer(schema(), vec![], config)?;
+        let mut writer = AsyncTsFileWriter::new(schema(), vec![], config)?;
+        for i in 0..10_000 {
+            expected.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).await?;
+        }
+        expected.close()?;
+        writer.close().await?;
+
+        assert_eq!(writer.into_inner(), expected.into_inner());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flush_writes_to_sink() -> Result<(), TsFileError> {
+        let mut writer = AsyncTsFileWriter::new(schema(), vec![], Default::default())?;
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).await?;
+        let header_only = writer.out.len();
+
+        writer.flush().await?;
+
+        assert!(writer.out.len() > header_only);
+        assert!(writer.writer.file_io_writer.out.buffer.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+//! Bloom filter over the paths (`device.measurement`) of a TsFile, stored in the footer.
+//!
+//! It is compatible with the `BloomFilter` of IoTDB, so it answers whether a file possibly
+//! contains a series without reading the metadata index. Files written by IoTDB and by this
+//! crate can be checked alike.
+//!
+//! ```
+//! use tsfile_writer::writer::bloom_filter::BloomFilter;
+//! use tsfile_writer::writer::Serializable;
+//!
+//! let mut filter = BloomFilter::with_error_rate(0.05, 2);
+//! filter.add("root.sg.d1.s1");
+//! filter.add("root.sg.d1.s2");
+//! assert!(filter.contains("root.sg.d1.s1"));
+//!
+//! let mut bytes = vec![];
+//! filter.serialize(&mut bytes).unwrap();
+//! let filter = BloomFilter::deserialize(&mut bytes.as_slice()).unwrap();
+//! assert!(filter.might_contain("root.sg.d1", "s2"));
+//! ```
+use crate::writer::murmur128::Murmur128;
+use crate::writer::utils::{read_bytes, read_var_u32, write_var_u32};
+use crate::writer::{PositionedWrite, Serializable, TsFileError};
+use std::io::Read;
+
+/// Seeds of the hash functions, the n-th hash function uses the n-th seed
+const SEEDS: [i32; 8] = [5, 7, 11, 19, 31, 37, 43, 59];
+/// The error rate is clamped to this range
+const MIN_ERROR_RATE: f64 = 0.01;
+const MAX_ERROR_RATE: f64 = 0.1;
+/// Minimal number of bits
+const MINIMAL_SIZE: u32 = 256;
+/// The size is an `int` in IoTDB
+const MAXIMAL_SIZE: u32 = i32::MAX as u32;
+
+#[derive(Clone, Debug)]
+pub struct BloomFilter {
+    /// Read filters only hold the serialized bytes, i.e. trailing zero bytes may be missing
+    bits: Vec<u8>,
+    size: u32,
+    hash_function_size: u32,
+}
+
+impl BloomFilter {
+    /// Empty filter with the given number of bits and hash functions (at most 8)
+    pub fn new(size: u32, hash_function_size: u32) -> Result<BloomFilter, TsFileError> {
+        if size == 0 || size > MAXIMAL_SIZE || hash_function_size as usize > SEEDS.len() {
+            return Err(TsFileError::Error {
+                source: Some(format!(
+                    "Invalid bloom filter with {} bits and {} hash functions",
+                    size, hash_function_size
+                )),
+            });
+        }
+        Ok(BloomFilter {
+            bits: vec![0; size.div_ceil(8) as usize],
+            size,
+            hash_function_size,
+        })
+    }
+
+    /// Empty filter for the expected number of paths, sized like IoTDB does for the error rate
+    /// (clamped to 0.01 - 0.1)
+    pub fn with_error_rate(error_rate: f64, expected_paths: usize) -> BloomFilter {
+        let error = error_rate.clamp(MIN_ERROR_RATE, MAX_ERROR_RATE);
+        let ln2 = 2.0_f64.ln();
+
+        let size = (-(expected_paths as f64) * error.ln() / ln2 / ln2) as u32 + 1;
+        let hash_function_size = ((-error.ln() / ln2) + 1.0) as u32;
+
+        let size = size.clamp(MINIMAL_SIZE, MAXIMAL_SIZE);
+        BloomFilter {
+            bits: vec![0; size.div_ceil(8) as usize],
+            size,
+            hash_function_size: hash_function_size.min(SEEDS.len() as u32),
+        }
+    }
+
+    /// Number of bits of the filter
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn hash_function_size(&self) -> u32 {
+        self.hash_function_size
+    }
+
+    fn bit_indices<'a>(&'a self, path: &'a str) -> impl Iterator<Item = usize> + 'a {
+        SEEDS[..self.hash_function_size as usize]
+            .iter()
+            .map(move |seed| {
+                // Math.abs(Murmur128Hash.hash(value, seed)) % cap
+                let hash = Murmur128::hash(path, *seed).wrapping_abs();
+                (hash % self.size as i32).unsigned_abs() as usize
+            })
+    }
+
+    /// Adds a full path, e.g. `root.sg.d1.s1`
+    pub fn add(&mut self, path: &str) {
+        for bit in self.bit_indices(path).collect::<Vec<_>>() {
+            if bit / 8 >= self.bits.len() {
+                self.bits.resize(bit / 8 + 1, 0);
+            }
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Returns `false` if the path is definitely not contained
+    pub fn contains(&self, path: &str) -> bool {
+        self.bit_indices(path).all(|bit| {
+            // Trailing zero bytes are not serialized
+            match self.bits.get(bit / 8) {
+                Some(byte) => byte & (1 << (bit % 8)) != 0,
+                None => false,
+            }
+        })
+    }
+
+    /// Returns `false` if the series is definitely not contained
+    pub fn might_contain(&self, device: &str, measurement: &str) -> bool {
+        self.contains(&format!("{}.{}", device, measurement))
+    }
+
+    /// Reads a filter in the format of the TsFile footer. Like `BitSet.valueOf` in IoTDB only
+    /// the serialized bytes are kept, so a corrupt size can not cause a large allocation.
+    pub fn deserialize(reader: &mut dyn Read) -> Result<BloomFilter, TsFileError> {
+        let number_of_bytes = read_var_u32(reader)?;
+        let bits = read_bytes(reader, number_of_bytes as usize)?;
+        let size = read_var_u32(reader)?;
+        let hash_function_size = read_var_u32(reader)?;
+        if size == 0 || size > MAXIMAL_SIZE || hash_function_size as usize > SEEDS.len() {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!(
+                    "Invalid bloom filter with {} bits and {} hash functions",
+                    size, hash_function_size
+                )),
+            });
+        }
+        if number_of_bytes > size.div_ceil(8) {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!(
+                    "Bloom filter with {} bits contains {} bytes",
+                    size, number_of_bytes
+                )),
+            });
+        }
+        Ok(BloomFilter {
+            bits,
+            size,
+            hash_function_size,
+        })
+    }
+
+    /// The bits without trailing zero bytes
+    fn used_bits(&self) -> &[u8] {
+        let length = self
+            .bits
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |last| last + 1);
+        &self.bits[..length]
+    }
+}
+
+/// Filters are equal regardless of trailing zero bytes that were not serialized
+impl PartialEq for BloomFilter {
+    fn eq(&self, other: &BloomFilter) -> bool {
+        self.size == other.size
+            && self.hash_function_size == other.hash_function_size
+            && self.used_bits() == other.used_bits()
+    }
+}
+
+/// Writes the filter in the format of the TsFile footer
+impl Serializable for BloomFilter {
+    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
+        // Like BitSet.toByteArray() without trailing zero bytes
+        let bits = self.used_bits();
+        write_var_u32(bits.len() as u32, file)?;
+        file.write_all(bits)?;
+        write_var_u32(self.size, file)?;
+        write_var_u32(self.hash_function_size, file)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::bloom_filter::BloomFilter;
+    use crate::writer::{Serializable, TsFileError};
+
+    #[test]
+    fn roundtrip() {
+        let mut filter = BloomFilter::with_error_rate(0.05, 1_000);
+        // Like IoTDB: 6236 bits, 5 hash functions
+        assert_eq!((filter.size(), filter.hash_function_size()), (6236, 5));
+        for i in 0..1_000 {
+            filter.add(&format!("root.sg.d{}.s1", i));
+        }
+        let mut bytes = vec![];
+        filter.serialize(&mut bytes).unwrap();
+        let read = BloomFilter::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, filter);
+        assert!((0..1_000).all(|i| read.might_contain(&format!("root.sg.d{}", i), "s1")));
+        let false_positives = (0..1_000)
+            .filter(|i| read.contains(&format!("root.sg.d{}.s2", i)))
+            .count();
+        assert!(false_positives < 100);
+    }
+
+    #[test]
+    fn limits() {
+        let filter = BloomFilter::with_error_rate(0.5, 0);
+        assert_eq!((filter.size(), filter.hash_function_size()), (256, 4));
+        assert!(!filter.contains("d1.s1"));
+        assert!(BloomFilter::new(0, 1).is_err());
+        assert!(BloomFilter::new(256, 9).is_err());
+        // Two bytes for 8 bits
+        let bytes = [0x02, 0x01, 0x01, 0x08, 0x01];
+        assert!(BloomFilter::deserialize(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn corrupt_sizes() {
+        let deserialize = |bytes: &[u8]| BloomFilter::deserialize(&mut &bytes[..]);
+        // u32::MAX bits (var int), 5 hash functions
+        assert!(matches!(
+            deserialize(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x05]),
+            Err(TsFileError::Corrupt { .. })
+        ));
+        // u32::MAX - 7 bits
+        assert!(matches!(
+            deserialize(&[0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0x0F, 0x05]),
+            Err(TsFileError::Corrupt { .. })
+        ));
+        // More bytes than left in the footer
+        assert!(matches!(
+            deserialize(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x01]),
+            Err(TsFileError::Corrupt { .. })
+        ));
+
+        // The largest size of IoTDB only holds the serialized byte
+        let filter = deserialize(&[0x01, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x01]).unwrap();
+        assert_eq!(filter.size(), i32::MAX as u32);
+        assert_eq!(filter.bits.len(), 1);
+        assert!(!filter.contains("root.sg.d1.s1"));
+    }
+
+    #[test]
+    fn size_not_multiple_of_eight() {
+        let mut filter = BloomFilter::new(257, 8).unwrap();
+        for i in 0..100 {
+            filter.add(&format!("d{}.s1", i));
+        }
+        assert!((0..100).all(|i| filter.contains(&format!("d{}.s1", i))));
+    }
+}
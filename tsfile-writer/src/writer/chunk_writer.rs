@@ -1,21 +1,19 @@
 use crate::writer::encoding::time_encoder::LongTs2DiffEncoder;
 use crate::writer::encoding::Encoder;
+use crate::writer::metrics::WriterMetrics;
 use crate::writer::statistics::Statistics;
+use crate::writer::ts_file_config::TsFileConfig;
 use crate::writer::tsfile_io_writer::TsFileIoWriter;
 use crate::writer::utils::{size_var_i32, size_var_u32};
 use crate::writer::{
-    utils, write_str, CompressionType, IoTDBValue, PositionedWrite, Serializable, TSDataType,
-    TSEncoding, TsFileError, CHUNK_HEADER, ONLY_ONE_PAGE_CHUNK_HEADER,
+    utils, write_str, CompressionType, IoTDBValue, IoTDBValues, MeasurementSchema, PositionedWrite,
+    Serializable, TSDataType, TSEncoding, TsFileError, CHUNK_HEADER, ONLY_ONE_PAGE_CHUNK_HEADER,
 };
-use snap::raw::max_compress_len;
 use std::fmt::{Display, Formatter};
-use std::io;
 use std::io::Write;
 
-const MAX_NUMBER_OF_POINTS_IN_PAGE: u32 = 1048576;
 const VALUE_COUNT_IN_ONE_PAGE_FOR_NEXT_CHECK: u32 = 7989;
-const PAGE_SIZE_THRESHOLD: u32 = 65536;
-const MINIMUM_RECORD_COUNT_FOR_CHECK: u32 = 1500;
+pub(crate) const MINIMUM_RECORD_COUNT_FOR_CHECK: u32 = 1500;
 
 struct PageWriter {
     time_encoder: LongTs2DiffEncoder,
@@ -28,12 +26,12 @@ struct PageWriter {
 }
 
 impl PageWriter {
-    fn new(data_type: TSDataType, encoding: TSEncoding) -> Result<PageWriter, TsFileError> {
+    fn new(schema: &MeasurementSchema) -> Result<PageWriter, TsFileError> {
         Ok(PageWriter {
             time_encoder: LongTs2DiffEncoder::new(),
-            value_encoder: <dyn Encoder>::new(data_type, encoding)?,
-            data_type,
-            statistics: Statistics::new(data_type),
+            value_encoder: <dyn Encoder>::for_schema(schema)?,
+            data_type: schema.data_type,
+            statistics: Statistics::new(schema.data_type),
             buffer: Vec::with_capacity(65536),
             point_number: 0,
         })
@@ -57,22 +55,32 @@ impl PageWriter {
         max_size
     }
 
-    fn write(&mut self, timestamp: i64, value: &mut IoTDBValue) -> Result<u32, &str> {
-        self.time_encoder.write(&timestamp.into());
-        self.value_encoder.write(value);
-        self.statistics.update(timestamp, value);
+    fn write(&mut self, timestamp: i64, value: &IoTDBValue) -> Result<u32, TsFileError> {
+        self.time_encoder.write(&timestamp.into())?;
+        self.value_encoder.write(value)?;
+        self.statistics.update(timestamp, value)?;
         self.point_number += 1;
         Ok(1)
     }
 
-    pub(crate) fn prepare_buffer(&mut self) {
+    fn write_batch(&mut self, timestamps: &[i64], values: IoTDBValues) -> Result<u32, TsFileError> {
+        self.time_encoder
+            .write_batch(IoTDBValues::LONG(timestamps))?;
+        self.value_encoder.write_batch(values)?;
+        self.statistics.update_batch(timestamps, values)?;
+        self.point_number += timestamps.len() as u32;
+        Ok(timestamps.len() as u32)
+    }
+
+    pub(crate) fn prepare_buffer(&mut self) -> Result<(), TsFileError> {
         // serialize time_encoder and value encoder
         self.buffer.clear();
         let mut time_buffer = vec![];
         self.time_encoder.serialize(&mut time_buffer);
-        crate::writer::write_var_u32(time_buffer.len() as u32, &mut self.buffer);
-        self.buffer.write_all(time_buffer.as_slice());
+        crate::writer::write_var_u32(time_buffer.len() as u32, &mut self.buffer)?;
+        self.buffer.write_all(time_buffer.as_slice())?;
         self.value_encoder.serialize(&mut self.buffer);
+        Ok(())
     }
 }
 
@@ -81,6 +89,7 @@ pub struct ChunkWriter {
     pub(crate) data_type: TSDataType,
     pub compression_type: CompressionType,
     pub encoding: TSEncoding,
+    max_point_number: Option<u32>,
     #[allow(dead_code)]
     pub(crate) mask: u8,
     #[allow(dead_code)]
@@ -92,6 +101,12 @@ pub struct ChunkWriter {
     first_page_statistics: Option<Statistics>,
     value_count_in_one_page_for_next_check: u32,
     size_without_statistics: usize,
+    page_size_threshold: u32,
+    max_number_of_points_in_page: u32,
+    single_page_chunks: bool,
+    /// Sizes of all pages sealed by this writer, before and after compression
+    uncompressed_page_bytes: u64,
+    compressed_page_bytes: u64,
 }
 
 impl ChunkWriter {
@@ -100,12 +115,14 @@ impl ChunkWriter {
         data_type: TSDataType,
         compression_type: CompressionType,
         encoding: TSEncoding,
+        config: &TsFileConfig,
     ) -> ChunkWriter {
         ChunkWriter {
             measurement_id: measurement_id.to_owned(),
             data_type,
             compression_type,
             encoding,
+            max_point_number: None,
             mask: 0,
             offset_of_chunk_header: None,
             statistics: Statistics::new(data_type),
@@ -115,9 +132,54 @@ impl ChunkWriter {
             first_page_statistics: None,
             value_count_in_one_page_for_next_check: VALUE_COUNT_IN_ONE_PAGE_FOR_NEXT_CHECK,
             size_without_statistics: 0,
+            page_size_threshold: config.page_size_threshold,
+            max_number_of_points_in_page: config.max_number_of_points_in_page,
+            single_page_chunks: config.single_page_chunks,
+            uncompressed_page_bytes: 0,
+            compressed_page_bytes: 0,
+        }
+    }
+
+    pub(crate) fn from_schema(
+        measurement_id: &str,
+        schema: &MeasurementSchema,
+        config: &TsFileConfig,
+    ) -> ChunkWriter {
+        let mut chunk_writer = ChunkWriter::new(
+            measurement_id,
+            schema.data_type,
+            schema.compression,
+            schema.encoding,
+            config,
+        );
+        chunk_writer.max_point_number = schema.max_point_number;
+        chunk_writer
+    }
+
+    fn schema(&self) -> MeasurementSchema {
+        MeasurementSchema {
+            data_type: self.data_type,
+            encoding: self.encoding,
+            compression: self.compression_type,
+            max_point_number: self.max_point_number,
         }
     }
 
+    /// Adds the points that are not flushed yet and the sizes of the sealed pages
+    pub(crate) fn add_metrics(&self, device: &str, metrics: &mut WriterMetrics) {
+        let page_points = match &self.current_page_writer {
+            Some(page_writer) => page_writer.point_number,
+            None => 0,
+        };
+        metrics.add_points(
+            device,
+            &self.measurement_id,
+            (self.statistics.count() + page_points) as u64,
+        );
+        metrics.uncompressed_page_bytes += self.uncompressed_page_bytes;
+        metrics.compressed_page_bytes += self.compressed_page_bytes;
+    }
+
     // // This method is used?!
     // #[allow(dead_code)]
     // pub(crate) fn serialize(&mut self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
@@ -177,14 +239,10 @@ impl ChunkWriter {
         }
     }
 
-    pub(crate) fn seal_current_page(&mut self) {
+    pub(crate) fn seal_current_page(&mut self) -> Result<(), TsFileError> {
         match &self.current_page_writer {
-            None => {}
-            Some(pw) => {
-                if pw.point_number > 0 {
-                    self.write_page_to_buffer();
-                }
-            }
+            Some(pw) if pw.point_number > 0 => self.write_page_to_buffer(),
+            _ => Ok(()),
         }
     }
 
@@ -208,24 +266,35 @@ impl ChunkWriter {
     pub(crate) fn write_to_file_writer<T: PositionedWrite>(
         &mut self,
         file_writer: &mut TsFileIoWriter<T>,
-    ) {
-        self.seal_current_page();
-        self.write_all_pages_of_chunk_to_ts_file(file_writer, &self.statistics);
+    ) -> Result<(), TsFileError> {
+        self.seal_current_page()?;
+        self.write_all_pages_of_chunk_to_ts_file(file_writer, &self.statistics)?;
 
         // re-init this chunk writer
         self.page_buffer.clear();
         self.num_pages = 0;
         self.first_page_statistics = None;
         self.statistics = Statistics::new(self.data_type);
+        self.size_without_statistics = 0;
+        Ok(())
+    }
+
+    /// Whether any point was written since the last flush
+    pub(crate) fn has_data(&self) -> bool {
+        self.num_pages > 0
+            || match &self.current_page_writer {
+                Some(page_writer) => page_writer.point_number > 0,
+                None => false,
+            }
     }
 
     fn write_all_pages_of_chunk_to_ts_file<T: PositionedWrite>(
         &self,
         file_writer: &mut TsFileIoWriter<T>,
         statistics: &Statistics,
-    ) {
+    ) -> Result<(), TsFileError> {
         if statistics.count() == 0 {
-            return;
+            return Ok(());
         }
         file_writer.start_flush_chunk(
             self.measurement_id.clone(),
@@ -236,13 +305,13 @@ impl ChunkWriter {
             self.page_buffer.len() as u32,
             self.num_pages,
             0,
-        );
+        )?;
 
         let data_offset = file_writer.out.get_position();
         log::trace!("Dumping pages at offset {}", data_offset);
 
         // Write the full page
-        file_writer.out.write_all(&self.page_buffer);
+        file_writer.out.write_all(&self.page_buffer)?;
 
         log::trace!("Offset after {}", file_writer.out.get_position());
 
@@ -257,7 +326,7 @@ impl ChunkWriter {
         //           + pageBuffer.size());
         // }
         //
-        file_writer.end_current_chunk();
+        file_writer.end_current_chunk()
     }
 
     pub(crate) fn estimate_max_series_mem_size(&mut self) -> u32 {
@@ -280,54 +349,81 @@ impl ChunkWriter {
         }
     }
 
-    pub fn write(&mut self, timestamp: i64, mut value: IoTDBValue) -> Result<u32, TsFileError> {
-        // self.statistics.update(timestamp, &value);
-        match &mut self.current_page_writer {
-            None => {
-                // Create a page
-                self.current_page_writer = Some(PageWriter::new(self.data_type, self.encoding)?)
-            }
-            Some(_) => {
-                // do nothing
-            }
+    pub fn write(&mut self, timestamp: i64, value: IoTDBValue) -> Result<u32, TsFileError> {
+        if value.data_type() != self.data_type {
+            return Err(TsFileError::WrongTypeForSeries);
+        }
+        if self.current_page_writer.is_none() {
+            // Create a page
+            self.current_page_writer = Some(PageWriter::new(&self.schema())?)
         }
         let records_written = match &mut self.current_page_writer {
-            None => {
-                panic!("Something bad happened!");
-            }
-            Some(page_writer) => page_writer.write(timestamp, &mut value).unwrap(),
+            Some(page_writer) => page_writer.write(timestamp, &value)?,
+            None => 0,
         };
-        self.check_page_size_and_may_open_new_page();
+        self.check_page_size_and_may_open_new_page()?;
         Ok(records_written)
     }
 
-    fn check_page_size_and_may_open_new_page(&mut self) {
-        if self.current_page_writer.is_none() {
-            return;
+    /// Writes the points in slices up to the next check of the page size, so the pages are the
+    /// same as if the points were written one by one
+    pub(crate) fn write_batch(
+        &mut self,
+        timestamps: &[i64],
+        values: IoTDBValues,
+    ) -> Result<u32, TsFileError> {
+        if values.data_type() != self.data_type {
+            return Err(TsFileError::WrongTypeForSeries);
+        }
+        let mut records_written = 0;
+        let mut start = 0;
+        while start < timestamps.len() {
+            if self.current_page_writer.is_none() {
+                self.current_page_writer = Some(PageWriter::new(&self.schema())?)
+            }
+            if let Some(page_writer) = self.current_page_writer.as_mut() {
+                let next_check = self
+                    .max_number_of_points_in_page
+                    .min(self.value_count_in_one_page_for_next_check);
+                let count = next_check.saturating_sub(page_writer.point_number).max(1) as usize;
+                let end = timestamps.len().min(start + count);
+                records_written +=
+                    page_writer.write_batch(&timestamps[start..end], values.slice(start, end))?;
+                start = end;
+            }
+            self.check_page_size_and_may_open_new_page()?;
         }
-        let page_writer = self.current_page_writer.as_mut().unwrap();
-        if page_writer.point_number > MAX_NUMBER_OF_POINTS_IN_PAGE {
-            self.write_page_to_buffer();
+        Ok(records_written)
+    }
+
+    fn check_page_size_and_may_open_new_page(&mut self) -> Result<(), TsFileError> {
+        let page_writer = match self.current_page_writer.as_mut() {
+            Some(page_writer) => page_writer,
+            None => return Ok(()),
+        };
+        if page_writer.point_number >= self.max_number_of_points_in_page {
+            self.write_page_to_buffer()?;
         } else if page_writer.point_number >= self.value_count_in_one_page_for_next_check {
             let current_page_size = page_writer.estimate_max_mem_size();
 
-            if current_page_size > PAGE_SIZE_THRESHOLD {
+            if current_page_size > self.page_size_threshold {
                 log::trace!(
             "enough size, write page {}, pageSizeThreshold:{}, currentPateSize:{}, valueCountInOnePage:{}",
             self.measurement_id.clone(),
-            PAGE_SIZE_THRESHOLD,
+            self.page_size_threshold,
             current_page_size,
             page_writer.point_number);
-                self.write_page_to_buffer();
+                self.write_page_to_buffer()?;
                 self.value_count_in_one_page_for_next_check = MINIMUM_RECORD_COUNT_FOR_CHECK;
             } else {
                 // valueCountInOnePageForNextCheck =
                 //     (int) (((float) pageSizeThreshold / currentPageSize) * pageWriter.getPointNumber());
                 self.value_count_in_one_page_for_next_check =
-                    ((PAGE_SIZE_THRESHOLD as f32) / (current_page_size as f32)
+                    ((self.page_size_threshold as f32) / (current_page_size as f32)
                         * (page_writer.point_number as f32)) as u32;
             }
         }
+        Ok(())
     }
 
     //   private void checkPageSizeAndMayOpenANewPage() {
@@ -357,79 +453,81 @@ impl ChunkWriter {
     // }
     fn write_page_to_buffer(&mut self) -> Result<(), TsFileError> {
         if let Some(page_writer) = self.current_page_writer.as_mut() {
-            page_writer.prepare_buffer();
+            #[cfg(feature = "tracing")]
+            let _span = tracing_crate::debug_span!(
+                "encode_page",
+                measurement = self.measurement_id.as_str(),
+                points = page_writer.point_number
+            )
+            .entered();
+            page_writer.prepare_buffer()?;
 
             let buffer_size: u32 = page_writer.buffer.len() as u32;
 
             let uncompressed_bytes = buffer_size;
-            let mut compressed_buffer: Vec<u8> = Vec::new();
+            let mut compressed_buffer: Vec<u8> = vec![];
 
             let compressed_bytes = match self.compression_type {
                 CompressionType::UNCOMPRESSED => uncompressed_bytes,
-                CompressionType::SNAPPY => {
-                    let mut encoder1 = snap::raw::Encoder::new();
-                    let out_buffer_len = max_compress_len(page_writer.buffer.len());
-                    let mut out = vec![0; out_buffer_len];
-                    match encoder1.compress(&page_writer.buffer, out.as_mut_slice()) {
-                        Ok(size) => {
-                            let mut reader = &out.as_mut_slice()[..size];
-                            io::copy(&mut reader, &mut compressed_buffer);
-                            size as u32
-                        }
-                        Err(_) => {
-                            return Err(TsFileError::Compression);
-                        }
-                    }
+                _ => {
+                    compressed_buffer = self.compression_type.compress(&page_writer.buffer)?;
+                    compressed_buffer.len() as u32
                 }
             };
+            self.uncompressed_page_bytes += uncompressed_bytes as u64;
+            self.compressed_page_bytes += compressed_bytes as u64;
 
-            // TODO we need a change here if multiple pages exist
-            if self.num_pages == 0 {
+            // The statistics of the first page are only written once a second page follows,
+            // unless all page headers contain statistics
+            if self.num_pages == 0 && self.single_page_chunks {
                 // Uncompressed size
                 self.size_without_statistics +=
-                    utils::write_var_u32(uncompressed_bytes as u32, &mut self.page_buffer)?
-                        as usize;
+                    utils::write_var_u32(uncompressed_bytes, &mut self.page_buffer)? as usize;
                 // Compressed size
                 self.size_without_statistics +=
-                    utils::write_var_u32(compressed_bytes as u32, &mut self.page_buffer)? as usize;
+                    utils::write_var_u32(compressed_bytes, &mut self.page_buffer)? as usize;
 
                 // Write page content
                 match self.compression_type {
                     CompressionType::UNCOMPRESSED => {
-                        self.page_buffer.write_all(&page_writer.buffer);
+                        self.page_buffer.write_all(&page_writer.buffer)?;
                     }
                     _ => {
-                        self.page_buffer.write_all(&compressed_buffer);
+                        self.page_buffer.write_all(&compressed_buffer)?;
                     }
                 }
 
                 page_writer.buffer.clear();
 
                 self.first_page_statistics = Some(page_writer.statistics.clone())
-            } else if self.num_pages == 1 {
+            } else if self.num_pages == 1 && self.single_page_chunks {
                 let temp = self.page_buffer.clone();
                 self.page_buffer.clear();
 
                 log::trace!("Page Buffer offset: {}", self.page_buffer.get_position());
                 let header_bytes = &temp[0..self.size_without_statistics];
-                self.page_buffer.write_all(header_bytes);
+                self.page_buffer.write_all(header_bytes)?;
                 log::trace!("Page Buffer offset: {}", self.page_buffer.get_position());
                 match &self.first_page_statistics {
-                    Some(stat) => stat.serialize(&mut self.page_buffer),
-                    _ => panic!("This should not happen!"),
+                    Some(stat) => stat.serialize(&mut self.page_buffer)?,
+                    None => {
+                        return Err(TsFileError::IllegalState {
+                            source: Some("Statistics of the first page are missing".to_owned()),
+                        })
+                    }
                 };
                 log::trace!("Page Buffer offset: {}", self.page_buffer.get_position());
                 let remainder_bytes = &temp[self.size_without_statistics..];
-                self.page_buffer.write_all(remainder_bytes);
+                self.page_buffer.write_all(remainder_bytes)?;
                 log::trace!("Page Buffer offset: {}", self.page_buffer.get_position());
                 // Uncompressed size
-                utils::write_var_u32(uncompressed_bytes as u32, &mut self.page_buffer);
+                utils::write_var_u32(uncompressed_bytes, &mut self.page_buffer)?;
                 // Compressed size
-                utils::write_var_u32(compressed_bytes as u32, &mut self.page_buffer);
+                utils::write_var_u32(compressed_bytes, &mut self.page_buffer)?;
                 log::trace!("Page Buffer offset: {}", self.page_buffer.get_position());
                 // Write page content
                 log::trace!("Statistics: {:?}", &page_writer.statistics);
-                page_writer.statistics.serialize(&mut self.page_buffer);
+                page_writer.statistics.serialize(&mut self.page_buffer)?;
 
                 log::trace!(
                     "Flushing page at page buffer offset {}",
@@ -441,10 +539,10 @@ impl ChunkWriter {
                 // Write page content
                 match self.compression_type {
                     CompressionType::UNCOMPRESSED => {
-                        self.page_buffer.write_all(&page_writer.buffer);
+                        self.page_buffer.write_all(&page_writer.buffer)?;
                     }
                     _ => {
-                        self.page_buffer.write_all(&compressed_buffer);
+                        self.page_buffer.write_all(&compressed_buffer)?;
                     }
                 }
 
@@ -461,21 +559,21 @@ impl ChunkWriter {
                 self.first_page_statistics = None;
             } else {
                 // Uncompressed size
-                utils::write_var_u32(uncompressed_bytes as u32, &mut self.page_buffer);
+                utils::write_var_u32(uncompressed_bytes, &mut self.page_buffer)?;
                 // Compressed size
-                utils::write_var_u32(compressed_bytes as u32, &mut self.page_buffer);
+                utils::write_var_u32(compressed_bytes, &mut self.page_buffer)?;
                 // Write page content
-                page_writer.statistics.serialize(&mut self.page_buffer);
+                page_writer.statistics.serialize(&mut self.page_buffer)?;
 
                 let pos_before_flush = self.page_buffer.get_position();
 
                 // Write page content
                 match self.compression_type {
                     CompressionType::UNCOMPRESSED => {
-                        self.page_buffer.write_all(&page_writer.buffer);
+                        self.page_buffer.write_all(&page_writer.buffer)?;
                     }
                     _ => {
-                        self.page_buffer.write_all(&compressed_buffer);
+                        self.page_buffer.write_all(&compressed_buffer)?;
                     }
                 }
 
@@ -488,7 +586,7 @@ impl ChunkWriter {
                 page_writer.buffer.clear();
             }
             self.num_pages += 1;
-            self.statistics.merge(&page_writer.statistics);
+            self.statistics.merge(&page_writer.statistics)?;
             page_writer.reset();
         }
 
@@ -502,7 +600,8 @@ pub struct ChunkHeader {
     pub data_type: TSDataType,
     pub compression: CompressionType,
     pub encoding: TSEncoding,
-    pub num_pages: u32,
+    /// Page headers contain statistics, always the case for chunks with more than one page
+    pub page_statistics: bool,
     pub mask: u8,
 }
 
@@ -513,10 +612,10 @@ impl ChunkHeader {
     ) -> Result<(), TsFileError> {
         // Marker
         // (byte)((numOfPages <= 1 ? MetaMarker.ONLY_ONE_PAGE_CHUNK_HEADER : MetaMarker.CHUNK_HEADER) | (byte) mask),
-        let marker = if self.num_pages <= 1 {
-            ONLY_ONE_PAGE_CHUNK_HEADER
-        } else {
+        let marker = if self.page_statistics {
             CHUNK_HEADER
+        } else {
+            ONLY_ONE_PAGE_CHUNK_HEADER
         };
         let marker = marker | self.mask;
         file_writer.write_all(&[marker])?; // Marker
@@ -540,7 +639,7 @@ impl ChunkHeader {
         data_type: TSDataType,
         compression: CompressionType,
         encoding: TSEncoding,
-        num_pages: u32,
+        page_statistics: bool,
         mask: u8,
     ) -> ChunkHeader {
         ChunkHeader {
@@ -549,7 +648,7 @@ impl ChunkHeader {
             data_type,
             compression,
             encoding,
-            num_pages,
+            page_statistics,
             mask,
         }
     }
@@ -560,7 +659,7 @@ pub struct ChunkMetadata {
     pub(crate) measurement_id: String,
     pub(crate) data_type: TSDataType,
     pub(crate) mask: u8,
-    offset_of_chunk_header: i64,
+    pub(crate) offset_of_chunk_header: i64,
     pub(crate) statistics: Statistics,
 }
 
@@ -585,12 +684,12 @@ impl ChunkMetadata {
         &self,
         file: &mut dyn PositionedWrite,
         serialize_statistics: bool,
-    ) -> io::Result<()> {
-        let result = file.write_all(&self.offset_of_chunk_header.to_be_bytes());
+    ) -> Result<(), TsFileError> {
+        file.write_all(&self.offset_of_chunk_header.to_be_bytes())?;
         if serialize_statistics {
-            self.statistics.serialize(file);
+            self.statistics.serialize(file)?;
         }
-        result
+        Ok(())
     }
 }
 
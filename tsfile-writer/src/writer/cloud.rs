@@ -0,0 +1,126 @@
+//! Writes TsFiles directly to object stores like S3, GCS or Azure (feature `cloud`).
+//!
+//! The file is streamed to the store by the `object_store` `BufWriter`, i.e. as multipart
+//! upload for large files. The backends are enabled by the features of the `object_store`
+//! crate, e.g. `object_store = { version = "0.14", features = ["aws"] }`.
+use std::sync::Arc;
+
+use object_store::buffered::BufWriter;
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::writer::async_writer::AsyncTsFileWriter;
+use crate::writer::errors::TsFileError;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::Schema;
+
+impl<'a> AsyncTsFileWriter<'a, BufWriter> {
+    /// Writes the TsFile to the given location of the store, the object is complete
+    /// after `close`
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use object_store::memory::InMemory;
+    /// use object_store::path::Path;
+    /// use tsfile_writer::writer::async_writer::AsyncTsFileWriter;
+    /// use tsfile_writer::writer::compression::CompressionType;
+    /// use tsfile_writer::writer::encoding::TSEncoding;
+    /// use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+    ///
+    /// # async fn run() -> Result<(), tsfile_writer::writer::errors::TsFileError> {
+    /// let schema = Schema::simple(
+    ///     "d1",
+    ///     "s1",
+    ///     TSDataType::INT64,
+    ///     TSEncoding::PLAIN,
+    ///     CompressionType::UNCOMPRESSED,
+    /// );
+    /// let store = Arc::new(InMemory::new());
+    /// let path = Path::from("archive/1.tsfile");
+    /// let mut writer = AsyncTsFileWriter::new_object_store(schema, store, path, Default::default())?;
+    /// writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).await?;
+    /// writer.close().await?;
+    /// # Ok(())
+    /// # }
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(run())
+    /// #     .unwrap();
+    /// ```
+    pub fn new_object_store(
+        schema: Schema<'a>,
+        store: Arc<dyn ObjectStore>,
+        path: Path,
+        config: TsFileConfig,
+    ) -> Result<AsyncTsFileWriter<'a, BufWriter>, TsFileError> {
+        AsyncTsFileWriter::new(schema, BufWriter::new(store, path), config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use object_store::buffered::BufWriter;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{ObjectStore, ObjectStoreExt};
+
+    use crate::writer::async_writer::AsyncTsFileWriter;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+
+    fn schema<'a>() -> Schema<'a> {
+        Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::TS2DIFF,
+            CompressionType::UNCOMPRESSED,
+        )
+    }
+
+    #[tokio::test]
+    async fn multipart_upload() -> Result<(), TsFileError> {
+        let store = Arc::new(InMemory::new());
+        let path = Path::from("archive/1.tsfile");
+        // Small capacity to force a multipart upload
+        let sink = BufWriter::with_capacity(store.clone(), path.clone(), 1024);
+
+        let mut expected = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        let mut writer = AsyncTsFileWriter::new(schema(), sink, Default::default())?;
+        for i in 0..10_000 {
+            expected.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).await?;
+        }
+        expected.close()?;
+        writer.close().await?;
+
+        let uploaded = store.get(&path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(uploaded.to_vec(), expected.into_inner());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nothing_uploaded_before_close() -> Result<(), TsFileError> {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let path = Path::from("archive/2.tsfile");
+
+        let mut writer = AsyncTsFileWriter::new_object_store(
+            schema(),
+            store.clone(),
+            path.clone(),
+            Default::default(),
+        )?;
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).await?;
+        assert!(store.head(&path).await.is_err());
+
+        writer.close().await?;
+        assert!(store.head(&path).await.is_ok());
+        Ok(())
+    }
+}
@@ -1,10 +1,18 @@
 //! Contains the compression algorithms
-use crate::writer::CompressionType::{SNAPPY, UNCOMPRESSED};
+use crate::writer::CompressionType::{GZIP, LZ4, SNAPPY, UNCOMPRESSED};
+use crate::writer::TsFileError;
 
 #[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum CompressionType {
     UNCOMPRESSED,
     SNAPPY,
+    GZIP,
+    LZ4,
 }
 
 impl TryFrom<u8> for CompressionType {
@@ -14,6 +22,8 @@ impl TryFrom<u8> for CompressionType {
         match value {
             0x00 => Ok(UNCOMPRESSED),
             0x01 => Ok(SNAPPY),
+            0x02 => Ok(GZIP),
+            0x07 => Ok(LZ4),
             _ => Err(()),
         }
     }
@@ -24,6 +34,196 @@ impl CompressionType {
         match self {
             CompressionType::UNCOMPRESSED => 0x00,
             CompressionType::SNAPPY => 0x01,
+            CompressionType::GZIP => 0x02,
+            CompressionType::LZ4 => 0x07,
         }
     }
+
+    /// Compresses the given page data.
+    /// Returns `TsFileError::Compression` if the codec is not enabled via its cargo feature.
+    pub(crate) fn compress(&self, data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+        match self {
+            CompressionType::UNCOMPRESSED => Ok(data.to_vec()),
+            CompressionType::SNAPPY => compress_snappy(data),
+            CompressionType::GZIP => compress_gzip(data),
+            CompressionType::LZ4 => compress_lz4(data),
+        }
+    }
+
+    /// Decompresses page data, `uncompressed_size` is taken from the page header.
+    pub(crate) fn decompress(
+        &self,
+        data: &[u8],
+        uncompressed_size: usize,
+    ) -> Result<Vec<u8>, TsFileError> {
+        if uncompressed_size > self.max_uncompressed_size(data.len()) {
+            return Err(TsFileError::Corrupt {
+                source: Some(format!(
+                    "Page of {} bytes cannot decompress to {} bytes",
+                    data.len(),
+                    uncompressed_size
+                )),
+            });
+        }
+        match self {
+            CompressionType::UNCOMPRESSED => Ok(data.to_vec()),
+            CompressionType::SNAPPY => decompress_snappy(data, uncompressed_size),
+            CompressionType::GZIP => decompress_gzip(data, uncompressed_size),
+            CompressionType::LZ4 => decompress_lz4(data, uncompressed_size),
+        }
+    }
+
+    /// Upper bound of the size of `compressed_size` bytes after decompression, given by the
+    /// best ratio each codec can achieve. Protects readers from allocating absurd buffers for
+    /// corrupt page headers.
+    fn max_uncompressed_size(&self, compressed_size: usize) -> usize {
+        let ratio = match self {
+            CompressionType::UNCOMPRESSED => 1,
+            // A copy of 64 bytes takes 3 bytes
+            CompressionType::SNAPPY => 22,
+            // Theoretical limit of deflate
+            CompressionType::GZIP => 1032,
+            // Each additional byte of a match length adds up to 255 bytes
+            CompressionType::LZ4 => 255,
+        };
+        compressed_size.saturating_mul(ratio).saturating_add(64)
+    }
+}
+
+#[cfg(feature = "snappy")]
+fn compress_snappy(data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    snap::raw::Encoder::new()
+        .compress_vec(data)
+        .map_err(|_| TsFileError::Compression)
+}
+
+#[cfg(not(feature = "snappy"))]
+fn compress_snappy(_data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    log::error!("SNAPPY compression requires the 'snappy' feature");
+    Err(TsFileError::Compression)
+}
+
+#[cfg(feature = "snappy")]
+fn decompress_snappy(data: &[u8], uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    // Snappy allocates the size of its own header, which has to match the page header
+    let length = snap::raw::decompress_len(data).map_err(|_| TsFileError::Compression)?;
+    if length > uncompressed_size {
+        return Err(TsFileError::Corrupt {
+            source: Some(format!(
+                "Snappy data of {} bytes in a page of {} bytes",
+                length, uncompressed_size
+            )),
+        });
+    }
+    snap::raw::Decoder::new()
+        .decompress_vec(data)
+        .map_err(|_| TsFileError::Compression)
+}
+
+#[cfg(not(feature = "snappy"))]
+fn decompress_snappy(_data: &[u8], _uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    log::error!("SNAPPY compression requires the 'snappy' feature");
+    Err(TsFileError::Compression)
+}
+
+#[cfg(feature = "gzip")]
+fn compress_gzip(data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .map_err(|_| TsFileError::Compression)?;
+    encoder.finish().map_err(|_| TsFileError::Compression)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress_gzip(_data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    log::error!("GZIP compression requires the 'gzip' feature");
+    Err(TsFileError::Compression)
+}
+
+#[cfg(feature = "gzip")]
+fn decompress_gzip(data: &[u8], uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    use std::io::Read;
+
+    let mut result = Vec::with_capacity(uncompressed_size);
+    flate2::read::GzDecoder::new(data)
+        .read_to_end(&mut result)
+        .map_err(|_| TsFileError::Compression)?;
+    Ok(result)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress_gzip(_data: &[u8], _uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    log::error!("GZIP compression requires the 'gzip' feature");
+    Err(TsFileError::Compression)
+}
+
+/// IoTDB uses the raw LZ4 block format without a size prefix
+#[cfg(feature = "lz4")]
+fn compress_lz4(data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    Ok(lz4_flex::block::compress(data))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn compress_lz4(_data: &[u8]) -> Result<Vec<u8>, TsFileError> {
+    log::error!("LZ4 compression requires the 'lz4' feature");
+    Err(TsFileError::Compression)
+}
+
+#[cfg(feature = "lz4")]
+fn decompress_lz4(data: &[u8], uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    lz4_flex::block::decompress(data, uncompressed_size).map_err(|_| TsFileError::Compression)
+}
+
+#[cfg(not(feature = "lz4"))]
+fn decompress_lz4(_data: &[u8], _uncompressed_size: usize) -> Result<Vec<u8>, TsFileError> {
+    log::error!("LZ4 compression requires the 'lz4' feature");
+    Err(TsFileError::Compression)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::compression::CompressionType;
+
+    #[test]
+    fn serialize_matches_java_enum() {
+        for (compression, value) in [
+            (CompressionType::UNCOMPRESSED, 0),
+            (CompressionType::SNAPPY, 1),
+            (CompressionType::GZIP, 2),
+            (CompressionType::LZ4, 7),
+        ] {
+            assert_eq!(compression.serialize(), value);
+            assert_eq!(CompressionType::try_from(value), Ok(compression));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn gzip_round_trip() {
+        let data = b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbbb".to_vec();
+        let compressed = CompressionType::GZIP.compress(&data).unwrap();
+        // GZIP magic bytes
+        assert_eq!(&compressed[0..2], &[0x1f, 0x8b]);
+
+        let decompressed = CompressionType::GZIP
+            .decompress(&compressed, data.len())
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn lz4_round_trip() {
+        let data = b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbbb".to_vec();
+        let compressed = CompressionType::LZ4.compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+
+        let decompressed = CompressionType::LZ4
+            .decompress(&compressed, data.len())
+            .unwrap();
+        assert_eq!(decompressed, data);
+    }
 }
@@ -0,0 +1,416 @@
+//! Writer that can be shared between threads, e.g. one thread per data source.
+//!
+//! Every device has its own lock, so threads that write to different devices encode their
+//! points in parallel. Only flushes (automatic ones and `close`) lock the whole file.
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, File};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::writer::errors::TsFileError;
+#[cfg(feature = "parallel")]
+use crate::writer::group_writer::seal_in_parallel;
+use crate::writer::group_writer::GroupWriter;
+use crate::writer::listener::FlushListener;
+use crate::writer::tablet::Tablet;
+use crate::writer::ts_file_config::{DuplicatePolicy, ReorderWindow, TsFileConfig};
+use crate::writer::tsfile_io_writer::TsFileIoWriter;
+use crate::writer::tsfile_writer::{
+    check_batch, commit_file, create_file, flush_group, sync_handle, DataPoint,
+};
+use crate::writer::{BatchValue, IoTDBValue, PositionedWrite, Schema, WriteWrapper};
+
+/// `TsFileWriter` for concurrent writes through a shared reference (`Send + Sync`)
+///
+/// ```
+/// use std::sync::Arc;
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::concurrent_writer::ConcurrentTsFileWriter;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+/// use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+/// use tsfile_writer::writer::{IoTDBValue, TSDataType};
+///
+/// let schema = TsFileSchemaBuilder::new()
+///     .add(
+///         "plc1",
+///         DeviceBuilder::new()
+///             .add("s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED)
+///             .build(),
+///     )
+///     .add(
+///         "plc2",
+///         DeviceBuilder::new()
+///             .add("s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED)
+///             .build(),
+///     )
+///     .build();
+/// let writer = Arc::new(
+///     ConcurrentTsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap(),
+/// );
+///
+/// let threads: Vec<_> = ["plc1", "plc2"]
+///     .into_iter()
+///     .map(|device| {
+///         let writer = writer.clone();
+///         std::thread::spawn(move || {
+///             for i in 0..100 {
+///                 writer.write(device, "s1", i, IoTDBValue::LONG(i)).unwrap();
+///             }
+///         })
+///     })
+///     .collect();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// writer.close().unwrap();
+/// ```
+pub struct ConcurrentTsFileWriter<'a, T: PositionedWrite> {
+    /// Empty if the writer does not write to a file
+    filename: String,
+    group_writers: BTreeMap<&'a str, Mutex<GroupWriter<'a>>>,
+    file_io_writer: Mutex<TsFileIoWriter<'a, T>>,
+    record_count: AtomicU32,
+    record_count_for_next_mem_check: AtomicU32,
+    closed: AtomicBool,
+    config: TsFileConfig,
+}
+
+impl<'a> ConcurrentTsFileWriter<'a, WriteWrapper<File>> {
+    /// Creates the file, missing directories are created
+    pub fn new(
+        filename: &'a str,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<ConcurrentTsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        if let Some(folder) = std::path::Path::new(filename).parent() {
+            create_dir_all(folder)?;
+        }
+        let file = create_file(Path::new(filename), &config)?;
+        let sync_file = Some(sync_handle(&file)?);
+        let mut writer =
+            ConcurrentTsFileWriter::new_from_writer(schema, WriteWrapper::new(file), config)?;
+        writer.filename = filename.to_owned();
+        match writer.file_io_writer.get_mut() {
+            Ok(file_io_writer) => file_io_writer.sync_file = sync_file,
+            Err(_) => return Err(poisoned()),
+        }
+        Ok(writer)
+    }
+}
+
+impl<'a, T: PositionedWrite> ConcurrentTsFileWriter<'a, T> {
+    /// The reorder window and `DuplicatePolicy::OverwriteLast` are not supported, they need
+    /// a buffer per device that is shared by all threads.
+    pub fn new_from_writer(
+        schema: Schema<'a>,
+        file_writer: T,
+        config: TsFileConfig,
+    ) -> Result<ConcurrentTsFileWriter<'a, T>, TsFileError> {
+        config.validate()?;
+        if config.reorder_window != ReorderWindow::Disabled
+            || config.duplicate_policy == DuplicatePolicy::OverwriteLast
+        {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(
+                    "Reorder window and overwriting duplicates in the concurrent writer".to_owned(),
+                ),
+            });
+        }
+        let group_writers = schema
+            .measurement_groups
+            .iter()
+            .map(|(&path, group)| Ok((path, Mutex::new(GroupWriter::new(path, group, &config)?))))
+            .collect::<Result<_, TsFileError>>()?;
+        Ok(ConcurrentTsFileWriter {
+            filename: String::new(),
+            group_writers,
+            file_io_writer: Mutex::new(TsFileIoWriter::new(file_writer, config)?),
+            record_count: AtomicU32::new(0),
+            record_count_for_next_mem_check: AtomicU32::new(100),
+            closed: AtomicBool::new(false),
+            config,
+        })
+    }
+
+    pub fn write(
+        &self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamp: i64,
+        value: IoTDBValue,
+    ) -> Result<(), TsFileError> {
+        let records = self
+            .group(device)?
+            .write(measurement_id, timestamp, value)?;
+        self.written(records)
+    }
+
+    pub fn write_many(
+        &self,
+        device: &'a str,
+        timestamp: i64,
+        values: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        let records = self.group(device)?.write_many(timestamp, values)?;
+        self.written(records)
+    }
+
+    /// See `TsFileWriter::write_batch`
+    pub fn write_batch<V: BatchValue>(
+        &self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamps: &[i64],
+        values: &[V],
+    ) -> Result<(), TsFileError> {
+        let values = V::values(values);
+        check_batch(timestamps, values)?;
+        let mut group = self.group(device)?;
+        if !group.can_write_batch(measurement_id, timestamps) {
+            drop(group);
+            for (i, &timestamp) in timestamps.iter().enumerate() {
+                self.write(device, measurement_id, timestamp, values.get(i))?;
+            }
+            return Ok(());
+        }
+        let records = group.write_batch(measurement_id, timestamps, values)?;
+        drop(group);
+        self.written(records)
+    }
+
+    /// See `TsFileWriter::write_tablet`
+    pub fn write_tablet(&self, device: &'a str, tablet: Tablet) -> Result<(), TsFileError> {
+        let records = self.group(device)?.write_tablet(tablet)?;
+        self.written(records)
+    }
+
+    /// Sets the listener that is notified about every chunk group, flush and the close
+    pub fn set_flush_listener(
+        &self,
+        listener: impl FlushListener + 'static,
+    ) -> Result<(), TsFileError> {
+        lock(&self.file_io_writer)?.listener = Some(Box::new(listener));
+        Ok(())
+    }
+
+    /// Flushes all buffered data as chunk groups to the file
+    pub fn flush(&self) -> Result<(), TsFileError> {
+        let mut file_io_writer = lock(&self.file_io_writer)?;
+        self.flush_all_chunk_groups(&mut file_io_writer)
+    }
+
+    /// Flushes all data and writes the metadata, later writes fail
+    pub fn close(&self) -> Result<(), TsFileError> {
+        let mut file_io_writer = lock(&self.file_io_writer)?;
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Err(closed());
+        }
+        self.flush_all_chunk_groups(&mut file_io_writer)?;
+        file_io_writer.end_file()?;
+        if !self.filename.is_empty() {
+            commit_file(Path::new(&self.filename), &self.config)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the sink, e.g. the buffer with the written TsFile (after `close`)
+    pub fn into_inner(self) -> Result<T, TsFileError> {
+        match self.file_io_writer.into_inner() {
+            Ok(mut file_io_writer) => {
+                file_io_writer
+                    .out
+                    .take()
+                    .ok_or_else(|| TsFileError::IllegalState {
+                        source: Some("The sink was already taken".to_owned()),
+                    })
+            }
+            Err(_) => Err(poisoned()),
+        }
+    }
+
+    fn group(&self, device: &str) -> Result<MutexGuard<'_, GroupWriter<'a>>, TsFileError> {
+        let group = match self.group_writers.get(device) {
+            Some(group) => lock(group)?,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        };
+        // Checked under the lock of the device, so `close` flushes all accepted points
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(closed());
+        }
+        Ok(group)
+    }
+
+    /// Counts the records like `TsFileWriter` and flushes if the memory budget is exceeded,
+    /// only one thread checks the memory at a time
+    fn written(&self, records: u32) -> Result<(), TsFileError> {
+        let record_count = self.record_count.fetch_add(records, Ordering::SeqCst) + records;
+        if record_count < self.record_count_for_next_mem_check.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut file_io_writer = lock(&self.file_io_writer)?;
+        let record_count_for_next_mem_check =
+            self.record_count_for_next_mem_check.load(Ordering::SeqCst);
+        if self.record_count.load(Ordering::SeqCst) < record_count_for_next_mem_check {
+            // Another thread checked in the meantime
+            return Ok(());
+        }
+        let threshold = self.config.chunk_group_size_threshold;
+        let mut mem_size = 0_u32;
+        for group in self.group_writers.values() {
+            mem_size += lock(group)?.update_max_group_mem_size();
+        }
+        let mem_size = mem_size.max(1);
+        self.record_count_for_next_mem_check.store(
+            ((record_count_for_next_mem_check as u64 * threshold as u64 / mem_size as u64) as u32)
+                .max(1),
+            Ordering::SeqCst,
+        );
+        if mem_size > threshold {
+            self.flush_all_chunk_groups(&mut file_io_writer)?;
+        }
+        Ok(())
+    }
+
+    /// The caller holds the lock of the io writer, all devices are locked for the flush
+    fn flush_all_chunk_groups(
+        &self,
+        file_io_writer: &mut TsFileIoWriter<'a, T>,
+    ) -> Result<(), TsFileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing_crate::info_span!("flush").entered();
+        let mut groups = self
+            .group_writers
+            .values()
+            .map(lock)
+            .collect::<Result<Vec<_>, TsFileError>>()?;
+        #[cfg(feature = "parallel")]
+        seal_in_parallel(
+            groups.iter_mut().map(|group| &mut **group),
+            self.config.flush_threads,
+        )?;
+        for (&device_id, group) in self.group_writers.keys().zip(groups.iter_mut()) {
+            flush_group(file_io_writer, device_id, group)?;
+        }
+        file_io_writer.end_flush();
+        self.record_count.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, TsFileError> {
+    mutex.lock().map_err(|_| poisoned())
+}
+
+fn poisoned() -> TsFileError {
+    TsFileError::IllegalState {
+        source: Some("A thread panicked while writing".to_owned()),
+    }
+}
+
+pub(crate) fn closed() -> TsFileError {
+    TsFileError::IllegalState {
+        source: Some("The writer is already closed".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::concurrent_writer::ConcurrentTsFileWriter;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::{ReorderWindow, TsFileConfig};
+    use crate::writer::tsfile_writer::DataPoint;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    const DEVICES: [&str; 4] = ["plc1", "plc2", "plc3", "plc4"];
+
+    fn schema() -> Schema<'static> {
+        let mut builder = TsFileSchemaBuilder::new();
+        for (i, device) in DEVICES.iter().enumerate() {
+            let mut device_builder = if i % 2 == 0 {
+                DeviceBuilder::new()
+            } else {
+                DeviceBuilder::aligned()
+            };
+            builder.add(
+                device,
+                device_builder
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            );
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn concurrent_writes() -> Result<(), TsFileError> {
+        let config = TsFileConfig::default().chunk_group_size_threshold(64 * 1024);
+        let writer = Arc::new(ConcurrentTsFileWriter::new_from_writer(
+            schema(),
+            vec![],
+            config,
+        )?);
+        let threads: Vec<_> = DEVICES
+            .iter()
+            .map(|&device| {
+                let writer = writer.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50_000 {
+                        writer
+                            .write_many(device, i, vec![DataPoint::new("s1", IoTDBValue::LONG(i))])
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        writer.close()?;
+        assert!(writer
+            .write("plc1", "s1", 50_000, IoTDBValue::LONG(0))
+            .is_err());
+        let writer = match Arc::try_unwrap(writer) {
+            Ok(writer) => writer,
+            Err(_) => panic!("Writer is still shared"),
+        };
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()?))?;
+        for device in DEVICES {
+            let metadata = reader.get_timeseries_metadata(device, "s1")?.unwrap();
+            // The memory budget caused flushes while writing
+            assert!(metadata.chunk_metadata_list.len() > 1);
+            let points = reader.query(device, "s1")?.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points.len(), 50_000);
+            assert!(points
+                .iter()
+                .enumerate()
+                .all(|(i, p)| p == &(i as i64, IoTDBValue::LONG(i as i64))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_config() {
+        let config = TsFileConfig::default().reorder_window(ReorderWindow::Points(10));
+        assert!(matches!(
+            ConcurrentTsFileWriter::new_from_writer(schema(), vec![], config),
+            Err(TsFileError::UnsupportedFeature { .. })
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+//! Declarative description of a schema, e.g. in a config file (feature `serde`, for TOML
+//! feature `toml`). A `Schema` is serialized in the same format.
+//!
+//! ```
+//! use tsfile_writer::writer::Schema;
+//!
+//! let json = r#"{
+//!     "devices": {
+//!         "root.sg.d1": {
+//!             "measurements": {
+//!                 "s1": { "data_type": "INT64", "encoding": "TS2DIFF", "compression": "UNCOMPRESSED" }
+//!             }
+//!         },
+//!         "root.sg.d2": {
+//!             "aligned": true,
+//!             "measurements": {
+//!                 "s1": { "data_type": "DOUBLE", "encoding": "GORILLA", "compression": "UNCOMPRESSED" }
+//!             }
+//!         }
+//!     }
+//! }"#;
+//! let schema = Schema::from_json(json).unwrap();
+//! ```
+use crate::writer::errors::TsFileError;
+use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+use crate::writer::{MeasurementGroup, MeasurementSchema, Schema};
+use serde_crate::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// All devices of a schema by their path. Names are borrowed from the input, so they must not
+/// contain escape sequences.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(crate = "serde_crate", deny_unknown_fields)]
+pub struct SchemaDeclaration<'a> {
+    #[serde(borrow)]
+    pub devices: BTreeMap<&'a str, DeviceDeclaration<'a>>,
+}
+
+/// The measurements of a device, which is not aligned by default
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(crate = "serde_crate", deny_unknown_fields)]
+pub struct DeviceDeclaration<'a> {
+    #[serde(default)]
+    pub aligned: bool,
+    #[serde(borrow)]
+    pub measurements: BTreeMap<&'a str, MeasurementSchema>,
+}
+
+impl<'a> SchemaDeclaration<'a> {
+    /// Builds the schema with the validation of the `TsFileSchemaBuilder`
+    pub fn build(&self) -> Result<Schema<'a>, TsFileError> {
+        let mut builder = TsFileSchemaBuilder::new();
+        for (&device, declaration) in &self.devices {
+            builder.add(device, declaration.build()?);
+        }
+        builder.try_build()
+    }
+}
+
+impl<'a> DeviceDeclaration<'a> {
+    /// Builds the device with the validation of the `DeviceBuilder`
+    pub fn build(&self) -> Result<MeasurementGroup<'a>, TsFileError> {
+        let mut builder = match self.aligned {
+            true => DeviceBuilder::aligned(),
+            false => DeviceBuilder::new(),
+        };
+        for (&measurement, schema) in &self.measurements {
+            builder.add(
+                measurement,
+                schema.data_type,
+                schema.encoding,
+                schema.compression,
+            );
+        }
+        builder.try_build()
+    }
+}
+
+impl<'a> TryFrom<SchemaDeclaration<'a>> for Schema<'a> {
+    type Error = TsFileError;
+
+    fn try_from(declaration: SchemaDeclaration<'a>) -> Result<Self, Self::Error> {
+        declaration.build()
+    }
+}
+
+impl<'a> TryFrom<DeviceDeclaration<'a>> for MeasurementGroup<'a> {
+    type Error = TsFileError;
+
+    fn try_from(declaration: DeviceDeclaration<'a>) -> Result<Self, Self::Error> {
+        declaration.build()
+    }
+}
+
+impl<'a> Schema<'a> {
+    /// Reads the schema from a JSON `SchemaDeclaration`, see `from_toml` for TOML. Other formats
+    /// can be read with the respective serde crate, a `Schema` implements `Deserialize` as well.
+    pub fn from_json(json: &'a str) -> Result<Schema<'a>, TsFileError> {
+        serde_json::from_str(json).map_err(|e| TsFileError::SchemaError {
+            source: Some(format!("Invalid schema declaration: {}", e)),
+        })
+    }
+
+    /// Writes the schema as JSON `SchemaDeclaration`, devices and measurements are sorted
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("A schema is always serializable")
+    }
+}
+
+/// `SchemaDeclaration` with owned names, the toml crate can not borrow from its input
+#[cfg(feature = "toml")]
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate", deny_unknown_fields)]
+struct OwnedSchemaDeclaration {
+    devices: BTreeMap<String, OwnedDeviceDeclaration>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate", deny_unknown_fields)]
+struct OwnedDeviceDeclaration {
+    #[serde(default)]
+    aligned: bool,
+    measurements: BTreeMap<String, MeasurementSchema>,
+}
+
+/// The name as it is written in the text
+#[cfg(feature = "toml")]
+fn borrow_name<'a>(text: &'a str, name: &str) -> Result<&'a str, TsFileError> {
+    match text.find(name) {
+        Some(start) => Ok(&text[start..start + name.len()]),
+        None => Err(TsFileError::SchemaError {
+            source: Some(format!(
+                "Invalid schema declaration: '{}' contains escape sequences",
+                name
+            )),
+        }),
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<'a> Schema<'a> {
+    /// Reads the schema from a TOML `SchemaDeclaration`. Like for `from_json` the names are
+    /// borrowed from the text, so they must not contain escape sequences.
+    pub fn from_toml(toml: &'a str) -> Result<Schema<'a>, TsFileError> {
+        let declaration: OwnedSchemaDeclaration =
+            toml_crate::from_str(toml).map_err(|e| TsFileError::SchemaError {
+                source: Some(format!("Invalid schema declaration: {}", e)),
+            })?;
+        let mut devices = BTreeMap::new();
+        for (device, declaration) in declaration.devices {
+            let mut measurements = BTreeMap::new();
+            for (measurement, schema) in declaration.measurements {
+                measurements.insert(borrow_name(toml, &measurement)?, schema);
+            }
+            devices.insert(
+                borrow_name(toml, &device)?,
+                DeviceDeclaration {
+                    aligned: declaration.aligned,
+                    measurements,
+                },
+            );
+        }
+        SchemaDeclaration { devices }.build()
+    }
+
+    /// Writes the schema as TOML `SchemaDeclaration`, devices and measurements are sorted
+    pub fn to_toml(&self) -> String {
+        toml_crate::to_string(self).expect("A schema is always serializable")
+    }
+}
+
+/// The measurements of a `DeviceTemplate` are shared, serde only serializes an `Arc` with the
+/// `rc` feature
+pub(crate) fn shared<V: Serialize, S: Serializer>(
+    map: &Arc<BTreeMap<&str, V>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    (**map).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::{CompressionType, IoTDBValue, Schema, TSDataType, TSEncoding};
+
+    #[test]
+    fn from_json() {
+        let json = r#"{
+            "devices": {
+                "root.sg.d1": {
+                    "measurements": {
+                        "s1": { "data_type": "INT64", "encoding": "TS2DIFF", "compression": "UNCOMPRESSED" },
+                        "s2": { "data_type": "FLOAT", "encoding": "GORILLA", "compression": "SNAPPY" }
+                    }
+                },
+                "root.sg.d2": {
+                    "aligned": true,
+                    "measurements": {
+                        "s1": { "data_type": "TEXT", "encoding": "PLAIN", "compression": "GZIP" }
+                    }
+                }
+            }
+        }"#;
+        let schema = Schema::from_json(json).unwrap();
+
+        let d1 = &schema.measurement_groups["root.sg.d1"];
+        assert!(!d1.is_aligned());
+        let s2 = &d1.measurement_schemas["s2"];
+        assert_eq!(s2.data_type, TSDataType::FLOAT);
+        assert_eq!(s2.encoding, TSEncoding::GORILLA);
+        assert_eq!(s2.compression, CompressionType::SNAPPY);
+        let d2 = &schema.measurement_groups["root.sg.d2"];
+        assert!(d2.is_aligned());
+        assert_eq!(d2.measurement_schemas["s1"].data_type, TSDataType::TEXT);
+    }
+
+    #[test]
+    fn invalid_declarations() {
+        let error = |json: &str| match Schema::from_json(json) {
+            Err(TsFileError::SchemaError {
+                source: Some(source),
+            }) => source,
+            _ => panic!("No schema error for {}", json),
+        };
+        assert!(error(r#"{ "devices": { "d1": { "measurements": {} } } }"#)
+            .contains("Device has no measurements"));
+        assert!(error(
+            r#"{ "devices": { "d1": { "measurements": {
+                "s1": { "data_type": "VECTOR", "encoding": "PLAIN", "compression": "UNCOMPRESSED" }
+            } } } }"#
+        )
+        .starts_with("Invalid schema declaration: unknown variant `VECTOR`"));
+        assert!(error(
+            r#"{ "devices": { "d 1": { "measurements": {
+                "s1": { "data_type": "INT32", "encoding": "PLAIN", "compression": "UNCOMPRESSED" }
+            } } } }"#
+        )
+        .contains("illegal character"));
+        assert!(error(r#"{ "device": {} }"#).contains("unknown field `device`"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s2",
+                        TSDataType::INT32,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::SNAPPY,
+                    )
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let json = schema.to_json();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"devices":{"d1":{"measurements":{"#,
+                r#""s1":{"data_type":"INT64","encoding":"PLAIN","compression":"UNCOMPRESSED"},"#,
+                r#""s2":{"data_type":"INT32","encoding":"TS2DIFF","compression":"SNAPPY"}},"#,
+                r#""aligned":false},"d2":{"measurements":{"#,
+                r#""s1":{"data_type":"DOUBLE","encoding":"GORILLA","compression":"UNCOMPRESSED"}},"#,
+                r#""aligned":true}}}"#
+            )
+        );
+        assert_eq!(Schema::from_json(&json).unwrap().to_json(), json);
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn toml_round_trip() {
+        let toml = r#"
+            [devices."root.sg.d1".measurements]
+            s1 = { data_type = "INT64", encoding = "TS2DIFF", compression = "UNCOMPRESSED" }
+            s2 = { data_type = "FLOAT", encoding = "GORILLA", compression = "SNAPPY" }
+
+            [devices."root.sg.d2"]
+            aligned = true
+            measurements.s1 = { data_type = "TEXT", encoding = "PLAIN", compression = "GZIP" }
+        "#;
+        let schema = Schema::from_toml(toml).unwrap();
+        let d1 = &schema.measurement_groups["root.sg.d1"];
+        assert!(!d1.is_aligned());
+        assert_eq!(d1.measurement_schemas["s2"].encoding, TSEncoding::GORILLA);
+        assert!(schema.measurement_groups["root.sg.d2"].is_aligned());
+
+        let toml = schema.to_toml();
+        assert_eq!(
+            toml,
+            concat!(
+                "[devices.\"root.sg.d1\"]\n",
+                "aligned = false\n\n",
+                "[devices.\"root.sg.d1\".measurements.s1]\n",
+                "data_type = \"INT64\"\n",
+                "encoding = \"TS2DIFF\"\n",
+                "compression = \"UNCOMPRESSED\"\n\n",
+                "[devices.\"root.sg.d1\".measurements.s2]\n",
+                "data_type = \"FLOAT\"\n",
+                "encoding = \"GORILLA\"\n",
+                "compression = \"SNAPPY\"\n\n",
+                "[devices.\"root.sg.d2\"]\n",
+                "aligned = true\n\n",
+                "[devices.\"root.sg.d2\".measurements.s1]\n",
+                "data_type = \"TEXT\"\n",
+                "encoding = \"PLAIN\"\n",
+                "compression = \"GZIP\"\n",
+            )
+        );
+        let read = Schema::from_toml(&toml).unwrap();
+        assert_eq!(read.to_toml(), toml);
+        assert_eq!(read.to_json(), schema.to_json());
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn invalid_toml_declarations() {
+        let error = |toml: &str| match Schema::from_toml(toml) {
+            Err(TsFileError::SchemaError {
+                source: Some(source),
+            }) => source,
+            _ => panic!("No schema error for {}", toml),
+        };
+        assert!(error("[devices.d1.measurements]").contains("Device has no measurements"));
+        assert!(error(
+            r#"[devices."d\u0031".measurements]
+            s1 = { data_type = "INT32", encoding = "PLAIN", compression = "UNCOMPRESSED" }"#
+        )
+        .contains("'d1' contains escape sequences"));
+        assert!(error("[device]").contains("unknown field `device`"));
+    }
+
+    #[test]
+    fn values() {
+        let values = vec![
+            IoTDBValue::DOUBLE(1.5),
+            IoTDBValue::FLOAT(2.5),
+            IoTDBValue::INT(-3),
+            IoTDBValue::LONG(4),
+            IoTDBValue::TEXT("five".to_owned()),
+        ];
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"DOUBLE":1.5},{"FLOAT":2.5},{"INT":-3},{"LONG":4},{"TEXT":"five"}]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<IoTDBValue>>(&json).unwrap(),
+            values
+        );
+    }
+}
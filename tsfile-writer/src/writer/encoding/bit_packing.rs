@@ -0,0 +1,224 @@
+//! Kernels for the blocks of the TS_2DIFF encoding.
+//!
+//! The loops work on whole blocks without data dependent branches, so the compiler vectorizes
+//! them. On x86_64 they are compiled a second time with AVX2 enabled, which is selected at
+//! runtime if the CPU supports it.
+
+/// Integer types that are encoded in TS_2DIFF blocks
+pub(crate) trait Packable: Copy {
+    fn wrapping_sub(self, other: Self) -> Self;
+    /// The bits of the value as unsigned number, e.g. -1_i32 is `u32::MAX`
+    fn to_bits(self) -> u64;
+}
+
+impl Packable for i64 {
+    #[inline(always)]
+    fn wrapping_sub(self, other: Self) -> Self {
+        i64::wrapping_sub(self, other)
+    }
+
+    #[inline(always)]
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+}
+
+impl Packable for i32 {
+    #[inline(always)]
+    fn wrapping_sub(self, other: Self) -> Self {
+        i32::wrapping_sub(self, other)
+    }
+
+    #[inline(always)]
+    fn to_bits(self) -> u64 {
+        self as u32 as u64
+    }
+}
+
+/// Appends the differences of consecutive values to `out`, starting with the difference of the
+/// first value to `previous`, and returns the smallest difference. `values` must not be empty.
+pub(crate) fn deltas<T: Packable + Ord>(previous: T, values: &[T], out: &mut Vec<T>) -> T {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::is_x86_feature_detected!("avx2") {
+            // Safe as the CPU supports AVX2
+            return unsafe { deltas_avx2(previous, values, out) };
+        }
+    }
+    deltas_portable(previous, values, out)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn deltas_avx2<T: Packable + Ord>(previous: T, values: &[T], out: &mut Vec<T>) -> T {
+    deltas_portable(previous, values, out)
+}
+
+#[inline(always)]
+fn deltas_portable<T: Packable + Ord>(previous: T, values: &[T], out: &mut Vec<T>) -> T {
+    let first = values[0].wrapping_sub(previous);
+    out.reserve(values.len());
+    out.push(first);
+    let mut min = first;
+    for pair in values.windows(2) {
+        let delta = pair[1].wrapping_sub(pair[0]);
+        min = min.min(delta);
+        out.push(delta);
+    }
+    min
+}
+
+/// Subtracts the minimum from all deltas (wrapping like in Java) and returns the number of
+/// bits of the largest result
+pub(crate) fn normalize<T: Packable>(deltas: &mut [T], min: T) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::is_x86_feature_detected!("avx2") {
+            // Safe as the CPU supports AVX2
+            return unsafe { normalize_avx2(deltas, min) };
+        }
+    }
+    normalize_portable(deltas, min)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn normalize_avx2<T: Packable>(deltas: &mut [T], min: T) -> u32 {
+    normalize_portable(deltas, min)
+}
+
+#[inline(always)]
+fn normalize_portable<T: Packable>(deltas: &mut [T], min: T) -> u32 {
+    // The width of the largest value is the width of all values or-ed together
+    let mut bits = 0_u64;
+    for delta in deltas.iter_mut() {
+        *delta = delta.wrapping_sub(min);
+        bits |= delta.to_bits();
+    }
+    64 - bits.leading_zeros()
+}
+
+/// Appends the lowest `width` bits of every value to the buffer, most significant bit first.
+/// The last byte is padded with zeros.
+pub(crate) fn pack<T: Packable>(values: &[T], width: u32, out: &mut Vec<u8>) {
+    if width == 0 {
+        return;
+    }
+    out.reserve(values.len() * width as usize / 8 + 1);
+    if matches!(width, 8 | 16 | 24 | 32 | 40 | 48 | 56 | 64) {
+        let skip = 8 - width as usize / 8;
+        for value in values {
+            out.extend_from_slice(&value.to_bits().to_be_bytes()[skip..]);
+        }
+        return;
+    }
+    let mask = u64::MAX >> (64 - width);
+    let rest = if width <= 16 {
+        // 8 values of up to 16 bits are exactly `width` bytes of an u128
+        let skip = 16 - width as usize;
+        let blocks = values.chunks_exact(8);
+        let rest = blocks.remainder();
+        for block in blocks {
+            let mut bits = 0_u128;
+            for value in block {
+                bits = (bits << width) | (value.to_bits() & mask) as u128;
+            }
+            out.extend_from_slice(&bits.to_be_bytes()[skip..]);
+        }
+        rest
+    } else {
+        values
+    };
+    // At most 7 pending bits and one value are kept in the u128
+    let mut bits = 0_u128;
+    let mut count = 0;
+    for value in rest {
+        bits = (bits << width) | (value.to_bits() & mask) as u128;
+        count += width;
+        while count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    if count > 0 {
+        out.push((bits << (8 - count)) as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::encoding::bit_packing::{deltas, normalize, pack};
+    use crate::writer::encoding::time_encoder::{IntTs2DiffEncoder, LongTs2DiffEncoder};
+
+    /// Deterministic pseudo random numbers
+    fn random(count: usize) -> Vec<i64> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as i64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut deltas = vec![3_i64, 5, 10];
+        assert_eq!(normalize(&mut deltas, 3), 3);
+        assert_eq!(deltas, [0, 2, 7]);
+
+        let mut deltas = vec![i32::MAX, i32::MIN];
+        assert_eq!(normalize(&mut deltas, i32::MIN), 32);
+        assert_eq!(deltas, [-1, 0]);
+
+        assert_eq!(normalize(&mut [7_i64; 128], 7), 0);
+        assert_eq!(normalize::<i64>(&mut [], i64::MAX), 0);
+    }
+
+    #[test]
+    fn test_deltas() {
+        let mut out = vec![0];
+        assert_eq!(deltas(1_i64, &[3, 4, 10, 9], &mut out), -1);
+        assert_eq!(out, [0, 2, 1, 6, -1]);
+
+        let mut out = vec![];
+        assert_eq!(deltas(i32::MIN, &[i32::MAX], &mut out), -1);
+    }
+
+    #[test]
+    fn test_pack_matches_reference() {
+        let values = random(131);
+        for width in 0..=64 {
+            let long_values: Vec<i64> = values
+                .iter()
+                .map(|&v| {
+                    if width == 64 {
+                        v
+                    } else {
+                        v & (i64::MAX >> (63 - width))
+                    }
+                })
+                .collect();
+            let mut expected = vec![];
+            for (i, &value) in long_values.iter().enumerate() {
+                LongTs2DiffEncoder::to_bytes(value, &mut expected, i * width as usize, width);
+            }
+            let mut packed = vec![];
+            pack(&long_values, width, &mut packed);
+            assert_eq!(packed, expected, "width {}", width);
+
+            if width <= 32 {
+                let int_values: Vec<i32> = long_values.iter().map(|&v| v as i32).collect();
+                let mut expected = vec![];
+                for (i, &value) in int_values.iter().enumerate() {
+                    IntTs2DiffEncoder::to_bytes(value, &mut expected, i * width as usize, width);
+                }
+                let mut packed = vec![];
+                pack(&int_values, width, &mut packed);
+                assert_eq!(packed, expected, "width {}", width);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! TS2DIFF Encoding for FLOAT and DOUBLE with a limited precision (`max_point_number`).
+//!
+//! Like the `FloatEncoder` of IoTDB values are multiplied by `10^max_point_number`, rounded
+//! and encoded as INT32 (FLOAT) or INT64 (DOUBLE). Each page starts with the number of
+//! decimal places as var int, followed by the integer encoding.
+use crate::writer::encoding::time_encoder::{IntTs2DiffEncoder, LongTs2DiffEncoder};
+use crate::writer::encoding::Encoder;
+use crate::writer::{write_var_u32, IoTDBValue, TSDataType, TsFileError};
+
+/// Decimal places of FLOAT and DOUBLE values if the schema does not set them, like the
+/// `float_precision` of IoTDB
+pub const DEFAULT_MAX_POINT_NUMBER: u32 = 2;
+
+pub struct FloatEncoder {
+    encoder: Box<dyn Encoder>,
+    max_point_number: u32,
+    max_point_value: f64,
+    has_values: bool,
+}
+
+impl FloatEncoder {
+    pub(crate) fn new(
+        data_type: TSDataType,
+        max_point_number: u32,
+    ) -> Result<FloatEncoder, TsFileError> {
+        let encoder: Box<dyn Encoder> = match data_type {
+            TSDataType::FLOAT => Box::new(IntTs2DiffEncoder::new()),
+            TSDataType::DOUBLE => Box::new(LongTs2DiffEncoder::new()),
+            _ => return Err(TsFileError::Encoding),
+        };
+        Ok(FloatEncoder {
+            encoder,
+            max_point_number,
+            max_point_value: 10_f64.powi(max_point_number as i32),
+            has_values: false,
+        })
+    }
+
+    /// `Math.round` of Java, i.e. halves are rounded up (towards positive infinity)
+    fn round(&self, value: f64) -> i64 {
+        (value * self.max_point_value + 0.5).floor() as i64
+    }
+}
+
+impl Encoder for FloatEncoder {
+    fn write(&mut self, value: &IoTDBValue) -> Result<(), TsFileError> {
+        let value = match value {
+            IoTDBValue::FLOAT(v) => IoTDBValue::INT(self.round(*v as f64) as i32),
+            IoTDBValue::DOUBLE(v) => IoTDBValue::LONG(self.round(*v)),
+            _ => return Err(TsFileError::WrongTypeForSeries),
+        };
+        self.encoder.write(&value)?;
+        self.has_values = true;
+        Ok(())
+    }
+
+    fn size(&mut self) -> u32 {
+        self.encoder.size()
+    }
+
+    fn get_max_byte_size(&self) -> u32 {
+        // The var int of the decimal places
+        5 + self.encoder.get_max_byte_size()
+    }
+
+    fn serialize(&mut self, buffer: &mut Vec<u8>) {
+        if self.has_values {
+            // Writing to a Vec does not fail
+            let _ = write_var_u32(self.max_point_number, buffer);
+        }
+        self.encoder.serialize(buffer);
+        self.has_values = false;
+    }
+
+    fn reset(&mut self) {
+        self.encoder.reset();
+        self.has_values = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::encoding::float::FloatEncoder;
+    use crate::writer::encoding::time_encoder::IntTs2DiffEncoder;
+    use crate::writer::encoding::Encoder;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn test_float_encoding() {
+        let mut encoder = FloatEncoder::new(TSDataType::FLOAT, 2).unwrap();
+        for value in [1.0, 1.234, -0.005] {
+            encoder.write(&IoTDBValue::FLOAT(value)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // The precision followed by the TS2DIFF encoding of 100, 123 and 0
+        // (-0.5 is rounded up like by Math.round)
+        let mut expected = vec![0x02];
+        let mut ints = IntTs2DiffEncoder::new();
+        for value in [100, 123, 0] {
+            ints.write(&IoTDBValue::INT(value)).unwrap();
+        }
+        ints.serialize(&mut expected);
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn test_empty_page() {
+        let mut encoder = FloatEncoder::new(TSDataType::DOUBLE, 3).unwrap();
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        assert!(FloatEncoder::new(TSDataType::INT32, 2).is_err());
+        let mut encoder = FloatEncoder::new(TSDataType::FLOAT, 2).unwrap();
+        assert!(encoder.write(&IoTDBValue::DOUBLE(1.0)).is_err());
+    }
+}
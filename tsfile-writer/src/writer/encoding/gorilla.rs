@@ -0,0 +1,222 @@
+//! XOR based Gorilla Encoding (V2) for floating point values
+use crate::writer::encoding::Encoder;
+use crate::writer::{IoTDBValue, TsFileError};
+
+impl TryFrom<&IoTDBValue> for f32 {
+    type Error = TsFileError;
+
+    fn try_from(value: &IoTDBValue) -> Result<Self, Self::Error> {
+        match value {
+            IoTDBValue::FLOAT(v) => Ok(*v),
+            _ => Err(TsFileError::WrongTypeForSeries),
+        }
+    }
+}
+
+impl TryFrom<&IoTDBValue> for f64 {
+    type Error = TsFileError;
+
+    fn try_from(value: &IoTDBValue) -> Result<Self, Self::Error> {
+        match value {
+            IoTDBValue::DOUBLE(v) => Ok(*v),
+            _ => Err(TsFileError::WrongTypeForSeries),
+        }
+    }
+}
+
+macro_rules! gorilla_encoder {
+    ( $name:ident, $type:ty, $bits:ty, $value_bits:expr, $leading_zero_bits:expr, $meaningful_xor_bits:expr ) => {
+        pub struct $name {
+            first_value_was_written: bool,
+            stored_value: $bits,
+            stored_leading_zeros: u32,
+            stored_trailing_zeros: u32,
+            bits_left: u32,
+            current_byte: u8,
+            buffer: Vec<u8>,
+        }
+
+        impl Encoder for $name {
+            fn write(&mut self, value: &IoTDBValue) -> Result<(), TsFileError> {
+                let value: $type = value.try_into()?;
+                self.encode(value.to_bits());
+                Ok(())
+            }
+
+            fn size(&mut self) -> u32 {
+                self.buffer.len() as u32
+            }
+
+            fn get_max_byte_size(&self) -> u32 {
+                // Ending value plus the padding byte written on flush
+                (2 + $value_bits + $leading_zero_bits + $meaningful_xor_bits) / 8 + 2
+            }
+
+            fn serialize(&mut self, buffer: &mut Vec<u8>) {
+                self.flush();
+                buffer.extend_from_slice(&self.buffer);
+            }
+
+            fn reset(&mut self) {
+                self.reset_state();
+                self.buffer.clear();
+            }
+        }
+
+        impl $name {
+            pub(crate) fn new() -> $name {
+                Self {
+                    first_value_was_written: false,
+                    stored_value: 0,
+                    stored_leading_zeros: u32::MAX,
+                    stored_trailing_zeros: 0,
+                    bits_left: 8,
+                    current_byte: 0,
+                    buffer: vec![],
+                }
+            }
+
+            fn reset_state(&mut self) {
+                self.first_value_was_written = false;
+                self.stored_value = 0;
+                self.stored_leading_zeros = u32::MAX;
+                self.stored_trailing_zeros = 0;
+                self.bits_left = 8;
+                self.current_byte = 0;
+            }
+
+            fn encode(&mut self, value: $bits) {
+                if self.first_value_was_written {
+                    self.compress_value(value);
+                } else {
+                    self.stored_value = value;
+                    self.write_bits(value as u64, $value_bits);
+                    self.first_value_was_written = true;
+                }
+            }
+
+            fn compress_value(&mut self, value: $bits) {
+                let xor = self.stored_value ^ value;
+                self.stored_value = value;
+
+                if xor == 0 {
+                    self.write_bit(false);
+                    return;
+                }
+                self.write_bit(true);
+
+                let leading_zeros = xor.leading_zeros();
+                let trailing_zeros = xor.trailing_zeros();
+                if leading_zeros >= self.stored_leading_zeros
+                    && trailing_zeros >= self.stored_trailing_zeros
+                {
+                    // Reuse the window of the previous value
+                    self.write_bit(false);
+                    let significant_bits =
+                        $value_bits - self.stored_leading_zeros - self.stored_trailing_zeros;
+                    self.write_bits((xor >> self.stored_trailing_zeros) as u64, significant_bits);
+                } else {
+                    self.write_bit(true);
+                    let significant_bits = $value_bits - leading_zeros - trailing_zeros;
+                    self.write_bits(leading_zeros as u64, $leading_zero_bits);
+                    // significant bits is always > 0, so we store it reduced by one
+                    self.write_bits((significant_bits - 1) as u64, $meaningful_xor_bits);
+                    self.write_bits((xor >> trailing_zeros) as u64, significant_bits);
+
+                    self.stored_leading_zeros = leading_zeros;
+                    self.stored_trailing_zeros = trailing_zeros;
+                }
+            }
+
+            fn flush(&mut self) {
+                if !self.first_value_was_written {
+                    return;
+                }
+                // Ending marker is NaN
+                self.encode(<$type>::NAN.to_bits());
+                // The (possibly empty) last byte is always written, the decoder relies on it
+                self.bits_left = 0;
+                self.flip_byte();
+                self.reset_state();
+            }
+
+            fn write_bit(&mut self, bit: bool) {
+                if bit {
+                    self.current_byte |= 1 << (self.bits_left - 1);
+                }
+                self.bits_left -= 1;
+                self.flip_byte();
+            }
+
+            fn write_bits(&mut self, value: u64, mut bits: u32) {
+                while bits > 0 {
+                    if bits >= self.bits_left {
+                        let shift = bits - self.bits_left;
+                        self.current_byte |= ((value >> shift) & ((1 << self.bits_left) - 1)) as u8;
+                        bits -= self.bits_left;
+                        self.bits_left = 0;
+                    } else {
+                        let shift = self.bits_left - bits;
+                        self.current_byte |= ((value & ((1 << bits) - 1)) << shift) as u8;
+                        self.bits_left -= bits;
+                        bits = 0;
+                    }
+                    self.flip_byte();
+                }
+            }
+
+            fn flip_byte(&mut self) {
+                if self.bits_left == 0 {
+                    self.buffer.push(self.current_byte);
+                    self.current_byte = 0;
+                    self.bits_left = 8;
+                }
+            }
+        }
+    };
+}
+
+gorilla_encoder!(FloatGorillaEncoder, f32, u32, 32, 5, 5);
+gorilla_encoder!(DoubleGorillaEncoder, f64, u64, 64, 6, 6);
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::encoding::gorilla::{DoubleGorillaEncoder, FloatGorillaEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
+
+    #[test]
+    fn test_float_encoding() {
+        let mut encoder = FloatGorillaEncoder::new();
+        encoder.write(&IoTDBValue::FLOAT(1.0)).unwrap();
+        encoder.write(&IoTDBValue::FLOAT(1.0)).unwrap();
+
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // first value, one bit for "same value", then the NaN ending with a new window
+        assert_eq!(buffer, [0x3F, 0x80, 0x00, 0x00, 0x61, 0x44, 0x04]);
+    }
+
+    #[test]
+    fn test_double_encoding() {
+        let mut encoder = DoubleGorillaEncoder::new();
+        encoder.write(&IoTDBValue::DOUBLE(1.0)).unwrap();
+
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // 1.0 as first value followed by the NaN ending
+        // xor is 0x4008000000000000 -> 1 leading zero, 51 trailing zeros, 12 significant bits
+        assert_eq!(
+            buffer,
+            [0x3F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC1, 0x2E, 0x00, 0x40]
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut encoder = FloatGorillaEncoder::new();
+        assert!(encoder.write(&IoTDBValue::DOUBLE(1.0)).is_err());
+    }
+}
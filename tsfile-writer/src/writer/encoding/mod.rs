@@ -1,17 +1,31 @@
 //! Different Encoding Algorithms for TsFiles
-use crate::writer::{IoTDBValue, TSDataType, TsFileError};
+use crate::writer::{IoTDBValue, IoTDBValues, MeasurementSchema, TSDataType, TsFileError};
 
+mod bit_packing;
+pub mod float;
+pub mod gorilla;
 pub mod plain;
 pub mod time_encoder;
+pub mod zigzag;
 
+use crate::writer::encoding::float::{FloatEncoder, DEFAULT_MAX_POINT_NUMBER};
+use crate::writer::encoding::gorilla::{DoubleGorillaEncoder, FloatGorillaEncoder};
 use crate::writer::encoding::plain::PlainEncoder;
 use crate::writer::encoding::time_encoder::{IntTs2DiffEncoder, LongTs2DiffEncoder};
-use crate::writer::TSEncoding::{PLAIN, TS2DIFF};
+use crate::writer::encoding::zigzag::{IntZigzagEncoder, LongZigzagEncoder};
+use crate::writer::TSEncoding::{GORILLA, PLAIN, TS2DIFF, ZIGZAG};
 
 #[derive(PartialEq, Copy, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum TSEncoding {
     PLAIN,
     TS2DIFF,
+    GORILLA,
+    ZIGZAG,
 }
 
 impl TryFrom<u8> for TSEncoding {
@@ -21,6 +35,8 @@ impl TryFrom<u8> for TSEncoding {
         match value {
             0 => Ok(PLAIN),
             4 => Ok(TS2DIFF),
+            8 => Ok(GORILLA),
+            9 => Ok(ZIGZAG),
             _ => Err(()),
         }
     }
@@ -31,12 +47,26 @@ impl TSEncoding {
         match self {
             TSEncoding::PLAIN => 0,
             TSEncoding::TS2DIFF => 4,
+            TSEncoding::GORILLA => 8,
+            TSEncoding::ZIGZAG => 9,
         }
     }
+
+    /// Whether values of the data type can be written with this encoding
+    pub fn supports(&self, data_type: TSDataType) -> bool {
+        <dyn Encoder>::new(data_type, *self).is_ok()
+    }
 }
 
-pub trait Encoder {
+pub trait Encoder: Send {
     fn write(&mut self, value: &IoTDBValue) -> Result<(), TsFileError>;
+    /// Writes all values, encoders override it if they can encode them faster than one by one
+    fn write_batch(&mut self, values: IoTDBValues) -> Result<(), TsFileError> {
+        for i in 0..values.len() {
+            self.write(&values.get(i))?;
+        }
+        Ok(())
+    }
     fn size(&mut self) -> u32;
     fn get_max_byte_size(&self) -> u32;
     fn serialize(&mut self, buffer: &mut Vec<u8>);
@@ -49,10 +79,35 @@ impl dyn Encoder {
         encoding: TSEncoding,
     ) -> Result<Box<dyn Encoder>, TsFileError> {
         match (data_type, encoding) {
+            // The time column of aligned series is encoded by the time chunk writer
+            (TSDataType::VECTOR, _) => Err(TsFileError::Encoding),
             (_, TSEncoding::PLAIN) => Ok(Box::new(PlainEncoder::new(data_type))),
             (TSDataType::INT64, TSEncoding::TS2DIFF) => Ok(Box::new(LongTs2DiffEncoder::new())),
             (TSDataType::INT32, TSEncoding::TS2DIFF) => Ok(Box::new(IntTs2DiffEncoder::new())),
+            (TSDataType::FLOAT | TSDataType::DOUBLE, TSEncoding::TS2DIFF) => Ok(Box::new(
+                FloatEncoder::new(data_type, DEFAULT_MAX_POINT_NUMBER)?,
+            )),
             (_, TSEncoding::TS2DIFF) => Err(TsFileError::Encoding),
+            (TSDataType::FLOAT, TSEncoding::GORILLA) => Ok(Box::new(FloatGorillaEncoder::new())),
+            (TSDataType::DOUBLE, TSEncoding::GORILLA) => Ok(Box::new(DoubleGorillaEncoder::new())),
+            (_, TSEncoding::GORILLA) => Err(TsFileError::Encoding),
+            (TSDataType::INT32, TSEncoding::ZIGZAG) => Ok(Box::new(IntZigzagEncoder::new())),
+            (TSDataType::INT64, TSEncoding::ZIGZAG) => Ok(Box::new(LongZigzagEncoder::new())),
+            (_, TSEncoding::ZIGZAG) => Err(TsFileError::Encoding),
+        }
+    }
+    /// Encoder for the values of a measurement, including the options of its schema
+    pub(crate) fn for_schema(schema: &MeasurementSchema) -> Result<Box<dyn Encoder>, TsFileError> {
+        match (schema.data_type, schema.encoding, schema.max_point_number) {
+            (
+                TSDataType::FLOAT | TSDataType::DOUBLE,
+                TSEncoding::TS2DIFF,
+                Some(max_point_number),
+            ) => Ok(Box::new(FloatEncoder::new(
+                schema.data_type,
+                max_point_number,
+            )?)),
+            _ => <dyn Encoder>::new(schema.data_type, schema.encoding),
         }
     }
 }
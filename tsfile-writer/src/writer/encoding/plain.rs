@@ -1,7 +1,7 @@
 use std::io::Write;
 
 use crate::writer::encoding::Encoder;
-use crate::writer::{utils, IoTDBValue};
+use crate::writer::{utils, IoTDBValue, IoTDBValues};
 use crate::writer::{TSDataType, TsFileError};
 
 pub struct PlainEncoder {
@@ -33,10 +33,43 @@ impl Encoder for PlainEncoder {
             IoTDBValue::LONG(v) => {
                 self.buffer.write_all(&v.to_be_bytes())?;
             }
+            IoTDBValue::TEXT(v) => {
+                utils::write_var_i32(v.len() as i32, &mut self.buffer)?;
+                self.buffer.write_all(v.as_bytes())?;
+            }
         };
         Ok(())
     }
 
+    fn write_batch(&mut self, values: IoTDBValues) -> Result<(), TsFileError> {
+        match values {
+            IoTDBValues::DOUBLE(values) => {
+                self.buffer.reserve(values.len() * 8);
+                for v in values {
+                    self.buffer.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+            IoTDBValues::FLOAT(values) => {
+                self.buffer.reserve(values.len() * 4);
+                for v in values {
+                    self.buffer.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+            IoTDBValues::INT(values) => {
+                for v in values {
+                    utils::write_var_i32(*v, &mut self.buffer)?;
+                }
+            }
+            IoTDBValues::LONG(values) => {
+                self.buffer.reserve(values.len() * 8);
+                for v in values {
+                    self.buffer.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn size(&mut self) -> u32 {
         self.buffer.len() as u32
     }
@@ -46,10 +79,13 @@ impl Encoder for PlainEncoder {
             TSDataType::INT32 => (24 + self.buffer.len()) as u32,
             TSDataType::INT64 => 0,
             TSDataType::FLOAT => 0,
+            TSDataType::DOUBLE => 0,
+            TSDataType::TEXT => 0,
+            TSDataType::VECTOR => 0,
         }
     }
     fn serialize(&mut self, buffer: &mut Vec<u8>) {
-        buffer.write(&self.buffer);
+        buffer.extend_from_slice(&self.buffer);
     }
 
     fn reset(&mut self) {
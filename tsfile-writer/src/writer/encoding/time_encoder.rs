@@ -1,7 +1,9 @@
+use crate::writer::encoding::bit_packing::{deltas, normalize, pack};
 use crate::writer::encoding::Encoder;
-use crate::writer::{IoTDBValue, TsFileError};
-use std::cmp::max;
-use std::io::Write;
+use crate::writer::{IoTDBValue, IoTDBValues, TsFileError};
+
+/// Number of deltas in a block
+const BLOCK_SIZE: usize = 128;
 
 impl TryFrom<&IoTDBValue> for i64 {
     type Error = TsFileError;
@@ -26,7 +28,7 @@ impl TryFrom<&IoTDBValue> for i32 {
 }
 
 macro_rules! ts2diff_encoder {
-    ( $name:ident, $type:ty, $num_bits:expr ) => {
+    ( $name:ident, $type:ty, $variant:ident, $num_bits:expr ) => {
         pub struct $name {
             first_value: Option<$type>,
             min_delta: $type,
@@ -44,8 +46,8 @@ macro_rules! ts2diff_encoder {
                         self.previous_value = value;
                     }
                     Some(_) => {
-                        // calc delta
-                        let delta = value - self.previous_value;
+                        // calc delta, overflows wrap around like in the java implementation
+                        let delta = value.wrapping_sub(self.previous_value);
                         // If delta is min, store it
                         if delta < self.min_delta {
                             self.min_delta = delta;
@@ -55,12 +57,40 @@ macro_rules! ts2diff_encoder {
                         self.previous_value = value;
                     }
                 }
-                if self.values.len() == 128 {
+                if self.values.len() == BLOCK_SIZE {
                     self.flush();
                 }
                 Ok(())
             }
 
+            fn write_batch(&mut self, values: IoTDBValues) -> Result<(), TsFileError> {
+                let mut values = match values {
+                    IoTDBValues::$variant(values) => values,
+                    _ => return Err(TsFileError::WrongTypeForSeries),
+                };
+                while let Some((&first, rest)) = values.split_first() {
+                    if self.first_value.is_none() {
+                        self.first_value = Some(first);
+                        self.previous_value = first;
+                        values = rest;
+                        continue;
+                    }
+                    // The deltas of the rest of the current block at once
+                    let (block, rest) =
+                        values.split_at((BLOCK_SIZE - self.values.len()).min(values.len()));
+                    let min_delta = deltas(self.previous_value, block, &mut self.values);
+                    if min_delta < self.min_delta {
+                        self.min_delta = min_delta;
+                    }
+                    self.previous_value = block[block.len() - 1];
+                    values = rest;
+                    if self.values.len() == BLOCK_SIZE {
+                        self.flush();
+                    }
+                }
+                Ok(())
+            }
+
             fn size(&mut self) -> u32 {
                 self.buffer.len() as u32
             }
@@ -73,7 +103,7 @@ macro_rules! ts2diff_encoder {
                 // Flush
                 self.flush();
                 // Copy internal buffer to out buffer
-                buffer.write_all(&self.buffer);
+                buffer.extend_from_slice(&self.buffer);
             }
 
             fn reset(&mut self) {
@@ -97,69 +127,34 @@ macro_rules! ts2diff_encoder {
                 }
             }
 
-            fn get_value_width(v: $type) -> u32 {
-                $num_bits - v.leading_zeros()
-            }
-
-            fn calculate_bit_widths_for_delta_block_buffer(
-                &mut self,
-                delta_block_buffer: &[$type],
-            ) -> u32 {
-                let mut width = 0;
-
-                for i in 0..delta_block_buffer.len() {
-                    let v = *delta_block_buffer.get(i).expect("");
-                    let value_width = Self::get_value_width(v);
-                    width = max(width, value_width)
-                }
-
-                width
-            }
-
             fn flush(&mut self) {
                 if self.first_value == None {
                     return;
                 }
-                // Preliminary calculations
-                let mut delta_block_buffer: Vec<$type> = vec![];
-
-                for delta in &self.values {
-                    delta_block_buffer.push(delta - self.min_delta);
-                }
-
-                let write_width =
-                    self.calculate_bit_widths_for_delta_block_buffer(&delta_block_buffer);
+                // Subtract the min delta, the values are reused for the next block
+                let mut deltas = std::mem::take(&mut self.values);
+                let write_width = normalize(&mut deltas, self.min_delta);
 
                 // Write Header
                 // Write number of entries
-                let number_of_entries: u32 = self.values.len() as u32;
-                self.buffer.write_all(&number_of_entries.to_be_bytes());
+                let number_of_entries: u32 = deltas.len() as u32;
+                self.buffer
+                    .extend_from_slice(&number_of_entries.to_be_bytes());
                 // Write "write-width"
-                self.buffer.write_all(&write_width.to_be_bytes());
+                self.buffer.extend_from_slice(&write_width.to_be_bytes());
 
                 // Min Delta Base
-                self.buffer.write_all(&self.min_delta.to_be_bytes());
+                self.buffer.extend_from_slice(&self.min_delta.to_be_bytes());
                 // First Value
                 self.buffer
-                    .write_all(&self.first_value.expect("").to_be_bytes());
+                    .extend_from_slice(&self.first_value.expect("").to_be_bytes());
                 // End Header
 
-                // now we can drop the long-to-bytes values here
-                let mut payload_buffer = vec![];
-                for (i, buffer) in delta_block_buffer.iter().enumerate() {
-                    Self::to_bytes(
-                        *buffer,
-                        &mut payload_buffer,
-                        (i * write_width as usize) as usize,
-                        write_width,
-                    );
-                }
-
-                // Copy over to "real" buffer
-                self.buffer.write_all(payload_buffer.as_slice());
+                pack(&deltas, write_width, &mut self.buffer);
 
                 // Now reset everything
-                self.values.clear();
+                deltas.clear();
+                self.values = deltas;
                 self.first_value = None;
                 self.previous_value = 0;
                 self.min_delta = <$type>::MAX;
@@ -168,11 +163,12 @@ macro_rules! ts2diff_encoder {
     };
 }
 
-ts2diff_encoder!(LongTs2DiffEncoder, i64, 64);
-ts2diff_encoder!(IntTs2DiffEncoder, i32, 32);
+ts2diff_encoder!(LongTs2DiffEncoder, i64, LONG, 64);
+ts2diff_encoder!(IntTs2DiffEncoder, i32, INT, 32);
 
+/// Bitwise reference of the packing in `bit_packing`
+#[cfg(test)]
 impl LongTs2DiffEncoder {
-    #[allow(unused_variables)]
     pub(crate) fn to_bytes(number: i64, result: &mut Vec<u8>, pos: usize, width: u32) {
         let mut cnt = (pos & 0x07) as u8;
         let mut index = pos >> 3;
@@ -187,7 +183,7 @@ impl LongTs2DiffEncoder {
             my_width -= m;
             let old_count = cnt;
             cnt += m;
-            let y = (number >> my_width) as u8;
+            let y = (my_number >> my_width) as u8;
             let y = y << (8 - cnt);
 
             // We need a mask like that
@@ -219,6 +215,7 @@ impl LongTs2DiffEncoder {
     }
 }
 
+#[cfg(test)]
 impl IntTs2DiffEncoder {
     pub(crate) fn to_bytes(number: i32, result: &mut Vec<u8>, pos: usize, width: u32) {
         let mut cnt = (pos & 0x07) as u8;
@@ -234,11 +231,11 @@ impl IntTs2DiffEncoder {
                 my_width
             };
             my_width -= m;
-            let mut mask = (1 << (8 - cnt)) as i32;
+            let mut mask: i32 = 1 << (8 - cnt);
             cnt += m;
             let mut y = (my_number >> my_width) as u8;
             y <<= 8 - cnt;
-            mask = !(mask - (1 << (8 - cnt)) as i32);
+            mask = !(mask - (1 << (8 - cnt)));
 
             if index <= result.len() {
                 result.resize(index + 1, 0);
@@ -257,13 +254,15 @@ impl IntTs2DiffEncoder {
 #[cfg(test)]
 mod tests {
     use crate::writer::encoding::time_encoder::{IntTs2DiffEncoder, LongTs2DiffEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
 
     #[test]
     fn test_long_to_bytes() {
         let mut result = vec![];
         let width = 4;
-        LongTs2DiffEncoder::to_bytes(1, &mut result, width * 0, width as u32);
-        LongTs2DiffEncoder::to_bytes(1, &mut result, width * 1, width as u32);
+        LongTs2DiffEncoder::to_bytes(1, &mut result, 0, width as u32);
+        LongTs2DiffEncoder::to_bytes(1, &mut result, width, width as u32);
         LongTs2DiffEncoder::to_bytes(1, &mut result, width * 2, width as u32);
 
         assert_eq!(result, [0b00010001, 0b00010000])
@@ -273,8 +272,8 @@ mod tests {
     fn test_long_to_bytes_2() {
         let mut result = vec![];
         let width = 7;
-        LongTs2DiffEncoder::to_bytes(0b0000001, &mut result, width * 0, width as u32);
-        LongTs2DiffEncoder::to_bytes(0b0000001, &mut result, width * 1, width as u32);
+        LongTs2DiffEncoder::to_bytes(0b0000001, &mut result, 0, width as u32);
+        LongTs2DiffEncoder::to_bytes(0b0000001, &mut result, width, width as u32);
         LongTs2DiffEncoder::to_bytes(0b0000001, &mut result, width * 2, width as u32);
 
         assert_eq!(result, [0b00000010, 0b00000100, 0b00001000])
@@ -284,20 +283,88 @@ mod tests {
     fn test_long_to_bytes_3() {
         let mut result = vec![];
         let width = 7;
-        LongTs2DiffEncoder::to_bytes(0, &mut result, width * 0, width as u32);
-        LongTs2DiffEncoder::to_bytes(81, &mut result, width * 1, width as u32);
+        LongTs2DiffEncoder::to_bytes(0, &mut result, 0, width as u32);
+        LongTs2DiffEncoder::to_bytes(81, &mut result, width, width as u32);
 
         assert_eq!(result, [1, 68])
     }
 
+    #[test]
+    fn test_long_block_layout() {
+        let mut encoder = LongTs2DiffEncoder::new();
+        for i in 0..=130 {
+            encoder.write(&IoTDBValue::LONG(i)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // First block: 128 deltas of width 0, min delta 1, first value 0
+        // Second block: 1 delta of width 0, min delta 1, first value 129
+        let mut expected = vec![];
+        expected.extend_from_slice(&128_u32.to_be_bytes());
+        expected.extend_from_slice(&0_u32.to_be_bytes());
+        expected.extend_from_slice(&1_i64.to_be_bytes());
+        expected.extend_from_slice(&0_i64.to_be_bytes());
+        expected.extend_from_slice(&1_u32.to_be_bytes());
+        expected.extend_from_slice(&0_u32.to_be_bytes());
+        expected.extend_from_slice(&1_i64.to_be_bytes());
+        expected.extend_from_slice(&129_i64.to_be_bytes());
+
+        assert_eq!(expected, buffer);
+    }
+
+    #[test]
+    fn test_overflowing_deltas() {
+        let mut encoder = IntTs2DiffEncoder::new();
+        for v in [i32::MIN, i32::MAX, i32::MIN, 0] {
+            encoder.write(&IoTDBValue::INT(v)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // Header of 16 bytes + 3 deltas of 32 bit
+        assert_eq!(buffer.len(), 16 + 12);
+    }
+
     #[test]
     fn test_int_to_bytes() {
         let mut result = vec![];
         let width = 4;
-        IntTs2DiffEncoder::to_bytes(1, &mut result, width * 0, width as u32);
-        IntTs2DiffEncoder::to_bytes(1, &mut result, width * 1, width as u32);
+        IntTs2DiffEncoder::to_bytes(1, &mut result, 0, width as u32);
+        IntTs2DiffEncoder::to_bytes(1, &mut result, width, width as u32);
         IntTs2DiffEncoder::to_bytes(1, &mut result, width * 2, width as u32);
 
         assert_eq!(result, [0b00010001, 0b00010000])
     }
+
+    #[test]
+    fn test_long_bytes_like_java() {
+        let values = [10, 300, 290, 5000, 4000, 70000];
+        let mut encoder = LongTs2DiffEncoder::new();
+        for v in values {
+            encoder.write(&IoTDBValue::LONG(v)).unwrap();
+        }
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // Layout of the LongDeltaEncoder of the Java TsFile: 5 deltas of 17 bit minus the
+        // minimal delta of -1000, packed big endian across byte boundaries
+        let packed = [
+            0x02, 0x85, 0x00, 0xF7, 0x82, 0xC9, 0xC0, 0x00, 0x08, 0x2D, 0xC0,
+        ];
+        let mut expected = vec![];
+        expected.extend_from_slice(&5_u32.to_be_bytes());
+        expected.extend_from_slice(&17_u32.to_be_bytes());
+        expected.extend_from_slice(&(-1000_i64).to_be_bytes());
+        expected.extend_from_slice(&10_i64.to_be_bytes());
+        expected.extend_from_slice(&packed);
+        assert_eq!(buffer, expected);
+
+        // The bit by bit packing of the tests above gives the same bytes
+        let mut result = vec![];
+        for (i, delta) in [1290, 990, 5710, 0, 67000].into_iter().enumerate() {
+            LongTs2DiffEncoder::to_bytes(delta, &mut result, i * 17, 17);
+        }
+        assert_eq!(result, packed);
+    }
 }
@@ -0,0 +1,128 @@
+//! ZIGZAG Encoding for integers whose sign changes frequently.
+//!
+//! Values are mapped to unsigned integers (0, -1, 1, -2, ... become 0, 1, 2, 3, ...) which are
+//! written as var ints, so small negative values take as few bytes as small positive ones.
+//! Like in the Java implementation a page is a single block of the byte length of the var ints,
+//! the number of values and the var ints.
+use crate::writer::encoding::Encoder;
+use crate::writer::{write_var_u32, IoTDBValue, TsFileError};
+
+macro_rules! zigzag_encoder {
+    ( $name:ident, $type:ty, $unsigned:ty, $num_bits:expr ) => {
+        pub struct $name {
+            count: u32,
+            buffer: Vec<u8>,
+        }
+
+        impl Encoder for $name {
+            fn write(&mut self, value: &IoTDBValue) -> Result<(), TsFileError> {
+                let value: $type = value.try_into()?;
+                let mut zigzag = ((value << 1) ^ (value >> ($num_bits - 1))) as $unsigned;
+                while zigzag & !0x7F != 0 {
+                    self.buffer.push((zigzag & 0x7F) as u8 | 0x80);
+                    zigzag >>= 7;
+                }
+                self.buffer.push(zigzag as u8);
+                self.count += 1;
+                Ok(())
+            }
+
+            fn size(&mut self) -> u32 {
+                self.buffer.len() as u32
+            }
+
+            fn get_max_byte_size(&self) -> u32 {
+                // Byte length and number of values
+                10
+            }
+
+            fn serialize(&mut self, buffer: &mut Vec<u8>) {
+                if self.count == 0 {
+                    return;
+                }
+                // Writing to a Vec does not fail
+                let _ = write_var_u32(self.buffer.len() as u32, buffer);
+                let _ = write_var_u32(self.count, buffer);
+                buffer.extend_from_slice(&self.buffer);
+                self.reset();
+            }
+
+            fn reset(&mut self) {
+                self.count = 0;
+                self.buffer.clear();
+            }
+        }
+
+        impl $name {
+            pub(crate) fn new() -> $name {
+                Self {
+                    count: 0,
+                    buffer: vec![],
+                }
+            }
+        }
+    };
+}
+
+zigzag_encoder!(IntZigzagEncoder, i32, u32, 32);
+zigzag_encoder!(LongZigzagEncoder, i64, u64, 64);
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::encoding::zigzag::{IntZigzagEncoder, LongZigzagEncoder};
+    use crate::writer::encoding::Encoder;
+    use crate::writer::IoTDBValue;
+
+    #[test]
+    fn test_int_encoding() {
+        let mut encoder = IntZigzagEncoder::new();
+        for value in [0, -1, 1, -2, 64, i32::MIN] {
+            encoder.write(&IoTDBValue::INT(value)).unwrap();
+        }
+
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // Output of the IntZigzagEncoder of IoTDB for the same values:
+        // 11 bytes of 6 var ints, 64 -> 128 needs two bytes, i32::MIN -> u32::MAX five
+        assert_eq!(
+            buffer,
+            [0x0B, 0x06, 0x00, 0x01, 0x02, 0x03, 0x80, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F]
+        );
+    }
+
+    #[test]
+    fn test_long_encoding() {
+        let mut encoder = LongZigzagEncoder::new();
+        for value in [-3, 300, i64::MAX] {
+            encoder.write(&IoTDBValue::LONG(value)).unwrap();
+        }
+
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+
+        // Output of the LongZigzagEncoder of IoTDB for the same values:
+        // -3 -> 5, 300 -> 600 and i64::MAX -> u64::MAX - 1 in ten bytes
+        assert_eq!(
+            buffer,
+            [
+                0x0D, 0x03, 0x05, 0xD8, 0x04, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_page() {
+        let mut encoder = IntZigzagEncoder::new();
+        let mut buffer = vec![];
+        encoder.serialize(&mut buffer);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut encoder = IntZigzagEncoder::new();
+        assert!(encoder.write(&IoTDBValue::LONG(1)).is_err());
+    }
+}
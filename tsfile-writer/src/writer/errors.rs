@@ -1,16 +1,35 @@
 //! Default Errors for the TsFile crate
+use std::fmt::{Display, Formatter};
 use std::io::Error;
 
 #[derive(Debug)]
 pub enum TsFileError {
-    Error { source: Option<String> }, // Generic Error
-    IoError { source: std::io::Error },
+    Error {
+        source: Option<String>,
+    }, // Generic Error
+    IoError {
+        source: std::io::Error,
+    },
     WriteError,
     OutOfOrderData,
-    IllegalState { source: Option<String> },
+    IllegalState {
+        source: Option<String>,
+    },
     Compression,
     WrongTypeForSeries,
     Encoding,
+    /// The data does not match the schema, e.g. unknown devices or measurements
+    SchemaError {
+        source: Option<String>,
+    },
+    /// Parts of the TsFile format that are not (yet) supported by this crate
+    UnsupportedFeature {
+        source: Option<String>,
+    },
+    /// The file violates the TsFile format, e.g. lengths or offsets that point outside of it
+    Corrupt {
+        source: Option<String>,
+    },
 }
 
 impl PartialEq for TsFileError {
@@ -30,6 +49,51 @@ impl PartialEq for TsFileError {
             TsFileError::Compression => matches!(other, TsFileError::Compression),
             TsFileError::WrongTypeForSeries => matches!(other, TsFileError::WrongTypeForSeries),
             TsFileError::Encoding => matches!(other, TsFileError::Encoding),
+            TsFileError::SchemaError { source: a } => match other {
+                TsFileError::SchemaError { source: b } => a == b,
+                _ => false,
+            },
+            TsFileError::UnsupportedFeature { source: a } => match other {
+                TsFileError::UnsupportedFeature { source: b } => a == b,
+                _ => false,
+            },
+            TsFileError::Corrupt { source: a } => match other {
+                TsFileError::Corrupt { source: b } => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Display for TsFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (message, source) = match self {
+            TsFileError::Error { source } => ("Error", source.as_deref()),
+            TsFileError::IoError { source } => return write!(f, "IO error: {}", source),
+            TsFileError::WriteError => ("Write error", None),
+            TsFileError::OutOfOrderData => ("Timestamp is not larger than the last one", None),
+            TsFileError::IllegalState { source } => ("Illegal state", source.as_deref()),
+            TsFileError::Compression => ("Compression failed", None),
+            TsFileError::WrongTypeForSeries => ("Value has the wrong type for the series", None),
+            TsFileError::Encoding => ("Encoding failed", None),
+            TsFileError::SchemaError { source } => ("Schema error", source.as_deref()),
+            TsFileError::UnsupportedFeature { source } => {
+                ("Unsupported feature", source.as_deref())
+            }
+            TsFileError::Corrupt { source } => ("Corrupt TsFile", source.as_deref()),
+        };
+        match source {
+            Some(source) => write!(f, "{}: {}", message, source),
+            None => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TsFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TsFileError::IoError { source } => Some(source),
+            _ => None,
         }
     }
 }
@@ -39,3 +103,29 @@ impl From<std::io::Error> for TsFileError {
         TsFileError::IoError { source: e }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::writer::errors::TsFileError;
+    use std::error::Error;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            TsFileError::SchemaError {
+                source: Some("Unknown device d1".to_owned())
+            }
+            .to_string(),
+            "Schema error: Unknown device d1"
+        );
+        assert_eq!(
+            TsFileError::WrongTypeForSeries.to_string(),
+            "Value has the wrong type for the series"
+        );
+
+        let error: TsFileError =
+            std::io::Error::new(std::io::ErrorKind::WriteZero, "disk full").into();
+        assert_eq!(error.to_string(), "IO error: disk full");
+        assert!(error.source().is_some());
+    }
+}
@@ -1,22 +1,104 @@
 use crate::writer::chunk_writer::ChunkWriter;
 use crate::writer::errors::TsFileError;
+use crate::writer::metrics::WriterMetrics;
+use crate::writer::tablet::Tablet;
+use crate::writer::ts_file_config::{DuplicatePolicy, TsFileConfig};
 use crate::writer::tsfile_io_writer::TsFileIoWriter;
 use crate::writer::tsfile_writer::DataPoint;
-use crate::writer::{IoTDBValue, PositionedWrite};
+use crate::writer::vector_chunk_writer::VectorChunkWriter;
+use crate::writer::{
+    IoTDBValue, IoTDBValues, MeasurementGroup, MeasurementSchema, PositionedWrite, TSEncoding,
+};
 use std::collections::BTreeMap;
 
 pub struct GroupWriter<'a> {
     pub(crate) path: &'a str,
     pub(crate) chunk_writers: BTreeMap<&'a str, ChunkWriter>,
     pub(crate) last_time_map: BTreeMap<&'a str, i64>,
+    /// Only set for aligned devices, then `chunk_writers` is empty
+    aligned_chunk_writer: Option<VectorChunkWriter>,
+    last_aligned_time: Option<i64>,
+    duplicate_policy: DuplicatePolicy,
+    /// Whether points were written since the last flush. Only dirty groups are sealed and
+    /// flushed, so devices that receive data rarely cost nothing on a flush.
+    dirty: bool,
 }
 
 impl<'a> GroupWriter<'a> {
+    pub(crate) fn new(
+        path: &'a str,
+        group: &MeasurementGroup<'a>,
+        config: &TsFileConfig,
+    ) -> Result<GroupWriter<'a>, TsFileError> {
+        let (chunk_writers, aligned_chunk_writer) = if group.is_aligned() {
+            (
+                BTreeMap::new(),
+                Some(VectorChunkWriter::new(
+                    group
+                        .measurement_schemas
+                        .iter()
+                        .map(|(&measurement_id, schema)| (measurement_id, schema)),
+                    config,
+                )?),
+            )
+        } else {
+            // Only the time chunks of aligned devices record their encoding, readers of
+            // other series expect TS2DIFF
+            if config.time_encoding != TSEncoding::TS2DIFF {
+                return Err(TsFileError::UnsupportedFeature {
+                    source: Some(format!(
+                        "Time encoding {:?} for the non aligned device {}",
+                        config.time_encoding, path
+                    )),
+                });
+            }
+            (
+                group
+                    .measurement_schemas
+                    .iter()
+                    .map(|(&measurement_id, measurement_schema)| {
+                        (
+                            measurement_id,
+                            ChunkWriter::from_schema(measurement_id, measurement_schema, config),
+                        )
+                    })
+                    .collect(),
+                None,
+            )
+        };
+        Ok(GroupWriter {
+            path,
+            chunk_writers,
+            last_time_map: BTreeMap::new(),
+            aligned_chunk_writer,
+            last_aligned_time: None,
+            duplicate_policy: config.duplicate_policy,
+            dirty: false,
+        })
+    }
+
+    /// Adds chunk writers for new measurements of a non aligned device
+    pub(crate) fn add_measurements(
+        &mut self,
+        measurements: &BTreeMap<&'a str, MeasurementSchema>,
+        config: &TsFileConfig,
+    ) {
+        for (&measurement_id, schema) in measurements {
+            self.chunk_writers.insert(
+                measurement_id,
+                ChunkWriter::from_schema(measurement_id, schema, config),
+            );
+        }
+    }
+
     pub(crate) fn write_many(
         &mut self,
         timestamp: i64,
         values: impl IntoIterator<Item = DataPoint<'a>>,
     ) -> Result<u32, TsFileError> {
+        if self.aligned_chunk_writer.is_some() {
+            return self.write_aligned(timestamp, values);
+        }
         let mut records = 0;
         for dp in values {
             records += self.write(dp.measurement_id, timestamp, dp.value)?;
@@ -26,6 +108,30 @@ impl<'a> GroupWriter<'a> {
 }
 
 impl<'a> GroupWriter<'a> {
+    /// Continues after a point that is already in the file, later points have to be newer
+    pub(crate) fn restore_last_time(&mut self, measurement_id: &str, timestamp: i64) {
+        if self.aligned_chunk_writer.is_some() {
+            let last_time = self.last_aligned_time.get_or_insert(timestamp);
+            *last_time = (*last_time).max(timestamp);
+        } else if let Some((&measurement_id, _)) = self.chunk_writers.get_key_value(measurement_id)
+        {
+            let last_time = self
+                .last_time_map
+                .entry(measurement_id)
+                .or_insert(timestamp);
+            *last_time = (*last_time).max(timestamp);
+        }
+    }
+
+    /// Timestamp of the last point of the series, of the last row for aligned devices
+    pub(crate) fn last_time(&self, measurement_id: &str) -> Option<i64> {
+        if self.aligned_chunk_writer.is_some() {
+            self.last_aligned_time
+        } else {
+            self.last_time_map.get(measurement_id).copied()
+        }
+    }
+
     pub(crate) fn get_last_time_map(&mut self) -> BTreeMap<&'a str, i64> {
         self.last_time_map.clone()
     }
@@ -35,18 +141,43 @@ impl<'a> GroupWriter<'a> {
     pub(crate) fn flush_to_filewriter<T: PositionedWrite>(
         &mut self,
         file_writer: &mut TsFileIoWriter<T>,
-    ) -> u64 {
+    ) -> Result<u64, TsFileError> {
         log::info!("Start flush device id: {}", &self.path);
 
-        self.seal_all_chunks();
+        self.seal_all_chunks()?;
 
         let current_chunk_group_size = self.get_current_chunk_group_size();
 
+        if let Some(aligned_chunk_writer) = self.aligned_chunk_writer.as_mut() {
+            aligned_chunk_writer.write_to_file_writer(file_writer)?;
+        }
         for (_, series_writer) in self.chunk_writers.iter_mut() {
-            series_writer.write_to_file_writer(file_writer);
+            series_writer.write_to_file_writer(file_writer)?;
         }
+        self.dirty = false;
+
+        Ok(current_chunk_group_size)
+    }
 
-        current_chunk_group_size
+    /// Whether any chunk contains data that was not flushed yet, the chunks are only checked
+    /// if the group is dirty
+    pub(crate) fn has_data(&self) -> bool {
+        self.dirty
+            && (self.chunk_writers.values().any(|writer| writer.has_data())
+                || match &self.aligned_chunk_writer {
+                    Some(aligned_chunk_writer) => aligned_chunk_writer.has_data(),
+                    None => false,
+                })
+    }
+
+    /// Adds the points that are not flushed yet and the sizes of the sealed pages
+    pub(crate) fn add_metrics(&self, metrics: &mut WriterMetrics) {
+        for chunk_writer in self.chunk_writers.values() {
+            chunk_writer.add_metrics(self.path, metrics);
+        }
+        if let Some(aligned_chunk_writer) = &self.aligned_chunk_writer {
+            aligned_chunk_writer.add_metrics(self.path, metrics);
+        }
     }
 
     pub(crate) fn update_max_group_mem_size(&mut self) -> u32 {
@@ -60,12 +191,19 @@ impl<'a> GroupWriter<'a> {
             );
             buffer_size += chunk_writer_size;
         }
+        if let Some(aligned_chunk_writer) = self.aligned_chunk_writer.as_mut() {
+            buffer_size += aligned_chunk_writer.estimate_max_series_mem_size();
+        }
         buffer_size
     }
-    fn seal_all_chunks(&mut self) {
+    fn seal_all_chunks(&mut self) -> Result<(), TsFileError> {
         for (_, writer) in self.chunk_writers.iter_mut() {
-            writer.seal_current_page();
+            writer.seal_current_page()?;
+        }
+        if let Some(aligned_chunk_writer) = self.aligned_chunk_writer.as_mut() {
+            aligned_chunk_writer.seal_current_page()?;
         }
+        Ok(())
     }
     fn get_current_chunk_group_size(&mut self) -> u64 {
         // long size = 0;
@@ -77,10 +215,80 @@ impl<'a> GroupWriter<'a> {
         for (_, writer) in self.chunk_writers.iter_mut() {
             size += writer.get_serialized_chunk_size();
         }
+        if let Some(aligned_chunk_writer) = &self.aligned_chunk_writer {
+            size += aligned_chunk_writer.get_serialized_chunk_size();
+        }
         size
     }
 }
 
+/// Chunk writer whose current page is sealed on another thread
+#[cfg(feature = "parallel")]
+enum Sealable<'b> {
+    Chunk(&'b mut ChunkWriter),
+    Aligned(&'b mut VectorChunkWriter),
+}
+
+#[cfg(feature = "parallel")]
+impl<'b> Sealable<'b> {
+    fn seal(&mut self) -> Result<(), TsFileError> {
+        match self {
+            Sealable::Chunk(writer) => writer.seal_current_page(),
+            Sealable::Aligned(writer) => writer.seal_current_page(),
+        }
+    }
+}
+
+/// Seals the current pages of all series of the groups before they are flushed, the pages are
+/// encoded and compressed on up to `threads` threads (0 for one per CPU core). Writing the
+/// sealed chunks to the file stays sequential.
+#[cfg(feature = "parallel")]
+pub(crate) fn seal_in_parallel<'a: 'b, 'b>(
+    groups: impl IntoIterator<Item = &'b mut GroupWriter<'a>>,
+    threads: usize,
+) -> Result<(), TsFileError> {
+    let mut writers = vec![];
+    for group in groups.into_iter().filter(|group| group.dirty) {
+        writers.extend(
+            group
+                .chunk_writers
+                .values_mut()
+                .filter(|writer| writer.has_data())
+                .map(Sealable::Chunk),
+        );
+        if let Some(aligned_chunk_writer) = group.aligned_chunk_writer.as_mut() {
+            if aligned_chunk_writer.has_data() {
+                writers.push(Sealable::Aligned(aligned_chunk_writer));
+            }
+        }
+    }
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    }
+    .min(writers.len());
+    if threads <= 1 {
+        return writers.iter_mut().try_for_each(Sealable::seal);
+    }
+    let mut parts: Vec<Vec<Sealable>> = (0..threads).map(|_| vec![]).collect();
+    for (i, writer) in writers.into_iter().enumerate() {
+        parts[i % threads].push(writer);
+    }
+    let panicked = || TsFileError::IllegalState {
+        source: Some("A thread panicked while encoding".to_owned()),
+    };
+    crossbeam_utils::thread::scope(|scope| {
+        let handles: Vec<_> = parts
+            .into_iter()
+            .map(|mut part| scope.spawn(move |_| part.iter_mut().try_for_each(Sealable::seal)))
+            .collect();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().unwrap_or_else(|_| Err(panicked())))
+    })
+    .unwrap_or_else(|_| Err(panicked()))
+}
+
 impl<'a> GroupWriter<'a> {
     pub(crate) fn write(
         &mut self,
@@ -88,32 +296,215 @@ impl<'a> GroupWriter<'a> {
         timestamp: i64,
         value: IoTDBValue,
     ) -> Result<u32, TsFileError> {
+        if self.aligned_chunk_writer.is_some() {
+            return self.write_aligned(timestamp, vec![DataPoint::new(measurement_id, value)]);
+        }
         // Check is historic
-        self.check_is_history_data(measurement_id, timestamp)?;
+        if self.check_is_history_data(measurement_id, timestamp)? {
+            return Ok(0);
+        }
 
         let record_count = match &mut self.chunk_writers.get_mut(measurement_id) {
-            Some(chunk_writer) => chunk_writer.write(timestamp, value).unwrap(),
+            Some(chunk_writer) => chunk_writer.write(timestamp, value)?,
             None => {
-                return Err(TsFileError::IllegalState {
+                return Err(TsFileError::SchemaError {
                     source: Some("Unknown measurement id".to_owned()),
                 });
             }
         };
+        self.dirty = true;
         self.last_time_map.insert(measurement_id, timestamp);
         Ok(record_count)
     }
 
+    /// Whether `write_batch` can encode the points at once, i.e. the series is not aligned and
+    /// the timestamps are strictly increasing and newer than the last point of the series
+    pub(crate) fn can_write_batch(&self, measurement_id: &str, timestamps: &[i64]) -> bool {
+        let newer = match (timestamps.first(), self.last_time_map.get(measurement_id)) {
+            (Some(&first_time), Some(&last_time)) => first_time > last_time,
+            _ => true,
+        };
+        self.aligned_chunk_writer.is_none()
+            && newer
+            && timestamps.windows(2).all(|pair| pair[0] < pair[1])
+    }
+
+    /// Writes the points of a single series, the caller checks `can_write_batch` before
+    pub(crate) fn write_batch(
+        &mut self,
+        measurement_id: &'a str,
+        timestamps: &[i64],
+        values: IoTDBValues,
+    ) -> Result<u32, TsFileError> {
+        let record_count = match self.chunk_writers.get_mut(measurement_id) {
+            Some(chunk_writer) => chunk_writer.write_batch(timestamps, values)?,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("Unknown measurement id".to_owned()),
+                });
+            }
+        };
+        self.dirty = true;
+        if let Some(&last_time) = timestamps.last() {
+            self.last_time_map.insert(measurement_id, last_time);
+        }
+        Ok(record_count)
+    }
+
+    /// Returns `true` if the point is a duplicate that is discarded
     fn check_is_history_data(
         &mut self,
         measurement_id: &'a str,
         timestamp: i64,
-    ) -> Result<(), TsFileError> {
-        if !self.last_time_map.contains_key(measurement_id) {
-            self.last_time_map.insert(measurement_id, -1);
+    ) -> Result<bool, TsFileError> {
+        match self.last_time_map.get(measurement_id) {
+            Some(&last_time) => self.check_timestamp(timestamp, last_time),
+            None => Ok(false),
+        }
+    }
+
+    /// Already encoded points can not be overwritten, so only `KeepFirst` accepts duplicates
+    fn check_timestamp(&self, timestamp: i64, last_time: i64) -> Result<bool, TsFileError> {
+        if timestamp == last_time && self.duplicate_policy == DuplicatePolicy::KeepFirst {
+            return Ok(true);
         }
-        if timestamp <= *self.last_time_map.get(measurement_id).unwrap() {
+        if timestamp <= last_time {
             return Err(TsFileError::OutOfOrderData);
         }
+        Ok(false)
+    }
+}
+
+impl<'a> GroupWriter<'a> {
+    /// Writes a row for an aligned device, all measurements share the time column, thus
+    /// timestamps have to increase for the whole device
+    fn write_aligned(
+        &mut self,
+        timestamp: i64,
+        values: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<u32, TsFileError> {
+        if let Some(last_time) = self.last_aligned_time {
+            if self.check_timestamp(timestamp, last_time)? {
+                return Ok(0);
+            }
+        }
+        let record_count = match self.aligned_chunk_writer.as_mut() {
+            Some(aligned_chunk_writer) => aligned_chunk_writer.write(timestamp, values)?,
+            None => {
+                return Err(TsFileError::IllegalState {
+                    source: Some("Device is not aligned".to_owned()),
+                });
+            }
+        };
+        self.dirty = true;
+        self.last_aligned_time = Some(timestamp);
+        Ok(record_count)
+    }
+}
+
+impl<'a> GroupWriter<'a> {
+    /// Writes all columns of the tablet, the whole tablet is validated first so that
+    /// nothing is written for an invalid tablet
+    pub(crate) fn write_tablet(&mut self, tablet: Tablet) -> Result<u32, TsFileError> {
+        tablet.check_timestamps()?;
+        let (first_time, last_time) = match (tablet.timestamps.first(), tablet.timestamps.last()) {
+            (Some(&first_time), Some(&last_time)) => (first_time, last_time),
+            _ => return Ok(0),
+        };
+
+        if let Some(aligned_chunk_writer) = self.aligned_chunk_writer.as_mut() {
+            if let Some(last_aligned_time) = self.last_aligned_time {
+                if first_time <= last_aligned_time {
+                    return Err(TsFileError::OutOfOrderData);
+                }
+            }
+            self.dirty = true;
+            let record_count = aligned_chunk_writer.write_tablet(&tablet)?;
+            self.last_aligned_time = Some(last_time);
+            return Ok(record_count);
+        }
+
+        for column in &tablet.columns {
+            let chunk_writer = match self.chunk_writers.get(column.measurement_id) {
+                Some(chunk_writer) => chunk_writer,
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some("Unknown measurement id".to_owned()),
+                    });
+                }
+            };
+            column.check_type(chunk_writer.data_type)?;
+            if let Some(&last_time) = self.last_time_map.get(column.measurement_id) {
+                if first_time <= last_time {
+                    return Err(TsFileError::OutOfOrderData);
+                }
+            }
+        }
+
+        self.dirty = true;
+        let mut record_count = 0;
+        let timestamps = tablet.timestamps;
+        for column in tablet.columns {
+            // Already validated above
+            if let Some((&measurement_id, chunk_writer)) = self
+                .chunk_writers
+                .iter_mut()
+                .find(|(&measurement_id, _)| measurement_id == column.measurement_id)
+            {
+                let mut last_row = None;
+                for (row, value) in column.into_values() {
+                    record_count += chunk_writer.write(timestamps[row], value)?;
+                    last_row = Some(row);
+                }
+                if let Some(row) = last_row {
+                    self.last_time_map.insert(measurement_id, timestamps[row]);
+                }
+            }
+        }
+        Ok(record_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::group_writer::GroupWriter;
+    use crate::writer::schema::DeviceBuilder;
+    use crate::writer::ts_file_config::{DuplicatePolicy, TsFileConfig};
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn rejected_points_leave_group_clean() -> Result<(), TsFileError> {
+        let config = TsFileConfig::default().duplicate_policy(DuplicatePolicy::KeepFirst);
+        for mut device_builder in [DeviceBuilder::new(), DeviceBuilder::aligned()] {
+            let group = device_builder
+                .add(
+                    "s1",
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build();
+            let mut group_writer = GroupWriter::new("d1", &group, &config)?;
+            group_writer.restore_last_time("s1", 2);
+
+            // Duplicates are discarded and older points are rejected
+            assert_eq!(group_writer.write("s1", 2, IoTDBValue::LONG(2))?, 0);
+            assert!(matches!(
+                group_writer.write("s1", 1, IoTDBValue::LONG(1)),
+                Err(TsFileError::OutOfOrderData)
+            ));
+            assert!(matches!(
+                group_writer.write("s1", 3, IoTDBValue::INT(3)),
+                Err(TsFileError::WrongTypeForSeries)
+            ));
+            assert!(!group_writer.dirty);
+
+            group_writer.write("s1", 3, IoTDBValue::LONG(3))?;
+            assert!(group_writer.dirty);
+        }
         Ok(())
     }
 }
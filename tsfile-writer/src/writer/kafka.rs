@@ -0,0 +1,772 @@
+//! Bridge from a Kafka topic to TsFiles (feature `kafka`).
+//!
+//! Every message of the topic is one point as JSON,
+//! `{"device": "root.sg.d1", "measurement": "s1", "timestamp": 1, "value": 13}`, its value is
+//! converted to the data type of the series in the schema. Every partition of the topic is
+//! written to its own directory `partition-{partition}` by a `RollingTsFileWriter`. The offsets
+//! of a partition are only committed once all messages up to them are flushed to a file, so
+//! after a crash the consumer restarts behind the data in the files (at least once).
+//!
+//! With the feature `avro` the messages can be Avro records of `AVRO_SCHEMA` instead, see
+//! `RecordFormat`. Only the binary encoding of this schema is decoded, there is no schema
+//! resolution for records that were written with another schema.
+//!
+//! The crate does not depend on a Kafka client, the consumer of the client is wrapped in a
+//! `Consumer`, e.g. for a `BaseConsumer` of `rdkafka`:
+//!
+//! ```ignore
+//! impl Consumer for TopicConsumer {
+//!     fn poll(&mut self) -> Result<Option<Message>, TsFileError> {
+//!         match self.consumer.poll(Duration::from_millis(100)) {
+//!             Some(Ok(message)) => Ok(Some(Message {
+//!                 partition: message.partition(),
+//!                 offset: message.offset(),
+//!                 payload: message.payload().unwrap_or_default().to_vec(),
+//!             })),
+//!             Some(Err(e)) => Err(TsFileError::Error { source: Some(e.to_string()) }),
+//!             None => Ok(None),
+//!         }
+//!     }
+//!
+//!     fn commit(&mut self, partition: i32, offset: i64) -> Result<(), TsFileError> {
+//!         let mut offsets = TopicPartitionList::new();
+//!         offsets.add_partition_offset(&self.topic, partition, Offset::Offset(offset)).unwrap();
+//!         self.consumer
+//!             .commit(&offsets, CommitMode::Sync)
+//!             .map_err(|e| TsFileError::Error { source: Some(e.to_string()) })
+//!     }
+//! }
+//! ```
+use crate::writer::errors::TsFileError;
+use crate::writer::listener::FlushListener;
+use crate::writer::rolling_writer::RollingTsFileWriter;
+use crate::writer::schema::schema_error;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::reorder_window;
+use crate::writer::{IoTDBValue, Schema, TSDataType};
+use serde_crate::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Message of a partition of the topic
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub partition: i32,
+    pub offset: i64,
+    pub payload: Vec<u8>,
+}
+
+/// Consumer of the topic, implemented for the consumer of a Kafka client
+pub trait Consumer {
+    /// The next message, `None` if there is no message at the moment
+    fn poll(&mut self) -> Result<Option<Message>, TsFileError>;
+
+    /// Commits the offset of the partition, i.e. the offset of the next message to consume
+    /// like in Kafka
+    fn commit(&mut self, partition: i32, offset: i64) -> Result<(), TsFileError>;
+}
+
+/// Schema of the Avro records, the value is converted to the data type of the series like the
+/// value of a JSON record
+#[cfg(feature = "avro")]
+pub const AVRO_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "Point",
+  "namespace": "org.apache.iotdb.tsfile",
+  "fields": [
+    {"name": "device", "type": "string"},
+    {"name": "measurement", "type": "string"},
+    {"name": "timestamp", "type": "long"},
+    {"name": "value", "type": ["int", "long", "float", "double", "string"]}
+  ]
+}"#;
+
+/// Format of the messages of the topic
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// JSON object, see the module documentation. This is the default.
+    Json,
+    /// Avro binary encoding of a record of `AVRO_SCHEMA`
+    #[cfg(feature = "avro")]
+    Avro,
+    /// Avro record with the header of the Confluent schema registry, a zero byte and the
+    /// 4 byte id of the schema. The id is not checked.
+    #[cfg(feature = "avro")]
+    ConfluentAvro,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "serde_crate", deny_unknown_fields)]
+struct JsonRecord {
+    device: String,
+    measurement: String,
+    timestamp: i64,
+    value: serde_json::Value,
+}
+
+/// Point of a message, its value is not converted yet
+struct Record {
+    device: String,
+    measurement: String,
+    timestamp: i64,
+    value: RecordValue,
+}
+
+enum RecordValue {
+    Json(serde_json::Value),
+    #[cfg(feature = "avro")]
+    Avro(AvroValue),
+}
+
+/// Branch of the value union of `AVRO_SCHEMA`
+#[cfg(feature = "avro")]
+#[derive(Debug)]
+enum AvroValue {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+}
+
+impl RecordValue {
+    fn convert(&self, data_type: TSDataType) -> Option<IoTDBValue> {
+        match self {
+            RecordValue::Json(value) => convert(value, data_type),
+            #[cfg(feature = "avro")]
+            RecordValue::Avro(value) => convert_avro(value, data_type),
+        }
+    }
+}
+
+impl std::fmt::Display for RecordValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordValue::Json(value) => write!(f, "{}", value),
+            #[cfg(feature = "avro")]
+            RecordValue::Avro(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// Offsets of a partition, shared with the flush listener of its writer
+#[derive(Default)]
+struct Offsets {
+    /// Last offset that was written to the writer
+    written: Option<i64>,
+    /// Last offset that was flushed to a file
+    flushed: Option<i64>,
+    committed: Option<i64>,
+}
+
+#[derive(Clone)]
+struct CommitOnFlush(Arc<Mutex<Offsets>>);
+
+impl CommitOnFlush {
+    fn flushed(&mut self) {
+        if let Ok(mut offsets) = self.0.lock() {
+            offsets.flushed = offsets.written;
+        }
+    }
+}
+
+impl FlushListener for CommitOnFlush {
+    fn after_flush(&mut self, _position: u64) {
+        self.flushed();
+    }
+
+    fn after_close(&mut self, _size: u64) {
+        self.flushed();
+    }
+}
+
+struct Partition<'a> {
+    writer: RollingTsFileWriter<'a>,
+    offsets: Arc<Mutex<Offsets>>,
+}
+
+/// Consumes a topic and writes its messages to TsFiles, see the module documentation
+pub struct KafkaBridge<'a, C: Consumer> {
+    consumer: C,
+    directory: PathBuf,
+    schema: Schema<'a>,
+    config: TsFileConfig,
+    format: RecordFormat,
+    max_points: Option<u64>,
+    time_partition_interval: Option<i64>,
+    partitions: BTreeMap<i32, Partition<'a>>,
+}
+
+impl<'a, C: Consumer> KafkaBridge<'a, C> {
+    /// Fails if the config has a reorder window (or overwrites duplicates), buffered rows would
+    /// not be contained in the flushes
+    pub fn new(
+        consumer: C,
+        directory: impl Into<PathBuf>,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<KafkaBridge<'a, C>, TsFileError> {
+        config.validate()?;
+        if reorder_window(&config).is_some() {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some("Offsets cannot be committed with a reorder window".to_owned()),
+            });
+        }
+        Ok(KafkaBridge {
+            consumer,
+            directory: directory.into(),
+            schema,
+            config,
+            format: RecordFormat::Json,
+            max_points: None,
+            time_partition_interval: None,
+            partitions: BTreeMap::new(),
+        })
+    }
+
+    /// Format of the messages, JSON by default
+    pub fn format(&mut self, format: RecordFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// See `RollingTsFileWriter::max_points`
+    pub fn max_points(&mut self, points: u64) -> &mut Self {
+        self.max_points = Some(points);
+        self
+    }
+
+    /// See `RollingTsFileWriter::time_partition_interval`, files are aligned to the time
+    /// partitions of IoTDB
+    pub fn time_partition_interval(&mut self, interval: i64) -> &mut Self {
+        self.time_partition_interval = Some(interval);
+        self
+    }
+
+    /// Writes the next message and commits the offsets that were flushed since the last call.
+    /// Returns `false` if there was no message. A message that cannot be written fails, it is
+    /// skipped and its offset is committed with the next flush.
+    pub fn poll(&mut self) -> Result<bool, TsFileError> {
+        let message = match self.consumer.poll()? {
+            Some(message) => message,
+            None => return Ok(false),
+        };
+        let result = self.write(&message);
+        // Messages that cannot be written are skipped, their offsets are committed as well
+        if let Ok(mut offsets) = self.partition(message.partition).offsets.lock() {
+            offsets.written = Some(message.offset);
+        }
+        self.commit()?;
+        result.map(|_| true)
+    }
+
+    /// Polls until there is no message
+    pub fn poll_all(&mut self) -> Result<(), TsFileError> {
+        while self.poll()? {}
+        Ok(())
+    }
+
+    /// Closes the files of all partitions, commits all offsets and returns the written files
+    pub fn close(&mut self) -> Result<Vec<PathBuf>, TsFileError> {
+        let mut files = vec![];
+        for partition in self.partitions.values_mut() {
+            files.append(&mut partition.writer.close()?);
+        }
+        self.commit()?;
+        Ok(files)
+    }
+
+    fn write(&mut self, message: &Message) -> Result<(), TsFileError> {
+        let record =
+            read_record(&message.payload, self.format).map_err(|e| TsFileError::Error {
+                source: Some(format!(
+                    "Invalid record at offset {} of partition {}: {}",
+                    message.offset, message.partition, e
+                )),
+            })?;
+        let (device, group) = match self
+            .schema
+            .measurement_groups
+            .get_key_value(&*record.device)
+        {
+            Some((&device, group)) => (device, group),
+            None => return Err(schema_error(format!("Unknown device '{}'", record.device))),
+        };
+        let (measurement, data_type) = match group
+            .measurement_schemas
+            .get_key_value(&*record.measurement)
+        {
+            Some((&measurement, schema)) => (measurement, schema.data_type),
+            None => {
+                return Err(schema_error(format!(
+                    "Unknown measurement '{}' of device '{}'",
+                    record.measurement, device
+                )))
+            }
+        };
+        let value = match record.value.convert(data_type) {
+            Some(value) => value,
+            None => {
+                return Err(schema_error(format!(
+                    "Value {} of '{}.{}' is no {:?}",
+                    record.value, device, measurement, data_type
+                )))
+            }
+        };
+
+        self.partition(message.partition)
+            .writer
+            .write(device, measurement, record.timestamp, value)
+    }
+
+    fn partition(&mut self, partition: i32) -> &mut Partition<'a> {
+        let KafkaBridge {
+            directory,
+            schema,
+            config,
+            max_points,
+            time_partition_interval,
+            partitions,
+            ..
+        } = self;
+        partitions.entry(partition).or_insert_with(|| {
+            let mut writer = RollingTsFileWriter::new(
+                directory.join(format!("partition-{}", partition)),
+                schema.clone(),
+                *config,
+            );
+            if let Some(points) = *max_points {
+                writer.max_points(points);
+            }
+            if let Some(interval) = *time_partition_interval {
+                writer.time_partition_interval(interval);
+            }
+            let offsets = Arc::new(Mutex::new(Offsets::default()));
+            writer.set_flush_listener(CommitOnFlush(offsets.clone()));
+            Partition { writer, offsets }
+        })
+    }
+
+    fn commit(&mut self) -> Result<(), TsFileError> {
+        for (&partition, state) in &self.partitions {
+            let mut offsets = match state.offsets.lock() {
+                Ok(offsets) => offsets,
+                Err(_) => continue,
+            };
+            if offsets.flushed > offsets.committed {
+                if let Some(flushed) = offsets.flushed {
+                    self.consumer.commit(partition, flushed + 1)?;
+                }
+                offsets.committed = offsets.flushed;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_record(payload: &[u8], format: RecordFormat) -> Result<Record, String> {
+    match format {
+        RecordFormat::Json => {
+            let record: JsonRecord = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+            Ok(Record {
+                device: record.device,
+                measurement: record.measurement,
+                timestamp: record.timestamp,
+                value: RecordValue::Json(record.value),
+            })
+        }
+        #[cfg(feature = "avro")]
+        RecordFormat::Avro => read_avro(payload),
+        #[cfg(feature = "avro")]
+        RecordFormat::ConfluentAvro => match payload {
+            [0, _, _, _, _, record @ ..] => read_avro(record),
+            _ => Err("No header of the Confluent schema registry".to_owned()),
+        },
+    }
+}
+
+/// Reads a record of `AVRO_SCHEMA`, the fields in the order of the schema
+#[cfg(feature = "avro")]
+fn read_avro(mut payload: &[u8]) -> Result<Record, String> {
+    let device = read_avro_string(&mut payload)?;
+    let measurement = read_avro_string(&mut payload)?;
+    let timestamp = read_avro_long(&mut payload)?;
+    let value = match read_avro_long(&mut payload)? {
+        0 => AvroValue::Int(
+            i32::try_from(read_avro_long(&mut payload)?)
+                .map_err(|_| "Int exceeds 32 bits".to_owned())?,
+        ),
+        1 => AvroValue::Long(read_avro_long(&mut payload)?),
+        2 => AvroValue::Float(f32::from_le_bytes(
+            read_avro_bytes(&mut payload, 4)?.try_into().unwrap(),
+        )),
+        3 => AvroValue::Double(f64::from_le_bytes(
+            read_avro_bytes(&mut payload, 8)?.try_into().unwrap(),
+        )),
+        4 => AvroValue::String(read_avro_string(&mut payload)?),
+        branch => return Err(format!("Unknown branch {} of the value", branch)),
+    };
+    if !payload.is_empty() {
+        return Err(format!("{} bytes after the record", payload.len()));
+    }
+    Ok(Record {
+        device,
+        measurement,
+        timestamp,
+        value: RecordValue::Avro(value),
+    })
+}
+
+/// Zig-zag encoded variable length long, also used for int
+#[cfg(feature = "avro")]
+fn read_avro_long(payload: &mut &[u8]) -> Result<i64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_avro_bytes(payload, 1)?[0];
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err("Long of more than 10 bytes".to_owned())
+}
+
+#[cfg(feature = "avro")]
+fn read_avro_string(payload: &mut &[u8]) -> Result<String, String> {
+    let length = read_avro_long(payload)?;
+    let length = usize::try_from(length).map_err(|_| format!("Negative length {}", length))?;
+    let bytes = read_avro_bytes(payload, length)?;
+    String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "avro")]
+fn read_avro_bytes<'p>(payload: &mut &'p [u8], length: usize) -> Result<&'p [u8], String> {
+    if payload.len() < length {
+        return Err("Unexpected end of the record".to_owned());
+    }
+    let (bytes, rest) = payload.split_at(length);
+    *payload = rest;
+    Ok(bytes)
+}
+
+/// Converts an Avro value to the data type of the series like `convert`
+#[cfg(feature = "avro")]
+fn convert_avro(value: &AvroValue, data_type: TSDataType) -> Option<IoTDBValue> {
+    match (value, data_type) {
+        (&AvroValue::Int(value), TSDataType::INT32) => Some(IoTDBValue::INT(value)),
+        (&AvroValue::Long(value), TSDataType::INT32) => {
+            i32::try_from(value).ok().map(IoTDBValue::INT)
+        }
+        (&AvroValue::Int(value), TSDataType::INT64) => Some(IoTDBValue::LONG(value as i64)),
+        (&AvroValue::Long(value), TSDataType::INT64) => Some(IoTDBValue::LONG(value)),
+        (&AvroValue::Int(value), TSDataType::FLOAT) => Some(IoTDBValue::FLOAT(value as f32)),
+        (&AvroValue::Long(value), TSDataType::FLOAT) => Some(IoTDBValue::FLOAT(value as f32)),
+        (&AvroValue::Float(value), TSDataType::FLOAT) => Some(IoTDBValue::FLOAT(value)),
+        (&AvroValue::Double(value), TSDataType::FLOAT) => Some(IoTDBValue::FLOAT(value as f32)),
+        (&AvroValue::Int(value), TSDataType::DOUBLE) => Some(IoTDBValue::DOUBLE(value as f64)),
+        (&AvroValue::Long(value), TSDataType::DOUBLE) => Some(IoTDBValue::DOUBLE(value as f64)),
+        (&AvroValue::Float(value), TSDataType::DOUBLE) => Some(IoTDBValue::DOUBLE(value as f64)),
+        (&AvroValue::Double(value), TSDataType::DOUBLE) => Some(IoTDBValue::DOUBLE(value)),
+        (AvroValue::String(value), TSDataType::TEXT) => Some(IoTDBValue::TEXT(value.clone())),
+        _ => None,
+    }
+}
+
+/// Converts a JSON value to the data type of the series, `None` if it does not fit
+fn convert(value: &serde_json::Value, data_type: TSDataType) -> Option<IoTDBValue> {
+    match data_type {
+        TSDataType::INT32 => value
+            .as_i64()
+            .and_then(|value| i32::try_from(value).ok())
+            .map(IoTDBValue::INT),
+        TSDataType::INT64 => value.as_i64().map(IoTDBValue::LONG),
+        TSDataType::FLOAT => value.as_f64().map(|value| IoTDBValue::FLOAT(value as f32)),
+        TSDataType::DOUBLE => value.as_f64().map(IoTDBValue::DOUBLE),
+        TSDataType::TEXT => value
+            .as_str()
+            .map(|value| IoTDBValue::TEXT(value.to_owned())),
+        TSDataType::VECTOR => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::kafka::{Consumer, KafkaBridge, Message};
+    use crate::writer::ts_file_config::{DuplicatePolicy, TsFileConfig};
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::collections::VecDeque;
+
+    #[derive(Default)]
+    struct TestConsumer {
+        messages: VecDeque<Message>,
+        commits: Vec<(i32, i64)>,
+    }
+
+    impl TestConsumer {
+        fn send(&mut self, partition: i32, offset: i64, payload: &str) {
+            self.send_bytes(partition, offset, payload.as_bytes());
+        }
+
+        fn send_bytes(&mut self, partition: i32, offset: i64, payload: &[u8]) {
+            self.messages.push_back(Message {
+                partition,
+                offset,
+                payload: payload.to_vec(),
+            });
+        }
+    }
+
+    impl Consumer for TestConsumer {
+        fn poll(&mut self) -> Result<Option<Message>, TsFileError> {
+            Ok(self.messages.pop_front())
+        }
+
+        fn commit(&mut self, partition: i32, offset: i64) -> Result<(), TsFileError> {
+            self.commits.push((partition, offset));
+            Ok(())
+        }
+    }
+
+    fn schema() -> Schema<'static> {
+        Schema::simple(
+            "root.sg.d1",
+            "s1",
+            TSDataType::INT32,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        )
+    }
+
+    fn record(timestamp: i64, value: &str) -> String {
+        format!(
+            r#"{{"device": "root.sg.d1", "measurement": "s1", "timestamp": {}, "value": {}}}"#,
+            timestamp, value
+        )
+    }
+
+    #[test]
+    fn commit_on_flush() -> Result<(), TsFileError> {
+        let directory = "target/kafka_bridge";
+        let _ = std::fs::remove_dir_all(directory);
+        let mut consumer = TestConsumer::default();
+        for offset in 0..7 {
+            consumer.send(0, offset, &record(offset, &offset.to_string()));
+        }
+        consumer.send(1, 0, &record(1, "-1"));
+        consumer.send(1, 1, &record(2, "-2"));
+
+        let mut bridge = KafkaBridge::new(consumer, directory, schema(), Default::default())?;
+        bridge.max_points(3);
+        bridge.poll_all()?;
+        // Starting the next file flushes the previous one
+        assert_eq!(bridge.consumer.commits, vec![(0, 3), (0, 6)]);
+
+        let files = bridge.close()?;
+        assert_eq!(
+            bridge.consumer.commits,
+            vec![(0, 3), (0, 6), (0, 7), (1, 2)]
+        );
+        assert_eq!(files.len(), 4);
+        assert!(files[3].starts_with("target/kafka_bridge/partition-1"));
+        let mut reader = TsFileReader::open(files[3].to_str().unwrap())?;
+        let points = reader
+            .query("root.sg.d1", "s1")?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            points,
+            vec![(1, IoTDBValue::INT(-1)), (2, IoTDBValue::INT(-2))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_records() -> Result<(), TsFileError> {
+        let directory = "target/kafka_bridge_invalid";
+        let _ = std::fs::remove_dir_all(directory);
+        let mut consumer = TestConsumer::default();
+        consumer.send(0, 0, "no json");
+        consumer.send(0, 1, &record(1, "1.5"));
+        consumer.send(0, 2, &record(1, "1").replace("d1", "d2"));
+        consumer.send(0, 3, &record(1, "1"));
+
+        let mut bridge = KafkaBridge::new(consumer, directory, schema(), Default::default())?;
+        let mut errors = vec![];
+        for _ in 0..3 {
+            match bridge.poll() {
+                Err(TsFileError::Error {
+                    source: Some(message),
+                })
+                | Err(TsFileError::SchemaError {
+                    source: Some(message),
+                }) => errors.push(message),
+                _ => panic!("Expected an error"),
+            }
+        }
+        assert!(errors[0].starts_with("Invalid record at offset 0 of partition 0"));
+        assert_eq!(errors[1], "Value 1.5 of 'root.sg.d1.s1' is no INT32");
+        assert_eq!(errors[2], "Unknown device 'root.sg.d2'");
+        assert!(bridge.poll()?);
+        assert!(!bridge.poll()?);
+        bridge.close()?;
+        assert_eq!(bridge.consumer.commits, vec![(0, 4)]);
+
+        let config = TsFileConfig::default().duplicate_policy(DuplicatePolicy::OverwriteLast);
+        assert!(KafkaBridge::new(TestConsumer::default(), directory, schema(), config).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "avro")]
+    fn avro_long(value: i64, payload: &mut Vec<u8>) {
+        let mut value = ((value << 1) ^ (value >> 63)) as u64;
+        while value > 0x7F {
+            payload.push((value & 0x7F) as u8 | 0x80);
+            value >>= 7;
+        }
+        payload.push(value as u8);
+    }
+
+    #[cfg(feature = "avro")]
+    fn avro_string(value: &str, payload: &mut Vec<u8>) {
+        avro_long(value.len() as i64, payload);
+        payload.extend_from_slice(value.as_bytes());
+    }
+
+    /// Record of `AVRO_SCHEMA` with the branch of the value union and its encoded value
+    #[cfg(feature = "avro")]
+    fn avro_record(measurement: &str, timestamp: i64, branch: i64, value: &[u8]) -> Vec<u8> {
+        let mut payload = vec![];
+        avro_string("root.sg.d1", &mut payload);
+        avro_string(measurement, &mut payload);
+        avro_long(timestamp, &mut payload);
+        avro_long(branch, &mut payload);
+        payload.extend_from_slice(value);
+        payload
+    }
+
+    #[test]
+    #[cfg(feature = "avro")]
+    fn avro_records() -> Result<(), TsFileError> {
+        use crate::writer::kafka::RecordFormat;
+        use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+
+        let directory = "target/kafka_bridge_avro";
+        let _ = std::fs::remove_dir_all(directory);
+        let mut device = DeviceBuilder::new();
+        for (measurement, data_type) in [
+            ("s1", TSDataType::INT32),
+            ("s2", TSDataType::INT64),
+            ("s3", TSDataType::FLOAT),
+            ("s4", TSDataType::DOUBLE),
+            ("s5", TSDataType::TEXT),
+        ] {
+            device.add(
+                measurement,
+                data_type,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            );
+        }
+        let schema = TsFileSchemaBuilder::new()
+            .add("root.sg.d1", device.build())
+            .build();
+
+        let mut consumer = TestConsumer::default();
+        // {"device": "root.sg.d1", "measurement": "s1", "timestamp": 1, "value": {"int": 13}}
+        let mut payload = vec![0x14];
+        payload.extend_from_slice(b"root.sg.d1");
+        payload.extend_from_slice(&[0x04, b's', b'1', 0x02, 0x00, 0x1A]);
+        consumer.send_bytes(0, 0, &payload);
+        let mut long = vec![];
+        avro_long(-5_000_000_000, &mut long);
+        consumer.send_bytes(0, 1, &avro_record("s2", 1, 1, &long));
+        consumer.send_bytes(0, 2, &avro_record("s3", 1, 2, &1.5f32.to_le_bytes()));
+        consumer.send_bytes(0, 3, &avro_record("s4", 1, 0, &[0x05]));
+        let mut text = vec![];
+        avro_string("ab", &mut text);
+        consumer.send_bytes(0, 4, &avro_record("s5", 1, 4, &text));
+
+        let mut bridge = KafkaBridge::new(consumer, directory, schema, Default::default())?;
+        bridge.format(RecordFormat::Avro);
+        bridge.poll_all()?;
+        let files = bridge.close()?;
+        let mut reader = TsFileReader::open(files[0].to_str().unwrap())?;
+        let mut values = vec![];
+        for measurement in ["s1", "s2", "s3", "s4", "s5"] {
+            for point in reader.query("root.sg.d1", measurement)? {
+                values.push(point?);
+            }
+        }
+        assert_eq!(
+            values,
+            vec![
+                (1, IoTDBValue::INT(13)),
+                (1, IoTDBValue::LONG(-5_000_000_000)),
+                (1, IoTDBValue::FLOAT(1.5)),
+                (1, IoTDBValue::DOUBLE(-3.0)),
+                (1, IoTDBValue::TEXT("ab".to_owned())),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "avro")]
+    fn invalid_avro_records() -> Result<(), TsFileError> {
+        use crate::writer::kafka::RecordFormat;
+
+        let directory = "target/kafka_bridge_avro_invalid";
+        let _ = std::fs::remove_dir_all(directory);
+        let confluent = |record: &[u8]| [&[0, 0, 0, 0, 42], record].concat();
+        let record = avro_record("s1", 1, 0, &[0x1A]);
+
+        let mut consumer = TestConsumer::default();
+        consumer.send_bytes(0, 0, &record);
+        consumer.send_bytes(0, 1, &confluent(&record[..record.len() - 1]));
+        consumer.send_bytes(0, 2, &confluent(&[record.as_slice(), &[0]].concat()));
+        consumer.send_bytes(0, 3, &confluent(&avro_record("s1", 2, 5, &[0x1A])));
+        consumer.send_bytes(
+            0,
+            4,
+            &confluent(&avro_record("s1", 2, 3, &1.5f64.to_le_bytes())),
+        );
+        consumer.send_bytes(0, 5, &confluent(&avro_record("s1", 2, 1, &[0xFF; 11])));
+        consumer.send_bytes(0, 6, &confluent(&[0x01]));
+        consumer.send_bytes(0, 7, &confluent(&record));
+
+        let mut bridge = KafkaBridge::new(consumer, directory, schema(), Default::default())?;
+        bridge.format(RecordFormat::ConfluentAvro);
+        let mut errors = vec![];
+        for _ in 0..7 {
+            match bridge.poll() {
+                Err(TsFileError::Error {
+                    source: Some(message),
+                })
+                | Err(TsFileError::SchemaError {
+                    source: Some(message),
+                }) => errors.push(message),
+                _ => panic!("Expected an error"),
+            }
+        }
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid record at offset 0 of partition 0: No header of the Confluent schema registry",
+                "Invalid record at offset 1 of partition 0: Unexpected end of the record",
+                "Invalid record at offset 2 of partition 0: 1 bytes after the record",
+                "Invalid record at offset 3 of partition 0: Unknown branch 5 of the value",
+                "Value Double(1.5) of 'root.sg.d1.s1' is no INT32",
+                "Invalid record at offset 5 of partition 0: Long of more than 10 bytes",
+                "Invalid record at offset 6 of partition 0: Negative length -1",
+            ]
+        );
+        assert!(bridge.poll()?);
+        bridge.close()?;
+        assert_eq!(bridge.consumer.commits, vec![(0, 8)]);
+        Ok(())
+    }
+}
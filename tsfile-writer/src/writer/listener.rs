@@ -0,0 +1,22 @@
+//! Notifications about the flushes of a writer, e.g. to commit the offsets of the input (like
+//! Kafka offsets) only once the data is written to the sink
+
+/// Listener for the flushes of a `TsFileWriter` or `ConcurrentTsFileWriter`, set with
+/// `set_flush_listener`. All methods do nothing by default.
+///
+/// The sink is flushed (`Write::flush`) before the `after_*` methods are called. For files this
+/// hands the data to the operating system, it does not sync it to the disk.
+pub trait FlushListener: Send {
+    /// Called before the chunk group of the device is written, starting at `offset`
+    fn before_chunk_group(&mut self, _device: &str, _offset: u64) {}
+
+    /// Called after the chunk group of the device was written, `size` bytes starting at `offset`
+    fn after_chunk_group(&mut self, _device: &str, _offset: u64, _size: u64) {}
+
+    /// Called after all buffered data was written as chunk groups, `position` is the number of
+    /// bytes written so far. All points written before the flush are in the sink now.
+    fn after_flush(&mut self, _position: u64) {}
+
+    /// Called after the metadata was written on close, `size` is the size of the whole file
+    fn after_close(&mut self, _size: u64) {}
+}
@@ -0,0 +1,51 @@
+//! Counters of a writer, e.g. for the capacity dashboards of an ingestion service,
+//! see `TsFileWriter::metrics`
+use std::collections::BTreeMap;
+
+/// Snapshot of the counters of a `TsFileWriter`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriterMetrics {
+    /// Points per series (device and measurement), flushed and buffered ones. Discarded
+    /// duplicates and points in the reorder window are not counted.
+    pub points: BTreeMap<(String, String), u64>,
+    /// Bytes written to the sink so far
+    pub bytes_written: u64,
+    /// Chunk groups written to the sink so far
+    pub chunk_groups_flushed: u64,
+    /// Estimated memory of the data that is not flushed yet, it is compared to
+    /// `TsFileConfig::chunk_group_size_threshold`
+    pub buffered_bytes: u64,
+    /// Size of all sealed pages after encoding
+    pub uncompressed_page_bytes: u64,
+    /// Size of all sealed pages after compression
+    pub compressed_page_bytes: u64,
+    /// Number of bits of the bloom filter, `None` until `close` or if no filter is written
+    pub bloom_filter_size: Option<u32>,
+    /// Number of hash functions of the bloom filter, `None` like `bloom_filter_size`
+    pub bloom_filter_hash_functions: Option<u32>,
+}
+
+impl WriterMetrics {
+    /// Number of points of all series
+    pub fn total_points(&self) -> u64 {
+        self.points.values().sum()
+    }
+
+    /// Size of the pages before compression divided by the size after compression, `None`
+    /// until the first page is sealed. After `close` all pages are sealed.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed_page_bytes == 0 {
+            return None;
+        }
+        Some(self.uncompressed_page_bytes as f64 / self.compressed_page_bytes as f64)
+    }
+
+    pub(crate) fn add_points(&mut self, device: &str, measurement: &str, points: u64) {
+        if points > 0 {
+            *self
+                .points
+                .entry((device.to_owned(), measurement.to_owned()))
+                .or_default() += points;
+        }
+    }
+}
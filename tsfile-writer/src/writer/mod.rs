@@ -2,7 +2,6 @@
 //! Currently not all features of TsFiles are supported.
 //! Most notably:
 //!
-//! * No Aligned Chunks can be written
 //! * Not all Encodings are available
 //! * Not all DataTypes are supported
 //! * Not all Compression Types are supported
@@ -80,38 +79,63 @@
 //! writer.write("d2", "s1", 1, IoTDBValue::LONG(14));
 //! writer.write("d2", "s2", 1, IoTDBValue::FLOAT(14.0 as f32));
 //! ```
-#![allow(unused_must_use)]
 extern crate core;
 
 use std::cmp::Ordering;
-use std::collections::hash_map::IntoIter;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::IntoIter;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 use std::io::Write;
+use std::sync::Arc;
 use std::{io, vec};
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "async")]
+pub mod async_writer;
+pub mod bloom_filter;
 mod chunk_writer;
+#[cfg(feature = "cloud")]
+pub mod cloud;
 pub mod compression;
+pub mod concurrent_writer;
+#[cfg(feature = "serde")]
+pub mod declaration;
 pub mod encoding;
 pub mod errors;
 mod group_writer;
-mod murmur128;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod listener;
+pub mod metrics;
+pub mod modification;
+pub mod murmur128;
+pub mod partitioned_writer;
+pub mod record;
+pub(crate) mod recover;
+mod reorder;
+pub mod resource;
+pub mod rolling_writer;
 pub mod schema;
-mod statistics;
+pub mod series_writer;
+pub mod statistics;
+pub mod tablet;
 mod test;
 pub mod test_utils;
+pub mod timestamp;
 pub mod ts_file_config;
-mod tsfile_io_writer;
+pub(crate) mod tsfile_io_writer;
 pub mod tsfile_writer;
-mod utils;
+pub(crate) mod utils;
+mod vector_chunk_writer;
+pub mod wal;
 
 use crate::writer::chunk_writer::ChunkMetadata;
 use crate::writer::compression::CompressionType;
 use crate::writer::encoding::TSEncoding;
 use crate::writer::errors::TsFileError;
 
-use crate::writer::murmur128::Murmur128;
 use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
 use crate::writer::statistics::Statistics;
 use crate::writer::ts_file_config::TsFileConfig;
@@ -120,17 +144,143 @@ use crate::writer::MetadataIndexNodeType::LeafDevice;
 
 /// Central enum to pass Values to the writer
 #[allow(dead_code)]
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum IoTDBValue {
     DOUBLE(f64),
     FLOAT(f32),
     INT(i32),
     LONG(i64),
+    TEXT(String),
+}
+
+impl IoTDBValue {
+    pub fn data_type(&self) -> TSDataType {
+        match self {
+            IoTDBValue::DOUBLE(_) => TSDataType::DOUBLE,
+            IoTDBValue::FLOAT(_) => TSDataType::FLOAT,
+            IoTDBValue::INT(_) => TSDataType::INT32,
+            IoTDBValue::LONG(_) => TSDataType::INT64,
+            IoTDBValue::TEXT(_) => TSDataType::TEXT,
+        }
+    }
+}
+
+// There is no `From<bool>` as TsFiles of this crate have no BOOLEAN data type
+macro_rules! iotdb_value_conversions {
+    ($($t:ty => $variant:ident),*) => {
+        $(
+            impl From<$t> for IoTDBValue {
+                fn from(x: $t) -> Self {
+                    IoTDBValue::$variant(x)
+                }
+            }
+
+            impl TryFrom<IoTDBValue> for $t {
+                type Error = TsFileError;
+
+                fn try_from(value: IoTDBValue) -> Result<Self, Self::Error> {
+                    match value {
+                        IoTDBValue::$variant(x) => Ok(x),
+                        _ => Err(TsFileError::WrongTypeForSeries),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+iotdb_value_conversions!(f64 => DOUBLE, f32 => FLOAT, i32 => INT, i64 => LONG, String => TEXT);
+
+impl From<&str> for IoTDBValue {
+    fn from(x: &str) -> Self {
+        IoTDBValue::TEXT(x.to_owned())
+    }
+}
+
+/// Values of a single type, see `TsFileWriter::write_batch`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoTDBValues<'b> {
+    DOUBLE(&'b [f64]),
+    FLOAT(&'b [f32]),
+    INT(&'b [i32]),
+    LONG(&'b [i64]),
+}
+
+impl<'b> IoTDBValues<'b> {
+    pub fn data_type(&self) -> TSDataType {
+        match self {
+            IoTDBValues::DOUBLE(_) => TSDataType::DOUBLE,
+            IoTDBValues::FLOAT(_) => TSDataType::FLOAT,
+            IoTDBValues::INT(_) => TSDataType::INT32,
+            IoTDBValues::LONG(_) => TSDataType::INT64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IoTDBValues::DOUBLE(values) => values.len(),
+            IoTDBValues::FLOAT(values) => values.len(),
+            IoTDBValues::INT(values) => values.len(),
+            IoTDBValues::LONG(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Value at the given index, panics if it is out of bounds
+    pub fn get(&self, index: usize) -> IoTDBValue {
+        match self {
+            IoTDBValues::DOUBLE(values) => IoTDBValue::DOUBLE(values[index]),
+            IoTDBValues::FLOAT(values) => IoTDBValue::FLOAT(values[index]),
+            IoTDBValues::INT(values) => IoTDBValue::INT(values[index]),
+            IoTDBValues::LONG(values) => IoTDBValue::LONG(values[index]),
+        }
+    }
+
+    /// Values from `start` (inclusive) to `end` (exclusive)
+    pub fn slice(&self, start: usize, end: usize) -> IoTDBValues<'b> {
+        match self {
+            IoTDBValues::DOUBLE(values) => IoTDBValues::DOUBLE(&values[start..end]),
+            IoTDBValues::FLOAT(values) => IoTDBValues::FLOAT(&values[start..end]),
+            IoTDBValues::INT(values) => IoTDBValues::INT(&values[start..end]),
+            IoTDBValues::LONG(values) => IoTDBValues::LONG(&values[start..end]),
+        }
+    }
+}
+
+/// Primitive types that can be written with `TsFileWriter::write_batch`
+pub trait BatchValue: Copy {
+    fn values(values: &[Self]) -> IoTDBValues<'_>;
+}
+
+impl BatchValue for f64 {
+    fn values(values: &[f64]) -> IoTDBValues<'_> {
+        IoTDBValues::DOUBLE(values)
+    }
+}
+
+impl BatchValue for f32 {
+    fn values(values: &[f32]) -> IoTDBValues<'_> {
+        IoTDBValues::FLOAT(values)
+    }
 }
 
-impl From<i64> for IoTDBValue {
-    fn from(x: i64) -> Self {
-        IoTDBValue::LONG(x)
+impl BatchValue for i32 {
+    fn values(values: &[i32]) -> IoTDBValues<'_> {
+        IoTDBValues::INT(values)
+    }
+}
+
+impl BatchValue for i64 {
+    fn values(values: &[i64]) -> IoTDBValues<'_> {
+        IoTDBValues::LONG(values)
     }
 }
 
@@ -141,6 +291,7 @@ pub trait PositionedWrite: Write {
     fn get_position(&self) -> u64;
 }
 
+/// Tracks the position for any `Write`, e.g. a `File` or a `TcpStream`
 pub struct WriteWrapper<T: Write> {
     position: u64,
     writer: T,
@@ -169,12 +320,17 @@ impl<T: Write> PositionedWrite for WriteWrapper<T> {
 }
 
 impl<T: Write> WriteWrapper<T> {
-    fn new(writer: T) -> WriteWrapper<T> {
+    pub fn new(writer: T) -> WriteWrapper<T> {
         WriteWrapper {
             position: 0,
             writer,
         }
     }
+
+    /// Returns the wrapped writer
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
 }
 
 impl PositionedWrite for Vec<u8> {
@@ -184,10 +340,20 @@ impl PositionedWrite for Vec<u8> {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 pub enum TSDataType {
     INT32,
     INT64,
     FLOAT,
+    DOUBLE,
+    TEXT,
+    /// Time column of aligned series
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    VECTOR,
 }
 
 impl TSDataType {
@@ -196,6 +362,9 @@ impl TSDataType {
             TSDataType::INT32 => 1,
             TSDataType::INT64 => 2,
             TSDataType::FLOAT => 3,
+            TSDataType::DOUBLE => 4,
+            TSDataType::TEXT => 5,
+            TSDataType::VECTOR => 6,
         }
     }
 }
@@ -208,16 +377,31 @@ impl TryFrom<u8> for TSDataType {
             1 => Ok(TSDataType::INT32),
             2 => Ok(TSDataType::INT64),
             3 => Ok(TSDataType::FLOAT),
+            4 => Ok(TSDataType::DOUBLE),
+            5 => Ok(TSDataType::TEXT),
+            6 => Ok(TSDataType::VECTOR),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate", deny_unknown_fields)
+)]
 pub struct MeasurementSchema {
     pub data_type: TSDataType,
     pub encoding: TSEncoding,
     pub compression: CompressionType,
+    /// Decimal places that are kept of FLOAT and DOUBLE values encoded with TS2DIFF, like the
+    /// property `max_point_number` of IoTDB. Defaults to `DEFAULT_MAX_POINT_NUMBER`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_point_number: Option<u32>,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -239,13 +423,12 @@ impl Ord for Path {
 
 impl PartialOrd<Self> for Path {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.path.partial_cmp(&other.path)
+        Some(self.cmp(other))
     }
 }
 
 impl MeasurementSchema {
-    #[allow(dead_code)]
-    fn new(
+    pub fn new(
         data_type: TSDataType,
         encoding: TSEncoding,
         compression: CompressionType,
@@ -254,24 +437,63 @@ impl MeasurementSchema {
             data_type,
             encoding,
             compression,
+            max_point_number: None,
         }
     }
+
+    /// See `max_point_number`
+    pub fn max_point_number(mut self, decimal_places: u32) -> MeasurementSchema {
+        self.max_point_number = Some(decimal_places);
+        self
+    }
 }
 
+/// Serialized like a `DeviceDeclaration`
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(
+        crate = "serde_crate",
+        try_from = "declaration::DeviceDeclaration<'a>",
+        bound(deserialize = "'de: 'a")
+    )
+)]
 pub struct MeasurementGroup<'a> {
-    measurement_schemas: HashMap<&'a str, MeasurementSchema>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "measurements", serialize_with = "declaration::shared")
+    )]
+    /// Shared by all devices of a `DeviceTemplate`
+    pub(crate) measurement_schemas: Arc<BTreeMap<&'a str, MeasurementSchema>>,
+    aligned: bool,
 }
 
 impl<'a> MeasurementGroup<'a> {
     pub fn get_timeseries(&self) -> IntoIter<&'a str, MeasurementSchema> {
-        self.measurement_schemas.clone().into_iter()
+        (*self.measurement_schemas).clone().into_iter()
+    }
+
+    /// Whether all measurements of the device share one time column
+    pub fn is_aligned(&self) -> bool {
+        self.aligned
     }
 }
 
+/// Serialized like a `SchemaDeclaration`
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(
+        crate = "serde_crate",
+        try_from = "declaration::SchemaDeclaration<'a>",
+        bound(deserialize = "'de: 'a")
+    )
+)]
 pub struct Schema<'a> {
-    measurement_groups: HashMap<&'a str, MeasurementGroup<'a>>,
+    #[cfg_attr(feature = "serde", serde(rename = "devices"))]
+    measurement_groups: BTreeMap<&'a str, MeasurementGroup<'a>>,
 }
 
 impl<'a> Schema<'a> {
@@ -309,9 +531,9 @@ impl<'a> Schema<'a> {
     }
 }
 
-struct ChunkGroupMetadata {
-    device_id: String,
-    chunk_metadata: Vec<ChunkMetadata>,
+pub(crate) struct ChunkGroupMetadata {
+    pub(crate) device_id: String,
+    pub(crate) chunk_metadata: Vec<ChunkMetadata>,
 }
 
 impl ChunkGroupMetadata {
@@ -328,6 +550,7 @@ enum MetadataIndexNodeType {
     LeafMeasurement,
     InternalMeasurement,
     LeafDevice,
+    InternalDevice,
 }
 
 impl Serializable for MetadataIndexNodeType {
@@ -347,15 +570,14 @@ impl Serializable for MetadataIndexNodeType {
             MetadataIndexNodeType::LeafMeasurement => 0x03,
             MetadataIndexNodeType::InternalMeasurement => 0x02,
             LeafDevice => 0x01,
+            MetadataIndexNodeType::InternalDevice => 0x00,
         };
-        file.write(&[byte]);
+        file.write_all(&[byte])?;
 
         Ok(())
     }
 }
 
-struct TimeseriesMetadata {}
-
 #[derive(Clone)]
 struct MetadataIndexEntry {
     name: String,
@@ -368,8 +590,8 @@ impl Serializable for MetadataIndexEntry {
         // byteLen += ReadWriteIOUtils.writeVar(name, outputStream);
         // byteLen += ReadWriteIOUtils.write(offset, outputStream);
         // return byteLen;
-        write_str(file, self.name.as_str());
-        file.write(&self.offset.to_be_bytes());
+        write_str(file, self.name.as_str())?;
+        file.write_all(&self.offset.to_be_bytes())?;
         // file.write(&(self.offset as i64).to_be_bytes());
 
         Ok(())
@@ -393,14 +615,14 @@ impl Serializable for MetadataIndexNode {
         // byteLen += ReadWriteIOUtils.write(endOffset, outputStream);
         // byteLen += ReadWriteIOUtils.write(nodeType.serialize(), outputStream);
         // return byteLen;
-        write_var_u32(self.children.len() as u32, file);
+        write_var_u32(self.children.len() as u32, file)?;
 
         for metadata_index_entry in self.children.iter() {
-            metadata_index_entry.serialize(file);
+            metadata_index_entry.serialize(file)?;
         }
 
-        file.write(&self.end_offset.to_be_bytes());
-        self.node_type.serialize(file);
+        file.write_all(&self.end_offset.to_be_bytes())?;
+        self.node_type.serialize(file)?;
 
         Ok(())
     }
@@ -417,113 +639,91 @@ impl MetadataIndexNode {
 
     fn add_current_index_node_to_queue(
         current_index_node: &mut MetadataIndexNode,
-        measurement_metadata_index_queue: &mut Vec<MetadataIndexNode>,
+        metadata_index_queue: &mut VecDeque<MetadataIndexNode>,
         file: &mut dyn PositionedWrite,
     ) {
-        // TODO file.getPosition
         // currentIndexNode.setEndOffset(out.getPosition());
         current_index_node.end_offset = file.get_position() as usize;
         // metadataIndexNodeQueue.add(currentIndexNode);
-        measurement_metadata_index_queue.push(current_index_node.clone());
+        metadata_index_queue.push_back(current_index_node.clone());
     }
 
-    #[allow(unused_variables)]
+    /// Serializes the nodes of the queue and builds levels of internal nodes of the given type
+    /// on top of them until a single root node is left
     fn generate_root_node(
-        mut measurement_metadata_index_queue: Vec<MetadataIndexNode>,
+        mut metadata_index_queue: VecDeque<MetadataIndexNode>,
         file: &mut dyn PositionedWrite,
         node_type: MetadataIndexNodeType,
         config: &TsFileConfig,
-    ) -> MetadataIndexNode {
-        // int queueSize = metadataIndexNodeQueue.size();
-        // MetadataIndexNode metadataIndexNode;
-        // MetadataIndexNode currentIndexNode = new MetadataIndexNode(type);
-        // while (queueSize != 1) {
-        //   for (int i = 0; i < queueSize; i++) {
-        //     metadataIndexNode = metadataIndexNodeQueue.poll();
-        //     // when constructing from internal node, each node is related to an entry
-        //     if (currentIndexNode.isFull()) {
-        //       addCurrentIndexNodeToQueue(currentIndexNode, metadataIndexNodeQueue, out);
-        //       currentIndexNode = new MetadataIndexNode(type);
-        //     }
-        //     currentIndexNode.addEntry(
-        //         new MetadataIndexEntry(metadataIndexNode.peek().getName(), out.getPosition()));
-        //     metadataIndexNode.serializeTo(out.wrapAsStream());
-        //   }
-        //   addCurrentIndexNodeToQueue(currentIndexNode, metadataIndexNodeQueue, out);
-        //   currentIndexNode = new MetadataIndexNode(type);
-        //   queueSize = metadataIndexNodeQueue.size();
-        // }
-        // return metadataIndexNodeQueue.poll();
-        // TODO
-        let mut queue_size = measurement_metadata_index_queue.len();
-        let mut metadata_index_node;
-        let mut current_index_metadata = MetadataIndexNode::new(node_type.clone());
-
-        while queue_size != 1 {
-            for i in 0..queue_size {
-                metadata_index_node = measurement_metadata_index_queue.last().unwrap().clone();
-                let device = match metadata_index_node.children.get(0) {
-                    None => {
-                        panic!("...")
-                    }
-                    Some(inner) => inner.name.clone(),
+    ) -> Result<MetadataIndexNode, TsFileError> {
+        let mut queue_size = metadata_index_queue.len();
+        let mut current_index_node = MetadataIndexNode::new(node_type.clone());
+
+        while queue_size > 1 {
+            for _ in 0..queue_size {
+                let metadata_index_node = match metadata_index_queue.pop_front() {
+                    Some(node) => node,
+                    None => return Err(Self::empty_index_node()),
                 };
-                measurement_metadata_index_queue.remove(measurement_metadata_index_queue.len() - 1);
-                if current_index_metadata.is_full(config) {
-                    current_index_metadata.end_offset = file.get_position() as usize;
-                    measurement_metadata_index_queue.push(current_index_metadata.clone());
+                // when constructing from internal node, each node is related to an entry
+                if current_index_node.is_full(config) {
+                    Self::add_current_index_node_to_queue(
+                        &mut current_index_node,
+                        &mut metadata_index_queue,
+                        file,
+                    );
+                    current_index_node = MetadataIndexNode::new(node_type.clone());
                 }
-                // ...
-                let name = match metadata_index_node.children.get(0) {
-                    None => {
-                        panic!("This should not happen!")
-                    }
-                    Some(node) => node.name.clone(),
+                let name = match metadata_index_node.children.first() {
+                    None => return Err(Self::empty_index_node()),
+                    Some(entry) => entry.name.clone(),
                 };
-                current_index_metadata.children.push(MetadataIndexEntry {
+                current_index_node.children.push(MetadataIndexEntry {
                     name,
                     offset: file.get_position() as usize,
                 });
+                metadata_index_node.serialize(file)?;
             }
-            // ...
             Self::add_current_index_node_to_queue(
-                &mut current_index_metadata,
-                &mut measurement_metadata_index_queue,
+                &mut current_index_node,
+                &mut metadata_index_queue,
                 file,
             );
-            current_index_metadata = MetadataIndexNode {
-                children: vec![],
-                end_offset: 0,
-                node_type: node_type.clone(),
-            };
-            queue_size = measurement_metadata_index_queue.len();
+            current_index_node = MetadataIndexNode::new(node_type.clone());
+            queue_size = metadata_index_queue.len();
+        }
+        match metadata_index_queue.pop_front() {
+            Some(node) => Ok(node),
+            None => Err(Self::empty_index_node()),
+        }
+    }
+
+    fn empty_index_node() -> TsFileError {
+        TsFileError::IllegalState {
+            source: Some("Metadata index node without children".to_owned()),
         }
-        return measurement_metadata_index_queue.get(0).unwrap().clone();
     }
 
-    #[allow(unused_variables)]
     fn construct_metadata_index(
         device_timeseries_metadata_map: &BTreeMap<String, Vec<Box<dyn TimeSeriesMetadatable>>>,
         file: &mut dyn PositionedWrite,
         config: &TsFileConfig,
-    ) -> MetadataIndexNode {
-        let mut device_metadata_index_map: HashMap<String, MetadataIndexNode> = HashMap::new();
+    ) -> Result<MetadataIndexNode, TsFileError> {
+        // Devices have to be sorted, as readers do a binary search on the index entries
+        let mut device_metadata_index_map: BTreeMap<String, MetadataIndexNode> = BTreeMap::new();
 
         for (device, list_metadata) in device_timeseries_metadata_map.iter() {
             if list_metadata.is_empty() {
                 continue;
             }
 
-            let mut measurement_metadata_index_queue: Vec<MetadataIndexNode> = vec![];
+            let mut measurement_metadata_index_queue: VecDeque<MetadataIndexNode> = VecDeque::new();
 
-            let timeseries_metadata: TimeseriesMetadata;
             let mut current_index_node: MetadataIndexNode =
                 MetadataIndexNode::new(MetadataIndexNodeType::LeafMeasurement);
 
             // for (int i = 0; i < entry.getValue().size(); i++) {
-            for i in 0..list_metadata.len() {
-                let timeseries_metadata = list_metadata.get(i).unwrap();
-
+            for (i, timeseries_metadata) in list_metadata.iter().enumerate() {
                 if i % config.max_degree_of_index_node == 0 {
                     if current_index_node.is_full(config) {
                         Self::add_current_index_node_to_queue(
@@ -539,22 +739,25 @@ impl MetadataIndexNode {
                         offset: file.get_position() as usize,
                     });
                 }
-                timeseries_metadata.serialize(file);
+                timeseries_metadata.serialize(file)?;
             }
             // addCurrentIndexNodeToQueue(currentIndexNode, measurementMetadataIndexQueue, out);
             // deviceMetadataIndexMap.put(
             //       entry.getKey(),
             //       generateRootNode(
             //           measurementMetadataIndexQueue, out, MetadataIndexNodeType.INTERNAL_MEASUREMENT));
-            current_index_node.end_offset = file.get_position() as usize;
-            measurement_metadata_index_queue.push(current_index_node.clone());
+            Self::add_current_index_node_to_queue(
+                &mut current_index_node,
+                &mut measurement_metadata_index_queue,
+                file,
+            );
 
             let root_node = Self::generate_root_node(
                 measurement_metadata_index_queue,
                 file,
                 MetadataIndexNodeType::InternalMeasurement,
                 config,
-            );
+            )?;
             device_metadata_index_map.insert(device.clone(), root_node);
         }
 
@@ -566,54 +769,54 @@ impl MetadataIndexNode {
                     name: s.to_owned(),
                     offset: file.get_position() as usize,
                 });
-                value.serialize(file);
+                value.serialize(file)?;
             }
             metadata_index_node.end_offset = file.get_position() as usize;
-            return metadata_index_node;
+            return Ok(metadata_index_node);
         }
 
-        panic!("This is not yet implemented!");
-
-        // // if not exceed the max child nodes num, ignore the device index and directly point to the
-        // // measurement
-        // if (deviceMetadataIndexMap.size() <= config.getMaxDegreeOfIndexNode()) {
-        //   MetadataIndexNode metadataIndexNode =
-        //       new MetadataIndexNode(MetadataIndexNodeType.LEAF_DEVICE);
-        //   for (Map.Entry<String, MetadataIndexNode> entry : deviceMetadataIndexMap.entrySet()) {
-        //     metadataIndexNode.addEntry(new MetadataIndexEntry(entry.getKey(), out.getPosition()));
-        //     entry.getValue().serializeTo(out.wrapAsStream());
-        //   }
-        //   metadataIndexNode.setEndOffset(out.getPosition());
-        //   return metadataIndexNode;
-        // }
-        //
-        // // else, build level index for devices
-        // Queue<MetadataIndexNode> deviceMetadataIndexQueue = new ArrayDeque<>();
-        // MetadataIndexNode currentIndexNode = new MetadataIndexNode(MetadataIndexNodeType.LEAF_DEVICE);
-        //
-        // for (Map.Entry<String, MetadataIndexNode> entry : deviceMetadataIndexMap.entrySet()) {
-        //   // when constructing from internal node, each node is related to an entry
-        //   if (currentIndexNode.isFull()) {
-        //     addCurrentIndexNodeToQueue(currentIndexNode, deviceMetadataIndexQueue, out);
-        //     currentIndexNode = new MetadataIndexNode(MetadataIndexNodeType.LEAF_DEVICE);
-        //   }
-        //   currentIndexNode.addEntry(new MetadataIndexEntry(entry.getKey(), out.getPosition()));
-        //   entry.getValue().serializeTo(out.wrapAsStream());
-        // }
-        // addCurrentIndexNodeToQueue(currentIndexNode, deviceMetadataIndexQueue, out);
-        // MetadataIndexNode deviceMetadataIndexNode =
-        //     generateRootNode(deviceMetadataIndexQueue, out, MetadataIndexNodeType.INTERNAL_DEVICE);
-        // deviceMetadataIndexNode.setEndOffset(out.getPosition());
-        // return deviceMetadataIndexNode;
+        // else, build level index for devices
+        let mut device_metadata_index_queue: VecDeque<MetadataIndexNode> = VecDeque::new();
+        let mut current_index_node = MetadataIndexNode::new(LeafDevice);
+
+        for (device, value) in device_metadata_index_map {
+            // when constructing from internal node, each node is related to an entry
+            if current_index_node.is_full(config) {
+                Self::add_current_index_node_to_queue(
+                    &mut current_index_node,
+                    &mut device_metadata_index_queue,
+                    file,
+                );
+                current_index_node = MetadataIndexNode::new(LeafDevice);
+            }
+            current_index_node.children.push(MetadataIndexEntry {
+                name: device,
+                offset: file.get_position() as usize,
+            });
+            value.serialize(file)?;
+        }
+        Self::add_current_index_node_to_queue(
+            &mut current_index_node,
+            &mut device_metadata_index_queue,
+            file,
+        );
+        let mut device_metadata_index_node = Self::generate_root_node(
+            device_metadata_index_queue,
+            file,
+            MetadataIndexNodeType::InternalDevice,
+            config,
+        )?;
+        device_metadata_index_node.end_offset = file.get_position() as usize;
+        Ok(device_metadata_index_node)
     }
     fn is_full(&self, config: &TsFileConfig) -> bool {
         self.children.len() >= config.max_degree_of_index_node
     }
 }
 
-trait TimeSeriesMetadatable {
+trait TimeSeriesMetadatable: Send {
     fn get_measurement_id(&self) -> String;
-    fn serialize(&self, file: &mut dyn PositionedWrite) -> io::Result<()>;
+    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError>;
 }
 
 impl TimeSeriesMetadatable for TimeSeriesMetadata {
@@ -621,13 +824,13 @@ impl TimeSeriesMetadatable for TimeSeriesMetadata {
         self.measurement_id.clone()
     }
 
-    fn serialize(&self, file: &mut dyn PositionedWrite) -> io::Result<()> {
-        file.write_all(&[self.time_series_metadata_type]);
-        write_str(file, self.measurement_id.as_str());
-        file.write_all(&[self.data_type.serialize()]);
-        write_var_u32(self.chunk_meta_data_list_data_size as u32, file);
-        self.statistics.serialize(file);
-        file.write_all(&self.buffer);
+    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
+        file.write_all(&[self.time_series_metadata_type])?;
+        write_str(file, self.measurement_id.as_str())?;
+        file.write_all(&[self.data_type.serialize()])?;
+        write_var_u32(self.chunk_meta_data_list_data_size as u32, file)?;
+        self.statistics.serialize(file)?;
+        file.write_all(&self.buffer)?;
         Ok(())
     }
 }
@@ -653,138 +856,6 @@ struct TimeSeriesMetadata {
 //     }
 // }
 
-struct HashFunction {
-    cap: i32,
-    seed: i32,
-}
-
-impl HashFunction {
-    fn new(cap: i32, seed: i32) -> HashFunction {
-        HashFunction { cap, seed }
-    }
-
-    fn _murmur_hash(&self, s: &str, seed: i32) -> i32 {
-        Murmur128::hash(s, seed)
-    }
-
-    fn hash(&self, value: &str) -> usize {
-        // return Math.abs(Murmur128Hash.hash(value, seed)) % cap;
-        (self._murmur_hash(value, self.seed).abs() % self.cap) as usize
-    }
-}
-
-struct BloomFilter {
-    size: i32,
-    hash_function_size: i32,
-    func: Vec<HashFunction>,
-    bit_set: Vec<bool>,
-}
-
-impl BloomFilter {
-    fn add(&mut self, path: String) {
-        for f in self.func.iter() {
-            let bit_id = f.hash(&path);
-            // println!("{path} - {} -> {}", f.seed, bit_id);
-            self.bit_set[bit_id] = true;
-        }
-    }
-
-    fn new(size: i32, hash_function_size: i32, config: &TsFileConfig) -> BloomFilter {
-        let mut func = vec![];
-
-        for i in 0..hash_function_size {
-            func.push(HashFunction::new(size, config.seeds[i as usize] as i32));
-        }
-
-        let bit_set = vec![false; size as usize];
-
-        BloomFilter {
-            size,
-            hash_function_size,
-            func,
-            bit_set,
-        }
-    }
-
-    fn build(paths: Vec<Path>, config: &TsFileConfig) -> BloomFilter {
-        let mut filter =
-            BloomFilter::empty_filter(config.bloom_filter_error_rate, paths.len() as i32, config);
-
-        for path in paths {
-            filter.add(path.path);
-        }
-
-        filter
-    }
-
-    fn empty_filter(error_percent: f64, num_of_string: i32, config: &TsFileConfig) -> BloomFilter {
-        let mut error = error_percent;
-        error = error.max(config.min_bloom_filter_error_rate);
-        error = error.min(config.max_bloom_filter_error_rate);
-
-        let ln2 = 2.0_f64.ln();
-
-        let size = (-num_of_string as f64 * error.ln() / ln2 / ln2) as i32 + 1;
-        let hash_function_size = ((-1.0 * error.ln() / ln2) + 1.0) as i32;
-
-        BloomFilter::new(
-            size.max(config.minimal_size),
-            hash_function_size.min(config.maximal_hash_function_size),
-            config,
-        )
-    }
-
-    fn serialize_bits(&self) -> Vec<u8> {
-        let number_of_bytes = if self.bit_set.len() % 8 == 0 {
-            self.bit_set.len() / 8
-        } else {
-            (self.bit_set.len() + (self.bit_set.len() % 8)) / 8
-        };
-
-        let mut result = vec![0_u8; number_of_bytes];
-
-        for i in 0..self.bit_set.len() {
-            let byte_index = (i - (i % 8)) / 8;
-            let bit_index = i % 8;
-
-            // println!("{} - {} / {} -> {}", i, byte_index, bit_index, self.bit_set[i]);
-
-            // TODO why is this necessary?
-            // if byte_index >= result.len() {
-            //     println!("Skipped!!!!");
-            //     continue;
-            // }
-
-            let value = *result.get(byte_index).unwrap();
-
-            // See https://stackoverflow.com/questions/57449264/how-to-get-replace-a-value-in-rust-vec
-            let bit: u8 = if self.bit_set[i] { 0x01 } else { 0x00 };
-            std::mem::replace(&mut result[byte_index], value | (bit << bit_index));
-        }
-
-        // Remove all trailing zero-bytes
-        while result.last().is_some() && *result.last().unwrap() == 0x00 {
-            result.remove(result.len() - 1);
-        }
-
-        result
-    }
-}
-
-impl Serializable for BloomFilter {
-    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
-        // Real
-        let bytes = self.serialize_bits();
-
-        write_var_u32(bytes.len() as u32, file);
-        file.write_all(bytes.as_slice());
-        write_var_u32(self.size as u32, file);
-        write_var_u32(self.hash_function_size as u32, file);
-
-        Ok(())
-    }
-}
-
 struct TsFileMetadata {
     metadata_index: Option<MetadataIndexNode>,
     meta_offset: u64,
@@ -803,15 +874,15 @@ impl Serializable for TsFileMetadata {
     fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
         match self.metadata_index.clone() {
             Some(index) => {
-                index.serialize(file);
+                index.serialize(file)?;
             }
             None => {
                 // Write 0 as 4 bytes (u32)
-                file.write_all(&0x00_u32.to_be_bytes());
+                file.write_all(&0x00_u32.to_be_bytes())?;
             }
         }
         // Meta Offset
-        file.write_all(&self.meta_offset.to_be_bytes());
+        file.write_all(&self.meta_offset.to_be_bytes())?;
 
         Ok(())
     }
@@ -835,16 +906,6 @@ impl Serializable for ChunkGroupHeader<'_> {
     }
 }
 
-struct ChunkGroup<'a> {
-    header: ChunkGroupHeader<'a>,
-}
-
-impl Serializable for ChunkGroup<'_> {
-    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
-        self.header.serialize(file)
-    }
-}
-
 pub trait Serializable {
     fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError>;
 }
@@ -857,21 +918,90 @@ fn write_str(file: &mut dyn PositionedWrite, s: &str) -> Result<(), TsFileError>
     Ok(())
 }
 
+pub const ONLY_ONE_PAGE_CHUNK_HEADER: u8 = 5;
+pub const CHUNK_HEADER: u8 = 1;
+/// Mask for the chunk type of the time column of aligned series
+pub const TIME_SERIES_MASK: u8 = 0x80;
+/// Mask for the chunk type of value columns of aligned series
+pub const VALUE_SERIES_MASK: u8 = 0x40;
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+    use std::sync::Arc;
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::reader::{
+        MetadataIndexNode as ReaderIndexNode, MetadataIndexNodeType, TimeseriesMetadata,
+    };
     use crate::writer::compression::CompressionType;
     use crate::writer::encoding::TSEncoding;
     use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
     use crate::writer::tsfile_writer::TsFileWriter;
-    use crate::writer::utils::{read_var_u32, write_var_u32};
+    use crate::writer::utils::{read_str, read_var_u32, write_var_u32};
     use crate::writer::{
         IoTDBValue, MeasurementGroup, MeasurementSchema, Schema, TSDataType, TsFileError,
-        WriteWrapper,
+        WriteWrapper, ONLY_ONE_PAGE_CHUNK_HEADER, TIME_SERIES_MASK,
     };
 
+    #[test]
+    fn value_conversions() {
+        assert_eq!(IoTDBValue::from(1), IoTDBValue::INT(1));
+        assert_eq!(IoTDBValue::from(1_i64), IoTDBValue::LONG(1));
+        assert_eq!(IoTDBValue::from(1.5_f32), IoTDBValue::FLOAT(1.5));
+        assert_eq!(IoTDBValue::from(1.5), IoTDBValue::DOUBLE(1.5));
+        assert_eq!(IoTDBValue::from("a"), IoTDBValue::TEXT("a".to_owned()));
+
+        assert_eq!(i64::try_from(IoTDBValue::LONG(2)).unwrap(), 2);
+        assert_eq!(
+            String::try_from(IoTDBValue::TEXT("b".to_owned())).unwrap(),
+            "b"
+        );
+        assert!(matches!(
+            i32::try_from(IoTDBValue::LONG(2)),
+            Err(TsFileError::WrongTypeForSeries)
+        ));
+    }
+
+    #[test]
+    fn write_into_value() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::TEXT,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+        writer.write("d1", "s1", 1, 7)?;
+        writer.write("d1", "s2", 1, "seven")?;
+        assert!(matches!(
+            writer.write("d1", "s1", 2, 7_i64),
+            Err(TsFileError::WrongTypeForSeries)
+        ));
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let points: Vec<_> = reader.query("d1", "s2")?.collect::<Result<_, _>>()?;
+        assert_eq!(points, vec![(1, IoTDBValue::TEXT("seven".to_owned()))]);
+        Ok(())
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
@@ -899,12 +1029,13 @@ mod tests {
             CompressionType::UNCOMPRESSED,
         );
 
-        let mut measurement_schema_map = HashMap::new();
+        let mut measurement_schema_map = BTreeMap::new();
         measurement_schema_map.insert("s1", measurement_schema);
         let measurement_group = MeasurementGroup {
-            measurement_schemas: measurement_schema_map,
+            measurement_schemas: Arc::new(measurement_schema_map),
+            aligned: false,
         };
-        let mut measurement_groups_map = HashMap::new();
+        let mut measurement_groups_map = BTreeMap::new();
         let d1 = "d1";
         measurement_groups_map.insert(d1, measurement_group);
         let schema = Schema {
@@ -916,13 +1047,13 @@ mod tests {
         let mut writer =
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
-        TsFileWriter::write(&mut writer, "d1", "s1", 1, IoTDBValue::INT(13));
-        TsFileWriter::write(&mut writer, "d1", "s1", 10, IoTDBValue::INT(14));
-        TsFileWriter::write(&mut writer, "d1", "s1", 100, IoTDBValue::INT(15));
+        TsFileWriter::write(&mut writer, "d1", "s1", 1, IoTDBValue::INT(13)).unwrap();
+        TsFileWriter::write(&mut writer, "d1", "s1", 10, IoTDBValue::INT(14)).unwrap();
+        TsFileWriter::write(&mut writer, "d1", "s1", 100, IoTDBValue::INT(15)).unwrap();
 
-        writer.close();
+        writer.close().unwrap();
 
-        let buffer_writer = writer.file_io_writer.out;
+        let buffer_writer = writer.into_inner();
 
         assert_eq!(buffer_writer.writer, expectation);
         assert_eq!(buffer_writer.position, expectation.len() as u64);
@@ -960,11 +1091,15 @@ mod tests {
         let mut writer = TsFileWriter::new(filename.as_str(), schema, Default::default()).unwrap();
 
         for i in 0..100 {
-            writer.write(device, "s1", i, IoTDBValue::INT(i as i32));
-            writer.write(device, "s2", i, IoTDBValue::INT(i as i32));
+            writer
+                .write(device, "s1", i, IoTDBValue::INT(i as i32))
+                .unwrap();
+            writer
+                .write(device, "s2", i, IoTDBValue::INT(i as i32))
+                .unwrap();
         }
 
-        writer.close();
+        writer.close().unwrap();
     }
 
     #[test]
@@ -988,17 +1123,13 @@ mod tests {
 
         let result = writer.write("d1", "s1", 0, IoTDBValue::LONG(0));
 
-        match result {
-            Ok(_) => {}
-            Err(_) => {
-                assert!(false);
-            }
-        }
+        assert!(result.is_ok());
 
-        writer.close();
+        writer.close().unwrap();
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn write_float() {
         let schema = TsFileSchemaBuilder::new()
             .add(
@@ -1019,14 +1150,143 @@ mod tests {
 
         let result = writer.write("d1", "s1", 0, IoTDBValue::FLOAT(3.141));
 
-        match result {
-            Ok(_) => {}
-            Err(_) => {
-                assert!(false);
-            }
+        assert!(result.is_ok());
+
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn write_double() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::DOUBLE,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+
+        for i in 0..10 {
+            let result = writer.write("d1", "s1", i, IoTDBValue::DOUBLE(i as f64 * 0.5));
+            assert!(result.is_ok());
+        }
+
+        writer.close().unwrap();
+
+        // Chunk header: marker, measurement id "s1", size, data type DOUBLE, compression, encoding
+        let out = &writer.file_io_writer.out;
+        assert_eq!(&out[11..15], &[0x05, 0x04, 0x73, 0x31]);
+        assert_eq!(out[16], 0x04);
+    }
+
+    #[test]
+    fn write_text() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::TEXT,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+
+        writer
+            .write("d1", "s1", 1, IoTDBValue::TEXT("abc".to_owned()))
+            .unwrap();
+        writer
+            .write("d1", "s1", 2, IoTDBValue::TEXT("de".to_owned()))
+            .unwrap();
+
+        writer.close().unwrap();
+
+        let out = &writer.file_io_writer.out;
+        // Data type TEXT in chunk header
+        assert_eq!(out[16], 0x05);
+        // Values are written as var int length + bytes
+        let values = [0x06, 0x61, 0x62, 0x63, 0x04, 0x64, 0x65];
+        assert!(out.windows(values.len()).any(|w| w == values));
+        // Statistics contain first and last value with i32 length
+        let statistics = [
+            0x00, 0x00, 0x00, 0x03, 0x61, 0x62, 0x63, 0x00, 0x00, 0x00, 0x02, 0x64, 0x65,
+        ];
+        assert!(out.windows(statistics.len()).any(|w| w == statistics));
+    }
+
+    #[test]
+    fn write_gorilla() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::FLOAT,
+            TSEncoding::GORILLA,
+            CompressionType::UNCOMPRESSED,
+        );
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+
+        writer.write("d1", "s1", 1, IoTDBValue::FLOAT(1.0)).unwrap();
+        writer.write("d1", "s1", 2, IoTDBValue::FLOAT(1.0)).unwrap();
+
+        writer.close().unwrap();
+
+        let out = &writer.file_io_writer.out;
+        // Data type FLOAT, uncompressed, GORILLA in chunk header
+        assert_eq!(&out[16..19], &[0x03, 0x00, 0x08]);
+        // Gorilla encoded values including the NaN ending
+        let values = [0x3F, 0x80, 0x00, 0x00, 0x61, 0x44, 0x04];
+        assert!(out.windows(values.len()).any(|w| w == values));
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn write_gzip() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::GZIP,
+        );
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+
+        for i in 0..100 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+        }
+
+        writer.close().unwrap();
+
+        let out = &writer.file_io_writer.out;
+        // Data type INT64, GZIP, PLAIN in chunk header (after a two byte chunk size)
+        assert_eq!(&out[17..20], &[0x02, 0x02, 0x00]);
+        // GZIP magic bytes in page content
+        assert!(out.windows(2).any(|w| w == [0x1f, 0x8b]));
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn write_lz4() {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::LZ4,
+        );
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+
+        for i in 0..100 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
         }
 
-        writer.close();
+        writer.close().unwrap();
+
+        let out = &writer.file_io_writer.out;
+        // Data type INT64, LZ4, PLAIN in chunk header (after a two byte chunk size)
+        assert_eq!(&out[17..20], &[0x02, 0x07, 0x00]);
     }
 
     #[test]
@@ -1037,7 +1297,7 @@ mod tests {
             let mut result: Vec<u8> = vec![];
 
             // Write it
-            write_var_u32(number, &mut result);
+            write_var_u32(number, &mut result).unwrap();
             // Read it back
             let result: u32 = read_var_u32(&mut result.as_slice()).unwrap();
 
@@ -1058,6 +1318,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_str_with_corrupt_length() {
+        // Claims a length of 1 GiB, followed by a single byte
+        let bytes = [0x80, 0x80, 0x80, 0x80, 0x08, b'a'];
+        assert!(matches!(
+            read_str(&mut bytes.as_slice()),
+            Err(TsFileError::Corrupt { .. })
+        ));
+        // Too many continuation bytes
+        let bytes = [0xFF; 6];
+        assert!(matches!(
+            read_var_u32(&mut bytes.as_slice()),
+            Err(TsFileError::Corrupt { .. })
+        ));
+    }
+
     #[test]
     fn write_var_int_2() {
         let number: u32 = 128;
@@ -1168,11 +1444,13 @@ mod tests {
         let mut writer =
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
-        writer.write("d1", "s1", 1, IoTDBValue::INT(13));
-        writer.write("d1", "s2", 1, IoTDBValue::LONG(14));
-        writer.write("d1", "s3", 1, IoTDBValue::FLOAT(15.0));
+        writer.write("d1", "s1", 1, IoTDBValue::INT(13)).unwrap();
+        writer.write("d1", "s2", 1, IoTDBValue::LONG(14)).unwrap();
+        writer
+            .write("d1", "s3", 1, IoTDBValue::FLOAT(15.0))
+            .unwrap();
 
-        writer.close();
+        writer.close().unwrap();
 
         // assert_eq!(buffer_writer.writer, expected);
         assert_eq!(writer.file_io_writer.out.position, expected.len() as u64);
@@ -1217,9 +1495,9 @@ mod tests {
         let mut writer =
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
-        writer.write("d1", "s", 1, IoTDBValue::INT(13));
+        writer.write("d1", "s", 1, IoTDBValue::INT(13)).unwrap();
 
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(writer.file_io_writer.out.writer, expected);
     }
@@ -1265,9 +1543,9 @@ mod tests {
         let mut writer =
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
-        writer.write("d1", "s", 1, IoTDBValue::LONG(13));
+        writer.write("d1", "s", 1, IoTDBValue::LONG(13)).unwrap();
 
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(writer.file_io_writer.out.writer, expected);
     }
@@ -1312,9 +1590,9 @@ mod tests {
         let mut writer =
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
-        writer.write("d1", "s", 1, IoTDBValue::FLOAT(13.0));
+        writer.write("d1", "s", 1, IoTDBValue::FLOAT(13.0)).unwrap();
 
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(writer.file_io_writer.out.writer, expected);
     }
@@ -1510,10 +1788,12 @@ mod tests {
             TsFileWriter::new_from_writer(schema, buffer_writer, Default::default()).unwrap();
 
         for i in 0..1001 {
-            writer.write("d1", "s", i, IoTDBValue::INT(i as i32));
+            writer
+                .write("d1", "s", i, IoTDBValue::INT(i as i32))
+                .unwrap();
         }
 
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(writer.file_io_writer.out.writer, expected);
     }
@@ -1538,10 +1818,10 @@ mod tests {
             TsFileWriter::new("target/10000_int64.tsfile", schema, Default::default()).unwrap();
 
         for i in 0..10001 {
-            writer.write("d1", "s", i, IoTDBValue::LONG(2 * i));
+            writer.write("d1", "s", i, IoTDBValue::LONG(2 * i)).unwrap();
         }
 
-        writer.close();
+        writer.close().unwrap();
     }
 
     #[test]
@@ -1565,6 +1845,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "snappy")]
     fn write_i64_snappy() {
         let expected = [
             0x54, 0x73, 0x46, 0x69, 0x6C, 0x65, 0x03, 0x00, 0x04, 0x64, 0x31, 0x05, 0x04, 0x73,
@@ -1602,8 +1883,8 @@ mod tests {
 
         let mut writer = TsFileWriter::new_from_writer(schema, buffer, Default::default()).unwrap();
 
-        writer.write("d1", "s1", 1, IoTDBValue::LONG(13));
-        writer.close();
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+        writer.close().unwrap();
 
         assert_eq!(expected, writer.file_io_writer.out.as_slice());
     }
@@ -1650,7 +1931,7 @@ mod tests {
         for i in 0..10 {
             writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
         }
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(expected, writer.file_io_writer.out.as_slice());
 
@@ -1698,7 +1979,7 @@ mod tests {
         for i in 0..10 {
             writer.write("d1", "s1", i, IoTDBValue::INT(i as i32))?;
         }
-        writer.close();
+        writer.close().unwrap();
 
         assert_eq!(expected, writer.file_io_writer.out.as_slice());
 
@@ -1706,7 +1987,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore = "Is luck dependent due to HashMap"]
     fn write_snappy_complex() {
         let expected = [
             0x54, 0x73, 0x46, 0x69, 0x6C, 0x65, 0x03, 0x00, 0x04, 0x64, 0x31, 0x05, 0x04, 0x73,
@@ -1776,10 +2056,12 @@ mod tests {
 
         let mut writer = TsFileWriter::new_from_writer(schema, buffer, Default::default()).unwrap();
 
-        writer.write("d1", "s1", 1, IoTDBValue::LONG(13));
-        writer.write("d1", "s2", 1, IoTDBValue::FLOAT(14.0));
-        writer.write("d2", "s1", 1, IoTDBValue::INT(15));
-        writer.close();
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+        writer
+            .write("d1", "s2", 1, IoTDBValue::FLOAT(14.0))
+            .unwrap();
+        writer.write("d2", "s1", 1, IoTDBValue::INT(15)).unwrap();
+        writer.close().unwrap();
 
         assert_eq!(expected, writer.file_io_writer.out.as_slice());
     }
@@ -1798,11 +2080,339 @@ mod tests {
 
         writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
 
-        writer.close();
+        writer.close().unwrap();
 
         Ok(())
     }
-}
 
-pub const ONLY_ONE_PAGE_CHUNK_HEADER: u8 = 5;
-pub const CHUNK_HEADER: u8 = 1;
+    #[test]
+    fn write_aligned() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+
+        writer.write("d1", "s1", 1, IoTDBValue::INT(1))?;
+        writer.write("d1", "s2", 2, IoTDBValue::LONG(2))?;
+        // The time column is shared, so timestamps have to increase per device
+        assert_eq!(
+            Some(TsFileError::OutOfOrderData),
+            writer.write("d1", "s1", 2, IoTDBValue::INT(2)).err()
+        );
+        assert_eq!(
+            Some(TsFileError::WrongTypeForSeries),
+            writer.write("d1", "s1", 3, IoTDBValue::LONG(3)).err()
+        );
+        writer.close().unwrap();
+
+        let out = writer.file_io_writer.out.as_slice();
+        // Chunk group header for d1
+        assert_eq!(&out[7..11], &[0x00, 0x04, 0x64, 0x31]);
+        // Time chunk: single page with time mask, empty measurement id
+        assert_eq!(out[11], ONLY_ONE_PAGE_CHUNK_HEADER | TIME_SERIES_MASK);
+        assert_eq!(out[12], 0x00);
+        // VECTOR, UNCOMPRESSED, TS2DIFF
+        assert_eq!(&out[14..17], &[0x06, 0x00, 0x04]);
+
+        Ok(())
+    }
+
+    fn write_devices(devices: &[String], config: TsFileConfig) -> Result<Vec<u8>, TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for device in devices {
+            schema.add(
+                device,
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            );
+        }
+        let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], config)?;
+        for (i, device) in devices.iter().enumerate() {
+            writer.write(device, "s1", 1, IoTDBValue::LONG(i as i64))?;
+        }
+        writer.close()?;
+        Ok(writer.into_inner())
+    }
+
+    #[test]
+    fn many_devices() -> Result<(), TsFileError> {
+        let devices: Vec<String> = (0..1000).map(|i| format!("root.sg.d{}", i)).collect();
+        let mut sorted = devices.clone();
+        sorted.sort();
+
+        for config in [
+            TsFileConfig::default(),
+            // Three levels of internal device nodes
+            TsFileConfig::default().max_degree_of_index_node(4),
+        ] {
+            let buffer = write_devices(&devices, config)?;
+
+            let mut reader = TsFileReader::new(Cursor::new(buffer))?;
+            assert_eq!(reader.metadata_reader().devices()?, sorted);
+            for (i, device) in devices.iter().enumerate() {
+                let points = reader.query(device, "s1")?.collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(points, vec![(1, IoTDBValue::LONG(i as i64))]);
+            }
+            assert_eq!(reader.query("root.sg.d1000", "s1")?.count(), 0);
+        }
+        Ok(())
+    }
+
+    /// Checks that the children of every node are sorted and returns the timeseries of every
+    /// leaf entry, found by the offsets of the entries only
+    fn collect_index(
+        buffer: &[u8],
+        node: &ReaderIndexNode,
+        device: Option<&str>,
+        result: &mut Vec<(String, String)>,
+    ) -> Result<(), TsFileError> {
+        assert!(node
+            .children
+            .windows(2)
+            .all(|pair| pair[0].name < pair[1].name));
+        for (index, entry) in node.children.iter().enumerate() {
+            match node.node_type {
+                MetadataIndexNodeType::InternalDevice
+                | MetadataIndexNodeType::InternalMeasurement => {
+                    let child = read_index_node(buffer, entry.offset)?;
+                    // An internal entry is named after the first name below it
+                    assert_eq!(child.children[0].name, entry.name);
+                    collect_index(buffer, &child, device, result)?;
+                }
+                MetadataIndexNodeType::LeafDevice => {
+                    let child = read_index_node(buffer, entry.offset)?;
+                    collect_index(buffer, &child, Some(&entry.name), result)?;
+                }
+                MetadataIndexNodeType::LeafMeasurement => {
+                    let end = node.child_end_offset(index) as usize;
+                    let mut metadata = &buffer[entry.offset as usize..end];
+                    let first = TimeseriesMetadata::deserialize(&mut metadata)?;
+                    // A group of timeseries is named after its first one
+                    assert_eq!(first.measurement_id, entry.name);
+                    let device = device.expect("A measurement node below a device");
+                    result.push((device.to_owned(), first.measurement_id));
+                    while !metadata.is_empty() {
+                        let timeseries = TimeseriesMetadata::deserialize(&mut metadata)?;
+                        result.push((device.to_owned(), timeseries.measurement_id));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Searches the timeseries like IoTDB does, by binary search on every level
+    fn search_index(
+        buffer: &[u8],
+        root: &ReaderIndexNode,
+        device: &str,
+        measurement: &str,
+    ) -> Result<Option<TimeseriesMetadata>, TsFileError> {
+        let mut node = root.clone();
+        loop {
+            let (name, exact) = match node.node_type {
+                MetadataIndexNodeType::InternalDevice => (device, false),
+                MetadataIndexNodeType::LeafDevice => (device, true),
+                _ => (measurement, false),
+            };
+            let index = match node.find_child(name, exact) {
+                Some(index) => index,
+                None => return Ok(None),
+            };
+            let (start, end) = (
+                node.children[index].offset as usize,
+                node.child_end_offset(index) as usize,
+            );
+            if node.node_type == MetadataIndexNodeType::LeafMeasurement {
+                let mut metadata = &buffer[start..end];
+                while !metadata.is_empty() {
+                    let timeseries = TimeseriesMetadata::deserialize(&mut metadata)?;
+                    if timeseries.measurement_id == measurement {
+                        return Ok(Some(timeseries));
+                    }
+                }
+                return Ok(None);
+            }
+            node = ReaderIndexNode::deserialize(&mut &buffer[start..end])?;
+        }
+    }
+
+    #[test]
+    fn binary_search_every_path() -> Result<(), TsFileError> {
+        // Devices with a growing number of measurements, names that sort differently than
+        // their numbers
+        let devices: Vec<(String, Vec<String>)> = (0..40)
+            .map(|d| {
+                let measurements = (0..(d % 7) * 3 + 1).map(|m| format!("s{}", m)).collect();
+                (format!("root.sg.d{}", d), measurements)
+            })
+            .collect();
+        let paths: Vec<(&str, &str)> = devices
+            .iter()
+            .flat_map(|(device, measurements)| {
+                measurements
+                    .iter()
+                    .map(move |m| (device.as_str(), m.as_str()))
+            })
+            .collect();
+        for degree in [2, 3, 256] {
+            let mut schema = TsFileSchemaBuilder::new();
+            for (device, measurements) in &devices {
+                let mut device_builder = DeviceBuilder::new();
+                for measurement in measurements {
+                    device_builder.add(
+                        measurement,
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    );
+                }
+                schema.add(device, device_builder.build());
+            }
+            let config = TsFileConfig::default().max_degree_of_index_node(degree);
+            let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], config)?;
+            for (i, (device, measurement)) in paths.iter().enumerate() {
+                writer.write(device, measurement, 1, IoTDBValue::LONG(i as i64))?;
+            }
+            writer.close()?;
+            let buffer = writer.into_inner();
+
+            let root = TsFileReader::new(Cursor::new(buffer.clone()))?
+                .metadata()
+                .metadata_index
+                .clone();
+            let mut indexed = vec![];
+            collect_index(&buffer, &root, None, &mut indexed)?;
+            let mut sorted: Vec<(String, String)> = paths
+                .iter()
+                .map(|(device, measurement)| (device.to_string(), measurement.to_string()))
+                .collect();
+            sorted.sort();
+            assert_eq!(indexed, sorted);
+
+            for &(device, measurement) in &paths {
+                let timeseries = search_index(&buffer, &root, device, measurement)?
+                    .unwrap_or_else(|| panic!("{}.{} not found", device, measurement));
+                assert_eq!(timeseries.measurement_id, measurement);
+                assert_eq!(timeseries.statistics.count(), 1);
+            }
+            assert!(search_index(&buffer, &root, "root.sg.d40", "s0")?.is_none());
+            assert!(search_index(&buffer, &root, "root.sg.d1", "s9")?.is_none());
+            assert!(search_index(&buffer, &root, "root", "s0")?.is_none());
+        }
+        Ok(())
+    }
+
+    fn write_measurements(
+        measurements: &[String],
+        config: TsFileConfig,
+    ) -> Result<Vec<u8>, TsFileError> {
+        let mut device = DeviceBuilder::new();
+        for measurement in measurements {
+            device.add(
+                measurement,
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            );
+        }
+        let schema = TsFileSchemaBuilder::new().add("d1", device.build()).build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        for (i, measurement) in measurements.iter().enumerate() {
+            writer.write("d1", measurement, 1, IoTDBValue::LONG(i as i64))?;
+        }
+        writer.close()?;
+        Ok(writer.into_inner())
+    }
+
+    fn read_index_node(buffer: &[u8], start: i64) -> Result<ReaderIndexNode, TsFileError> {
+        ReaderIndexNode::deserialize(&mut &buffer[start as usize..])
+    }
+
+    #[test]
+    fn many_measurements() -> Result<(), TsFileError> {
+        let measurements: Vec<String> = (0..1000).map(|i| format!("s{:04}", i)).collect();
+        let buffer = write_measurements(&measurements, TsFileConfig::default())?;
+
+        // Same tree as the Java TsFileIOWriter: every entry of a leaf points to a group of
+        // 256 timeseries and is named after the first one
+        let mut reader = TsFileReader::new(Cursor::new(buffer.clone()))?;
+        let root = reader.metadata().metadata_index.clone();
+        assert_eq!(root.node_type, MetadataIndexNodeType::LeafDevice);
+        assert_eq!(root.children.len(), 1);
+        let device_node = read_index_node(&buffer, root.children[0].offset)?;
+        assert_eq!(
+            device_node.node_type,
+            MetadataIndexNodeType::LeafMeasurement
+        );
+        let names: Vec<&str> = device_node
+            .children
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["s0000", "s0256", "s0512", "s0768"]);
+
+        let metadata = reader
+            .metadata_reader()
+            .get_device_timeseries_metadata("d1")?;
+        assert_eq!(metadata.len(), 1000);
+        for (i, measurement) in measurements.iter().enumerate() {
+            let points = reader
+                .query("d1", measurement)?
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points, vec![(1, IoTDBValue::LONG(i as i64))]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_levels_of_internal_measurement_nodes() -> Result<(), TsFileError> {
+        let measurements: Vec<String> = (0..100).map(|i| format!("s{:03}", i)).collect();
+        // 25 groups of 4 timeseries in 7 leaves, 2 internal nodes below the root
+        let config = TsFileConfig::default().max_degree_of_index_node(4);
+        let buffer = write_measurements(&measurements, config)?;
+
+        let mut reader = TsFileReader::new(Cursor::new(buffer.clone()))?;
+        let root = reader.metadata().metadata_index.clone();
+        let mut node = read_index_node(&buffer, root.children[0].offset)?;
+        let mut levels = vec![];
+        while node.node_type == MetadataIndexNodeType::InternalMeasurement {
+            levels.push(node.children.len());
+            assert_eq!(node.children[0].name, "s000");
+            node = read_index_node(&buffer, node.children[0].offset)?;
+        }
+        assert_eq!(levels, vec![2, 4]);
+        assert_eq!(node.node_type, MetadataIndexNodeType::LeafMeasurement);
+
+        for (i, measurement) in measurements.iter().enumerate() {
+            let points = reader
+                .query("d1", measurement)?
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points, vec![(1, IoTDBValue::LONG(i as i64))]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+//! Deletions of already written data in a modification (`.mods`) file next to the TsFile.
+//!
+//! The file uses the text format of IoTDB, one deletion per line:
+//! `DELETION,{path},{file offset},{start time},{end time}`. Deletions apply to the chunks that
+//! start before the file offset, i.e. to all data that was written when the deletion was
+//! recorded. `TsFileReader::open` reads the modification file and skips deleted points.
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::writer::errors::TsFileError;
+use crate::writer::resource::TsFileResource;
+
+const DELETION: &str = "DELETION";
+const SEPARATOR: char = ',';
+
+/// Deletion of all points of the matching series with `start_time <= timestamp <= end_time`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deletion {
+    /// Full path of the series, e.g. `root.sg.d1.s1`. `*` matches a single level and `**`
+    /// any number of levels, e.g. `root.sg.d1.*` deletes all measurements of a device
+    pub path: String,
+    pub file_offset: i64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+impl Deletion {
+    pub fn new(path: &str, file_offset: i64, start_time: i64, end_time: i64) -> Deletion {
+        Deletion {
+            path: path.to_owned(),
+            file_offset,
+            start_time,
+            end_time,
+        }
+    }
+
+    /// Whether the deletion affects the series
+    pub fn matches(&self, device: &str, measurement: &str) -> bool {
+        let pattern: Vec<&str> = self.path.split('.').collect();
+        let path: Vec<&str> = device.split('.').chain(Some(measurement)).collect();
+        matches_levels(&pattern, &path)
+    }
+
+    /// Whether the deletion affects points of the chunk at the given offset
+    pub fn applies_to(&self, offset_of_chunk_header: i64) -> bool {
+        offset_of_chunk_header < self.file_offset
+    }
+
+    pub fn covers(&self, timestamp: i64) -> bool {
+        self.start_time <= timestamp && timestamp <= self.end_time
+    }
+
+    fn parse(line: &str) -> Result<Deletion, TsFileError> {
+        let fields: Vec<&str> = line.split(SEPARATOR).collect();
+        let number = |field: &str| {
+            field.trim().parse::<i64>().map_err(|_| TsFileError::Error {
+                source: Some(format!("Invalid modification: {}", line)),
+            })
+        };
+        match fields[..] {
+            [DELETION, path, file_offset, start_time, end_time] => Ok(Deletion::new(
+                path,
+                number(file_offset)?,
+                number(start_time)?,
+                number(end_time)?,
+            )),
+            // Files of older IoTDB versions only contain the end time
+            [DELETION, path, file_offset, end_time] => Ok(Deletion::new(
+                path,
+                number(file_offset)?,
+                i64::MIN,
+                number(end_time)?,
+            )),
+            _ => Err(TsFileError::UnsupportedFeature {
+                source: Some(format!("Modification {}", line)),
+            }),
+        }
+    }
+}
+
+fn matches_levels(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            matches_levels(&pattern[1..], path)
+                || (!path.is_empty() && matches_levels(pattern, &path[1..]))
+        }
+        (Some(&level), Some(&name)) if level == "*" || level == name => {
+            matches_levels(&pattern[1..], &path[1..])
+        }
+        _ => false,
+    }
+}
+
+/// The modification file of a TsFile
+///
+/// ```no_run
+/// use tsfile_writer::writer::modification::ModificationFile;
+///
+/// ModificationFile::new("data.tsfile")
+///     .delete("root.sg.d1.s1", 0, 1000)
+///     .unwrap();
+/// ```
+pub struct ModificationFile {
+    tsfile: String,
+}
+
+impl ModificationFile {
+    pub fn new(tsfile: &str) -> ModificationFile {
+        ModificationFile {
+            tsfile: tsfile.to_owned(),
+        }
+    }
+
+    /// Path of the modification file, the path of the TsFile with suffix `.mods`
+    pub fn path(&self) -> String {
+        format!("{}.mods", self.tsfile)
+    }
+
+    /// Deletes the points of the matching series in the time range (inclusive) from all data
+    /// that is in the TsFile now. The deletion is appended to the modification file and a
+    /// `.resource` file of the TsFile is updated to reference it.
+    pub fn delete(&self, path: &str, start_time: i64, end_time: i64) -> Result<(), TsFileError> {
+        let file_offset = std::fs::metadata(&self.tsfile)?.len() as i64;
+        self.write(&Deletion::new(path, file_offset, start_time, end_time))
+    }
+
+    /// Appends the deletion to the modification file
+    pub fn write(&self, deletion: &Deletion) -> Result<(), TsFileError> {
+        if deletion.path.contains(SEPARATOR) {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!("Invalid path {}", deletion.path)),
+            });
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        writeln!(
+            file,
+            "{}{}{}{}{}{}{}{}{}",
+            DELETION,
+            SEPARATOR,
+            deletion.path,
+            SEPARATOR,
+            deletion.file_offset,
+            SEPARATOR,
+            deletion.start_time,
+            SEPARATOR,
+            deletion.end_time
+        )?;
+        file.flush()?;
+
+        if Path::new(&TsFileResource::path(&self.tsfile)).exists() {
+            let mut resource = TsFileResource::read_file(&self.tsfile)?;
+            let name = Path::new(&self.path())
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            if resource.mod_file != name {
+                resource.mod_file = name;
+                resource.write_file(&self.tsfile)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All deletions in the order they were recorded, empty if there is no modification file
+    pub fn read(&self) -> Result<Vec<Deletion>, TsFileError> {
+        if !Path::new(&self.path()).exists() {
+            return Ok(vec![]);
+        }
+        let mut content = String::new();
+        File::open(self.path())?.read_to_string(&mut content)?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Deletion::parse)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::modification::{Deletion, ModificationFile};
+    use crate::writer::resource::TsFileResource;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriterBuilder;
+    use crate::writer::{IoTDBValue, TSDataType};
+
+    #[test]
+    fn matches() {
+        let deletion = |path: &str| Deletion::new(path, 0, 0, 0);
+        assert!(deletion("root.sg.d1.s1").matches("root.sg.d1", "s1"));
+        assert!(!deletion("root.sg.d1.s1").matches("root.sg.d1", "s2"));
+        assert!(deletion("root.sg.d1.*").matches("root.sg.d1", "s2"));
+        assert!(!deletion("root.sg.*").matches("root.sg.d1", "s2"));
+        assert!(deletion("root.**").matches("root.sg.d1", "s2"));
+        assert!(deletion("root.**.s2").matches("root.sg.d1", "s2"));
+        assert!(!deletion("root.**.s2").matches("root.sg.d1", "s1"));
+    }
+
+    #[test]
+    fn delete() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "root.sg.d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "root.sg.d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let path = "target/modification.tsfile";
+        let _ = std::fs::remove_file(ModificationFile::new(path).path());
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .write_resource(true)
+            .build(path)?;
+        for i in 0..100 {
+            writer.write("root.sg.d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("root.sg.d1", "s2", i, IoTDBValue::LONG(i))?;
+            writer.write("root.sg.d2", "s1", i, IoTDBValue::LONG(i))?;
+            if i == 49 {
+                writer.flush()?;
+            }
+        }
+        writer.close()?;
+
+        let mods = ModificationFile::new(path);
+        mods.delete("root.sg.d1.s1", 10, 19)?;
+        mods.delete("root.sg.*.s1", 90, 200)?;
+        // Covers a whole chunk
+        mods.delete("root.sg.d1.s2", 0, 49)?;
+        assert_eq!(mods.read()?.len(), 3);
+        assert_eq!(
+            TsFileResource::read_file(path)?.mod_file.as_deref(),
+            Some("modification.tsfile.mods")
+        );
+
+        let mut reader = TsFileReader::open(path)?;
+        assert_eq!(reader.deletions().len(), 3);
+        let mut timestamps = |device: &str, measurement: &str| -> Vec<i64> {
+            reader
+                .query(device, measurement)
+                .unwrap()
+                .map(|point| point.unwrap().0)
+                .collect()
+        };
+        let d1_s1: Vec<i64> = (0..10).chain(20..90).collect();
+        assert_eq!(timestamps("root.sg.d1", "s1"), d1_s1);
+        assert_eq!(
+            timestamps("root.sg.d1", "s2"),
+            (50..100).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps("root.sg.d2", "s1"), (0..90).collect::<Vec<_>>());
+
+        // Deletions only apply to data that was written before them
+        std::fs::write(
+            mods.path(),
+            "DELETION,root.sg.d1.s2,0,0,99\nDELETION,root.sg.d2.s1,1000000,0\n",
+        )?;
+        let mut reader = TsFileReader::open(path)?;
+        assert_eq!(reader.query("root.sg.d1", "s2")?.count(), 100);
+        assert_eq!(reader.query("root.sg.d2", "s1")?.count(), 99);
+        Ok(())
+    }
+}
@@ -1,154 +1,184 @@
+//! 128 bit MurmurHash3 (x64 variant) like `Murmur128Hash` of IoTDB, the hash of the bloom
+//! filter. It has to match the Java implementation bit for bit, otherwise IoTDB considers
+//! series that exist in a file as missing.
+//!
+//! ```
+//! use tsfile_writer::writer::murmur128::Murmur128;
+//!
+//! assert_eq!(Murmur128::hash("root.sg.d1.s1", 5), -1021017105);
+//! ```
+
+const C1: i64 = 0x87c3_7b91_1142_53d5_u64 as i64;
+const C2: i64 = 0x4cf5_ad43_2745_937f;
+
 pub struct Murmur128 {}
 
 impl Murmur128 {
-    /**
-     * get hashcode of value by seed
-     *
-     * @param value value
-     * @param seed seed
-     * @return hashcode of value
-     */
+    /// Hash of `Murmur128Hash.hash(String, int)`: the sum of both halves of the 128 bit hash of
+    /// the UTF-8 bytes, truncated to 32 bit.
+    ///
+    /// Like in Java the length is the number of UTF-16 code units of the value and not the
+    /// number of bytes, thus only a prefix of the bytes of non ASCII values is hashed.
     pub fn hash(value: &str, seed: i32) -> i32 {
-        return Self::inner_hash(
-            value.as_bytes(),
-            0,
-            value.as_bytes().len() as i32,
-            seed as i64,
-        ) as i32;
+        let length = value.encode_utf16().count();
+        let (h1, h2) = Self::inner_hash(value.as_bytes(), length, seed as i64);
+        h1.wrapping_add(h2) as i32
+    }
+
+    /// Both 64 bit halves of the 128 bit hash of the bytes
+    pub fn hash128(key: &[u8], seed: i64) -> (i64, i64) {
+        Self::inner_hash(key, key.len(), seed)
+    }
+
+    /// Little endian 64 bit block
+    fn get_block(key: &[u8], index: usize) -> i64 {
+        let mut block = [0_u8; 8];
+        block.copy_from_slice(&key[index * 8..index * 8 + 8]);
+        i64::from_le_bytes(block)
     }
 
-    /** Methods to perform murmur 128 hash. */
-    fn get_block(key: &[u8], offset: usize, index: i32) -> i64 {
-        let i8: usize = (index << 3) as usize;
-        let block_offset: usize = (offset + i8) as usize;
-        (key[block_offset] as i64 & 0xff)
-            + ((key[block_offset + 1] as i64 & 0xff) << 8)
-            + ((key[block_offset + 2] as i64 & 0xff) << 16)
-            + ((key[block_offset + 3] as i64 & 0xff) << 24)
-            + ((key[block_offset + 4] as i64 & 0xff) << 32)
-            + ((key[block_offset + 5] as i64 & 0xff) << 40)
-            + ((key[block_offset + 6] as i64 & 0xff) << 48)
-            + ((key[block_offset + 7] as i64 & 0xff) << 56)
+    fn mix_k1(k1: i64) -> i64 {
+        k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
     }
 
-    fn rotl64(v: i64, n: i64) -> i64 {
-        (v << n) | ((v as u64) >> (64 - n)) as i64
+    fn mix_k2(k2: i64) -> i64 {
+        k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
     }
 
-    #[allow(overflowing_literals)]
     fn fmix(mut k: i64) -> i64 {
         k ^= ((k as u64) >> 33) as i64;
-        k = ((k as i128) * 0xff51afd7ed558ccd) as i64;
+        k = k.wrapping_mul(0xff51_afd7_ed55_8ccd_u64 as i64);
         k ^= ((k as u64) >> 33) as i64;
-        k = ((k as i128) * 0xc4ceb9fe1a85ec53) as i64;
+        k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53_u64 as i64);
         k ^= ((k as u64) >> 33) as i64;
         k
     }
 
-    #[allow(overflowing_literals, arithmetic_overflow)]
-    fn inner_hash(key: &[u8], mut offset: usize, length: i32, seed: i64) -> i64 {
-        let nblocks = length >> 4; // Process as 128-bit blocks.
+    /// Hashes the first `length` bytes of the key, Java arithmetic on longs wraps around
+    fn inner_hash(key: &[u8], length: usize, seed: i64) -> (i64, i64) {
         let mut h1 = seed;
         let mut h2 = seed;
-        let c1 = 0x87c37b91114253d5;
-        let c2 = 0x4cf5ad432745937f;
-        // ----------
-        // body
+
+        // Body of 128 bit blocks
+        let nblocks = length >> 4;
         for i in 0..nblocks {
-            let mut k1 = Self::get_block(key, offset, i * 2);
-            let mut k2 = Self::get_block(key, offset, i * 2 + 1);
-            k1 *= c1;
-            k1 = Self::rotl64(k1, 31);
-            k1 *= c2;
-            h1 ^= k1;
-            h1 = Self::rotl64(h1, 27);
-            h1 += h2;
-            h1 = h1 * 5 + 0x52dce729;
-            k2 *= c2;
-            k2 = Self::rotl64(k2, 33);
-            k2 *= c1;
-            h2 ^= k2;
-            h2 = Self::rotl64(h2, 31);
-            h2 += h1;
-            h2 = h2 * 5 + 0x38495ab5;
+            h1 ^= Self::mix_k1(Self::get_block(key, i * 2));
+            h1 = h1
+                .rotate_left(27)
+                .wrapping_add(h2)
+                .wrapping_mul(5)
+                .wrapping_add(0x52dc_e729);
+            h2 ^= Self::mix_k2(Self::get_block(key, i * 2 + 1));
+            h2 = h2
+                .rotate_left(31)
+                .wrapping_add(h1)
+                .wrapping_mul(5)
+                .wrapping_add(0x3849_5ab5);
+        }
+
+        // Tail, the bytes are signed in Java and thus sign extended
+        let tail = &key[nblocks * 16..length];
+        let byte = |index: usize| tail[index] as i8 as i64;
+        let mut k1 = 0_i64;
+        let mut k2 = 0_i64;
+        for index in (8..tail.len()).rev() {
+            k2 ^= byte(index) << ((index - 8) * 8);
+        }
+        if tail.len() > 8 {
+            h2 ^= Self::mix_k2(k2);
         }
-        // ----------
-        // tail
-        // Advance offset to the unprocessed tail of the data.
-        offset += nblocks as usize * 16;
-        let mut k1 = 0;
-        let mut k2 = 0;
-        let mut identifier = length & 15;
-        // Dirty trick to simulate fallthrough in javas case
-        while identifier > 0 {
-            match identifier {
-                15 => {
-                    k2 ^= (key[offset + 14] as i64) << 48;
-                }
-                14 => {
-                    k2 ^= (key[offset + 13] as i64) << 40;
-                }
-                13 => {
-                    k2 ^= (key[offset + 12] as i64) << 32;
-                }
-                12 => {
-                    k2 ^= (key[offset + 11] as i64) << 24;
-                }
-                11 => {
-                    k2 ^= (key[offset + 10] as i64) << 16;
-                }
-                10 => {
-                    k2 ^= (key[offset + 9] as i64) << 8;
-                }
-                9 => {
-                    k2 ^= key[offset + 8] as i64;
-                    k2 = ((k2 as i128) * (c2 as i128)) as i64;
-                    k2 = Self::rotl64(k2, 33);
-                    k2 = ((k2 as i128) * (c1 as i128)) as i64;
-                    h2 ^= k2;
-                }
-                8 => {
-                    k1 ^= (key[offset + 7] as i64) << 56;
-                }
-                7 => {
-                    k1 ^= (key[offset + 6] as i64) << 48;
-                }
-                6 => {
-                    k1 ^= (key[offset + 5] as i64) << 40;
-                }
-                5 => {
-                    k1 ^= (key[offset + 4] as i64) << 32;
-                }
-                4 => {
-                    k1 ^= (key[offset + 3] as i64) << 24;
-                }
-                3 => {
-                    k1 ^= (key[offset + 2] as i64) << 16;
-                }
-                2 => {
-                    k1 ^= (key[offset + 1] as i64) << 8;
-                }
-                1 => {
-                    k1 ^= key[offset] as i64;
-                    k1 = ((k1 as i128) * (c1 as i128)) as i64;
-                    k1 = Self::rotl64(k1, 31);
-                    k1 = ((k1 as i128) * (c2 as i128)) as i64;
-                    h1 ^= k1;
-                }
-                _ => {}
-            }
-            identifier -= 1
+        for index in (0..tail.len().min(8)).rev() {
+            k1 ^= byte(index) << (index * 8);
         }
-        // ----------
-        // finalization
+        if !tail.is_empty() {
+            h1 ^= Self::mix_k1(k1);
+        }
+
+        // Finalization
         h1 ^= length as i64;
         h2 ^= length as i64;
-        h1 += h2;
-        h2 += h1;
+        h1 = h1.wrapping_add(h2);
+        h2 = h2.wrapping_add(h1);
         h1 = Self::fmix(h1);
         h2 = Self::fmix(h2);
-        h1 = ((h1 as i128) + (h2 as i128)) as i64;
-        h2 = ((h2 as i128) + (h1 as i128)) as i64;
-        h1.overflowing_add(h2).0
+        h1 = h1.wrapping_add(h2);
+        h2 = h2.wrapping_add(h1);
+        (h1, h2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::murmur128::Murmur128;
+
+    /// Generated with `Murmur128Hash.hash(value, seed)` of IoTDB
+    const JAVA_HASHES: [(&str, i32, i32); 40] = [
+        ("", 0, 0),
+        ("", 5, -1795273567),
+        ("", 59, -523148402),
+        ("", -1, -423762788),
+        ("a", 0, 1197998563),
+        ("a", 5, 623062375),
+        ("a", 59, 812441596),
+        ("a", -1, -1944236869),
+        ("root.sg.d1.s1", 0, -1689404043),
+        ("root.sg.d1.s1", 5, -1021017105),
+        ("root.sg.d1.s1", 59, -1238114800),
+        ("root.sg.d1.s1", -1, 797507648),
+        ("root.sg.d1.temperature", 0, -1871757455),
+        ("root.sg.d1.temperature", 5, -1064258112),
+        ("root.sg.d1.temperature", 59, 1527012414),
+        ("root.sg.d1.temperature", -1, -1387872854),
+        ("root.sg.d1.s1.0123456789abcdef", 0, 1832037911),
+        ("root.sg.d1.s1.0123456789abcdef", 5, -846045594),
+        ("root.sg.d1.s1.0123456789abcdef", 59, -1261175664),
+        ("root.sg.d1.s1.0123456789abcdef", -1, -1763495063),
+        ("d1.s1", 0, 497697570),
+        ("d1.s1", 5, -1349307291),
+        ("d1.s1", 59, 873111754),
+        ("d1.s1", -1, -846178421),
+        ("ü", 0, -2048911157),
+        ("ü", 5, -1851878415),
+        ("ü", 59, 16205277),
+        ("ü", -1, -1097953963),
+        ("root.sg.Wärme.s1", 0, 2111430297),
+        ("root.sg.Wärme.s1", 5, 1848335300),
+        ("root.sg.Wärme.s1", 59, 2062817916),
+        ("root.sg.Wärme.s1", -1, -2004811477),
+        ("root.传感器.温度", 0, -1594305419),
+        ("root.传感器.温度", 5, 1638918256),
+        ("root.传感器.温度", 59, 573365187),
+        ("root.传感器.温度", -1, 310139844),
+        ("x😀y", 0, -1681262884),
+        ("x😀y", 5, -1246001042),
+        ("x😀y", 59, -90269414),
+        ("x😀y", -1, -365203838),
+    ];
+
+    #[test]
+    fn java_parity() {
+        for (value, seed, expected) in JAVA_HASHES {
+            assert_eq!(
+                Murmur128::hash(value, seed),
+                expected,
+                "{:?} with seed {}",
+                value,
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn hash128() {
+        assert_eq!(Murmur128::hash128(b"", 0), (0, 0));
+        assert_eq!(
+            Murmur128::hash128(b"root.sg.d1.s1", 0),
+            (8435427582084048221, -1216728298216302568)
+        );
+        // One full block and a tail of one byte
+        assert_eq!(
+            Murmur128::hash128(b"0123456789abcdef0", 0),
+            (-1502884478548852619, 8357388652349827274)
+        );
     }
 }
@@ -0,0 +1,394 @@
+//! Writer that routes points into one TsFile per time partition (and device group), like the
+//! time partitions of IoTDB.
+//!
+//! Partitions start at multiples of the partition interval. Files are written to
+//! `{directory}/{group}/{partition}/{creation time}-{version}-0-0.tsfile`, the group directory
+//! is omitted if the devices are not grouped. As several partitions are open at the same time,
+//! bulk backfills do not need to be sorted by time across partitions.
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, File};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::writer::errors::TsFileError;
+use crate::writer::timestamp::Timestamp;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::{create_file, sync_handle, DataPoint, TsFileWriter};
+use crate::writer::{IoTDBValue, Schema, WriteWrapper};
+
+/// Maps a device to the name of its group
+type GroupBy = Box<dyn Fn(&str) -> String>;
+
+/// Writes points to one TsFile per time partition and device group
+///
+/// ```no_run
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+/// use tsfile_writer::writer::partitioned_writer::TimePartitionedWriter;
+/// use tsfile_writer::writer::{Schema, TSDataType};
+///
+/// let schema = Schema::simple(
+///     "root.sg1.d1",
+///     "s1",
+///     TSDataType::INT64,
+///     TSEncoding::PLAIN,
+///     CompressionType::UNCOMPRESSED,
+/// );
+/// // One file per day, days that are two days behind the newest point are sealed
+/// let day = 24 * 60 * 60 * 1000;
+/// let mut writer =
+///     TimePartitionedWriter::new("target/partitioned", schema, Default::default(), day);
+/// writer
+///     .active_window(2 * day)
+///     .group_by(|device| device.rsplit_once('.').map_or(device, |(group, _)| group).to_owned());
+/// writer.write("root.sg1.d1", "s1", 1, 13_i64).unwrap();
+/// let files = writer.close().unwrap();
+/// ```
+pub struct TimePartitionedWriter<'a> {
+    directory: PathBuf,
+    schema: Schema<'a>,
+    config: TsFileConfig,
+    interval: i64,
+    active_window: Option<i64>,
+    group_by: Option<GroupBy>,
+    /// Group of every device, computed once per device
+    groups: BTreeMap<&'a str, String>,
+    open: BTreeMap<(String, i64), TsFileWriter<'a, WriteWrapper<File>>>,
+    newest: Option<i64>,
+    version: u64,
+    files: Vec<PathBuf>,
+}
+
+impl<'a> TimePartitionedWriter<'a> {
+    /// Files are created below the given directory, partitions start at multiples of the
+    /// interval (in the unit of the timestamps, e.g. `7 * 24 * 60 * 60 * 1000` for the default
+    /// of IoTDB)
+    pub fn new(
+        directory: impl Into<PathBuf>,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+        interval: i64,
+    ) -> TimePartitionedWriter<'a> {
+        TimePartitionedWriter {
+            directory: directory.into(),
+            schema,
+            config,
+            interval,
+            active_window: None,
+            group_by: None,
+            groups: BTreeMap::new(),
+            open: BTreeMap::new(),
+            newest: None,
+            version: 0,
+            files: vec![],
+        }
+    }
+
+    /// Seals partitions that end this much before the newest point written so far (in the
+    /// unit of the timestamps). A later point of a sealed partition starts another file of
+    /// the partition. Without a window all partitions stay open until `close`.
+    pub fn active_window(&mut self, window: i64) -> &mut Self {
+        self.active_window = Some(window);
+        self
+    }
+
+    /// Writes the devices of each group into their own files, e.g. grouped by the database
+    /// (storage group) of the device path. Files of a group only contain its devices.
+    pub fn group_by(&mut self, group: impl Fn(&str) -> String + 'static) -> &mut Self {
+        self.group_by = Some(Box::new(group));
+        self
+    }
+
+    pub fn write(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamp: impl Timestamp,
+        value: impl Into<IoTDBValue>,
+    ) -> Result<(), TsFileError> {
+        let timestamp = timestamp.into_timestamp(self.config.timestamp_precision)?;
+        self.writer(device, timestamp)?
+            .write(device, measurement_id, timestamp, value)?;
+        self.seal_inactive(timestamp)
+    }
+
+    pub fn write_many(
+        &mut self,
+        device: &'a str,
+        timestamp: impl Timestamp,
+        values: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        let timestamp = timestamp.into_timestamp(self.config.timestamp_precision)?;
+        self.writer(device, timestamp)?
+            .write_many(device, timestamp, values)?;
+        self.seal_inactive(timestamp)
+    }
+
+    /// Closes all open partitions and returns the paths of all written files in the order
+    /// they were started
+    pub fn close(&mut self) -> Result<Vec<PathBuf>, TsFileError> {
+        let mut result = Ok(());
+        for (_, mut writer) in std::mem::take(&mut self.open) {
+            if let Err(e) = writer.close() {
+                result = result.and(Err(e));
+            }
+        }
+        result.map(|_| self.files.clone())
+    }
+
+    /// Paths of all files that were started so far, some of them may still be open
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Number of partitions that have an open file
+    pub fn open_partitions(&self) -> usize {
+        self.open.len()
+    }
+
+    fn writer(
+        &mut self,
+        device: &'a str,
+        timestamp: i64,
+    ) -> Result<&mut TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        if self.interval <= 0 {
+            return Err(TsFileError::IllegalState {
+                source: Some("The partition interval has to be positive".to_owned()),
+            });
+        }
+        if !self.schema.measurement_groups.contains_key(device) {
+            return Err(TsFileError::SchemaError {
+                source: Some(format!("Unknown device {}", device)),
+            });
+        }
+        let group = self.group(device);
+        let key = (group, timestamp.div_euclid(self.interval));
+        if !self.open.contains_key(&key) {
+            let writer = self.create_writer(&key.0, key.1)?;
+            self.open.insert(key.clone(), writer);
+        }
+        match self.open.get_mut(&key) {
+            Some(writer) => Ok(writer),
+            None => Err(TsFileError::IllegalState {
+                source: Some("No open file".to_owned()),
+            }),
+        }
+    }
+
+    fn group(&mut self, device: &'a str) -> String {
+        if let Some(group) = self.groups.get(device) {
+            return group.clone();
+        }
+        let group = match &self.group_by {
+            Some(group_by) => group_by(device),
+            None => String::new(),
+        };
+        self.groups.insert(device, group.clone());
+        group
+    }
+
+    fn create_writer(
+        &mut self,
+        group: &str,
+        partition: i64,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        let schema = match &self.group_by {
+            Some(group_by) => Schema {
+                measurement_groups: self
+                    .schema
+                    .measurement_groups
+                    .iter()
+                    .filter(|(device, _)| group_by(device) == group)
+                    .map(|(device, measurements)| (*device, measurements.clone()))
+                    .collect(),
+            },
+            None => self.schema.clone(),
+        };
+        let directory = self.directory.join(group).join(partition.to_string());
+        create_dir_all(&directory)?;
+        self.version += 1;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0);
+        let path = directory.join(format!("{}-{}-0-0.tsfile", millis, self.version));
+
+        let file = create_file(&path, &self.config)?;
+        let sync_file = Some(sync_handle(&file)?);
+        let mut writer =
+            TsFileWriter::new_from_writer(schema, WriteWrapper::new(file), self.config)?;
+        writer.filename = path.to_string_lossy().into_owned();
+        writer.file_io_writer.sync_file = sync_file;
+        self.files.push(path);
+        Ok(writer)
+    }
+
+    /// Closes the partitions that ended more than the active window before the newest point
+    fn seal_inactive(&mut self, timestamp: i64) -> Result<(), TsFileError> {
+        let newest = match self.newest {
+            Some(newest) if newest >= timestamp => return Ok(()),
+            _ => timestamp,
+        };
+        self.newest = Some(newest);
+        let window = match self.active_window {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        let interval = self.interval;
+        let inactive: Vec<_> = self
+            .open
+            .keys()
+            .filter(|(_, partition)| {
+                (partition.saturating_add(1)).saturating_mul(interval)
+                    <= newest.saturating_sub(window)
+            })
+            .cloned()
+            .collect();
+        for key in inactive {
+            if let Some(mut writer) = self.open.remove(&key) {
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::partitioned_writer::TimePartitionedWriter;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::DataPoint;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::path::Path;
+
+    fn schema() -> Schema<'static> {
+        let device = || {
+            DeviceBuilder::new()
+                .add(
+                    "s1",
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build()
+        };
+        TsFileSchemaBuilder::new()
+            .add("root.sg1.d1", device())
+            .add("root.sg2.d1", device())
+            .build()
+    }
+
+    fn writer(directory: &str) -> TimePartitionedWriter<'static> {
+        let _ = std::fs::remove_dir_all(directory);
+        TimePartitionedWriter::new(directory, schema(), Default::default(), 100)
+    }
+
+    fn times(file: &Path, device: &str) -> Vec<i64> {
+        let mut reader = TsFileReader::open(file.to_str().unwrap()).unwrap();
+        reader
+            .query(device, "s1")
+            .map(|points| points.map(|point| point.unwrap().0).collect())
+            .unwrap_or_default()
+    }
+
+    fn relative(directory: &str, file: &Path) -> String {
+        let parent = file.parent().unwrap().strip_prefix(directory).unwrap();
+        parent.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn unordered_partitions() -> Result<(), TsFileError> {
+        let directory = "target/partitioned_unordered";
+        let mut writer = writer(directory);
+        // Backfill that alternates between partitions
+        for i in 0..100 {
+            writer.write("root.sg1.d1", "s1", i + 200, IoTDBValue::LONG(i))?;
+            writer.write_many(
+                "root.sg1.d1",
+                i - 100,
+                vec![DataPoint::new("s1", IoTDBValue::LONG(i))],
+            )?;
+        }
+        assert_eq!(writer.open_partitions(), 2);
+        let files = writer.close()?;
+        assert_eq!(files.len(), 2);
+        assert_eq!(relative(directory, &files[0]), "2");
+        assert_eq!(relative(directory, &files[1]), "-1");
+        assert_eq!(
+            times(&files[0], "root.sg1.d1"),
+            (200..300).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            times(&files[1], "root.sg1.d1"),
+            (-100..0).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn active_window_and_groups() -> Result<(), TsFileError> {
+        let directory = "target/partitioned_window";
+        let mut writer = writer(directory);
+        writer
+            .active_window(50)
+            .group_by(|device| device.rsplit_once('.').unwrap().0.to_owned());
+        for i in 0..150_i64 {
+            writer.write("root.sg1.d1", "s1", i, i)?;
+            writer.write("root.sg2.d1", "s1", i, i)?;
+        }
+        // Partition 0 is sealed once a point reaches 150
+        assert_eq!(writer.open_partitions(), 4);
+        writer.write("root.sg1.d1", "s1", 150, 150_i64)?;
+        assert_eq!(writer.open_partitions(), 2);
+        // A late point of a sealed partition starts another file
+        writer.write("root.sg2.d1", "s1", 10, 10_i64)?;
+
+        let files = writer.close()?;
+        let paths: Vec<_> = files.iter().map(|file| relative(directory, file)).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "root.sg1/0",
+                "root.sg2/0",
+                "root.sg1/1",
+                "root.sg2/1",
+                "root.sg2/0"
+            ]
+        );
+        assert_eq!(
+            times(&files[0], "root.sg1.d1"),
+            (0..100).collect::<Vec<_>>()
+        );
+        // Files of a group only contain its devices
+        assert!(times(&files[0], "root.sg2.d1").is_empty());
+        assert_eq!(
+            times(&files[3], "root.sg2.d1"),
+            (100..150).collect::<Vec<_>>()
+        );
+        assert_eq!(times(&files[4], "root.sg2.d1"), vec![10]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let mut writer = writer("target/partitioned_errors");
+        assert!(matches!(
+            writer.write("root.sg3.d1", "s1", 1, 1_i64),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        let mut writer = TimePartitionedWriter::new(
+            "target/partitioned_errors",
+            schema(),
+            Default::default(),
+            0,
+        );
+        assert!(matches!(
+            writer.write("root.sg1.d1", "s1", 1, 1_i64),
+            Err(TsFileError::IllegalState { .. })
+        ));
+    }
+}
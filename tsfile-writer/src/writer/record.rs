@@ -0,0 +1,89 @@
+//! Rows of a device, either as `TSRecord` like in the Java API of TsFile or as structs, usually
+//! with `#[derive(TsRecord)]` of the feature `derive`
+use crate::writer::errors::TsFileError;
+use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+use crate::writer::{IoTDBValue, MeasurementGroup, PositionedWrite};
+
+#[cfg(feature = "derive")]
+pub use tsfile_derive::TsRecord;
+
+/// A struct with one field per measurement and a timestamp field. The derive macro maps the
+/// field types `i32`, `i64`, `f32`, `f64` and `String` to the data types `INT32`, `INT64`,
+/// `FLOAT`, `DOUBLE` and `TEXT`, fields of type `Option` are null if they are `None`.
+///
+/// ```ignore
+/// #[derive(TsRecord)]
+/// #[tsfile(aligned)]
+/// struct Telemetry {
+///     #[tsfile(timestamp)]
+///     time: i64,
+///     #[tsfile(encoding = "GORILLA", compression = "SNAPPY")]
+///     temperature: f64,
+///     #[tsfile(name = "hum")]
+///     humidity: Option<f32>,
+///     #[tsfile(skip)]
+///     received: std::time::Instant,
+/// }
+/// ```
+///
+/// Without attributes integers are encoded with `TS2DIFF`, floating point values with `GORILLA`
+/// and texts with `PLAIN`, none of them are compressed.
+pub trait TsRecord {
+    /// The measurements of the device, e.g. for `TsFileSchemaBuilder::add`
+    fn measurement_group() -> MeasurementGroup<'static>;
+
+    /// Writes the struct as one row of the device
+    fn write_record<'a, T: PositionedWrite>(
+        &self,
+        writer: &mut TsFileWriter<'a, T>,
+        device: &'a str,
+    ) -> Result<(), TsFileError>;
+}
+
+/// The points of one device at the same timestamp, written with `TsFileWriter::write_record`.
+/// Mirrors the `TSRecord` of the Java API, `add_point` corresponds to `addTuple`.
+///
+/// ```
+/// # use tsfile_writer::writer::compression::CompressionType;
+/// # use tsfile_writer::writer::encoding::TSEncoding;
+/// # use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+/// # use tsfile_writer::writer::{Schema, TSDataType};
+/// use tsfile_writer::writer::record::TSRecord;
+/// use tsfile_writer::writer::IoTDBValue;
+///
+/// # let schema = Schema::simple("d1", "s1", TSDataType::INT64, TSEncoding::PLAIN, CompressionType::UNCOMPRESSED);
+/// # let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+/// let mut record = TSRecord::new(1, "d1");
+/// record.add("s1", IoTDBValue::LONG(13));
+/// writer.write_record(record).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TSRecord<'a> {
+    pub timestamp: i64,
+    pub device: &'a str,
+    pub points: Vec<DataPoint<'a>>,
+}
+
+impl<'a> TSRecord<'a> {
+    pub fn new(timestamp: i64, device: &'a str) -> TSRecord<'a> {
+        TSRecord {
+            timestamp,
+            device,
+            points: vec![],
+        }
+    }
+
+    pub fn add_point(&mut self, point: DataPoint<'a>) -> &mut TSRecord<'a> {
+        self.points.push(point);
+        self
+    }
+
+    /// Adds the value of a measurement, primitive values convert into `IoTDBValue`
+    pub fn add(
+        &mut self,
+        measurement_id: &'a str,
+        value: impl Into<IoTDBValue>,
+    ) -> &mut TSRecord<'a> {
+        self.add_point(DataPoint::new(measurement_id, value.into()))
+    }
+}
@@ -0,0 +1,568 @@
+//! Recovery of TsFiles that were not closed, e.g. because the process died before `close`.
+//! Like the `RestorableTsFileIOWriter` of Apache IoTDB the data section is scanned up to the
+//! last complete chunk, the remainder is truncated and the writer continues after it.
+use crate::reader::chunk_reader::ChunkReader;
+use crate::reader::layout::{
+    invalid, is_chunk_header, unexpected_marker, CHUNK_GROUP_HEADER, SEPARATOR,
+};
+use crate::reader::tsfile_reader::TsFileReader;
+use crate::writer::errors::TsFileError;
+use crate::writer::schema::schema_error;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_io_writer::TsFileIoWriter;
+use crate::writer::tsfile_writer::{sync_handle, temp_path, TsFileWriter};
+use crate::writer::utils::{read_byte, read_str};
+use crate::writer::wal::{wal_path, WriteAheadLog};
+use crate::writer::{
+    ChunkGroupMetadata, ChunkMetadata, Schema, Statistics, WriteWrapper, TIME_SERIES_MASK,
+    VALUE_SERIES_MASK,
+};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Magic string and version
+const FILE_HEADER_SIZE: u64 = 7;
+
+impl<'a> TsFileWriter<'a, WriteWrapper<File>> {
+    /// Continues writing a file that was not closed. All complete chunks are kept, an incomplete
+    /// chunk at the end of the file (and the metadata section, if it is incomplete) is truncated.
+    /// Later points of a series have to be newer than the last point in the file.
+    ///
+    /// The last chunk group is kept even if it may be missing some of its chunks. With
+    /// `TsFileConfig::write_ahead_log` the points of the log that are newer than the file are
+    /// written again (see `TsFileWriter::replay`), so they are buffered like before the crash.
+    ///
+    /// Fails if the file is complete, if it is no TsFile or if data types or the alignment of
+    /// the series in the file differ from the schema. A file shorter than the file header is
+    /// started anew.
+    pub fn recover(
+        filename: &'a str,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        let mut writer = TsFileWriter::recover_file(filename, schema, config)?;
+        if config.write_ahead_log {
+            // Replayed points are in the log already
+            let (wal, records) = WriteAheadLog::open(&wal_path(filename))?;
+            let replayed = writer.replay(records)?;
+            log::info!("Replayed {} points of the write ahead log", replayed);
+            writer.wal = Some(wal);
+        }
+        Ok(writer)
+    }
+
+    fn recover_file(
+        filename: &'a str,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        config.validate()?;
+        // With `temp_file` the unfinished file still has its temporary name
+        let path = if config.temp_file {
+            temp_path(Path::new(filename))
+        } else {
+            PathBuf::from(filename)
+        };
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let length = file.metadata()?.len();
+        if length < FILE_HEADER_SIZE {
+            return TsFileWriter::create(filename, schema, config);
+        }
+        if TsFileReader::new(&file).is_ok() {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!("File {} is complete, nothing to recover", filename)),
+            });
+        }
+        let (chunk_group_metadata_list, end) = scan(&mut BufReader::new(&file), length)?;
+        check_schema(&chunk_group_metadata_list, &schema)?;
+        log::info!(
+            "Recovered {} chunk groups of {}, truncating {} bytes",
+            chunk_group_metadata_list.len(),
+            filename,
+            length - end
+        );
+
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+        let sync_file = Some(sync_handle(&file)?);
+        let last_times = last_times(&chunk_group_metadata_list);
+        let io_writer = TsFileIoWriter::resume(
+            WriteWrapper {
+                position: end,
+                writer: file,
+            },
+            config,
+            chunk_group_metadata_list,
+        );
+        let mut writer = TsFileWriter::with_io_writer(schema, io_writer, config)?;
+        writer.filename = filename.to_owned();
+        writer.file_io_writer.sync_file = sync_file;
+        for (device, measurement_id, timestamp) in last_times {
+            writer.restore_last_time(&device, &measurement_id, timestamp);
+        }
+        Ok(writer)
+    }
+}
+
+/// Truncates the file at `end` and writes the metadata section of the chunk groups after it,
+/// see `repair_file`
+pub(crate) fn write_metadata(
+    mut file: File,
+    end: u64,
+    config: TsFileConfig,
+    chunk_group_metadata_list: Vec<ChunkGroupMetadata>,
+) -> Result<(), TsFileError> {
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end))?;
+    let sync_file = Some(sync_handle(&file)?);
+    let mut io_writer = TsFileIoWriter::resume(
+        WriteWrapper {
+            position: end,
+            writer: file,
+        },
+        config,
+        chunk_group_metadata_list,
+    );
+    io_writer.sync_file = sync_file;
+    io_writer.end_file()
+}
+
+/// Reads the chunk groups of a file without metadata section. Returns the chunk groups and the
+/// end of the last complete chunk, scanning stops at the first chunk that cannot be read.
+pub(crate) fn scan<R: Read + Seek>(
+    reader: &mut R,
+    length: u64,
+) -> Result<(Vec<ChunkGroupMetadata>, u64), TsFileError> {
+    let data_section = scan_data_section(reader, length)?;
+    Ok((data_section.chunk_groups, data_section.end))
+}
+
+/// Chunk groups of the data section of a file, see `scan_data_section`
+pub(crate) struct DataSection {
+    pub(crate) chunk_groups: Vec<ChunkGroupMetadata>,
+    /// End of the last complete chunk
+    pub(crate) end: u64,
+    /// Offset of the last chunk group with chunks that is complete
+    pub(crate) last_chunk_group: Option<u64>,
+    /// End of the last complete chunk group, i.e. one that is followed by another chunk group,
+    /// the metadata section or the end of the file
+    pub(crate) chunk_group_end: u64,
+    /// Offset of the marker of the metadata section, if the scan reached it
+    pub(crate) separator: Option<u64>,
+    /// Why the scan stopped before the metadata section or the end of the file
+    pub(crate) error: Option<TsFileError>,
+}
+
+/// Scans the data section chunk by chunk up to the metadata section or the end of the file.
+/// Scanning stops at the first chunk group or chunk that cannot be read.
+pub(crate) fn scan_data_section<R: Read + Seek>(
+    reader: &mut R,
+    length: u64,
+) -> Result<DataSection, TsFileError> {
+    let mut magic = [0_u8; 6];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut magic)?;
+    if &magic != b"TsFile" {
+        return Err(TsFileError::Error {
+            source: Some("File does not start with the magic string of a TsFile".to_owned()),
+        });
+    }
+
+    let mut chunk_groups = vec![];
+    let mut current: Option<ChunkGroupMetadata> = None;
+    let mut current_offset = FILE_HEADER_SIZE;
+    let mut last_chunk_group = None;
+    // Timestamps of the aligned values of the current chunk group
+    let mut time_chunk: Option<ChunkReader> = None;
+    let mut end = FILE_HEADER_SIZE;
+    let mut chunk_group_end = FILE_HEADER_SIZE;
+    let mut separator = None;
+    let mut error = None;
+    let mut offset = FILE_HEADER_SIZE;
+    while offset < length {
+        reader.seek(SeekFrom::Start(offset))?;
+        match read_byte(reader)? {
+            CHUNK_GROUP_HEADER => {
+                // All chunks of the previous chunk group are complete
+                if has_chunks(&current) {
+                    last_chunk_group = Some(current_offset);
+                }
+                chunk_group_end = offset;
+                let device_id = match read_str(reader) {
+                    Ok(device_id) => device_id,
+                    Err(e) => {
+                        error = Some(invalid(offset as i64, e));
+                        break;
+                    }
+                };
+                chunk_groups.extend(current.take());
+                current = Some(ChunkGroupMetadata::new(device_id, vec![]));
+                current_offset = offset;
+                time_chunk = None;
+            }
+            marker if is_chunk_header(marker) => {
+                let chunk_group = match current.as_mut() {
+                    Some(chunk_group) => chunk_group,
+                    None => {
+                        error = Some(unexpected_marker(marker, offset as i64));
+                        break;
+                    }
+                };
+                let chunk = match ChunkReader::read(reader, offset as i64) {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        error = Some(invalid(offset as i64, e));
+                        break;
+                    }
+                };
+                let statistics = match chunk_statistics(&chunk, time_chunk.as_ref()) {
+                    Ok(statistics) => statistics,
+                    Err(e) => {
+                        error = Some(invalid(offset as i64, e));
+                        break;
+                    }
+                };
+                let header = chunk.header();
+                chunk_group.chunk_metadata.push(ChunkMetadata::new(
+                    header.measurement_id.clone(),
+                    header.data_type,
+                    offset,
+                    statistics,
+                    header.chunk_type & (TIME_SERIES_MASK | VALUE_SERIES_MASK),
+                ));
+                if header.is_time_chunk() {
+                    time_chunk = Some(chunk);
+                }
+                end = reader.stream_position()?;
+            }
+            SEPARATOR => {
+                separator = Some(offset);
+                break;
+            }
+            // Garbage, e.g. of an interrupted write
+            marker => {
+                error = Some(unexpected_marker(marker, offset as i64));
+                break;
+            }
+        }
+        offset = reader.stream_position()?;
+    }
+    if error.is_none() {
+        // The last chunk group ends with the metadata section or the file
+        if has_chunks(&current) {
+            last_chunk_group = Some(current_offset);
+        }
+        chunk_group_end = end;
+    }
+    chunk_groups.extend(current);
+    // Empty chunk groups are not part of the metadata, like in `end_chunk_group`
+    chunk_groups.retain(|chunk_group| !chunk_group.chunk_metadata.is_empty());
+    Ok(DataSection {
+        chunk_groups,
+        end,
+        last_chunk_group,
+        chunk_group_end,
+        separator,
+        error,
+    })
+}
+
+fn has_chunks(chunk_group: &Option<ChunkGroupMetadata>) -> bool {
+    chunk_group
+        .as_ref()
+        .is_some_and(|chunk_group| !chunk_group.chunk_metadata.is_empty())
+}
+
+/// Statistics of the chunk, taken from the page headers or computed from the points if the
+/// chunk has only one page
+fn chunk_statistics(
+    chunk: &ChunkReader,
+    time_chunk: Option<&ChunkReader>,
+) -> Result<Statistics, TsFileError> {
+    let header = chunk.header();
+    let mut statistics = Statistics::new(header.data_type);
+    if header.has_page_statistics() {
+        for page in chunk.pages()? {
+            if let Some(page_statistics) = &page.header().statistics {
+                statistics.merge(page_statistics)?;
+            }
+        }
+    } else if header.is_time_chunk() {
+        for page in chunk.pages()? {
+            for timestamp in page.timestamps()? {
+                statistics.update_time(timestamp)?;
+            }
+        }
+    } else {
+        let points = match (header.is_value_chunk(), time_chunk) {
+            (true, Some(time_chunk)) => chunk.read_aligned(time_chunk)?,
+            (true, None) => {
+                return Err(TsFileError::Error {
+                    source: Some(format!(
+                        "Value chunk {} without time chunk",
+                        header.measurement_id
+                    )),
+                })
+            }
+            (false, _) => chunk.read_all()?,
+        };
+        for (timestamp, value) in &points {
+            statistics.update(*timestamp, value)?;
+        }
+    }
+    Ok(statistics)
+}
+
+/// The writer would write chunks of different data types for the same series otherwise
+fn check_schema(
+    chunk_groups: &[ChunkGroupMetadata],
+    schema: &Schema<'_>,
+) -> Result<(), TsFileError> {
+    for chunk_group in chunk_groups {
+        let group = match schema
+            .measurement_groups
+            .get(chunk_group.device_id.as_str())
+        {
+            Some(group) => group,
+            None => continue,
+        };
+        for chunk_metadata in &chunk_group.chunk_metadata {
+            if (chunk_metadata.mask != 0) != group.is_aligned() {
+                return Err(schema_error(format!(
+                    "Device '{}' has a different alignment in the file than in the schema",
+                    chunk_group.device_id
+                )));
+            }
+            match group
+                .measurement_schemas
+                .get(chunk_metadata.measurement_id.as_str())
+            {
+                Some(measurement) if measurement.data_type != chunk_metadata.data_type => {
+                    return Err(schema_error(format!(
+                        "Measurement '{}' of device '{}' is {:?} in the file but {:?} in the schema",
+                        chunk_metadata.measurement_id,
+                        chunk_group.device_id,
+                        chunk_metadata.data_type,
+                        measurement.data_type
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Time of the last point of every series (device and measurement) in the chunk groups
+fn last_times(chunk_groups: &[ChunkGroupMetadata]) -> Vec<(String, String, i64)> {
+    chunk_groups
+        .iter()
+        .flat_map(|chunk_group| {
+            chunk_group
+                .chunk_metadata
+                .iter()
+                .filter(|chunk_metadata| chunk_metadata.statistics.count() > 0)
+                .map(move |chunk_metadata| {
+                    (
+                        chunk_group.device_id.clone(),
+                        chunk_metadata.measurement_id.clone(),
+                        chunk_metadata.statistics.end_time(),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::wal::wal_path;
+    use crate::writer::{IoTDBValue, PositionedWrite, Schema, TSDataType};
+    use std::fs::{create_dir_all, metadata, read, write};
+
+    fn schema(data_type: TSDataType) -> Schema<'static> {
+        TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        data_type,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::DOUBLE,
+                        TSEncoding::PLAIN,
+                        CompressionType::SNAPPY,
+                    )
+                    .build(),
+            )
+            .build()
+    }
+
+    /// Two flushes without close, the second chunk group of d2 is cut in the middle
+    fn unsealed_file(filename: &str) -> usize {
+        let config = TsFileConfig::default().max_number_of_points_in_page(10);
+        let mut writer =
+            TsFileWriter::new_from_writer(schema(TSDataType::INT64), vec![], config).unwrap();
+        for i in 0..25 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        let first_flush = writer.file_io_writer.out.len();
+        for i in 25..30 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+            writer
+                .write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.truncate(bytes.len() - 3);
+        create_dir_all("target").unwrap();
+        write(filename, &bytes).unwrap();
+        first_flush
+    }
+
+    fn points(filename: &str, device: &str) -> Vec<(i64, IoTDBValue)> {
+        TsFileReader::open(filename)
+            .unwrap()
+            .query(device, "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn recover() -> Result<(), TsFileError> {
+        let filename = "target/recover.tsfile";
+        let first_flush = unsealed_file(filename);
+        let config = TsFileConfig::default();
+        let mut writer = TsFileWriter::recover(filename, schema(TSDataType::INT64), config)?;
+        // The time chunk of d2 was complete, its value chunk not
+        assert!(writer.file_io_writer.out.get_position() > first_flush as u64);
+
+        assert!(matches!(
+            writer.write("d1", "s1", 29, IoTDBValue::LONG(29)),
+            Err(TsFileError::OutOfOrderData)
+        ));
+        assert!(writer
+            .write("d2", "s1", 24, IoTDBValue::DOUBLE(24.0))
+            .is_err());
+        writer.write("d1", "s1", 30, IoTDBValue::LONG(30))?;
+        writer.write("d2", "s1", 30, IoTDBValue::DOUBLE(30.0))?;
+        writer.close()?;
+
+        let d1 = points(filename, "d1");
+        assert_eq!(d1.len(), 31);
+        assert_eq!(d1[30], (30, IoTDBValue::LONG(30)));
+        // Only the values of the first flush and the new point
+        let d2 = points(filename, "d2");
+        assert_eq!(d2.len(), 26);
+        assert_eq!(d2[24], (24, IoTDBValue::DOUBLE(24.0)));
+        assert_eq!(d2[25], (30, IoTDBValue::DOUBLE(30.0)));
+
+        // A complete file is not recovered
+        assert!(matches!(
+            TsFileWriter::recover(filename, schema(TSDataType::INT64), config),
+            Err(TsFileError::IllegalState { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn recover_with_different_schema() {
+        let filename = "target/recover_schema.tsfile";
+        unsealed_file(filename);
+        match TsFileWriter::recover(filename, schema(TSDataType::INT32), Default::default()) {
+            Err(TsFileError::SchemaError {
+                source: Some(message),
+            }) => assert_eq!(
+                message,
+                "Measurement 's1' of device 'd1' is INT64 in the file but INT32 in the schema"
+            ),
+            _ => panic!("Expected a schema error"),
+        }
+    }
+
+    #[test]
+    fn replay_write_ahead_log() -> Result<(), TsFileError> {
+        let filename = "target/replay_wal.tsfile";
+        let wal = wal_path(filename);
+        let config = TsFileConfig::default()
+            .write_ahead_log(true)
+            .close_on_drop(false);
+        let mut writer = TsFileWriter::new(filename, schema(TSDataType::INT64), config)?;
+        for i in 0..100 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        let flushed_log = read(&wal)?;
+        writer.flush()?;
+        assert_eq!(metadata(&wal)?.len(), 0);
+        let batch: Vec<i64> = (100..125).collect();
+        writer.write_batch("d1", "s1", &batch, &batch)?;
+        for i in 125..150 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        // The process dies before the log was truncated after the flush
+        drop(writer);
+        let mut log = flushed_log;
+        log.extend(read(&wal)?);
+        write(&wal, log)?;
+
+        let mut writer = TsFileWriter::recover(filename, schema(TSDataType::INT64), config)?;
+        writer.write("d1", "s1", 150, IoTDBValue::LONG(150))?;
+        writer.close()?;
+        assert!(!wal.exists());
+        let d1 = points(filename, "d1");
+        assert_eq!(d1.len(), 151);
+        assert_eq!(d1[149], (149, IoTDBValue::LONG(149)));
+        Ok(())
+    }
+
+    #[test]
+    fn flush_open() -> Result<(), TsFileError> {
+        let filename = "target/flush_open.tsfile";
+        let config = TsFileConfig::default().close_on_drop(false);
+        let mut writer = TsFileWriter::new(filename, schema(TSDataType::INT64), config)?;
+        for i in 0..50 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        let checkpoint = writer.flush_open()?;
+        assert_eq!(metadata(filename)?.len(), checkpoint);
+        // The same series continues after the checkpoint
+        for i in 50..60 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("d2", "s1", i, IoTDBValue::DOUBLE(i as f64))?;
+        }
+        assert!(writer.flush_open()? > checkpoint);
+        writer.write("d1", "s1", 60, IoTDBValue::LONG(60))?;
+        // The process dies, the last point was not flushed
+        drop(writer);
+
+        let mut writer = TsFileWriter::recover(filename, schema(TSDataType::INT64), config)?;
+        writer.close()?;
+        let d1 = points(filename, "d1");
+        assert_eq!(d1.len(), 60);
+        assert_eq!(d1[59], (59, IoTDBValue::LONG(59)));
+        assert_eq!(points(filename, "d2").len(), 10);
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+//! Per device buffer that sorts rows by time before they reach the group writer,
+//! see `TsFileConfig::reorder_window`
+use crate::writer::errors::TsFileError;
+use crate::writer::ts_file_config::{DuplicatePolicy, ReorderWindow};
+use crate::writer::tsfile_writer::DataPoint;
+use std::collections::BTreeMap;
+
+pub(crate) struct ReorderBuffer<'a> {
+    window: ReorderWindow,
+    duplicate_policy: DuplicatePolicy,
+    rows: BTreeMap<i64, Vec<DataPoint<'a>>>,
+    /// Timestamp of the last row that left the buffer
+    watermark: Option<i64>,
+}
+
+impl<'a> ReorderBuffer<'a> {
+    pub(crate) fn new(
+        window: ReorderWindow,
+        duplicate_policy: DuplicatePolicy,
+    ) -> ReorderBuffer<'a> {
+        ReorderBuffer {
+            window,
+            duplicate_policy,
+            rows: BTreeMap::new(),
+            watermark: None,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Whether the row can not be sorted in anymore as newer rows were already released
+    pub(crate) fn is_late(&self, timestamp: i64) -> bool {
+        match self.watermark {
+            Some(watermark) => timestamp < watermark,
+            None => false,
+        }
+    }
+
+    /// Whether the row has the timestamp of the last released row, it has to be written
+    /// directly so that the group writer handles duplicates
+    pub(crate) fn is_released(&self, timestamp: i64) -> bool {
+        self.watermark == Some(timestamp)
+    }
+
+    /// Adds the points to the row of the timestamp, the caller has to check `is_late` and
+    /// `is_released` before. Points of a measurement that is already in the row are handled
+    /// by the `DuplicatePolicy`.
+    pub(crate) fn insert(
+        &mut self,
+        timestamp: i64,
+        points: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        let row = self.rows.entry(timestamp).or_default();
+        for point in points {
+            match row
+                .iter_mut()
+                .find(|existing| existing.measurement_id == point.measurement_id)
+            {
+                Some(existing) => match self.duplicate_policy {
+                    DuplicatePolicy::Error => return Err(TsFileError::OutOfOrderData),
+                    DuplicatePolicy::KeepFirst => {}
+                    DuplicatePolicy::OverwriteLast => *existing = point,
+                },
+                None => row.push(point),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the oldest rows that fell out of the window, in time order
+    pub(crate) fn pop_ready(&mut self) -> Vec<(i64, Vec<DataPoint<'a>>)> {
+        let mut ready = vec![];
+        while let Some(&first) = self.rows.keys().next() {
+            let outside = match self.window {
+                ReorderWindow::Disabled => true,
+                ReorderWindow::Points(points) => self.rows.len() > points,
+                ReorderWindow::Time(window) => {
+                    let last = *self.rows.keys().next_back().unwrap_or(&first);
+                    last.saturating_sub(first) > window
+                }
+            };
+            if !outside {
+                break;
+            }
+            if let Some(points) = self.rows.remove(&first) {
+                ready.push((first, points));
+            }
+            self.watermark = Some(first);
+        }
+        ready
+    }
+
+    /// Rows up to the timestamp were written bypassing the buffer, e.g. by a tablet
+    pub(crate) fn advance(&mut self, timestamp: i64) {
+        self.watermark = Some(self.watermark.map_or(timestamp, |w| w.max(timestamp)));
+    }
+
+    /// Removes all rows, e.g. before a flush
+    pub(crate) fn drain(&mut self) -> Vec<(i64, Vec<DataPoint<'a>>)> {
+        if let Some(&last) = self.rows.keys().next_back() {
+            self.watermark = Some(last);
+        }
+        std::mem::take(&mut self.rows).into_iter().collect()
+    }
+}
@@ -0,0 +1,235 @@
+//! The `.resource` file that IoTDB expects next to every TsFile it loads.
+//!
+//! It contains the start and end time of every device in the file (the `DeviceTimeIndex` of
+//! IoTDB), so IoTDB does not have to read the whole file to find the devices.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+use crate::writer::errors::TsFileError;
+
+/// Version of the serialization of the resource
+const VERSION_NUMBER: u8 = 1;
+/// Type of the time index, only the device time index is written
+const DEVICE_TIME_INDEX_TYPE: u8 = 1;
+
+/// Start and end time of every device of a TsFile
+#[derive(Clone, Debug, PartialEq)]
+pub struct TsFileResource {
+    /// Device to start and end time (both inclusive)
+    pub devices: BTreeMap<String, (i64, i64)>,
+    /// Name of the modification file with deletions of this file, if there is one
+    pub mod_file: Option<String>,
+}
+
+impl TsFileResource {
+    pub fn new(devices: BTreeMap<String, (i64, i64)>) -> TsFileResource {
+        TsFileResource {
+            devices,
+            mod_file: None,
+        }
+    }
+
+    /// Path of the sidecar for the given TsFile
+    pub fn path(tsfile: &str) -> String {
+        format!("{}.resource", tsfile)
+    }
+
+    /// Writes the resource next to the given TsFile
+    pub fn write_file(&self, tsfile: &str) -> Result<(), TsFileError> {
+        let mut out = BufWriter::new(File::create(TsFileResource::path(tsfile))?);
+        self.serialize(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Reads the resource of the given TsFile
+    pub fn read_file(tsfile: &str) -> Result<TsFileResource, TsFileError> {
+        let mut bytes = vec![];
+        File::open(TsFileResource::path(tsfile))?.read_to_end(&mut bytes)?;
+        TsFileResource::deserialize(&mut bytes.as_slice())
+    }
+
+    pub fn serialize(&self, out: &mut dyn Write) -> Result<(), TsFileError> {
+        out.write_all(&[VERSION_NUMBER, DEVICE_TIME_INDEX_TYPE])?;
+        out.write_all(&(self.devices.len() as i32).to_be_bytes())?;
+        for (start_time, end_time) in self.devices.values() {
+            out.write_all(&start_time.to_be_bytes())?;
+            out.write_all(&end_time.to_be_bytes())?;
+        }
+        for (index, device) in self.devices.keys().enumerate() {
+            write_string(out, Some(device))?;
+            out.write_all(&(index as i32).to_be_bytes())?;
+        }
+        // Max and min plan index, not used for files that were not written by IoTDB
+        out.write_all(&i64::MIN.to_be_bytes())?;
+        out.write_all(&i64::MAX.to_be_bytes())?;
+        write_string(out, self.mod_file.as_deref())?;
+        Ok(())
+    }
+
+    pub fn deserialize(input: &mut dyn Read) -> Result<TsFileResource, TsFileError> {
+        let mut header = [0_u8; 2];
+        input.read_exact(&mut header)?;
+        if header != [VERSION_NUMBER, DEVICE_TIME_INDEX_TYPE] {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!(
+                    "Resource version {} with time index type {}",
+                    header[0], header[1]
+                )),
+            });
+        }
+        let count = read_i32(input)?.max(0) as usize;
+        let mut times = Vec::with_capacity(count);
+        for _ in 0..count {
+            times.push((read_i64(input)?, read_i64(input)?));
+        }
+        let mut devices = BTreeMap::new();
+        for _ in 0..count {
+            let device = read_string(input)?.unwrap_or_default();
+            let index = read_i32(input)?;
+            match times.get(index.max(0) as usize) {
+                Some(&time) if index >= 0 => devices.insert(device, time),
+                _ => {
+                    return Err(TsFileError::Error {
+                        source: Some(format!("Invalid device index {}", index)),
+                    })
+                }
+            };
+        }
+        read_i64(input)?;
+        read_i64(input)?;
+        let mod_file = read_string(input)?;
+        Ok(TsFileResource { devices, mod_file })
+    }
+}
+
+/// Strings are serialized with their length like in Java, `None` is written as length -1
+fn write_string(out: &mut dyn Write, value: Option<&str>) -> Result<(), TsFileError> {
+    match value {
+        Some(value) => {
+            out.write_all(&(value.len() as i32).to_be_bytes())?;
+            out.write_all(value.as_bytes())?;
+        }
+        None => out.write_all(&(-1_i32).to_be_bytes())?,
+    }
+    Ok(())
+}
+
+fn read_string(input: &mut dyn Read) -> Result<Option<String>, TsFileError> {
+    let length = read_i32(input)?;
+    if length < 0 {
+        return Ok(None);
+    }
+    let mut bytes = vec![0_u8; length as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|e| TsFileError::Error {
+            source: Some(format!("Invalid string in resource: {}", e)),
+        })
+}
+
+fn read_i32(input: &mut dyn Read) -> Result<i32, TsFileError> {
+    let mut bytes = [0_u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes))
+}
+
+fn read_i64(input: &mut dyn Read) -> Result<i64, TsFileError> {
+    let mut bytes = [0_u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(i64::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::resource::TsFileResource;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriterBuilder;
+    use crate::writer::{IoTDBValue, TSDataType};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn write_resource() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "root.sg.d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "root.sg.d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "root.sg.d3",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let path = "target/resource.tsfile";
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .write_resource(true)
+            .build(path)?;
+        for i in 0..100 {
+            writer.write("root.sg.d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("root.sg.d2", "s1", i + 1000, IoTDBValue::LONG(i))?;
+            if i == 50 {
+                writer.flush()?;
+            }
+        }
+        writer.close()?;
+
+        let expected = BTreeMap::from([
+            ("root.sg.d1".to_owned(), (0, 99)),
+            ("root.sg.d2".to_owned(), (1000, 1099)),
+        ]);
+        let resource = TsFileResource::read_file(path)?;
+        assert_eq!(resource, TsFileResource::new(expected));
+        assert_eq!(writer.resource(), resource);
+
+        let mut bytes = vec![];
+        resource.serialize(&mut bytes)?;
+        // Version, time index type and number of devices
+        assert_eq!(bytes[0..6], [1, 1, 0, 0, 0, 2]);
+        // Start and end time of the first device
+        assert_eq!(
+            bytes[6..22],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 99]
+        );
+        assert_eq!(bytes[bytes.len() - 4..], [255; 4]);
+
+        let mut with_mods = resource;
+        with_mods.mod_file = Some("resource.tsfile.mods".to_owned());
+        bytes.clear();
+        with_mods.serialize(&mut bytes)?;
+        assert_eq!(
+            TsFileResource::deserialize(&mut bytes.as_slice())?,
+            with_mods
+        );
+        Ok(())
+    }
+}
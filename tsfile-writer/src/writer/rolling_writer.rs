@@ -0,0 +1,311 @@
+//! Writer that splits the data into several TsFiles, like the sequence files of IoTDB.
+//!
+//! The current file is closed and the next one is started once it reaches a maximum size, a
+//! maximum number of points or when a point belongs to the next time partition. Files are
+//! named like in IoTDB, `{creation time}-{version}-0-0.tsfile`, so they can be loaded by it.
+use std::fs::{create_dir_all, File};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::writer::errors::TsFileError;
+use crate::writer::listener::FlushListener;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_writer::{create_file, sync_handle, DataPoint, TsFileWriter};
+use crate::writer::{IoTDBValue, Schema, WriteWrapper};
+
+/// The estimated file size is only checked every this many points as it iterates all series
+const SIZE_CHECK_INTERVAL: u64 = 100;
+
+/// Writes points to a sequence of TsFiles in a directory
+///
+/// ```no_run
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+/// use tsfile_writer::writer::rolling_writer::RollingTsFileWriter;
+/// use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+///
+/// let schema = Schema::simple(
+///     "d1",
+///     "s1",
+///     TSDataType::INT64,
+///     TSEncoding::PLAIN,
+///     CompressionType::UNCOMPRESSED,
+/// );
+/// let mut writer = RollingTsFileWriter::new("target/rolling", schema, Default::default());
+/// writer
+///     .max_file_size(64 * 1024 * 1024)
+///     .time_partition_interval(7 * 24 * 60 * 60 * 1000);
+/// writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+/// let files = writer.close().unwrap();
+/// ```
+pub struct RollingTsFileWriter<'a> {
+    directory: PathBuf,
+    schema: Schema<'a>,
+    config: TsFileConfig,
+    max_file_size: Option<u64>,
+    max_points: Option<u64>,
+    time_partition_interval: Option<i64>,
+    current: Option<TsFileWriter<'a, WriteWrapper<File>>>,
+    current_partition: Option<i64>,
+    points: u64,
+    next_size_check: u64,
+    version: u64,
+    files: Vec<PathBuf>,
+    flush_listener: Option<Box<dyn Fn() -> Box<dyn FlushListener>>>,
+}
+
+impl<'a> RollingTsFileWriter<'a> {
+    /// Files are created in the given directory, it is created if it does not exist
+    pub fn new(
+        directory: impl Into<PathBuf>,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> RollingTsFileWriter<'a> {
+        RollingTsFileWriter {
+            directory: directory.into(),
+            schema,
+            config,
+            max_file_size: None,
+            max_points: None,
+            time_partition_interval: None,
+            current: None,
+            current_partition: None,
+            points: 0,
+            next_size_check: SIZE_CHECK_INTERVAL,
+            version: 0,
+            files: vec![],
+            flush_listener: None,
+        }
+    }
+
+    /// Starts a new file once the current one is estimated to contain this many bytes,
+    /// including data that is not flushed yet. Files can be slightly larger.
+    pub fn max_file_size(&mut self, bytes: u64) -> &mut Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Starts a new file once the current one contains this many points
+    pub fn max_points(&mut self, points: u64) -> &mut Self {
+        self.max_points = Some(points);
+        self
+    }
+
+    /// Starts a new file if a point belongs to another time partition than the previous one,
+    /// partitions start at multiples of the interval (in the unit of the timestamps).
+    /// Data should arrive in time order, otherwise every change of the partition starts a file.
+    pub fn time_partition_interval(&mut self, interval: i64) -> &mut Self {
+        self.time_partition_interval = Some(interval);
+        self
+    }
+
+    /// Sets the listener for the flushes of every file, each file gets its own clone. Files are
+    /// flushed on close, so `after_close` is called whenever the next file is started.
+    pub fn set_flush_listener(&mut self, listener: impl FlushListener + Clone + 'static) {
+        self.flush_listener = Some(Box::new(move || Box::new(listener.clone())));
+    }
+
+    pub fn write(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamp: i64,
+        value: IoTDBValue,
+    ) -> Result<(), TsFileError> {
+        self.current_writer(timestamp)?
+            .write(device, measurement_id, timestamp, value)?;
+        self.points += 1;
+        Ok(())
+    }
+
+    pub fn write_many(
+        &mut self,
+        device: &'a str,
+        timestamp: i64,
+        values: Vec<DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        let count = values.len() as u64;
+        self.current_writer(timestamp)?
+            .write_many(device, timestamp, values)?;
+        self.points += count;
+        Ok(())
+    }
+
+    /// Closes the current file and returns the paths of all written files in order
+    pub fn close(&mut self) -> Result<Vec<PathBuf>, TsFileError> {
+        self.close_current()?;
+        Ok(self.files.clone())
+    }
+
+    /// Paths of all files that were started so far, the last one may still be open
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    fn current_writer(
+        &mut self,
+        timestamp: i64,
+    ) -> Result<&mut TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        let partition = match self.time_partition_interval {
+            Some(interval) if interval > 0 => Some(timestamp.div_euclid(interval)),
+            _ => None,
+        };
+        if self.current.is_some() && self.is_full(partition) {
+            self.close_current()?;
+        }
+        if self.current.is_none() {
+            let path = self.next_path();
+            create_dir_all(&self.directory)?;
+            let file = create_file(&path, &self.config)?;
+            let sync_file = Some(sync_handle(&file)?);
+            let mut writer = TsFileWriter::new_from_writer(
+                self.schema.clone(),
+                WriteWrapper::new(file),
+                self.config,
+            )?;
+            writer.filename = path.to_string_lossy().into_owned();
+            writer.file_io_writer.sync_file = sync_file;
+            if let Some(listener) = &self.flush_listener {
+                writer.file_io_writer.listener = Some(listener());
+            }
+            self.current = Some(writer);
+            self.files.push(path);
+            self.current_partition = partition;
+            self.points = 0;
+            self.next_size_check = SIZE_CHECK_INTERVAL;
+        }
+        match self.current.as_mut() {
+            Some(writer) => Ok(writer),
+            None => Err(TsFileError::IllegalState {
+                source: Some("No open file".to_owned()),
+            }),
+        }
+    }
+
+    fn is_full(&mut self, partition: Option<i64>) -> bool {
+        if partition != self.current_partition {
+            return true;
+        }
+        if let Some(max_points) = self.max_points {
+            if self.points >= max_points {
+                return true;
+            }
+        }
+        if let (Some(max_file_size), Some(writer)) = (self.max_file_size, self.current.as_mut()) {
+            if self.points >= self.next_size_check {
+                self.next_size_check = self.points + SIZE_CHECK_INTERVAL;
+                if writer.estimated_size() >= max_file_size {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn close_current(&mut self) -> Result<(), TsFileError> {
+        if let Some(mut writer) = self.current.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+
+    /// `{creation time}-{version}-0-0.tsfile` like the files of IoTDB
+    fn next_path(&mut self) -> PathBuf {
+        self.version += 1;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or(0);
+        self.directory
+            .join(format!("{}-{}-0-0.tsfile", millis, self.version))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::rolling_writer::RollingTsFileWriter;
+    use crate::writer::tsfile_writer::DataPoint;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::path::PathBuf;
+
+    fn schema() -> Schema<'static> {
+        Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        )
+    }
+
+    fn counts(files: &[PathBuf]) -> Vec<usize> {
+        files
+            .iter()
+            .map(|file| {
+                let mut reader = TsFileReader::open(file.to_str().unwrap()).unwrap();
+                reader.query("d1", "s1").unwrap().count()
+            })
+            .collect()
+    }
+
+    fn writer(directory: &str) -> RollingTsFileWriter<'static> {
+        let _ = std::fs::remove_dir_all(directory);
+        RollingTsFileWriter::new(directory, schema(), Default::default())
+    }
+
+    #[test]
+    fn max_points() -> Result<(), TsFileError> {
+        let mut writer = writer("target/rolling_points");
+        writer.max_points(1_000);
+        for i in 0..2_500 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        let files = writer.close()?;
+        assert_eq!(counts(&files), vec![1_000, 1_000, 500]);
+
+        // IoTDB file names with increasing versions
+        let names: Vec<String> = files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        for (i, name) in names.iter().enumerate() {
+            let parts: Vec<&str> = name.trim_end_matches(".tsfile").split('-').collect();
+            assert!(parts[0].parse::<u128>().is_ok());
+            assert_eq!(parts[1..], [(i + 1).to_string().as_str(), "0", "0"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn time_partition_interval() -> Result<(), TsFileError> {
+        let mut writer = writer("target/rolling_partitions");
+        writer.time_partition_interval(100);
+        for i in -50..250 {
+            writer.write_many("d1", i, vec![DataPoint::new("s1", IoTDBValue::LONG(i))])?;
+        }
+        assert_eq!(writer.files().len(), 4);
+        let files = writer.close()?;
+        assert_eq!(counts(&files), vec![50, 100, 100, 50]);
+        Ok(())
+    }
+
+    #[test]
+    fn max_file_size() -> Result<(), TsFileError> {
+        let mut writer = writer("target/rolling_size");
+        writer.max_file_size(64 * 1024);
+        for i in 0..100_000 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        let files = writer.close()?;
+        assert!(files.len() > 1);
+        assert_eq!(counts(&files).iter().sum::<usize>(), 100_000);
+        for file in &files {
+            assert!(std::fs::metadata(file)?.len() < 2 * 64 * 1024);
+        }
+        Ok(())
+    }
+}
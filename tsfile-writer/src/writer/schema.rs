@@ -1,18 +1,25 @@
 //! Contains the classes for defining the Schema of a TsFile, i.e. which Devices / Snesors
 //! it contains and their datatype / encoding / compression
+use crate::writer::errors::TsFileError;
 use crate::writer::{
     CompressionType, MeasurementGroup, MeasurementSchema, Schema, TSDataType, TSEncoding,
 };
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Characters that IoTDB does not accept in the nodes of a path without quoting
+const ILLEGAL_CHARACTERS: &[char] = &['.', ',', ';', '\'', '"', '`', '(', ')', '*', '?', '\\'];
 
 pub struct TsFileSchemaBuilder<'a> {
-    measurement_groups_map: HashMap<&'a str, MeasurementGroup<'a>>,
+    measurement_groups_map: BTreeMap<&'a str, MeasurementGroup<'a>>,
+    duplicates: Vec<&'a str>,
 }
 
 impl<'a> TsFileSchemaBuilder<'a> {
     pub fn new() -> TsFileSchemaBuilder<'a> {
         TsFileSchemaBuilder {
-            measurement_groups_map: HashMap::new(),
+            measurement_groups_map: BTreeMap::new(),
+            duplicates: vec![],
         }
     }
 
@@ -21,18 +28,51 @@ impl<'a> TsFileSchemaBuilder<'a> {
         device: &'a str,
         schema: MeasurementGroup<'a>,
     ) -> &mut TsFileSchemaBuilder<'a> {
-        self.measurement_groups_map.insert(device, schema);
+        if self.measurement_groups_map.insert(device, schema).is_some() {
+            self.duplicates.push(device);
+        }
         self
     }
 
+    /// Builds the schema, see `try_build`
+    ///
+    /// # Panics
+    ///
+    /// If the schema is invalid
     pub fn build(&mut self) -> Schema<'a> {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Builds the schema, fails if a device is added twice or a device path is empty or
+    /// contains illegal characters. The nodes of a device path are separated by `.`.
+    pub fn try_build(&mut self) -> Result<Schema<'a>, TsFileError> {
+        if let Some(device) = self.duplicates.first() {
+            return Err(schema_error(format!("Device '{}' is added twice", device)));
+        }
+        for device in self.measurement_groups_map.keys() {
+            check_device(device)?;
+        }
         // Copy the content
-        let mut measurement_groups: HashMap<&str, MeasurementGroup> = HashMap::new();
+        let mut measurement_groups: BTreeMap<&str, MeasurementGroup> = BTreeMap::new();
         measurement_groups.clear();
         for (s, mg) in self.measurement_groups_map.iter_mut() {
             measurement_groups.insert(s, mg.clone());
         }
-        Schema { measurement_groups }
+        Ok(Schema { measurement_groups })
+    }
+}
+
+impl<'a> TsFileSchemaBuilder<'a> {
+    /// Adds all devices with the measurements of the template
+    pub fn add_template(
+        &mut self,
+        template: &DeviceTemplate<'a>,
+        devices: impl IntoIterator<Item = &'a str>,
+    ) -> &mut TsFileSchemaBuilder<'a> {
+        for device in devices {
+            self.add(device, template.instantiate());
+        }
+        self
     }
 }
 
@@ -42,14 +82,46 @@ impl<'a> Default for TsFileSchemaBuilder<'a> {
     }
 }
 
+/// Measurements that many devices have in common, e.g. all turbines of a wind park. The devices
+/// of a template share the measurement schemas instead of a copy each.
+#[derive(Clone)]
+pub struct DeviceTemplate<'a> {
+    group: MeasurementGroup<'a>,
+}
+
+impl<'a> DeviceTemplate<'a> {
+    pub fn new(group: MeasurementGroup<'a>) -> DeviceTemplate<'a> {
+        DeviceTemplate { group }
+    }
+
+    /// The measurements for a device of the template
+    pub fn instantiate(&self) -> MeasurementGroup<'a> {
+        self.group.clone()
+    }
+}
+
 pub struct DeviceBuilder<'a> {
-    measurement_groups_map: HashMap<&'a str, MeasurementSchema>,
+    measurement_groups_map: BTreeMap<&'a str, MeasurementSchema>,
+    aligned: bool,
+    duplicates: Vec<&'a str>,
 }
 
 impl<'a> DeviceBuilder<'a> {
     pub fn new() -> DeviceBuilder<'a> {
         DeviceBuilder {
-            measurement_groups_map: HashMap::new(),
+            measurement_groups_map: BTreeMap::new(),
+            aligned: false,
+            duplicates: vec![],
+        }
+    }
+
+    /// Builder for an aligned device, i.e. all measurements share a single time column.
+    /// The time column is encoded with TS2DIFF and not compressed.
+    pub fn aligned() -> DeviceBuilder<'a> {
+        DeviceBuilder {
+            measurement_groups_map: BTreeMap::new(),
+            aligned: true,
+            duplicates: vec![],
         }
     }
 
@@ -60,28 +132,62 @@ impl<'a> DeviceBuilder<'a> {
         encoding: TSEncoding,
         compression: CompressionType,
     ) -> &mut DeviceBuilder<'a> {
-        self.measurement_groups_map.insert(
+        self.add_schema(
             measurement,
-            MeasurementSchema {
-                data_type,
-                compression,
-                encoding,
-            },
-        );
+            MeasurementSchema::new(data_type, encoding, compression),
+        )
+    }
+
+    /// Adds a measurement with all options of its schema, e.g. `max_point_number`
+    pub fn add_schema(
+        &mut self,
+        measurement: &'a str,
+        schema: MeasurementSchema,
+    ) -> &mut DeviceBuilder<'a> {
+        if self
+            .measurement_groups_map
+            .insert(measurement, schema)
+            .is_some()
+        {
+            self.duplicates.push(measurement);
+        }
         self
     }
 
+    /// Builds the device, see `try_build`
+    ///
+    /// # Panics
+    ///
+    /// If the device is invalid
     pub fn build(&mut self) -> MeasurementGroup<'a> {
-        assert!(!self.measurement_groups_map.is_empty());
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Builds the device, fails if it has no measurements, a measurement is added twice or the
+    /// name of a measurement is empty or contains illegal characters (including `.`)
+    pub fn try_build(&mut self) -> Result<MeasurementGroup<'a>, TsFileError> {
+        if self.measurement_groups_map.is_empty() {
+            return Err(schema_error("Device has no measurements".to_owned()));
+        }
+        if let Some(measurement) = self.duplicates.first() {
+            return Err(schema_error(format!(
+                "Measurement '{}' is added twice",
+                measurement
+            )));
+        }
+        for measurement in self.measurement_groups_map.keys() {
+            check_node("Measurement", measurement, measurement)?;
+        }
         // Copy the content
-        let mut measurement_schemas: HashMap<&'a str, MeasurementSchema> = HashMap::new();
+        let mut measurement_schemas: BTreeMap<&'a str, MeasurementSchema> = BTreeMap::new();
         measurement_schemas.clear();
         for (s, ms) in self.measurement_groups_map.iter_mut() {
             measurement_schemas.insert(s, ms.clone());
         }
-        MeasurementGroup {
-            measurement_schemas,
-        }
+        Ok(MeasurementGroup {
+            measurement_schemas: Arc::new(measurement_schemas),
+            aligned: self.aligned,
+        })
     }
 }
 
@@ -91,10 +197,50 @@ impl<'a> Default for DeviceBuilder<'a> {
     }
 }
 
+/// Checks that the device path is not empty and its nodes contain no illegal characters
+pub(crate) fn check_device(device: &str) -> Result<(), TsFileError> {
+    if device.is_empty() {
+        return Err(schema_error("Device path is empty".to_owned()));
+    }
+    for node in device.split('.') {
+        check_node("Device path", device, node)?;
+    }
+    Ok(())
+}
+
+/// Checks a single node of the path `name`, `kind` is used for the error message
+fn check_node(kind: &str, name: &str, node: &str) -> Result<(), TsFileError> {
+    if node.is_empty() {
+        return Err(schema_error(format!(
+            "{} '{}' contains an empty node",
+            kind, name
+        )));
+    }
+    match node
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || ILLEGAL_CHARACTERS.contains(c))
+    {
+        Some(c) => Err(schema_error(format!(
+            "{} '{}' contains the illegal character {:?}",
+            kind, name, c
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn schema_error(message: String) -> TsFileError {
+    TsFileError::SchemaError {
+        source: Some(message),
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
-    use crate::writer::{CompressionType, TSDataType, TSEncoding};
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, DeviceTemplate, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{CompressionType, IoTDBValue, TSDataType, TSEncoding};
+    use std::sync::Arc;
 
     #[test]
     fn use_fluent_builder() {
@@ -128,5 +274,161 @@ mod test {
                 .len(),
             2
         );
+        assert!(!schema.measurement_groups.get("d1").unwrap().is_aligned());
+    }
+
+    #[test]
+    fn aligned_device() {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+
+        assert!(schema.measurement_groups.get("d1").unwrap().is_aligned());
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        let device = |measurements: &[&'static str]| {
+            let mut builder = DeviceBuilder::new();
+            for measurement in measurements {
+                builder.add(
+                    measurement,
+                    TSDataType::INT32,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                );
+            }
+            builder
+                .try_build()
+                .map(|group| group.measurement_schemas.len())
+        };
+        let error = |message: &str| {
+            Err(TsFileError::SchemaError {
+                source: Some(message.to_owned()),
+            })
+        };
+
+        assert_eq!(device(&["s1", "s_2"]), Ok(2));
+        assert_eq!(device(&[]), error("Device has no measurements"));
+        assert_eq!(
+            device(&["s1", "s2", "s1"]),
+            error("Measurement 's1' is added twice")
+        );
+        assert_eq!(
+            device(&[""]),
+            error("Measurement '' contains an empty node")
+        );
+        assert_eq!(
+            device(&["s.1"]),
+            error("Measurement 's.1' contains the illegal character '.'")
+        );
+        assert_eq!(
+            device(&["s 1"]),
+            error("Measurement 's 1' contains the illegal character ' '")
+        );
+
+        let schema = |devices: &[&'static str]| {
+            let mut builder = TsFileSchemaBuilder::new();
+            for device in devices {
+                builder.add(
+                    device,
+                    DeviceBuilder::new()
+                        .add(
+                            "s1",
+                            TSDataType::INT32,
+                            TSEncoding::PLAIN,
+                            CompressionType::UNCOMPRESSED,
+                        )
+                        .build(),
+                );
+            }
+            builder
+                .try_build()
+                .map(|schema| schema.measurement_groups.len())
+        };
+        assert_eq!(schema(&["root.sg.d1", "d2"]), Ok(2));
+        assert_eq!(schema(&["d1", "d1"]), error("Device 'd1' is added twice"));
+        assert_eq!(schema(&[""]), error("Device path is empty"));
+        assert_eq!(
+            schema(&["root..d1"]),
+            error("Device path 'root..d1' contains an empty node")
+        );
+        assert_eq!(
+            schema(&["root.sg.d*"]),
+            error("Device path 'root.sg.d*' contains the illegal character '*'")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Measurement 's1' is added twice")]
+    fn build_panics_on_invalid_device() {
+        DeviceBuilder::new()
+            .add(
+                "s1",
+                TSDataType::INT32,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .add(
+                "s1",
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .build();
+    }
+
+    #[test]
+    fn device_template() {
+        let turbine = DeviceTemplate::new(
+            DeviceBuilder::new()
+                .add(
+                    "s_speed",
+                    TSDataType::FLOAT,
+                    TSEncoding::GORILLA,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "s_temp",
+                    TSDataType::FLOAT,
+                    TSEncoding::GORILLA,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "s_vibration",
+                    TSDataType::DOUBLE,
+                    TSEncoding::GORILLA,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .build(),
+        );
+        let devices: Vec<String> = (0..500).map(|i| format!("root.park.t{}", i)).collect();
+        let schema = TsFileSchemaBuilder::new()
+            .add_template(&turbine, devices.iter().map(String::as_str))
+            .build();
+
+        assert_eq!(schema.measurement_groups.len(), 500);
+        let first = &schema.measurement_groups["root.park.t0"].measurement_schemas;
+        assert_eq!(first.len(), 3);
+        assert!(schema
+            .measurement_groups
+            .values()
+            .all(|group| Arc::ptr_eq(&group.measurement_schemas, first)));
+
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+        writer
+            .write("root.park.t499", "s_temp", 1, IoTDBValue::FLOAT(20.5))
+            .unwrap();
+        writer.close().unwrap();
     }
 }
@@ -0,0 +1,221 @@
+//! Typed handle to write the points of a single series, see `TsFileWriter::get_series_writer`
+use crate::writer::errors::TsFileError;
+use crate::writer::tsfile_writer::TsFileWriter;
+use crate::writer::{BatchValue, PositionedWrite};
+
+/// Number of points that are buffered before they are written as a batch
+const BUFFER_SIZE: usize = 1024;
+
+/// Writes the points of a single series whose data type is checked once on creation. The
+/// points are buffered and written with `TsFileWriter::write_batch`, so a point costs neither
+/// lookups of the device and the measurement nor matching on `IoTDBValue`.
+///
+/// Errors of buffered points, e.g. points out of order, are returned by the `write` that fills
+/// the buffer, by `flush` or by `finish`. Dropping the handle flushes it as well but can only log
+/// errors.
+///
+/// ```
+/// use tsfile_writer::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+/// use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+/// use tsfile_writer::writer::{TSDataType, encoding::TSEncoding, compression::CompressionType};
+///
+/// let schema = TsFileSchemaBuilder::new()
+///     .add(
+///         "d1",
+///         DeviceBuilder::new()
+///             .add("s1", TSDataType::INT64, TSEncoding::TS2DIFF, CompressionType::UNCOMPRESSED)
+///             .build(),
+///     )
+///     .build();
+/// let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+/// let mut s1 = writer.get_series_writer::<i64>("d1", "s1").unwrap();
+/// for i in 0..10_000 {
+///     s1.write(i, i * 2).unwrap();
+/// }
+/// s1.finish().unwrap();
+/// writer.close().unwrap();
+/// ```
+pub struct SeriesWriter<'w, 'a, T: PositionedWrite, V: BatchValue> {
+    writer: &'w mut TsFileWriter<'a, T>,
+    device: &'a str,
+    measurement_id: &'a str,
+    timestamps: Vec<i64>,
+    values: Vec<V>,
+}
+
+impl<'w, 'a, T: PositionedWrite, V: BatchValue> SeriesWriter<'w, 'a, T, V> {
+    pub(crate) fn new(
+        writer: &'w mut TsFileWriter<'a, T>,
+        device: &'a str,
+        measurement_id: &'a str,
+    ) -> SeriesWriter<'w, 'a, T, V> {
+        SeriesWriter {
+            writer,
+            device,
+            measurement_id,
+            timestamps: Vec::with_capacity(BUFFER_SIZE),
+            values: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    pub fn write(&mut self, timestamp: i64, value: V) -> Result<(), TsFileError> {
+        self.timestamps.push(timestamp);
+        self.values.push(value);
+        if self.timestamps.len() >= BUFFER_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered points to the `TsFileWriter`, the buffer is empty afterwards even
+    /// if writing fails
+    pub fn flush(&mut self) -> Result<(), TsFileError> {
+        if self.timestamps.is_empty() {
+            return Ok(());
+        }
+        let result = self.writer.write_batch(
+            self.device,
+            self.measurement_id,
+            &self.timestamps,
+            &self.values,
+        );
+        self.timestamps.clear();
+        self.values.clear();
+        result
+    }
+
+    /// Flushes the buffered points and releases the `TsFileWriter`
+    pub fn finish(mut self) -> Result<(), TsFileError> {
+        self.flush()
+    }
+}
+
+impl<'w, 'a, T: PositionedWrite, V: BatchValue> Drop for SeriesWriter<'w, 'a, T, V> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!(
+                "Unable to write the points of {}.{} on drop: {:?}",
+                self.device,
+                self.measurement_id,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+
+    fn schema() -> Schema<'static> {
+        TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn same_file_as_single_points() -> Result<(), TsFileError> {
+        let mut expected = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        for i in 0..5_000 {
+            expected.write("d1", "s1", i, IoTDBValue::LONG(i * i))?;
+            expected.write("d1", "s2", i, IoTDBValue::DOUBLE(i as f64 / 2.0))?;
+        }
+        expected.close()?;
+
+        let mut writer = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        {
+            let mut s1 = writer.get_series_writer::<i64>("d1", "s1")?;
+            for i in 0..5_000 {
+                s1.write(i, i * i)?;
+            }
+            s1.finish()?;
+        }
+        {
+            // Flushed on drop
+            let mut s2 = writer.get_series_writer::<f64>("d1", "s2")?;
+            for i in 0..5_000 {
+                s2.write(i, i as f64 / 2.0)?;
+            }
+        }
+        writer.close()?;
+        assert_eq!(writer.into_inner(), expected.into_inner());
+        Ok(())
+    }
+
+    #[test]
+    fn aligned_device() -> Result<(), TsFileError> {
+        let mut writer = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        let mut s1 = writer.get_series_writer::<i32>("d2", "s1")?;
+        for i in 0..2_000 {
+            s1.write(i as i64, i)?;
+        }
+        drop(s1);
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let points: Vec<_> = reader.query("d2", "s1")?.collect::<Result<_, _>>()?;
+        assert_eq!(points.len(), 2_000);
+        assert_eq!(points[1_999], (1_999, IoTDBValue::INT(1_999)));
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> Result<(), TsFileError> {
+        let mut writer = TsFileWriter::new_from_writer(schema(), vec![], Default::default())?;
+        assert!(matches!(
+            writer.get_series_writer::<i32>("d1", "s1"),
+            Err(TsFileError::WrongTypeForSeries)
+        ));
+        assert!(matches!(
+            writer.get_series_writer::<i64>("d1", "s3"),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        assert!(matches!(
+            writer.get_series_writer::<i64>("d3", "s1"),
+            Err(TsFileError::SchemaError { .. })
+        ));
+
+        let mut s1 = writer.get_series_writer::<i64>("d1", "s1")?;
+        s1.write(2, 2)?;
+        s1.write(1, 1)?;
+        assert!(matches!(s1.flush(), Err(TsFileError::OutOfOrderData)));
+        // The buffer is empty after a failed flush
+        s1.flush()?;
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
-use crate::writer::utils::size_var_u32;
+use crate::writer::utils::{read_bytes, read_i32, read_i64, read_var_u32, size_var_u32};
 use crate::writer::{
-    write_var_u32, IoTDBValue, PositionedWrite, Serializable, TSDataType, TsFileError,
+    write_var_u32, IoTDBValue, IoTDBValues, PositionedWrite, Serializable, TSDataType, TsFileError,
 };
+use std::io::Read;
 
 #[derive(Clone, Debug)]
 #[allow(clippy::upper_case_acronyms)]
@@ -9,14 +10,97 @@ pub enum Statistics {
     INT32(StatisticsStruct<i32, i64>),
     INT64(StatisticsStruct<i64, f64>),
     FLOAT(StatisticsStruct<f32, f64>),
+    DOUBLE(StatisticsStruct<f64, f64>),
+    TEXT(BinaryStatistics),
+    /// Time column of aligned series (VECTOR)
+    TIME(TimeStatistics),
 }
 
 impl Statistics {
-    pub(crate) fn count(&self) -> u32 {
+    /// Number of points
+    pub fn count(&self) -> u32 {
         match self {
             Statistics::INT32(s) => s.count,
             Statistics::INT64(s) => s.count,
             Statistics::FLOAT(s) => s.count,
+            Statistics::DOUBLE(s) => s.count,
+            Statistics::TEXT(s) => s.count,
+            Statistics::TIME(s) => s.count,
+        }
+    }
+    /// Timestamp of the first point
+    pub fn start_time(&self) -> i64 {
+        match self {
+            Statistics::INT32(s) => s.ts_first,
+            Statistics::INT64(s) => s.ts_first,
+            Statistics::FLOAT(s) => s.ts_first,
+            Statistics::DOUBLE(s) => s.ts_first,
+            Statistics::TEXT(s) => s.ts_first,
+            Statistics::TIME(s) => s.ts_first,
+        }
+    }
+    /// Timestamp of the last point
+    pub fn end_time(&self) -> i64 {
+        match self {
+            Statistics::INT32(s) => s.ts_last,
+            Statistics::INT64(s) => s.ts_last,
+            Statistics::FLOAT(s) => s.ts_last,
+            Statistics::DOUBLE(s) => s.ts_last,
+            Statistics::TEXT(s) => s.ts_last,
+            Statistics::TIME(s) => s.ts_last,
+        }
+    }
+    /// Smallest value, not available for TEXT and TIME
+    pub fn min_value(&self) -> Option<IoTDBValue> {
+        match self {
+            Statistics::INT32(s) => Some(IoTDBValue::INT(s.min_value)),
+            Statistics::INT64(s) => Some(IoTDBValue::LONG(s.min_value)),
+            Statistics::FLOAT(s) => Some(IoTDBValue::FLOAT(s.min_value)),
+            Statistics::DOUBLE(s) => Some(IoTDBValue::DOUBLE(s.min_value)),
+            Statistics::TEXT(_) | Statistics::TIME(_) => None,
+        }
+    }
+    /// Largest value, not available for TEXT and TIME
+    pub fn max_value(&self) -> Option<IoTDBValue> {
+        match self {
+            Statistics::INT32(s) => Some(IoTDBValue::INT(s.max_value)),
+            Statistics::INT64(s) => Some(IoTDBValue::LONG(s.max_value)),
+            Statistics::FLOAT(s) => Some(IoTDBValue::FLOAT(s.max_value)),
+            Statistics::DOUBLE(s) => Some(IoTDBValue::DOUBLE(s.max_value)),
+            Statistics::TEXT(_) | Statistics::TIME(_) => None,
+        }
+    }
+    /// Value of the first point, not available for TIME
+    pub fn first_value(&self) -> Option<IoTDBValue> {
+        match self {
+            Statistics::INT32(s) => Some(IoTDBValue::INT(s.first_value)),
+            Statistics::INT64(s) => Some(IoTDBValue::LONG(s.first_value)),
+            Statistics::FLOAT(s) => Some(IoTDBValue::FLOAT(s.first_value)),
+            Statistics::DOUBLE(s) => Some(IoTDBValue::DOUBLE(s.first_value)),
+            Statistics::TEXT(s) => Some(IoTDBValue::TEXT(s.first_value.clone())),
+            Statistics::TIME(_) => None,
+        }
+    }
+    /// Value of the last point, not available for TIME
+    pub fn last_value(&self) -> Option<IoTDBValue> {
+        match self {
+            Statistics::INT32(s) => Some(IoTDBValue::INT(s.last_value)),
+            Statistics::INT64(s) => Some(IoTDBValue::LONG(s.last_value)),
+            Statistics::FLOAT(s) => Some(IoTDBValue::FLOAT(s.last_value)),
+            Statistics::DOUBLE(s) => Some(IoTDBValue::DOUBLE(s.last_value)),
+            Statistics::TEXT(s) => Some(IoTDBValue::TEXT(s.last_value.clone())),
+            Statistics::TIME(_) => None,
+        }
+    }
+    /// Sum of all values, LONG for INT32 and DOUBLE otherwise (like in Java).
+    /// Not available for TEXT and TIME
+    pub fn sum_value(&self) -> Option<IoTDBValue> {
+        match self {
+            Statistics::INT32(s) => Some(IoTDBValue::LONG(s.sum_value)),
+            Statistics::INT64(s) => Some(IoTDBValue::DOUBLE(s.sum_value)),
+            Statistics::FLOAT(s) => Some(IoTDBValue::DOUBLE(s.sum_value)),
+            Statistics::DOUBLE(s) => Some(IoTDBValue::DOUBLE(s.sum_value)),
+            Statistics::TEXT(_) | Statistics::TIME(_) => None,
         }
     }
     pub(crate) fn get_serialized_size(&self) -> u32 {
@@ -24,62 +108,143 @@ impl Statistics {
             Statistics::INT32(s) => s.get_serialized_size(),
             Statistics::INT64(s) => s.get_serialized_size(),
             Statistics::FLOAT(s) => s.get_serialized_size(),
+            Statistics::DOUBLE(s) => s.get_serialized_size(),
+            Statistics::TEXT(s) => s.get_serialized_size(),
+            Statistics::TIME(s) => s.get_serialized_size(),
         }
     }
 }
 
 impl Statistics {
-    pub(crate) fn update(&mut self, timestamp: i64, value: &IoTDBValue) {
+    pub(crate) fn update(&mut self, timestamp: i64, value: &IoTDBValue) -> Result<(), TsFileError> {
         match (self, value) {
             (Statistics::INT32(s), IoTDBValue::INT(v)) => s.update(timestamp, *v),
             (Statistics::INT64(s), IoTDBValue::LONG(v)) => s.update(timestamp, *v),
             (Statistics::FLOAT(s), IoTDBValue::FLOAT(v)) => s.update(timestamp, *v),
-            _ => todo!(),
+            (Statistics::DOUBLE(s), IoTDBValue::DOUBLE(v)) => s.update(timestamp, *v),
+            (Statistics::TEXT(s), IoTDBValue::TEXT(v)) => s.update(timestamp, v),
+            _ => return Err(TsFileError::WrongTypeForSeries),
+        }
+        Ok(())
+    }
+
+    /// Updates the statistics with all points of a batch, the timestamps belong to the values
+    pub(crate) fn update_batch(
+        &mut self,
+        timestamps: &[i64],
+        values: IoTDBValues,
+    ) -> Result<(), TsFileError> {
+        match (self, values) {
+            (Statistics::INT32(s), IoTDBValues::INT(v)) => s.update_batch(timestamps, v),
+            (Statistics::INT64(s), IoTDBValues::LONG(v)) => s.update_batch(timestamps, v),
+            (Statistics::FLOAT(s), IoTDBValues::FLOAT(v)) => s.update_batch(timestamps, v),
+            (Statistics::DOUBLE(s), IoTDBValues::DOUBLE(v)) => s.update_batch(timestamps, v),
+            _ => return Err(TsFileError::WrongTypeForSeries),
+        }
+        Ok(())
+    }
+
+    /// Updates statistics of a time column, the value is not needed
+    pub(crate) fn update_time(&mut self, timestamp: i64) -> Result<(), TsFileError> {
+        match self {
+            Statistics::TIME(s) => s.update(timestamp),
+            _ => {
+                return Err(TsFileError::IllegalState {
+                    source: Some("update_time is only available for TIME statistics".to_owned()),
+                })
+            }
         }
+        Ok(())
     }
 }
 
 impl Statistics {
-    pub(crate) fn merge(&mut self, other: &Statistics) {
-        match self {
-            Statistics::INT32(s) => match other {
-                Statistics::INT32(othr) => s.merge(othr),
-                _ => {
-                    panic!("...")
-                }
-            },
-            Statistics::INT64(s) => match other {
-                Statistics::INT64(othr) => s.merge(othr),
-                _ => {
-                    panic!("...")
-                }
-            },
-            Statistics::FLOAT(s) => match other {
-                Statistics::FLOAT(othr) => s.merge(othr),
-                _ => {
-                    panic!("...")
-                }
-            },
+    pub(crate) fn merge(&mut self, other: &Statistics) -> Result<(), TsFileError> {
+        match (self, other) {
+            (Statistics::INT32(s), Statistics::INT32(othr)) => s.merge(othr),
+            (Statistics::INT64(s), Statistics::INT64(othr)) => s.merge(othr),
+            (Statistics::FLOAT(s), Statistics::FLOAT(othr)) => s.merge(othr),
+            (Statistics::DOUBLE(s), Statistics::DOUBLE(othr)) => s.merge(othr),
+            (Statistics::TEXT(s), Statistics::TEXT(othr)) => s.merge(othr),
+            (Statistics::TIME(s), Statistics::TIME(othr)) => s.merge(othr),
+            _ => Err(TsFileError::IllegalState {
+                source: Some("Cannot merge statistics of different types".to_owned()),
+            }),
         }
     }
 }
 
+/// Statistics read from a file may be corrupt, so merging them must not overflow
+fn merge_count(count: u32, other: u32) -> Result<u32, TsFileError> {
+    count.checked_add(other).ok_or(TsFileError::Corrupt {
+        source: Some("Merged statistics exceed the maximal count".to_owned()),
+    })
+}
+
+/// Sum of the values, only the integer sum of INT32 statistics can overflow
+trait SumValue: Sized {
+    fn checked_sum(self, other: Self) -> Option<Self>;
+}
+
+impl SumValue for i64 {
+    fn checked_sum(self, other: i64) -> Option<i64> {
+        self.checked_add(other)
+    }
+}
+
+impl SumValue for f64 {
+    fn checked_sum(self, other: f64) -> Option<f64> {
+        Some(self + other)
+    }
+}
+
 impl Statistics {
     pub fn new(data_type: TSDataType) -> Statistics {
         match data_type {
             TSDataType::INT32 => Statistics::INT32(StatisticsStruct::<i32, i64>::new()),
             TSDataType::INT64 => Statistics::INT64(StatisticsStruct::<i64, f64>::new()),
             TSDataType::FLOAT => Statistics::FLOAT(StatisticsStruct::<f32, f64>::new()),
+            TSDataType::DOUBLE => Statistics::DOUBLE(StatisticsStruct::<f64, f64>::new()),
+            TSDataType::TEXT => Statistics::TEXT(BinaryStatistics::new()),
+            TSDataType::VECTOR => Statistics::TIME(TimeStatistics::new()),
         }
     }
 }
 
+impl Statistics {
+    /// Reads statistics as written by `serialize` for the given data type
+    pub(crate) fn deserialize(
+        data_type: TSDataType,
+        reader: &mut dyn Read,
+    ) -> Result<Statistics, TsFileError> {
+        Ok(match data_type {
+            TSDataType::INT32 => {
+                Statistics::INT32(StatisticsStruct::<i32, i64>::deserialize(reader)?)
+            }
+            TSDataType::INT64 => {
+                Statistics::INT64(StatisticsStruct::<i64, f64>::deserialize(reader)?)
+            }
+            TSDataType::FLOAT => {
+                Statistics::FLOAT(StatisticsStruct::<f32, f64>::deserialize(reader)?)
+            }
+            TSDataType::DOUBLE => {
+                Statistics::DOUBLE(StatisticsStruct::<f64, f64>::deserialize(reader)?)
+            }
+            TSDataType::TEXT => Statistics::TEXT(BinaryStatistics::deserialize(reader)?),
+            TSDataType::VECTOR => Statistics::TIME(TimeStatistics::deserialize(reader)?),
+        })
+    }
+}
+
 impl Serializable for Statistics {
     fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
         match self {
             Statistics::INT32(s) => s.serialize(file),
             Statistics::INT64(s) => s.serialize(file),
             Statistics::FLOAT(s) => s.serialize(file),
+            Statistics::DOUBLE(s) => s.serialize(file),
+            Statistics::TEXT(s) => s.serialize(file),
+            Statistics::TIME(s) => s.serialize(file),
         }
     }
 }
@@ -119,6 +284,12 @@ impl StatisticsStruct<f32, f64> {
     }
 }
 
+impl StatisticsStruct<f64, f64> {
+    pub(crate) fn get_serialized_size(&self) -> u32 {
+        size_var_u32(self.count) as u32 + 16 + 40
+    }
+}
+
 macro_rules! implement_statistics {
     ( $type:ty, $sum:ty ) => {
         impl StatisticsStruct<$type, $sum> {
@@ -135,12 +306,24 @@ macro_rules! implement_statistics {
                 }
             }
 
-            pub(crate) fn merge(&mut self, statistics: &StatisticsStruct<$type, $sum>) {
-                if statistics.ts_first < self.ts_first {
+            pub(crate) fn merge(
+                &mut self,
+                statistics: &StatisticsStruct<$type, $sum>,
+            ) -> Result<(), TsFileError> {
+                if statistics.count == 0 {
+                    return Ok(());
+                }
+                let count = merge_count(self.count, statistics.count)?;
+                let sum_value = self.sum_value.checked_sum(statistics.sum_value).ok_or(
+                    TsFileError::Corrupt {
+                        source: Some("Merged statistics exceed the maximal sum".to_owned()),
+                    },
+                )?;
+                if statistics.ts_first <= self.ts_first {
                     self.ts_first = statistics.ts_first;
                     self.first_value = statistics.first_value;
                 }
-                if statistics.ts_last > self.ts_last {
+                if statistics.ts_last >= self.ts_last {
                     self.ts_last = statistics.ts_last;
                     self.last_value = statistics.last_value;
                 }
@@ -150,8 +333,9 @@ macro_rules! implement_statistics {
                 if statistics.min_value < self.min_value {
                     self.min_value = statistics.min_value;
                 }
-                self.count += statistics.count;
-                self.sum_value += statistics.sum_value;
+                self.count = count;
+                self.sum_value = sum_value;
+                Ok(())
             }
 
             pub(crate) fn update(&mut self, timestamp: i64, value: $type) {
@@ -172,6 +356,45 @@ macro_rules! implement_statistics {
                 self.count += 1;
                 self.sum_value += value as $sum;
             }
+
+            pub(crate) fn update_batch(&mut self, timestamps: &[i64], values: &[$type]) {
+                for (&timestamp, &value) in timestamps.iter().zip(values) {
+                    self.update(timestamp, value);
+                }
+            }
+
+            pub(crate) fn deserialize(
+                reader: &mut dyn Read,
+            ) -> Result<StatisticsStruct<$type, $sum>, TsFileError> {
+                let count = read_var_u32(reader)?;
+                let ts_first = read_i64(reader)?;
+                let ts_last = read_i64(reader)?;
+
+                let mut value_buffer = [0_u8; std::mem::size_of::<$type>()];
+                let mut read_value = |reader: &mut dyn Read| -> Result<$type, TsFileError> {
+                    reader.read_exact(&mut value_buffer)?;
+                    Ok(<$type>::from_be_bytes(value_buffer))
+                };
+                let min_value = read_value(reader)?;
+                let max_value = read_value(reader)?;
+                let first_value = read_value(reader)?;
+                let last_value = read_value(reader)?;
+
+                let mut sum_buffer = [0_u8; std::mem::size_of::<$sum>()];
+                reader.read_exact(&mut sum_buffer)?;
+                let sum_value = <$sum>::from_be_bytes(sum_buffer);
+
+                Ok(StatisticsStruct {
+                    ts_first,
+                    ts_last,
+                    min_value,
+                    max_value,
+                    first_value,
+                    last_value,
+                    count,
+                    sum_value,
+                })
+            }
         }
 
         impl Serializable for StatisticsStruct<$type, $sum> {
@@ -196,5 +419,320 @@ macro_rules! implement_statistics {
 implement_statistics!(i32, i64);
 implement_statistics!(i64, f64);
 implement_statistics!(f32, f64);
-// TODO Implement / use
-// implement_statistics!(f64);
+implement_statistics!(f64, f64);
+
+/// Statistics for TEXT series, only first and last value are tracked
+#[derive(Clone, Debug)]
+pub struct BinaryStatistics {
+    ts_first: i64,
+    ts_last: i64,
+
+    first_value: String,
+    last_value: String,
+    count: u32,
+}
+
+impl BinaryStatistics {
+    pub(crate) fn new() -> BinaryStatistics {
+        BinaryStatistics {
+            ts_first: i64::MAX,
+            ts_last: i64::MIN,
+            first_value: String::new(),
+            last_value: String::new(),
+            count: 0,
+        }
+    }
+
+    pub(crate) fn get_serialized_size(&self) -> u32 {
+        size_var_u32(self.count) as u32
+            + 16
+            + 4
+            + self.first_value.len() as u32
+            + 4
+            + self.last_value.len() as u32
+    }
+
+    pub(crate) fn merge(&mut self, statistics: &BinaryStatistics) -> Result<(), TsFileError> {
+        if statistics.count == 0 {
+            return Ok(());
+        }
+        let count = merge_count(self.count, statistics.count)?;
+        if statistics.ts_first <= self.ts_first {
+            self.ts_first = statistics.ts_first;
+            self.first_value = statistics.first_value.clone();
+        }
+        if statistics.ts_last >= self.ts_last {
+            self.ts_last = statistics.ts_last;
+            self.last_value = statistics.last_value.clone();
+        }
+        self.count = count;
+        Ok(())
+    }
+
+    pub(crate) fn update(&mut self, timestamp: i64, value: &str) {
+        if timestamp < self.ts_first {
+            self.ts_first = timestamp;
+            self.first_value = value.to_owned();
+        }
+        if timestamp > self.ts_last {
+            self.ts_last = timestamp;
+            self.last_value = value.to_owned();
+        }
+        self.count += 1;
+    }
+
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<BinaryStatistics, TsFileError> {
+        let count = read_var_u32(reader)?;
+        let ts_first = read_i64(reader)?;
+        let ts_last = read_i64(reader)?;
+        let first_value = Self::read_binary(reader)?;
+        let last_value = Self::read_binary(reader)?;
+
+        Ok(BinaryStatistics {
+            ts_first,
+            ts_last,
+            first_value,
+            last_value,
+            count,
+        })
+    }
+
+    fn read_binary(reader: &mut dyn Read) -> Result<String, TsFileError> {
+        let length = read_i32(reader)?;
+        let bytes = read_bytes(reader, length.max(0) as usize)?;
+        String::from_utf8(bytes).map_err(|_| TsFileError::Corrupt {
+            source: Some("Binary statistics are not valid UTF-8".to_owned()),
+        })
+    }
+}
+
+impl Serializable for BinaryStatistics {
+    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
+        // Header for statistics
+        write_var_u32(self.count, file)?;
+        file.write_all(&self.ts_first.to_be_bytes())?;
+        file.write_all(&self.ts_last.to_be_bytes())?;
+
+        // Binaries are written with their length as i32
+        file.write_all(&(self.first_value.len() as i32).to_be_bytes())?;
+        file.write_all(self.first_value.as_bytes())?;
+        file.write_all(&(self.last_value.len() as i32).to_be_bytes())?;
+        file.write_all(self.last_value.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Statistics for the time column of aligned series, only times and count are tracked
+#[derive(Clone, Debug)]
+pub struct TimeStatistics {
+    ts_first: i64,
+    ts_last: i64,
+    count: u32,
+}
+
+impl TimeStatistics {
+    pub(crate) fn new() -> TimeStatistics {
+        TimeStatistics {
+            ts_first: i64::MAX,
+            ts_last: i64::MIN,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn get_serialized_size(&self) -> u32 {
+        size_var_u32(self.count) as u32 + 16
+    }
+
+    pub(crate) fn merge(&mut self, statistics: &TimeStatistics) -> Result<(), TsFileError> {
+        let count = merge_count(self.count, statistics.count)?;
+        self.ts_first = self.ts_first.min(statistics.ts_first);
+        self.ts_last = self.ts_last.max(statistics.ts_last);
+        self.count = count;
+        Ok(())
+    }
+
+    pub(crate) fn update(&mut self, timestamp: i64) {
+        self.ts_first = self.ts_first.min(timestamp);
+        self.ts_last = self.ts_last.max(timestamp);
+        self.count += 1;
+    }
+
+    pub(crate) fn deserialize(reader: &mut dyn Read) -> Result<TimeStatistics, TsFileError> {
+        let count = read_var_u32(reader)?;
+        let ts_first = read_i64(reader)?;
+        let ts_last = read_i64(reader)?;
+        Ok(TimeStatistics {
+            ts_first,
+            ts_last,
+            count,
+        })
+    }
+}
+
+impl Serializable for TimeStatistics {
+    fn serialize(&self, file: &mut dyn PositionedWrite) -> Result<(), TsFileError> {
+        write_var_u32(self.count, file)?;
+        file.write_all(&self.ts_first.to_be_bytes())?;
+        file.write_all(&self.ts_last.to_be_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::statistics::Statistics;
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::TsFileWriter;
+    use crate::writer::{IoTDBValue, Schema, Serializable, TSDataType};
+    use std::io::Cursor;
+
+    fn float_statistics(points: &[(i64, f32)]) -> Result<Statistics, TsFileError> {
+        let mut statistics = Statistics::new(TSDataType::FLOAT);
+        for (timestamp, value) in points {
+            statistics.update(*timestamp, &IoTDBValue::FLOAT(*value))?;
+        }
+        Ok(statistics)
+    }
+
+    fn assert_float_statistics(statistics: &Statistics, expected: &[(i64, f32)]) {
+        let values = expected.iter().map(|(_, v)| *v);
+        assert_eq!(statistics.count(), expected.len() as u32);
+        assert_eq!(statistics.start_time(), expected[0].0);
+        assert_eq!(statistics.end_time(), expected[expected.len() - 1].0);
+        assert_eq!(
+            statistics.min_value(),
+            Some(IoTDBValue::FLOAT(values.clone().fold(f32::MAX, f32::min)))
+        );
+        assert_eq!(
+            statistics.max_value(),
+            Some(IoTDBValue::FLOAT(values.clone().fold(f32::MIN, f32::max)))
+        );
+        assert_eq!(
+            statistics.first_value(),
+            Some(IoTDBValue::FLOAT(expected[0].1))
+        );
+        assert_eq!(
+            statistics.last_value(),
+            Some(IoTDBValue::FLOAT(expected[expected.len() - 1].1))
+        );
+        assert_eq!(
+            statistics.sum_value(),
+            Some(IoTDBValue::DOUBLE(values.map(|v| v as f64).sum()))
+        );
+    }
+
+    #[test]
+    fn update_merge_and_serialize() -> Result<(), TsFileError> {
+        let first = [(1, 3.5), (2, -1.25), (3, 7.0)];
+        let second = [(4, 0.5), (5, 9.75)];
+
+        let mut statistics = float_statistics(&first)?;
+        statistics.merge(&float_statistics(&second)?)?;
+        // Empty statistics do not change anything
+        statistics.merge(&Statistics::new(TSDataType::FLOAT))?;
+        let all: Vec<(i64, f32)> = first.iter().chain(second.iter()).cloned().collect();
+        assert_float_statistics(&statistics, &all);
+
+        let mut buffer: Vec<u8> = vec![];
+        statistics.serialize(&mut buffer)?;
+        // count, start and end time, min, max, first, last and a double sum
+        assert_eq!(buffer.len(), 1 + 16 + 4 * 4 + 8);
+        assert_eq!(buffer.len() as u32, statistics.get_serialized_size());
+        let deserialized = Statistics::deserialize(TSDataType::FLOAT, &mut buffer.as_slice())?;
+        assert_float_statistics(&deserialized, &all);
+
+        assert!(statistics
+            .merge(&Statistics::new(TSDataType::DOUBLE))
+            .is_err());
+        assert_eq!(
+            statistics.update(6, &IoTDBValue::INT(1)),
+            Err(TsFileError::WrongTypeForSeries)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn merge_corrupt_statistics() -> Result<(), TsFileError> {
+        // Maximal count and sum, e.g. of a damaged footer
+        let mut buffer = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F];
+        buffer.extend_from_slice(&[0; 16 + 4 * 4]);
+        buffer.extend_from_slice(&i64::MAX.to_be_bytes());
+        let corrupt = Statistics::deserialize(TSDataType::INT32, &mut buffer.as_slice())?;
+        assert_eq!(corrupt.count(), u32::MAX);
+
+        let mut statistics = Statistics::new(TSDataType::INT32);
+        statistics.update(1, &IoTDBValue::INT(1))?;
+        assert!(matches!(
+            statistics.clone().merge(&corrupt),
+            Err(TsFileError::Corrupt { .. })
+        ));
+        let mut sum_only = corrupt.clone();
+        if let Statistics::INT32(s) = &mut sum_only {
+            s.count = 1;
+        }
+        assert!(matches!(
+            statistics.merge(&sum_only),
+            Err(TsFileError::Corrupt { .. })
+        ));
+
+        let mut time = Statistics::new(TSDataType::VECTOR);
+        time.update_time(1)?;
+        let mut time_buffer = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F];
+        time_buffer.extend_from_slice(&[0; 16]);
+        let corrupt = Statistics::deserialize(TSDataType::VECTOR, &mut time_buffer.as_slice())?;
+        assert!(matches!(
+            time.merge(&corrupt),
+            Err(TsFileError::Corrupt { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn float_statistics_in_file() -> Result<(), TsFileError> {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::FLOAT,
+            TSEncoding::GORILLA,
+            CompressionType::UNCOMPRESSED,
+        );
+        let config = TsFileConfig::default().max_number_of_points_in_page(100);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        let points: Vec<(i64, f32)> = (0..1000)
+            .map(|i| (i, ((i * 37) % 101) as f32 - 50.5))
+            .collect();
+        for (i, (timestamp, value)) in points.iter().enumerate() {
+            writer.write("d1", "s1", *timestamp, IoTDBValue::FLOAT(*value))?;
+            // Two chunks
+            if i == 499 {
+                writer.flush()?;
+            }
+        }
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let metadata = reader.get_timeseries_metadata("d1", "s1")?.unwrap();
+        assert_float_statistics(&metadata.statistics, &points);
+        assert_eq!(metadata.chunk_metadata_list.len(), 2);
+        for (chunk_index, chunk_metadata) in metadata.chunk_metadata_list.iter().enumerate() {
+            let chunk_points = &points[chunk_index * 500..(chunk_index + 1) * 500];
+            assert_float_statistics(&chunk_metadata.statistics, chunk_points);
+
+            let chunk = reader.read_chunk(chunk_metadata)?;
+            for (page_index, page) in chunk.pages()?.iter().enumerate() {
+                let page_points = &chunk_points[page_index * 100..(page_index + 1) * 100];
+                match &page.header().statistics {
+                    Some(statistics) => assert_float_statistics(statistics, page_points),
+                    None => panic!("Chunks with multiple pages have page statistics"),
+                }
+            }
+        }
+        Ok(())
+    }
+}
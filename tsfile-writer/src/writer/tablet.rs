@@ -0,0 +1,301 @@
+//! Column oriented batches of rows for a single device, see `TsFileWriter::write_tablet`.
+//!
+//! Writing a tablet is much cheaper than writing the same rows one by one, as the schema
+//! is validated only once per tablet and the values are encoded column by column.
+use crate::writer::errors::TsFileError;
+use crate::writer::{IoTDBValue, TSDataType};
+
+/// Values of a single measurement within a `Tablet`
+pub struct TabletColumn<'a> {
+    pub(crate) measurement_id: &'a str,
+    values: Vec<IoTDBValue>,
+    /// `true` marks a null value (like the `BitMap` of the Java Tablet)
+    bitmap: Option<Vec<bool>>,
+}
+
+impl<'a> TabletColumn<'a> {
+    fn is_null(bitmap: &Option<Vec<bool>>, row: usize) -> bool {
+        match bitmap {
+            Some(bitmap) => bitmap[row],
+            None => false,
+        }
+    }
+
+    /// Value of the given row, `None` if it is null
+    pub(crate) fn get(&self, row: usize) -> Option<&IoTDBValue> {
+        if Self::is_null(&self.bitmap, row) {
+            return None;
+        }
+        self.values.get(row)
+    }
+
+    /// Checks that all non null values have the given type
+    pub(crate) fn check_type(&self, data_type: TSDataType) -> Result<(), TsFileError> {
+        for (row, value) in self.values.iter().enumerate() {
+            if !Self::is_null(&self.bitmap, row) && value.data_type() != data_type {
+                return Err(TsFileError::WrongTypeForSeries);
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes the column and returns all non null values with their row
+    pub(crate) fn into_values(self) -> impl Iterator<Item = (usize, IoTDBValue)> {
+        let bitmap = self.bitmap;
+        self.values
+            .into_iter()
+            .enumerate()
+            .filter(move |(row, _)| !Self::is_null(&bitmap, *row))
+    }
+}
+
+/// Batch of rows for a single device, stored column wise.
+///
+/// Every column contains exactly one value per timestamp, null values are marked in an
+/// (optional) bitmap. Timestamps have to be strictly increasing.
+pub struct Tablet<'a> {
+    pub(crate) timestamps: Vec<i64>,
+    pub(crate) columns: Vec<TabletColumn<'a>>,
+}
+
+impl<'a> Tablet<'a> {
+    pub fn new(timestamps: Vec<i64>) -> Tablet<'a> {
+        Tablet {
+            timestamps,
+            columns: vec![],
+        }
+    }
+
+    /// Adds a column without null values
+    pub fn add_column(
+        &mut self,
+        measurement_id: &'a str,
+        values: Vec<IoTDBValue>,
+    ) -> Result<&mut Tablet<'a>, TsFileError> {
+        self.push_column(measurement_id, values, None)
+    }
+
+    /// Adds a column with null values, `true` in the bitmap marks a null value.
+    /// The value at a null position is ignored, but has to be present.
+    pub fn add_column_with_bitmap(
+        &mut self,
+        measurement_id: &'a str,
+        values: Vec<IoTDBValue>,
+        bitmap: Vec<bool>,
+    ) -> Result<&mut Tablet<'a>, TsFileError> {
+        if bitmap.len() != self.timestamps.len() {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!(
+                    "Bitmap of {} has {} entries but the tablet has {} rows",
+                    measurement_id,
+                    bitmap.len(),
+                    self.timestamps.len()
+                )),
+            });
+        }
+        self.push_column(measurement_id, values, Some(bitmap))
+    }
+
+    fn push_column(
+        &mut self,
+        measurement_id: &'a str,
+        values: Vec<IoTDBValue>,
+        bitmap: Option<Vec<bool>>,
+    ) -> Result<&mut Tablet<'a>, TsFileError> {
+        if values.len() != self.timestamps.len() {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!(
+                    "Column {} has {} values but the tablet has {} rows",
+                    measurement_id,
+                    values.len(),
+                    self.timestamps.len()
+                )),
+            });
+        }
+        if self
+            .columns
+            .iter()
+            .any(|column| column.measurement_id == measurement_id)
+        {
+            return Err(TsFileError::IllegalState {
+                source: Some(format!("Duplicate column {}", measurement_id)),
+            });
+        }
+        self.columns.push(TabletColumn {
+            measurement_id,
+            values,
+            bitmap,
+        });
+        Ok(self)
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Checks that the timestamps are strictly increasing
+    pub(crate) fn check_timestamps(&self) -> Result<(), TsFileError> {
+        if self.timestamps.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(TsFileError::OutOfOrderData);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tablet::Tablet;
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::{IoTDBValue, Schema, TSDataType};
+    use std::io::Cursor;
+
+    fn schema(device: DeviceBuilder) -> Schema {
+        let mut device = device;
+        TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                device
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build()
+    }
+
+    /// 10k rows, s2 is null for every third row
+    fn tablet<'a>() -> Tablet<'a> {
+        let mut tablet = Tablet::new((0..10_000).collect());
+        tablet
+            .add_column("s1", (0..10_000).map(IoTDBValue::LONG).collect())
+            .unwrap()
+            .add_column_with_bitmap(
+                "s2",
+                (0..10_000).map(|i| IoTDBValue::DOUBLE(i as f64)).collect(),
+                (0..10_000).map(|i| i % 3 == 0).collect(),
+            )
+            .unwrap();
+        tablet
+    }
+
+    fn query(buffer: Vec<u8>, measurement: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(Cursor::new(buffer)).unwrap();
+        reader
+            .query("d1", measurement)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn write_tablet_equals_write() {
+        let mut writer =
+            TsFileWriter::new_from_writer(schema(DeviceBuilder::new()), vec![], Default::default())
+                .unwrap();
+        writer.write_tablet("d1", tablet()).unwrap();
+        writer.close().unwrap();
+
+        let mut expected =
+            TsFileWriter::new_from_writer(schema(DeviceBuilder::new()), vec![], Default::default())
+                .unwrap();
+        for i in 0..10_000 {
+            let mut values = vec![DataPoint::new("s1", IoTDBValue::LONG(i))];
+            if i % 3 != 0 {
+                values.push(DataPoint::new("s2", IoTDBValue::DOUBLE(i as f64)));
+            }
+            expected.write_many("d1", i, values).unwrap();
+        }
+        expected.close().unwrap();
+
+        assert_eq!(*writer.file_io_writer.out, *expected.file_io_writer.out);
+    }
+
+    #[test]
+    fn write_aligned_tablet() {
+        let mut writer = TsFileWriter::new_from_writer(
+            schema(DeviceBuilder::aligned()),
+            vec![],
+            Default::default(),
+        )
+        .unwrap();
+        writer.write_tablet("d1", tablet()).unwrap();
+        // Only s1
+        let mut tablet = Tablet::new(vec![10_000, 10_001]);
+        tablet
+            .add_column("s1", vec![IoTDBValue::LONG(1), IoTDBValue::LONG(2)])
+            .unwrap();
+        writer.write_tablet("d1", tablet).unwrap();
+        writer.close().unwrap();
+
+        let buffer = writer.into_inner();
+        assert_eq!(query(buffer.clone(), "s1").len(), 10_002);
+        let points = query(buffer, "s2");
+        assert_eq!(points.len(), 6_666);
+        assert_eq!(points[0], (1, IoTDBValue::DOUBLE(1.0)));
+    }
+
+    #[test]
+    fn invalid_tablets() {
+        let mut tablet = Tablet::new(vec![1, 2]);
+        assert!(tablet.add_column("s1", vec![IoTDBValue::LONG(1)]).is_err());
+        assert!(tablet
+            .add_column_with_bitmap(
+                "s1",
+                vec![IoTDBValue::LONG(1), IoTDBValue::LONG(2)],
+                vec![false]
+            )
+            .is_err());
+        tablet
+            .add_column("s1", vec![IoTDBValue::LONG(1), IoTDBValue::LONG(2)])
+            .unwrap();
+        assert!(tablet
+            .add_column("s1", vec![IoTDBValue::LONG(1), IoTDBValue::LONG(2)])
+            .is_err());
+
+        let mut writer =
+            TsFileWriter::new_from_writer(schema(DeviceBuilder::new()), vec![], Default::default())
+                .unwrap();
+
+        let mut tablet = Tablet::new(vec![1, 2]);
+        tablet
+            .add_column("s1", vec![IoTDBValue::LONG(1), IoTDBValue::INT(2)])
+            .unwrap();
+        assert_eq!(
+            writer.write_tablet("d1", tablet).err(),
+            Some(TsFileError::WrongTypeForSeries)
+        );
+
+        let mut tablet = Tablet::new(vec![2, 1]);
+        tablet
+            .add_column("s1", vec![IoTDBValue::LONG(1), IoTDBValue::LONG(2)])
+            .unwrap();
+        assert_eq!(
+            writer.write_tablet("d1", tablet).err(),
+            Some(TsFileError::OutOfOrderData)
+        );
+
+        let mut tablet = Tablet::new(vec![1]);
+        tablet.add_column("s3", vec![IoTDBValue::LONG(1)]).unwrap();
+        assert!(writer.write_tablet("d1", tablet).is_err());
+        let mut tablet = Tablet::new(vec![1]);
+        tablet.add_column("s1", vec![IoTDBValue::LONG(1)]).unwrap();
+        assert!(writer.write_tablet("d2", tablet).is_err());
+
+        // Nothing of the invalid tablets was written
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1)).unwrap();
+    }
+}
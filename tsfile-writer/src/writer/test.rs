@@ -59,7 +59,7 @@
 // }
 
 #[cfg(test)]
-mod test {
+mod tests {
     use std::env::current_dir;
 
     use crate::writer::test_utils::{validate_output, write_ts_file};
@@ -79,9 +79,10 @@ mod test {
 
         write_ts_file("target/benchmark.tsfile", schema, |writer| {
             for i in 0..30000001 {
-                writer.write("d1", "s", i, IoTDBValue::LONG(i));
+                writer.write("d1", "s", i, IoTDBValue::LONG(i)).unwrap();
             }
-        });
+        })
+        .unwrap();
 
         // Analyse the file
         let expected_structure = "---------------------
@@ -35,7 +35,7 @@ pub fn write_ts_file<'a, F: FnOnce(&mut TsFileWriter<WriteWrapper<File>>)>(
     test_code(&mut writer);
 
     // writer.flush();
-    writer.close();
+    writer.close()?;
 
     Ok(())
 }
@@ -0,0 +1,180 @@
+//! Conversion of points in time into the `i64` timestamps of a TsFile, see `Timestamp`
+use crate::writer::errors::TsFileError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Unit of the timestamps in a file, it has to match the `timestamp_precision` of the IoTDB
+/// instance that loads the file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimestampPrecision {
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampPrecision {
+    fn of_duration(self, duration: Duration) -> u128 {
+        match self {
+            TimestampPrecision::Milliseconds => duration.as_millis(),
+            TimestampPrecision::Microseconds => duration.as_micros(),
+            TimestampPrecision::Nanoseconds => duration.as_nanos(),
+        }
+    }
+}
+
+/// A point in time that the write methods of `TsFileWriter` accept. Plain `i64` values are
+/// written as they are, other types are converted with the `TimestampPrecision` of the
+/// writer's `TsFileConfig`, which fails for times that do not fit into an `i64`.
+pub trait Timestamp {
+    fn into_timestamp(self, precision: TimestampPrecision) -> Result<i64, TsFileError>;
+}
+
+impl Timestamp for i64 {
+    fn into_timestamp(self, _precision: TimestampPrecision) -> Result<i64, TsFileError> {
+        Ok(self)
+    }
+}
+
+impl Timestamp for SystemTime {
+    fn into_timestamp(self, precision: TimestampPrecision) -> Result<i64, TsFileError> {
+        let (duration, negative) = match self.duration_since(UNIX_EPOCH) {
+            Ok(duration) => (duration, false),
+            Err(e) => (e.duration(), true),
+        };
+        let value = precision.of_duration(duration);
+        let out_of_range = || TsFileError::Error {
+            source: Some(format!("{:?} is out of range for {:?}", self, precision)),
+        };
+        if negative {
+            // The magnitude of i64::MIN is one larger than i64::MAX
+            i64::try_from(value - 1)
+                .map(|value| -value - 1)
+                .map_err(|_| out_of_range())
+        } else {
+            i64::try_from(value).map_err(|_| out_of_range())
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono_crate::TimeZone> Timestamp for chrono_crate::DateTime<Tz> {
+    fn into_timestamp(self, precision: TimestampPrecision) -> Result<i64, TsFileError> {
+        match precision {
+            TimestampPrecision::Milliseconds => Ok(self.timestamp_millis()),
+            TimestampPrecision::Microseconds => Ok(self.timestamp_micros()),
+            TimestampPrecision::Nanoseconds => {
+                self.timestamp_nanos_opt()
+                    .ok_or_else(|| TsFileError::Error {
+                        source: Some(format!("{:?} is out of range for {:?}", self, precision)),
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::timestamp::{Timestamp, TimestampPrecision};
+    use crate::writer::ts_file_config::TsFileConfig;
+    use crate::writer::tsfile_writer::{DataPoint, TsFileWriter};
+    use crate::writer::TSDataType;
+    use std::io::Cursor;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::reader::tsfile_reader::TsFileReader;
+
+    #[test]
+    fn system_time() -> Result<(), TsFileError> {
+        let time = UNIX_EPOCH + Duration::new(1_650_000_000, 123_456_789);
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Milliseconds)?,
+            1_650_000_000_123
+        );
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Microseconds)?,
+            1_650_000_000_123_456
+        );
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Nanoseconds)?,
+            1_650_000_000_123_456_789
+        );
+        assert_eq!(
+            (UNIX_EPOCH - Duration::from_millis(5))
+                .into_timestamp(TimestampPrecision::Milliseconds)?,
+            -5
+        );
+        // Year 2262 and later do not fit into nanoseconds
+        let late = UNIX_EPOCH + Duration::from_secs(300 * 365 * 24 * 3600);
+        assert!(late
+            .into_timestamp(TimestampPrecision::Nanoseconds)
+            .is_err());
+        assert!(late
+            .into_timestamp(TimestampPrecision::Milliseconds)
+            .is_ok());
+        assert_eq!(17.into_timestamp(TimestampPrecision::Nanoseconds)?, 17);
+        let _ = SystemTime::now().into_timestamp(TimestampPrecision::Milliseconds)?;
+        Ok(())
+    }
+
+    #[test]
+    fn write_with_precision() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().timestamp_precision(TimestampPrecision::Microseconds);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        writer.write("d1", "s1", UNIX_EPOCH + Duration::from_millis(1), 1_i64)?;
+        writer.write_many(
+            "d1",
+            UNIX_EPOCH + Duration::from_millis(2),
+            [DataPoint::new("s1", 2_i64.into())],
+        )?;
+        // Plain timestamps are not converted
+        writer.write("d1", "s1", 3_000, 3_i64)?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let times: Vec<_> = reader
+            .query("d1", "s1")?
+            .map(|point| point.map(|(time, _)| time))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(times, vec![1_000, 2_000, 3_000]);
+        Ok(())
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono() -> Result<(), TsFileError> {
+        use chrono_crate::TimeZone;
+
+        let time = chrono_crate::Utc
+            .timestamp_opt(1_650_000_000, 123_456_789)
+            .unwrap();
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Milliseconds)?,
+            1_650_000_000_123
+        );
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Microseconds)?,
+            1_650_000_000_123_456
+        );
+        assert_eq!(
+            time.into_timestamp(TimestampPrecision::Nanoseconds)?,
+            1_650_000_000_123_456_789
+        );
+        Ok(())
+    }
+}
@@ -1,24 +1,291 @@
+//! Options of the writer, the defaults mirror the `TSFileConfig` of Apache IoTDB
+use crate::reader::VERSION_NUMBER;
+use crate::writer::encoding::TSEncoding;
+use crate::writer::errors::TsFileError;
+use crate::writer::timestamp::TimestampPrecision;
+
+/// Size of the buffer that sorts late arriving points of a device before they are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReorderWindow {
+    /// Points have to arrive in time order, this is the default
+    Disabled,
+    /// Keeps the given number of rows per device
+    Points(usize),
+    /// Keeps all rows that are at most this much older than the newest row of the device,
+    /// in the unit of the timestamps (e.g. `5 * 60 * 1000` for 5 minutes of milliseconds)
+    Time(i64),
+}
+
+/// What happens to points that are older than the reorder window, i.e. older than a point
+/// of the same device that was already written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LateDataPolicy {
+    /// The write fails with `TsFileError::OutOfOrderData`, this is the default
+    Error,
+    /// The point is silently discarded
+    Drop,
+    /// The point is kept aside and returned by `TsFileWriter::take_late_points`, e.g. to
+    /// write it to a separate unsequence file
+    Separate,
+}
+
+/// What happens if a point is written twice for the same device, measurement and timestamp
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    /// The write fails with `TsFileError::OutOfOrderData`, this is the default
+    Error,
+    /// The second point is silently discarded
+    KeepFirst,
+    /// The second point replaces the first one. Only rows that were not encoded yet can be
+    /// replaced, thus the writer keeps at least the newest row of every device in memory
+    /// (like a reorder window of one point)
+    OverwriteLast,
+}
+
 #[derive(Clone, Copy)]
 pub struct TsFileConfig {
     pub(crate) max_degree_of_index_node: usize,
+    pub(crate) bloom_filter: bool,
     pub(crate) bloom_filter_error_rate: f64,
-    pub(crate) min_bloom_filter_error_rate: f64,
-    pub(crate) max_bloom_filter_error_rate: f64,
-    pub(crate) minimal_size: i32,
-    pub(crate) maximal_hash_function_size: i32,
-    pub(crate) seeds: [u8; 8],
+    pub(crate) chunk_group_size_threshold: u32,
+    pub(crate) page_size_threshold: u32,
+    pub(crate) max_number_of_points_in_page: u32,
+    pub(crate) version: u8,
+    pub(crate) reorder_window: ReorderWindow,
+    pub(crate) late_data_policy: LateDataPolicy,
+    pub(crate) duplicate_policy: DuplicatePolicy,
+    pub(crate) write_resource: bool,
+    pub(crate) flush_threads: usize,
+    pub(crate) time_encoding: TSEncoding,
+    pub(crate) single_page_chunks: bool,
+    pub(crate) close_on_drop: bool,
+    pub(crate) temp_file: bool,
+    pub(crate) sync_on_flush: bool,
+    pub(crate) sync_on_close: bool,
+    pub(crate) timestamp_precision: TimestampPrecision,
+    pub(crate) write_ahead_log: bool,
+}
+
+impl TsFileConfig {
+    /// Memory budget (in bytes) for buffered data, if the estimated size of all chunk groups
+    /// exceeds it they are flushed to the file. Defaults to 128 MB.
+    pub fn chunk_group_size_threshold(mut self, bytes: u32) -> TsFileConfig {
+        self.chunk_group_size_threshold = bytes;
+        self
+    }
+
+    /// Pages are sealed once their estimated size (in bytes) exceeds the threshold.
+    /// Defaults to 64 KB.
+    pub fn page_size_threshold(mut self, bytes: u32) -> TsFileConfig {
+        self.page_size_threshold = bytes;
+        self
+    }
+
+    /// Pages are sealed once they contain this number of points. Defaults to 1048576.
+    pub fn max_number_of_points_in_page(mut self, points: u32) -> TsFileConfig {
+        self.max_number_of_points_in_page = points;
+        self
+    }
+
+    /// Expected false positive rate of the bloom filter of all paths in the file footer.
+    /// It is clamped to the range 0.01 - 0.1 when writing. Defaults to 0.05.
+    pub fn bloom_filter_error_rate(mut self, rate: f64) -> TsFileConfig {
+        self.bloom_filter_error_rate = rate;
+        self
+    }
+
+    /// Writes the bloom filter of all paths to the footer, which takes at least 32 bytes.
+    /// Readers without it have to look up paths in the metadata index. The filter is still
+    /// written if the file has properties, as they follow it in the footer. Defaults to `true`.
+    pub fn bloom_filter(mut self, enabled: bool) -> TsFileConfig {
+        self.bloom_filter = enabled;
+        self
+    }
+
+    /// Maximum number of children of a node in the metadata index tree. Defaults to 256.
+    pub fn max_degree_of_index_node(mut self, degree: usize) -> TsFileConfig {
+        self.max_degree_of_index_node = degree;
+        self
+    }
+
+    /// Version of the TsFile format that is written, only version 3 is supported.
+    pub fn version(mut self, version: u8) -> TsFileConfig {
+        self.version = version;
+        self
+    }
+
+    /// Buffers rows per device and sorts them by time before they are encoded, so points may
+    /// arrive out of order within the window. Defaults to `ReorderWindow::Disabled`.
+    pub fn reorder_window(mut self, window: ReorderWindow) -> TsFileConfig {
+        self.reorder_window = window;
+        self
+    }
+
+    /// Handling of points that arrive too late for the reorder window.
+    /// Defaults to `LateDataPolicy::Error`.
+    pub fn late_data_policy(mut self, policy: LateDataPolicy) -> TsFileConfig {
+        self.late_data_policy = policy;
+        self
+    }
+
+    /// Handling of points with the timestamp of an earlier point of the same series, for
+    /// aligned devices the timestamp of an earlier row. Defaults to `DuplicatePolicy::Error`.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> TsFileConfig {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Writes the `.resource` file that IoTDB needs to load the TsFile next to it on `close`.
+    /// Only used for writers of files, defaults to `false`.
+    pub fn write_resource(mut self, enabled: bool) -> TsFileConfig {
+        self.write_resource = enabled;
+        self
+    }
+
+    /// Number of threads that encode and compress the last pages of the series in parallel
+    /// when chunk groups are flushed, 0 for one per CPU core. Only used with the feature
+    /// `parallel`, otherwise flushes are single threaded. Defaults to 0.
+    pub fn flush_threads(mut self, threads: usize) -> TsFileConfig {
+        self.flush_threads = threads;
+        self
+    }
+
+    /// Encoding of the time column of aligned devices, `TS2DIFF` or `PLAIN`. Defaults to
+    /// `TS2DIFF`, which stores regular intervals in a few bits per point.
+    ///
+    /// Only the time chunks of aligned devices record their encoding, readers decode the time
+    /// column of other series as `TS2DIFF`. Writers with `PLAIN` fail with `UnsupportedFeature`
+    /// for non aligned devices.
+    pub fn time_encoding(mut self, encoding: TSEncoding) -> TsFileConfig {
+        self.time_encoding = encoding;
+        self
+    }
+
+    /// Chunks with a single page are marked as such and their page header omits the
+    /// statistics, which are the same as those of the chunk. This is what IoTDB writes.
+    /// Disabled, the page headers of all chunks contain statistics, so readers that work on
+    /// pages alone can prune them. Defaults to `true`.
+    pub fn single_page_chunks(mut self, enabled: bool) -> TsFileConfig {
+        self.single_page_chunks = enabled;
+        self
+    }
+
+    /// A `TsFileWriter` that is dropped without `close` flushes its data and writes the footer,
+    /// so a forgotten `close` does not leave an invalid file. Errors are logged as the drop
+    /// cannot return them. Disabled, only an error is logged. Defaults to `true`.
+    pub fn close_on_drop(mut self, enabled: bool) -> TsFileConfig {
+        self.close_on_drop = enabled;
+        self
+    }
+
+    /// Writers that create the file write into `<name>.tmp` and rename it to `<name>` when
+    /// `close` succeeds, so a partially written file never appears under its final name, e.g.
+    /// for daemons that pick up new files. Defaults to `false`.
+    pub fn temp_file(mut self, enabled: bool) -> TsFileConfig {
+        self.temp_file = enabled;
+        self
+    }
+
+    /// Writers that create the file sync it to the disk (`File::sync_data`) after every chunk
+    /// group, so flushed data survives a power loss. This slows down flushes considerably.
+    /// Defaults to `false`.
+    pub fn sync_on_flush(mut self, enabled: bool) -> TsFileConfig {
+        self.sync_on_flush = enabled;
+        self
+    }
+
+    /// Writers that create the file sync it to the disk (`File::sync_all`) on `close`, with
+    /// `temp_file` the directory is synced after the rename as well (on Unix). Defaults to
+    /// `false`.
+    pub fn sync_on_close(mut self, enabled: bool) -> TsFileConfig {
+        self.sync_on_close = enabled;
+        self
+    }
+
+    /// Unit of the timestamps that the writer converts `SystemTime` and (with the feature
+    /// `chrono`) `DateTime` values into, see `Timestamp`. Plain `i64` timestamps are written
+    /// as they are. Defaults to `TimestampPrecision::Milliseconds`, like IoTDB.
+    pub fn timestamp_precision(mut self, precision: TimestampPrecision) -> TsFileConfig {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    /// Writers that create the file log every point to `{file}.wal` before it is buffered, the
+    /// log is truncated after each flush and removed on `close`. `TsFileWriter::recover`
+    /// replays the points of the log that are not in the file, so a crash does not lose the
+    /// buffered data. Points that are still in the reorder window keep the log from being
+    /// truncated. Defaults to `false`.
+    pub fn write_ahead_log(mut self, enabled: bool) -> TsFileConfig {
+        self.write_ahead_log = enabled;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), TsFileError> {
+        if self.version != VERSION_NUMBER {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!("TsFile version {}", self.version)),
+            });
+        }
+        if self.max_degree_of_index_node < 2 {
+            return Err(TsFileError::IllegalState {
+                source: Some("The max degree of index nodes has to be at least 2".to_owned()),
+            });
+        }
+        if !(self.bloom_filter_error_rate > 0.0 && self.bloom_filter_error_rate < 1.0) {
+            return Err(TsFileError::IllegalState {
+                source: Some("The bloom filter error rate has to be between 0 and 1".to_owned()),
+            });
+        }
+        if self.page_size_threshold == 0 || self.max_number_of_points_in_page == 0 {
+            return Err(TsFileError::IllegalState {
+                source: Some("Pages have to contain at least one point".to_owned()),
+            });
+        }
+        match self.reorder_window {
+            ReorderWindow::Points(0) => {
+                return Err(TsFileError::IllegalState {
+                    source: Some("The reorder window has to contain at least one point".to_owned()),
+                })
+            }
+            ReorderWindow::Time(window) if window < 0 => {
+                return Err(TsFileError::IllegalState {
+                    source: Some("The reorder window must not be negative".to_owned()),
+                })
+            }
+            _ => {}
+        }
+        if !matches!(self.time_encoding, TSEncoding::TS2DIFF | TSEncoding::PLAIN) {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!("Time encoding {:?}", self.time_encoding)),
+            });
+        }
+        Ok(())
+    }
 }
 
 impl Default for TsFileConfig {
     fn default() -> Self {
         Self {
             max_degree_of_index_node: 256,
+            bloom_filter: true,
             bloom_filter_error_rate: 0.05,
-            min_bloom_filter_error_rate: 0.01,
-            max_bloom_filter_error_rate: 0.1,
-            minimal_size: 256,
-            maximal_hash_function_size: 8,
-            seeds: [5, 7, 11, 19, 31, 37, 43, 59],
+            chunk_group_size_threshold: 128 * 1024 * 1024,
+            page_size_threshold: 64 * 1024,
+            max_number_of_points_in_page: 1024 * 1024,
+            version: VERSION_NUMBER,
+            reorder_window: ReorderWindow::Disabled,
+            late_data_policy: LateDataPolicy::Error,
+            duplicate_policy: DuplicatePolicy::Error,
+            write_resource: false,
+            flush_threads: 0,
+            time_encoding: TSEncoding::TS2DIFF,
+            single_page_chunks: true,
+            close_on_drop: true,
+            temp_file: false,
+            sync_on_flush: false,
+            sync_on_close: false,
+            timestamp_precision: TimestampPrecision::Milliseconds,
+            write_ahead_log: false,
         }
     }
 }
@@ -1,33 +1,119 @@
+use crate::reader::metadata_reader::{DeviceSummary, TimeseriesSummary, TsFileSummary};
+use crate::writer::bloom_filter::BloomFilter;
 use crate::writer::chunk_writer::ChunkHeader;
 use crate::writer::errors::TsFileError;
+use crate::writer::listener::FlushListener;
+use crate::writer::metrics::WriterMetrics;
+use crate::writer::utils::write_var_u32;
 use crate::writer::{
-    BloomFilter, ChunkGroupHeader, ChunkGroupMetadata, ChunkMetadata, CompressionType,
+    write_str, ChunkGroupHeader, ChunkGroupMetadata, ChunkMetadata, CompressionType,
     MetadataIndexNode, Path, PositionedWrite, Serializable, Statistics, TSDataType, TSEncoding,
     TimeSeriesMetadata, TimeSeriesMetadatable, TsFileConfig, TsFileMetadata,
 };
 use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+
+/// The sink of a writer, it is only taken out by `into_inner`
+pub(crate) struct Sink<T>(Option<T>);
+
+impl<T> Sink<T> {
+    pub(crate) fn take(&mut self) -> Option<T> {
+        self.0.take()
+    }
+
+    pub(crate) fn is_taken(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+impl<T> Deref for Sink<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.as_ref().expect("Sink was taken by into_inner")
+    }
+}
+
+impl<T> DerefMut for Sink<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.0.as_mut().expect("Sink was taken by into_inner")
+    }
+}
+
+impl<T: Write> Write for Sink<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.deref_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deref_mut().flush()
+    }
+}
+
+impl<T: PositionedWrite> PositionedWrite for Sink<T> {
+    fn get_position(&self) -> u64 {
+        self.deref().get_position()
+    }
+}
 
 pub struct TsFileIoWriter<'a, T: PositionedWrite> {
-    config: TsFileConfig,
-    pub(crate) out: T,
+    pub(crate) config: TsFileConfig,
+    pub(crate) out: Sink<T>,
     current_chunk_group_device_id: Option<&'a str>,
     chunk_metadata_list: Vec<ChunkMetadata>,
     current_chunk_metadata: Option<ChunkMetadata>,
     chunk_group_metadata_list: Vec<ChunkGroupMetadata>,
     timeseries_metadata_map: BTreeMap<String, Vec<Box<dyn TimeSeriesMetadatable>>>,
+    pub(crate) properties: BTreeMap<String, String>,
+    pub(crate) listener: Option<Box<dyn FlushListener>>,
+    /// Handle to sync the written file, only set for writers of files
+    pub(crate) sync_file: Option<File>,
+    /// Start of the current chunk group
+    chunk_group_offset: u64,
+    /// Number of bits and hash functions of the bloom filter written on close
+    bloom_filter_size: Option<(u32, u32)>,
+    /// Offset of the `TsFileMetadata`, written on close
+    metadata_offset: u64,
 }
 
 impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
-    pub(crate) fn end_current_chunk(&mut self) {
-        match &self.current_chunk_metadata {
-            None => {
-                panic!("Something went wrong!");
-            }
+    pub(crate) fn end_current_chunk(&mut self) -> Result<(), TsFileError> {
+        match self.current_chunk_metadata.take() {
+            None => Err(TsFileError::IllegalState {
+                source: Some("end_current_chunk called without a started chunk".to_owned()),
+            }),
             Some(metadata) => {
-                self.chunk_metadata_list.push(metadata.clone());
+                self.chunk_metadata_list.push(metadata);
+                Ok(())
             }
         }
-        self.current_chunk_metadata = None;
+    }
+}
+
+/// Already serialized chunk (header and pages), e.g. copied from another file
+pub(crate) struct RawChunk {
+    pub(crate) measurement_id: String,
+    pub(crate) data_type: TSDataType,
+    pub(crate) statistics: Statistics,
+    pub(crate) mask: u8,
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
+    /// Appends the chunk to the current chunk group
+    pub(crate) fn write_raw_chunk(&mut self, chunk: RawChunk) -> Result<(), TsFileError> {
+        self.current_chunk_metadata = Some(ChunkMetadata::new(
+            chunk.measurement_id,
+            chunk.data_type,
+            self.out.get_position(),
+            chunk.statistics,
+            chunk.mask,
+        ));
+        self.out.write_all(&chunk.bytes)?;
+        self.end_current_chunk()
     }
 }
 
@@ -43,7 +129,8 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
         data_size: u32,
         num_pages: u32,
         mask: u8,
-    ) {
+    ) -> Result<(), TsFileError> {
+        let page_statistics = num_pages > 1 || !self.config.single_page_chunks;
         self.current_chunk_metadata = Some(ChunkMetadata::new(
             measurement_id.clone(),
             data_type,
@@ -57,10 +144,10 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
             data_type,
             compression,
             encoding,
-            num_pages,
+            page_statistics,
             mask,
         );
-        header.serialize(&mut self.out);
+        header.serialize(&mut self.out)
     }
 }
 
@@ -71,20 +158,49 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
     ) -> Result<TsFileIoWriter<'a, T>, TsFileError> {
         let mut io_writer = TsFileIoWriter {
             config,
-            out: writer,
+            out: Sink(Some(writer)),
             current_chunk_group_device_id: None,
             chunk_metadata_list: vec![],
             current_chunk_metadata: None,
             chunk_group_metadata_list: vec![],
             timeseries_metadata_map: BTreeMap::new(),
+            properties: BTreeMap::new(),
+            listener: None,
+            sync_file: None,
+            chunk_group_offset: 0,
+            bloom_filter_size: None,
+            metadata_offset: 0,
         };
         io_writer.start_file()?;
         Ok(io_writer)
     }
 
+    /// Continues a file whose chunk groups were written already, see `TsFileWriter::recover`
+    pub(crate) fn resume(
+        writer: T,
+        config: TsFileConfig,
+        chunk_group_metadata_list: Vec<ChunkGroupMetadata>,
+    ) -> TsFileIoWriter<'a, T> {
+        TsFileIoWriter {
+            config,
+            out: Sink(Some(writer)),
+            current_chunk_group_device_id: None,
+            chunk_metadata_list: vec![],
+            current_chunk_metadata: None,
+            chunk_group_metadata_list,
+            timeseries_metadata_map: BTreeMap::new(),
+            properties: BTreeMap::new(),
+            listener: None,
+            sync_file: None,
+            chunk_group_offset: 0,
+            bloom_filter_size: None,
+            metadata_offset: 0,
+        }
+    }
+
     fn start_file(&mut self) -> Result<(), TsFileError> {
         self.out.write_all("TsFile".as_bytes())?;
-        self.out.write_all(&[0x03])?;
+        self.out.write_all(&[self.config.version])?;
         Ok(())
     }
 
@@ -94,6 +210,10 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
             &device_id,
             self.out.get_position()
         );
+        self.chunk_group_offset = self.out.get_position();
+        if let Some(listener) = self.listener.as_mut() {
+            listener.before_chunk_group(device_id, self.chunk_group_offset);
+        }
         let chunk_group_header = ChunkGroupHeader::new(device_id);
         chunk_group_header.serialize(&mut self.out)?;
 
@@ -112,11 +232,11 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
     //     chunkMetadataList = null;
     //     out.flush();
     //   }
-    pub(crate) fn end_chunk_group(&mut self) {
-        if self.current_chunk_group_device_id == None || self.chunk_metadata_list.is_empty() {
-            return;
-        }
-        let device_id = self.current_chunk_group_device_id.unwrap();
+    pub(crate) fn end_chunk_group(&mut self) -> Result<(), TsFileError> {
+        let device_id = match self.current_chunk_group_device_id {
+            Some(device_id) if !self.chunk_metadata_list.is_empty() => device_id,
+            _ => return Ok(()),
+        };
         // for chunk_metadata in &self.chunk_metadata_list {
         //     self.chunk_group_metadata_list.get_mut(device_id.as_str()).unwrap().push(
         //         chunk_metadata.clone()
@@ -128,10 +248,122 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
         ));
         self.current_chunk_group_device_id = None;
         self.chunk_metadata_list.clear();
-        self.out.flush();
+        self.out.flush()?;
+        if self.config.sync_on_flush {
+            if let Some(file) = &self.sync_file {
+                file.sync_data()?;
+            }
+        }
+        if let Some(listener) = self.listener.as_mut() {
+            let size = self.out.get_position() - self.chunk_group_offset;
+            listener.after_chunk_group(device_id, self.chunk_group_offset, size);
+        }
+        Ok(())
     }
 
-    pub(crate) fn end_file(&mut self) {
+    /// Notifies the listener that all buffered data was flushed
+    pub(crate) fn end_flush(&mut self) {
+        if let Some(listener) = self.listener.as_mut() {
+            listener.after_flush(self.out.get_position());
+        }
+    }
+
+    /// Adds the points of the chunk groups written so far, the time chunks of aligned devices
+    /// are not counted
+    pub(crate) fn add_metrics(&self, metrics: &mut WriterMetrics) {
+        for group_metadata in &self.chunk_group_metadata_list {
+            for chunk_metadata in &group_metadata.chunk_metadata {
+                if !chunk_metadata.measurement_id.is_empty() {
+                    metrics.add_points(
+                        &group_metadata.device_id,
+                        &chunk_metadata.measurement_id,
+                        chunk_metadata.statistics.count() as u64,
+                    );
+                }
+            }
+        }
+        metrics.chunk_groups_flushed += self.chunk_group_metadata_list.len() as u64;
+        metrics.bytes_written += self.out.get_position();
+        if let Some((size, hash_functions)) = self.bloom_filter_size {
+            metrics.bloom_filter_size = Some(size);
+            metrics.bloom_filter_hash_functions = Some(hash_functions);
+        }
+    }
+
+    /// Whether a chunk group of the device was written already
+    pub(crate) fn has_chunk_group(&self, device_id: &str) -> bool {
+        self.current_chunk_group_device_id == Some(device_id)
+            || self
+                .chunk_group_metadata_list
+                .iter()
+                .any(|group_metadata| group_metadata.device_id == device_id)
+    }
+
+    /// Start and end time of every device in the chunk groups written so far
+    pub(crate) fn device_time_ranges(&self) -> BTreeMap<String, (i64, i64)> {
+        let mut ranges = BTreeMap::new();
+        for group_metadata in &self.chunk_group_metadata_list {
+            for chunk_metadata in &group_metadata.chunk_metadata {
+                let statistics = &chunk_metadata.statistics;
+                if statistics.count() == 0 {
+                    continue;
+                }
+                let range = ranges
+                    .entry(group_metadata.device_id.clone())
+                    .or_insert((statistics.start_time(), statistics.end_time()));
+                range.0 = range.0.min(statistics.start_time());
+                range.1 = range.1.max(statistics.end_time());
+            }
+        }
+        ranges
+    }
+
+    /// Devices and timeseries of all chunk groups, complete after `end_file`
+    pub(crate) fn summary(&self, path: Option<String>) -> Result<TsFileSummary, TsFileError> {
+        let mut devices: BTreeMap<&str, BTreeMap<&str, TimeseriesSummary>> = BTreeMap::new();
+        for group_metadata in &self.chunk_group_metadata_list {
+            let device = devices.entry(&group_metadata.device_id).or_default();
+            for chunk_metadata in &group_metadata.chunk_metadata {
+                let statistics = &chunk_metadata.statistics;
+                match device.get_mut(chunk_metadata.measurement_id.as_str()) {
+                    Some(timeseries) => {
+                        timeseries.chunk_count += 1;
+                        timeseries.statistics.merge(statistics)?;
+                        timeseries.start_time = timeseries.statistics.start_time();
+                        timeseries.end_time = timeseries.statistics.end_time();
+                    }
+                    None => {
+                        device.insert(
+                            &chunk_metadata.measurement_id,
+                            TimeseriesSummary {
+                                measurement_id: chunk_metadata.measurement_id.clone(),
+                                data_type: chunk_metadata.data_type,
+                                chunk_count: 1,
+                                start_time: statistics.start_time(),
+                                end_time: statistics.end_time(),
+                                statistics: statistics.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        Ok(TsFileSummary {
+            path,
+            file_size: self.out.get_position(),
+            metadata_offset: self.metadata_offset,
+            devices: devices
+                .into_iter()
+                .map(|(device, timeseries)| {
+                    DeviceSummary::new(device.to_owned(), timeseries.into_values().collect())
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn end_file(&mut self) -> Result<(), TsFileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing_crate::info_span!("end_file").entered();
         // Statistics
         // Fetch all metadata
         // self.chunk_group_metadata = self
@@ -151,12 +383,9 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
                 let path = Path {
                     path: device_path.clone(),
                 };
-                if !&chunk_metadata_map.contains_key(&path) {
-                    chunk_metadata_map.insert(path.clone(), vec![]);
-                }
                 chunk_metadata_map
-                    .get_mut(&path)
-                    .unwrap()
+                    .entry(path)
+                    .or_default()
                     .push(chunk_metadata.clone());
             }
         }
@@ -165,51 +394,83 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
         let meta_offset = self.out.get_position();
 
         // Write Marker 0x02
-        self.out.write_all(&[0x02]);
+        self.out.write_all(&[0x02])?;
 
-        let metadata_index_node = self.flush_metadata_index(&chunk_metadata_map);
+        #[cfg(feature = "tracing")]
+        let index_span =
+            tracing_crate::info_span!("metadata_index", series = chunk_metadata_map.len())
+                .entered();
+        let metadata_index_node = self.flush_metadata_index(&chunk_metadata_map)?;
+        #[cfg(feature = "tracing")]
+        drop(index_span);
 
         let ts_file_metadata = TsFileMetadata::new(Some(metadata_index_node), meta_offset);
 
         let footer_index = self.out.get_position();
+        self.metadata_offset = footer_index;
+
+        ts_file_metadata.serialize(&mut self.out)?;
+
+        // Readers take everything after the TsFileMetadata as bloom filter, so it can only be
+        // omitted if nothing else follows
+        if self.config.bloom_filter || !self.properties.is_empty() {
+            #[cfg(feature = "tracing")]
+            let bloom_filter_span = tracing_crate::info_span!("bloom_filter").entered();
+            let mut bloom_filter = BloomFilter::with_error_rate(
+                self.config.bloom_filter_error_rate,
+                chunk_metadata_map.len(),
+            );
+            for path in chunk_metadata_map.keys() {
+                bloom_filter.add(&path.path);
+            }
 
-        ts_file_metadata.serialize(&mut self.out);
-
-        // Now serialize the Bloom Filter ?!
-
-        let paths = chunk_metadata_map.keys().into_iter().cloned().collect();
-
-        let bloom_filter = BloomFilter::build(paths, &self.config);
+            bloom_filter.serialize(&mut self.out)?;
+            self.bloom_filter_size = Some((bloom_filter.size(), bloom_filter.hash_function_size()));
+            #[cfg(feature = "tracing")]
+            drop(bloom_filter_span);
+        }
 
-        bloom_filter.serialize(&mut self.out);
+        // User defined properties follow the bloom filter like in TsFile 2.0, older readers
+        // ignore the remaining bytes of the footer
+        if !self.properties.is_empty() {
+            write_var_u32(self.properties.len() as u32, &mut self.out)?;
+            for (key, value) in &self.properties {
+                write_str(&mut self.out, key)?;
+                write_str(&mut self.out, value)?;
+            }
+        }
 
         let size_of_footer = (self.out.get_position() - footer_index) as u32;
 
-        self.out.write_all(&size_of_footer.to_be_bytes());
+        self.out.write_all(&size_of_footer.to_be_bytes())?;
 
         // Footer
-        self.out.write_all("TsFile".as_bytes());
+        self.out.write_all("TsFile".as_bytes())?;
+        self.out.flush()?;
+        if self.config.sync_on_close {
+            if let Some(file) = &self.sync_file {
+                file.sync_all()?;
+            }
+        }
+        #[cfg(feature = "tracing")]
+        tracing_crate::debug!(bytes = self.out.get_position(), "Closed file");
+        if let Some(listener) = self.listener.as_mut() {
+            listener.after_close(self.out.get_position());
+        }
+        Ok(())
     }
 
     fn flush_metadata_index(
         &mut self,
         chunk_metadata_list: &BTreeMap<Path, Vec<ChunkMetadata>>,
-    ) -> MetadataIndexNode {
-        let mut last_path: Option<String> = None;
+    ) -> Result<MetadataIndexNode, TsFileError> {
+        // The map is ordered by the paths, so the timeseries metadata is in alphabetical order
         for (path, metadata) in chunk_metadata_list.iter() {
-            // TODO do we really need this check here?
-            // ensure that paths are printed in alphabetical order
-            match &last_path {
-                None => last_path = Some(path.path.clone()),
-                Some(p) => {
-                    assert!(
-                        p < &path.path,
-                        "Something went wrong.. footer was written in wrong order"
-                    );
-                }
-            }
-            // Now regular operation starts
-            let data_type = metadata.get(0).unwrap().data_type;
+            let first = match metadata.first() {
+                Some(first) => first,
+                None => continue,
+            };
+            let data_type = first.data_type;
             let serialize_statistic = metadata.len() > 1;
             let mut statistics = Statistics::new(data_type);
             let mut buffer: Vec<u8> = vec![];
@@ -219,11 +480,11 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
                     continue;
                 }
                 // Serialize
-                m.serialize(&mut buffer, serialize_statistic);
+                m.serialize(&mut buffer, serialize_statistic)?;
 
                 let statistic = &m.statistics;
                 // Update the statistics
-                statistics.merge(statistic);
+                statistics.merge(statistic)?;
             }
 
             // Build Timeseries Index
@@ -231,32 +492,23 @@ impl<'a, T: PositionedWrite> TsFileIoWriter<'a, T> {
                 time_series_metadata_type: match serialize_statistic {
                     true => 1,
                     false => 0,
-                } | metadata.get(0).unwrap().mask,
+                } | first.mask,
                 chunk_meta_data_list_data_size: buffer.len(),
-                measurement_id: metadata.get(0).unwrap().measurement_id.to_owned(),
+                measurement_id: first.measurement_id.to_owned(),
                 data_type,
                 statistics,
                 buffer,
             };
 
             // Add to the global struct
-            let split = path.path.split('.').collect::<Vec<&str>>();
-            let mut device_id = "".to_owned();
-            for i in 0..split.len() - 1 {
-                if i > 0 {
-                    device_id.push('.');
-                }
-                device_id.push_str(*split.get(i).unwrap());
-            }
-
-            if !self.timeseries_metadata_map.contains_key(&device_id) {
-                self.timeseries_metadata_map
-                    .insert(device_id.clone(), vec![]);
-            }
+            let device_id = match path.path.rfind('.') {
+                Some(index) => path.path[..index].to_owned(),
+                None => "".to_owned(),
+            };
 
             self.timeseries_metadata_map
-                .get_mut(&device_id)
-                .unwrap()
+                .entry(device_id)
+                .or_default()
                 .push(Box::new(timeseries_metadata));
         }
 
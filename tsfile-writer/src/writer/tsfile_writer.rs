@@ -1,20 +1,35 @@
 //! Contains the TsFileWriter as central class to write tsfiles
-use crate::writer::chunk_writer::ChunkWriter;
+use crate::reader::metadata_reader::TsFileSummary;
+use crate::writer::concurrent_writer::closed;
 use crate::writer::errors::TsFileError;
+#[cfg(feature = "parallel")]
+use crate::writer::group_writer::seal_in_parallel;
 use crate::writer::group_writer::GroupWriter;
-use crate::writer::ts_file_config::TsFileConfig;
-use crate::writer::tsfile_io_writer::TsFileIoWriter;
+use crate::writer::listener::FlushListener;
+use crate::writer::metrics::WriterMetrics;
+use crate::writer::record::TSRecord;
+use crate::writer::reorder::ReorderBuffer;
+use crate::writer::resource::TsFileResource;
+use crate::writer::schema::{check_device, schema_error};
+use crate::writer::series_writer::SeriesWriter;
+use crate::writer::tablet::Tablet;
+use crate::writer::timestamp::{Timestamp, TimestampPrecision};
+use crate::writer::ts_file_config::{DuplicatePolicy, LateDataPolicy, ReorderWindow, TsFileConfig};
+use crate::writer::tsfile_io_writer::{RawChunk, TsFileIoWriter};
+use crate::writer::wal::{wal_path, WalRecord, WriteAheadLog};
 use crate::writer::{
-    ChunkGroupMetadata, IoTDBValue, PositionedWrite, Schema, TimeSeriesMetadatable, WriteWrapper,
+    BatchValue, ChunkGroupMetadata, IoTDBValue, IoTDBValues, MeasurementGroup, PositionedWrite,
+    Schema, TSEncoding, TimeSeriesMetadatable, WriteWrapper, TIME_SERIES_MASK,
 };
-use std::borrow::Borrow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::fs::{create_dir_all, File};
-
-const CHUNK_GROUP_SIZE_THRESHOLD_BYTE: u32 = 128 * 1024 * 1024;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Class defined to hold a datapoint for writing into a given device.
 /// Consists of a measurement_id and a value
+#[derive(Clone, Debug, PartialEq)]
 pub struct DataPoint<'a> {
     pub(crate) measurement_id: &'a str,
     pub(crate) value: IoTDBValue,
@@ -29,6 +44,16 @@ impl<'a> DataPoint<'a> {
     }
 }
 
+/// A point that arrived too late for the reorder window and was kept aside because of
+/// `LateDataPolicy::Separate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatePoint<'a> {
+    pub device: &'a str,
+    pub measurement_id: &'a str,
+    pub timestamp: i64,
+    pub value: IoTDBValue,
+}
+
 /// Central class to write TsFiles
 /// a TsFileWriter always produces one file.
 /// The file is opened on creation of the TsFileWriter and finished on closing.
@@ -103,126 +128,795 @@ impl<'a> DataPoint<'a> {
 /// writer.write("d2", "s2", 1, IoTDBValue::FLOAT(14.0 as f32));
 /// ```
 pub struct TsFileWriter<'a, T: PositionedWrite> {
-    #[allow(dead_code)]
-    filename: String,
+    /// Empty if the writer does not write to a file
+    pub(crate) filename: String,
     pub(crate) file_io_writer: TsFileIoWriter<'a, T>,
     group_writers: BTreeMap<&'a str, GroupWriter<'a>>,
     #[allow(dead_code)]
     chunk_group_metadata: Vec<ChunkGroupMetadata>,
     #[allow(dead_code)]
-    timeseries_metadata_map: HashMap<String, Vec<Box<dyn TimeSeriesMetadatable>>>,
+    timeseries_metadata_map: BTreeMap<String, Vec<Box<dyn TimeSeriesMetadatable>>>,
     record_count: u32,
     record_count_for_next_mem_check: u32,
     non_aligned_timeseries_last_time_map: BTreeMap<&'a str, BTreeMap<&'a str, i64>>,
+    reorder_buffers: BTreeMap<&'a str, ReorderBuffer<'a>>,
+    late_points: Vec<LatePoint<'a>>,
     pub schema: Schema<'a>,
-    #[allow(dead_code)]
     config: TsFileConfig,
+    closed: bool,
+    pub(crate) wal: Option<WriteAheadLog>,
 }
 
 impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
-    pub fn close(&mut self) {
+    /// Flushes all data and writes the footer. Returns the devices and timeseries of the file
+    /// like `TsFileMetadataReader::summary`, without reading the file again.
+    pub fn close(&mut self) -> Result<TsFileSummary, TsFileError> {
+        self.check_open()?;
         log::info!("start close file");
-        self.flush_all_chunk_groups();
-        self.file_io_writer.end_file();
+        self.drain_reorder_buffers()?;
+        self.flush_all_chunk_groups()?;
+        self.file_io_writer.end_file()?;
+        self.closed = true;
+        if !self.filename.is_empty() {
+            commit_file(Path::new(&self.filename), &self.config)?;
+        }
+        if self.config.write_resource && !self.filename.is_empty() {
+            self.resource().write_file(&self.filename)?;
+        }
+        if self.wal.take().is_some() {
+            std::fs::remove_file(wal_path(&self.filename))?;
+        }
+        let path = Some(self.filename.clone()).filter(|filename| !filename.is_empty());
+        self.file_io_writer.summary(path)
+    }
+
+    /// Adds a user defined property to the file, e.g. the id of the source. Properties are
+    /// written to the footer on `close` and returned by `TsFileMetadataReader::properties`.
+    pub fn set_property(&mut self, key: &str, value: &str) {
+        self.file_io_writer
+            .properties
+            .insert(key.to_owned(), value.to_owned());
+    }
+
+    /// Sets the listener that is notified about every chunk group, flush and the close
+    pub fn set_flush_listener(&mut self, listener: impl FlushListener + 'static) {
+        self.file_io_writer.listener = Some(Box::new(listener));
+    }
+
+    /// Start and end time of every device that was flushed, after `close` this is the
+    /// content of the `.resource` file
+    pub fn resource(&self) -> TsFileResource {
+        TsFileResource::new(self.file_io_writer.device_time_ranges())
+    }
+
+    /// Returns the sink, e.g. the buffer with the written TsFile (after `close`). The file is
+    /// not finalized if `close` was not called before.
+    pub fn into_inner(mut self) -> T {
+        self.file_io_writer
+            .out
+            .take()
+            .expect("Sink is only taken by into_inner")
+    }
+
+    /// Writes, flushes and a second close fail once the footer is written
+    fn check_open(&self) -> Result<(), TsFileError> {
+        if self.closed {
+            return Err(closed());
+        }
+        Ok(())
+    }
+}
+
+/// A writer that is dropped without `close` finalizes the file, unless this was disabled with
+/// `TsFileConfig::close_on_drop`. Errors cannot be returned here and are logged.
+impl<'a, T: PositionedWrite> Drop for TsFileWriter<'a, T> {
+    fn drop(&mut self) {
+        if self.closed || self.file_io_writer.out.is_taken() {
+            return;
+        }
+        if !self.config.close_on_drop || std::thread::panicking() {
+            log::error!(
+                "TsFileWriter for '{}' was dropped without close, the file is incomplete",
+                self.filename
+            );
+            return;
+        }
+        log::warn!(
+            "TsFileWriter for '{}' was dropped without close, closing it",
+            self.filename
+        );
+        if let Err(e) = self.close() {
+            log::error!(
+                "Unable to close TsFileWriter for '{}' on drop, the file is incomplete: {:?}",
+                self.filename,
+                e
+            );
+        }
     }
 }
 
 impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
     pub fn write(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamp: impl Timestamp,
+        value: impl Into<IoTDBValue>,
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        let timestamp = timestamp.into_timestamp(self.config.timestamp_precision)?;
+        let value = value.into();
+        self.log(device, [(measurement_id, timestamp, &value)])?;
+        self.write_point(device, measurement_id, timestamp, value)
+    }
+
+    fn write_point(
         &mut self,
         device: &'a str,
         measurement_id: &'a str,
         timestamp: i64,
         value: IoTDBValue,
     ) -> Result<(), TsFileError> {
+        if !self.reorder_buffers.is_empty() {
+            return self.write_reordered(
+                device,
+                timestamp,
+                vec![DataPoint::new(measurement_id, value)],
+            );
+        }
         match self.group_writers.get_mut(device) {
             Some(group) => {
                 let records_written = group.write(measurement_id, timestamp, value)?;
                 self.record_count += records_written;
             }
             None => {
-                return Err(TsFileError::IllegalState {
+                return Err(TsFileError::SchemaError {
                     source: Some("No Group Writer found".to_owned()),
                 });
             }
         }
-        self.check_memory_size_and_may_flush_chunks();
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    /// Adds a device or new measurements of a device to the schema of the open writer. This is
+    /// only possible as long as no chunk group of the device was written. Measurements of an
+    /// aligned device can only be added before its first row.
+    pub fn register_timeseries(
+        &mut self,
+        device: &'a str,
+        group: MeasurementGroup<'a>,
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        check_device(device)?;
+        if self.file_io_writer.has_chunk_group(device) {
+            return Err(schema_error(format!(
+                "Device '{}' was already flushed, its schema cannot change",
+                device
+            )));
+        }
+        let existing = match self.schema.measurement_groups.get_mut(device) {
+            None => {
+                self.group_writers
+                    .insert(device, GroupWriter::new(device, &group, &self.config)?);
+                if let Some(window) = reorder_window(&self.config) {
+                    self.reorder_buffers.insert(
+                        device,
+                        ReorderBuffer::new(window, self.config.duplicate_policy),
+                    );
+                }
+                self.schema.measurement_groups.insert(device, group);
+                return Ok(());
+            }
+            Some(existing) => existing,
+        };
+        if existing.aligned != group.aligned {
+            return Err(schema_error(format!(
+                "Device '{}' is registered as {} device",
+                device,
+                if existing.aligned {
+                    "aligned"
+                } else {
+                    "non aligned"
+                }
+            )));
+        }
+        if let Some(measurement) = group
+            .measurement_schemas
+            .keys()
+            .find(|&measurement| existing.measurement_schemas.contains_key(measurement))
+        {
+            return Err(schema_error(format!(
+                "Measurement '{}' of device '{}' is already registered",
+                measurement, device
+            )));
+        }
+        let group_writer = match self.group_writers.get_mut(device) {
+            Some(group_writer) => group_writer,
+            None => {
+                return Err(TsFileError::IllegalState {
+                    source: Some(format!("No Group Writer found for device '{}'", device)),
+                })
+            }
+        };
+        if !group_writer.has_data() {
+            Arc::make_mut(&mut existing.measurement_schemas).extend(
+                group
+                    .measurement_schemas
+                    .iter()
+                    .map(|(&measurement, schema)| (measurement, schema.clone())),
+            );
+            *group_writer = GroupWriter::new(device, existing, &self.config)?;
+        } else if group.aligned {
+            return Err(TsFileError::UnsupportedFeature {
+                source: Some(format!(
+                    "Measurements cannot be added to the aligned device '{}' after its first row",
+                    device
+                )),
+            });
+        } else {
+            group_writer.add_measurements(&group.measurement_schemas, &self.config);
+            Arc::make_mut(&mut existing.measurement_schemas).extend(
+                group
+                    .measurement_schemas
+                    .iter()
+                    .map(|(&measurement, schema)| (measurement, schema.clone())),
+            );
+        }
         Ok(())
     }
 
     pub fn write_many(
+        &mut self,
+        device: &'a str,
+        timestamp: impl Timestamp,
+        values: impl IntoIterator<Item = DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        let timestamp = timestamp.into_timestamp(self.config.timestamp_precision)?;
+        if self.wal.is_some() {
+            let values: Vec<_> = values.into_iter().collect();
+            self.log(
+                device,
+                values
+                    .iter()
+                    .map(|point| (point.measurement_id, timestamp, &point.value)),
+            )?;
+            return self.write_many_points(device, timestamp, values);
+        }
+        self.write_many_points(device, timestamp, values)
+    }
+
+    fn write_many_points(
         &mut self,
         device: &'a str,
         timestamp: i64,
         values: impl IntoIterator<Item = DataPoint<'a>>,
     ) -> Result<(), TsFileError> {
+        if !self.reorder_buffers.is_empty() {
+            return self.write_reordered(device, timestamp, values.into_iter().collect());
+        }
         match self.group_writers.get_mut(device) {
             Some(group) => {
                 let records_written = group.write_many(timestamp, values)?;
                 self.record_count += records_written;
             }
-            None => return Err(TsFileError::IllegalState { source: None }),
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        }
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    /// Writes a row in which some measurements may be null. For aligned devices the nulls are
+    /// marked in the bitmaps of the value columns, for non aligned devices they are skipped.
+    /// A row without any value is not written, measurements of null values have to be in the
+    /// schema nevertheless.
+    pub fn write_many_nullable(
+        &mut self,
+        device: &'a str,
+        timestamp: impl Timestamp,
+        values: impl IntoIterator<Item = (&'a str, Option<IoTDBValue>)>,
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        let timestamp = timestamp.into_timestamp(self.config.timestamp_precision)?;
+        let group = match self.schema.measurement_groups.get(device) {
+            Some(group) => group,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        };
+        let mut points = vec![];
+        for (measurement_id, value) in values {
+            match value {
+                Some(value) => points.push(DataPoint::new(measurement_id, value)),
+                None if group.measurement_schemas.contains_key(measurement_id) => {}
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some("Unknown measurement id".to_owned()),
+                    })
+                }
+            }
+        }
+        if points.is_empty() {
+            return Ok(());
+        }
+        self.write_many(device, timestamp, points)
+    }
+
+    /// Writes all points of the record as one row of its device, like `write_many`. A record
+    /// without points is not written.
+    pub fn write_record(&mut self, record: TSRecord<'a>) -> Result<(), TsFileError> {
+        self.check_open()?;
+        if record.points.is_empty() {
+            return Ok(());
+        }
+        self.write_many(record.device, record.timestamp, record.points)
+    }
+
+    /// Writes rows of several devices at once, e.g. all values of one poll cycle. The rows are
+    /// sorted by device (rows of the same device keep their order), so the group writer of a
+    /// device is looked up once per call instead of once per row, and the memory budget is
+    /// checked once after all rows. Fails before writing anything if a device is unknown, a
+    /// row that fails otherwise does not undo the rows written before it.
+    pub fn write_row_group<S: Timestamp>(
+        &mut self,
+        rows: Vec<(&'a str, S, Vec<DataPoint<'a>>)>,
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        let precision = self.config.timestamp_precision;
+        let mut rows = rows
+            .into_iter()
+            .map(|(device, timestamp, points)| {
+                Ok((device, timestamp.into_timestamp(precision)?, points))
+            })
+            .collect::<Result<Vec<_>, TsFileError>>()?;
+        rows.sort_by_key(|(device, _, _)| *device);
+        let mut previous = None;
+        for (device, _, _) in &rows {
+            if previous != Some(*device) && !self.group_writers.contains_key(device) {
+                return Err(TsFileError::SchemaError {
+                    source: Some(format!("No Group Writer found for device '{}'", device)),
+                });
+            }
+            previous = Some(*device);
+        }
+        if let Some(wal) = self.wal.as_mut() {
+            for (device, timestamp, points) in &rows {
+                for point in points {
+                    wal.add(device, point.measurement_id, *timestamp, &point.value)?;
+                }
+            }
+            wal.commit()?;
+        }
+        if !self.reorder_buffers.is_empty() {
+            for (device, timestamp, points) in rows {
+                self.write_reordered(device, timestamp, points)?;
+            }
+            return Ok(());
+        }
+
+        let mut rows = rows.into_iter().peekable();
+        while let Some((device, timestamp, points)) = rows.next() {
+            let group = match self.group_writers.get_mut(device) {
+                Some(group) => group,
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some("No Group Writer found".to_owned()),
+                    })
+                }
+            };
+            self.record_count += group.write_many(timestamp, points)?;
+            while let Some((_, timestamp, points)) = rows.next_if(|row| row.0 == device) {
+                self.record_count += group.write_many(timestamp, points)?;
+            }
+        }
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    /// Writes the points of a single series from a slice of timestamps and a slice of values
+    /// of the same length. The points are encoded at once, which is much faster than writing
+    /// them one by one. Batches that are not in time order, contain duplicates or belong to
+    /// an aligned device or the reorder window are written point by point, thus a failing
+    /// point does not undo the points before it.
+    pub fn write_batch<V: BatchValue>(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+        timestamps: &[i64],
+        values: &[V],
+    ) -> Result<(), TsFileError> {
+        self.check_open()?;
+        let values = V::values(values);
+        check_batch(timestamps, values)?;
+        if self.wal.is_some() {
+            let points: Vec<_> = (0..timestamps.len()).map(|i| values.get(i)).collect();
+            self.log(
+                device,
+                timestamps
+                    .iter()
+                    .zip(&points)
+                    .map(|(&timestamp, value)| (measurement_id, timestamp, value)),
+            )?;
+        }
+        let group = match self.group_writers.get_mut(device) {
+            Some(group) => group,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        };
+        if !self.reorder_buffers.is_empty() || !group.can_write_batch(measurement_id, timestamps) {
+            for (i, &timestamp) in timestamps.iter().enumerate() {
+                self.write_point(device, measurement_id, timestamp, values.get(i))?;
+            }
+            return Ok(());
+        }
+        self.record_count += group.write_batch(measurement_id, timestamps, values)?;
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    /// Typed handle to write the points of a single series, the data type of the series has to
+    /// match `V`. See `SeriesWriter`.
+    pub fn get_series_writer<V: BatchValue>(
+        &mut self,
+        device: &'a str,
+        measurement_id: &'a str,
+    ) -> Result<SeriesWriter<'_, 'a, T, V>, TsFileError> {
+        self.check_open()?;
+        let schema = match self.schema.measurement_groups.get(device) {
+            Some(group) => group.measurement_schemas.get(measurement_id),
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        };
+        match schema {
+            Some(schema) if schema.data_type == V::values(&[]).data_type() => {
+                Ok(SeriesWriter::new(self, device, measurement_id))
+            }
+            Some(_) => Err(TsFileError::WrongTypeForSeries),
+            None => Err(TsFileError::SchemaError {
+                source: Some("Unknown measurement id".to_owned()),
+            }),
+        }
+    }
+
+    /// Writes all rows of the tablet to the given device, this is much faster than writing
+    /// the rows one by one. Nothing is written if the tablet does not match the schema.
+    /// Tablets bypass the reorder window, rows of the device buffered before are written first.
+    pub fn write_tablet(&mut self, device: &'a str, tablet: Tablet) -> Result<(), TsFileError> {
+        self.check_open()?;
+        if self.wal.is_some() {
+            let points = tablet
+                .timestamps
+                .iter()
+                .enumerate()
+                .flat_map(|(row, &timestamp)| {
+                    tablet.columns.iter().filter_map(move |column| {
+                        column
+                            .get(row)
+                            .map(|value| (column.measurement_id, timestamp, value))
+                    })
+                });
+            if let Some(wal) = self.wal.as_mut() {
+                for (measurement_id, timestamp, value) in points {
+                    wal.add(device, measurement_id, timestamp, value)?;
+                }
+                wal.commit()?;
+            }
+        }
+        if let Some(buffer) = self.reorder_buffers.get_mut(device) {
+            let rows = buffer.drain();
+            self.write_rows(device, rows)?;
+        }
+        match self.group_writers.get_mut(device) {
+            Some(group) => {
+                let last_timestamp = tablet.timestamps.last().copied();
+                let records_written = group.write_tablet(tablet)?;
+                self.record_count += records_written;
+                if let (Some(buffer), Some(timestamp)) =
+                    (self.reorder_buffers.get_mut(device), last_timestamp)
+                {
+                    buffer.advance(timestamp);
+                }
+            }
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                });
+            }
+        }
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    /// Flushes all buffered data as chunk groups to the file, this includes the rows in the
+    /// reorder window
+    pub fn flush(&mut self) -> Result<(), TsFileError> {
+        self.check_open()?;
+        self.drain_reorder_buffers()?;
+        self.flush_all_chunk_groups()?;
+        Ok(())
+    }
+
+    /// Checkpoint of a long running writer: flushes all buffered data like `flush` and syncs
+    /// the file to the disk, regardless of `TsFileConfig::sync_on_flush`, before the write
+    /// ahead log is truncated. The writer stays open and accepts newer points of all series.
+    /// The chunk groups written so far are kept by `TsFileWriter::recover` if the process dies
+    /// before `close`. Returns the number of bytes that are written, i.e. the end of the last
+    /// chunk group.
+    pub fn flush_open(&mut self) -> Result<u64, TsFileError> {
+        self.check_open()?;
+        self.drain_reorder_buffers()?;
+        self.write_chunk_groups()?;
+        if let Some(file) = &self.file_io_writer.sync_file {
+            file.sync_data()?;
+        }
+        self.truncate_wal()?;
+        Ok(self.file_io_writer.out.get_position())
+    }
+
+    /// Returns the current counters of the writer. Points in the reorder window are counted
+    /// once they leave it.
+    pub fn metrics(&mut self) -> WriterMetrics {
+        let mut metrics = WriterMetrics::default();
+        self.file_io_writer.add_metrics(&mut metrics);
+        for group_writer in self.group_writers.values() {
+            group_writer.add_metrics(&mut metrics);
+        }
+        metrics.buffered_bytes = self.calculate_mem_size_for_all_groups() as u64;
+        metrics
+    }
+
+    /// Returns the points that arrived too late for the reorder window with
+    /// `LateDataPolicy::Separate`, in the order of arrival
+    pub fn take_late_points(&mut self) -> Vec<LatePoint<'a>> {
+        std::mem::take(&mut self.late_points)
+    }
+
+    fn write_reordered(
+        &mut self,
+        device: &'a str,
+        timestamp: i64,
+        points: Vec<DataPoint<'a>>,
+    ) -> Result<(), TsFileError> {
+        let buffer = match self.reorder_buffers.get_mut(device) {
+            Some(buffer) => buffer,
+            None => {
+                return Err(TsFileError::SchemaError {
+                    source: Some("No Group Writer found".to_owned()),
+                })
+            }
+        };
+        if buffer.is_late(timestamp) {
+            return match self.config.late_data_policy {
+                LateDataPolicy::Error => Err(TsFileError::OutOfOrderData),
+                LateDataPolicy::Drop => Ok(()),
+                LateDataPolicy::Separate => {
+                    self.late_points
+                        .extend(points.into_iter().map(|point| LatePoint {
+                            device,
+                            measurement_id: point.measurement_id,
+                            timestamp,
+                            value: point.value,
+                        }));
+                    Ok(())
+                }
+            };
+        }
+        if buffer.is_released(timestamp) {
+            self.write_rows(device, vec![(timestamp, points)])?;
+            return Ok(());
+        }
+        buffer.insert(timestamp, points)?;
+        let rows = buffer.pop_ready();
+        self.write_rows(device, rows)?;
+        self.check_memory_size_and_may_flush_chunks()?;
+        Ok(())
+    }
+
+    fn write_rows(
+        &mut self,
+        device: &'a str,
+        rows: Vec<(i64, Vec<DataPoint<'a>>)>,
+    ) -> Result<(), TsFileError> {
+        if let Some(group) = self.group_writers.get_mut(device) {
+            for (timestamp, points) in rows {
+                self.record_count += group.write_many(timestamp, points)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn drain_reorder_buffers(&mut self) -> Result<(), TsFileError> {
+        let devices: Vec<&'a str> = self.reorder_buffers.keys().copied().collect();
+        for device in devices {
+            if let Some(buffer) = self.reorder_buffers.get_mut(device) {
+                let rows = buffer.drain();
+                self.write_rows(device, rows)?;
+            }
         }
-        self.check_memory_size_and_may_flush_chunks();
         Ok(())
     }
 
+    /// Writes already serialized chunks as a new chunk group of the device. Buffered data is
+    /// flushed before, so the chunks of every timeseries stay in time order. Chunks without
+    /// points are skipped like those of the chunk writers, e.g. of files written by IoTDB.
+    pub(crate) fn write_raw_chunk_group(
+        &mut self,
+        device: &'a str,
+        chunks: Vec<RawChunk>,
+    ) -> Result<(), TsFileError> {
+        let chunks: Vec<RawChunk> = chunks
+            .into_iter()
+            .filter(|chunk| chunk.statistics.count() > 0)
+            .collect();
+        // The time chunk of an aligned device is no series of its own
+        if chunks.iter().all(|chunk| chunk.mask == TIME_SERIES_MASK) {
+            return Ok(());
+        }
+        self.flush()?;
+        self.file_io_writer.start_chunk_group(device)?;
+        for chunk in chunks {
+            self.file_io_writer.write_raw_chunk(chunk)?;
+        }
+        self.file_io_writer.end_chunk_group()
+    }
+
     fn check_memory_size_and_may_flush_chunks(&mut self) -> Result<bool, TsFileError> {
         if self.record_count >= self.record_count_for_next_mem_check {
-            let mem_size = self.calculate_mem_size_for_all_groups();
+            let threshold = self.config.chunk_group_size_threshold;
+            let mem_size = self.calculate_mem_size_for_all_groups().max(1);
             log::trace!("Memcount calculated: {}", mem_size);
             log::trace!(
                 "{:.2?}% - {} / {} for flushing",
-                mem_size as f64 / CHUNK_GROUP_SIZE_THRESHOLD_BYTE as f64 * 100.0,
+                mem_size as f64 / threshold as f64 * 100.0,
                 mem_size,
-                CHUNK_GROUP_SIZE_THRESHOLD_BYTE
+                threshold
+            );
+            // in the java impl there can be an overflow...
+            self.record_count_for_next_mem_check = ((self.record_count_for_next_mem_check as u64
+                * threshold as u64
+                / mem_size as u64) as u32)
+                .max(1);
+            log::trace!(
+                "Next record count for check {}",
+                self.record_count_for_next_mem_check
             );
-            if mem_size > CHUNK_GROUP_SIZE_THRESHOLD_BYTE {
-                self.record_count_for_next_mem_check = (self.record_count_for_next_mem_check as u64
-                    * CHUNK_GROUP_SIZE_THRESHOLD_BYTE as u64
-                    / mem_size as u64)
-                    as u32;
+            if mem_size > threshold {
                 return self.flush_all_chunk_groups();
-            } else {
-                // println!("Record Count: {}, CHUNK_GROUP_SIZE_THRESHOLD_BYTE: {}, memsize: {}", self.record_count_for_next_mem_check, CHUNK_GROUP_SIZE_THRESHOLD_BYTE, mem_size);
-                // in the java impl there can be an overflow...
-                self.record_count_for_next_mem_check = (self.record_count_for_next_mem_check as u64
-                    * CHUNK_GROUP_SIZE_THRESHOLD_BYTE as u64
-                    / mem_size as u64)
-                    as u32;
-                log::trace!(
-                    "Next record count for check {}",
-                    self.record_count_for_next_mem_check
-                );
-                return Ok(false);
             }
         }
         Ok(false)
     }
 
     fn flush_all_chunk_groups(&mut self) -> Result<bool, TsFileError> {
+        self.write_chunk_groups()?;
+        self.truncate_wal()?;
+        Ok(true)
+    }
+
+    fn write_chunk_groups(&mut self) -> Result<(), TsFileError> {
         if self.record_count > 0 {
+            #[cfg(feature = "tracing")]
+            let _span = tracing_crate::info_span!("flush", records = self.record_count).entered();
+            #[cfg(feature = "parallel")]
+            seal_in_parallel(self.group_writers.values_mut(), self.config.flush_threads)?;
             for (&device_id, group_writer) in self.group_writers.iter_mut() {
-                // self.file_writer.start_chunk_group(device_id);
-                // self.file_writer
-                self.file_io_writer.start_chunk_group(device_id)?;
-                let pos = self.file_io_writer.out.get_position();
-                let data_size = group_writer.flush_to_filewriter(&mut self.file_io_writer);
-
-                if self.file_io_writer.out.get_position() - pos != data_size {
-                    return Err(TsFileError::IllegalState {
-                        source: Some("Bytes written are not as expected!".to_owned()),
-                    });
+                if flush_group(&mut self.file_io_writer, device_id, group_writer)? {
+                    self.non_aligned_timeseries_last_time_map
+                        .insert(device_id, group_writer.get_last_time_map());
                 }
+            }
+            self.file_io_writer.end_flush();
+            self.reset();
+        }
+        Ok(())
+    }
+
+    fn truncate_wal(&mut self) -> Result<(), TsFileError> {
+        if let Some(wal) = self.wal.as_mut() {
+            // Points in the reorder window are not in the file yet
+            if self
+                .reorder_buffers
+                .values()
+                .all(|buffer| buffer.is_empty())
+            {
+                wal.truncate()?;
+            }
+        }
+        Ok(())
+    }
 
-                self.file_io_writer.end_chunk_group();
+    /// Appends the points of a write to the write ahead log, if there is one
+    fn log<'p>(
+        &mut self,
+        device: &str,
+        points: impl IntoIterator<Item = (&'p str, i64, &'p IoTDBValue)>,
+    ) -> Result<(), TsFileError> {
+        if let Some(wal) = self.wal.as_mut() {
+            for (measurement_id, timestamp, value) in points {
+                wal.add(device, measurement_id, timestamp, value)?;
+            }
+            wal.commit()?;
+        }
+        Ok(())
+    }
 
-                self.non_aligned_timeseries_last_time_map
-                    .insert(device_id, group_writer.get_last_time_map());
+    /// Writes the points of a write ahead log, see `read_wal`. Points that are not newer than
+    /// the last point of their series (e.g. as they were flushed to the file before a crash)
+    /// are skipped, as are points that failed when they were logged, e.g. because of their
+    /// data type. Returns the number of points that were written.
+    pub fn replay(
+        &mut self,
+        records: impl IntoIterator<Item = WalRecord>,
+    ) -> Result<usize, TsFileError> {
+        self.check_open()?;
+        let mut replayed = 0;
+        for record in records {
+            let (device, measurement_id) = match self
+                .schema
+                .measurement_groups
+                .get_key_value(record.device.as_str())
+                .and_then(|(&device, group)| {
+                    group
+                        .measurement_schemas
+                        .get_key_value(record.measurement_id.as_str())
+                        .map(|(&measurement_id, _)| (device, measurement_id))
+                }) {
+                Some(path) => path,
+                None => {
+                    log::warn!(
+                        "Skipping point of unknown series {}.{}",
+                        record.device,
+                        record.measurement_id
+                    );
+                    continue;
+                }
+            };
+            let last_time = self
+                .group_writers
+                .get(device)
+                .and_then(|group| group.last_time(measurement_id));
+            if last_time.is_some() && last_time >= Some(record.timestamp) {
+                continue;
+            }
+            match self.write(device, measurement_id, record.timestamp, record.value) {
+                Ok(()) => replayed += 1,
+                Err(TsFileError::WrongTypeForSeries) | Err(TsFileError::OutOfOrderData) => {
+                    log::warn!(
+                        "Skipping point {} of {}.{} that cannot be written",
+                        record.timestamp,
+                        record.device,
+                        record.measurement_id
+                    );
+                }
+                Err(e) => return Err(e),
             }
-            self.reset();
         }
-        Ok(true)
+        Ok(replayed)
+    }
+
+    /// Continues after a point that is already in the file, see `TsFileWriter::recover`
+    pub(crate) fn restore_last_time(&mut self, device: &str, measurement_id: &str, timestamp: i64) {
+        if let Some(group) = self.group_writers.get_mut(device) {
+            group.restore_last_time(measurement_id, timestamp);
+        }
+    }
+
+    /// Bytes written so far plus the estimated size of the buffered chunks
+    pub(crate) fn estimated_size(&mut self) -> u64 {
+        self.file_io_writer.out.get_position() + self.calculate_mem_size_for_all_groups() as u64
     }
 
     fn calculate_mem_size_for_all_groups(&mut self) -> u32 {
@@ -239,110 +933,2071 @@ impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
     }
 
     fn reset(&mut self) {
+        // Group Writers are re-initialized by the flush, but keep the last times
+        // so that out of order data is detected across flushes
         self.record_count = 0;
-        // Reset Group Writers
-        let schema = self.schema.clone();
-        self.group_writers = schema
-            .measurement_groups
-            .into_iter()
-            .map(|(path, v)| {
-                (
-                    path,
-                    GroupWriter {
-                        path,
-                        chunk_writers: v
-                            .measurement_schemas
-                            .iter()
-                            .map(|(&measurement_id, measurement_schema)| {
-                                (
-                                    measurement_id,
-                                    ChunkWriter::new(
-                                        measurement_id,
-                                        measurement_schema.data_type,
-                                        measurement_schema.compression,
-                                        measurement_schema.encoding,
-                                    ),
-                                )
-                            })
-                            .collect(),
-                        last_time_map: BTreeMap::new(),
-                    },
-                )
-            })
-            .collect();
     }
 }
 
-impl<'a> TsFileWriter<'a, WriteWrapper<File>> {
-    // "Default" constructor to use... writes to a file
-    pub fn new(
-        filename: &'a str,
-        schema: Schema<'a>,
-        config: TsFileConfig,
-    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
-        // Create directory, if not exists
-        let folder = match std::path::Path::new(filename).parent() {
-            Some(f) => f,
-            None => {
-                return Err(TsFileError::Error { source: None });
-            }
-        };
-        create_dir_all(folder);
-        // Create the file
-        let file = WriteWrapper::new(File::create(filename).expect("create failed"));
+/// The window of the reorder buffers, `None` if rows are written directly
+pub(crate) fn reorder_window(config: &TsFileConfig) -> Option<ReorderWindow> {
+    // Duplicates can only be overwritten as long as the row is not encoded yet
+    match (config.reorder_window, config.duplicate_policy) {
+        (ReorderWindow::Disabled, DuplicatePolicy::OverwriteLast) => Some(ReorderWindow::Points(1)),
+        (ReorderWindow::Disabled, _) => None,
+        (window, _) => Some(window),
+    }
+}
 
-        TsFileWriter::new_from_writer(schema, file, config)
+/// Timestamps and values of a batch have to have the same length
+pub(crate) fn check_batch(timestamps: &[i64], values: IoTDBValues) -> Result<(), TsFileError> {
+    if timestamps.len() != values.len() {
+        return Err(TsFileError::IllegalState {
+            source: Some(format!(
+                "Batch has {} timestamps but {} values",
+                timestamps.len(),
+                values.len()
+            )),
+        });
     }
+    Ok(())
 }
 
-impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
-    pub(crate) fn new_from_writer(
-        schema: Schema<'a>,
-        file_writer: T,
-        config: TsFileConfig,
-    ) -> Result<TsFileWriter<'a, T>, TsFileError> {
-        let group_writers = schema
-            .clone()
-            .measurement_groups
-            .into_iter()
-            .map(|(path, v)| {
-                (
-                    path.borrow(),
-                    GroupWriter {
-                        path,
-                        chunk_writers: v
-                            .measurement_schemas
-                            .iter()
-                            .map(|(&measurement_id, measurement_schema)| {
-                                (
-                                    measurement_id,
-                                    ChunkWriter::new(
-                                        measurement_id,
-                                        measurement_schema.data_type,
-                                        measurement_schema.compression,
-                                        measurement_schema.encoding,
-                                    ),
-                                )
-                            })
-                            .collect(),
-                        last_time_map: BTreeMap::new(),
-                    },
-                )
-            })
-            .collect();
+/// Writes the buffered data of the device as a chunk group, returns `false` if there was none
+pub(crate) fn flush_group<'a, T: PositionedWrite>(
+    file_io_writer: &mut TsFileIoWriter<'a, T>,
+    device_id: &'a str,
+    group_writer: &mut GroupWriter<'a>,
+) -> Result<bool, TsFileError> {
+    // Devices without new data since the last flush get no chunk group
+    if !group_writer.has_data() {
+        return Ok(false);
+    }
+    #[cfg(feature = "tracing")]
+    let _span = tracing_crate::info_span!("flush_chunk_group", device = device_id).entered();
+    file_io_writer.start_chunk_group(device_id)?;
+    let pos = file_io_writer.out.get_position();
+    let data_size = group_writer.flush_to_filewriter(file_io_writer)?;
+
+    if file_io_writer.out.get_position() - pos != data_size {
+        return Err(TsFileError::IllegalState {
+            source: Some("Bytes written are not as expected!".to_owned()),
+        });
+    }
+
+    file_io_writer.end_chunk_group()?;
+    #[cfg(feature = "tracing")]
+    tracing_crate::debug!(bytes = data_size, "Flushed chunk group");
+    Ok(true)
+}
+
+/// The file that is written until `close` with `TsFileConfig::temp_file`
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Creates the file to write, the temporary file with `TsFileConfig::temp_file`
+pub(crate) fn create_file(path: &Path, config: &TsFileConfig) -> Result<File, TsFileError> {
+    if config.temp_file {
+        Ok(File::create(temp_path(path))?)
+    } else {
+        Ok(File::create(path)?)
+    }
+}
+
+/// Second handle of the file to sync it with `TsFileConfig::sync_on_flush`, `sync_on_close` or
+/// `TsFileWriter::flush_open`
+pub(crate) fn sync_handle(file: &File) -> Result<File, TsFileError> {
+    Ok(file.try_clone()?)
+}
 
+/// Moves the temporary file to its final name after the footer was written
+pub(crate) fn commit_file(path: &Path, config: &TsFileConfig) -> Result<(), TsFileError> {
+    if config.temp_file {
+        std::fs::rename(temp_path(path), path)?;
+        // The rename is durable once the directory is synced
+        #[cfg(unix)]
+        if config.sync_on_close {
+            if let Some(folder) = path
+                .parent()
+                .filter(|folder| !folder.as_os_str().is_empty())
+            {
+                File::open(folder)?.sync_all()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<'a> TsFileWriter<'a, WriteWrapper<File>> {
+    // "Default" constructor to use... writes to a file
+    pub fn new(
+        filename: &'a str,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        let mut writer = TsFileWriter::create(filename, schema, config)?;
+        if config.write_ahead_log {
+            writer.wal = Some(WriteAheadLog::create(&wal_path(filename))?);
+        }
+        Ok(writer)
+    }
+
+    /// Creates the file, without write ahead log
+    pub(crate) fn create(
+        filename: &'a str,
+        schema: Schema<'a>,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        config.validate()?;
+        // Create directory, if not exists
+        let folder = match std::path::Path::new(filename).parent() {
+            Some(f) => f,
+            None => {
+                return Err(TsFileError::Error { source: None });
+            }
+        };
+        create_dir_all(folder)?;
+        // Create the file
+        let file = create_file(Path::new(filename), &config)?;
+        let sync_file = Some(sync_handle(&file)?);
+
+        let mut writer = TsFileWriter::new_from_writer(schema, WriteWrapper::new(file), config)?;
+        writer.filename = filename.to_owned();
+        writer.file_io_writer.sync_file = sync_file;
+        Ok(writer)
+    }
+}
+
+impl<'a, T: PositionedWrite> TsFileWriter<'a, T> {
+    /// Writes the TsFile to the given sink instead of a file, e.g. a `Vec<u8>` or any other
+    /// `Write` wrapped in a `WriteWrapper`
+    ///
+    /// ```
+    /// use tsfile_writer::writer::compression::CompressionType;
+    /// use tsfile_writer::writer::encoding::TSEncoding;
+    /// use tsfile_writer::writer::tsfile_writer::TsFileWriter;
+    /// use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+    ///
+    /// let schema = Schema::simple(
+    ///     "d1",
+    ///     "s1",
+    ///     TSDataType::INT64,
+    ///     TSEncoding::PLAIN,
+    ///     CompressionType::UNCOMPRESSED,
+    /// );
+    /// let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default()).unwrap();
+    /// writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+    /// writer.close().unwrap();
+    ///
+    /// let bytes: Vec<u8> = writer.into_inner();
+    /// assert_eq!(&bytes[0..6], b"TsFile");
+    /// ```
+    pub fn new_from_writer(
+        schema: Schema<'a>,
+        file_writer: T,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, T>, TsFileError> {
+        config.validate()?;
         let io_writer = TsFileIoWriter::new(file_writer, config)?;
+        TsFileWriter::with_io_writer(schema, io_writer, config)
+    }
+
+    pub(crate) fn with_io_writer(
+        schema: Schema<'a>,
+        io_writer: TsFileIoWriter<'a, T>,
+        config: TsFileConfig,
+    ) -> Result<TsFileWriter<'a, T>, TsFileError> {
+        let group_writers = schema
+            .measurement_groups
+            .iter()
+            .map(|(&path, group)| Ok((path, GroupWriter::new(path, group, &config)?)))
+            .collect::<Result<_, TsFileError>>()?;
+        let reorder_buffers = match reorder_window(&config) {
+            None => BTreeMap::new(),
+            Some(window) => schema
+                .measurement_groups
+                .keys()
+                .map(|&path| (path, ReorderBuffer::new(window, config.duplicate_policy)))
+                .collect(),
+        };
         Ok(TsFileWriter {
             filename: String::from(""),
             schema,
             group_writers,
             chunk_group_metadata: vec![],
-            timeseries_metadata_map: HashMap::new(),
+            timeseries_metadata_map: BTreeMap::new(),
             record_count: 0,
             record_count_for_next_mem_check: 100,
             non_aligned_timeseries_last_time_map: BTreeMap::new(),
+            reorder_buffers,
+            late_points: vec![],
             config,
+            closed: false,
+            wal: None,
             file_io_writer: io_writer,
         })
     }
 }
+
+/// Builder for a `TsFileWriter` with a custom `TsFileConfig`
+///
+/// ```
+/// use tsfile_writer::writer::compression::CompressionType;
+/// use tsfile_writer::writer::encoding::TSEncoding;
+/// use tsfile_writer::writer::tsfile_writer::TsFileWriterBuilder;
+/// use tsfile_writer::writer::{IoTDBValue, Schema, TSDataType};
+///
+/// let schema = Schema::simple(
+///     "d1",
+///     "s1",
+///     TSDataType::INT64,
+///     TSEncoding::PLAIN,
+///     CompressionType::UNCOMPRESSED,
+/// );
+/// let mut writer = TsFileWriterBuilder::new(schema)
+///     .page_size_threshold(16 * 1024)
+///     .max_degree_of_index_node(64)
+///     .build("target/builder_doc.tsfile")
+///     .unwrap();
+/// writer.write("d1", "s1", 1, IoTDBValue::LONG(13)).unwrap();
+/// writer.close().unwrap();
+/// ```
+pub struct TsFileWriterBuilder<'a> {
+    schema: Schema<'a>,
+    config: TsFileConfig,
+    properties: Vec<(String, String)>,
+}
+
+impl<'a> TsFileWriterBuilder<'a> {
+    pub fn new(schema: Schema<'a>) -> TsFileWriterBuilder<'a> {
+        TsFileWriterBuilder {
+            schema,
+            config: TsFileConfig::default(),
+            properties: vec![],
+        }
+    }
+
+    /// Replaces all options by the given config
+    pub fn config(&mut self, config: TsFileConfig) -> &mut TsFileWriterBuilder<'a> {
+        self.config = config;
+        self
+    }
+
+    /// See `TsFileConfig::chunk_group_size_threshold`
+    pub fn chunk_group_size_threshold(&mut self, bytes: u32) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.chunk_group_size_threshold(bytes);
+        self
+    }
+
+    /// See `TsFileConfig::page_size_threshold`
+    pub fn page_size_threshold(&mut self, bytes: u32) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.page_size_threshold(bytes);
+        self
+    }
+
+    /// See `TsFileConfig::max_number_of_points_in_page`
+    pub fn max_number_of_points_in_page(&mut self, points: u32) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.max_number_of_points_in_page(points);
+        self
+    }
+
+    /// See `TsFileConfig::bloom_filter_error_rate`
+    pub fn bloom_filter_error_rate(&mut self, rate: f64) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.bloom_filter_error_rate(rate);
+        self
+    }
+
+    /// See `TsFileConfig::bloom_filter`
+    pub fn bloom_filter(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.bloom_filter(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::max_degree_of_index_node`
+    pub fn max_degree_of_index_node(&mut self, degree: usize) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.max_degree_of_index_node(degree);
+        self
+    }
+
+    /// See `TsFileConfig::version`
+    pub fn version(&mut self, version: u8) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.version(version);
+        self
+    }
+
+    /// See `TsFileConfig::reorder_window`
+    pub fn reorder_window(&mut self, window: ReorderWindow) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.reorder_window(window);
+        self
+    }
+
+    /// See `TsFileConfig::late_data_policy`
+    pub fn late_data_policy(&mut self, policy: LateDataPolicy) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.late_data_policy(policy);
+        self
+    }
+
+    /// See `TsFileConfig::duplicate_policy`
+    pub fn duplicate_policy(&mut self, policy: DuplicatePolicy) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.duplicate_policy(policy);
+        self
+    }
+
+    /// See `TsFileConfig::write_resource`
+    pub fn write_resource(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.write_resource(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::flush_threads`
+    pub fn flush_threads(&mut self, threads: usize) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.flush_threads(threads);
+        self
+    }
+
+    /// See `TsFileConfig::time_encoding`
+    pub fn time_encoding(&mut self, encoding: TSEncoding) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.time_encoding(encoding);
+        self
+    }
+
+    /// See `TsFileConfig::single_page_chunks`
+    pub fn single_page_chunks(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.single_page_chunks(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::close_on_drop`
+    pub fn close_on_drop(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.close_on_drop(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::temp_file`
+    pub fn temp_file(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.temp_file(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::sync_on_flush`
+    pub fn sync_on_flush(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.sync_on_flush(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::sync_on_close`
+    pub fn sync_on_close(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.sync_on_close(enabled);
+        self
+    }
+
+    /// See `TsFileConfig::timestamp_precision`
+    pub fn timestamp_precision(
+        &mut self,
+        precision: TimestampPrecision,
+    ) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.timestamp_precision(precision);
+        self
+    }
+
+    /// See `TsFileConfig::write_ahead_log`
+    pub fn write_ahead_log(&mut self, enabled: bool) -> &mut TsFileWriterBuilder<'a> {
+        self.config = self.config.write_ahead_log(enabled);
+        self
+    }
+
+    /// See `TsFileWriter::set_property`
+    pub fn property(&mut self, key: &str, value: &str) -> &mut TsFileWriterBuilder<'a> {
+        self.properties.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Creates a writer for the given file, missing directories are created
+    pub fn build(
+        &mut self,
+        filename: &'a str,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<File>>, TsFileError> {
+        let writer = TsFileWriter::new(filename, self.schema.clone(), self.config)?;
+        Ok(self.with_properties(writer))
+    }
+
+    /// Creates a writer that writes the file to the given writer, e.g. a socket or a buffer
+    pub fn build_with_writer<W: Write>(
+        &mut self,
+        writer: W,
+    ) -> Result<TsFileWriter<'a, WriteWrapper<W>>, TsFileError> {
+        let writer = TsFileWriter::new_from_writer(
+            self.schema.clone(),
+            WriteWrapper::new(writer),
+            self.config,
+        )?;
+        Ok(self.with_properties(writer))
+    }
+
+    /// Creates a writer that keeps the file in memory, e.g. for targets without a file system
+    /// like `wasm32-unknown-unknown`. The file is returned by `into_inner` after `close`.
+    pub fn build_in_memory(&mut self) -> Result<TsFileWriter<'a, Vec<u8>>, TsFileError> {
+        let writer = TsFileWriter::new_from_writer(self.schema.clone(), vec![], self.config)?;
+        Ok(self.with_properties(writer))
+    }
+
+    fn with_properties<T: PositionedWrite>(
+        &self,
+        mut writer: TsFileWriter<'a, T>,
+    ) -> TsFileWriter<'a, T> {
+        for (key, value) in &self.properties {
+            writer.set_property(key, value);
+        }
+        writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::tsfile_reader::TsFileReader;
+    use crate::writer::compression::CompressionType;
+    use crate::writer::encoding::TSEncoding;
+    use crate::writer::errors::TsFileError;
+    use crate::writer::metrics::WriterMetrics;
+    use crate::writer::record::TSRecord;
+    use crate::writer::schema::{DeviceBuilder, TsFileSchemaBuilder};
+    use crate::writer::tablet::Tablet;
+    use crate::writer::ts_file_config::{
+        DuplicatePolicy, LateDataPolicy, ReorderWindow, TsFileConfig,
+    };
+    use crate::writer::tsfile_writer::{DataPoint, LatePoint, TsFileWriter, TsFileWriterBuilder};
+    use crate::writer::{IoTDBValue, MeasurementSchema, PositionedWrite, Schema, TSDataType};
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    #[test]
+    fn auto_flush() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d3",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let config = TsFileConfig::default().chunk_group_size_threshold(256 * 1024);
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+
+        for i in 0..200_000 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write_many("d2", i, vec![DataPoint::new("s1", IoTDBValue::LONG(i))])?;
+        }
+        // Data was already flushed
+        assert!(writer.file_io_writer.out.len() > 1024 * 1024);
+        // Last times are kept across flushes
+        assert_eq!(
+            writer.write("d1", "s1", 0, IoTDBValue::LONG(0)),
+            Err(TsFileError::OutOfOrderData)
+        );
+        assert_eq!(
+            writer.write("d2", "s1", 0, IoTDBValue::LONG(0)),
+            Err(TsFileError::OutOfOrderData)
+        );
+        writer.write("d3", "s1", 0, IoTDBValue::LONG(0))?;
+        writer.close().unwrap();
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        for device in ["d1", "d2"] {
+            let metadata = reader.get_timeseries_metadata(device, "s1")?.unwrap();
+            assert!(metadata.chunk_metadata_list.len() > 1);
+            assert_eq!(metadata.statistics.count(), 200_000);
+
+            let points = reader.query(device, "s1")?.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points.len(), 200_000);
+            assert!(points
+                .iter()
+                .enumerate()
+                .all(|(i, p)| p == &(i as i64, IoTDBValue::LONG(i as i64))));
+        }
+        // Only a single chunk as d3 had no data in the earlier flushes
+        let metadata = reader.get_timeseries_metadata("d3", "s1")?.unwrap();
+        assert_eq!(metadata.chunk_metadata_list.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_instead_of_panics() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+
+        assert_eq!(
+            writer.write("d1", "s1", 1, IoTDBValue::FLOAT(1.0)),
+            Err(TsFileError::WrongTypeForSeries)
+        );
+        assert!(matches!(
+            writer.write("d2", "s1", 1, IoTDBValue::LONG(1)),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        assert!(matches!(
+            writer.write("d1", "s2", 1, IoTDBValue::LONG(1)),
+            Err(TsFileError::SchemaError { .. })
+        ));
+
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        assert_eq!(reader.query("d1", "s1")?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn max_point_number() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add_schema(
+                        "s1",
+                        MeasurementSchema::new(
+                            TSDataType::DOUBLE,
+                            TSEncoding::TS2DIFF,
+                            CompressionType::UNCOMPRESSED,
+                        )
+                        .max_point_number(1),
+                    )
+                    // IoTDB's default of 2 decimal places
+                    .add(
+                        "s2",
+                        TSDataType::FLOAT,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add_schema(
+                        "s1",
+                        MeasurementSchema::new(
+                            TSDataType::FLOAT,
+                            TSEncoding::TS2DIFF,
+                            CompressionType::UNCOMPRESSED,
+                        )
+                        .max_point_number(0),
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+        writer.write("d1", "s1", 1, IoTDBValue::DOUBLE(20.26))?;
+        writer.write("d1", "s2", 1, IoTDBValue::FLOAT(20.256))?;
+        writer.write("d2", "s1", 1, IoTDBValue::FLOAT(20.6))?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let value = |reader: &mut TsFileReader<_>, device, measurement| {
+            reader.query(device, measurement)?.next().unwrap()
+        };
+        assert_eq!(value(&mut reader, "d1", "s1")?.1, IoTDBValue::DOUBLE(20.3));
+        assert_eq!(value(&mut reader, "d1", "s2")?.1, IoTDBValue::FLOAT(20.26));
+        assert_eq!(value(&mut reader, "d2", "s1")?.1, IoTDBValue::FLOAT(21.0));
+        Ok(())
+    }
+
+    #[test]
+    fn builder() -> Result<(), TsFileError> {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let config = TsFileConfig::default()
+            .page_size_threshold(1024)
+            .bloom_filter_error_rate(0.01);
+
+        let mut writer = TsFileWriterBuilder::new(schema.clone())
+            .page_size_threshold(1024)
+            .bloom_filter_error_rate(0.01)
+            .build_with_writer(vec![])?;
+        let mut in_memory = TsFileWriterBuilder::new(schema.clone())
+            .config(config)
+            .build_in_memory()?;
+        let mut expected = TsFileWriter::new_from_writer(schema.clone(), vec![], config)?;
+        for i in 0..10_000 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            in_memory.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            expected.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        writer.close()?;
+        in_memory.close()?;
+        expected.close()?;
+        let expected = expected.into_inner();
+        assert_eq!(writer.into_inner().into_inner(), expected);
+        assert_eq!(in_memory.into_inner(), expected);
+
+        assert!(matches!(
+            TsFileWriterBuilder::new(schema.clone())
+                .version(2)
+                .build_with_writer(vec![]),
+            Err(TsFileError::UnsupportedFeature { .. })
+        ));
+        assert!(TsFileWriterBuilder::new(schema)
+            .max_degree_of_index_node(1)
+            .build_with_writer(vec![])
+            .is_err());
+
+        Ok(())
+    }
+
+    fn page_counts(config: TsFileConfig, device: DeviceBuilder, points: i64) -> Vec<u32> {
+        let mut device = device;
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                device
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config).unwrap();
+        for i in 0..points {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i)).unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(reader.query("d1", "s1").unwrap().count(), points as usize);
+        let metadata = reader.get_timeseries_metadata("d1", "s1").unwrap().unwrap();
+        let chunk = reader.read_chunk(&metadata.chunk_metadata_list[0]).unwrap();
+        chunk
+            .pages()
+            .unwrap()
+            .iter()
+            .map(|page| match &page.header().statistics {
+                Some(statistics) => statistics.count(),
+                None => points as u32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn max_number_of_points_in_page() {
+        let config = TsFileConfig::default().max_number_of_points_in_page(1_000);
+        let mut expected = vec![1_000; 10];
+        expected.push(500);
+
+        assert_eq!(page_counts(config, DeviceBuilder::new(), 10_500), expected);
+        assert_eq!(
+            page_counts(config, DeviceBuilder::aligned(), 10_500),
+            expected
+        );
+        assert_eq!(
+            page_counts(config, DeviceBuilder::new(), 1_000),
+            vec![1_000]
+        );
+    }
+
+    #[test]
+    fn page_size_threshold() {
+        let default_pages = page_counts(Default::default(), DeviceBuilder::new(), 100_000).len();
+        let config = TsFileConfig::default().page_size_threshold(8 * 1024);
+        let pages = page_counts(config, DeviceBuilder::new(), 100_000).len();
+        assert!(pages > default_pages * 4);
+
+        let pages = page_counts(config, DeviceBuilder::aligned(), 100_000).len();
+        assert!(pages > default_pages * 4);
+    }
+
+    fn reorder_writer(config: TsFileConfig) -> TsFileWriter<'static, Vec<u8>> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        TsFileWriter::new_from_writer(schema, vec![], config).unwrap()
+    }
+
+    fn timestamps(bytes: Vec<u8>, device: &str) -> Vec<i64> {
+        let mut reader = TsFileReader::new(Cursor::new(bytes)).unwrap();
+        reader
+            .query(device, "s1")
+            .unwrap()
+            .map(|point| point.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn reorder_window() -> Result<(), TsFileError> {
+        let mut writer =
+            reorder_writer(TsFileConfig::default().reorder_window(ReorderWindow::Points(3)));
+        for i in [2, 1, 4, 3, 6, 5, 8, 7, 10, 9] {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write_many("d2", i, vec![DataPoint::new("s1", IoTDBValue::LONG(i))])?;
+        }
+        // Rows 1 to 7 left the window of 3 rows
+        assert_eq!(
+            writer.write("d1", "s1", 7, IoTDBValue::LONG(7)),
+            Err(TsFileError::OutOfOrderData)
+        );
+        let mut tablet = Tablet::new(vec![11, 12]);
+        tablet.add_column("s1", vec![IoTDBValue::LONG(11), IoTDBValue::LONG(12)])?;
+        writer.write_tablet("d2", tablet)?;
+        assert_eq!(
+            writer.write("d2", "s1", 12, IoTDBValue::LONG(12)),
+            Err(TsFileError::OutOfOrderData)
+        );
+        writer.close()?;
+        let bytes = writer.into_inner();
+        assert_eq!(
+            timestamps(bytes.clone(), "d1"),
+            (1..=10).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(bytes, "d2"), (1..=12).collect::<Vec<_>>());
+
+        let mut writer =
+            reorder_writer(TsFileConfig::default().reorder_window(ReorderWindow::Time(100)));
+        for i in [100, 50, 150, 120, 300, 200] {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        // 300 released all rows older than 200, the newest of them is 150
+        assert_eq!(
+            writer.write("d1", "s1", 150, IoTDBValue::LONG(150)),
+            Err(TsFileError::OutOfOrderData)
+        );
+        writer.write("d1", "s1", 151, IoTDBValue::LONG(151))?;
+        writer.close()?;
+        assert_eq!(
+            timestamps(writer.into_inner(), "d1"),
+            vec![50, 100, 120, 150, 151, 200, 300]
+        );
+
+        assert!(TsFileWriterBuilder::new(Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        ))
+        .reorder_window(ReorderWindow::Points(0))
+        .build_in_memory()
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn late_data_policy() -> Result<(), TsFileError> {
+        for policy in [LateDataPolicy::Drop, LateDataPolicy::Separate] {
+            let mut writer = reorder_writer(
+                TsFileConfig::default()
+                    .reorder_window(ReorderWindow::Points(1))
+                    .late_data_policy(policy),
+            );
+            for i in [1, 2, 3, 1, 4] {
+                writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            }
+            writer.flush()?;
+            writer.write("d1", "s1", 3, IoTDBValue::LONG(5))?;
+            let late_points = writer.take_late_points();
+            writer.close()?;
+            assert_eq!(timestamps(writer.into_inner(), "d1"), vec![1, 2, 3, 4]);
+            if policy == LateDataPolicy::Drop {
+                assert!(late_points.is_empty());
+            } else {
+                assert_eq!(
+                    late_points,
+                    vec![
+                        LatePoint {
+                            device: "d1",
+                            measurement_id: "s1",
+                            timestamp: 1,
+                            value: IoTDBValue::LONG(1),
+                        },
+                        LatePoint {
+                            device: "d1",
+                            measurement_id: "s1",
+                            timestamp: 3,
+                            value: IoTDBValue::LONG(5),
+                        },
+                    ]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn values(bytes: Vec<u8>, device: &str) -> Vec<(i64, IoTDBValue)> {
+        let mut reader = TsFileReader::new(Cursor::new(bytes)).unwrap();
+        reader
+            .query(device, "s1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn duplicate_policy() -> Result<(), TsFileError> {
+        let write = |policy: DuplicatePolicy, window: ReorderWindow| {
+            let mut writer = reorder_writer(
+                TsFileConfig::default()
+                    .reorder_window(window)
+                    .duplicate_policy(policy),
+            );
+            let mut results = vec![];
+            for (timestamp, value) in [(1, 1), (2, 2), (2, 3), (3, 4), (1, 5), (3, 6)] {
+                results.push(
+                    writer
+                        .write("d1", "s1", timestamp, IoTDBValue::LONG(value))
+                        .is_ok()
+                        && writer
+                            .write_many(
+                                "d2",
+                                timestamp,
+                                vec![DataPoint::new("s1", IoTDBValue::LONG(value))],
+                            )
+                            .is_ok(),
+                );
+            }
+            writer.close().unwrap();
+            let bytes = writer.into_inner();
+            assert_eq!(values(bytes.clone(), "d1"), values(bytes.clone(), "d2"));
+            (results, values(bytes, "d1"))
+        };
+        let expected = |values: [i64; 3]| {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| (i as i64 + 1, IoTDBValue::LONG(v)))
+                .collect::<Vec<_>>()
+        };
+
+        for window in [ReorderWindow::Disabled, ReorderWindow::Points(10)] {
+            let (results, points) = write(DuplicatePolicy::Error, window);
+            assert_eq!(results, vec![true, true, false, true, false, false]);
+            assert_eq!(points, expected([1, 2, 4]));
+
+            let (results, points) = write(DuplicatePolicy::KeepFirst, window);
+            assert_eq!(
+                results,
+                vec![
+                    true,
+                    true,
+                    true,
+                    true,
+                    window != ReorderWindow::Disabled,
+                    true
+                ]
+            );
+            assert_eq!(points, expected([1, 2, 4]));
+        }
+        // Overwriting works within the window, older rows are already encoded
+        let (results, points) = write(DuplicatePolicy::OverwriteLast, ReorderWindow::Disabled);
+        assert_eq!(results, vec![true, true, true, true, false, true]);
+        assert_eq!(points, expected([1, 3, 6]));
+        let (results, points) = write(DuplicatePolicy::OverwriteLast, ReorderWindow::Points(10));
+        assert_eq!(results, vec![true; 6]);
+        assert_eq!(points, expected([5, 3, 6]));
+        Ok(())
+    }
+
+    #[test]
+    fn flush_threads() -> Result<(), TsFileError> {
+        const MEASUREMENTS: [&str; 8] = ["s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8"];
+        let write = |threads: usize| -> Result<Vec<u8>, TsFileError> {
+            let mut schema = TsFileSchemaBuilder::new();
+            for (device, mut device_builder) in [
+                ("d1", DeviceBuilder::new()),
+                ("d2", DeviceBuilder::aligned()),
+            ] {
+                for measurement in MEASUREMENTS {
+                    device_builder.add(
+                        measurement,
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    );
+                }
+                schema.add(device, device_builder.build());
+            }
+            let mut writer = TsFileWriterBuilder::new(schema.build())
+                .flush_threads(threads)
+                .build_in_memory()?;
+            for i in 0..10_000 {
+                for device in ["d1", "d2"] {
+                    let values: Vec<DataPoint> = MEASUREMENTS
+                        .iter()
+                        .enumerate()
+                        .map(|(j, &measurement)| {
+                            DataPoint::new(measurement, IoTDBValue::LONG(i * j as i64))
+                        })
+                        .collect();
+                    writer.write_many(device, i, values)?;
+                }
+                if i == 5_000 {
+                    writer.flush()?;
+                }
+            }
+            writer.close()?;
+            Ok(writer.into_inner())
+        };
+        // The file does not depend on the number of threads that encoded it
+        let bytes = write(1)?;
+        assert_eq!(write(4)?, bytes);
+        assert_eq!(write(0)?, bytes);
+        let mut reader = TsFileReader::new(Cursor::new(bytes))?;
+        assert_eq!(reader.query("d2", "s8")?.count(), 10_000);
+        Ok(())
+    }
+
+    #[test]
+    fn time_encoding() -> Result<(), TsFileError> {
+        let device = |aligned: bool| {
+            match aligned {
+                true => DeviceBuilder::aligned(),
+                false => DeviceBuilder::new(),
+            }
+            .add(
+                "s1",
+                TSDataType::INT32,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .build()
+        };
+        let write = |encoding: TSEncoding| -> Result<Vec<u8>, TsFileError> {
+            let schema = TsFileSchemaBuilder::new().add("d1", device(true)).build();
+            let mut writer = TsFileWriterBuilder::new(schema)
+                .time_encoding(encoding)
+                .max_number_of_points_in_page(100)
+                .build_in_memory()?;
+            // Regular interval of one second
+            for i in 0..1_000 {
+                writer.write("d1", "s1", i * 1_000, IoTDBValue::INT(i as i32))?;
+            }
+            writer.close()?;
+            Ok(writer.into_inner())
+        };
+        let ts2diff = write(TSEncoding::TS2DIFF)?;
+        let plain = write(TSEncoding::PLAIN)?;
+        assert!(ts2diff.len() * 2 < plain.len());
+
+        for bytes in [ts2diff, plain] {
+            let mut reader = TsFileReader::new(Cursor::new(bytes))?;
+            let points = reader.query("d1", "s1")?.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points.len(), 1_000);
+            assert_eq!(points[999], (999_000, IoTDBValue::INT(999)));
+        }
+
+        // The time chunk of aligned devices records the encoding
+        let mut reader = TsFileReader::new(Cursor::new(write(TSEncoding::PLAIN)?))?;
+        assert!(reader.metadata_reader().properties().is_empty());
+        let time_metadata = reader.get_timeseries_metadata("d1", "")?.unwrap();
+        let time_chunk = reader.read_chunk(&time_metadata.chunk_metadata_list[0])?;
+        assert_eq!(time_chunk.header().encoding, TSEncoding::PLAIN);
+
+        // Readers expect TS2DIFF for the time column of non aligned series
+        let config = TsFileConfig::default().time_encoding(TSEncoding::PLAIN);
+        let schema = TsFileSchemaBuilder::new().add("d1", device(false)).build();
+        assert!(matches!(
+            TsFileWriter::new_from_writer(schema, vec![], config),
+            Err(TsFileError::UnsupportedFeature { .. })
+        ));
+        let schema = TsFileSchemaBuilder::new().add("d1", device(true)).build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], config)?;
+        assert!(matches!(
+            writer.register_timeseries("d2", device(false)),
+            Err(TsFileError::UnsupportedFeature { .. })
+        ));
+        assert!(writer.write("d2", "s1", 1, IoTDBValue::INT(1)).is_err());
+        writer.write("d1", "s1", 1, IoTDBValue::INT(1))?;
+        writer.close()?;
+
+        let config = TsFileConfig::default().time_encoding(TSEncoding::GORILLA);
+        assert!(
+            TsFileWriter::new_from_writer(TsFileSchemaBuilder::new().build(), vec![], config)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn temp_file() -> Result<(), TsFileError> {
+        let path = Path::new("target/temp_file.tsfile");
+        let temp = Path::new("target/temp_file.tsfile.tmp");
+        let _ = std::fs::remove_file(path);
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .temp_file(true)
+            .build("target/temp_file.tsfile")?;
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+        writer.flush()?;
+        assert!(temp.exists());
+        assert!(!path.exists());
+
+        writer.close()?;
+        assert!(!temp.exists());
+        let mut reader = TsFileReader::new(File::open(path)?)?;
+        assert_eq!(reader.query("d1", "s1")?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_output() -> Result<(), TsFileError> {
+        let devices = ["root.b", "root.a", "root.c.d", "root.c"];
+        let measurements = ["s2", "s10", "s1", "a"];
+        let write = |reverse: bool| -> Result<Vec<u8>, TsFileError> {
+            let mut schema = TsFileSchemaBuilder::new();
+            for device in devices {
+                let mut device_builder = DeviceBuilder::new();
+                for measurement in measurements {
+                    device_builder.add(
+                        measurement,
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    );
+                }
+                schema.add(device, device_builder.build());
+            }
+            // A small index node size to get a tree with several levels
+            let config = TsFileConfig::default().max_degree_of_index_node(2);
+            let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], config)?;
+            let mut order: Vec<_> = devices
+                .iter()
+                .flat_map(|device| measurements.iter().map(move |m| (*device, *m)))
+                .collect();
+            if reverse {
+                order.reverse();
+            }
+            for (device, measurement) in order {
+                writer.write(device, measurement, 1, IoTDBValue::LONG(1))?;
+            }
+            writer.close()?;
+            Ok(writer.into_inner())
+        };
+        let file = write(false)?;
+        assert_eq!(file, write(false)?);
+        assert_eq!(file, write(true)?);
+
+        let mut reader = TsFileReader::new(Cursor::new(file))?;
+        assert_eq!(
+            reader.metadata_reader().devices()?,
+            vec!["root.a", "root.b", "root.c", "root.c.d"]
+        );
+        let ids: Vec<_> = reader
+            .metadata_reader()
+            .get_device_timeseries_metadata("root.b")?
+            .into_iter()
+            .map(|metadata| metadata.measurement_id)
+            .collect();
+        assert_eq!(ids, vec!["a", "s1", "s10", "s2"]);
+        for device in devices {
+            for measurement in measurements {
+                assert_eq!(reader.query(device, measurement)?.count(), 1);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriterBuilder::new(schema)
+            .sync_on_flush(true)
+            .sync_on_close(true)
+            .temp_file(true)
+            .build("target/sync.tsfile")?;
+        assert!(writer.file_io_writer.sync_file.is_some());
+        for i in 0..10 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.flush()?;
+        }
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(File::open("target/sync.tsfile")?)?;
+        assert_eq!(reader.query("d1", "s1")?.count(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn close_on_drop() -> Result<(), TsFileError> {
+        let write = |close_on_drop: bool| -> Result<Vec<u8>, TsFileError> {
+            let mut buffer = vec![];
+            let schema = TsFileSchemaBuilder::new()
+                .add(
+                    "d1",
+                    DeviceBuilder::new()
+                        .add(
+                            "s1",
+                            TSDataType::INT64,
+                            TSEncoding::PLAIN,
+                            CompressionType::UNCOMPRESSED,
+                        )
+                        .build(),
+                )
+                .build();
+            let mut writer = TsFileWriterBuilder::new(schema)
+                .close_on_drop(close_on_drop)
+                .build_with_writer(&mut buffer)?;
+            for i in 0..10 {
+                writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            }
+            // Dropped without close
+            drop(writer);
+            Ok(buffer)
+        };
+
+        let mut reader = TsFileReader::new(Cursor::new(write(true)?))?;
+        let metadata = reader.get_timeseries_metadata("d1", "s1")?.unwrap();
+        assert_eq!(metadata.statistics.count(), 10);
+
+        assert!(TsFileReader::new(Cursor::new(write(false)?)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn closed_writer() -> Result<(), TsFileError> {
+        let schema = Schema::simple(
+            "d1",
+            "s1",
+            TSDataType::INT64,
+            TSEncoding::PLAIN,
+            CompressionType::UNCOMPRESSED,
+        );
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+        writer.close()?;
+        let size = writer.file_io_writer.out.get_position();
+
+        let is_closed = |result: Result<(), TsFileError>| match result {
+            Err(TsFileError::IllegalState {
+                source: Some(message),
+            }) => message == "The writer is already closed",
+            _ => false,
+        };
+        assert!(is_closed(writer.close().map(|_| ())));
+        assert!(is_closed(writer.write("d1", "s1", 2, IoTDBValue::LONG(2))));
+        assert!(is_closed(writer.write_many(
+            "d1",
+            2,
+            vec![DataPoint::new("s1", IoTDBValue::LONG(2))]
+        )));
+        assert!(is_closed(writer.write_batch("d1", "s1", &[2], &[2_i64])));
+        assert!(is_closed(writer.flush()));
+        assert!(is_closed(writer.flush_open().map(|_| ())));
+
+        // Nothing was appended after the footer
+        assert_eq!(writer.file_io_writer.out.get_position(), size);
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        assert_eq!(reader.query("d1", "s1")?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn close_summary() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::DOUBLE,
+                        TSEncoding::GORILLA,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer =
+            TsFileWriter::new("target/close_summary.tsfile", schema, Default::default())?;
+        for i in 0..20 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+            writer.write("d2", "s1", 100 + i, IoTDBValue::INT(i as i32))?;
+            if i == 9 {
+                writer.flush()?;
+            }
+        }
+        writer.write("d1", "s2", 50, IoTDBValue::DOUBLE(1.5))?;
+        let summary = writer.close()?;
+
+        assert_eq!(summary.path.as_deref(), Some("target/close_summary.tsfile"));
+        assert_eq!(
+            summary.file_size,
+            std::fs::metadata("target/close_summary.tsfile")?.len()
+        );
+        let d1 = &summary.devices[0];
+        assert_eq!(
+            (d1.device.as_str(), d1.start_time, d1.end_time),
+            ("d1", 0, 50)
+        );
+        assert_eq!(d1.timeseries[0].chunk_count, 2);
+        assert_eq!(d1.timeseries[0].statistics.count(), 20);
+        assert_eq!(
+            d1.timeseries[0].statistics.max_value(),
+            Some(IoTDBValue::LONG(19))
+        );
+        assert_eq!(d1.timeseries[1].statistics.count(), 1);
+
+        // The same as read from the file
+        let mut reader = TsFileReader::open("target/close_summary.tsfile")?;
+        let read = reader.metadata_reader().summary()?;
+        assert_eq!(read.path, None);
+        assert_eq!(
+            (read.file_size, read.metadata_offset),
+            (summary.file_size, summary.metadata_offset)
+        );
+        assert_eq!(
+            format!("{:?}", read.devices),
+            format!("{:?}", summary.devices)
+        );
+        // Time column and value column of the aligned device
+        let measurements: Vec<&str> = summary.devices[1]
+            .timeseries
+            .iter()
+            .map(|t| t.measurement_id.as_str())
+            .collect();
+        assert_eq!(measurements, vec!["", "s1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn single_page_chunks() -> Result<(), TsFileError> {
+        let write = |single_page_chunks: bool| -> Result<Vec<u8>, TsFileError> {
+            let schema = TsFileSchemaBuilder::new()
+                .add(
+                    "d1",
+                    DeviceBuilder::new()
+                        .add(
+                            "s1",
+                            TSDataType::INT32,
+                            TSEncoding::PLAIN,
+                            CompressionType::SNAPPY,
+                        )
+                        .build(),
+                )
+                .add(
+                    "d2",
+                    DeviceBuilder::aligned()
+                        .add(
+                            "s1",
+                            TSDataType::INT32,
+                            TSEncoding::PLAIN,
+                            CompressionType::SNAPPY,
+                        )
+                        .build(),
+                )
+                .build();
+            let mut writer = TsFileWriterBuilder::new(schema)
+                .single_page_chunks(single_page_chunks)
+                .max_number_of_points_in_page(100)
+                .build_in_memory()?;
+            // One chunk with a single page and one with three pages per series
+            for i in 0..50 {
+                writer.write("d1", "s1", i, IoTDBValue::INT(i as i32))?;
+                writer.write("d2", "s1", i, IoTDBValue::INT(i as i32))?;
+            }
+            writer.flush()?;
+            for i in 50..300 {
+                writer.write("d1", "s1", i, IoTDBValue::INT(i as i32))?;
+                writer.write("d2", "s1", i, IoTDBValue::INT(i as i32))?;
+            }
+            writer.close()?;
+            Ok(writer.into_inner())
+        };
+
+        for single_page_chunks in [true, false] {
+            let mut reader = TsFileReader::new(Cursor::new(write(single_page_chunks)?))?;
+            for (device, measurement) in [("d1", "s1"), ("d2", ""), ("d2", "s1")] {
+                let metadata = reader
+                    .get_timeseries_metadata(device, measurement)?
+                    .unwrap();
+                let chunks = &metadata.chunk_metadata_list;
+                assert_eq!(chunks.len(), 2);
+
+                let chunk = reader.read_chunk(&chunks[0])?;
+                assert_eq!(chunk.header().has_page_statistics(), !single_page_chunks);
+                let pages = chunk.pages()?;
+                assert_eq!(pages.len(), 1);
+                match &pages[0].header().statistics {
+                    Some(statistics) => {
+                        assert_eq!((statistics.count(), statistics.end_time()), (50, 49))
+                    }
+                    None => assert!(single_page_chunks),
+                }
+
+                let chunk = reader.read_chunk(&chunks[1])?;
+                assert!(chunk.header().has_page_statistics());
+                let counts: Vec<u32> = chunk
+                    .pages()?
+                    .iter()
+                    .map(|page| page.header().statistics.as_ref().unwrap().count())
+                    .collect();
+                assert_eq!(counts, vec![100, 100, 50]);
+            }
+            let points = reader.query("d2", "s1")?.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(points.len(), 300);
+            assert_eq!(points[299], (299, IoTDBValue::INT(299)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_many_nullable() -> Result<(), TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for (device, mut device_builder) in [
+            ("d1", DeviceBuilder::new()),
+            ("d2", DeviceBuilder::aligned()),
+        ] {
+            for measurement in ["s1", "s2"] {
+                device_builder.add(
+                    measurement,
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                );
+            }
+            schema.add(device, device_builder.build());
+        }
+        let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], Default::default())?;
+        for device in ["d1", "d2"] {
+            for i in 0..4 {
+                let s2 = if i % 2 == 0 {
+                    Some(IoTDBValue::LONG(i * 10))
+                } else {
+                    None
+                };
+                writer.write_many_nullable(
+                    device,
+                    i,
+                    vec![("s1", Some(IoTDBValue::LONG(i))), ("s2", s2)],
+                )?;
+            }
+            // Not written at all
+            writer.write_many_nullable(device, 10, vec![("s1", None), ("s2", None)])?;
+            assert!(matches!(
+                writer.write_many_nullable(device, 11, vec![("s3", None)]),
+                Err(TsFileError::SchemaError { .. })
+            ));
+        }
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        for device in ["d1", "d2"] {
+            let s1: Vec<_> = reader.query(device, "s1")?.collect::<Result<_, _>>()?;
+            assert_eq!(s1.len(), 4);
+            assert_eq!(s1[3], (3, IoTDBValue::LONG(3)));
+            let s2: Vec<_> = reader.query(device, "s2")?.collect::<Result<_, _>>()?;
+            assert_eq!(
+                s2,
+                vec![(0, IoTDBValue::LONG(0)), (2, IoTDBValue::LONG(20))]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_record() -> Result<(), TsFileError> {
+        let mut device = DeviceBuilder::new();
+        device
+            .add(
+                "s1",
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            )
+            .add(
+                "s2",
+                TSDataType::FLOAT,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            );
+        let schema = TsFileSchemaBuilder::new().add("d1", device.build()).build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+        for i in 0..3 {
+            let mut record = TSRecord::new(i, "d1");
+            record
+                .add_point(DataPoint::new("s1", IoTDBValue::LONG(i)))
+                .add("s2", i as f32);
+            writer.write_record(record)?;
+        }
+        // Not written at all
+        writer.write_record(TSRecord::new(10, "d1"))?;
+        let mut record = TSRecord::new(11, "d2");
+        record.add("s1", 1_i64);
+        assert!(matches!(
+            writer.write_record(record),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let s1: Vec<_> = reader.query("d1", "s1")?.collect::<Result<_, _>>()?;
+        assert_eq!(s1.len(), 3);
+        assert_eq!(s1[2], (2, IoTDBValue::LONG(2)));
+        let s2: Vec<_> = reader.query("d1", "s2")?.collect::<Result<_, _>>()?;
+        assert_eq!(s2[1], (1, IoTDBValue::FLOAT(1.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn flush_only_dirty_devices() -> Result<(), TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for (device, mut device_builder) in [
+            ("d1", DeviceBuilder::new()),
+            ("d2", DeviceBuilder::aligned()),
+            ("d3", DeviceBuilder::new()),
+        ] {
+            device_builder.add(
+                "s1",
+                TSDataType::INT64,
+                TSEncoding::PLAIN,
+                CompressionType::UNCOMPRESSED,
+            );
+            schema.add(device, device_builder.build());
+        }
+        let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], Default::default())?;
+        for device in ["d1", "d2", "d3"] {
+            writer.write(device, "s1", 1, IoTDBValue::LONG(1))?;
+        }
+        writer.flush()?;
+        writer.write("d2", "s1", 2, IoTDBValue::LONG(2))?;
+        writer.flush()?;
+        writer.write("d1", "s1", 3, IoTDBValue::LONG(3))?;
+        writer.flush()?;
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let devices: Vec<_> = reader
+            .chunk_groups()?
+            .into_iter()
+            .map(|chunk_group| chunk_group.device)
+            .collect();
+        assert_eq!(devices, vec!["d1", "d2", "d3", "d2", "d1"]);
+        let metadata = reader.get_timeseries_metadata("d3", "s1")?.unwrap();
+        assert_eq!(metadata.chunk_metadata_list.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn series_without_points() -> Result<(), TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for (device, mut device_builder) in [
+            ("d1", DeviceBuilder::new()),
+            ("d2", DeviceBuilder::aligned()),
+        ] {
+            for measurement in ["s1", "s2", "s3"] {
+                device_builder.add(
+                    measurement,
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                );
+            }
+            schema.add(device, device_builder.build());
+        }
+        let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], Default::default())?;
+        for device in ["d1", "d2"] {
+            writer.write(device, "s1", 1, IoTDBValue::LONG(1))?;
+            writer.write_many_nullable(
+                device,
+                2,
+                vec![("s1", Some(IoTDBValue::LONG(2))), ("s2", None)],
+            )?;
+        }
+        writer.flush()?;
+        // s2 only has points in the second chunk group
+        for device in ["d1", "d2"] {
+            writer.write_many(
+                device,
+                3,
+                vec![
+                    DataPoint::new("s1", IoTDBValue::LONG(3)),
+                    DataPoint::new("s2", IoTDBValue::LONG(3)),
+                ],
+            )?;
+        }
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let chunks: Vec<Vec<String>> = reader
+            .chunk_groups()?
+            .into_iter()
+            .map(|chunk_group| {
+                chunk_group
+                    .chunks
+                    .into_iter()
+                    .map(|chunk| chunk.header.measurement_id)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            chunks,
+            vec![
+                vec!["s1"],
+                vec!["", "s1"],
+                vec!["s1", "s2"],
+                vec!["", "s1", "s2"]
+            ]
+        );
+        for device in ["d1", "d2"] {
+            assert!(reader.get_timeseries_metadata(device, "s3")?.is_none());
+            let s2 = reader.get_timeseries_metadata(device, "s2")?.unwrap();
+            assert_eq!(s2.chunk_metadata_list.len(), 1);
+            let points: Vec<_> = reader.query(device, "s2")?.collect::<Result<_, _>>()?;
+            assert_eq!(points, vec![(3, IoTDBValue::LONG(3))]);
+            let points: Vec<_> = reader.query(device, "s1")?.collect::<Result<_, _>>()?;
+            assert_eq!(points.len(), 3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_row_group() -> Result<(), TsFileError> {
+        let mut schema = TsFileSchemaBuilder::new();
+        for (device, mut device_builder) in [
+            ("d1", DeviceBuilder::new()),
+            ("d2", DeviceBuilder::aligned()),
+        ] {
+            for measurement in ["s1", "s2"] {
+                device_builder.add(
+                    measurement,
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                );
+            }
+            schema.add(device, device_builder.build());
+        }
+        let mut writer = TsFileWriter::new_from_writer(schema.build(), vec![], Default::default())?;
+        for cycle in 0..2 {
+            // Rows of a poll cycle in the order of the source, not of the devices
+            let mut rows = vec![];
+            for i in cycle * 3..cycle * 3 + 3 {
+                for device in ["d2", "d1"] {
+                    rows.push((
+                        device,
+                        i,
+                        vec![
+                            DataPoint::new("s1", IoTDBValue::LONG(i)),
+                            DataPoint::new("s2", IoTDBValue::LONG(i * 10)),
+                        ],
+                    ));
+                }
+            }
+            writer.write_row_group(rows)?;
+        }
+        // Nothing is written for unknown devices
+        let rows = vec![
+            ("d1", 10, vec![DataPoint::new("s1", IoTDBValue::LONG(10))]),
+            ("d3", 10, vec![DataPoint::new("s1", IoTDBValue::LONG(10))]),
+        ];
+        assert!(matches!(
+            writer.write_row_group(rows),
+            Err(TsFileError::SchemaError { .. })
+        ));
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        for device in ["d1", "d2"] {
+            let s1: Vec<_> = reader.query(device, "s1")?.collect::<Result<_, _>>()?;
+            assert_eq!(s1.len(), 6);
+            assert_eq!(s1[5], (5, IoTDBValue::LONG(5)));
+            let s2: Vec<_> = reader.query(device, "s2")?.collect::<Result<_, _>>()?;
+            assert_eq!(s2[2], (2, IoTDBValue::LONG(20)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_batch() -> Result<(), TsFileError> {
+        let schema = || {
+            let mut device = DeviceBuilder::new();
+            device
+                .add(
+                    "long",
+                    TSDataType::INT64,
+                    TSEncoding::TS2DIFF,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "int",
+                    TSDataType::INT32,
+                    TSEncoding::TS2DIFF,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "float",
+                    TSDataType::FLOAT,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                )
+                .add(
+                    "double",
+                    TSDataType::DOUBLE,
+                    TSEncoding::GORILLA,
+                    CompressionType::UNCOMPRESSED,
+                );
+            TsFileSchemaBuilder::new()
+                .add("d1", device.build())
+                .add(
+                    "d2",
+                    DeviceBuilder::aligned()
+                        .add(
+                            "long",
+                            TSDataType::INT64,
+                            TSEncoding::TS2DIFF,
+                            CompressionType::UNCOMPRESSED,
+                        )
+                        .build(),
+                )
+                .build()
+        };
+        // Small pages, so batches span several pages
+        let config = TsFileConfig::default().max_number_of_points_in_page(1_000);
+        let timestamps: Vec<i64> = (0..10_000).map(|i| i * 3).collect();
+        let longs: Vec<i64> = (0..10_000).map(|i| i * i).collect();
+        let ints: Vec<i32> = (0..10_000).map(|i| i % 17 - 8).collect();
+        let floats: Vec<f32> = (0..10_000).map(|i| i as f32 / 4.0).collect();
+        let doubles: Vec<f64> = (0..10_000).map(|i| (i as f64).sin()).collect();
+
+        let mut batched = TsFileWriter::new_from_writer(schema(), vec![], config)?;
+        for (start, end) in [(0, 4_000), (4_000, 10_000)] {
+            let ts = &timestamps[start..end];
+            batched.write_batch("d1", "long", ts, &longs[start..end])?;
+            batched.write_batch("d1", "int", ts, &ints[start..end])?;
+            batched.write_batch("d1", "float", ts, &floats[start..end])?;
+            batched.write_batch("d1", "double", ts, &doubles[start..end])?;
+            batched.write_batch("d2", "long", ts, &longs[start..end])?;
+            batched.flush()?;
+        }
+        batched.close()?;
+
+        let mut expected = TsFileWriter::new_from_writer(schema(), vec![], config)?;
+        for (start, end) in [(0, 4_000), (4_000, 10_000)] {
+            for i in start..end {
+                let t = timestamps[i];
+                expected.write("d1", "long", t, IoTDBValue::LONG(longs[i]))?;
+            }
+            for i in start..end {
+                let t = timestamps[i];
+                expected.write("d1", "int", t, IoTDBValue::INT(ints[i]))?;
+            }
+            for i in start..end {
+                let t = timestamps[i];
+                expected.write("d1", "float", t, IoTDBValue::FLOAT(floats[i]))?;
+            }
+            for i in start..end {
+                let t = timestamps[i];
+                expected.write("d1", "double", t, IoTDBValue::DOUBLE(doubles[i]))?;
+            }
+            for i in start..end {
+                let t = timestamps[i];
+                expected.write("d2", "long", t, IoTDBValue::LONG(longs[i]))?;
+            }
+            expected.flush()?;
+        }
+        expected.close()?;
+        assert_eq!(batched.into_inner(), expected.into_inner());
+
+        // Batches that are not in order are written point by point
+        let mut writer = TsFileWriter::new_from_writer(
+            schema(),
+            vec![],
+            TsFileConfig::default().duplicate_policy(DuplicatePolicy::KeepFirst),
+        )?;
+        writer.write_batch("d1", "long", &[1, 2, 2, 3], &[1_i64, 2, 3, 4])?;
+        assert!(matches!(
+            writer.write_batch("d1", "long", &[4, 1], &[5_i64, 6]),
+            Err(TsFileError::OutOfOrderData)
+        ));
+        assert!(matches!(
+            writer.write_batch("d1", "long", &[5], &[1_i32]),
+            Err(TsFileError::WrongTypeForSeries)
+        ));
+        assert!(matches!(
+            writer.write_batch("d1", "long", &[5, 6], &[1_i64]),
+            Err(TsFileError::IllegalState { .. })
+        ));
+        writer.close()?;
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        let points = reader
+            .query("d1", "long")?
+            .collect::<Result<Vec<_>, TsFileError>>()?;
+        let expected: Vec<(i64, IoTDBValue)> = [(1, 1), (2, 2), (3, 4), (4, 5)]
+            .iter()
+            .map(|&(t, v)| (t, IoTDBValue::LONG(v)))
+            .collect();
+        assert_eq!(points, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn metrics() -> Result<(), TsFileError> {
+        let schema = TsFileSchemaBuilder::new()
+            .add(
+                "d1",
+                DeviceBuilder::new()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::TS2DIFF,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .add(
+                "d2",
+                DeviceBuilder::aligned()
+                    .add(
+                        "s1",
+                        TSDataType::INT64,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .add(
+                        "s2",
+                        TSDataType::INT32,
+                        TSEncoding::PLAIN,
+                        CompressionType::UNCOMPRESSED,
+                    )
+                    .build(),
+            )
+            .build();
+        let mut writer = TsFileWriter::new_from_writer(schema, vec![], Default::default())?;
+        for i in 0..10 {
+            writer.write("d1", "s1", i, IoTDBValue::LONG(i))?;
+        }
+        for i in 0..5 {
+            let mut values = vec![DataPoint::new("s1", IoTDBValue::LONG(i))];
+            if i < 3 {
+                values.push(DataPoint::new("s2", IoTDBValue::INT(i as i32)));
+            }
+            writer.write_many("d2", i, values)?;
+        }
+
+        let metrics = writer.metrics();
+        let points = |metrics: &WriterMetrics, device: &str, measurement: &str| {
+            metrics.points[&(device.to_owned(), measurement.to_owned())]
+        };
+        assert_eq!(points(&metrics, "d1", "s1"), 10);
+        assert_eq!(points(&metrics, "d2", "s1"), 5);
+        assert_eq!(points(&metrics, "d2", "s2"), 3);
+        assert_eq!(metrics.chunk_groups_flushed, 0);
+        assert!(metrics.buffered_bytes > 0);
+        assert_eq!(metrics.compression_ratio(), None);
+
+        writer.flush()?;
+        writer.write("d1", "s1", 10, IoTDBValue::LONG(10))?;
+        let metrics = writer.metrics();
+        assert_eq!(points(&metrics, "d1", "s1"), 11);
+        assert_eq!(metrics.total_points(), 19);
+        assert_eq!(metrics.chunk_groups_flushed, 2);
+        assert_eq!(
+            metrics.bytes_written,
+            writer.file_io_writer.out.len() as u64
+        );
+        assert_eq!(metrics.compression_ratio(), Some(1.0));
+        assert_eq!(metrics.bloom_filter_size, None);
+
+        writer.close()?;
+        let metrics = writer.metrics();
+        assert_eq!(metrics.total_points(), 19);
+        assert_eq!(metrics.chunk_groups_flushed, 3);
+        // Minimal size for 3 paths, 5 hash functions for the error rate of 0.05
+        assert_eq!(metrics.bloom_filter_size, Some(256));
+        assert_eq!(metrics.bloom_filter_hash_functions, Some(5));
+        assert_eq!(metrics.bytes_written, writer.into_inner().len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_listener() -> Result<(), TsFileError> {
+        use crate::writer::listener::FlushListener;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Events(Arc<Mutex<Vec<String>>>);
+
+        impl FlushListener for Events {
+            fn before_chunk_group(&mut self, device: &str, offset: u64) {
+                let event = format!("before {} {}", device, offset);
+                self.0.lock().unwrap().push(event);
+            }
+            fn after_chunk_group(&mut self, device: &str, offset: u64, size: u64) {
+                let event = format!("after {} {} {}", device, offset, size);
+                self.0.lock().unwrap().push(event);
+            }
+            fn after_flush(&mut self, position: u64) {
+                self.0.lock().unwrap().push(format!("flush {}", position));
+            }
+            fn after_close(&mut self, size: u64) {
+                self.0.lock().unwrap().push(format!("close {}", size));
+            }
+        }
+
+        let events = Events::default();
+        let mut writer = reorder_writer(TsFileConfig::default());
+        writer.set_flush_listener(events.clone());
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+        writer.write_many("d2", 1, vec![DataPoint::new("s1", IoTDBValue::LONG(1))])?;
+        writer.flush()?;
+        let flushed = writer.file_io_writer.out.len() as u64;
+        writer.write("d1", "s1", 2, IoTDBValue::LONG(2))?;
+        writer.close()?;
+        let size = writer.into_inner().len() as u64;
+
+        let events = events.0.lock().unwrap();
+        assert_eq!(events.len(), 9);
+        // Chunk groups follow each other, starting after the magic string and version
+        let mut offset = 7;
+        for (i, device) in ["d1", "d2"].iter().enumerate() {
+            assert_eq!(events[2 * i], format!("before {} {}", device, offset));
+            let after: Vec<&str> = events[2 * i + 1].split(' ').collect();
+            assert_eq!(after[..3], ["after", device, &offset.to_string()]);
+            offset += after[3].parse::<u64>().unwrap();
+        }
+        assert_eq!(offset, flushed);
+        assert_eq!(events[4], format!("flush {}", flushed));
+        assert_eq!(events[5], format!("before d1 {}", flushed));
+        assert!(events[6].starts_with(&format!("after d1 {}", flushed)));
+        assert!(events[7].starts_with("flush"));
+        assert_eq!(events[8], format!("close {}", size));
+        Ok(())
+    }
+
+    #[test]
+    fn register_timeseries() -> Result<(), TsFileError> {
+        let device = |aligned: bool, measurements: &[&'static str]| {
+            let mut builder = match aligned {
+                true => DeviceBuilder::aligned(),
+                false => DeviceBuilder::new(),
+            };
+            for measurement in measurements {
+                builder.add(
+                    measurement,
+                    TSDataType::INT64,
+                    TSEncoding::PLAIN,
+                    CompressionType::UNCOMPRESSED,
+                );
+            }
+            builder.build()
+        };
+        let mut writer = reorder_writer(TsFileConfig::default());
+        writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+        // New measurement of a device with buffered data
+        writer.register_timeseries("d1", device(false, &["s2"]))?;
+        writer.write("d1", "s2", 2, IoTDBValue::LONG(2))?;
+        // New devices
+        writer.register_timeseries("d3", device(false, &["s1"]))?;
+        writer.register_timeseries("d4", device(true, &["s1"]))?;
+        writer.register_timeseries("d4", device(true, &["s2"]))?;
+        writer.write("d3", "s1", 3, IoTDBValue::LONG(3))?;
+        writer.write_many(
+            "d4",
+            4,
+            vec![
+                DataPoint::new("s1", IoTDBValue::LONG(4)),
+                DataPoint::new("s2", IoTDBValue::LONG(5)),
+            ],
+        )?;
+
+        let error = |result: Result<(), TsFileError>| match result {
+            Err(TsFileError::SchemaError {
+                source: Some(source),
+            })
+            | Err(TsFileError::UnsupportedFeature {
+                source: Some(source),
+            }) => source,
+            _ => panic!("No error"),
+        };
+        assert_eq!(
+            error(writer.register_timeseries("d1", device(false, &["s2"]))),
+            "Measurement 's2' of device 'd1' is already registered"
+        );
+        assert_eq!(
+            error(writer.register_timeseries("d1", device(true, &["s3"]))),
+            "Device 'd1' is registered as non aligned device"
+        );
+        assert_eq!(
+            error(writer.register_timeseries("d4", device(true, &["s3"]))),
+            "Measurements cannot be added to the aligned device 'd4' after its first row"
+        );
+        assert_eq!(
+            error(writer.register_timeseries("d 5", device(false, &["s1"]))),
+            "Device path 'd 5' contains the illegal character ' '"
+        );
+        writer.flush()?;
+        assert_eq!(
+            error(writer.register_timeseries("d1", device(false, &["s3"]))),
+            "Device 'd1' was already flushed, its schema cannot change"
+        );
+        writer.close()?;
+
+        let mut reader = TsFileReader::new(Cursor::new(writer.into_inner()))?;
+        for (device, measurement, timestamp) in [("d1", "s2", 2), ("d3", "s1", 3), ("d4", "s2", 4)]
+        {
+            let points: Vec<i64> = reader
+                .query(device, measurement)?
+                .map(|point| point.unwrap().0)
+                .collect();
+            assert_eq!(points, [timestamp]);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() -> Result<(), TsFileError> {
+        use std::sync::{Arc, Mutex};
+        use tracing_crate::span::{Attributes, Id, Record};
+        use tracing_crate::{Event, Metadata, Subscriber};
+
+        /// Records the names of all spans
+        struct Spans(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Subscriber for Spans {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut spans = self.0.lock().unwrap();
+                spans.push(span.metadata().name());
+                Id::from_u64(spans.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let spans = Arc::new(Mutex::new(vec![]));
+        tracing_crate::subscriber::with_default(Spans(spans.clone()), || {
+            let mut writer = reorder_writer(TsFileConfig::default());
+            writer.write("d1", "s1", 1, IoTDBValue::LONG(1))?;
+            writer.write_many("d2", 1, vec![DataPoint::new("s1", IoTDBValue::LONG(1))])?;
+            writer.close()
+        })?;
+        let spans = spans.lock().unwrap();
+        for name in [
+            "flush",
+            "flush_chunk_group",
+            "encode_page",
+            "encode_aligned_page",
+            "end_file",
+            "metadata_index",
+            "bloom_filter",
+        ] {
+            assert!(spans.contains(&name), "Missing span {}", name);
+        }
+        Ok(())
+    }
+}
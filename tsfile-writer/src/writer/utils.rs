@@ -1,6 +1,9 @@
 use crate::writer::{PositionedWrite, TsFileError};
 use std::io::Read;
 
+/// Initial capacity of `read_bytes`, larger buffers grow while reading
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
 pub fn write_var_u32(num: u32, buffer: &mut dyn PositionedWrite) -> Result<u8, TsFileError> {
     let mut number = num;
 
@@ -44,22 +47,78 @@ pub fn write_var_i32(num: i32, buffer: &mut dyn PositionedWrite) -> Result<u8, T
     write_var_u32(u_value as u32, buffer)
 }
 
-#[allow(dead_code)]
-fn read_byte(buffer: &mut dyn Read) -> Result<u8, TsFileError> {
+pub(crate) fn read_byte(buffer: &mut dyn Read) -> Result<u8, TsFileError> {
     let mut read_buffer: [u8; 1] = [0];
     buffer.read_exact(&mut read_buffer)?;
     Ok(read_buffer[0])
 }
 
-#[allow(dead_code)]
 pub fn read_var_u32(buffer: &mut dyn Read) -> Result<u32, TsFileError> {
     let mut value: u32 = 0;
     let mut i: u8 = 0;
     let mut b = read_byte(buffer)?;
-    while b != u8::MAX && (b & 0x80) != 0 {
+    while (b & 0x80) != 0 {
+        if i >= 28 {
+            return Err(TsFileError::Corrupt {
+                source: Some("Var int is too long".to_owned()),
+            });
+        }
         value |= ((b & 0x7F) as u32) << i;
         i += 7;
         b = read_byte(buffer)?;
     }
     Ok(value | ((b as u32) << i))
 }
+
+pub fn read_var_i32(buffer: &mut dyn Read) -> Result<i32, TsFileError> {
+    let u_value = read_var_u32(buffer)?;
+    let value = (u_value >> 1) as i32;
+    if u_value & 1 != 0 {
+        Ok(!value)
+    } else {
+        Ok(value)
+    }
+}
+
+pub(crate) fn read_i32(buffer: &mut dyn Read) -> Result<i32, TsFileError> {
+    let mut read_buffer = [0_u8; 4];
+    buffer.read_exact(&mut read_buffer)?;
+    Ok(i32::from_be_bytes(read_buffer))
+}
+
+pub(crate) fn read_i64(buffer: &mut dyn Read) -> Result<i64, TsFileError> {
+    let mut read_buffer = [0_u8; 8];
+    buffer.read_exact(&mut read_buffer)?;
+    Ok(i64::from_be_bytes(read_buffer))
+}
+
+/// Reads `len` bytes. The buffer only grows with the bytes actually read, so a corrupt length
+/// fails at the end of the input instead of allocating an absurd buffer upfront.
+pub(crate) fn read_bytes(buffer: &mut dyn Read, len: usize) -> Result<Vec<u8>, TsFileError> {
+    let mut bytes = Vec::with_capacity(len.min(READ_BUFFER_SIZE));
+    Read::take(&mut *buffer, len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(TsFileError::Corrupt {
+            source: Some(format!(
+                "Expected {} bytes but only {} are left",
+                len,
+                bytes.len()
+            )),
+        });
+    }
+    Ok(bytes)
+}
+
+/// Reads a string written by `write_str`, i.e. var int length followed by the bytes
+pub(crate) fn read_str(buffer: &mut dyn Read) -> Result<String, TsFileError> {
+    let len = read_var_i32(buffer)?;
+    if len < 0 {
+        return Err(TsFileError::Corrupt {
+            source: Some(format!("Invalid string length {}", len)),
+        });
+    }
+    let bytes = read_bytes(buffer, len as usize)?;
+    String::from_utf8(bytes).map_err(|_| TsFileError::Corrupt {
+        source: Some("String is not valid UTF-8".to_owned()),
+    })
+}
@@ -0,0 +1,472 @@
+//! Writer for aligned devices, i.e. a single time chunk that is shared by one value chunk
+//! per measurement.
+//!
+//! The layout follows the aligned format of the Java implementation:
+//! * The time chunk has an empty measurement id, data type `VECTOR` and TS2DIFF (or PLAIN, see
+//!   `TsFileConfig::time_encoding`) encoded pages.
+//! * Each value page starts with the number of rows, followed by a bitmap of the non null rows
+//!   and the encoded non null values.
+//! * Pages of the time and value chunks are always sealed together, so that they contain
+//!   the same rows.
+use crate::writer::chunk_writer::MINIMUM_RECORD_COUNT_FOR_CHECK;
+use crate::writer::encoding::Encoder;
+use crate::writer::metrics::WriterMetrics;
+use crate::writer::statistics::Statistics;
+use crate::writer::tablet::Tablet;
+use crate::writer::ts_file_config::TsFileConfig;
+use crate::writer::tsfile_io_writer::TsFileIoWriter;
+use crate::writer::tsfile_writer::DataPoint;
+use crate::writer::utils::{size_var_i32, size_var_u32};
+use crate::writer::{
+    utils, CompressionType, IoTDBValue, MeasurementSchema, PositionedWrite, Serializable,
+    TSDataType, TSEncoding, TsFileError, TIME_SERIES_MASK, VALUE_SERIES_MASK,
+};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// A sealed page, the data is already compressed
+struct Page {
+    uncompressed_size: u32,
+    data: Vec<u8>,
+    statistics: Statistics,
+}
+
+/// Sealed pages of a single chunk (time or value) which are serialized on flush
+struct ChunkPages {
+    measurement_id: String,
+    data_type: TSDataType,
+    compression: CompressionType,
+    encoding: TSEncoding,
+    mask: u8,
+    single_page_chunks: bool,
+    pages: Vec<Page>,
+    statistics: Statistics,
+    /// Sizes of all pages added since the creation, before and after compression
+    uncompressed_bytes: u64,
+    compressed_bytes: u64,
+}
+
+impl ChunkPages {
+    fn new(
+        measurement_id: &str,
+        data_type: TSDataType,
+        compression: CompressionType,
+        encoding: TSEncoding,
+        mask: u8,
+        config: &TsFileConfig,
+    ) -> ChunkPages {
+        ChunkPages {
+            measurement_id: measurement_id.to_owned(),
+            data_type,
+            compression,
+            encoding,
+            mask,
+            single_page_chunks: config.single_page_chunks,
+            pages: vec![],
+            statistics: Statistics::new(data_type),
+            uncompressed_bytes: 0,
+            compressed_bytes: 0,
+        }
+    }
+
+    fn add_page(&mut self, buffer: Vec<u8>, statistics: Statistics) -> Result<(), TsFileError> {
+        let data = match self.compression {
+            CompressionType::UNCOMPRESSED => buffer.clone(),
+            _ => self.compression.compress(&buffer)?,
+        };
+        self.statistics.merge(&statistics)?;
+        self.uncompressed_bytes += buffer.len() as u64;
+        self.compressed_bytes += data.len() as u64;
+        self.pages.push(Page {
+            uncompressed_size: buffer.len() as u32,
+            data,
+            statistics,
+        });
+        Ok(())
+    }
+
+    /// Page statistics are only written if the chunk has more than one page, see
+    /// `TsFileConfig::single_page_chunks`
+    fn has_page_statistics(&self) -> bool {
+        self.pages.len() > 1 || !self.single_page_chunks
+    }
+
+    fn page_size(&self, page: &Page) -> u32 {
+        let statistics_size = if self.has_page_statistics() {
+            page.statistics.get_serialized_size()
+        } else {
+            0
+        };
+        size_var_u32(page.uncompressed_size) as u32
+            + size_var_u32(page.data.len() as u32) as u32
+            + statistics_size
+            + page.data.len() as u32
+    }
+
+    fn data_size(&self) -> u32 {
+        self.pages.iter().map(|page| self.page_size(page)).sum()
+    }
+
+    /// Chunks without a single (non null) value are not written at all
+    fn is_empty(&self) -> bool {
+        self.statistics.count() == 0
+    }
+
+    fn get_serialized_chunk_size(&self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        let measurement_length = self.measurement_id.len() as i32;
+        let data_size = self.data_size();
+        1_u64 // chunkType
+            + size_var_i32(measurement_length) as u64 // measurementID length
+            + measurement_length as u64 // measurementID
+            + size_var_u32(data_size) as u64 // dataSize
+            + 1_u64 // dataType
+            + 1_u64 // compressionType
+            + 1_u64 // encodingType
+            + data_size as u64
+    }
+
+    fn estimate_mem_size(&self) -> u32 {
+        self.data_size()
+    }
+
+    fn write_to_file_writer<T: PositionedWrite>(
+        &mut self,
+        file_writer: &mut TsFileIoWriter<T>,
+    ) -> Result<(), TsFileError> {
+        if !self.is_empty() {
+            file_writer.start_flush_chunk(
+                self.measurement_id.clone(),
+                self.compression,
+                self.data_type,
+                self.encoding,
+                self.statistics.clone(),
+                self.data_size(),
+                self.pages.len() as u32,
+                self.mask,
+            )?;
+            let page_statistics = self.has_page_statistics();
+            for page in &self.pages {
+                utils::write_var_u32(page.uncompressed_size, &mut file_writer.out)?;
+                utils::write_var_u32(page.data.len() as u32, &mut file_writer.out)?;
+                if page_statistics {
+                    page.statistics.serialize(&mut file_writer.out)?;
+                }
+                file_writer.out.write_all(&page.data)?;
+            }
+            file_writer.end_current_chunk()?;
+        }
+
+        // re-init the chunk
+        self.pages.clear();
+        self.statistics = Statistics::new(self.data_type);
+        Ok(())
+    }
+}
+
+struct TimeChunkWriter {
+    encoder: Box<dyn Encoder>,
+    statistics: Statistics,
+    chunk: ChunkPages,
+}
+
+impl TimeChunkWriter {
+    fn new(config: &TsFileConfig) -> Result<TimeChunkWriter, TsFileError> {
+        let encoding = config.time_encoding;
+        Ok(TimeChunkWriter {
+            encoder: <dyn Encoder>::new(TSDataType::INT64, encoding)?,
+            statistics: Statistics::new(TSDataType::VECTOR),
+            // The time column is never compressed
+            chunk: ChunkPages::new(
+                "",
+                TSDataType::VECTOR,
+                CompressionType::UNCOMPRESSED,
+                encoding,
+                TIME_SERIES_MASK,
+                config,
+            ),
+        })
+    }
+
+    fn write(&mut self, timestamp: i64) -> Result<(), TsFileError> {
+        self.encoder.write(&timestamp.into())?;
+        self.statistics.update_time(timestamp)?;
+        Ok(())
+    }
+
+    fn estimate_page_size(&mut self) -> u32 {
+        self.encoder.size() + self.encoder.get_max_byte_size()
+    }
+
+    fn seal_page(&mut self) -> Result<(), TsFileError> {
+        if self.statistics.count() == 0 {
+            return Ok(());
+        }
+        let mut buffer = vec![];
+        self.encoder.serialize(&mut buffer);
+        self.encoder.reset();
+        let statistics =
+            std::mem::replace(&mut self.statistics, Statistics::new(TSDataType::VECTOR));
+        self.chunk.add_page(buffer, statistics)
+    }
+}
+
+struct ValueChunkWriter {
+    data_type: TSDataType,
+    encoder: Box<dyn Encoder>,
+    statistics: Statistics,
+    bitmap: Vec<u8>,
+    row_count: u32,
+    chunk: ChunkPages,
+}
+
+impl ValueChunkWriter {
+    fn new(
+        measurement_id: &str,
+        schema: &MeasurementSchema,
+        config: &TsFileConfig,
+    ) -> Result<ValueChunkWriter, TsFileError> {
+        Ok(ValueChunkWriter {
+            data_type: schema.data_type,
+            encoder: <dyn Encoder>::for_schema(schema)?,
+            statistics: Statistics::new(schema.data_type),
+            bitmap: vec![],
+            row_count: 0,
+            chunk: ChunkPages::new(
+                measurement_id,
+                schema.data_type,
+                schema.compression,
+                schema.encoding,
+                VALUE_SERIES_MASK,
+                config,
+            ),
+        })
+    }
+
+    /// Writes a row, `None` marks the row as null in the bitmap
+    fn write(&mut self, timestamp: i64, value: Option<&IoTDBValue>) -> Result<(), TsFileError> {
+        if self.bitmap.len() * 8 == self.row_count as usize {
+            self.bitmap.push(0);
+        }
+        if let Some(value) = value {
+            self.encoder.write(value)?;
+            self.statistics.update(timestamp, value)?;
+            // Set bits mark non null rows, starting at the most significant bit
+            if let Some(byte) = self.bitmap.last_mut() {
+                *byte |= 0x80 >> (self.row_count % 8);
+            }
+        }
+        self.row_count += 1;
+        Ok(())
+    }
+
+    fn estimate_page_size(&mut self) -> u32 {
+        4 + self.bitmap.len() as u32 + self.encoder.size() + self.encoder.get_max_byte_size()
+    }
+
+    fn seal_page(&mut self) -> Result<(), TsFileError> {
+        if self.row_count == 0 {
+            return Ok(());
+        }
+        let mut buffer = vec![];
+        buffer.write_all(&self.row_count.to_be_bytes())?;
+        buffer.write_all(&self.bitmap)?;
+        self.encoder.serialize(&mut buffer);
+        self.encoder.reset();
+        self.bitmap.clear();
+        self.row_count = 0;
+        let statistics = std::mem::replace(&mut self.statistics, Statistics::new(self.data_type));
+        self.chunk.add_page(buffer, statistics)
+    }
+}
+
+/// Chunk writer for all measurements of an aligned device
+pub(crate) struct VectorChunkWriter {
+    time_writer: TimeChunkWriter,
+    value_writers: BTreeMap<String, ValueChunkWriter>,
+    rows_in_page: u32,
+    rows_for_next_check: u32,
+    page_size_threshold: u32,
+    max_number_of_points_in_page: u32,
+}
+
+impl VectorChunkWriter {
+    pub(crate) fn new<'a>(
+        schemas: impl IntoIterator<Item = (&'a str, &'a MeasurementSchema)>,
+        config: &TsFileConfig,
+    ) -> Result<VectorChunkWriter, TsFileError> {
+        let mut value_writers = BTreeMap::new();
+        for (measurement_id, schema) in schemas {
+            value_writers.insert(
+                measurement_id.to_owned(),
+                ValueChunkWriter::new(measurement_id, schema, config)?,
+            );
+        }
+        Ok(VectorChunkWriter {
+            time_writer: TimeChunkWriter::new(config)?,
+            value_writers,
+            rows_in_page: 0,
+            rows_for_next_check: MINIMUM_RECORD_COUNT_FOR_CHECK,
+            page_size_threshold: config.page_size_threshold,
+            max_number_of_points_in_page: config.max_number_of_points_in_page,
+        })
+    }
+
+    /// Writes a single row, measurements that are not contained in the row are null
+    pub(crate) fn write<'b>(
+        &mut self,
+        timestamp: i64,
+        values: impl IntoIterator<Item = DataPoint<'b>>,
+    ) -> Result<u32, TsFileError> {
+        // Validate the whole row first, so that no partial row is written
+        let mut row = BTreeMap::new();
+        for data_point in values {
+            match self.value_writers.get(data_point.measurement_id) {
+                Some(writer) if data_point.value.data_type() == writer.data_type => {
+                    row.insert(data_point.measurement_id, data_point.value);
+                }
+                Some(_) => return Err(TsFileError::WrongTypeForSeries),
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some("Unknown measurement id".to_owned()),
+                    });
+                }
+            }
+        }
+
+        self.time_writer.write(timestamp)?;
+        for (measurement_id, writer) in self.value_writers.iter_mut() {
+            writer.write(timestamp, row.get(measurement_id.as_str()))?;
+        }
+        self.rows_in_page += 1;
+        self.check_page_size_and_may_open_new_page()?;
+        Ok(row.len() as u32)
+    }
+
+    /// Writes all rows of the tablet, measurements without a column are null
+    pub(crate) fn write_tablet(&mut self, tablet: &Tablet) -> Result<u32, TsFileError> {
+        // Column of the tablet for each value writer
+        let mut columns = vec![None; self.value_writers.len()];
+        for column in &tablet.columns {
+            match self
+                .value_writers
+                .iter()
+                .position(|(measurement_id, _)| measurement_id == column.measurement_id)
+            {
+                Some(index) => {
+                    let (_, writer) = self.value_writers.iter().nth(index).unwrap();
+                    column.check_type(writer.data_type)?;
+                    columns[index] = Some(column);
+                }
+                None => {
+                    return Err(TsFileError::SchemaError {
+                        source: Some("Unknown measurement id".to_owned()),
+                    });
+                }
+            }
+        }
+
+        let mut record_count = 0;
+        for (row, &timestamp) in tablet.timestamps.iter().enumerate() {
+            self.time_writer.write(timestamp)?;
+            for (writer, column) in self.value_writers.values_mut().zip(&columns) {
+                let value = column.and_then(|column| column.get(row));
+                if value.is_some() {
+                    record_count += 1;
+                }
+                writer.write(timestamp, value)?;
+            }
+            self.rows_in_page += 1;
+            self.check_page_size_and_may_open_new_page()?;
+        }
+        Ok(record_count)
+    }
+
+    fn check_page_size_and_may_open_new_page(&mut self) -> Result<(), TsFileError> {
+        if self.rows_in_page >= self.max_number_of_points_in_page {
+            self.seal_current_page()?;
+        } else if self.rows_in_page >= self.rows_for_next_check {
+            let mut current_page_size = self.time_writer.estimate_page_size();
+            for writer in self.value_writers.values_mut() {
+                current_page_size += writer.estimate_page_size();
+            }
+            if current_page_size > self.page_size_threshold {
+                log::trace!(
+                    "enough size, write aligned page, pageSizeThreshold:{}, currentPageSize:{}, rows:{}",
+                    self.page_size_threshold,
+                    current_page_size,
+                    self.rows_in_page
+                );
+                self.seal_current_page()?;
+            } else {
+                self.rows_for_next_check = self.rows_in_page + MINIMUM_RECORD_COUNT_FOR_CHECK;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn seal_current_page(&mut self) -> Result<(), TsFileError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing_crate::debug_span!("encode_aligned_page", rows = self.rows_in_page).entered();
+        self.time_writer.seal_page()?;
+        for writer in self.value_writers.values_mut() {
+            writer.seal_page()?;
+        }
+        self.rows_in_page = 0;
+        self.rows_for_next_check = MINIMUM_RECORD_COUNT_FOR_CHECK;
+        Ok(())
+    }
+
+    /// Whether any row was written since the last flush
+    pub(crate) fn has_data(&self) -> bool {
+        self.rows_in_page > 0 || !self.time_writer.chunk.is_empty()
+    }
+
+    /// Adds the rows that are not flushed yet and the sizes of the sealed pages
+    pub(crate) fn add_metrics(&self, device: &str, metrics: &mut WriterMetrics) {
+        let chunks = std::iter::once(&self.time_writer.chunk)
+            .chain(self.value_writers.values().map(|writer| &writer.chunk));
+        for chunk in chunks {
+            metrics.uncompressed_page_bytes += chunk.uncompressed_bytes;
+            metrics.compressed_page_bytes += chunk.compressed_bytes;
+        }
+        for (measurement, writer) in &self.value_writers {
+            let points = writer.chunk.statistics.count() + writer.statistics.count();
+            metrics.add_points(device, measurement, points as u64);
+        }
+    }
+
+    pub(crate) fn get_serialized_chunk_size(&self) -> u64 {
+        self.time_writer.chunk.get_serialized_chunk_size()
+            + self
+                .value_writers
+                .values()
+                .map(|writer| writer.chunk.get_serialized_chunk_size())
+                .sum::<u64>()
+    }
+
+    pub(crate) fn estimate_max_series_mem_size(&mut self) -> u32 {
+        let mut size =
+            self.time_writer.chunk.estimate_mem_size() + self.time_writer.estimate_page_size();
+        for writer in self.value_writers.values_mut() {
+            size += writer.chunk.estimate_mem_size() + writer.estimate_page_size();
+        }
+        size
+    }
+
+    /// Writes the time chunk followed by all value chunks
+    pub(crate) fn write_to_file_writer<T: PositionedWrite>(
+        &mut self,
+        file_writer: &mut TsFileIoWriter<T>,
+    ) -> Result<(), TsFileError> {
+        self.seal_current_page()?;
+        self.time_writer.chunk.write_to_file_writer(file_writer)?;
+        for writer in self.value_writers.values_mut() {
+            writer.chunk.write_to_file_writer(file_writer)?;
+        }
+        Ok(())
+    }
+}
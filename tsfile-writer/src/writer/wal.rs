@@ -0,0 +1,223 @@
+//! Write ahead log of the points that a `TsFileWriter` buffers in memory, see
+//! `TsFileConfig::write_ahead_log`.
+//!
+//! The log is a sidecar file `{file}.wal` next to the TsFile. Every write appends its points
+//! before they are buffered, after each flush the log is truncated as all points are in the
+//! TsFile then. A record consists of its length (4 bytes), device, measurement, timestamp and
+//! the value with its data type. A record at the end of the log that is incomplete, e.g.
+//! because the process died while appending it, is ignored.
+use crate::writer::errors::TsFileError;
+use crate::writer::utils::{read_byte, read_i32, read_i64, read_str};
+use crate::writer::{write_str, IoTDBValue, PositionedWrite, TSDataType};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// A point of the log
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalRecord {
+    pub device: String,
+    pub measurement_id: String,
+    pub timestamp: i64,
+    pub value: IoTDBValue,
+}
+
+/// Path of the log of the given TsFile
+pub fn wal_path(filename: &str) -> PathBuf {
+    PathBuf::from(format!("{}.wal", filename))
+}
+
+/// Reads all complete records of a log, an empty list if it does not exist
+pub fn read_wal(path: impl AsRef<Path>) -> Result<Vec<WalRecord>, TsFileError> {
+    match File::open(path) {
+        Ok(file) => Ok(read_records(&mut BufReader::new(file))?.0),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The complete records and the number of bytes they take
+fn read_records(reader: &mut dyn Read) -> Result<(Vec<WalRecord>, u64), TsFileError> {
+    let mut records = vec![];
+    let mut end = 0;
+    loop {
+        let mut length = [0_u8; 4];
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let length = u32::from_be_bytes(length) as u64;
+        let mut body = vec![];
+        reader.take(length).read_to_end(&mut body)?;
+        if body.len() as u64 != length {
+            break;
+        }
+        match read_record(&mut body.as_slice()) {
+            Ok(record) => records.push(record),
+            Err(_) => break,
+        }
+        end += 4 + length;
+    }
+    Ok((records, end))
+}
+
+fn read_record(body: &mut dyn Read) -> Result<WalRecord, TsFileError> {
+    let device = read_str(body)?;
+    let measurement_id = read_str(body)?;
+    let timestamp = read_i64(body)?;
+    let value = match TSDataType::try_from(read_byte(body)?) {
+        Ok(TSDataType::INT32) => IoTDBValue::INT(read_i32(body)?),
+        Ok(TSDataType::INT64) => IoTDBValue::LONG(read_i64(body)?),
+        Ok(TSDataType::FLOAT) => IoTDBValue::FLOAT(f32::from_bits(read_i32(body)? as u32)),
+        Ok(TSDataType::DOUBLE) => IoTDBValue::DOUBLE(f64::from_bits(read_i64(body)? as u64)),
+        Ok(TSDataType::TEXT) => IoTDBValue::TEXT(read_str(body)?),
+        _ => {
+            return Err(TsFileError::Error {
+                source: Some("Unknown data type in write ahead log".to_owned()),
+            })
+        }
+    };
+    Ok(WalRecord {
+        device,
+        measurement_id,
+        timestamp,
+        value,
+    })
+}
+
+pub(crate) struct WriteAheadLog {
+    file: File,
+    /// Records of the current write, they are appended to the file at once
+    buffer: Vec<u8>,
+    body: Vec<u8>,
+}
+
+impl WriteAheadLog {
+    /// Starts an empty log, an existing log is truncated
+    pub(crate) fn create(path: &Path) -> Result<WriteAheadLog, TsFileError> {
+        Ok(WriteAheadLog::new(File::create(path)?))
+    }
+
+    /// Opens an existing log (or starts an empty one) and returns its complete records. An
+    /// incomplete record at the end is truncated, new records are appended after the others.
+    pub(crate) fn open(path: &Path) -> Result<(WriteAheadLog, Vec<WalRecord>), TsFileError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let (records, end) = read_records(&mut BufReader::new(&file))?;
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+        Ok((WriteAheadLog::new(file), records))
+    }
+
+    fn new(file: File) -> WriteAheadLog {
+        WriteAheadLog {
+            file,
+            buffer: vec![],
+            body: vec![],
+        }
+    }
+
+    /// Adds a point to the current write
+    pub(crate) fn add(
+        &mut self,
+        device: &str,
+        measurement_id: &str,
+        timestamp: i64,
+        value: &IoTDBValue,
+    ) -> Result<(), TsFileError> {
+        self.body.clear();
+        let body: &mut dyn PositionedWrite = &mut self.body;
+        write_str(body, device)?;
+        write_str(body, measurement_id)?;
+        body.write_all(&timestamp.to_be_bytes())?;
+        body.write_all(&[value.data_type().serialize()])?;
+        match value {
+            IoTDBValue::INT(v) => body.write_all(&v.to_be_bytes())?,
+            IoTDBValue::LONG(v) => body.write_all(&v.to_be_bytes())?,
+            IoTDBValue::FLOAT(v) => body.write_all(&v.to_bits().to_be_bytes())?,
+            IoTDBValue::DOUBLE(v) => body.write_all(&v.to_bits().to_be_bytes())?,
+            IoTDBValue::TEXT(v) => write_str(body, v)?,
+        }
+        self.buffer
+            .extend_from_slice(&(self.body.len() as u32).to_be_bytes());
+        self.buffer.extend_from_slice(&self.body);
+        Ok(())
+    }
+
+    /// Appends the points of the current write to the log. This hands them to the operating
+    /// system, so they survive the process but not necessarily a power loss.
+    pub(crate) fn commit(&mut self) -> Result<(), TsFileError> {
+        let result = self.file.write_all(&self.buffer);
+        self.buffer.clear();
+        Ok(result?)
+    }
+
+    /// Removes all records, e.g. after they were flushed to the TsFile
+    pub(crate) fn truncate(&mut self) -> Result<(), TsFileError> {
+        self.buffer.clear();
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::writer::errors::TsFileError;
+    use crate::writer::wal::{read_wal, WalRecord, WriteAheadLog};
+    use crate::writer::IoTDBValue;
+    use std::fs::OpenOptions;
+    use std::path::Path;
+
+    #[test]
+    fn incomplete_record() -> Result<(), TsFileError> {
+        std::fs::create_dir_all("target")?;
+        let path = Path::new("target/incomplete_record.wal");
+        let mut wal = WriteAheadLog::create(path)?;
+        let values = [
+            IoTDBValue::INT(-1),
+            IoTDBValue::LONG(2),
+            IoTDBValue::FLOAT(3.5),
+            IoTDBValue::DOUBLE(-4.25),
+            IoTDBValue::TEXT("five".to_owned()),
+        ];
+        for (i, value) in values.iter().enumerate() {
+            wal.add("d1", "s1", i as i64, value)?;
+        }
+        wal.commit()?;
+        drop(wal);
+        let expected: Vec<_> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| WalRecord {
+                device: "d1".to_owned(),
+                measurement_id: "s1".to_owned(),
+                timestamp: i as i64,
+                value: value.clone(),
+            })
+            .collect();
+        assert_eq!(read_wal(path)?, expected);
+
+        // Cut off the last byte, the last record is dropped and truncated on open
+        let file = OpenOptions::new().write(true).open(path)?;
+        let length = file.metadata()?.len();
+        file.set_len(length - 1)?;
+        let (mut wal, records) = WriteAheadLog::open(path)?;
+        assert_eq!(records, expected[..4]);
+        wal.add("d2", "s2", 7, &IoTDBValue::LONG(7))?;
+        wal.commit()?;
+        let records = read_wal(path)?;
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[4].device, "d2");
+
+        wal.truncate()?;
+        assert!(read_wal(path)?.is_empty());
+        assert!(read_wal("target/missing.wal")?.is_empty());
+        Ok(())
+    }
+}